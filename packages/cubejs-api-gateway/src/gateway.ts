@@ -366,7 +366,8 @@ class ApiGateway {
     }
 
     try {
-      const metaConfig = await this.getCompilerApi(context).metaConfig({
+      const compilerApi = this.getCompilerApi(context);
+      const metaConfig = await compilerApi.metaConfig({
         requestId: context.requestId,
       });
       const cubes = metaConfig
@@ -376,7 +377,11 @@ class ApiGateway {
           measures: cube.measures.filter(visibilityFilter),
           dimensions: cube.dimensions.filter(visibilityFilter),
         }));
-      res({ cubes });
+      res({
+        cubes,
+        // Used by SQL API to check if cached meta is still actual
+        compilerId: compilerApi.compilerVersion,
+      });
     } catch (e) {
       this.handleError({
         e,
@@ -844,7 +849,9 @@ class ApiGateway {
           queryType,
           results,
           pivotQuery: getPivotQuery(queryType, normalizedQueries),
-          slowQuery
+          slowQuery,
+          // Used by SQL API to drop cached meta when the schema is recompiled
+          compilerId: this.getCompilerApi(context).compilerVersion,
         });
       } else {
         res(results[0]);
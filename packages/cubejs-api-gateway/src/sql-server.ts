@@ -35,17 +35,17 @@ export class SQLServer {
     this.sqlInterfaceInstance = await registerInterface({
       port: options.sqlPort,
       nonce: options.sqlNonce,
-      checkAuth: async ({ request, user }) => {
-        const { password } = await checkSqlAuth(request, user);
+      checkAuth: async ({ request, user, options: connectionOptions }) => {
+        const { password, securityContext, canSwitchUser } = await checkSqlAuth(request, user, connectionOptions);
 
-        // Strip securityContext to improve speed deserialization
+        // securityContext is stored in the native session and passed back with every meta/load call
         return {
-          password
+          password,
+          securityContext,
+          canSwitchUser: canSwitchUser || false,
         };
       },
-      meta: async ({ request, user }) => {
-        // @todo Store security context in native
-        const { securityContext } = await checkSqlAuth(request, user);
+      meta: async ({ request, securityContext }) => {
         const context = await this.apiGateway.contextByReq(<any> request, securityContext, request.id);
 
         // eslint-disable-next-line no-async-promise-executor
@@ -62,9 +62,7 @@ export class SQLServer {
           }
         });
       },
-      load: async ({ request, user, query }) => {
-        // @todo Store security context in native
-        const { securityContext } = await checkSqlAuth(request, user);
+      load: async ({ request, securityContext, query }) => {
         const context = await this.apiGateway.contextByReq(<any> request, securityContext, request.id);
 
         // eslint-disable-next-line no-async-promise-executor
@@ -88,8 +86,8 @@ export class SQLServer {
   }
 
   protected wrapCheckSqlAuthFn(checkSqlAuth: CheckSQLAuthFn): CheckSQLAuthFn {
-    return async (req, user) => {
-      const response = await checkSqlAuth(req, user);
+    return async (req, user, connectionOptions) => {
+      const response = await checkSqlAuth(req, user, connectionOptions);
       if (typeof response !== 'object' || response.password === null) {
         throw new Error('checkSqlAuth must return an object');
       }
@@ -131,7 +129,8 @@ export class SQLServer {
 
       return {
         password: allowedPassword,
-        securityContext: {}
+        securityContext: {},
+        canSwitchUser: false,
       };
     };
   }
@@ -47,7 +47,11 @@ type CheckAuthFn =
  */
 type CheckSQLAuthSuccessResponse = {
   password: string | null,
-  securityContext?: any
+  securityContext?: any,
+  /**
+   * Allows the session to switch to another user with SET user = '...'.
+   */
+  canSwitchUser?: boolean,
 };
 
 /**
@@ -56,7 +60,7 @@ type CheckSQLAuthSuccessResponse = {
  * auth logic.
  */
 type CheckSQLAuthFn =
-  (ctx: any, user: string | null) =>
+  (ctx: any, user: string | null, options?: Record<string, string>) =>
     Promise<CheckSQLAuthSuccessResponse> |
     CheckSQLAuthSuccessResponse;
 
@@ -7,18 +7,21 @@ export interface Request {
 
 export interface CheckAuthPayload {
     request: Request,
-    user: string|null
+    user: string|null,
+    options: Record<string, string>,
 }
 
 export interface LoadPayload {
     request: Request,
     user: string,
+    securityContext: any,
     query: any
 }
 
 export interface MetaPayload {
     request: Request,
-    user: string|null
+    user: string|null,
+    securityContext: any,
 }
 
 export type SQLInterfaceOptions = {
@@ -8,7 +8,7 @@ use log::trace;
 use neon::prelude::*;
 use serde::Deserialize;
 use serde_derive::Serialize;
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use uuid::Uuid;
 
 use crate::channel::call_js_with_channel_as_callback;
@@ -37,16 +37,25 @@ pub struct TransportRequest {
 struct CheckAuthRequest {
     request: TransportRequest,
     user: Option<String>,
+    options: HashMap<String, String>,
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct CheckAuthResponse {
     password: Option<String>,
+    security_context: Option<serde_json::Value>,
+    #[serde(default)]
+    can_switch_user: bool,
 }
 
 #[async_trait]
 impl SqlAuthService for NodeBridgeAuthService {
-    async fn authenticate(&self, user: Option<String>) -> Result<AuthenticateResponse, CubeError> {
+    async fn authenticate(
+        &self,
+        user: Option<String>,
+        options: HashMap<String, String>,
+    ) -> Result<AuthenticateResponse, CubeError> {
         trace!("[auth] Request ->");
 
         let request_id = Uuid::new_v4().to_string();
@@ -56,6 +65,7 @@ impl SqlAuthService for NodeBridgeAuthService {
                 id: format!("{}-span-1", request_id),
             },
             user: user.clone(),
+            options,
         })?;
         let response: CheckAuthResponse = call_js_with_channel_as_callback(
             self.channel.clone(),
@@ -69,6 +79,8 @@ impl SqlAuthService for NodeBridgeAuthService {
             AuthContext {
                 access_token: user.unwrap_or_else(|| "fake".to_string()),
                 base_path: "fake".to_string(),
+                security_context: response.security_context,
+                can_switch_user: response.can_switch_user,
             },
             response.password,
         ))
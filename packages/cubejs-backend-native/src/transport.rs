@@ -6,7 +6,7 @@ use cubeclient::models::{V1Error, V1LoadRequestQuery, V1LoadResponse, V1MetaResp
 use cubesql::{
     di_service,
     sql::AuthContext,
-    transport::{MetaContext, TransportService},
+    transport::{MetaCache, MetaContext, TransportService},
     CubeError,
 };
 use serde_derive::Serialize;
@@ -20,6 +20,7 @@ pub struct NodeBridgeTransport {
     channel: Arc<Channel>,
     on_load: Arc<Root<JsFunction>>,
     on_meta: Arc<Root<JsFunction>>,
+    meta_cache: MetaCache,
 }
 
 impl NodeBridgeTransport {
@@ -28,26 +29,35 @@ impl NodeBridgeTransport {
             channel: Arc::new(channel),
             on_load: Arc::new(on_load),
             on_meta: Arc::new(on_meta),
+            meta_cache: MetaCache::new(),
         }
     }
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct LoadRequest {
     request: TransportRequest,
     user: Option<String>,
+    security_context: Option<serde_json::Value>,
     query: V1LoadRequestQuery,
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct MetaRequest {
     request: TransportRequest,
     user: Option<String>,
+    security_context: Option<serde_json::Value>,
 }
 
 #[async_trait]
 impl TransportService for NodeBridgeTransport {
     async fn meta(&self, ctx: Arc<AuthContext>) -> Result<Arc<MetaContext>, CubeError> {
+        if let Some(value) = self.meta_cache.get(&ctx).await {
+            return Ok(value);
+        }
+
         trace!("[transport] Meta ->");
 
         let request_id = Uuid::new_v4().to_string();
@@ -56,6 +66,7 @@ impl TransportService for NodeBridgeTransport {
                 id: format!("{}-span-1", request_id),
             },
             user: Some(ctx.access_token.clone()),
+            security_context: ctx.security_context.clone(),
         })?;
         let response = call_js_with_channel_as_callback::<V1MetaResponse>(
             self.channel.clone(),
@@ -65,9 +76,7 @@ impl TransportService for NodeBridgeTransport {
         .await?;
         trace!("[transport] Meta <- {:?}", response);
 
-        Ok(Arc::new(MetaContext::new(
-            response.cubes.unwrap_or_default(),
-        )))
+        Ok(self.meta_cache.insert(&ctx, response).await)
     }
 
     async fn load(
@@ -86,6 +95,7 @@ impl TransportService for NodeBridgeTransport {
                     id: format!("{}-span-{}", request_id, span_counter),
                 },
                 user: Some(ctx.access_token.clone()),
+                security_context: ctx.security_context.clone(),
                 query: query.clone(),
             })?;
 
@@ -99,6 +109,10 @@ impl TransportService for NodeBridgeTransport {
 
             let load_err = match serde_json::from_value::<V1LoadResponse>(response.clone()) {
                 Ok(r) => {
+                    self.meta_cache
+                        .check_compiler_id(&ctx, r.compiler_id.as_deref())
+                        .await;
+
                     return Ok(r);
                 }
                 Err(err) => err,
@@ -127,6 +141,12 @@ impl TransportService for NodeBridgeTransport {
             return Err(CubeError::user(load_err.to_string()));
         }
     }
+
+    async fn invalidate_meta(&self, ctx: Arc<AuthContext>) -> Result<(), CubeError> {
+        self.meta_cache.invalidate(&ctx).await;
+
+        Ok(())
+    }
 }
 
 di_service!(NodeBridgeTransport, [TransportService]);
@@ -39,7 +39,10 @@ describe('SQLInteface', () => {
 
       if (user === 'allowed_user') {
         return {
-          password: 'password_for_allowed_user'
+          password: 'password_for_allowed_user',
+          securityContext: {
+            user
+          },
         }
       }
 
@@ -77,6 +80,7 @@ describe('SQLInteface', () => {
             id: expect.any(String)
           },
           user: user || null,
+          options: {},
         });
       };
 
@@ -127,6 +131,7 @@ describe('SQLInteface', () => {
           id: expect.any(String)
         },
         user: 'allowed_user',
+        options: {},
       });
 
       expect(meta.mock.calls.length).toEqual(1);
@@ -135,6 +140,9 @@ describe('SQLInteface', () => {
           id: expect.any(String)
         },
         user: 'allowed_user',
+        securityContext: {
+          user: 'allowed_user'
+        },
       });
 
       {
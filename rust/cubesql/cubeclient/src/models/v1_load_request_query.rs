@@ -16,6 +16,8 @@ pub struct V1LoadRequestQuery {
     pub offset: Option<i32>,
     #[serde(rename = "filters", skip_serializing_if = "Option::is_none")]
     pub filters: Option<Vec<crate::models::V1LoadRequestQueryFilterItem>>,
+    #[serde(rename = "timezone", skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
 }
 
 impl V1LoadRequestQuery {
@@ -29,6 +31,7 @@ impl V1LoadRequestQuery {
             limit: None,
             offset: None,
             filters: None,
+            timezone: None,
         }
     }
 }
@@ -8,6 +8,8 @@ pub struct V1LoadResponse {
     pub query_type: Option<String>,
     #[serde(rename = "results")]
     pub results: Vec<crate::models::V1LoadResult>,
+    #[serde(rename = "compilerId", skip_serializing_if = "Option::is_none")]
+    pub compiler_id: Option<String>,
 }
 
 impl V1LoadResponse {
@@ -17,6 +19,7 @@ impl V1LoadResponse {
             slow_query: None,
             query_type: None,
             results,
+            compiler_id: None,
         }
     }
 }
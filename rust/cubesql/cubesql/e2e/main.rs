@@ -7,6 +7,7 @@ use tests::{
     basic::{AsyncTestConstructorResult, AsyncTestSuite},
     mysql::MySqlIntegrationTestSuite,
     postgres::PostgresIntegrationTestSuite,
+    standalone::StandaloneIntegrationTestSuite,
 };
 
 pub mod tests;
@@ -47,6 +48,7 @@ fn main() {
         let mut runner = TestsRunner::new();
         runner.register_suite(MySqlIntegrationTestSuite::before_all().await);
         runner.register_suite(PostgresIntegrationTestSuite::before_all().await);
+        runner.register_suite(StandaloneIntegrationTestSuite::before_all().await);
 
         for suites in runner.suites.iter_mut() {
             suites.run().await.unwrap();
@@ -1,3 +1,4 @@
 pub mod basic;
 pub mod mysql;
 pub mod postgres;
+pub mod standalone;
@@ -150,16 +150,34 @@ impl MySqlIntegrationTestSuite {
             conn.exec_iter(&statement, ("test",)).await.unwrap();
         }
 
-        // @todo Not working, because deserialization on the server?
-        // {
-        //     let statement = conn.prep("/** 2 */ SELECT ?".to_string()).await.unwrap();
-        //     conn.exec_iter(&statement, (true,)).await.unwrap();
-        // }
-
-        // {
-        //     let statement = conn.prep("/** 3 */ SELECT ?".to_string()).await.unwrap();
-        //     conn.exec_iter(&statement, (false,)).await.unwrap();
-        // }
+        {
+            let statement = conn.prep("/** 2 */ SELECT ?".to_string()).await.unwrap();
+            conn.exec_iter(&statement, (true,)).await.unwrap();
+        }
+
+        {
+            let statement = conn.prep("/** 3 */ SELECT ?".to_string()).await.unwrap();
+            conn.exec_iter(&statement, (false,)).await.unwrap();
+        }
+
+        {
+            let statement = conn
+                .prep("SELECT COUNT(*) FROM Orders WHERE createdAt > ?".to_string())
+                .await
+                .unwrap();
+            // Result columns are described on prepare and rows come in the binary protocol
+            assert_eq!(statement.num_params(), 1);
+            assert_eq!(statement.columns().len(), 1);
+
+            let count: Option<i64> = conn
+                .exec_first(
+                    &statement,
+                    (mysql_async::Value::Date(2020, 1, 1, 0, 0, 0, 0),),
+                )
+                .await
+                .unwrap();
+            assert!(count.is_some());
+        }
 
         Ok(())
     }
@@ -1,11 +1,12 @@
 use std::collections::HashMap;
 
+use chrono::FixedOffset;
 use cubeclient::models::{V1CubeMeta, V1CubeMetaDimension, V1CubeMetaMeasure, V1CubeMetaSegment};
 use regex::Regex;
 use sqlparser::ast;
 
 use crate::{
-    compile::CompilationError,
+    compile::{engine::udf::is_session_time_zone, CompilationError},
     transport::{V1CubeMetaDimensionExt, V1CubeMetaMeasureExt, V1CubeMetaSegmentExt},
 };
 
@@ -23,6 +24,9 @@ pub enum Selection {
 pub struct QueryContext {
     pub meta: V1CubeMeta,
     aliases: HashMap<String, Selection>,
+    // session time zone, date literals are local time in it
+    pub time_zone: Option<String>,
+    pub time_zone_offset: FixedOffset,
 }
 
 impl QueryContext {
@@ -30,9 +34,16 @@ impl QueryContext {
         QueryContext {
             meta: meta.clone(),
             aliases: HashMap::new(),
+            time_zone: None,
+            time_zone_offset: FixedOffset::east(0),
         }
     }
 
+    pub fn with_time_zone(&mut self, time_zone: Option<String>, offset: FixedOffset) {
+        self.time_zone = time_zone;
+        self.time_zone_offset = offset;
+    }
+
     pub fn find_selection_for_identifier(
         &self,
         identifier: &String,
@@ -149,6 +160,13 @@ impl QueryContext {
             ast::Expr::Identifier(i) => {
                 Ok(self.find_selection_for_identifier(&i.value.to_string(), false))
             }
+            // col::date, CAST(col AS DATE)
+            ast::Expr::Cast {
+                expr,
+                data_type: ast::DataType::Date,
+            } => Ok(Some(
+                self.find_time_dimension_selection(expr, "day", "cast")?,
+            )),
             _ => {
                 return Err(CompilationError::Unsupported(format!(
                     "Unable to find selection in selection: {:?}",
@@ -271,39 +289,100 @@ impl QueryContext {
         }
     }
 
+    /// Unwraps a column which is used as a timestamp: `col`, `cube.col`, `col::timestamp`
+    /// and `timezone('<zone>', col)` (`col AT TIME ZONE '<zone>'`) in the session time zone.
+    fn unpack_timestamp_column(&self, expr: &ast::Expr) -> CompilationResult<Option<String>> {
+        match expr {
+            ast::Expr::Identifier(i) => Ok(Some(i.value.to_string())),
+            ast::Expr::CompoundIdentifier(i) => {
+                // @todo We need a context with main table rel
+                if i.len() == 2 {
+                    Ok(Some(i[1].value.to_string()))
+                } else {
+                    Err(CompilationError::Unsupported(format!(
+                        "Unsupported compound identifier: {:?}",
+                        expr
+                    )))
+                }
+            }
+            ast::Expr::Nested(nested) => self.unpack_timestamp_column(nested),
+            ast::Expr::Cast {
+                expr,
+                data_type: ast::DataType::Timestamp,
+            } => self.unpack_timestamp_column(expr),
+            ast::Expr::Function(f) if f.name.to_string().eq_ignore_ascii_case("timezone") => {
+                match f.args.as_slice() {
+                    [ast::FunctionArg::Unnamed(ast::Expr::Value(
+                        ast::Value::SingleQuotedString(zone),
+                    )), ast::FunctionArg::Unnamed(expr)] => {
+                        if is_session_time_zone(zone, &self.time_zone) {
+                            self.unpack_timestamp_column(expr)
+                        } else {
+                            Err(CompilationError::Unsupported(format!(
+                                "Time zone '{}' differs from the session time zone '{}', please use SET TIME ZONE",
+                                zone,
+                                self.time_zone.as_deref().unwrap_or("UTC")
+                            )))
+                        }
+                    }
+                    _ => Ok(None),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn find_time_dimension_selection(
+        &self,
+        expr: &ast::Expr,
+        granularity: &str,
+        fn_name: &str,
+    ) -> CompilationResult<Selection> {
+        let possible_dimension_name = match self.unpack_timestamp_column(expr)? {
+            Some(name) => name,
+            None => {
+                return Err(CompilationError::User(format!(
+                    "Unsupported variation of arguments passed to {} function, correct {}(string, column)",
+                    fn_name, fn_name
+                )))
+            }
+        };
+
+        let granularity = granularity.to_lowercase();
+        match granularity.as_str() {
+            "second" | "minute" | "hour" | "day" | "week" | "month" | "quarter" | "year" => (),
+            _ => {
+                return Err(CompilationError::User(format!(
+                    "Unsupported granularity {:?}",
+                    granularity
+                )));
+            }
+        };
+
+        if let Some(r) = self.find_dimension_for_identifier(&possible_dimension_name) {
+            if r.is_time() {
+                Ok(Selection::TimeDimension(r, granularity))
+            } else {
+                Err(CompilationError::User(format!(
+                    "Unable to use non time dimension \"{}\" as a column in {}, please specify time dimension",
+                    possible_dimension_name, fn_name
+                )))
+            }
+        } else {
+            Err(CompilationError::User(format!(
+                "Unknown dimension '{}' passed as a column in {}",
+                possible_dimension_name, fn_name
+            )))
+        }
+    }
+
     pub fn find_selection_for_date_trunc_fn(
         &self,
         f: &ast::Function,
     ) -> CompilationResult<Selection> {
         match f.args.as_slice() {
-            [ast::FunctionArg::Unnamed(ast::Expr::Value(ast::Value::SingleQuotedString(granularity))), ast::FunctionArg::Unnamed(ast::Expr::Identifier(column))] => {
-                let possible_dimension_name = column.value.to_string();
-
-                match granularity.as_str() {
-                    "second" | "minute" | "hour" | "day" | "week" | "month" | "quarter" | "year" => (),
-                    _ => {
-                        return Err(CompilationError::User(format!(
-                            "Unsupported granularity {:?}",
-                            granularity
-                        )));
-                    }
-                };
-
-                if let Some(r) = self.find_dimension_for_identifier(&possible_dimension_name) {
-                    if r.is_time() {
-                        Ok(Selection::TimeDimension(r, granularity.clone()))
-                    } else {
-                        Err(CompilationError::User(format!(
-                            "Unable to use non time dimension \"{}\" as a column in date_trunc, please specify time dimension",
-                            possible_dimension_name
-                        )))
-                    }
-                } else {
-                    Err(CompilationError::User(format!(
-                        "Unknown dimension '{}' passed as a column in date_trunc",
-                        possible_dimension_name
-                    )))
-                }
+            [ast::FunctionArg::Unnamed(ast::Expr::Value(ast::Value::SingleQuotedString(granularity))), ast::FunctionArg::Unnamed(expr)] => {
+                self.find_time_dimension_selection(expr, granularity, "date_trunc")
             }
             _ => Err(CompilationError::User(
                 "Unsupported variation of arguments passed to date_trunc function, correct date_trunc(string, column)".to_string()
@@ -311,6 +390,37 @@ impl QueryContext {
         }
     }
 
+    /// EXTRACT(YEAR FROM col) maps to the year granularity. Other fields can't be pushed down:
+    /// Cube groups by the truncated date, which doesn't merge e.g. the same month of different years.
+    pub fn find_selection_for_extract(
+        &self,
+        field: &ast::DateTimeField,
+        expr: &ast::Expr,
+    ) -> CompilationResult<Selection> {
+        match field {
+            ast::DateTimeField::Year => self.find_time_dimension_selection(expr, "year", "extract"),
+            _ => Err(CompilationError::Unsupported(format!(
+                "EXTRACT({} FROM ...), please use DATE_TRUNC instead",
+                field
+            ))),
+        }
+    }
+
+    fn find_selection_for_timezone_fn(&self, f: &ast::Function) -> CompilationResult<Selection> {
+        let expr = ast::Expr::Function(f.clone());
+        match self.unpack_timestamp_column(&expr)? {
+            Some(name) => self.find_selection_for_identifier(&name, false).ok_or_else(|| {
+                CompilationError::User(format!(
+                    "Unknown dimension '{}' passed as a column in timezone",
+                    name
+                ))
+            }),
+            None => Err(CompilationError::User(
+                "Unsupported variation of arguments passed to timezone function, correct timezone(string, column)".to_string()
+            )),
+        }
+    }
+
     pub fn find_selection_for_date_fn(&self, f: &ast::Function) -> CompilationResult<Selection> {
         match f.args.as_slice() {
             [ast::FunctionArg::Unnamed(ast::Expr::Function(date_sub))] => {
@@ -509,13 +619,14 @@ impl QueryContext {
         match selection {
             Selection::Measure(measure) => {
                 if measure.agg_type.is_some()
-                    && !measure.is_same_agg_type(&call_agg_type)
+                    && !measure.is_compatible_agg_type(&call_agg_type)
                 {
                     return Err(CompilationError::User(format!(
-                        "Measure aggregation type doesn't match. The aggregation type for '{}' is '{}()' but '{}()' was provided",
+                        "Measure aggregation type doesn't match. The aggregation type for '{}' is '{}()' but '{}({})' was provided",
                         measure.get_real_name(),
                         measure.agg_type.unwrap_or("unknown".to_string()).to_uppercase(),
                         f.name.to_string(),
+                        if f.distinct { "DISTINCT" } else { "" },
                     )));
                 } else {
                     // @todo Should we throw an exception?
@@ -567,6 +678,7 @@ impl QueryContext {
         match fn_name.as_str() {
             "date_add" => self.find_selection_for_date_add_fn(f),
             "date_trunc" => self.find_selection_for_date_trunc_fn(f),
+            "timezone" => self.find_selection_for_timezone_fn(f),
             "date" => self.find_selection_for_date_fn(f),
             "measure" => self.find_selection_for_measure_fn(f),
             "sum" | "min" | "max" | "avg" | "count" => self.find_selection_for_aggregation_fn(f),
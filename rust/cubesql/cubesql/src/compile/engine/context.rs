@@ -82,6 +82,14 @@ impl VarProvider for VariablesProvider {
                     return self.get_session_value(identifier, VarType::System);
                 }
 
+                // Time zone is set per session, see `QueryPlanner::set_time_zone`
+                if identifier.len() == 1 && identifier[0].eq_ignore_ascii_case("@@time_zone") {
+                    return self.get_session_value(
+                        vec!["@@session".to_string(), "time_zone".to_string()],
+                        VarType::System,
+                    );
+                }
+
                 return self.get_global_value(identifier);
             }
             ('@', _) => return self.get_session_value(identifier, VarType::UserDefined),
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use async_trait::async_trait;
 use datafusion::{
@@ -8,17 +8,30 @@ use datafusion::{
     physical_plan::{planner::DefaultPhysicalPlanner, ExecutionPlan, PhysicalPlanner},
 };
 
-use crate::transport::TransportService;
+use crate::{
+    sql::{RecordingTransport, SessionState},
+    transport::TransportService,
+};
 
 use super::scan::CubeScanExtensionPlanner;
 
 pub struct CubeQueryPlanner {
     pub transport: Arc<dyn TransportService>,
+    pub session_state: Arc<SessionState>,
+    pub query_timeout: Duration,
 }
 
 impl CubeQueryPlanner {
-    pub fn new(transport: Arc<dyn TransportService>) -> Self {
-        Self { transport }
+    pub fn new(
+        transport: Arc<dyn TransportService>,
+        session_state: Arc<SessionState>,
+        query_timeout: Duration,
+    ) -> Self {
+        Self {
+            transport,
+            session_state,
+            query_timeout,
+        }
     }
 }
 
@@ -31,10 +44,18 @@ impl QueryPlanner for CubeQueryPlanner {
         logical_plan: &LogicalPlan,
         ctx_state: &ExecutionContextState,
     ) -> Result<Arc<dyn ExecutionPlan>> {
+        // Load responses of a recorded session are stored in its fixture
+        let transport: Arc<dyn TransportService> = match self.session_state.recorder() {
+            Some(recorder) => Arc::new(RecordingTransport::new(self.transport.clone(), recorder)),
+            None => self.transport.clone(),
+        };
+
         // Teach the default physical planner how to plan TopK nodes.
         let physical_planner = DefaultPhysicalPlanner::with_extension_planners(vec![Arc::new(
             CubeScanExtensionPlanner {
-                transport: self.transport.clone(),
+                transport,
+                query_token: self.session_state.query_token(),
+                query_timeout: self.query_timeout,
             },
         )]);
         // Delegate most work of physical planning to the default physical planner
@@ -3,13 +3,17 @@ use std::{
     fmt,
     sync::Arc,
     task::{Context, Poll},
+    time::Duration,
 };
 
 use async_trait::async_trait;
 use cubeclient::models::{V1LoadRequestQuery, V1LoadResult};
 use datafusion::{
     arrow::{
-        array::{ArrayRef, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder},
+        array::{
+            ArrayRef, BooleanBuilder, Date32Builder, DecimalBuilder, Float64Builder, Int64Builder,
+            StringBuilder,
+        },
         datatypes::{DataType, SchemaRef},
         error::Result as ArrowResult,
         record_batch::RecordBatch,
@@ -24,12 +28,17 @@ use datafusion::{
 };
 use futures::Stream;
 use log::{error, warn};
+use tokio_util::sync::CancellationToken;
 
 use crate::{sql::AuthContext, transport::TransportService};
-use chrono::{TimeZone, Utc};
+use chrono::{Datelike, NaiveDate, TimeZone, Utc};
 use datafusion::arrow::array::TimestampNanosecondBuilder;
 use datafusion::arrow::datatypes::TimeUnit;
 
+/// Decimal measures are loaded with a fixed precision and scale, as Cube doesn't report them
+pub const MEASURE_DECIMAL_PRECISION: usize = 38;
+pub const MEASURE_DECIMAL_SCALE: usize = 10;
+
 #[derive(Debug, Clone)]
 pub struct CubeScanNode {
     pub schema: DFSchemaRef,
@@ -100,6 +109,9 @@ impl UserDefinedLogicalNode for CubeScanNode {
 //  the logical plan node.
 pub struct CubeScanExtensionPlanner {
     pub transport: Arc<dyn TransportService>,
+    // Cancellation of the session query and max time to wait for Cube
+    pub query_token: CancellationToken,
+    pub query_timeout: Duration,
 }
 
 impl ExtensionPlanner for CubeScanExtensionPlanner {
@@ -124,6 +136,8 @@ impl ExtensionPlanner for CubeScanExtensionPlanner {
                     transport: self.transport.clone(),
                     request: scan_node.request.clone(),
                     auth_context: scan_node.auth_context.clone(),
+                    query_token: self.query_token.clone(),
+                    query_timeout: self.query_timeout,
                 }))
             } else {
                 None
@@ -141,9 +155,25 @@ struct CubeScanExecutionPlan {
     auth_context: Arc<AuthContext>,
     // Shared references which will be injected by extension planner
     transport: Arc<dyn TransportService>,
+    query_token: CancellationToken,
+    query_timeout: Duration,
 }
 
 impl CubeScanExecutionPlan {
+    /// Whether `field_name` is the time dimension requested with `granularity`.
+    fn is_time_dimension(&self, field_name: &str, granularity: &str) -> bool {
+        self.request
+            .time_dimensions
+            .iter()
+            .flatten()
+            .any(|time_dimension| match &time_dimension.granularity {
+                Some(g) => {
+                    g == granularity && format!("{}.{}", time_dimension.dimension, g) == field_name
+                }
+                None => false,
+            })
+    }
+
     // This methods transform response from Cube.js to RecordBatch which stores
     // schema and array of columns.
     fn transform_response(&self, response: V1LoadResult) -> Result<RecordBatch> {
@@ -200,6 +230,22 @@ impl CubeScanExecutionPlan {
                             },
                             serde_json::Value::String(s) => match s.parse::<i64>() {
                                 Ok(v) => builder.append_value(v)?,
+                                // EXTRACT(YEAR FROM col) is requested as a year time dimension
+                                Err(_) if self.is_time_dimension(field_name, "year") => match Utc
+                                    .datetime_from_str(s.as_str(), "%Y-%m-%dT%H:%M:%S.%f")
+                                {
+                                    Ok(timestamp) => {
+                                        builder.append_value(timestamp.year() as i64)?
+                                    }
+                                    Err(error) => {
+                                        warn!(
+                                            "Unable to parse value as year: {}",
+                                            error.to_string()
+                                        );
+
+                                        builder.append_null()?
+                                    }
+                                },
                                 Err(error) => {
                                     warn!("Unable to parse value as i64: {}", error.to_string());
 
@@ -318,6 +364,79 @@ impl CubeScanExecutionPlan {
 
                     Arc::new(builder.finish()) as ArrayRef
                 }
+                DataType::Decimal(precision, scale) => {
+                    let mut builder = DecimalBuilder::new(response.data.len(), *precision, *scale);
+
+                    for row in response.data.iter() {
+                        let value = row.as_object().unwrap().get(field_name).ok_or(
+                            DataFusionError::Internal(
+                                "Unexpected response from Cube.js, rows are not objects"
+                                    .to_string(),
+                            ),
+                        )?;
+                        let decimal = match &value {
+                            serde_json::Value::Null => None,
+                            serde_json::Value::Number(number) => {
+                                parse_decimal(&number.to_string(), *scale)
+                            }
+                            serde_json::Value::String(s) => parse_decimal(s, *scale),
+                            v => {
+                                error!(
+                                    "Unable to map value {:?} to DataType::Decimal (returning null)",
+                                    v
+                                );
+
+                                None
+                            }
+                        };
+                        match decimal {
+                            Some(v) => builder.append_value(v)?,
+                            None => builder.append_null()?,
+                        };
+                    }
+
+                    Arc::new(builder.finish()) as ArrayRef
+                }
+                // CAST(col AS DATE) is requested as a day time dimension
+                DataType::Date32 => {
+                    let mut builder = Date32Builder::new(response.data.len());
+                    let epoch = NaiveDate::from_ymd(1970, 1, 1);
+
+                    for row in response.data.iter() {
+                        let value = row.as_object().unwrap().get(field_name).ok_or(
+                            DataFusionError::Internal(
+                                "Unexpected response from Cube.js, rows are not objects"
+                                    .to_string(),
+                            ),
+                        )?;
+                        match &value {
+                            serde_json::Value::Null => builder.append_null()?,
+                            serde_json::Value::String(s) => {
+                                let timestamp = Utc
+                                    .datetime_from_str(s.as_str(), "%Y-%m-%dT%H:%M:%S.%f")
+                                    .map_err(|e| {
+                                        DataFusionError::Execution(format!(
+                                            "Can't parse date: '{}': {}",
+                                            s, e
+                                        ))
+                                    })?;
+                                builder.append_value(
+                                    (timestamp.naive_utc().date() - epoch).num_days() as i32,
+                                )?;
+                            }
+                            v => {
+                                error!(
+                                    "Unable to map value {:?} to DataType::Date32 (returning null)",
+                                    v
+                                );
+
+                                builder.append_null()?
+                            }
+                        };
+                    }
+
+                    Arc::new(builder.finish()) as ArrayRef
+                }
                 t => {
                     return Err(DataFusionError::NotImplemented(format!(
                         "Type {} is not supported in response transformation from Cube.js",
@@ -333,6 +452,45 @@ impl CubeScanExecutionPlan {
     }
 }
 
+/// Parses a decimal string as an integer with `scale` fractional digits, extra digits are
+/// truncated. Numbers in exponent notation go through `f64`.
+fn parse_decimal(value: &str, scale: usize) -> Option<i128> {
+    if value.contains(|c| c == 'e' || c == 'E') {
+        let number = value.parse::<f64>().ok()?;
+        return parse_decimal(&format!("{:.*}", scale, number), scale);
+    }
+
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if (integer.is_empty() && fraction.is_empty())
+        || !integer
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        warn!("Unable to parse value as decimal: {}", value);
+
+        return None;
+    }
+
+    let fraction = fraction.get(..scale).unwrap_or(fraction);
+    let mut result: i128 = 0;
+    for c in integer
+        .chars()
+        .chain(fraction.chars())
+        .chain(std::iter::repeat('0').take(scale - fraction.len()))
+    {
+        result = result
+            .checked_mul(10)?
+            .checked_add(c.to_digit(10).unwrap() as i128)?;
+    }
+
+    Some(if negative { -result } else { result })
+}
+
 #[async_trait]
 impl ExecutionPlan for CubeScanExecutionPlan {
     /// Return a reference to Any that can be used for downcasting
@@ -363,10 +521,24 @@ impl ExecutionPlan for CubeScanExecutionPlan {
     }
 
     async fn execute(&self, _partition: usize) -> Result<SendableRecordBatchStream> {
-        let result = self
+        let load = self
             .transport
-            .load(self.request.clone(), self.auth_context.clone())
-            .await;
+            .load(self.request.clone(), self.auth_context.clone());
+
+        // Dropping the load future aborts the request to Cube
+        let result = tokio::select! {
+            _ = self.query_token.cancelled() => {
+                return Err(DataFusionError::Execution(
+                    "Query was cancelled".to_string(),
+                ));
+            }
+            result = tokio::time::timeout(self.query_timeout, load) => result.map_err(|_| {
+                DataFusionError::Execution(format!(
+                    "Query was cancelled after timeout of {:?}",
+                    self.query_timeout
+                ))
+            })?,
+        };
 
         let mut response = result.map_err(|err| DataFusionError::Execution(err.to_string()))?;
 
@@ -492,9 +664,9 @@ mod tests {
                             "timeDimensions": []
                         },
                         "data": [
-                            {"KibanaSampleDataEcommerce.count": null, "KibanaSampleDataEcommerce.maxPrice": null, "KibanaSampleDataEcommerce.isBool": null},
-                            {"KibanaSampleDataEcommerce.count": 5, "KibanaSampleDataEcommerce.maxPrice": 5.05, "KibanaSampleDataEcommerce.isBool": true},
-                            {"KibanaSampleDataEcommerce.count": "5", "KibanaSampleDataEcommerce.maxPrice": "5.05", "KibanaSampleDataEcommerce.isBool": false}
+                            {"KibanaSampleDataEcommerce.count": null, "KibanaSampleDataEcommerce.maxPrice": null, "KibanaSampleDataEcommerce.sumPrice": null, "KibanaSampleDataEcommerce.isBool": null},
+                            {"KibanaSampleDataEcommerce.count": 5, "KibanaSampleDataEcommerce.maxPrice": 5.05, "KibanaSampleDataEcommerce.sumPrice": 5.05, "KibanaSampleDataEcommerce.isBool": true},
+                            {"KibanaSampleDataEcommerce.count": "5", "KibanaSampleDataEcommerce.maxPrice": "5.05", "KibanaSampleDataEcommerce.sumPrice": "1234567890.0123456789", "KibanaSampleDataEcommerce.isBool": false}
                         ]
                     }
                 "#;
@@ -506,6 +678,7 @@ mod tests {
                     slow_query: None,
                     query_type: None,
                     results: vec![result],
+                    compiler_id: None,
                 })
             }
         }
@@ -522,6 +695,11 @@ mod tests {
                 DataType::Float64,
                 false,
             ),
+            Field::new(
+                "KibanaSampleDataEcommerce.sumPrice",
+                DataType::Decimal(MEASURE_DECIMAL_PRECISION, MEASURE_DECIMAL_SCALE),
+                false,
+            ),
             Field::new("KibanaSampleDataEcommerce.isBool", DataType::Boolean, false),
         ]));
 
@@ -541,17 +719,28 @@ mod tests {
                 limit: None,
                 offset: None,
                 filters: None,
+                timezone: None,
             },
             auth_context: Arc::new(AuthContext {
                 access_token: "access_token".to_string(),
                 base_path: "base_path".to_string(),
+                security_context: None,
+                can_switch_user: false,
             }),
             transport: get_test_transport(),
+            query_token: CancellationToken::new(),
+            query_timeout: Duration::from_secs(15),
         };
 
         let stream = scan_node.execute(0).await.unwrap();
         let batches = common::collect(stream).await.unwrap();
 
+        let mut sum_price =
+            DecimalBuilder::new(3, MEASURE_DECIMAL_PRECISION, MEASURE_DECIMAL_SCALE);
+        sum_price.append_null().unwrap();
+        sum_price.append_value(50_500_000_000).unwrap();
+        sum_price.append_value(12_345_678_900_123_456_789).unwrap();
+
         assert_eq!(
             batches[0],
             RecordBatch::try_new(
@@ -559,10 +748,96 @@ mod tests {
                 vec![
                     Arc::new(StringArray::from(vec![None, Some("5"), Some("5")])) as ArrayRef,
                     Arc::new(Float64Array::from(vec![None, Some(5.05), Some(5.05)])) as ArrayRef,
+                    Arc::new(sum_price.finish()) as ArrayRef,
                     Arc::new(BooleanArray::from(vec![None, Some(true), Some(false)])) as ArrayRef,
                 ],
             )
             .unwrap()
         )
     }
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!(parse_decimal("5.05", 2), Some(505));
+        assert_eq!(parse_decimal("-5.05", 3), Some(-5050));
+        assert_eq!(parse_decimal("5", 2), Some(500));
+        assert_eq!(parse_decimal(".5", 2), Some(50));
+        assert_eq!(parse_decimal("5.059", 2), Some(505));
+        assert_eq!(parse_decimal("1e3", 2), Some(100000));
+        assert_eq!(parse_decimal("five", 2), None);
+        assert_eq!(parse_decimal("", 2), None);
+    }
+
+    fn get_pending_transport() -> Arc<dyn TransportService> {
+        #[derive(Debug)]
+        struct PendingTransport {}
+
+        #[async_trait]
+        impl TransportService for PendingTransport {
+            async fn meta(&self, _ctx: Arc<AuthContext>) -> Result<Arc<MetaContext>, CubeError> {
+                panic!("It's a fake transport");
+            }
+
+            // Cube never answers
+            async fn load(
+                &self,
+                _query: V1LoadRequestQuery,
+                _ctx: Arc<AuthContext>,
+            ) -> Result<V1LoadResponse, CubeError> {
+                futures::future::pending().await
+            }
+        }
+
+        Arc::new(PendingTransport {})
+    }
+
+    fn get_pending_scan(
+        query_token: CancellationToken,
+        query_timeout: Duration,
+    ) -> CubeScanExecutionPlan {
+        CubeScanExecutionPlan {
+            schema: Arc::new(Schema::new(vec![Field::new(
+                "KibanaSampleDataEcommerce.count",
+                DataType::Utf8,
+                false,
+            )])),
+            member_fields: vec!["KibanaSampleDataEcommerce.count".to_string()],
+            request: V1LoadRequestQuery::new(),
+            auth_context: Arc::new(AuthContext {
+                access_token: "access_token".to_string(),
+                base_path: "base_path".to_string(),
+                security_context: None,
+                can_switch_user: false,
+            }),
+            transport: get_pending_transport(),
+            query_token,
+            query_timeout,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_df_cube_scan_cancel() {
+        let query_token = CancellationToken::new();
+        let scan_node = get_pending_scan(query_token.clone(), Duration::from_secs(15));
+
+        let cancel_token = query_token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            cancel_token.cancel();
+        });
+
+        let err = scan_node.execute(0).await.err().unwrap();
+        assert_eq!(err.to_string(), "Execution error: Query was cancelled");
+    }
+
+    #[tokio::test]
+    async fn test_df_cube_scan_timeout() {
+        let scan_node = get_pending_scan(CancellationToken::new(), Duration::from_millis(10));
+
+        let err = scan_node.execute(0).await.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Execution error: Query was cancelled after timeout of 10ms"
+        );
+    }
 }
@@ -8,29 +8,47 @@ pub mod referential_constraints;
 pub mod table_constraints;
 pub mod tables;
 // pg_catalog
+mod pg_am;
 mod pg_attrdef;
 mod pg_attribute;
 mod pg_class;
 mod pg_constraint;
+mod pg_database;
 mod pg_description;
+mod pg_enum;
+mod pg_extension;
 mod pg_index;
+mod pg_inherits;
 mod pg_namespace;
 mod pg_proc;
 mod pg_range;
+mod pg_roles;
 mod pg_settings;
+mod pg_stat_activity;
 mod pg_tables;
 mod pg_type;
+mod pg_user;
+mod pg_views;
 
 use super::utils;
+pub use pg_am::*;
 pub use pg_attrdef::*;
 pub use pg_attribute::*;
 pub use pg_class::*;
 pub use pg_constraint::*;
+pub use pg_database::*;
 pub use pg_description::*;
+pub use pg_enum::*;
+pub use pg_extension::*;
 pub use pg_index::*;
+pub use pg_inherits::*;
 pub use pg_namespace::*;
 pub use pg_proc::*;
 pub use pg_range::*;
+pub use pg_roles::*;
 pub use pg_settings::*;
+pub use pg_stat_activity::*;
 pub use pg_tables::*;
 pub use pg_type::*;
+pub use pg_user::*;
+pub use pg_views::*;
@@ -0,0 +1,192 @@
+use std::{any::Any, sync::Arc};
+
+use async_trait::async_trait;
+
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, BooleanBuilder, Int32Builder, StringBuilder, UInt32Builder},
+        datatypes::{DataType, Field, Schema, SchemaRef},
+        record_batch::RecordBatch,
+    },
+    datasource::{datasource::TableProviderFilterPushDown, TableProvider, TableType},
+    error::DataFusionError,
+    logical_plan::Expr,
+    physical_plan::{memory::MemoryExec, ExecutionPlan},
+};
+
+struct PgDatabase {
+    oid: u32,
+    datname: String,
+    datdba: u32,
+    encoding: i32,
+    datcollate: &'static str,
+    datctype: &'static str,
+    datistemplate: bool,
+    datallowconn: bool,
+    datconnlimit: i32,
+    datlastsysoid: u32,
+    datfrozenxid: i32,
+    datminmxid: i32,
+    dattablespace: u32,
+}
+
+struct PgCatalogDatabaseBuilder {
+    oid: UInt32Builder,
+    datname: StringBuilder,
+    datdba: UInt32Builder,
+    encoding: Int32Builder,
+    datcollate: StringBuilder,
+    datctype: StringBuilder,
+    datistemplate: BooleanBuilder,
+    datallowconn: BooleanBuilder,
+    datconnlimit: Int32Builder,
+    datlastsysoid: UInt32Builder,
+    datfrozenxid: Int32Builder,
+    datminmxid: Int32Builder,
+    dattablespace: UInt32Builder,
+    datacl: StringBuilder,
+}
+
+impl PgCatalogDatabaseBuilder {
+    fn new() -> Self {
+        let capacity = 10;
+
+        Self {
+            oid: UInt32Builder::new(capacity),
+            datname: StringBuilder::new(capacity),
+            datdba: UInt32Builder::new(capacity),
+            encoding: Int32Builder::new(capacity),
+            datcollate: StringBuilder::new(capacity),
+            datctype: StringBuilder::new(capacity),
+            datistemplate: BooleanBuilder::new(capacity),
+            datallowconn: BooleanBuilder::new(capacity),
+            datconnlimit: Int32Builder::new(capacity),
+            datlastsysoid: UInt32Builder::new(capacity),
+            datfrozenxid: Int32Builder::new(capacity),
+            datminmxid: Int32Builder::new(capacity),
+            dattablespace: UInt32Builder::new(capacity),
+            datacl: StringBuilder::new(capacity),
+        }
+    }
+
+    fn add_database(&mut self, db: &PgDatabase) {
+        self.oid.append_value(db.oid).unwrap();
+        self.datname.append_value(&db.datname).unwrap();
+        self.datdba.append_value(db.datdba).unwrap();
+        self.encoding.append_value(db.encoding).unwrap();
+        self.datcollate.append_value(db.datcollate).unwrap();
+        self.datctype.append_value(db.datctype).unwrap();
+        self.datistemplate.append_value(db.datistemplate).unwrap();
+        self.datallowconn.append_value(db.datallowconn).unwrap();
+        self.datconnlimit.append_value(db.datconnlimit).unwrap();
+        self.datlastsysoid.append_value(db.datlastsysoid).unwrap();
+        self.datfrozenxid.append_value(db.datfrozenxid).unwrap();
+        self.datminmxid.append_value(db.datminmxid).unwrap();
+        self.dattablespace.append_value(db.dattablespace).unwrap();
+        self.datacl.append_null().unwrap();
+    }
+
+    fn finish(mut self) -> Vec<Arc<dyn Array>> {
+        let mut columns: Vec<Arc<dyn Array>> = vec![];
+        columns.push(Arc::new(self.oid.finish()));
+        columns.push(Arc::new(self.datname.finish()));
+        columns.push(Arc::new(self.datdba.finish()));
+        columns.push(Arc::new(self.encoding.finish()));
+        columns.push(Arc::new(self.datcollate.finish()));
+        columns.push(Arc::new(self.datctype.finish()));
+        columns.push(Arc::new(self.datistemplate.finish()));
+        columns.push(Arc::new(self.datallowconn.finish()));
+        columns.push(Arc::new(self.datconnlimit.finish()));
+        columns.push(Arc::new(self.datlastsysoid.finish()));
+        columns.push(Arc::new(self.datfrozenxid.finish()));
+        columns.push(Arc::new(self.datminmxid.finish()));
+        columns.push(Arc::new(self.dattablespace.finish()));
+        columns.push(Arc::new(self.datacl.finish()));
+
+        columns
+    }
+}
+
+pub struct PgCatalogDatabaseProvider {
+    data: Arc<Vec<ArrayRef>>,
+}
+
+impl PgCatalogDatabaseProvider {
+    pub fn new(db: &str) -> Self {
+        let mut builder = PgCatalogDatabaseBuilder::new();
+        builder.add_database(&PgDatabase {
+            oid: 13757,
+            datname: db.to_string(),
+            datdba: 10,
+            // UTF8
+            encoding: 6,
+            datcollate: "en_US.utf8",
+            datctype: "en_US.utf8",
+            datistemplate: false,
+            datallowconn: true,
+            datconnlimit: -1,
+            datlastsysoid: 13756,
+            datfrozenxid: 727,
+            datminmxid: 1,
+            // pg_default
+            dattablespace: 1663,
+        });
+
+        Self {
+            data: Arc::new(builder.finish()),
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for PgCatalogDatabaseProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("oid", DataType::UInt32, false),
+            Field::new("datname", DataType::Utf8, false),
+            Field::new("datdba", DataType::UInt32, false),
+            Field::new("encoding", DataType::Int32, false),
+            Field::new("datcollate", DataType::Utf8, false),
+            Field::new("datctype", DataType::Utf8, false),
+            Field::new("datistemplate", DataType::Boolean, false),
+            Field::new("datallowconn", DataType::Boolean, false),
+            Field::new("datconnlimit", DataType::Int32, false),
+            Field::new("datlastsysoid", DataType::UInt32, false),
+            Field::new("datfrozenxid", DataType::Int32, false),
+            Field::new("datminmxid", DataType::Int32, false),
+            Field::new("dattablespace", DataType::UInt32, false),
+            Field::new("datacl", DataType::Utf8, true),
+        ]))
+    }
+
+    async fn scan(
+        &self,
+        projection: &Option<Vec<usize>>,
+        _batch_size: usize,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+        let batch = RecordBatch::try_new(self.schema(), self.data.to_vec())?;
+
+        Ok(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            self.schema(),
+            projection.clone(),
+        )?))
+    }
+
+    fn supports_filter_pushdown(
+        &self,
+        _filter: &Expr,
+    ) -> Result<TableProviderFilterPushDown, DataFusionError> {
+        Ok(TableProviderFilterPushDown::Unsupported)
+    }
+}
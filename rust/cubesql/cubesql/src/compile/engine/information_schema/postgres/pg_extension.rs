@@ -0,0 +1,149 @@
+use std::{any::Any, sync::Arc};
+
+use async_trait::async_trait;
+
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, BooleanBuilder, StringBuilder, UInt32Builder},
+        datatypes::{DataType, Field, Schema, SchemaRef},
+        record_batch::RecordBatch,
+    },
+    datasource::{datasource::TableProviderFilterPushDown, TableProvider, TableType},
+    error::DataFusionError,
+    logical_plan::Expr,
+    physical_plan::{memory::MemoryExec, ExecutionPlan},
+};
+
+struct PgExtension {
+    oid: u32,
+    extname: &'static str,
+    extowner: u32,
+    extnamespace: u32,
+    extrelocatable: bool,
+    extversion: &'static str,
+}
+
+struct PgCatalogExtensionBuilder {
+    oid: UInt32Builder,
+    extname: StringBuilder,
+    extowner: UInt32Builder,
+    extnamespace: UInt32Builder,
+    extrelocatable: BooleanBuilder,
+    extversion: StringBuilder,
+    extconfig: StringBuilder,
+    extcondition: StringBuilder,
+}
+
+impl PgCatalogExtensionBuilder {
+    fn new() -> Self {
+        let capacity = 10;
+
+        Self {
+            oid: UInt32Builder::new(capacity),
+            extname: StringBuilder::new(capacity),
+            extowner: UInt32Builder::new(capacity),
+            extnamespace: UInt32Builder::new(capacity),
+            extrelocatable: BooleanBuilder::new(capacity),
+            extversion: StringBuilder::new(capacity),
+            extconfig: StringBuilder::new(capacity),
+            extcondition: StringBuilder::new(capacity),
+        }
+    }
+
+    fn add_extension(&mut self, ext: &PgExtension) {
+        self.oid.append_value(ext.oid).unwrap();
+        self.extname.append_value(ext.extname).unwrap();
+        self.extowner.append_value(ext.extowner).unwrap();
+        self.extnamespace.append_value(ext.extnamespace).unwrap();
+        self.extrelocatable
+            .append_value(ext.extrelocatable)
+            .unwrap();
+        self.extversion.append_value(ext.extversion).unwrap();
+        self.extconfig.append_null().unwrap();
+        self.extcondition.append_null().unwrap();
+    }
+
+    fn finish(mut self) -> Vec<Arc<dyn Array>> {
+        let mut columns: Vec<Arc<dyn Array>> = vec![];
+        columns.push(Arc::new(self.oid.finish()));
+        columns.push(Arc::new(self.extname.finish()));
+        columns.push(Arc::new(self.extowner.finish()));
+        columns.push(Arc::new(self.extnamespace.finish()));
+        columns.push(Arc::new(self.extrelocatable.finish()));
+        columns.push(Arc::new(self.extversion.finish()));
+        columns.push(Arc::new(self.extconfig.finish()));
+        columns.push(Arc::new(self.extcondition.finish()));
+
+        columns
+    }
+}
+
+pub struct PgCatalogExtensionProvider {
+    data: Arc<Vec<ArrayRef>>,
+}
+
+impl PgCatalogExtensionProvider {
+    pub fn new() -> Self {
+        let mut builder = PgCatalogExtensionBuilder::new();
+        // Installed by default in every Postgres database
+        builder.add_extension(&PgExtension {
+            oid: 13749,
+            extname: "plpgsql",
+            extowner: 10,
+            extnamespace: 11,
+            extrelocatable: false,
+            extversion: "1.0",
+        });
+
+        Self {
+            data: Arc::new(builder.finish()),
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for PgCatalogExtensionProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("oid", DataType::UInt32, false),
+            Field::new("extname", DataType::Utf8, false),
+            Field::new("extowner", DataType::UInt32, false),
+            Field::new("extnamespace", DataType::UInt32, false),
+            Field::new("extrelocatable", DataType::Boolean, false),
+            Field::new("extversion", DataType::Utf8, false),
+            Field::new("extconfig", DataType::Utf8, true),
+            Field::new("extcondition", DataType::Utf8, true),
+        ]))
+    }
+
+    async fn scan(
+        &self,
+        projection: &Option<Vec<usize>>,
+        _batch_size: usize,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+        let batch = RecordBatch::try_new(self.schema(), self.data.to_vec())?;
+
+        Ok(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            self.schema(),
+            projection.clone(),
+        )?))
+    }
+
+    fn supports_filter_pushdown(
+        &self,
+        _filter: &Expr,
+    ) -> Result<TableProviderFilterPushDown, DataFusionError> {
+        Ok(TableProviderFilterPushDown::Unsupported)
+    }
+}
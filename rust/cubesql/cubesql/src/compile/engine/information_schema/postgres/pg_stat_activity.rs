@@ -0,0 +1,235 @@
+use std::{any::Any, sync::Arc};
+
+use async_trait::async_trait;
+
+use datafusion::{
+    arrow::{
+        array::{Array, Int32Builder, StringBuilder, TimestampNanosecondBuilder, UInt32Builder},
+        datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+        record_batch::RecordBatch,
+    },
+    datasource::{datasource::TableProviderFilterPushDown, TableProvider, TableType},
+    error::DataFusionError,
+    logical_plan::Expr,
+    physical_plan::{memory::MemoryExec, ExecutionPlan},
+};
+
+use crate::sql::{SessionManager, SessionProcessList};
+
+struct PgCatalogStatActivityBuilder {
+    datid: UInt32Builder,
+    datname: StringBuilder,
+    pid: Int32Builder,
+    leader_pid: Int32Builder,
+    usesysid: UInt32Builder,
+    usename: StringBuilder,
+    application_name: StringBuilder,
+    client_addr: StringBuilder,
+    client_hostname: StringBuilder,
+    client_port: Int32Builder,
+    backend_start: TimestampNanosecondBuilder,
+    xact_start: TimestampNanosecondBuilder,
+    query_start: TimestampNanosecondBuilder,
+    state_change: TimestampNanosecondBuilder,
+    wait_event_type: StringBuilder,
+    wait_event: StringBuilder,
+    state: StringBuilder,
+    backend_xid: StringBuilder,
+    backend_xmin: StringBuilder,
+    query: StringBuilder,
+    backend_type: StringBuilder,
+}
+
+impl PgCatalogStatActivityBuilder {
+    fn new() -> Self {
+        let capacity = 10;
+
+        Self {
+            datid: UInt32Builder::new(capacity),
+            datname: StringBuilder::new(capacity),
+            pid: Int32Builder::new(capacity),
+            leader_pid: Int32Builder::new(capacity),
+            usesysid: UInt32Builder::new(capacity),
+            usename: StringBuilder::new(capacity),
+            application_name: StringBuilder::new(capacity),
+            client_addr: StringBuilder::new(capacity),
+            client_hostname: StringBuilder::new(capacity),
+            client_port: Int32Builder::new(capacity),
+            backend_start: TimestampNanosecondBuilder::new(capacity),
+            xact_start: TimestampNanosecondBuilder::new(capacity),
+            query_start: TimestampNanosecondBuilder::new(capacity),
+            state_change: TimestampNanosecondBuilder::new(capacity),
+            wait_event_type: StringBuilder::new(capacity),
+            wait_event: StringBuilder::new(capacity),
+            state: StringBuilder::new(capacity),
+            backend_xid: StringBuilder::new(capacity),
+            backend_xmin: StringBuilder::new(capacity),
+            query: StringBuilder::new(capacity),
+            backend_type: StringBuilder::new(capacity),
+        }
+    }
+
+    fn add_row(&mut self, process_list: SessionProcessList, active: bool) {
+        // Same oid as the single database of pg_database
+        self.datid.append_value(13757).unwrap();
+
+        if let Some(database) = process_list.database {
+            self.datname.append_value(database).unwrap();
+        } else {
+            self.datname.append_null().unwrap();
+        }
+
+        self.pid.append_value(process_list.id as i32).unwrap();
+        self.leader_pid.append_null().unwrap();
+        self.usesysid.append_value(10).unwrap();
+
+        if let Some(user) = process_list.user {
+            self.usename.append_value(user).unwrap();
+        } else {
+            self.usename.append_null().unwrap();
+        }
+
+        self.application_name.append_value("").unwrap();
+        self.client_addr.append_value(process_list.host).unwrap();
+        self.client_hostname.append_null().unwrap();
+        self.client_port.append_null().unwrap();
+        self.backend_start.append_null().unwrap();
+        self.xact_start.append_null().unwrap();
+        self.query_start.append_null().unwrap();
+        self.state_change.append_null().unwrap();
+        self.wait_event_type.append_null().unwrap();
+        self.wait_event.append_null().unwrap();
+        self.state
+            .append_value(if active { "active" } else { "idle" })
+            .unwrap();
+        self.backend_xid.append_null().unwrap();
+        self.backend_xmin.append_null().unwrap();
+        self.query.append_null().unwrap();
+        self.backend_type.append_value("client backend").unwrap();
+    }
+
+    fn finish(mut self) -> Vec<Arc<dyn Array>> {
+        let mut columns: Vec<Arc<dyn Array>> = vec![];
+        columns.push(Arc::new(self.datid.finish()));
+        columns.push(Arc::new(self.datname.finish()));
+        columns.push(Arc::new(self.pid.finish()));
+        columns.push(Arc::new(self.leader_pid.finish()));
+        columns.push(Arc::new(self.usesysid.finish()));
+        columns.push(Arc::new(self.usename.finish()));
+        columns.push(Arc::new(self.application_name.finish()));
+        columns.push(Arc::new(self.client_addr.finish()));
+        columns.push(Arc::new(self.client_hostname.finish()));
+        columns.push(Arc::new(self.client_port.finish()));
+        columns.push(Arc::new(self.backend_start.finish()));
+        columns.push(Arc::new(self.xact_start.finish()));
+        columns.push(Arc::new(self.query_start.finish()));
+        columns.push(Arc::new(self.state_change.finish()));
+        columns.push(Arc::new(self.wait_event_type.finish()));
+        columns.push(Arc::new(self.wait_event.finish()));
+        columns.push(Arc::new(self.state.finish()));
+        columns.push(Arc::new(self.backend_xid.finish()));
+        columns.push(Arc::new(self.backend_xmin.finish()));
+        columns.push(Arc::new(self.query.finish()));
+        columns.push(Arc::new(self.backend_type.finish()));
+
+        columns
+    }
+}
+
+pub struct PgCatalogStatActivityProvider {
+    sessions: Arc<SessionManager>,
+    connection_id: u32,
+}
+
+impl PgCatalogStatActivityProvider {
+    /// `connection_id` is the session running the query, which is the only active one.
+    pub fn new(sessions: Arc<SessionManager>, connection_id: u32) -> Self {
+        Self {
+            sessions,
+            connection_id,
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for PgCatalogStatActivityProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("datid", DataType::UInt32, true),
+            Field::new("datname", DataType::Utf8, true),
+            Field::new("pid", DataType::Int32, false),
+            Field::new("leader_pid", DataType::Int32, true),
+            Field::new("usesysid", DataType::UInt32, true),
+            Field::new("usename", DataType::Utf8, true),
+            Field::new("application_name", DataType::Utf8, false),
+            Field::new("client_addr", DataType::Utf8, true),
+            Field::new("client_hostname", DataType::Utf8, true),
+            Field::new("client_port", DataType::Int32, true),
+            Field::new(
+                "backend_start",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            ),
+            Field::new(
+                "xact_start",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            ),
+            Field::new(
+                "query_start",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            ),
+            Field::new(
+                "state_change",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            ),
+            Field::new("wait_event_type", DataType::Utf8, true),
+            Field::new("wait_event", DataType::Utf8, true),
+            Field::new("state", DataType::Utf8, true),
+            Field::new("backend_xid", DataType::Utf8, true),
+            Field::new("backend_xmin", DataType::Utf8, true),
+            Field::new("query", DataType::Utf8, true),
+            Field::new("backend_type", DataType::Utf8, false),
+        ]))
+    }
+
+    async fn scan(
+        &self,
+        projection: &Option<Vec<usize>>,
+        _batch_size: usize,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+        let mut builder = PgCatalogStatActivityBuilder::new();
+
+        for process_list in self.sessions.process_list() {
+            let active = process_list.id == self.connection_id;
+            builder.add_row(process_list, active);
+        }
+
+        let batch = RecordBatch::try_new(self.schema(), builder.finish())?;
+
+        Ok(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            self.schema(),
+            projection.clone(),
+        )?))
+    }
+
+    fn supports_filter_pushdown(
+        &self,
+        _filter: &Expr,
+    ) -> Result<TableProviderFilterPushDown, DataFusionError> {
+        Ok(TableProviderFilterPushDown::Unsupported)
+    }
+}
@@ -0,0 +1,155 @@
+use std::{any::Any, sync::Arc};
+
+use async_trait::async_trait;
+
+use datafusion::{
+    arrow::{
+        array::{
+            Array, ArrayRef, BooleanBuilder, StringBuilder, TimestampNanosecondBuilder,
+            UInt32Builder,
+        },
+        datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+        record_batch::RecordBatch,
+    },
+    datasource::{datasource::TableProviderFilterPushDown, TableProvider, TableType},
+    error::DataFusionError,
+    logical_plan::Expr,
+    physical_plan::{memory::MemoryExec, ExecutionPlan},
+};
+
+struct PgUser {
+    usename: String,
+    usesysid: u32,
+    usecreatedb: bool,
+    usesuper: bool,
+}
+
+struct PgCatalogUserBuilder {
+    usename: StringBuilder,
+    usesysid: UInt32Builder,
+    usecreatedb: BooleanBuilder,
+    usesuper: BooleanBuilder,
+    userepl: BooleanBuilder,
+    usebypassrls: BooleanBuilder,
+    passwd: StringBuilder,
+    valuntil: TimestampNanosecondBuilder,
+    useconfig: StringBuilder,
+}
+
+impl PgCatalogUserBuilder {
+    fn new() -> Self {
+        let capacity = 10;
+
+        Self {
+            usename: StringBuilder::new(capacity),
+            usesysid: UInt32Builder::new(capacity),
+            usecreatedb: BooleanBuilder::new(capacity),
+            usesuper: BooleanBuilder::new(capacity),
+            userepl: BooleanBuilder::new(capacity),
+            usebypassrls: BooleanBuilder::new(capacity),
+            passwd: StringBuilder::new(capacity),
+            valuntil: TimestampNanosecondBuilder::new(capacity),
+            useconfig: StringBuilder::new(capacity),
+        }
+    }
+
+    fn add_user(&mut self, user: &PgUser) {
+        self.usename.append_value(&user.usename).unwrap();
+        self.usesysid.append_value(user.usesysid).unwrap();
+        self.usecreatedb.append_value(user.usecreatedb).unwrap();
+        self.usesuper.append_value(user.usesuper).unwrap();
+        self.userepl.append_value(false).unwrap();
+        self.usebypassrls.append_value(false).unwrap();
+        self.passwd.append_value("********").unwrap();
+        self.valuntil.append_null().unwrap();
+        self.useconfig.append_null().unwrap();
+    }
+
+    fn finish(mut self) -> Vec<Arc<dyn Array>> {
+        let mut columns: Vec<Arc<dyn Array>> = vec![];
+        columns.push(Arc::new(self.usename.finish()));
+        columns.push(Arc::new(self.usesysid.finish()));
+        columns.push(Arc::new(self.usecreatedb.finish()));
+        columns.push(Arc::new(self.usesuper.finish()));
+        columns.push(Arc::new(self.userepl.finish()));
+        columns.push(Arc::new(self.usebypassrls.finish()));
+        columns.push(Arc::new(self.passwd.finish()));
+        columns.push(Arc::new(self.valuntil.finish()));
+        columns.push(Arc::new(self.useconfig.finish()));
+
+        columns
+    }
+}
+
+pub struct PgCatalogUserProvider {
+    data: Arc<Vec<ArrayRef>>,
+}
+
+impl PgCatalogUserProvider {
+    /// Mirrors the single role of pg_roles.
+    pub fn new(user: &str) -> Self {
+        let mut builder = PgCatalogUserBuilder::new();
+        builder.add_user(&PgUser {
+            usename: user.to_string(),
+            usesysid: 10,
+            usecreatedb: false,
+            usesuper: false,
+        });
+
+        Self {
+            data: Arc::new(builder.finish()),
+        }
+    }
+}
+
+#[async_trait]
+impl TableProvider for PgCatalogUserProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::View
+    }
+
+    fn schema(&self) -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("usename", DataType::Utf8, false),
+            Field::new("usesysid", DataType::UInt32, false),
+            Field::new("usecreatedb", DataType::Boolean, false),
+            Field::new("usesuper", DataType::Boolean, false),
+            Field::new("userepl", DataType::Boolean, false),
+            Field::new("usebypassrls", DataType::Boolean, false),
+            Field::new("passwd", DataType::Utf8, true),
+            Field::new(
+                "valuntil",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            ),
+            Field::new("useconfig", DataType::Utf8, true),
+        ]))
+    }
+
+    async fn scan(
+        &self,
+        projection: &Option<Vec<usize>>,
+        _batch_size: usize,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+        let batch = RecordBatch::try_new(self.schema(), self.data.to_vec())?;
+
+        Ok(Arc::new(MemoryExec::try_new(
+            &[vec![batch]],
+            self.schema(),
+            projection.clone(),
+        )?))
+    }
+
+    fn supports_filter_pushdown(
+        &self,
+        _filter: &Expr,
+    ) -> Result<TableProviderFilterPushDown, DataFusionError> {
+        Ok(TableProviderFilterPushDown::Unsupported)
+    }
+}
@@ -30,11 +30,14 @@ use super::information_schema::postgres::{
     key_column_usage::InfoSchemaKeyColumnUsageProvider as PostgresSchemaKeyColumnUsageProvider,
     referential_constraints::InfoSchemaReferentialConstraintsProvider as PostgresSchemaReferentialConstraintsProvider,
     table_constraints::InfoSchemaTableConstraintsProvider as PostgresSchemaTableConstraintsProvider,
-    tables::InfoSchemaTableProvider as PostgresSchemaTableProvider, PgCatalogAttrdefProvider,
-    PgCatalogAttributeProvider, PgCatalogClassProvider, PgCatalogConstraintProvider,
-    PgCatalogDescriptionProvider, PgCatalogIndexProvider, PgCatalogNamespaceProvider,
-    PgCatalogProcProvider, PgCatalogRangeProvider, PgCatalogSettingsProvider,
-    PgCatalogTableProvider, PgCatalogTypeProvider,
+    tables::InfoSchemaTableProvider as PostgresSchemaTableProvider, PgCatalogAmProvider,
+    PgCatalogAttrdefProvider, PgCatalogAttributeProvider, PgCatalogClassProvider,
+    PgCatalogConstraintProvider, PgCatalogDatabaseProvider, PgCatalogDescriptionProvider,
+    PgCatalogEnumProvider, PgCatalogExtensionProvider, PgCatalogIndexProvider,
+    PgCatalogInheritsProvider, PgCatalogNamespaceProvider, PgCatalogProcProvider,
+    PgCatalogRangeProvider, PgCatalogRolesProvider, PgCatalogSettingsProvider,
+    PgCatalogStatActivityProvider, PgCatalogTableProvider, PgCatalogTypeProvider,
+    PgCatalogUserProvider, PgCatalogViewsProvider,
 };
 
 use crate::sql::ColumnType;
@@ -113,6 +116,12 @@ impl ContextProvider for CubeContext {
     }
 
     fn get_function_meta(&self, name: &str) -> Option<Arc<ScalarUDF>> {
+        // Postgres clients call catalog functions qualified, e.g. pg_catalog.format_type
+        let name = match self.session_state.protocol {
+            DatabaseProtocol::PostgreSQL => name.strip_prefix("pg_catalog.").unwrap_or(name),
+            DatabaseProtocol::MySQL => name,
+        };
+
         self.state.scalar_functions.get(name).cloned()
     }
 
@@ -292,6 +301,24 @@ impl DatabaseProtocol {
                 "pg_catalog.pg_description".to_string()
             } else if let Some(_) = any.downcast_ref::<PgCatalogConstraintProvider>() {
                 "pg_catalog.pg_constraint".to_string()
+            } else if let Some(_) = any.downcast_ref::<PgCatalogDatabaseProvider>() {
+                "pg_catalog.pg_database".to_string()
+            } else if let Some(_) = any.downcast_ref::<PgCatalogRolesProvider>() {
+                "pg_catalog.pg_roles".to_string()
+            } else if let Some(_) = any.downcast_ref::<PgCatalogUserProvider>() {
+                "pg_catalog.pg_user".to_string()
+            } else if let Some(_) = any.downcast_ref::<PgCatalogViewsProvider>() {
+                "pg_catalog.pg_views".to_string()
+            } else if let Some(_) = any.downcast_ref::<PgCatalogAmProvider>() {
+                "pg_catalog.pg_am".to_string()
+            } else if let Some(_) = any.downcast_ref::<PgCatalogEnumProvider>() {
+                "pg_catalog.pg_enum".to_string()
+            } else if let Some(_) = any.downcast_ref::<PgCatalogInheritsProvider>() {
+                "pg_catalog.pg_inherits".to_string()
+            } else if let Some(_) = any.downcast_ref::<PgCatalogExtensionProvider>() {
+                "pg_catalog.pg_extension".to_string()
+            } else if let Some(_) = any.downcast_ref::<PgCatalogStatActivityProvider>() {
+                "pg_catalog.pg_stat_activity".to_string()
             } else {
                 return Err(CubeError::internal(format!(
                     "Unknown table provider with schema: {:?}",
@@ -306,6 +333,11 @@ impl DatabaseProtocol {
         context: &CubeContext,
         tp: String,
     ) -> Option<std::sync::Arc<dyn datasource::TableProvider>> {
+        // pg_catalog is implicitly the first schema of the search path
+        if !tp.contains('.') && tp.to_lowercase().starts_with("pg_") {
+            return self.get_postgres_provider(context, format!("pg_catalog.{}", tp));
+        }
+
         if tp.eq_ignore_ascii_case("information_schema.columns") {
             return Some(Arc::new(PostgresSchemaColumnsProvider::new(
                 &context.meta.cubes,
@@ -374,12 +406,13 @@ impl DatabaseProtocol {
         }
 
         if tp.eq_ignore_ascii_case("pg_catalog.pg_settings") {
-            return Some(Arc::new(PgCatalogSettingsProvider::new(
-                context
-                    .sessions
-                    .server
-                    .all_variables(context.session_state.protocol.clone()),
-            )));
+            let mut variables = context
+                .sessions
+                .server
+                .all_variables(context.session_state.protocol.clone());
+            variables.extend(context.session_state.all_variables());
+
+            return Some(Arc::new(PgCatalogSettingsProvider::new(variables)));
         }
 
         if tp.eq_ignore_ascii_case("pg_catalog.pg_description") {
@@ -390,6 +423,51 @@ impl DatabaseProtocol {
             return Some(Arc::new(PgCatalogConstraintProvider::new()));
         }
 
+        if tp.eq_ignore_ascii_case("pg_catalog.pg_database") {
+            return Some(Arc::new(PgCatalogDatabaseProvider::new(
+                &context.session_state.database().unwrap_or("db".to_string()),
+            )));
+        }
+
+        if tp.eq_ignore_ascii_case("pg_catalog.pg_roles") {
+            return Some(Arc::new(PgCatalogRolesProvider::new(
+                &context.session_state.user().unwrap_or("cube".to_string()),
+            )));
+        }
+
+        if tp.eq_ignore_ascii_case("pg_catalog.pg_user") {
+            return Some(Arc::new(PgCatalogUserProvider::new(
+                &context.session_state.user().unwrap_or("cube".to_string()),
+            )));
+        }
+
+        if tp.eq_ignore_ascii_case("pg_catalog.pg_views") {
+            return Some(Arc::new(PgCatalogViewsProvider::new()));
+        }
+
+        if tp.eq_ignore_ascii_case("pg_catalog.pg_am") {
+            return Some(Arc::new(PgCatalogAmProvider::new()));
+        }
+
+        if tp.eq_ignore_ascii_case("pg_catalog.pg_enum") {
+            return Some(Arc::new(PgCatalogEnumProvider::new()));
+        }
+
+        if tp.eq_ignore_ascii_case("pg_catalog.pg_inherits") {
+            return Some(Arc::new(PgCatalogInheritsProvider::new()));
+        }
+
+        if tp.eq_ignore_ascii_case("pg_catalog.pg_extension") {
+            return Some(Arc::new(PgCatalogExtensionProvider::new()));
+        }
+
+        if tp.eq_ignore_ascii_case("pg_catalog.pg_stat_activity") {
+            return Some(Arc::new(PgCatalogStatActivityProvider::new(
+                context.sessions.clone(),
+                context.session_state.connection_id,
+            )));
+        }
+
         None
     }
 }
@@ -398,6 +476,10 @@ pub trait TableName {
     fn table_name(&self) -> &str;
 }
 
+/// Virtual column of every cube table. Cubes are joined `ON a.__cubeJoinField = b.__cubeJoinField`
+/// and Cube picks the join path defined in the data schema.
+pub const CUBE_JOIN_FIELD: &str = "__cubeJoinField";
+
 pub struct CubeTableProvider {
     cube: V1CubeMeta,
 }
@@ -443,6 +525,7 @@ impl TableProvider for CubeTableProvider {
                         true,
                     )
                 })
+                .chain(vec![Field::new(CUBE_JOIN_FIELD, DataType::Utf8, true)])
                 .collect(),
         ))
     }
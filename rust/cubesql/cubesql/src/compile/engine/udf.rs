@@ -28,7 +28,8 @@ use crate::{
     },
     sql::SessionState,
 };
-use chrono::{Duration, NaiveDateTime};
+use chrono::{Datelike, Duration, FixedOffset, NaiveDateTime, Offset, TimeZone, Timelike};
+use chrono_tz::Tz;
 use datafusion::arrow::array::{IntervalDayTimeArray, StringArray, TimestampNanosecondArray};
 use datafusion::logical_plan::create_udaf;
 use datafusion::physical_plan::datetime_expressions::date_trunc;
@@ -496,19 +497,43 @@ pub fn create_convert_tz_udf() -> ScalarUDF {
         let from_tz = downcast_string_arg!(&from_tz, "from_tz", i32);
         let to_tz = downcast_string_arg!(&to_tz, "to_tz", i32);
 
-        if from_tz.value(0) != "SYSTEM" || to_tz.value(0) != "+00:00" {
-            return Err(DataFusionError::NotImplemented(format!(
-                "convert_tz is not implemented, it's stub"
-            )));
-        }
-
         if input_tz.is_some() {
             return Err(DataFusionError::NotImplemented(format!(
-                "convert_tz is not implemented, it's stub"
+                "convert_tz is not implemented for timestamps with a time zone"
             )));
         };
 
-        Ok(input_dt.clone())
+        let timestamps = cast(input_dt, &DataType::Timestamp(TimeUnit::Nanosecond, None))?;
+        let timestamps = downcast_primitive_arg!(&timestamps, "dt", TimestampNanosecondType);
+
+        let mut builder = TimestampNanosecondArray::builder(timestamps.len());
+        for i in 0..timestamps.len() {
+            if timestamps.is_null(i) || from_tz.is_null(i) || to_tz.is_null(i) {
+                builder.append_null()?;
+                continue;
+            }
+
+            let unknown_zone = |zone: &str| {
+                DataFusionError::Execution(format!("convert_tz: unknown time zone '{}'", zone))
+            };
+
+            let nanos = timestamps.value(i);
+            let local = NaiveDateTime::from_timestamp(
+                nanos.div_euclid(1_000_000_000),
+                nanos.rem_euclid(1_000_000_000) as u32,
+            );
+            let (from_zone, to_zone) = (from_tz.value(i), to_tz.value(i));
+            let utc = time_zone_local_to_utc(system_time_zone_as_utc(from_zone), &local)
+                .ok_or_else(|| unknown_zone(from_zone))?;
+            let offset = time_zone_offset_at(system_time_zone_as_utc(to_zone), &utc)
+                .ok_or_else(|| unknown_zone(to_zone))?;
+
+            let result = utc + Duration::seconds(offset.local_minus_utc() as i64);
+            builder.append_value(result.timestamp_nanos())?;
+        }
+
+        let result = Arc::new(builder.finish()) as ArrayRef;
+        Ok(cast(&result, input_dt.data_type())?)
     });
 
     let return_type: ReturnTypeFunction = Arc::new(move |types| {
@@ -999,6 +1024,581 @@ pub fn create_current_schemas_udf() -> ScalarUDF {
     )
 }
 
+fn format_pg_type(oid: i64, typmod: Option<i64>) -> String {
+    let name = match oid {
+        0 => return "-".to_string(),
+        16 => "boolean",
+        17 => "bytea",
+        18 => "\"char\"",
+        19 => "name",
+        20 => "bigint",
+        21 => "smallint",
+        23 => "integer",
+        25 => "text",
+        26 => "oid",
+        700 => "real",
+        701 => "double precision",
+        1000 => "boolean[]",
+        1005 => "smallint[]",
+        1007 => "integer[]",
+        1009 => "text[]",
+        1015 => "character varying[]",
+        1016 => "bigint[]",
+        1021 => "real[]",
+        1022 => "double precision[]",
+        1042 => "character",
+        1043 => "character varying",
+        1082 => "date",
+        1083 => "time without time zone",
+        1114 => "timestamp without time zone",
+        1115 => "timestamp without time zone[]",
+        1182 => "date[]",
+        1184 => "timestamp with time zone",
+        1185 => "timestamp with time zone[]",
+        1186 => "interval",
+        1231 => "numeric[]",
+        1266 => "time with time zone",
+        1700 => "numeric",
+        _ => return "???".to_string(),
+    };
+
+    // Type modifiers are stored with VARHDRSZ (4) added for types with length or precision
+    match (oid, typmod) {
+        (1042 | 1043, Some(typmod)) if typmod >= 4 => format!("{}({})", name, typmod - 4),
+        (1700, Some(typmod)) if typmod >= 4 => {
+            let typmod = typmod - 4;
+            format!("{}({},{})", name, (typmod >> 16) & 0xffff, typmod & 0xffff)
+        }
+        (1114 | 1184, Some(typmod)) if typmod >= 0 => {
+            let (base, zone) = name.split_at(9);
+            format!("{}({}){}", base, typmod, zone)
+        }
+        _ => name.to_string(),
+    }
+}
+
+pub fn create_format_type_udf() -> ScalarUDF {
+    let fun = make_scalar_function(move |args: &[ArrayRef]| {
+        assert!(args.len() == 2);
+
+        let oids = cast(&args[0], &DataType::Int64)?;
+        let oids = downcast_primitive_arg!(oids, "type_oid", Int64Type);
+        let typmods = cast(&args[1], &DataType::Int64)?;
+        let typmods = downcast_primitive_arg!(typmods, "typemod", Int64Type);
+
+        let mut builder = StringBuilder::new(oids.len());
+        for i in 0..oids.len() {
+            if oids.is_null(i) {
+                builder.append_null()?;
+                continue;
+            }
+
+            let typmod = if typmods.is_null(i) {
+                None
+            } else {
+                Some(typmods.value(i))
+            };
+            builder.append_value(format_pg_type(oids.value(i), typmod))?;
+        }
+
+        Ok(Arc::new(builder.finish()) as ArrayRef)
+    });
+
+    let return_type: ReturnTypeFunction = Arc::new(move |_| Ok(Arc::new(DataType::Utf8)));
+
+    ScalarUDF::new(
+        "format_type",
+        &Signature::any(2, Volatility::Immutable),
+        &return_type,
+        &fun,
+    )
+}
+
+// Expressions are stored as text, there are no node trees to decompile
+pub fn create_pg_get_expr_udf() -> ScalarUDF {
+    let fun = make_scalar_function(move |args: &[ArrayRef]| {
+        assert!(args.len() == 2);
+
+        Ok(cast(&args[0], &DataType::Utf8)?)
+    });
+
+    let return_type: ReturnTypeFunction = Arc::new(move |_| Ok(Arc::new(DataType::Utf8)));
+
+    ScalarUDF::new(
+        "pg_get_expr",
+        &Signature::any(2, Volatility::Immutable),
+        &return_type,
+        &fun,
+    )
+}
+
+// Every relation belongs to a schema of the search path
+pub fn create_pg_table_is_visible_udf() -> ScalarUDF {
+    let fun = make_scalar_function(move |args: &[ArrayRef]| {
+        assert!(args.len() == 1);
+
+        let oids = &args[0];
+        let mut builder = BooleanBuilder::new(oids.len());
+        for i in 0..oids.len() {
+            if oids.is_null(i) {
+                builder.append_null()?;
+            } else {
+                builder.append_value(true)?;
+            }
+        }
+
+        Ok(Arc::new(builder.finish()) as ArrayRef)
+    });
+
+    let return_type: ReturnTypeFunction = Arc::new(move |_| Ok(Arc::new(DataType::Boolean)));
+
+    ScalarUDF::new(
+        "pg_table_is_visible",
+        &Signature::any(1, Volatility::Immutable),
+        &return_type,
+        &fun,
+    )
+}
+
+// The SQL API is read only: schemas can be used, but nothing can be created in them
+pub fn create_has_schema_privilege_udf() -> ScalarUDF {
+    let fun = make_scalar_function(move |args: &[ArrayRef]| {
+        // The user argument is optional, and the session user is the only one
+        let (schemas, privileges) = match args.len() {
+            2 => (&args[0], &args[1]),
+            3 => (&args[1], &args[2]),
+            _ => {
+                return Err(DataFusionError::Execution(format!(
+                    "has_schema_privilege expects 2 or 3 arguments, actual: {}",
+                    args.len()
+                )))
+            }
+        };
+        let schemas = downcast_string_arg!(schemas, "schema", i32);
+        let privileges = downcast_string_arg!(privileges, "privilege", i32);
+
+        let mut builder = BooleanBuilder::new(schemas.len());
+        for i in 0..schemas.len() {
+            if schemas.is_null(i) || privileges.is_null(i) {
+                builder.append_null()?;
+                continue;
+            }
+
+            match schemas.value(i) {
+                "public" | "pg_catalog" | "information_schema" => {}
+                schema => {
+                    return Err(DataFusionError::Execution(format!(
+                        "schema \"{}\" does not exist",
+                        schema
+                    )))
+                }
+            };
+
+            let mut granted = false;
+            for privilege in privileges.value(i).split(',') {
+                match privilege.trim().to_uppercase().as_str() {
+                    "USAGE" => granted = true,
+                    "CREATE" => {}
+                    privilege => {
+                        return Err(DataFusionError::Execution(format!(
+                            "unrecognized privilege type: \"{}\"",
+                            privilege
+                        )))
+                    }
+                }
+            }
+            builder.append_value(granted)?;
+        }
+
+        Ok(Arc::new(builder.finish()) as ArrayRef)
+    });
+
+    let return_type: ReturnTypeFunction = Arc::new(move |_| Ok(Arc::new(DataType::Boolean)));
+
+    ScalarUDF::new(
+        "has_schema_privilege",
+        &Signature::variadic(vec![DataType::Utf8], Volatility::Immutable),
+        &return_type,
+        &fun,
+    )
+}
+
+// pg_description is empty, so no object has a comment
+pub fn create_obj_description_udf() -> ScalarUDF {
+    let fun = make_scalar_function(move |args: &[ArrayRef]| {
+        assert!(args.len() == 2);
+
+        let mut builder = StringBuilder::new(args[0].len());
+        for _ in 0..args[0].len() {
+            builder.append_null()?;
+        }
+
+        Ok(Arc::new(builder.finish()) as ArrayRef)
+    });
+
+    let return_type: ReturnTypeFunction = Arc::new(move |_| Ok(Arc::new(DataType::Utf8)));
+
+    ScalarUDF::new(
+        "obj_description",
+        &Signature::any(2, Volatility::Immutable),
+        &return_type,
+        &fun,
+    )
+}
+
+// The session user is the single role of pg_roles, with oid 10
+pub fn create_pg_get_userbyid_udf(state: Arc<SessionState>) -> ScalarUDF {
+    let fun = make_scalar_function(move |args: &[ArrayRef]| {
+        assert!(args.len() == 1);
+
+        let oids = cast(&args[0], &DataType::Int64)?;
+        let oids = downcast_primitive_arg!(oids, "role_oid", Int64Type);
+        let user = state.user().unwrap_or("cube".to_string());
+
+        let mut builder = StringBuilder::new(oids.len());
+        for i in 0..oids.len() {
+            if oids.is_null(i) {
+                builder.append_null()?;
+            } else if oids.value(i) == 10 {
+                builder.append_value(&user)?;
+            } else {
+                builder.append_value(format!("unknown (OID={})", oids.value(i)))?;
+            }
+        }
+
+        Ok(Arc::new(builder.finish()) as ArrayRef)
+    });
+
+    let return_type: ReturnTypeFunction = Arc::new(move |_| Ok(Arc::new(DataType::Utf8)));
+
+    ScalarUDF::new(
+        "pg_get_userbyid",
+        &Signature::any(1, Volatility::Immutable),
+        &return_type,
+        &fun,
+    )
+}
+
+const TO_CHAR_PATTERNS: &[&str] = &[
+    "HH24", "HH12", "HH", "MI", "SS", "MS", "US", "YYYY", "YY", "MONTH", "Month", "month", "MON",
+    "Mon", "mon", "MM", "DAY", "Day", "day", "DY", "Dy", "dy", "DDD", "DD", "D", "Q", "AM", "PM",
+    "am", "pm",
+];
+
+fn format_to_char_pattern(dt: &NaiveDateTime, pattern: &str, fill: bool) -> String {
+    let (number, width) = match pattern {
+        "HH24" => (dt.hour(), 2),
+        "HH12" | "HH" => (dt.hour12().1, 2),
+        "MI" => (dt.minute(), 2),
+        "SS" => (dt.second(), 2),
+        "MS" => (dt.nanosecond() / 1_000_000, 3),
+        "US" => (dt.nanosecond() / 1_000, 6),
+        "YYYY" => (dt.year() as u32, 4),
+        "YY" => (dt.year() as u32 % 100, 2),
+        "MM" => (dt.month(), 2),
+        "DDD" => (dt.ordinal(), 3),
+        "DD" => (dt.day(), 2),
+        "D" => (dt.weekday().number_from_sunday(), 1),
+        "Q" => ((dt.month() - 1) / 3 + 1, 1),
+        _ => {
+            let (text, padded) = match pattern.to_uppercase().as_str() {
+                "MONTH" => (dt.format("%B").to_string(), true),
+                "MON" => (dt.format("%b").to_string(), false),
+                "DAY" => (dt.format("%A").to_string(), true),
+                "DY" => (dt.format("%a").to_string(), false),
+                _ => (dt.format("%p").to_string(), false),
+            };
+            let text = if pattern.chars().all(|c| c.is_uppercase()) {
+                text.to_uppercase()
+            } else if pattern.chars().all(|c| c.is_lowercase()) {
+                text.to_lowercase()
+            } else {
+                text
+            };
+
+            // Full names are blank-padded to the longest one
+            return if padded && fill {
+                format!("{:<9}", text)
+            } else {
+                text
+            };
+        }
+    };
+
+    if fill {
+        format!("{:0width$}", number, width = width)
+    } else {
+        number.to_string()
+    }
+}
+
+fn format_to_char(dt: &NaiveDateTime, format: &str) -> String {
+    let mut result = String::with_capacity(format.len());
+    let mut rest = format;
+
+    while let Some(c) = rest.chars().next() {
+        // Double quoted text is copied as is
+        if c == '"' {
+            let end = rest[1..].find('"').map(|i| i + 1).unwrap_or(rest.len());
+            result.push_str(&rest[1..end]);
+            rest = &rest[(end + 1).min(rest.len())..];
+            continue;
+        }
+
+        // FM prefix suppresses padding of the next pattern
+        let fill = !rest.starts_with("FM");
+        if !fill {
+            rest = &rest[2..];
+        }
+
+        match TO_CHAR_PATTERNS.iter().find(|p| rest.starts_with(*p)) {
+            Some(pattern) => {
+                result.push_str(&format_to_char_pattern(dt, pattern, fill));
+                rest = &rest[pattern.len()..];
+            }
+            None if fill => {
+                result.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+            None => {}
+        }
+    }
+
+    result
+}
+
+pub fn create_to_char_udf() -> ScalarUDF {
+    let fun = make_scalar_function(move |args: &[ArrayRef]| {
+        assert!(args.len() == 2);
+
+        let timestamps = downcast_primitive_arg!(&args[0], "timestamp", TimestampNanosecondType);
+        let formats = downcast_string_arg!(&args[1], "format", i32);
+
+        let mut builder = StringBuilder::new(timestamps.len());
+        for i in 0..timestamps.len() {
+            if timestamps.is_null(i) || formats.is_null(i) {
+                builder.append_null()?;
+                continue;
+            }
+
+            let dt = NaiveDateTime::from_timestamp(
+                timestamps.value(i).div_euclid(1_000_000_000),
+                timestamps.value(i).rem_euclid(1_000_000_000) as u32,
+            );
+            builder.append_value(format_to_char(&dt, formats.value(i)))?;
+        }
+
+        Ok(Arc::new(builder.finish()) as ArrayRef)
+    });
+
+    let return_type: ReturnTypeFunction = Arc::new(move |_| Ok(Arc::new(DataType::Utf8)));
+
+    ScalarUDF::new(
+        "to_char",
+        &Signature::exact(
+            vec![
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                DataType::Utf8,
+            ],
+            Volatility::Immutable,
+        ),
+        &return_type,
+        &fun,
+    )
+}
+
+/// Cube returns time dimensions in UTC, so only UTC aliases can be handled without a tz database.
+pub fn is_utc_time_zone(zone: &str) -> bool {
+    matches!(
+        zone.trim().to_uppercase().as_str(),
+        "UTC" | "GMT" | "Z" | "ETC/UTC" | "ETC/GMT" | "UCT" | "ZULU" | "+00" | "+00:00" | "-00"
+    )
+}
+
+/// Offset of a fixed time zone: UTC aliases, `+03:00`, `-0530`, `+3` or `Etc/GMT-3` (POSIX sign
+/// is inverted). Named zones like `Europe/Berlin` are resolved by `time_zone_offset_at`.
+pub fn fixed_time_zone_offset(zone: &str) -> Option<FixedOffset> {
+    let zone = zone.trim();
+    if is_utc_time_zone(zone) {
+        return Some(FixedOffset::east(0));
+    }
+
+    let upper = zone.to_uppercase();
+    let (sign, offset) = if let Some(offset) = upper.strip_prefix("ETC/GMT") {
+        match offset.chars().next() {
+            Some('+') => (-1, &offset[1..]),
+            Some('-') => (1, &offset[1..]),
+            _ => return None,
+        }
+    } else {
+        match zone.chars().next() {
+            Some('+') => (1, &zone[1..]),
+            Some('-') => (-1, &zone[1..]),
+            _ => return None,
+        }
+    };
+
+    let (hours, minutes) = match offset.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if offset.len() == 4 => offset.split_at(2),
+        None => (offset, "0"),
+    };
+    if hours.is_empty()
+        || hours.len() > 2
+        || minutes.len() > 2
+        || !hours
+            .chars()
+            .chain(minutes.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Validates a time zone set by the client and returns it in the form Cube accepts as the
+/// `timezone` of a load request: an IANA name or `Etc/GMT±N` for whole hour offsets.
+/// `None` stands for UTC, which is also the system time zone of the SQL API.
+pub fn normalize_time_zone(zone: &str) -> Result<Option<String>, String> {
+    let zone = zone.trim();
+    if zone.eq_ignore_ascii_case("SYSTEM")
+        || zone.eq_ignore_ascii_case("DEFAULT")
+        || zone.eq_ignore_ascii_case("LOCAL")
+        || is_utc_time_zone(zone)
+    {
+        return Ok(None);
+    }
+
+    if let Some(offset) = fixed_time_zone_offset(zone) {
+        let seconds = offset.local_minus_utc();
+        return match (seconds / 3600, seconds % 3600) {
+            (0, 0) => Ok(None),
+            // Etc/GMT zones have the inverted POSIX sign
+            (hours, 0) => Ok(Some(format!("Etc/GMT{:+}", -hours))),
+            _ => Err(format!(
+                "Time zone '{}' is not supported, only whole hour offsets can be used",
+                zone
+            )),
+        };
+    }
+
+    match zone.parse::<Tz>() {
+        Ok(tz) => Ok(Some(tz.name().to_string())),
+        Err(_) => Err(format!("Unknown or incorrect time zone: '{}'", zone)),
+    }
+}
+
+/// Cube returns time dimensions in the time zone of the load request, which is the session one,
+/// so `AT TIME ZONE '<zone>'` can be pushed down to Cube only for the session time zone.
+pub fn is_session_time_zone(zone: &str, session_time_zone: &Option<String>) -> bool {
+    match normalize_time_zone(zone) {
+        Ok(zone) => &zone == session_time_zone,
+        Err(_) => false,
+    }
+}
+
+/// SYSTEM time zone of the SQL API is UTC.
+fn system_time_zone_as_utc(zone: &str) -> &str {
+    if zone.trim().eq_ignore_ascii_case("SYSTEM") {
+        "UTC"
+    } else {
+        zone
+    }
+}
+
+/// Offset of a fixed or an IANA time zone at the given UTC time.
+pub fn time_zone_offset_at(zone: &str, utc: &NaiveDateTime) -> Option<FixedOffset> {
+    if let Some(offset) = fixed_time_zone_offset(zone) {
+        return Some(offset);
+    }
+
+    zone.trim()
+        .parse::<Tz>()
+        .ok()
+        .map(|tz| tz.offset_from_utc_datetime(utc).fix())
+}
+
+/// UTC time of a local time in a fixed or an IANA time zone. The earliest time is used for
+/// ambiguous local times, local times skipped by a DST transition are moved forward by an hour.
+pub fn time_zone_local_to_utc(zone: &str, local: &NaiveDateTime) -> Option<NaiveDateTime> {
+    if let Some(offset) = fixed_time_zone_offset(zone) {
+        return Some(*local - Duration::seconds(offset.local_minus_utc() as i64));
+    }
+
+    let tz = zone.trim().parse::<Tz>().ok()?;
+    tz.from_local_datetime(local)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(*local + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|datetime| datetime.naive_utc())
+}
+
+/// Backs `<timestamp> AT TIME ZONE '<zone>'` (see parser), as `timezone(zone, timestamp)` in Postgres.
+/// Timestamps of the SQL API are local time of the session time zone, so the result is the same
+/// moment as the local time in `zone`, as for `timestamptz` in Postgres.
+pub fn create_timezone_udf(state: Arc<SessionState>) -> ScalarUDF {
+    let fun = make_scalar_function(move |args: &[ArrayRef]| {
+        assert!(args.len() == 2);
+
+        let session_time_zone = state.time_zone().unwrap_or_else(|| "UTC".to_string());
+
+        let zones = cast(&args[0], &DataType::Utf8)?;
+        let zones = downcast_string_arg!(&zones, "zone", i32);
+        let timestamps = cast(&args[1], &DataType::Timestamp(TimeUnit::Nanosecond, None))?;
+        let timestamps = downcast_primitive_arg!(&timestamps, "timestamp", TimestampNanosecondType);
+
+        let mut builder = TimestampNanosecondArray::builder(timestamps.len());
+        for i in 0..timestamps.len() {
+            if zones.is_null(i) || timestamps.is_null(i) {
+                builder.append_null()?;
+                continue;
+            }
+
+            let timestamp = timestamps.value(i);
+            let local = NaiveDateTime::from_timestamp(
+                timestamp.div_euclid(1_000_000_000),
+                timestamp.rem_euclid(1_000_000_000) as u32,
+            );
+            let utc = time_zone_local_to_utc(&session_time_zone, &local).ok_or_else(|| {
+                DataFusionError::Execution(format!(
+                    "time zone \"{}\" not recognized",
+                    session_time_zone
+                ))
+            })?;
+            let offset = time_zone_offset_at(zones.value(i), &utc).ok_or_else(|| {
+                DataFusionError::Execution(format!(
+                    "time zone \"{}\" not recognized",
+                    zones.value(i)
+                ))
+            })?;
+
+            builder.append_value(
+                (utc + Duration::seconds(offset.local_minus_utc() as i64)).timestamp_nanos(),
+            )?;
+        }
+
+        Ok(Arc::new(builder.finish()) as ArrayRef)
+    });
+
+    let return_type: ReturnTypeFunction =
+        Arc::new(move |_| Ok(Arc::new(DataType::Timestamp(TimeUnit::Nanosecond, None))));
+
+    ScalarUDF::new(
+        "timezone",
+        &Signature::any(2, Volatility::Immutable),
+        &return_type,
+        &fun,
+    )
+}
+
 pub fn create_measure_udaf() -> AggregateUDF {
     create_udaf(
         "measure",
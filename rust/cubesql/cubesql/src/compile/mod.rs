@@ -1,5 +1,5 @@
 use std::sync::Arc;
-use std::{backtrace::Backtrace, env, fmt};
+use std::{backtrace::Backtrace, convert::TryFrom, env, fmt};
 
 use chrono::{prelude::*, Duration};
 
@@ -21,6 +21,7 @@ use cubeclient::models::{
 
 use crate::sql::database_variables::{DatabaseVariable, DatabaseVariables};
 use crate::sql::session::DatabaseProtocol;
+use crate::sql::statement::StatementTimeZone;
 use crate::sql::{
     dataframe, types::StatusFlags, ColumnFlags, ColumnType, Session, SessionManager, SessionState,
 };
@@ -39,7 +40,7 @@ use self::engine::context::VariablesProvider;
 use self::engine::df::planner::CubeQueryPlanner;
 use self::engine::df::scan::CubeScanNode;
 use self::engine::information_schema::mysql::ext::CubeColumnMySqlExt;
-use self::engine::provider::CubeContext;
+use self::engine::provider::{CubeContext, CUBE_JOIN_FIELD};
 use self::engine::udf::{
     create_connection_id_udf, create_convert_tz_udf, create_current_schema_udf,
     create_current_schemas_udf, create_current_user_udf, create_db_udf, create_if_udf,
@@ -50,11 +51,15 @@ use self::engine::udf::{
 use self::parser::parse_sql_to_statement;
 use crate::compile::engine::udf::{
     create_date_add_udf, create_date_sub_udf, create_date_udf, create_dayofmonth_udf,
-    create_dayofweek_udf, create_dayofyear_udf, create_hour_udf, create_makedate_udf,
-    create_measure_udaf, create_minute_udf, create_quarter_udf, create_second_udf,
-    create_str_to_date, create_year_udf,
+    create_dayofweek_udf, create_dayofyear_udf, create_format_type_udf,
+    create_has_schema_privilege_udf, create_hour_udf, create_makedate_udf, create_measure_udaf,
+    create_minute_udf, create_obj_description_udf, create_pg_get_expr_udf,
+    create_pg_get_userbyid_udf, create_pg_table_is_visible_udf, create_quarter_udf,
+    create_second_udf, create_str_to_date, create_timezone_udf, create_to_char_udf,
+    create_year_udf, normalize_time_zone,
 };
 use crate::compile::rewrite::converter::LogicalPlanToLanguageConverter;
+use crate::compile::rewrite::rewriter::RewriteTrace;
 
 pub mod builder;
 pub mod context;
@@ -110,6 +115,34 @@ fn compile_select_expr(
     builder: &mut QueryBuilder,
     mb_alias: Option<String>,
 ) -> CompilationResult<()> {
+    if let ast::Expr::Extract { field, expr } = expr {
+        let selection = ctx.find_selection_for_extract(field, expr)?;
+        if let Selection::TimeDimension(dimension, granularity) = selection {
+            if let Some(alias) = mb_alias.clone() {
+                ctx.with_alias(
+                    alias,
+                    Selection::TimeDimension(dimension.clone(), granularity.clone()),
+                );
+            };
+
+            // Cube responds with the truncated date, CubeScan hydrates it as a number
+            builder.with_time_dimension(
+                V1LoadRequestQueryTimeDimension {
+                    dimension: dimension.name.clone(),
+                    granularity: Some(granularity.clone()),
+                    date_range: None,
+                },
+                CompiledQueryFieldMeta {
+                    column_from: format!("{}.{}", dimension.name, granularity),
+                    column_to: mb_alias.unwrap_or(dimension.get_real_name()),
+                    column_type: ColumnType::Int64,
+                },
+            );
+        }
+
+        return Ok(());
+    }
+
     let selection =
         ctx.compile_selection_from_projection(expr)?
             .ok_or(CompilationError::Unknown(format!(
@@ -233,6 +266,17 @@ impl CompiledExpression {
         }
     }
 
+    /// Value of a filter. Date literals are local time of the session time zone, Cube applies
+    /// the time zone to them if it's passed with the query.
+    pub fn to_filter_value(&self, ctx: &QueryContext) -> CompilationResult<String> {
+        match &self {
+            CompiledExpression::DateLiteral(date) if ctx.time_zone.is_some() => {
+                Ok(date.format("%Y-%m-%dT%H:%M:%S%.3f").to_string())
+            }
+            _ => self.to_value_as_str(),
+        }
+    }
+
     pub fn to_value_as_str(&self) -> CompilationResult<String> {
         match &self {
             CompiledExpression::BooleanLiteral(v) => Ok(if *v {
@@ -362,7 +406,7 @@ fn date_function(f: &ast::Function, ctx: &QueryContext) -> CompilationResult<Com
     }
 }
 
-fn now_function(f: &ast::Function) -> CompilationResult<CompiledExpression> {
+fn now_function(f: &ast::Function, ctx: &QueryContext) -> CompilationResult<CompiledExpression> {
     if f.args.len() > 1 {
         return Err(CompilationError::User(format!(
             "Unsupported signature for NOW function: {:?}",
@@ -370,7 +414,10 @@ fn now_function(f: &ast::Function) -> CompilationResult<CompiledExpression> {
         )));
     };
 
-    Ok(CompiledExpression::DateLiteral(Utc::now()))
+    // Date literals are local time of the session
+    Ok(CompiledExpression::DateLiteral(
+        Utc::now() + Duration::seconds(ctx.time_zone_offset.local_minus_utc() as i64),
+    ))
 }
 
 fn date_add_function(
@@ -558,7 +605,7 @@ fn compile_expression(
             "str_to_date" => str_to_date_function(&f),
             "date" => date_function(&f, &ctx),
             "date_add" => date_add_function(&f, &ctx),
-            "now" => now_function(&f),
+            "now" => now_function(&f, &ctx),
             _ => Err(CompilationError::User(format!(
                 "Unsupported function: {:?}",
                 f
@@ -623,7 +670,7 @@ fn compiled_binary_op_expr(
             CompiledFilter::Filter {
                 member,
                 operator,
-                values: Some(vec![value.to_value_as_str()?]),
+                values: Some(vec![value.to_filter_value(ctx)?]),
             }
         }
         // Compile to CompiledFilter::Filter
@@ -681,7 +728,7 @@ fn compiled_binary_op_expr(
             CompiledFilter::Filter {
                 member,
                 operator,
-                values: Some(vec![value.to_value_as_str()?]),
+                values: Some(vec![value.to_filter_value(ctx)?]),
             }
         }
         // Compile to CompiledFilter::SegmentFilter (it will be pushed to segments via optimization)
@@ -872,8 +919,8 @@ fn compile_where_expression(
                     "inDateRange".to_string()
                 },
                 values: Some(vec![
-                    low_compiled_date.to_value_as_str()?,
-                    high_compiled_date.to_value_as_str()?,
+                    low_compiled_date.to_filter_value(ctx)?,
+                    high_compiled_date.to_filter_value(ctx)?,
                 ]),
             }))
         }
@@ -926,7 +973,7 @@ fn compile_where_expression(
             }?;
 
             fn compile_value(value: &ast::Expr, ctx: &QueryContext) -> CompilationResult<String> {
-                compile_expression(value, ctx)?.to_value_as_str()
+                compile_expression(value, ctx)?.to_filter_value(ctx)
             }
 
             let values = list
@@ -1367,6 +1414,16 @@ impl QueryPlanner {
         &self,
         stmt: &ast::Statement,
         q: &Box<ast::Query>,
+    ) -> CompilationResult<QueryPlan> {
+        self.select_to_plan_with_trace(stmt, q, &mut RewriteTrace::default())
+    }
+
+    /// Same as `select_to_plan`, `trace` is filled if the query is planned by the rewrite engine
+    fn select_to_plan_with_trace(
+        &self,
+        stmt: &ast::Statement,
+        q: &Box<ast::Query>,
+        trace: &mut RewriteTrace,
     ) -> CompilationResult<QueryPlan> {
         // TODO move CUBESQL_REWRITE_ENGINE env to config
         let rewrite_engine = env::var("CUBESQL_REWRITE_ENGINE")
@@ -1374,7 +1431,7 @@ impl QueryPlanner {
             .map(|v| v.parse::<bool>().unwrap())
             .unwrap_or(false);
         if rewrite_engine {
-            return self.create_df_logical_plan(stmt.clone());
+            return self.create_df_logical_plan_with_trace(stmt.clone(), trace);
         }
 
         let select = match &q.body {
@@ -1389,7 +1446,7 @@ impl QueryPlanner {
         let from_table = if select.from.len() == 1 {
             &select.from[0]
         } else {
-            return self.create_df_logical_plan(stmt.clone());
+            return self.create_df_logical_plan_with_trace(stmt.clone(), trace);
         };
 
         let (schema_name, table_name) = match &from_table.relation {
@@ -1420,13 +1477,11 @@ impl QueryPlanner {
             || schema_name.to_lowercase() == "performance_schema"
             || schema_name.to_lowercase() == "pg_catalog"
         {
-            return self.create_df_logical_plan(stmt.clone());
+            return self.create_df_logical_plan_with_trace(stmt.clone(), trace);
         }
 
         if !select.from[0].joins.is_empty() {
-            return Err(CompilationError::Unsupported(
-                "Query with JOIN instruction(s)".to_string(),
-            ));
+            return self.create_df_logical_plan_with_trace(stmt.clone(), trace);
         }
 
         if q.with.is_some() {
@@ -1483,8 +1538,13 @@ impl QueryPlanner {
 
         if let Some(cube) = self.meta.find_cube_with_name(table_name.clone()) {
             let mut ctx = QueryContext::new(&cube);
+            ctx.with_time_zone(self.state.time_zone(), self.state.time_zone_offset());
             let mut builder = compile_select(select, &mut ctx)?;
 
+            if let Some(time_zone) = &ctx.time_zone {
+                builder.with_timezone(time_zone.clone());
+            }
+
             if let Some(limit_expr) = &q.limit {
                 let limit = limit_expr.to_string().parse::<i32>().map_err(|e| {
                     CompilationError::Unsupported(format!(
@@ -1576,8 +1636,8 @@ impl QueryPlanner {
                     Arc::new(dataframe::DataFrame::new(vec![], vec![])),
                 ))
             }
-            (ast::Statement::Kill { .. }, DatabaseProtocol::MySQL) => {
-                Ok(QueryPlan::MetaOk(StatusFlags::empty()))
+            (ast::Statement::Kill { modifier, id }, DatabaseProtocol::MySQL) => {
+                self.kill_to_plan(modifier, *id)
             }
             // TODO: enable for Postgres after variables are supported
             (ast::Statement::SetVariable { key_values }, _) => {
@@ -1614,7 +1674,12 @@ impl QueryPlanner {
             (ast::Statement::ExplainTable { table_name, .. }, DatabaseProtocol::MySQL) => {
                 self.explain_table_to_plan(&table_name)
             }
-            (ast::Statement::Explain { statement, .. }, _) => self.explain_to_plan(&statement),
+            (
+                ast::Statement::Explain {
+                    statement, verbose, ..
+                },
+                _,
+            ) => self.explain_to_plan(&statement, *verbose),
             (ast::Statement::Use { db_name }, DatabaseProtocol::MySQL) => {
                 self.use_to_plan(&db_name)
             }
@@ -1977,7 +2042,12 @@ WHERE `TABLE_SCHEMA` = '{}'",
     fn explain_to_plan(
         &self,
         statement: &Box<ast::Statement>,
+        verbose: bool,
     ) -> Result<QueryPlan, CompilationError> {
+        if verbose {
+            return self.explain_verbose_to_plan(statement);
+        }
+
         let plan = self.plan(&statement)?;
 
         return Ok(QueryPlan::MetaTabular(
@@ -1996,12 +2066,126 @@ WHERE `TABLE_SCHEMA` = '{}'",
         ));
     }
 
+    /// EXPLAIN VERBOSE shows how the statement was pushed down to Cube: the plan that will be
+    /// executed, the resulting Cube queries and, if the statement was planned by the rewrite
+    /// engine, the applied rewrites, the extracted `CubeScan` nodes and expressions that
+    /// couldn't be rewritten to Cube members. The trace is collected while planning, so the
+    /// statement is planned once. Planning errors are reported as rows so the remaining
+    /// diagnostics are still shown.
+    fn explain_verbose_to_plan(
+        &self,
+        statement: &Box<ast::Statement>,
+    ) -> Result<QueryPlan, CompilationError> {
+        let mut rows = Vec::new();
+
+        let mut trace = RewriteTrace::default();
+        let plan = match statement.as_ref() {
+            ast::Statement::Query(q) => self.select_to_plan_with_trace(&statement, q, &mut trace),
+            _ => self.plan(&statement),
+        };
+        match &plan {
+            Ok(plan) => {
+                rows.push((
+                    "logical_plan",
+                    plan.print(true)
+                        .map_err(|error| CompilationError::Internal(error.message))?,
+                ));
+                if let QueryPlan::DataFusionSelect(_, plan, _) = plan {
+                    for request in find_cube_scan_requests(plan) {
+                        rows.push((
+                            "cube_query",
+                            serde_json::to_string_pretty(&request)
+                                .map_err(|error| CompilationError::Internal(error.to_string()))?,
+                        ));
+                    }
+                }
+            }
+            Err(error) => rows.push(("logical_plan_error", error.to_string())),
+        }
+
+        // Queries compiled by the legacy compiler don't go through the rewrite engine
+        if !trace.iterations.is_empty() {
+            rows.push(("rewrite_iterations", trace.iterations.join("\n")));
+            if let Some(stop_reason) = trace.stop_reason {
+                rows.push(("rewrite_stop_reason", stop_reason));
+            }
+            for cube_scan in trace.cube_scans {
+                rows.push(("rewrite_cube_scan", cube_scan));
+            }
+            if !trace.unmatched_exprs.is_empty() {
+                rows.push((
+                    "rewrite_unmatched_expressions",
+                    trace.unmatched_exprs.join("\n"),
+                ));
+            }
+        }
+
+        Ok(QueryPlan::MetaTabular(
+            StatusFlags::empty(),
+            Arc::new(dataframe::DataFrame::new(
+                vec![
+                    dataframe::Column::new(
+                        "plan_type".to_string(),
+                        ColumnType::String,
+                        ColumnFlags::empty(),
+                    ),
+                    dataframe::Column::new(
+                        "plan".to_string(),
+                        ColumnType::String,
+                        ColumnFlags::empty(),
+                    ),
+                ],
+                rows.into_iter()
+                    .map(|(plan_type, plan)| {
+                        dataframe::Row::new(vec![
+                            dataframe::TableValue::String(plan_type.to_string()),
+                            dataframe::TableValue::String(plan),
+                        ])
+                    })
+                    .collect(),
+            )),
+        ))
+    }
+
     fn use_to_plan(&self, db_name: &ast::Ident) -> Result<QueryPlan, CompilationError> {
         self.state.set_database(Some(db_name.value.clone()));
 
         Ok(QueryPlan::MetaOk(StatusFlags::empty()))
     }
 
+    /// `KILL QUERY <id>` cancels the query of the session, its id is listed in
+    /// `information_schema.processlist`. `KILL [CONNECTION] <id>` also closes its connection.
+    fn kill_to_plan(
+        &self,
+        modifier: &Option<ast::KillType>,
+        id: u64,
+    ) -> Result<QueryPlan, CompilationError> {
+        let session = u32::try_from(id)
+            .ok()
+            .and_then(|id| self.session_manager.get_session(id))
+            .ok_or_else(|| CompilationError::User(format!("Unknown thread id: {}", id)))?;
+
+        if session.state.user() != self.state.user() {
+            return Err(CompilationError::User(format!(
+                "You are not owner of thread {}",
+                id
+            )));
+        }
+
+        match modifier {
+            Some(ast::KillType::Query) => session.state.cancel_query(),
+            Some(ast::KillType::Connection) | None => session.state.close_connection(),
+            Some(modifier) => {
+                return Err(CompilationError::Unsupported(format!(
+                    "KILL {} is not supported",
+                    modifier
+                )))
+            }
+        }
+
+        Ok(QueryPlan::MetaOk(StatusFlags::empty()))
+    }
+
     fn set_variable_to_plan(
         &self,
         key_values: &Vec<ast::SetVariableKeyValue>,
@@ -2011,6 +2195,18 @@ WHERE `TABLE_SCHEMA` = '{}'",
         let mut session_columns_to_update: DatabaseVariables = DatabaseVariables::new();
         let mut global_columns_to_update: DatabaseVariables = DatabaseVariables::new();
 
+        let mut key_values_to_update = Vec::new();
+        for key_value in key_values.iter() {
+            if key_value.key.value.to_lowercase() == "user" {
+                self.switch_user(&key_value.value[0])?;
+            } else if self.is_time_zone_variable(&key_value.key.value) {
+                self.set_time_zone(&key_value.value[0])?;
+            } else {
+                key_values_to_update.push(key_value);
+            }
+        }
+        let key_values = key_values_to_update;
+
         match self.state.protocol {
             DatabaseProtocol::PostgreSQL => {
                 for key_value in key_values.iter() {
@@ -2135,11 +2331,100 @@ WHERE `TABLE_SCHEMA` = '{}'",
         ))
     }
 
+    fn is_time_zone_variable(&self, key: &str) -> bool {
+        match self.state.protocol {
+            DatabaseProtocol::MySQL => matches!(
+                key.to_lowercase().as_str(),
+                "time_zone" | "@@time_zone" | "@@session.time_zone" | "@@local.time_zone"
+            ),
+            DatabaseProtocol::PostgreSQL => key.eq_ignore_ascii_case("timezone"),
+        }
+    }
+
+    /// Time zone is a session variable, unlike other variables it's not shared between
+    /// connections. It's passed to Cube with every query of the session.
+    fn set_time_zone(&self, value: &ast::Expr) -> Result<(), CompilationError> {
+        let zone = match value {
+            ast::Expr::Identifier(ident) => ident.value.to_string(),
+            ast::Expr::Value(ast::Value::SingleQuotedString(zone)) => zone.to_string(),
+            ast::Expr::Value(ast::Value::DoubleQuotedString(zone)) => zone.to_string(),
+            ast::Expr::Value(ast::Value::Number(hours, _)) => format!("+{}", hours),
+            ast::Expr::UnaryOp {
+                op: ast::UnaryOperator::Minus,
+                expr,
+            } => match expr.as_ref() {
+                ast::Expr::Value(ast::Value::Number(hours, _)) => format!("-{}", hours),
+                _ => {
+                    return Err(CompilationError::User(
+                        "invalid time zone format".to_string(),
+                    ))
+                }
+            },
+            _ => {
+                return Err(CompilationError::User(
+                    "invalid time zone format".to_string(),
+                ))
+            }
+        };
+
+        normalize_time_zone(&zone).map_err(CompilationError::User)?;
+
+        let name = self.state.time_zone_variable().to_string();
+        let mut variables = DatabaseVariables::new();
+        variables.insert(
+            name.clone(),
+            DatabaseVariable::system(name, ScalarValue::Utf8(Some(zone)), None),
+        );
+        self.state.set_variables(variables);
+
+        Ok(())
+    }
+
+    /// Switches session to another user by `SET user = '...'`. Auth context of the new user is
+    /// requested before the next query.
+    fn switch_user(&self, value: &ast::Expr) -> Result<(), CompilationError> {
+        let user = match value {
+            ast::Expr::Identifier(ident) => ident.value.to_string(),
+            ast::Expr::Value(ast::Value::SingleQuotedString(user)) => user.to_string(),
+            ast::Expr::Value(ast::Value::DoubleQuotedString(user)) => user.to_string(),
+            _ => {
+                return Err(CompilationError::User(
+                    "invalid user variable format".to_string(),
+                ))
+            }
+        };
+
+        let current_user = self.state.user();
+        if current_user.as_ref() == Some(&user) {
+            return Ok(());
+        }
+
+        let can_switch_user = self
+            .state
+            .auth_context()
+            .map(|ctx| ctx.can_switch_user)
+            .unwrap_or(false);
+        if !can_switch_user {
+            return Err(CompilationError::User(format!(
+                "user '{}' is not allowed to switch to '{}'",
+                current_user.unwrap_or_default(),
+                user
+            )));
+        }
+
+        self.state.set_user(Some(user));
+        self.state.set_auth_context(None);
+
+        Ok(())
+    }
+
     fn create_execution_ctx(&self) -> ExecutionContext {
         let mut ctx = ExecutionContext::with_config(
             ExecutionConfig::new()
                 .with_query_planner(Arc::new(CubeQueryPlanner::new(
                     self.session_manager.server.transport.clone(),
+                    self.state.clone(),
+                    self.session_manager.server.configuration.query_timeout,
                 )))
                 .with_information_schema(false),
         );
@@ -2186,6 +2471,14 @@ WHERE `TABLE_SCHEMA` = '{}'",
         ctx.register_udf(create_str_to_date());
         ctx.register_udf(create_current_schema_udf());
         ctx.register_udf(create_current_schemas_udf());
+        ctx.register_udf(create_format_type_udf());
+        ctx.register_udf(create_pg_get_expr_udf());
+        ctx.register_udf(create_pg_table_is_visible_udf());
+        ctx.register_udf(create_has_schema_privilege_udf());
+        ctx.register_udf(create_obj_description_udf());
+        ctx.register_udf(create_pg_get_userbyid_udf(self.state.clone()));
+        ctx.register_udf(create_to_char_udf());
+        ctx.register_udf(create_timezone_udf(self.state.clone()));
 
         ctx.register_udaf(create_measure_udaf());
 
@@ -2193,6 +2486,18 @@ WHERE `TABLE_SCHEMA` = '{}'",
     }
 
     fn create_df_logical_plan(&self, stmt: ast::Statement) -> CompilationResult<QueryPlan> {
+        self.create_df_logical_plan_with_trace(stmt, &mut RewriteTrace::default())
+    }
+
+    fn create_df_logical_plan_with_trace(
+        &self,
+        stmt: ast::Statement,
+        trace: &mut RewriteTrace,
+    ) -> CompilationResult<QueryPlan> {
+        let mut stmt = stmt;
+        StatementTimeZone::new(self.state.time_zone_offset()).apply(&mut stmt);
+        let hide_join_field = selects_wildcard_only(&stmt);
+
         let ctx = self.create_execution_ctx();
 
         let state = Arc::new(ctx.state.lock().unwrap().clone());
@@ -2209,6 +2514,11 @@ WHERE `TABLE_SCHEMA` = '{}'",
             .map_err(|err| {
                 CompilationError::Internal(format!("Initial planning error: {}", err))
             })?;
+        let plan = if hide_join_field {
+            hide_cube_join_field(plan)?
+        } else {
+            plan
+        };
 
         let optimized_plan = plan;
         // ctx.optimize(&plan).map_err(|err| {
@@ -2221,7 +2531,7 @@ WHERE `TABLE_SCHEMA` = '{}'",
             .map_err(|e| CompilationError::User(e.to_string()))?;
         let rewrite_plan = converter
             .take_rewriter()
-            .find_best_plan(root, Arc::new(self.state.auth_context().unwrap()))
+            .find_best_plan_with_trace(root, Arc::new(self.state.auth_context().unwrap()), trace)
             .map_err(|e| CompilationError::User(e.to_string()))?; // TODO error
 
         log::debug!("Rewrite: {:#?}", rewrite_plan);
@@ -2234,6 +2544,81 @@ WHERE `TABLE_SCHEMA` = '{}'",
     }
 }
 
+/// True if the result columns of the query come from `*` and none of them names the join field.
+fn selects_wildcard_only(stmt: &ast::Statement) -> bool {
+    let select = match stmt {
+        ast::Statement::Query(query) => match &query.body {
+            ast::SetExpr::Select(select) => select,
+            _ => return false,
+        },
+        _ => return false,
+    };
+
+    let is_wildcard = |item: &ast::SelectItem| match item {
+        ast::SelectItem::Wildcard | ast::SelectItem::QualifiedWildcard(_) => true,
+        _ => false,
+    };
+    select.projection.iter().any(is_wildcard)
+        && !select.projection.iter().any(|item| {
+            !is_wildcard(item)
+                && item
+                    .to_string()
+                    .to_lowercase()
+                    .contains(&CUBE_JOIN_FIELD.to_lowercase())
+        })
+}
+
+/// DataFusion expands `*` to all fields of cube tables. The join field is only meant for join
+/// conditions, so it's removed from the result columns.
+fn hide_cube_join_field(plan: LogicalPlan) -> CompilationResult<LogicalPlan> {
+    Ok(match plan {
+        LogicalPlan::Projection {
+            expr,
+            input,
+            schema,
+            alias,
+        } => {
+            let (expr, fields): (Vec<_>, Vec<_>) = expr
+                .into_iter()
+                .zip(schema.fields().iter().cloned())
+                .filter(|(_, field)| !field.name().eq_ignore_ascii_case(CUBE_JOIN_FIELD))
+                .unzip();
+            let schema = DFSchema::new(fields).map_err(|err| {
+                CompilationError::Internal(format!("Initial planning error: {}", err))
+            })?;
+
+            LogicalPlan::Projection {
+                expr,
+                input,
+                schema: DFSchemaRef::new(schema),
+                alias,
+            }
+        }
+        LogicalPlan::Sort { expr, input } => LogicalPlan::Sort {
+            expr,
+            input: Arc::new(hide_cube_join_field(input.as_ref().clone())?),
+        },
+        LogicalPlan::Limit { n, input } => LogicalPlan::Limit {
+            n,
+            input: Arc::new(hide_cube_join_field(input.as_ref().clone())?),
+        },
+        plan => plan,
+    })
+}
+
+fn find_cube_scan_requests(plan: &LogicalPlan) -> Vec<V1LoadRequestQuery> {
+    if let LogicalPlan::Extension { node } = plan {
+        if let Some(cube_scan) = node.as_any().downcast_ref::<CubeScanNode>() {
+            return vec![cube_scan.request.clone()];
+        }
+    }
+
+    plan.inputs()
+        .into_iter()
+        .flat_map(find_cube_scan_requests)
+        .collect()
+}
+
 pub fn convert_statement_to_cube_query(
     stmt: &ast::Statement,
     meta: Arc<MetaContext>,
@@ -2337,7 +2722,7 @@ impl QueryPlan {
         match self {
             QueryPlan::DataFusionSelect(_, plan, _) => {
                 if pretty {
-                    Ok(plan.display_indent().to_string())
+                    Ok(display_indent_with_post_processing(plan))
                 } else {
                     Ok(plan.display().to_string())
                 }
@@ -2350,6 +2735,56 @@ impl QueryPlan {
     }
 }
 
+/// Renders the plan like `LogicalPlan::display_indent` but marks nodes that compute over the data
+/// loaded by `CubeScan` in DataFusion. Projections which only select or rename columns aren't marked.
+fn display_indent_with_post_processing(plan: &LogicalPlan) -> String {
+    fn is_cube_scan(plan: &LogicalPlan) -> bool {
+        match plan {
+            LogicalPlan::Extension { node } => {
+                node.as_any().downcast_ref::<CubeScanNode>().is_some()
+            }
+            _ => false,
+        }
+    }
+
+    fn contains_cube_scan(plan: &LogicalPlan) -> bool {
+        is_cube_scan(plan) || plan.inputs().into_iter().any(contains_cube_scan)
+    }
+
+    fn is_computing(plan: &LogicalPlan) -> bool {
+        match plan {
+            LogicalPlan::Projection { expr, .. } => expr.iter().any(|e| match e {
+                Expr::Column(_) => false,
+                Expr::Alias(e, _) => match e.as_ref() {
+                    Expr::Column(_) => false,
+                    _ => true,
+                },
+                _ => true,
+            }),
+            LogicalPlan::Extension { .. }
+            | LogicalPlan::TableScan { .. }
+            | LogicalPlan::EmptyRelation { .. } => false,
+            _ => true,
+        }
+    }
+
+    fn display_node(plan: &LogicalPlan, indent: usize, lines: &mut Vec<String>) {
+        let mut line = format!("{:indent$}{}", "", plan.display(), indent = indent * 2);
+        if is_computing(plan) && contains_cube_scan(plan) {
+            line.push_str(" [post-processing]");
+        }
+        lines.push(line);
+
+        for input in plan.inputs() {
+            display_node(input, indent + 1, lines);
+        }
+    }
+
+    let mut lines = Vec::new();
+    display_node(plan, 0, &mut lines);
+    lines.join("\n")
+}
+
 pub fn convert_sql_to_cube_query(
     query: &String,
     meta: Arc<MetaContext>,
@@ -2370,6 +2805,7 @@ mod tests {
 
     use super::*;
     use crate::{
+        compile::engine::df::scan::{MEASURE_DECIMAL_PRECISION, MEASURE_DECIMAL_SCALE},
         sql::{
             dataframe::batch_to_dataframe, server_manager::ServerConfiguration, types::StatusFlags,
             AuthContext, AuthenticateResponse, ServerManager, SqlAuthService,
@@ -2379,6 +2815,7 @@ mod tests {
     use datafusion::logical_plan::PlanVisitor;
     use log::Level;
     use simple_logger::SimpleLogger;
+    use std::collections::HashMap;
 
     fn init_logger() {
         let log_level = Level::Trace;
@@ -2395,6 +2832,7 @@ mod tests {
             V1CubeMeta {
                 name: "KibanaSampleDataEcommerce".to_string(),
                 title: None,
+                connected_component: Some(1),
                 dimensions: vec![
                     V1CubeMetaDimension {
                         name: "KibanaSampleDataEcommerce.order_date".to_string(),
@@ -2451,6 +2889,7 @@ mod tests {
             V1CubeMeta {
                 name: "Logs".to_string(),
                 title: None,
+                connected_component: Some(1),
                 dimensions: vec![],
                 measures: vec![
                     V1CubeMetaMeasure {
@@ -2492,6 +2931,8 @@ mod tests {
         session.state.set_auth_context(Some(AuthContext {
             access_token: "access_token".to_string(),
             base_path: "base_path".to_string(),
+            security_context: None,
+            can_switch_user: false,
         }));
 
         session
@@ -2506,11 +2947,14 @@ mod tests {
             async fn authenticate(
                 &self,
                 _user: Option<String>,
+                _options: HashMap<String, String>,
             ) -> Result<AuthenticateResponse, CubeError> {
                 Ok(AuthenticateResponse {
                     context: AuthContext {
                         access_token: "fake".to_string(),
                         base_path: "fake".to_string(),
+                        security_context: None,
+                        can_switch_user: false,
                     },
                     password: None,
                 })
@@ -2550,6 +2994,26 @@ mod tests {
         query.unwrap()
     }
 
+    fn rewrite_select_to_query_plan(query: String, db: DatabaseProtocol) -> QueryPlan {
+        rewrite_select_to_query_plan_with_meta(query, db, get_test_meta()).unwrap()
+    }
+
+    fn rewrite_select_to_query_plan_with_meta(
+        query: String,
+        db: DatabaseProtocol,
+        meta: Vec<V1CubeMeta>,
+    ) -> CompilationResult<QueryPlan> {
+        let session = get_test_session(db.clone());
+        let stmt = parse_sql_to_statement(&query, db).unwrap();
+        let planner = QueryPlanner::new(
+            session.state.clone(),
+            Arc::new(MetaContext::new(meta)),
+            session.session_manager.clone(),
+        );
+
+        planner.create_df_logical_plan(stmt)
+    }
+
     fn find_cube_scan_deep_search(parent: Arc<LogicalPlan>) -> CubeScanNode {
         pub struct FindCubeScanNodeVisitor(Option<CubeScanNode>);
 
@@ -2591,104 +3055,519 @@ mod tests {
     }
 
     #[test]
-    fn test_select_measure_via_function() {
-        let query_plan = convert_select_to_query_plan(
-            "SELECT MEASURE(maxPrice), MEASURE(minPrice), MEASURE(avgPrice) FROM KibanaSampleDataEcommerce".to_string(),
-        DatabaseProtocol::MySQL);
+    fn test_post_processing_projection() {
+        let query_plan = rewrite_select_to_query_plan(
+            "SELECT CONCAT(customer_gender, '-') AS gender, maxPrice / 2 AS half_price FROM KibanaSampleDataEcommerce".to_string(),
+            DatabaseProtocol::PostgreSQL,
+        );
+
+        assert!(query_plan
+            .print(true)
+            .unwrap()
+            .contains("[post-processing]"));
 
         let logical_plan = query_plan.as_logical_plan();
         assert_eq!(
             logical_plan.find_cube_scan().request,
             V1LoadRequestQuery {
-                measures: Some(vec![
-                    "KibanaSampleDataEcommerce.maxPrice".to_string(),
-                    "KibanaSampleDataEcommerce.minPrice".to_string(),
-                    "KibanaSampleDataEcommerce.avgPrice".to_string(),
-                ]),
+                measures: Some(vec!["KibanaSampleDataEcommerce.maxPrice".to_string()]),
+                dimensions: Some(vec!["KibanaSampleDataEcommerce.customer_gender".to_string()]),
                 segments: Some(vec![]),
-                dimensions: Some(vec![]),
                 time_dimensions: None,
                 order: None,
                 limit: None,
                 offset: None,
-                filters: None
+                filters: None,
+                timezone: None,
             }
         );
+        assert_eq!(
+            logical_plan
+                .find_projection_schema()
+                .fields()
+                .iter()
+                .map(|f| f.name().to_string())
+                .collect::<Vec<_>>(),
+            vec!["gender".to_string(), "half_price".to_string()]
+        );
     }
 
     #[test]
-    fn test_select_compound_identifiers() {
-        let query_plan = convert_select_to_query_plan(
-            "SELECT MEASURE(`KibanaSampleDataEcommerce`.`maxPrice`) AS maxPrice, MEASURE(`KibanaSampleDataEcommerce`.`minPrice`) AS minPrice FROM KibanaSampleDataEcommerce".to_string(), DatabaseProtocol::MySQL
+    fn test_post_processing_filter() {
+        let query_plan = rewrite_select_to_query_plan(
+            "SELECT customer_gender FROM KibanaSampleDataEcommerce WHERE LOWER(customer_gender) = 'female'".to_string(),
+            DatabaseProtocol::PostgreSQL,
         );
 
         let logical_plan = query_plan.as_logical_plan();
+        match &logical_plan {
+            LogicalPlan::Projection { input, .. } => match input.as_ref() {
+                LogicalPlan::Filter { .. } => (),
+                x => panic!("Expected filter evaluated by DataFusion but found {:?}", x),
+            },
+            x => panic!("Expected projection but found {:?}", x),
+        }
         assert_eq!(
             logical_plan.find_cube_scan().request,
             V1LoadRequestQuery {
-                measures: Some(vec![
-                    "KibanaSampleDataEcommerce.maxPrice".to_string(),
-                    "KibanaSampleDataEcommerce.minPrice".to_string(),
-                ]),
+                measures: Some(vec![]),
+                dimensions: Some(vec!["KibanaSampleDataEcommerce.customer_gender".to_string()]),
                 segments: Some(vec![]),
-                dimensions: Some(vec![]),
                 time_dimensions: None,
                 order: None,
                 limit: None,
                 offset: None,
-                filters: None
+                filters: None,
+                timezone: None,
             }
         );
     }
 
     #[test]
-    fn test_select_measure_aggregate_functions() {
-        let query_plan = convert_select_to_query_plan(
-            "SELECT MAX(maxPrice), MIN(minPrice), AVG(avgPrice) FROM KibanaSampleDataEcommerce"
-                .to_string(),
-            DatabaseProtocol::MySQL,
+    fn test_post_processing_filter_by_projected_dimension() {
+        let query_plan = rewrite_select_to_query_plan(
+            "SELECT customer_gender, maxPrice FROM KibanaSampleDataEcommerce WHERE LENGTH(customer_gender) > 3".to_string(),
+            DatabaseProtocol::PostgreSQL,
         );
 
+        let logical_plan = query_plan.as_logical_plan();
         assert_eq!(
-            query_plan.as_logical_plan().find_cube_scan().request,
+            logical_plan.find_cube_scan().request,
             V1LoadRequestQuery {
-                measures: Some(vec![
-                    "KibanaSampleDataEcommerce.maxPrice".to_string(),
-                    "KibanaSampleDataEcommerce.minPrice".to_string(),
-                    "KibanaSampleDataEcommerce.avgPrice".to_string(),
-                ]),
+                measures: Some(vec!["KibanaSampleDataEcommerce.maxPrice".to_string()]),
+                dimensions: Some(vec!["KibanaSampleDataEcommerce.customer_gender".to_string()]),
                 segments: Some(vec![]),
-                dimensions: Some(vec![]),
                 time_dimensions: None,
                 order: None,
                 limit: None,
                 offset: None,
-                filters: None
+                filters: None,
+                timezone: None,
             }
-        )
+        );
     }
 
     #[test]
-    fn test_order_alias_for_measure_default() {
-        let query_plan = convert_select_to_query_plan(
-            "SELECT COUNT(*) as cnt FROM KibanaSampleDataEcommerce ORDER BY cnt".to_string(),
-            DatabaseProtocol::MySQL,
+    fn test_post_processing_filter_changing_measure_grain() {
+        // Requesting customer_gender would return maxPrice per gender instead of a single row
+        let result = rewrite_select_to_query_plan_with_meta(
+            "SELECT maxPrice FROM KibanaSampleDataEcommerce WHERE LENGTH(customer_gender) > 3"
+                .to_string(),
+            DatabaseProtocol::PostgreSQL,
+            get_test_meta(),
         );
 
-        assert_eq!(
-            query_plan.as_logical_plan().find_cube_scan().request,
-            V1LoadRequestQuery {
-                measures: Some(vec!["KibanaSampleDataEcommerce.count".to_string(),]),
-                segments: Some(vec![]),
-                dimensions: Some(vec![]),
-                time_dimensions: None,
-                order: Some(vec![vec![
-                    "KibanaSampleDataEcommerce.count".to_string(),
-                    "asc".to_string(),
-                ]]),
-                limit: None,
-                offset: None,
-                filters: None
+        assert!(result.is_err());
+    }
+
+    fn post_processing_lines(query_plan: &QueryPlan) -> Vec<String> {
+        query_plan
+            .print(true)
+            .unwrap()
+            .lines()
+            .filter(|line| line.ends_with("[post-processing]"))
+            .map(|line| line.trim().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_post_processing_not_marked_for_aliases() {
+        let query_plan = rewrite_select_to_query_plan(
+            "SELECT customer_gender AS gender, maxPrice AS price FROM KibanaSampleDataEcommerce"
+                .to_string(),
+            DatabaseProtocol::PostgreSQL,
+        );
+
+        assert_eq!(post_processing_lines(&query_plan), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_post_processing_projection_over_aggregate() {
+        let query_plan = rewrite_select_to_query_plan(
+            "SELECT SUM(count) / MAX(maxPrice) AS ratio FROM KibanaSampleDataEcommerce".to_string(),
+            DatabaseProtocol::PostgreSQL,
+        );
+
+        let lines = post_processing_lines(&query_plan);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("Projection:"));
+
+        let logical_plan = query_plan.as_logical_plan();
+        assert_eq!(
+            logical_plan.find_cube_scan().request,
+            V1LoadRequestQuery {
+                measures: Some(vec![
+                    "KibanaSampleDataEcommerce.count".to_string(),
+                    "KibanaSampleDataEcommerce.maxPrice".to_string(),
+                ]),
+                dimensions: Some(vec![]),
+                segments: Some(vec![]),
+                time_dimensions: None,
+                order: None,
+                limit: None,
+                offset: None,
+                filters: None,
+                timezone: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_post_processing_sort() {
+        let query_plan = rewrite_select_to_query_plan(
+            "SELECT customer_gender FROM KibanaSampleDataEcommerce ORDER BY CONCAT(customer_gender, '-') DESC".to_string(),
+            DatabaseProtocol::PostgreSQL,
+        );
+
+        let lines = post_processing_lines(&query_plan);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("Sort:"));
+
+        let logical_plan = query_plan.as_logical_plan();
+        assert_eq!(
+            logical_plan.find_cube_scan().request,
+            V1LoadRequestQuery {
+                measures: Some(vec![]),
+                dimensions: Some(vec!["KibanaSampleDataEcommerce.customer_gender".to_string()]),
+                segments: Some(vec![]),
+                time_dimensions: None,
+                order: None,
+                limit: None,
+                offset: None,
+                filters: None,
+                timezone: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_post_processing_aggregate() {
+        let query_plan = rewrite_select_to_query_plan(
+            "SELECT LOWER(customer_gender) AS gender, COUNT(DISTINCT customer_gender) AS cnt FROM KibanaSampleDataEcommerce GROUP BY LOWER(customer_gender)".to_string(),
+            DatabaseProtocol::PostgreSQL,
+        );
+
+        assert!(post_processing_lines(&query_plan)
+            .iter()
+            .any(|line| line.starts_with("Aggregate:")));
+
+        let logical_plan = query_plan.as_logical_plan();
+        assert_eq!(
+            logical_plan.find_cube_scan().request,
+            V1LoadRequestQuery {
+                measures: Some(vec![]),
+                dimensions: Some(vec!["KibanaSampleDataEcommerce.customer_gender".to_string()]),
+                segments: Some(vec![]),
+                time_dimensions: None,
+                order: None,
+                limit: None,
+                offset: None,
+                filters: None,
+                timezone: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_measure_types_rewrite() {
+        let logical_plan = rewrite_select_to_query_plan(
+            "SELECT SUM(count), MAX(maxPrice), AVG(avgPrice) FROM KibanaSampleDataEcommerce"
+                .to_string(),
+            DatabaseProtocol::PostgreSQL,
+        )
+        .as_logical_plan();
+
+        let cube_scan = logical_plan.find_cube_scan();
+        assert_eq!(
+            cube_scan.request.measures,
+            Some(vec![
+                "KibanaSampleDataEcommerce.count".to_string(),
+                "KibanaSampleDataEcommerce.maxPrice".to_string(),
+                "KibanaSampleDataEcommerce.avgPrice".to_string(),
+            ])
+        );
+        assert_eq!(
+            cube_scan
+                .schema
+                .fields()
+                .iter()
+                .map(|f| f.data_type().clone())
+                .collect::<Vec<_>>(),
+            vec![DataType::Int64, DataType::Float64, DataType::Float64]
+        );
+
+        let mut meta = get_test_meta();
+        meta[0].measures.push(V1CubeMetaMeasure {
+            name: "KibanaSampleDataEcommerce.sumPrice".to_string(),
+            title: None,
+            _type: "number".to_string(),
+            agg_type: Some("sum".to_string()),
+        });
+
+        let logical_plan = rewrite_select_to_query_plan_with_meta(
+            "SELECT AVG(count), SUM(sumPrice) FROM KibanaSampleDataEcommerce".to_string(),
+            DatabaseProtocol::PostgreSQL,
+            meta,
+        )
+        .unwrap()
+        .as_logical_plan();
+
+        let cube_scan = logical_plan.find_cube_scan();
+        assert_eq!(
+            cube_scan.request.measures,
+            Some(vec![
+                "KibanaSampleDataEcommerce.count".to_string(),
+                "KibanaSampleDataEcommerce.sumPrice".to_string(),
+            ])
+        );
+        assert_eq!(
+            cube_scan
+                .schema
+                .fields()
+                .iter()
+                .map(|f| f.data_type().clone())
+                .collect::<Vec<_>>(),
+            vec![
+                DataType::Int64,
+                DataType::Decimal(MEASURE_DECIMAL_PRECISION, MEASURE_DECIMAL_SCALE)
+            ]
+        );
+
+        let result = rewrite_select_to_query_plan_with_meta(
+            "SELECT SUM(avgPrice) FROM KibanaSampleDataEcommerce".to_string(),
+            DatabaseProtocol::PostgreSQL,
+            get_test_meta(),
+        );
+
+        match result {
+            Err(CompilationError::User(message)) => assert_eq!(
+                message,
+                "Measure aggregation type doesn't match. The aggregation type for 'avgPrice' is 'AVG()' but 'SUM()' was provided"
+            ),
+            _ => panic!("Expected measure aggregation type error"),
+        }
+    }
+
+    #[test]
+    fn test_join_cubes() {
+        let query_plan = rewrite_select_to_query_plan(
+            "SELECT k.customer_gender, MEASURE(l.agentCount) FROM KibanaSampleDataEcommerce k LEFT JOIN Logs l ON k.__cubeJoinField = l.__cubeJoinField GROUP BY k.customer_gender".to_string(),
+            DatabaseProtocol::PostgreSQL,
+        );
+
+        let logical_plan = query_plan.as_logical_plan();
+        assert_eq!(
+            logical_plan.find_cube_scan().request,
+            V1LoadRequestQuery {
+                measures: Some(vec!["Logs.agentCount".to_string()]),
+                dimensions: Some(vec!["KibanaSampleDataEcommerce.customer_gender".to_string()]),
+                segments: Some(vec![]),
+                time_dimensions: None,
+                order: None,
+                limit: None,
+                offset: None,
+                filters: None,
+                timezone: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_join_cubes_without_relationship() {
+        let meta = get_test_meta()
+            .into_iter()
+            .map(|mut cube| {
+                if cube.name == "Logs" {
+                    cube.connected_component = Some(2);
+                }
+                cube
+            })
+            .collect::<Vec<_>>();
+
+        let result = rewrite_select_to_query_plan_with_meta(
+            "SELECT k.customer_gender, MEASURE(l.agentCount) FROM KibanaSampleDataEcommerce k LEFT JOIN Logs l ON k.__cubeJoinField = l.__cubeJoinField GROUP BY k.customer_gender".to_string(),
+            DatabaseProtocol::PostgreSQL,
+            meta,
+        );
+
+        match result {
+            Err(CompilationError::User(message)) => assert!(
+                message.contains("Can't join 'KibanaSampleDataEcommerce' and 'Logs' cubes"),
+                "Unexpected error: {}",
+                message
+            ),
+            _ => panic!("Expected join relationship error"),
+        }
+    }
+
+    #[test]
+    fn test_join_cubes_on_non_join_field() {
+        let result = rewrite_select_to_query_plan_with_meta(
+            "SELECT k.customer_gender, MEASURE(l.agentCount) FROM KibanaSampleDataEcommerce k LEFT JOIN Logs l ON k.count = l.agentCount GROUP BY k.customer_gender".to_string(),
+            DatabaseProtocol::PostgreSQL,
+            get_test_meta(),
+        );
+
+        match result {
+            Err(CompilationError::User(message)) => assert!(
+                message.contains("Can't join 'KibanaSampleDataEcommerce' and 'Logs' cubes: join condition should be KibanaSampleDataEcommerce.__cubeJoinField = Logs.__cubeJoinField"),
+                "Unexpected error: {}",
+                message
+            ),
+            _ => panic!("Expected join condition error"),
+        }
+    }
+
+    #[test]
+    fn test_join_cubes_on_join_field_of_one_side() {
+        let result = rewrite_select_to_query_plan_with_meta(
+            "SELECT k.customer_gender, MEASURE(l.agentCount) FROM KibanaSampleDataEcommerce k LEFT JOIN Logs l ON k.__cubeJoinField = k.__cubeJoinField GROUP BY k.customer_gender".to_string(),
+            DatabaseProtocol::PostgreSQL,
+            get_test_meta(),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_select_asterisk_hides_join_field() {
+        let query_plan = rewrite_select_to_query_plan(
+            "SELECT * FROM KibanaSampleDataEcommerce".to_string(),
+            DatabaseProtocol::PostgreSQL,
+        );
+
+        let fields = query_plan
+            .as_logical_plan()
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().to_string())
+            .collect::<Vec<_>>();
+        assert!(fields.contains(&"customer_gender".to_string()));
+        assert!(!fields.contains(&CUBE_JOIN_FIELD.to_string()));
+    }
+
+    #[test]
+    fn test_inner_join_cubes() {
+        let result = rewrite_select_to_query_plan_with_meta(
+            "SELECT k.customer_gender, MEASURE(l.agentCount) FROM KibanaSampleDataEcommerce k INNER JOIN Logs l ON k.__cubeJoinField = l.__cubeJoinField GROUP BY k.customer_gender".to_string(),
+            DatabaseProtocol::PostgreSQL,
+            get_test_meta(),
+        );
+
+        match result {
+            Err(CompilationError::User(message)) => assert!(
+                message.contains("INNER JOIN between cubes is not supported"),
+                "Unexpected error: {}",
+                message
+            ),
+            _ => panic!("Expected join type error"),
+        }
+    }
+
+    #[test]
+    fn test_select_measure_via_function() {
+        let query_plan = convert_select_to_query_plan(
+            "SELECT MEASURE(maxPrice), MEASURE(minPrice), MEASURE(avgPrice) FROM KibanaSampleDataEcommerce".to_string(),
+        DatabaseProtocol::MySQL);
+
+        let logical_plan = query_plan.as_logical_plan();
+        assert_eq!(
+            logical_plan.find_cube_scan().request,
+            V1LoadRequestQuery {
+                measures: Some(vec![
+                    "KibanaSampleDataEcommerce.maxPrice".to_string(),
+                    "KibanaSampleDataEcommerce.minPrice".to_string(),
+                    "KibanaSampleDataEcommerce.avgPrice".to_string(),
+                ]),
+                segments: Some(vec![]),
+                dimensions: Some(vec![]),
+                time_dimensions: None,
+                order: None,
+                limit: None,
+                offset: None,
+                filters: None,
+                timezone: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_select_compound_identifiers() {
+        let query_plan = convert_select_to_query_plan(
+            "SELECT MEASURE(`KibanaSampleDataEcommerce`.`maxPrice`) AS maxPrice, MEASURE(`KibanaSampleDataEcommerce`.`minPrice`) AS minPrice FROM KibanaSampleDataEcommerce".to_string(), DatabaseProtocol::MySQL
+        );
+
+        let logical_plan = query_plan.as_logical_plan();
+        assert_eq!(
+            logical_plan.find_cube_scan().request,
+            V1LoadRequestQuery {
+                measures: Some(vec![
+                    "KibanaSampleDataEcommerce.maxPrice".to_string(),
+                    "KibanaSampleDataEcommerce.minPrice".to_string(),
+                ]),
+                segments: Some(vec![]),
+                dimensions: Some(vec![]),
+                time_dimensions: None,
+                order: None,
+                limit: None,
+                offset: None,
+                filters: None,
+                timezone: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_select_measure_aggregate_functions() {
+        let query_plan = convert_select_to_query_plan(
+            "SELECT MAX(maxPrice), MIN(minPrice), AVG(avgPrice) FROM KibanaSampleDataEcommerce"
+                .to_string(),
+            DatabaseProtocol::MySQL,
+        );
+
+        assert_eq!(
+            query_plan.as_logical_plan().find_cube_scan().request,
+            V1LoadRequestQuery {
+                measures: Some(vec![
+                    "KibanaSampleDataEcommerce.maxPrice".to_string(),
+                    "KibanaSampleDataEcommerce.minPrice".to_string(),
+                    "KibanaSampleDataEcommerce.avgPrice".to_string(),
+                ]),
+                segments: Some(vec![]),
+                dimensions: Some(vec![]),
+                time_dimensions: None,
+                order: None,
+                limit: None,
+                offset: None,
+                filters: None,
+                timezone: None,
+            }
+        )
+    }
+
+    #[test]
+    fn test_order_alias_for_measure_default() {
+        let query_plan = convert_select_to_query_plan(
+            "SELECT COUNT(*) as cnt FROM KibanaSampleDataEcommerce ORDER BY cnt".to_string(),
+            DatabaseProtocol::MySQL,
+        );
+
+        assert_eq!(
+            query_plan.as_logical_plan().find_cube_scan().request,
+            V1LoadRequestQuery {
+                measures: Some(vec!["KibanaSampleDataEcommerce.count".to_string(),]),
+                segments: Some(vec![]),
+                dimensions: Some(vec![]),
+                time_dimensions: None,
+                order: Some(vec![vec![
+                    "KibanaSampleDataEcommerce.count".to_string(),
+                    "asc".to_string(),
+                ]]),
+                limit: None,
+                offset: None,
+                filters: None,
+                timezone: None,
             }
         )
     }
@@ -2712,7 +3591,8 @@ mod tests {
                     ]]),
                     limit: None,
                     offset: None,
-                    filters: None
+                    filters: None,
+                    timezone: None,
                 }
             ),
             // test_order_indentifier_default
@@ -2731,7 +3611,8 @@ mod tests {
                     ]]),
                     limit: None,
                     offset: None,
-                    filters: None
+                    filters: None,
+                    timezone: None,
                 }
             ),
             // test_order_compound_identifier_default
@@ -2750,7 +3631,8 @@ mod tests {
                     ]]),
                     limit: None,
                     offset: None,
-                    filters: None
+                    filters: None,
+                    timezone: None,
                 }
             ),
             // test_order_indentifier_asc
@@ -2769,7 +3651,8 @@ mod tests {
                     ]]),
                     limit: None,
                     offset: None,
-                    filters: None
+                    filters: None,
+                    timezone: None,
                 }
             ),
             // test_order_indentifier_desc
@@ -2788,7 +3671,8 @@ mod tests {
                     ]]),
                     limit: None,
                     offset: None,
-                    filters: None
+                    filters: None,
+                    timezone: None,
                 }
             ),
             // test_order_identifer_alias_ident_no_escape
@@ -2807,7 +3691,8 @@ mod tests {
                     ]]),
                     limit: None,
                     offset: None,
-                    filters: None
+                    filters: None,
+                    timezone: None,
                 }
             ),
             // test_order_identifer_alias_ident_escape
@@ -2826,7 +3711,8 @@ mod tests {
                     ]]),
                     limit: None,
                     offset: None,
-                    filters: None
+                    filters: None,
+                    timezone: None,
                 }
             ),
         ];
@@ -2866,7 +3752,8 @@ mod tests {
                 ]]),
                 limit: None,
                 offset: None,
-                filters: None
+                filters: None,
+                timezone: None,
             }
         )
     }
@@ -2934,6 +3821,7 @@ mod tests {
                 limit: None,
                 offset: None,
                 filters: None,
+                timezone: None,
             }
         )
     }
@@ -2960,6 +3848,7 @@ mod tests {
                 limit: None,
                 offset: None,
                 filters: None,
+                timezone: None,
             }
         );
 
@@ -2989,6 +3878,7 @@ mod tests {
                     limit: None,
                     offset: None,
                     filters: None,
+                    timezone: None,
                 },
             ),
             (
@@ -3002,6 +3892,7 @@ mod tests {
                     limit: None,
                     offset: None,
                     filters: None,
+                    timezone: None,
                 },
             ),
             (
@@ -3015,6 +3906,7 @@ mod tests {
                     limit: None,
                     offset: None,
                     filters: None,
+                    timezone: None,
                 },
             ),
             (
@@ -3028,6 +3920,7 @@ mod tests {
                     limit: None,
                     offset: None,
                     filters: None,
+                    timezone: None,
                 },
             ),
             (
@@ -3041,6 +3934,21 @@ mod tests {
                     limit: None,
                     offset: None,
                     filters: None,
+                    timezone: None,
+                },
+            ),
+            (
+                "SELECT AVG(count) FROM KibanaSampleDataEcommerce".to_string(),
+                V1LoadRequestQuery {
+                    measures: Some(vec!["KibanaSampleDataEcommerce.count".to_string()]),
+                    dimensions: Some(vec![]),
+                    segments: Some(vec![]),
+                    time_dimensions: None,
+                    order: None,
+                    limit: None,
+                    offset: None,
+                    filters: None,
+                    timezone: None,
                 },
             ),
             (
@@ -3054,6 +3962,7 @@ mod tests {
                     limit: None,
                     offset: None,
                     filters: None,
+                    timezone: None,
                 },
             ),
             (
@@ -3067,6 +3976,50 @@ mod tests {
                     limit: None,
                     offset: None,
                     filters: None,
+                    timezone: None,
+                },
+            ),
+            // Compatible with the measure type
+            (
+                "SELECT SUM(count) FROM KibanaSampleDataEcommerce".to_string(),
+                V1LoadRequestQuery {
+                    measures: Some(vec!["KibanaSampleDataEcommerce.count".to_string()]),
+                    dimensions: Some(vec![]),
+                    segments: Some(vec![]),
+                    time_dimensions: None,
+                    order: None,
+                    limit: None,
+                    offset: None,
+                    filters: None,
+                    timezone: None,
+                },
+            ),
+            (
+                "SELECT AVG(count) FROM KibanaSampleDataEcommerce".to_string(),
+                V1LoadRequestQuery {
+                    measures: Some(vec!["KibanaSampleDataEcommerce.count".to_string()]),
+                    dimensions: Some(vec![]),
+                    segments: Some(vec![]),
+                    time_dimensions: None,
+                    order: None,
+                    limit: None,
+                    offset: None,
+                    filters: None,
+                    timezone: None,
+                },
+            ),
+            (
+                "SELECT COUNT(DISTINCT agentCountApprox) FROM Logs".to_string(),
+                V1LoadRequestQuery {
+                    measures: Some(vec!["Logs.agentCountApprox".to_string()]),
+                    dimensions: Some(vec![]),
+                    segments: Some(vec![]),
+                    time_dimensions: None,
+                    order: None,
+                    limit: None,
+                    offset: None,
+                    filters: None,
+                    timezone: None,
                 },
             ),
         ];
@@ -3117,6 +4070,10 @@ mod tests {
                 "SELECT MAX(minPrice) FROM KibanaSampleDataEcommerce".to_string(),
                 CompilationError::User("Measure aggregation type doesn't match. The aggregation type for 'minPrice' is 'MIN()' but 'MAX()' was provided".to_string()),
             ),
+            (
+                "SELECT SUM(agentCount) FROM Logs".to_string(),
+                CompilationError::User("Measure aggregation type doesn't match. The aggregation type for 'agentCount' is 'COUNTDISTINCT()' but 'SUM()' was provided".to_string()),
+            ),
             // (
             //     "SELECT MAX(unknownIdentifier) FROM KibanaSampleDataEcommerce".to_string(),
             //     CompilationError::User("Unable to find measure with name 'unknownIdentifier' which is used as argument to aggregation function 'MAX()'".to_string()),
@@ -3208,7 +4165,8 @@ mod tests {
                     order: None,
                     limit: None,
                     offset: None,
-                    filters: None
+                    filters: None,
+                    timezone: None,
                 }
             );
 
@@ -3281,7 +4239,122 @@ mod tests {
                     order: None,
                     limit: None,
                     offset: None,
-                    filters: None
+                    filters: None,
+                    timezone: None,
+                }
+            )
+        }
+    }
+
+    #[test]
+    fn test_group_by_date_granularity_postgres() {
+        let supported_granularities = vec![
+            // Case insensitive granularity
+            [
+                "DATE_TRUNC('MONTH', order_date)".to_string(),
+                "month".to_string(),
+            ],
+            [
+                "DATE_TRUNC('month', \"KibanaSampleDataEcommerce\".\"order_date\")".to_string(),
+                "month".to_string(),
+            ],
+            [
+                "DATE_TRUNC('week', order_date::timestamp)".to_string(),
+                "week".to_string(),
+            ],
+            [
+                "DATE_TRUNC('quarter', order_date AT TIME ZONE 'UTC')".to_string(),
+                "quarter".to_string(),
+            ],
+            ["order_date::date".to_string(), "day".to_string()],
+            ["CAST(order_date AS DATE)".to_string(), "day".to_string()],
+            [
+                "EXTRACT(YEAR FROM order_date)".to_string(),
+                "year".to_string(),
+            ],
+        ];
+
+        for [subquery, expected_granularity] in supported_granularities.iter() {
+            let logical_plan = convert_select_to_query_plan(
+                format!("SELECT COUNT(*), {} AS __timestamp FROM KibanaSampleDataEcommerce GROUP BY __timestamp", subquery), DatabaseProtocol::PostgreSQL
+            ).as_logical_plan();
+
+            assert_eq!(
+                logical_plan.find_cube_scan().request,
+                V1LoadRequestQuery {
+                    measures: Some(vec!["KibanaSampleDataEcommerce.count".to_string(),]),
+                    dimensions: Some(vec![]),
+                    segments: Some(vec![]),
+                    time_dimensions: Some(vec![V1LoadRequestQueryTimeDimension {
+                        dimension: "KibanaSampleDataEcommerce.order_date".to_string(),
+                        granularity: Some(expected_granularity.to_string()),
+                        date_range: None,
+                    }]),
+                    order: None,
+                    limit: None,
+                    offset: None,
+                    filters: None,
+                    timezone: None,
+                }
+            )
+        }
+
+        let unsupported = vec![
+            "DATE_TRUNC('month', order_date AT TIME ZONE 'America/Los_Angeles')",
+            "EXTRACT(MONTH FROM order_date)",
+        ];
+
+        for subquery in unsupported.iter() {
+            let result = convert_sql_to_cube_query(
+                &format!("SELECT COUNT(*), {} AS __timestamp FROM KibanaSampleDataEcommerce GROUP BY __timestamp", subquery),
+                get_test_tenant_ctx(),
+                get_test_session(DatabaseProtocol::PostgreSQL),
+            );
+
+            assert!(result.is_err(), "Expected error for {}", subquery);
+        }
+    }
+
+    #[test]
+    fn test_date_granularity_postgres_rewrite() {
+        let supported_granularities = vec![
+            [
+                "DATE_TRUNC('MONTH', order_date AT TIME ZONE 'UTC')".to_string(),
+                "month".to_string(),
+            ],
+            [
+                "DATE_TRUNC('hour', order_date::timestamp)".to_string(),
+                "hour".to_string(),
+            ],
+            ["CAST(order_date AS DATE)".to_string(), "day".to_string()],
+        ];
+
+        for [expr, expected_granularity] in supported_granularities.iter() {
+            let logical_plan = rewrite_select_to_query_plan(
+                format!(
+                    "SELECT {} AS __timestamp, COUNT(*) FROM KibanaSampleDataEcommerce GROUP BY {}",
+                    expr, expr
+                ),
+                DatabaseProtocol::PostgreSQL,
+            )
+            .as_logical_plan();
+
+            assert_eq!(
+                logical_plan.find_cube_scan().request,
+                V1LoadRequestQuery {
+                    measures: Some(vec!["KibanaSampleDataEcommerce.count".to_string()]),
+                    dimensions: Some(vec![]),
+                    segments: Some(vec![]),
+                    time_dimensions: Some(vec![V1LoadRequestQueryTimeDimension {
+                        dimension: "KibanaSampleDataEcommerce.order_date".to_string(),
+                        granularity: Some(expected_granularity.to_string()),
+                        date_range: None,
+                    }]),
+                    order: None,
+                    limit: None,
+                    offset: None,
+                    filters: None,
+                    timezone: None,
                 }
             )
         }
@@ -4188,7 +5261,23 @@ mod tests {
         query: String,
         db: DatabaseProtocol,
     ) -> Result<(String, StatusFlags), CubeError> {
-        let query = convert_sql_to_cube_query(&query, get_test_tenant_ctx(), get_test_session(db));
+        execute_query_with_session_and_flags(query, get_test_session(db)).await
+    }
+
+    async fn execute_query_with_session(
+        query: String,
+        session: Arc<Session>,
+    ) -> Result<String, CubeError> {
+        Ok(execute_query_with_session_and_flags(query, session)
+            .await?
+            .0)
+    }
+
+    async fn execute_query_with_session_and_flags(
+        query: String,
+        session: Arc<Session>,
+    ) -> Result<(String, StatusFlags), CubeError> {
+        let query = convert_sql_to_cube_query(&query, get_test_tenant_ctx(), session);
         match query.unwrap() {
             QueryPlan::DataFusionSelect(flags, plan, ctx) => {
                 let df = DataFrameImpl::new(ctx.state, &plan);
@@ -4435,6 +5524,50 @@ mod tests {
             +--------------------------+"
         );
 
+        // Named zones are resolved with their DST offset
+        assert_eq!(
+            execute_query(
+                "select convert_tz('2021-07-01T12:00:00.000Z'::timestamp, 'UTC', 'Europe/Berlin') as r1, convert_tz('2021-12-08T15:50:14.337Z'::timestamp, 'America/Los_Angeles', '+00:00') as r2;".to_string(), DatabaseProtocol::MySQL
+            )
+            .await?,
+            "+--------------------------+--------------------------+\n\
+            | r1                       | r2                       |\n\
+            +--------------------------+--------------------------+\n\
+            | 2021-07-01T14:00:00.000Z | 2021-12-08T23:50:14.337Z |\n\
+            +--------------------------+--------------------------+"
+        );
+
+        assert!(execute_query(
+            "select convert_tz(now(), 'UTC', 'Europe/Nowhere') as r1;".to_string(),
+            DatabaseProtocol::MySQL
+        )
+        .await
+        .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_at_time_zone() -> Result<(), CubeError> {
+        assert_eq!(
+            execute_query(
+                "select '2021-12-08T15:50:14.337Z'::timestamp AT TIME ZONE 'UTC' as r1, ('2021-12-08T15:50:14.337Z'::timestamp) AT TIME ZONE 'America/Los_Angeles' as r2;".to_string(), DatabaseProtocol::PostgreSQL
+            )
+            .await?,
+            "+--------------------------+--------------------------+\n\
+            | r1                       | r2                       |\n\
+            +--------------------------+--------------------------+\n\
+            | 2021-12-08T15:50:14.337Z | 2021-12-08T07:50:14.337Z |\n\
+            +--------------------------+--------------------------+"
+        );
+
+        assert!(execute_query(
+            "select now() AT TIME ZONE 'Mars/Olympus_Mons' as r1;".to_string(),
+            DatabaseProtocol::PostgreSQL
+        )
+        .await
+        .is_err());
+
         Ok(())
     }
 
@@ -4784,6 +5917,42 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_set_user() -> Result<(), CubeError> {
+        let session = get_test_session(DatabaseProtocol::PostgreSQL);
+        let result = convert_sql_to_cube_query(
+            &"SET user = 'tenant'".to_string(),
+            get_test_tenant_ctx(),
+            session.clone(),
+        );
+        match result {
+            Err(CompilationError::User(message)) => assert_eq!(
+                message,
+                "user 'ovr' is not allowed to switch to 'tenant'".to_string()
+            ),
+            _ => panic!("Expected error for user without permission to switch"),
+        }
+        assert_eq!(session.state.user(), Some("ovr".to_string()));
+
+        let mut auth_context = session.state.auth_context().unwrap();
+        auth_context.can_switch_user = true;
+        session.state.set_auth_context(Some(auth_context));
+
+        convert_sql_to_cube_query(
+            &"SET user = 'tenant'".to_string(),
+            get_test_tenant_ctx(),
+            session.clone(),
+        )
+        .unwrap();
+        assert_eq!(session.state.user(), Some("tenant".to_string()));
+        assert!(session.state.auth_context().is_none());
+
+        let auth_context = session.auth_context(HashMap::new()).await?;
+        assert_eq!(auth_context.access_token, "fake".to_string());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_show_collation() -> Result<(), CubeError> {
         // Simplest syntax
@@ -4812,44 +5981,228 @@ mod tests {
             .await?
         );
 
-        // Superset query
-        insta::assert_snapshot!(
-            "show_collation_superset",
-            execute_query(
-                "show collation where charset = 'utf8mb4' and collation = 'utf8mb4_bin';"
-                    .to_string(),
-                DatabaseProtocol::MySQL
-            )
-            .await?
-        );
+        // Superset query
+        insta::assert_snapshot!(
+            "show_collation_superset",
+            execute_query(
+                "show collation where charset = 'utf8mb4' and collation = 'utf8mb4_bin';"
+                    .to_string(),
+                DatabaseProtocol::MySQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_explain() -> Result<(), CubeError> {
+        // SELECT with no tables (inline eval)
+        insta::assert_snapshot!(
+            execute_query("explain select 1+1;".to_string(), DatabaseProtocol::MySQL).await?
+        );
+
+        // SELECT with table and specific columns
+        insta::assert_snapshot!(
+            execute_query(
+                "explain select count, avgPrice from KibanaSampleDataEcommerce;".to_string(),
+                DatabaseProtocol::MySQL
+            )
+            .await?
+        );
+
+        // EXPLAIN for Postgres
+        insta::assert_snapshot!(
+            execute_query(
+                "explain select 1+1;".to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_session_time_zone() -> Result<(), CubeError> {
+        // MySQL, legacy planner
+        let session = get_test_session(DatabaseProtocol::MySQL);
+        convert_sql_to_cube_query(
+            &"SET time_zone = '+03:00'".to_string(),
+            get_test_tenant_ctx(),
+            session.clone(),
+        )?;
+        assert_eq!(session.state.time_zone(), Some("Etc/GMT-3".to_string()));
+
+        let query_plan = convert_sql_to_cube_query(
+            &"SELECT COUNT(*) FROM KibanaSampleDataEcommerce WHERE order_date >= STR_TO_DATE('2021-08-31 00:00:00.000000', '%Y-%m-%d %H:%i:%s.%f')".to_string(),
+            get_test_tenant_ctx(),
+            session.clone(),
+        )?;
+        let request = query_plan.as_logical_plan().find_cube_scan().request;
+        assert_eq!(request.timezone, Some("Etc/GMT-3".to_string()));
+        assert_eq!(
+            request.filters.unwrap()[0].values,
+            Some(vec!["2021-08-31T00:00:00.000".to_string()])
+        );
+
+        let result = execute_query_with_session(
+            "SELECT @@time_zone AS tz, convert_tz('2021-12-08T15:50:14.337Z'::timestamp, '+00:00', @@time_zone) AS local".to_string(),
+            session.clone(),
+        )
+        .await?;
+        assert!(result.contains("+03:00"));
+        assert!(result.contains("2021-12-08T18:50:14.337"));
+
+        // Time zone isn't shared between sessions
+        assert_eq!(
+            get_test_session(DatabaseProtocol::MySQL).state.time_zone(),
+            None
+        );
+
+        assert!(convert_sql_to_cube_query(
+            &"SET time_zone = 'Europe/'".to_string(),
+            get_test_tenant_ctx(),
+            session.clone(),
+        )
+        .is_err());
+
+        // Postgres, rewrite engine
+        let session = get_test_session(DatabaseProtocol::PostgreSQL);
+        assert_eq!(session.state.time_zone(), None);
+        convert_sql_to_cube_query(
+            &"SET TIME ZONE 'America/Los_Angeles'".to_string(),
+            get_test_tenant_ctx(),
+            session.clone(),
+        )?;
+        assert_eq!(
+            session.state.time_zone(),
+            Some("America/Los_Angeles".to_string())
+        );
+        assert!(
+            [-8 * 3600, -7 * 3600].contains(&session.state.time_zone_offset().local_minus_utc())
+        );
+        assert!(convert_sql_to_cube_query(
+            &"SET TIME ZONE 'Europe/Nowhere'".to_string(),
+            get_test_tenant_ctx(),
+            session.clone(),
+        )
+        .is_err());
+
+        let planner = QueryPlanner::new(
+            session.state.clone(),
+            get_test_tenant_ctx(),
+            session.session_manager.clone(),
+        );
+        let query_plan = planner.create_df_logical_plan(parse_sql_to_statement(
+            &"SELECT COUNT(*) FROM KibanaSampleDataEcommerce".to_string(),
+            DatabaseProtocol::PostgreSQL,
+        )?)?;
+        assert_eq!(
+            query_plan
+                .as_logical_plan()
+                .find_cube_scan()
+                .request
+                .timezone,
+            Some("America/Los_Angeles".to_string())
+        );
+
+        let result =
+            execute_query_with_session("SHOW timezone".to_string(), session.clone()).await?;
+        assert!(result.contains("America/Los_Angeles"));
 
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_explain() -> Result<(), CubeError> {
-        // SELECT with no tables (inline eval)
-        insta::assert_snapshot!(
-            execute_query("explain select 1+1;".to_string(), DatabaseProtocol::MySQL).await?
-        );
+    async fn test_kill_query() -> Result<(), CubeError> {
+        let session = get_test_session(DatabaseProtocol::MySQL);
+        let token = session.state.begin_query();
+
+        let other = session
+            .session_manager
+            .create_session(DatabaseProtocol::MySQL, "127.0.0.1".to_string());
+        other.state.set_user(Some("ovr".to_string()));
+
+        let kill = format!("KILL QUERY {}", session.state.connection_id);
+        execute_query_with_session(kill, other.clone()).await?;
+        assert!(token.is_cancelled());
+
+        // Next query of the session isn't affected, the connection is kept open
+        assert!(!session.state.begin_query().is_cancelled());
+        assert!(!session.state.connection_token().is_cancelled());
+
+        let killed = session
+            .session_manager
+            .create_session(DatabaseProtocol::MySQL, "127.0.0.1".to_string());
+        killed.state.set_user(Some("ovr".to_string()));
+        let token = killed.state.begin_query();
+
+        let kill = format!("KILL CONNECTION {}", killed.state.connection_id);
+        execute_query_with_session(kill, other.clone()).await?;
+        assert!(token.is_cancelled());
+        assert!(killed.state.connection_token().is_cancelled());
+
+        match convert_sql_to_cube_query(
+            &"KILL 999".to_string(),
+            get_test_tenant_ctx(),
+            other.clone(),
+        ) {
+            Err(CompilationError::User(message)) => assert_eq!(message, "Unknown thread id: 999"),
+            _ => panic!("KILL of unknown thread must fail"),
+        }
 
-        // SELECT with table and specific columns
-        insta::assert_snapshot!(
-            execute_query(
-                "explain select count, avgPrice from KibanaSampleDataEcommerce;".to_string(),
-                DatabaseProtocol::MySQL
-            )
-            .await?
-        );
+        other.state.set_user(Some("another".to_string()));
+        let kill = format!("KILL {}", session.state.connection_id);
+        match convert_sql_to_cube_query(&kill, get_test_tenant_ctx(), other) {
+            Err(CompilationError::User(message)) => assert_eq!(
+                message,
+                format!(
+                    "You are not owner of thread {}",
+                    session.state.connection_id
+                )
+            ),
+            _ => panic!("KILL of another user thread must fail"),
+        }
 
-        // EXPLAIN for Postgres
-        insta::assert_snapshot!(
-            execute_query(
-                "explain select 1+1;".to_string(),
-                DatabaseProtocol::PostgreSQL
-            )
-            .await?
-        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_explain_verbose() -> Result<(), CubeError> {
+        // Compiled by the legacy compiler
+        let result = execute_query(
+            "EXPLAIN VERBOSE SELECT COUNT(*), customer_gender FROM KibanaSampleDataEcommerce GROUP BY customer_gender".to_string(),
+            DatabaseProtocol::PostgreSQL,
+        )
+        .await?;
+        assert!(result.contains("logical_plan"));
+        assert!(result.contains("cube_query"));
+        assert!(result.contains("KibanaSampleDataEcommerce.count"));
+        assert!(result.contains("KibanaSampleDataEcommerce.customer_gender"));
+        assert!(!result.contains("rewrite_iterations"));
+
+        // Planned by the rewrite engine
+        let result = execute_query(
+            "EXPLAIN VERBOSE SELECT k.customer_gender, MEASURE(l.agentCount) FROM KibanaSampleDataEcommerce k LEFT JOIN Logs l ON k.__cubeJoinField = l.__cubeJoinField GROUP BY k.customer_gender".to_string(),
+            DatabaseProtocol::PostgreSQL,
+        )
+        .await?;
+        assert!(result.contains("logical_plan"));
+        assert!(result.contains("rewrite_iterations"));
+        assert!(result.contains("rewrite_cube_scan"));
+        assert!(result.contains("cube_query"));
+        assert!(result.contains("Logs.agentCount"));
+        assert!(!result.contains("rewrite_unmatched_expressions"));
+
+        let result = execute_query(
+            "EXPLAIN VERBOSE SELECT SUM(k.taxful_total_price * 2) FROM KibanaSampleDataEcommerce k LEFT JOIN Logs l ON k.__cubeJoinField = l.__cubeJoinField".to_string(),
+            DatabaseProtocol::PostgreSQL,
+        )
+        .await?;
+        assert!(result.contains("logical_plan_error"));
+        assert!(result.contains("rewrite_unmatched_expressions"));
+        assert!(result.contains("taxful_total_price"));
 
         Ok(())
     }
@@ -5159,6 +6512,319 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_pgcatalog_pgdatabase_postgres() -> Result<(), CubeError> {
+        insta::assert_snapshot!(
+            "pgcatalog_pgdatabase_postgres",
+            execute_query(
+                "SELECT * FROM pg_catalog.pg_database".to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pgcatalog_pgroles_postgres() -> Result<(), CubeError> {
+        insta::assert_snapshot!(
+            "pgcatalog_pgroles_postgres",
+            execute_query(
+                "SELECT * FROM pg_catalog.pg_roles".to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pgcatalog_pguser_postgres() -> Result<(), CubeError> {
+        insta::assert_snapshot!(
+            "pgcatalog_pguser_postgres",
+            execute_query(
+                "SELECT * FROM pg_catalog.pg_user".to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pgcatalog_pgviews_postgres() -> Result<(), CubeError> {
+        insta::assert_snapshot!(
+            "pgcatalog_pgviews_postgres",
+            execute_query(
+                "SELECT * FROM pg_catalog.pg_views".to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pgcatalog_pgam_postgres() -> Result<(), CubeError> {
+        insta::assert_snapshot!(
+            "pgcatalog_pgam_postgres",
+            execute_query(
+                "SELECT * FROM pg_catalog.pg_am".to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pgcatalog_pgenum_postgres() -> Result<(), CubeError> {
+        insta::assert_snapshot!(
+            "pgcatalog_pgenum_postgres",
+            execute_query(
+                "SELECT * FROM pg_catalog.pg_enum".to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pgcatalog_pginherits_postgres() -> Result<(), CubeError> {
+        insta::assert_snapshot!(
+            "pgcatalog_pginherits_postgres",
+            execute_query(
+                "SELECT * FROM pg_catalog.pg_inherits".to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pgcatalog_pgextension_postgres() -> Result<(), CubeError> {
+        insta::assert_snapshot!(
+            "pgcatalog_pgextension_postgres",
+            execute_query(
+                "SELECT * FROM pg_catalog.pg_extension".to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pgcatalog_pgstatactivity_postgres() -> Result<(), CubeError> {
+        insta::assert_snapshot!(
+            "pgcatalog_pgstatactivity_postgres",
+            execute_query(
+                "SELECT * FROM pg_catalog.pg_stat_activity".to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pgcatalog_functions_postgres() -> Result<(), CubeError> {
+        insta::assert_snapshot!(
+            "pgcatalog_format_type_postgres",
+            execute_query(
+                "SELECT format_type(1043, 36) AS \"varchar\", \
+                pg_catalog.format_type(1700, 655366) AS \"numeric\", \
+                format_type(1114, 3) AS \"timestamp\", \
+                format_type(9999, -1) AS \"unknown\""
+                    .to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        insta::assert_snapshot!(
+            "pgcatalog_to_char_postgres",
+            execute_query(
+                "SELECT to_char(CAST('2022-03-15 13:05:09.123' AS timestamp), 'YYYY-MM-DD HH24:MI:SS.MS') AS iso, \
+                to_char(CAST('2022-03-15 13:05:09.123' AS timestamp), 'FMMonth DD, YYYY') AS long, \
+                to_char(CAST('2022-03-15 13:05:09.123' AS timestamp), 'Dy, Mon DD HH12:MI AM') AS short, \
+                to_char(CAST('2022-03-15 13:05:09.123' AS timestamp), 'YYYY \"Q\"Q') AS quarter"
+                    .to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dbeaver_introspection_postgres() -> Result<(), CubeError> {
+        insta::assert_snapshot!(
+            "dbeaver_databases_postgres",
+            execute_query(
+                "SELECT db.oid, db.datname, pg_catalog.pg_get_userbyid(db.datdba) AS owner \
+                FROM pg_catalog.pg_database db \
+                WHERE db.datallowconn AND NOT db.datistemplate \
+                ORDER BY db.datname"
+                    .to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        insta::assert_snapshot!(
+            "dbeaver_namespaces_postgres",
+            execute_query(
+                "SELECT n.oid, n.nspname, d.description \
+                FROM pg_catalog.pg_namespace n \
+                LEFT OUTER JOIN pg_catalog.pg_description d ON d.objoid = n.oid \
+                ORDER BY n.nspname"
+                    .to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        insta::assert_snapshot!(
+            "dbeaver_tables_postgres",
+            execute_query(
+                "SELECT c.oid, c.relname, c.relkind, \
+                pg_catalog.pg_get_userbyid(c.relowner) AS owner, \
+                pg_catalog.obj_description(c.oid, 'pg_class') AS description \
+                FROM pg_catalog.pg_class c \
+                WHERE c.relnamespace = 2200 AND c.relkind NOT IN ('i', 'I', 'c') \
+                ORDER BY c.oid"
+                    .to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        insta::assert_snapshot!(
+            "dbeaver_roles_postgres",
+            execute_query(
+                "SELECT a.oid, a.rolname, a.rolsuper, a.rolcanlogin \
+                FROM pg_catalog.pg_roles a \
+                ORDER BY a.oid"
+                    .to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_powerbi_introspection_postgres() -> Result<(), CubeError> {
+        // Npgsql loads enum labels with unqualified catalog names
+        insta::assert_snapshot!(
+            "powerbi_enums_postgres",
+            execute_query(
+                "SELECT pg_type.oid, enumlabel \
+                FROM pg_enum \
+                JOIN pg_type ON pg_type.oid = enumtypid \
+                ORDER BY pg_type.oid, enumsortorder"
+                    .to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        insta::assert_snapshot!(
+            "powerbi_schemas_postgres",
+            execute_query(
+                "SELECT n.nspname AS \"TABLE_SCHEMA\" \
+                FROM pg_catalog.pg_namespace n \
+                WHERE has_schema_privilege(n.nspname, 'USAGE') \
+                AND n.nspname NOT IN ('pg_catalog', 'information_schema') \
+                ORDER BY \"TABLE_SCHEMA\""
+                    .to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_looker_introspection_postgres() -> Result<(), CubeError> {
+        insta::assert_snapshot!(
+            "looker_views_postgres",
+            execute_query(
+                "SELECT schemaname, viewname FROM pg_catalog.pg_views WHERE schemaname = 'public'"
+                    .to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        insta::assert_snapshot!(
+            "looker_extensions_postgres",
+            execute_query(
+                "SELECT extname, extversion FROM pg_catalog.pg_extension".to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_psql_introspection_postgres() -> Result<(), CubeError> {
+        // \d
+        insta::assert_snapshot!(
+            "psql_list_relations_postgres",
+            execute_query(
+                "SELECT n.nspname AS \"Schema\", \
+                c.relname AS \"Name\", \
+                CASE c.relkind WHEN 'r' THEN 'table' WHEN 'v' THEN 'view' WHEN 'm' THEN 'materialized view' WHEN 'i' THEN 'index' WHEN 'S' THEN 'sequence' WHEN 's' THEN 'special' WHEN 'f' THEN 'foreign table' WHEN 'p' THEN 'partitioned table' WHEN 'I' THEN 'partitioned index' END AS \"Type\", \
+                pg_catalog.pg_get_userbyid(c.relowner) AS \"Owner\" \
+                FROM pg_catalog.pg_class c \
+                LEFT JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace \
+                WHERE c.relkind IN ('r', 'p', 'v', 'm', 'S', 'f', '') \
+                AND n.nspname <> 'pg_catalog' \
+                AND n.nspname <> 'information_schema' \
+                AND pg_catalog.pg_table_is_visible(c.oid) \
+                ORDER BY \"Schema\", \"Name\""
+                    .to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        // \d KibanaSampleDataEcommerce
+        insta::assert_snapshot!(
+            "psql_describe_table_postgres",
+            execute_query(
+                "SELECT a.attname AS \"Column\", \
+                pg_catalog.format_type(a.atttypid, a.atttypmod) AS \"Type\", \
+                CASE WHEN a.attnotnull THEN 'not null' ELSE '' END AS \"Nullable\" \
+                FROM pg_catalog.pg_attribute a \
+                WHERE a.attrelid = 18000 AND a.attnum > 0 AND NOT a.attisdropped"
+                    .to_string(),
+                DatabaseProtocol::PostgreSQL
+            )
+            .await?
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_current_schema_postgres() -> Result<(), CubeError> {
         insta::assert_snapshot!(
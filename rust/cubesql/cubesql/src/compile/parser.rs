@@ -1,9 +1,151 @@
+use regex::Regex;
 use sqlparser::{ast::Statement, dialect::Dialect, dialect::PostgreSqlDialect, parser::Parser};
 
-use crate::{compile::CompilationError, sql::session::DatabaseProtocol};
+use crate::{
+    compile::CompilationError,
+    sql::{
+        session::DatabaseProtocol,
+        statement::{StatementAtTimeZone, AT_TIME_ZONE_MARKER},
+    },
+};
 
 use super::CompilationResult;
 
+/// Cube specific commands which are executed by the SQL API itself
+#[derive(Debug, PartialEq)]
+pub enum SystemCommand {
+    /// SYS REFRESH META, drops cached meta of the current security context
+    RefreshMeta,
+}
+
+pub fn parse_system_command(query: &str) -> Option<SystemCommand> {
+    let words = query
+        .trim()
+        .trim_end_matches(';')
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>();
+
+    match words.iter().map(|word| word.as_str()).collect::<Vec<_>>()[..] {
+        ["sys", "refresh", "meta"] => Some(SystemCommand::RefreshMeta),
+        _ => None,
+    }
+}
+
+lazy_static! {
+    static ref SET_TIME_ZONE: Regex =
+        Regex::new(r#"(?i)^\s*SET\s+(?:SESSION\s+|LOCAL\s+)?TIME\s+ZONE\s+"#).unwrap();
+}
+
+/// sqlparser doesn't know `<expr> AT TIME ZONE '<zone>'`. It's rewritten to a cast to the marker
+/// type `"AT TIME ZONE '<zone>'"`, which binds to the operand the same way as Postgres does, and
+/// `StatementAtTimeZone` replaces these casts by `timezone('<zone>', <expr>)` calls.
+fn rewrite_at_time_zone(query: &str) -> String {
+    let bytes = query.as_bytes();
+    let mut result = String::with_capacity(query.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\'' | b'"' => i = skip_quoted(bytes, i),
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = query[i..]
+                    .find('\n')
+                    .map(|pos| i + pos)
+                    .unwrap_or(bytes.len())
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = query[i + 2..]
+                    .find("*/")
+                    .map(|pos| i + 2 + pos + 2)
+                    .unwrap_or(bytes.len())
+            }
+            b'a' | b'A' if i == 0 || !is_identifier_byte(bytes[i - 1]) => {
+                match match_at_time_zone(bytes, i) {
+                    Some((zone_start, end)) => {
+                        result.push_str(&query[copied..i]);
+                        result.push_str(&format!(
+                            "::\"{}{}\"",
+                            AT_TIME_ZONE_MARKER,
+                            &query[zone_start..end]
+                        ));
+                        copied = end;
+                        i = end;
+                    }
+                    None => i += 1,
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    result.push_str(&query[copied..]);
+    result
+}
+
+fn is_identifier_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'$' || !byte.is_ascii()
+}
+
+/// Returns the position after the quoted string or identifier which starts at `start`.
+fn skip_quoted(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        if bytes[i] == quote {
+            // Doubled quote is an escaped one
+            if bytes.get(i + 1) == Some(&quote) {
+                i += 2;
+                continue;
+            }
+
+            return i + 1;
+        }
+
+        i += 1;
+    }
+
+    bytes.len()
+}
+
+/// Matches `AT TIME ZONE '<zone>'` at `start`, returns the positions of the zone literal.
+fn match_at_time_zone(bytes: &[u8], start: usize) -> Option<(usize, usize)> {
+    let mut i = start;
+    for keyword in ["AT", "TIME", "ZONE"].iter() {
+        let end = i + keyword.len();
+        if end > bytes.len() || !bytes[i..end].eq_ignore_ascii_case(keyword.as_bytes()) {
+            return None;
+        }
+
+        let spaces = bytes[end..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_whitespace())
+            .count();
+        if spaces == 0 {
+            return None;
+        }
+
+        i = end + spaces;
+    }
+
+    if bytes.get(i) != Some(&b'\'') {
+        return None;
+    }
+
+    let end = skip_quoted(bytes, i);
+    // The zone becomes a part of the quoted marker type
+    if bytes[end - 1] != b'\'' || end - i < 2 || bytes[i..end].contains(&b'"') {
+        return None;
+    }
+
+    Some((i, end))
+}
+
+/// sqlparser doesn't know Postgres `SET TIME ZONE <zone>`, it's an alias of `SET timezone = <zone>`.
+fn rewrite_set_time_zone(query: &str) -> String {
+    SET_TIME_ZONE.replace(query, "SET timezone = ").to_string()
+}
+
 #[derive(Debug)]
 pub struct MySqlDialectWithBackTicks {}
 
@@ -45,6 +187,11 @@ pub fn parse_sql_to_statement(
     let query = query.replace("unsigned integer", "bigint");
     let query = query.replace("UNSIGNED INTEGER", "bigint");
 
+    let query = match protocol {
+        DatabaseProtocol::PostgreSQL => rewrite_set_time_zone(&rewrite_at_time_zone(&query)),
+        DatabaseProtocol::MySQL => query,
+    };
+
     let parse_result = match protocol {
         DatabaseProtocol::MySQL => Parser::parse_sql(&MySqlDialectWithBackTicks {}, query.as_str()),
         DatabaseProtocol::PostgreSQL => Parser::parse_sql(&PostgreSqlDialect {}, query.as_str()),
@@ -57,7 +204,12 @@ pub fn parse_sql_to_statement(
         ))),
         Ok(stmts) => {
             if stmts.len() == 1 {
-                Ok(stmts[0].clone())
+                let mut stmt = stmts[0].clone();
+                if protocol == DatabaseProtocol::PostgreSQL {
+                    StatementAtTimeZone::new().apply(&mut stmt);
+                }
+
+                Ok(stmt)
             } else if stmts.is_empty() {
                 Err(CompilationError::User(format!(
                     "Invalid query, no statements was specified: {}",
@@ -77,6 +229,86 @@ pub fn parse_sql_to_statement(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_system_command() {
+        assert_eq!(
+            parse_system_command("SYS REFRESH META"),
+            Some(SystemCommand::RefreshMeta)
+        );
+        assert_eq!(
+            parse_system_command("  sys  refresh meta;"),
+            Some(SystemCommand::RefreshMeta)
+        );
+        assert_eq!(parse_system_command("SYS REFRESH"), None);
+        assert_eq!(parse_system_command("SELECT 1"), None);
+    }
+
+    #[test]
+    fn test_rewrite_at_time_zone() {
+        assert_eq!(
+            rewrite_at_time_zone("SELECT order_date AT TIME ZONE 'UTC' FROM t"),
+            "SELECT order_date ::\"AT TIME ZONE 'UTC'\" FROM t"
+        );
+        assert_eq!(
+            rewrite_at_time_zone(
+                "SELECT 'a AT TIME ZONE ''UTC''', \"AT TIME ZONE 'UTC'\" FROM t -- b AT TIME ZONE 'UTC'"
+            ),
+            "SELECT 'a AT TIME ZONE ''UTC''', \"AT TIME ZONE 'UTC'\" FROM t -- b AT TIME ZONE 'UTC'"
+        );
+        assert_eq!(
+            rewrite_at_time_zone("SELECT flat AT TIME ZONE 'UTC', at_time FROM t"),
+            "SELECT flat ::\"AT TIME ZONE 'UTC'\", at_time FROM t"
+        );
+    }
+
+    #[test]
+    fn test_at_time_zone() {
+        let cases = vec![
+            (
+                "SELECT order_date AT TIME ZONE 'UTC' FROM t",
+                "SELECT timezone('UTC', order_date) FROM t",
+            ),
+            (
+                "SELECT DATE_TRUNC('month', \"t\".\"order_date\"::timestamp at time zone 'America/Los_Angeles') FROM t",
+                "SELECT DATE_TRUNC('month', timezone('America/Los_Angeles', CAST(\"t\".\"order_date\" AS TIMESTAMP))) FROM t",
+            ),
+            (
+                "SELECT (a + b) AT TIME ZONE 'UTC' FROM t",
+                "SELECT timezone('UTC', (a + b)) FROM t",
+            ),
+            (
+                "SELECT now() AT TIME ZONE 'Europe/Berlin'",
+                "SELECT timezone('Europe/Berlin', now())",
+            ),
+            (
+                "SELECT a FROM t GROUP BY a AT TIME ZONE 'UTC' ORDER BY a AT TIME ZONE 'UTC'",
+                "SELECT a FROM t GROUP BY timezone('UTC', a) ORDER BY timezone('UTC', a)",
+            ),
+        ];
+
+        for (input, expected) in cases {
+            let stmt =
+                parse_sql_to_statement(&input.to_string(), DatabaseProtocol::PostgreSQL).unwrap();
+            assert_eq!(stmt.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_rewrite_set_time_zone() {
+        assert_eq!(
+            rewrite_set_time_zone("SET TIME ZONE 'Europe/Berlin'"),
+            "SET timezone = 'Europe/Berlin'"
+        );
+        assert_eq!(
+            rewrite_set_time_zone("set session time zone local"),
+            "SET timezone = local"
+        );
+        assert_eq!(
+            rewrite_set_time_zone("SET timezone TO 'UTC'"),
+            "SET timezone TO 'UTC'"
+        );
+    }
+
     #[test]
     fn test_no_statements_mysql() {
         let result = parse_sql_to_statement(
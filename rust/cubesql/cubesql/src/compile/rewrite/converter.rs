@@ -1,4 +1,6 @@
-use crate::compile::engine::df::scan::CubeScanNode;
+use crate::compile::engine::df::scan::{
+    CubeScanNode, MEASURE_DECIMAL_PRECISION, MEASURE_DECIMAL_SCALE,
+};
 use crate::compile::engine::provider::CubeContext;
 use crate::compile::rewrite::analysis::LogicalPlanAnalysis;
 use crate::compile::rewrite::rewriter::Rewriter;
@@ -41,6 +43,7 @@ use crate::compile::rewrite::TableScanLimit;
 use crate::compile::rewrite::TableScanProjection;
 use crate::compile::rewrite::TableScanSourceTableName;
 use crate::compile::rewrite::TableScanTableName;
+use crate::compile::rewrite::TimeDimensionDataType;
 use crate::compile::rewrite::TimeDimensionDateRange;
 use crate::compile::rewrite::TimeDimensionGranularity;
 use crate::compile::rewrite::TimeDimensionName;
@@ -49,15 +52,18 @@ use crate::compile::rewrite::UnionAlias;
 use crate::compile::rewrite::WindowFunctionExprFun;
 use crate::compile::rewrite::WindowFunctionExprWindowFrame;
 use crate::sql::auth_service::AuthContext;
+use crate::sql::ColumnType;
+use crate::transport::V1CubeMetaMeasureExt;
 use crate::CubeError;
 use cubeclient::models::{
     V1LoadRequestQuery, V1LoadRequestQueryFilterItem, V1LoadRequestQueryTimeDimension,
 };
-use datafusion::arrow::datatypes::{DataType, TimeUnit};
+use datafusion::arrow::datatypes::DataType;
 use datafusion::catalog::TableReference;
+use datafusion::error::DataFusionError;
 use datafusion::logical_plan::{
-    build_join_schema, exprlist_to_fields, normalize_cols, DFField, DFSchema, DFSchemaRef, Expr,
-    LogicalPlan,
+    build_join_schema, exprlist_to_fields, normalize_cols, Column, DFField, DFSchema, DFSchemaRef,
+    Expr, ExprRewriter, LogicalPlan,
 };
 use datafusion::physical_plan::planner::DefaultPhysicalPlanner;
 use datafusion::sql::planner::ContextProvider;
@@ -757,6 +763,52 @@ pub fn node_to_expr(
     })
 }
 
+/// Expressions evaluated on top of `CubeScan` still reference columns of the original table
+/// scan while `CubeScan` fields aren't qualified by the table name. Such columns are resolved
+/// against the actual input schema.
+struct PostProcessingColumnResolver<'a> {
+    schema: &'a DFSchema,
+}
+
+impl<'a> ExprRewriter for PostProcessingColumnResolver<'a> {
+    fn mutate(&mut self, expr: Expr) -> Result<Expr, DataFusionError> {
+        match expr {
+            Expr::Column(c) => {
+                if c.relation.is_none() || self.schema.index_of_column(&c).is_ok() {
+                    return Ok(Expr::Column(c));
+                }
+                for candidate in vec![
+                    Column::from_name(c.name.to_string()),
+                    Column::from_name(c.flat_name()),
+                ] {
+                    if self.schema.index_of_column(&candidate).is_ok() {
+                        return Ok(Expr::Column(candidate));
+                    }
+                }
+                Ok(Expr::Column(c))
+            }
+            e => Ok(e),
+        }
+    }
+}
+
+fn resolve_post_processing_expr(
+    expr: Expr,
+    schema: &DFSchema,
+    keep_name: bool,
+) -> Result<Expr, CubeError> {
+    let resolved = expr
+        .clone()
+        .rewrite(&mut PostProcessingColumnResolver { schema })?;
+    if !keep_name || resolved == expr {
+        return Ok(resolved);
+    }
+    Ok(match expr {
+        Expr::Column(_) | Expr::Alias(..) => resolved,
+        _ => Expr::Alias(Box::new(resolved), expr.name(&DFSchema::empty())?),
+    })
+}
+
 impl LanguageToLogicalPlanConverter {
     pub fn new(
         best_expr: RecExpr<LogicalPlanLanguage>,
@@ -784,6 +836,10 @@ impl LanguageToLogicalPlanConverter {
             LogicalPlanLanguage::Projection(params) => {
                 let expr = match_expr_list_node!(node_by_id, to_expr, params[0], ProjectionExpr);
                 let input = Arc::new(self.to_logical_plan(params[1])?);
+                let expr = expr
+                    .into_iter()
+                    .map(|e| resolve_post_processing_expr(e, input.schema(), true))
+                    .collect::<Result<Vec<_>, _>>()?;
                 let alias = match_data_node!(node_by_id, params[2], ProjectionAlias);
                 let input_schema = DFSchema::new(exprlist_to_fields(&expr, input.schema())?)?;
                 let schema = match alias {
@@ -800,6 +856,7 @@ impl LanguageToLogicalPlanConverter {
             LogicalPlanLanguage::Filter(params) => {
                 let predicate = self.to_expr(params[0])?;
                 let input = Arc::new(self.to_logical_plan(params[1])?);
+                let predicate = resolve_post_processing_expr(predicate, input.schema(), false)?;
                 LogicalPlan::Filter { predicate, input }
             }
             LogicalPlanLanguage::Window(params) => {
@@ -838,6 +895,10 @@ impl LanguageToLogicalPlanConverter {
             LogicalPlanLanguage::Sort(params) => {
                 let expr = match_expr_list_node!(node_by_id, to_expr, params[0], SortExp);
                 let input = Arc::new(self.to_logical_plan(params[1])?);
+                let expr = expr
+                    .into_iter()
+                    .map(|e| resolve_post_processing_expr(e, input.schema(), false))
+                    .collect::<Result<Vec<_>, _>>()?;
                 LogicalPlan::Sort { expr, input }
             }
             LogicalPlanLanguage::Join(params) => {
@@ -987,11 +1048,29 @@ impl LanguageToLogicalPlanConverter {
                                     );
                                     let expr = self.to_expr(measure_params[1])?;
                                     query_measures.push(measure.to_string());
+                                    let data_type = self
+                                        .cube_context
+                                        .meta
+                                        .find_measure_with_name(measure.to_string())
+                                        .map(|m| match m.get_sql_type() {
+                                            _ if m.is_decimal() => DataType::Decimal(
+                                                MEASURE_DECIMAL_PRECISION,
+                                                MEASURE_DECIMAL_SCALE,
+                                            ),
+                                            ColumnType::Int8
+                                            | ColumnType::Int32
+                                            | ColumnType::Int64 => DataType::Int64,
+                                            ColumnType::String | ColumnType::VarStr => {
+                                                DataType::Utf8
+                                            }
+                                            _ => DataType::Float64,
+                                        })
+                                        .unwrap_or(DataType::Float64);
                                     fields.push(DFField::new(
                                         None,
                                         // TODO empty schema
                                         &expr.name(&DFSchema::empty())?,
-                                        DataType::Int64,
+                                        data_type,
                                         // TODO actually nullable. Just to fit tests
                                         false,
                                     ));
@@ -1011,6 +1090,11 @@ impl LanguageToLogicalPlanConverter {
                                         TimeDimensionDateRange
                                     );
                                     let expr = self.to_expr(params[3])?;
+                                    let data_type = match_data_node!(
+                                        node_by_id,
+                                        params[4],
+                                        TimeDimensionDataType
+                                    );
                                     query_time_dimensions.push(V1LoadRequestQueryTimeDimension {
                                         dimension: dimension.to_string(),
                                         granularity: granularity.clone(),
@@ -1028,7 +1112,7 @@ impl LanguageToLogicalPlanConverter {
                                             None,
                                             // TODO empty schema
                                             &expr.name(&DFSchema::empty())?,
-                                            DataType::Timestamp(TimeUnit::Nanosecond, None),
+                                            data_type,
                                             // TODO actually nullable. Just to fit tests
                                             false,
                                         ));
@@ -1204,6 +1288,7 @@ impl LanguageToLogicalPlanConverter {
                         query.limit =
                             match_data_node!(node_by_id, cube_scan_params[4], CubeScanLimit)
                                 .map(|n| n as i32);
+                        query.timezone = self.cube_context.session_state.time_zone();
 
                         let aliases =
                             match_data_node!(node_by_id, cube_scan_params[6], CubeScanAliases);
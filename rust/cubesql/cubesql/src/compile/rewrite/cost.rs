@@ -5,9 +5,12 @@ use egg::{CostFunction, Id};
 pub struct BestCubePlan;
 
 impl CostFunction<LogicalPlanLanguage> for BestCubePlan {
+    // Replacers go first: post-processing rules put a member replacer into the same e-class as
+    // the plan they start from, and a plan with a replacer left can't be converted back.
     type Cost = (
-        /* Cube nodes */ i64,
         /* Replacers */ i64,
+        /* Joins */ i64,
+        /* Cube nodes */ i64,
         /* Structure points */ i64,
         /* AST size */ usize,
     );
@@ -23,10 +26,21 @@ impl CostFunction<LogicalPlanLanguage> for BestCubePlan {
             _ => 0,
         };
 
+        // Replacers that weren't fully rewritten can't be converted back to a plan so any
+        // plan without them should win even if it pushes fewer members down to Cube.
         let this_replacers = match enode {
             LogicalPlanLanguage::MemberReplacer(_) => 1,
             LogicalPlanLanguage::FilterReplacer(_) => 1,
             LogicalPlanLanguage::TimeDimensionDateRangeReplacer(_) => 1,
+            LogicalPlanLanguage::OrderReplacer(_) => 1,
+            LogicalPlanLanguage::ColumnAliasReplacer(_) => 1,
+            _ => 0,
+        };
+
+        // Joins of cubes should be evaluated by Cube even if it's only to report why it can't be
+        // done.
+        let this_joins = match enode {
+            LogicalPlanLanguage::Join(_) => 1,
             _ => 0,
         };
 
@@ -36,12 +50,20 @@ impl CostFunction<LogicalPlanLanguage> for BestCubePlan {
             _ => 0,
         };
         enode.children().iter().fold(
-            (this_cube_nodes, this_replacers, this_cube_structure, 1),
-            |(cube_nodes, replacers, structure, nodes), id| {
-                let (child_cube_nodes, child_replacers, child_structure, child_nodes) = costs(*id);
+            (
+                this_replacers,
+                this_joins,
+                this_cube_nodes,
+                this_cube_structure,
+                1,
+            ),
+            |(replacers, joins, cube_nodes, structure, nodes), id| {
+                let (child_replacers, child_joins, child_cube_nodes, child_structure, child_nodes) =
+                    costs(*id);
                 (
-                    cube_nodes + child_cube_nodes,
                     replacers + child_replacers,
+                    joins + child_joins,
+                    cube_nodes + child_cube_nodes,
                     structure + child_structure,
                     nodes + child_nodes,
                 )
@@ -2,7 +2,7 @@ mod analysis;
 pub mod converter;
 mod cost;
 pub mod language;
-mod rewriter;
+pub mod rewriter;
 mod rules;
 
 use crate::compile::rewrite::analysis::LogicalPlanAnalysis;
@@ -216,6 +216,7 @@ crate::plan_to_language! {
             granularity: Option<String>,
             date_range: Option<Vec<String>>,
             expr: Arc<Expr>,
+            data_type: DataType,
         },
         MemberAlias {
             name: String,
@@ -231,7 +232,7 @@ crate::plan_to_language! {
         },
         FilterReplacer {
             filters: Vec<LogicalPlan>,
-            cube: Option<String>,
+            cubes: Vec<(String, String)>,
         },
         OrderReplacer {
             sort_expr: Vec<LogicalPlan>,
@@ -243,6 +244,9 @@ crate::plan_to_language! {
             aliases: Vec<(String, String)>,
             cube: Option<String>,
         },
+        JoinedCubes {
+            relations: Vec<(String, String)>,
+        },
     }
 }
 
@@ -265,23 +269,61 @@ macro_rules! var {
     };
 }
 
-pub struct WithColumnRelation(String);
+pub struct WithColumnRelation(Option<String>);
 
 impl ExprRewriter for WithColumnRelation {
     fn mutate(&mut self, expr: Expr) -> Result<Expr, DataFusionError> {
         match expr {
             Expr::Column(c) => Ok(Expr::Column(Column {
                 name: c.name.to_string(),
-                relation: c.relation.or_else(|| Some(self.0.to_string())),
+                relation: c.relation.or_else(|| self.0.clone()),
             })),
             e => Ok(e),
         }
     }
 }
 
+/// `(relation, cube name)` pairs of a `CubeScan` source: either a single cube or cubes joined
+/// together by `JoinRules`.
+fn cube_scan_source_cubes(
+    egraph: &EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>,
+    source: Id,
+) -> Vec<Vec<(String, String)>> {
+    var_iter!(egraph[source], TableScanSourceTableName)
+        .map(|name| vec![(name.to_string(), name.to_string())])
+        .chain(var_iter!(egraph[source], JoinedCubesRelations).cloned())
+        .collect()
+}
+
+/// Relation used to qualify columns of a `CubeScan` source. Columns of joined cubes are always
+/// qualified by the planner so there's no default relation for them.
+fn cube_scan_source_relation(cubes: &[(String, String)]) -> Option<String> {
+    if cubes.len() == 1 {
+        Some(cubes[0].1.to_string())
+    } else {
+        None
+    }
+}
+
+/// Names of cubes `column` may belong to.
+fn column_cube_names(cubes: &[(String, String)], column: &Column) -> Vec<String> {
+    cubes
+        .iter()
+        .filter(|(relation, _)| {
+            cubes.len() == 1
+                || column
+                    .relation
+                    .as_ref()
+                    .map(|r| r.eq_ignore_ascii_case(relation))
+                    .unwrap_or(true)
+        })
+        .map(|(_, cube_name)| cube_name.to_string())
+        .collect()
+}
+
 fn column_name_to_member_name(
     member_name_to_expr: Vec<(String, Expr)>,
-    table_name: String,
+    table_name: Option<String>,
 ) -> HashMap<String, String> {
     let mut relation = WithColumnRelation(table_name);
     member_name_to_expr
@@ -290,7 +332,7 @@ fn column_name_to_member_name(
         .collect::<HashMap<_, _>>()
 }
 
-fn referenced_columns(referenced_expr: Vec<Expr>, table_name: String) -> Vec<String> {
+fn referenced_columns(referenced_expr: Vec<Expr>, table_name: Option<String>) -> Vec<String> {
     let mut relation = WithColumnRelation(table_name);
     referenced_expr
         .into_iter()
@@ -306,11 +348,7 @@ fn expr_column_name_with_relation(expr: Expr, relation: &mut WithColumnRelation)
 }
 
 fn expr_column_name(expr: Expr, cube: &Option<String>) -> String {
-    if let Some(cube) = cube.as_ref() {
-        expr_column_name_with_relation(expr, &mut WithColumnRelation(cube.to_string()))
-    } else {
-        expr.name(&DFSchema::empty()).unwrap()
-    }
+    expr_column_name_with_relation(expr, &mut WithColumnRelation(cube.clone()))
 }
 
 pub fn rewrite(
@@ -495,6 +533,10 @@ fn column_expr(column: impl Display) -> String {
     format!("(ColumnExpr {})", column)
 }
 
+fn cast_expr(expr: impl Display, data_type: impl Display) -> String {
+    format!("(CastExpr {} {})", expr, data_type)
+}
+
 fn alias_expr(column: impl Display, alias: impl Display) -> String {
     format!("(AliasExpr {} {})", column, alias)
 }
@@ -507,6 +549,20 @@ fn projection(expr: impl Display, input: impl Display, alias: impl Display) -> S
     format!("(Projection {} {} {})", expr, input, alias)
 }
 
+fn join(
+    left: impl Display,
+    right: impl Display,
+    left_on: impl Display,
+    right_on: impl Display,
+    join_type: impl Display,
+    join_constraint: impl Display,
+) -> String {
+    format!(
+        "(Join {} {} {} {} {} {})",
+        left, right, left_on, right_on, join_type, join_constraint
+    )
+}
+
 fn sort(expr: impl Display, input: impl Display) -> String {
     format!("(Sort {} {})", expr, input)
 }
@@ -603,10 +659,11 @@ fn time_dimension_expr(
     granularity: impl Display,
     date_range: impl Display,
     expr: impl Display,
+    data_type: impl Display,
 ) -> String {
     format!(
-        "(TimeDimension {} {} {} {})",
-        name, granularity, date_range, expr
+        "(TimeDimension {} {} {} {} {})",
+        name, granularity, date_range, expr, data_type
     )
 }
 
@@ -1,9 +1,10 @@
 use crate::compile::engine::provider::CubeContext;
 use crate::compile::rewrite::analysis::LogicalPlanAnalysis;
-use crate::compile::rewrite::converter::LanguageToLogicalPlanConverter;
+use crate::compile::rewrite::converter::{is_expr_node, LanguageToLogicalPlanConverter};
 use crate::compile::rewrite::cost::BestCubePlan;
 use crate::compile::rewrite::rules::dates::DateRules;
 use crate::compile::rewrite::rules::filters::FilterRules;
+use crate::compile::rewrite::rules::joins::JoinRules;
 use crate::compile::rewrite::rules::members::MemberRules;
 use crate::compile::rewrite::rules::order::OrderRules;
 use crate::compile::rewrite::LogicalPlanLanguage;
@@ -11,9 +12,22 @@ use crate::sql::AuthContext;
 use crate::CubeError;
 use datafusion::logical_plan::LogicalPlan;
 use datafusion::physical_plan::planner::DefaultPhysicalPlanner;
-use egg::{EGraph, Extractor, Id, Rewrite, Runner};
+use egg::{EGraph, Extractor, Id, Language, RecExpr, Rewrite, Runner};
 use std::sync::Arc;
 
+/// Diagnostics collected while rewriting a query. Shown by `EXPLAIN VERBOSE`.
+#[derive(Debug, Default, Clone)]
+pub struct RewriteTrace {
+    /// Rules applied on every runner iteration.
+    pub iterations: Vec<String>,
+    pub stop_reason: Option<String>,
+    /// `CubeScan` nodes of the extracted plan.
+    pub cube_scans: Vec<String>,
+    /// Heads of replacer lists which no rule was able to rewrite. Only collected if the query
+    /// wasn't pushed down to Cube.
+    pub unmatched_exprs: Vec<String>,
+}
+
 pub struct Rewriter {
     graph: EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>,
     cube_context: Arc<CubeContext>,
@@ -44,27 +58,119 @@ impl Rewriter {
         &mut self,
         root: Id,
         auth_context: Arc<AuthContext>,
+    ) -> Result<LogicalPlan, CubeError> {
+        self.find_best_plan_with_trace(root, auth_context, &mut RewriteTrace::default())
+    }
+
+    pub fn find_best_plan_with_trace(
+        &mut self,
+        root: Id,
+        auth_context: Arc<AuthContext>,
+        trace: &mut RewriteTrace,
     ) -> Result<LogicalPlan, CubeError> {
         let runner = self.rewrite_runner();
         let rules = self.rewrite_rules();
         let runner = runner.run(rules.iter());
         log::debug!("Iterations: {:?}", runner.iterations);
+        trace.iterations = runner
+            .iterations
+            .iter()
+            .enumerate()
+            .map(|(i, iteration)| {
+                let mut applied = iteration
+                    .applied
+                    .iter()
+                    .map(|(rule, count)| format!("{} x{}", rule, count))
+                    .collect::<Vec<_>>();
+                if applied.is_empty() {
+                    applied.push("-".to_string());
+                }
+                format!(
+                    "#{}: {} nodes, {} classes, applied: {}",
+                    i,
+                    iteration.egraph_nodes,
+                    iteration.egraph_classes,
+                    applied.join(", ")
+                )
+            })
+            .collect();
+        trace.stop_reason = runner.stop_reason.as_ref().map(|r| format!("{:?}", r));
         let extractor = Extractor::new(&runner.egraph, BestCubePlan);
         let (_, best) = extractor.find_best(root);
         let new_root = Id::from(best.as_ref().len() - 1);
         log::debug!("Best: {:?}", best);
+        trace.cube_scans = best
+            .as_ref()
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node, LogicalPlanLanguage::CubeScan(_)))
+            .map(|(i, _)| sub_expr(&best, Id::from(i)).pretty(80))
+            .collect();
+        if best
+            .as_ref()
+            .iter()
+            .any(|node| matches!(node, LogicalPlanLanguage::TableScan(_)))
+        {
+            trace.unmatched_exprs =
+                self.unmatched_exprs(&runner.egraph, &extractor, auth_context.clone());
+        }
         self.graph = runner.egraph.clone();
         let converter =
             LanguageToLogicalPlanConverter::new(best, self.cube_context.clone(), auth_context);
         converter.to_logical_plan(new_root)
     }
 
+    /// Replacers rewrite their list element by element so an e-class consisting of replacers
+    /// only is stuck on the head of its list.
+    fn unmatched_exprs(
+        &self,
+        egraph: &EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>,
+        extractor: &Extractor<BestCubePlan, LogicalPlanLanguage, LogicalPlanAnalysis>,
+        auth_context: Arc<AuthContext>,
+    ) -> Vec<String> {
+        let mut result = Vec::new();
+        for class in egraph.classes() {
+            if !class.nodes.iter().all(is_replacer_node) {
+                continue;
+            }
+            for node in class.nodes.iter() {
+                let list_class = &egraph[node.children()[0]];
+                for list in list_class.nodes.iter() {
+                    let head = match list.children().first() {
+                        Some(head) => egraph.find(*head),
+                        None => continue,
+                    };
+                    let (_, expr) = extractor.find_best(head);
+                    let expr_root = Id::from(expr.as_ref().len() - 1);
+                    let rendered = if is_expr_node(&expr[expr_root]) {
+                        let converter = LanguageToLogicalPlanConverter::new(
+                            expr.clone(),
+                            self.cube_context.clone(),
+                            auth_context.clone(),
+                        );
+                        converter
+                            .to_expr(expr_root)
+                            .map(|e| format!("{:?}", e))
+                            .unwrap_or_else(|_| expr.pretty(80))
+                    } else {
+                        expr.pretty(80)
+                    };
+                    if !result.contains(&rendered) {
+                        result.push(rendered);
+                    }
+                }
+            }
+        }
+        result
+    }
+
     pub fn rewrite_rules(&self) -> Vec<Rewrite<LogicalPlanLanguage, LogicalPlanAnalysis>> {
         let rules: Vec<Box<dyn RewriteRules>> = vec![
             Box::new(MemberRules::new(self.cube_context.clone())),
             Box::new(FilterRules::new(self.cube_context.clone())),
             Box::new(DateRules::new(self.cube_context.clone())),
             Box::new(OrderRules::new(self.cube_context.clone())),
+            Box::new(JoinRules::new(self.cube_context.clone())),
         ];
         let mut rewrites = Vec::new();
         for r in rules {
@@ -74,6 +180,33 @@ impl Rewriter {
     }
 }
 
+fn is_replacer_node(node: &LogicalPlanLanguage) -> bool {
+    matches!(
+        node,
+        LogicalPlanLanguage::MemberReplacer(_)
+            | LogicalPlanLanguage::FilterReplacer(_)
+            | LogicalPlanLanguage::TimeDimensionDateRangeReplacer(_)
+            | LogicalPlanLanguage::OrderReplacer(_)
+            | LogicalPlanLanguage::ColumnAliasReplacer(_)
+    )
+}
+
+fn sub_expr(expr: &RecExpr<LogicalPlanLanguage>, id: Id) -> RecExpr<LogicalPlanLanguage> {
+    fn add(
+        expr: &RecExpr<LogicalPlanLanguage>,
+        id: Id,
+        result: &mut RecExpr<LogicalPlanLanguage>,
+    ) -> Id {
+        let node = expr[id]
+            .clone()
+            .map_children(|child| add(expr, child, result));
+        result.add(node)
+    }
+    let mut result = RecExpr::default();
+    add(expr, id, &mut result);
+    result
+}
+
 pub trait RewriteRules {
     fn rewrite_rules(&self) -> Vec<Rewrite<LogicalPlanLanguage, LogicalPlanAnalysis>>;
 }
@@ -1,14 +1,21 @@
 use crate::compile::engine::provider::CubeContext;
+use crate::compile::engine::udf::is_session_time_zone;
 use crate::compile::rewrite::analysis::LogicalPlanAnalysis;
 use crate::compile::rewrite::rewriter::RewriteRules;
+use crate::compile::rewrite::CastExprDataType;
+use crate::compile::rewrite::LiteralExprValue;
 use crate::compile::rewrite::LogicalPlanLanguage;
 use crate::compile::rewrite::{binary_expr, column_expr, literal_expr, rewrite};
+use crate::compile::rewrite::{cast_expr, transforming_rewrite};
 use crate::compile::rewrite::{fun_expr, literal_string, to_day_interval_expr, udf_expr};
-use egg::Rewrite;
+use crate::var_iter;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::scalar::ScalarValue;
+use egg::{EGraph, Rewrite, Subst};
 use std::sync::Arc;
 
 pub struct DateRules {
-    _cube_context: Arc<CubeContext>,
+    cube_context: Arc<CubeContext>,
 }
 
 impl RewriteRules for DateRules {
@@ -214,14 +221,121 @@ impl RewriteRules for DateRules {
                     vec![literal_string("day"), column_expr("?column")],
                 ),
             ),
+            // Postgres: col::date, CAST(col AS DATE)
+            transforming_rewrite(
+                "cast-to-date-to-date-trunc",
+                cast_expr(column_expr("?column"), "?data_type"),
+                cast_expr(
+                    fun_expr(
+                        "DateTrunc",
+                        vec![literal_string("day"), column_expr("?column")],
+                    ),
+                    "?data_type",
+                ),
+                self.is_cast_to("?data_type", |data_type| {
+                    matches!(data_type, DataType::Date32 | DataType::Date64)
+                }),
+            ),
+            // Postgres: DATE_TRUNC('month', col::timestamp)
+            transforming_rewrite(
+                "date-trunc-cast-to-timestamp",
+                fun_expr(
+                    "DateTrunc",
+                    vec![
+                        literal_expr("?granularity"),
+                        cast_expr(column_expr("?column"), "?data_type"),
+                    ],
+                ),
+                fun_expr(
+                    "DateTrunc",
+                    vec![literal_expr("?granularity"), column_expr("?column")],
+                ),
+                self.is_cast_to("?data_type", |data_type| {
+                    matches!(data_type, DataType::Timestamp(_, None))
+                }),
+            ),
+            // Postgres: DATE_TRUNC('month', col AT TIME ZONE '<session time zone>'), see parser
+            // for AT TIME ZONE
+            transforming_rewrite(
+                "date-trunc-session-timezone",
+                fun_expr(
+                    "DateTrunc",
+                    vec![
+                        literal_expr("?granularity"),
+                        udf_expr(
+                            "timezone",
+                            vec![literal_expr("?zone"), column_expr("?column")],
+                        ),
+                    ],
+                ),
+                fun_expr(
+                    "DateTrunc",
+                    vec![literal_expr("?granularity"), column_expr("?column")],
+                ),
+                self.is_session_time_zone("?zone"),
+            ),
+            transforming_rewrite(
+                "cast-to-date-session-timezone",
+                cast_expr(
+                    udf_expr(
+                        "timezone",
+                        vec![literal_expr("?zone"), column_expr("?column")],
+                    ),
+                    "?data_type",
+                ),
+                cast_expr(
+                    fun_expr(
+                        "DateTrunc",
+                        vec![literal_string("day"), column_expr("?column")],
+                    ),
+                    "?data_type",
+                ),
+                self.is_session_time_zone_date_cast("?zone", "?data_type"),
+            ),
         ]
     }
 }
 
 impl DateRules {
     pub fn new(cube_context: Arc<CubeContext>) -> Self {
-        Self {
-            _cube_context: cube_context,
+        Self { cube_context }
+    }
+
+    fn is_cast_to(
+        &self,
+        data_type_var: &'static str,
+        predicate: fn(&DataType) -> bool,
+    ) -> impl Fn(&mut EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>, &mut Subst) -> bool {
+        let data_type_var = data_type_var.parse().unwrap();
+        move |egraph, subst| {
+            var_iter!(egraph[subst[data_type_var]], CastExprDataType).any(predicate)
+        }
+    }
+
+    fn is_session_time_zone(
+        &self,
+        zone_var: &'static str,
+    ) -> impl Fn(&mut EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>, &mut Subst) -> bool {
+        let zone_var = zone_var.parse().unwrap();
+        let session_state = self.cube_context.session_state.clone();
+        move |egraph, subst| {
+            let session_time_zone = session_state.time_zone();
+            var_iter!(egraph[subst[zone_var]], LiteralExprValue).any(|zone| match zone {
+                ScalarValue::Utf8(Some(zone)) => is_session_time_zone(zone, &session_time_zone),
+                _ => false,
+            })
         }
     }
+
+    fn is_session_time_zone_date_cast(
+        &self,
+        zone_var: &'static str,
+        data_type_var: &'static str,
+    ) -> impl Fn(&mut EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>, &mut Subst) -> bool {
+        let is_session_time_zone = self.is_session_time_zone(zone_var);
+        let is_date_cast = self.is_cast_to(data_type_var, |data_type| {
+            matches!(data_type, DataType::Date32 | DataType::Date64)
+        });
+        move |egraph, subst| is_session_time_zone(egraph, subst) && is_date_cast(egraph, subst)
+    }
 }
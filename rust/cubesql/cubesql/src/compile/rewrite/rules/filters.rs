@@ -2,12 +2,12 @@ use crate::compile::engine::provider::CubeContext;
 use crate::compile::rewrite::analysis::{ConstantData, LogicalPlanAnalysis};
 use crate::compile::rewrite::rewriter::RewriteRules;
 use crate::compile::rewrite::FilterMemberValues;
-use crate::compile::rewrite::FilterReplacerCube;
+use crate::compile::rewrite::FilterReplacerCubes;
 use crate::compile::rewrite::InListExprNegated;
 use crate::compile::rewrite::LiteralExprValue;
 use crate::compile::rewrite::LogicalPlanLanguage;
 use crate::compile::rewrite::SegmentMemberMember;
-use crate::compile::rewrite::TableScanSourceTableName;
+use crate::compile::rewrite::TimeDimensionDataType;
 use crate::compile::rewrite::TimeDimensionDateRange;
 use crate::compile::rewrite::TimeDimensionDateRangeReplacerDateRange;
 use crate::compile::rewrite::TimeDimensionDateRangeReplacerMember;
@@ -15,8 +15,9 @@ use crate::compile::rewrite::TimeDimensionGranularity;
 use crate::compile::rewrite::TimeDimensionName;
 use crate::compile::rewrite::{between_expr, FilterMemberMember};
 use crate::compile::rewrite::{
-    binary_expr, column_expr, cube_scan, cube_scan_filters, filter, filter_member, filter_op,
-    filter_op_filters, filter_replacer, literal_expr, rewrite, transforming_rewrite,
+    binary_expr, column_cube_names, column_expr, cube_scan, cube_scan_filters,
+    cube_scan_source_cubes, filter, filter_member, filter_op, filter_op_filters, filter_replacer,
+    literal_expr, rewrite, transforming_rewrite,
 };
 use crate::compile::rewrite::{
     cube_scan_filters_empty_tail, cube_scan_members, dimension_expr, measure_expr,
@@ -30,6 +31,7 @@ use crate::transport::MemberType;
 use crate::var;
 use crate::var_iter;
 use chrono::{SecondsFormat, TimeZone, Utc};
+use datafusion::arrow::datatypes::{DataType, TimeUnit};
 use datafusion::logical_plan::{Column, Operator};
 use datafusion::scalar::ScalarValue;
 use egg::{EGraph, Rewrite, Subst};
@@ -344,7 +346,13 @@ impl RewriteRules for FilterRules {
                     "?time_dimension_date_range",
                 ),
                 cube_scan_members(
-                    time_dimension_expr("?member", "?granularity", "?date_range", "?expr"),
+                    time_dimension_expr(
+                        "?member",
+                        "?granularity",
+                        "?date_range",
+                        "?expr",
+                        "?data_type",
+                    ),
                     "?members",
                 ),
                 self.push_down_time_dimension_replacer_new_time_dimension(
@@ -355,6 +363,7 @@ impl RewriteRules for FilterRules {
                     "?granularity",
                     "?date_range",
                     "?expr",
+                    "?data_type",
                 ),
             ),
             rewrite(
@@ -393,14 +402,26 @@ impl RewriteRules for FilterRules {
                 "time-dimension-date-range-replacer-time-dimension",
                 time_dimension_date_range_replacer(
                     cube_scan_members(
-                        time_dimension_expr("?member", "?granularity", "?date_range", "?expr"),
+                        time_dimension_expr(
+                            "?member",
+                            "?granularity",
+                            "?date_range",
+                            "?expr",
+                            "?data_type",
+                        ),
                         "?tail",
                     ),
                     "?time_dimension_member",
                     "?time_dimension_date_range",
                 ),
                 cube_scan_members(
-                    time_dimension_expr("?member", "?granularity", "?output_date_range", "?expr"),
+                    time_dimension_expr(
+                        "?member",
+                        "?granularity",
+                        "?output_date_range",
+                        "?expr",
+                        "?data_type",
+                    ),
                     "?tail",
                 ),
                 self.replace_time_dimension_date_range(
@@ -430,15 +451,14 @@ impl FilterRules {
         let exp_var = var!(exp_var);
         let cube_var = var!(cube_var);
         move |egraph, subst| {
-            for table_name in var_iter!(egraph[subst[table_name_var]], TableScanSourceTableName) {
+            for cubes in cube_scan_source_cubes(egraph, subst[table_name_var]) {
                 if let Some(_referenced_expr) = &egraph.index(subst[exp_var]).data.referenced_expr {
-                    let table_name = table_name.to_string();
                     // TODO check referenced_expr
                     subst.insert(
                         cube_var,
-                        egraph.add(LogicalPlanLanguage::FilterReplacerCube(FilterReplacerCube(
-                            Some(table_name.to_string()),
-                        ))),
+                        egraph.add(LogicalPlanLanguage::FilterReplacerCubes(
+                            FilterReplacerCubes(cubes),
+                        )),
                     );
                     return true;
                 }
@@ -465,15 +485,31 @@ impl FilterRules {
         let filter_op_var = filter_op_var.parse().unwrap();
         let filter_values_var = filter_values_var.parse().unwrap();
         let meta_context = self.cube_context.meta.clone();
+        // Timestamps are local time of the session time zone which is passed to Cube
+        let format_timestamp: fn(i64) -> String =
+            if self.cube_context.session_state.time_zone().is_some() {
+                |nanos| {
+                    Utc.timestamp_nanos(nanos)
+                        .format("%Y-%m-%dT%H:%M:%S%.3f")
+                        .to_string()
+                }
+            } else {
+                |nanos| {
+                    Utc.timestamp_nanos(nanos)
+                        .to_rfc3339_opts(SecondsFormat::Millis, true)
+                }
+            };
         move |egraph, subst| {
-            for cube in var_iter!(egraph[subst[cube_var]], FilterReplacerCube) {
+            for cubes in var_iter!(egraph[subst[cube_var]], FilterReplacerCubes) {
                 for expr_op in var_iter!(egraph[subst[op_var]], BinaryExprOp) {
                     for literal in var_iter!(egraph[subst[literal_var]], LiteralExprValue) {
-                        if let Some(cube) = cube
-                            .as_ref()
-                            .and_then(|cube| meta_context.find_cube_with_name(cube.to_string()))
-                        {
+                        for cube in cubes.iter().filter_map(|(_, cube)| {
+                            meta_context.find_cube_with_name(cube.to_string())
+                        }) {
                             for column in var_iter!(egraph[subst[column_var]], ColumnExprColumn) {
+                                if !is_cube_column(cubes, &cube.name, column) {
+                                    continue;
+                                }
                                 let member_name = format!("{}.{}", cube.name, column.name);
                                 if let Some(member_type) = cube.member_type(&member_name) {
                                     let op = match expr_op {
@@ -509,12 +545,8 @@ impl FilterRules {
                                         ScalarValue::Boolean(Some(value)) => value.to_string(),
                                         ScalarValue::Float64(Some(value)) => value.to_string(),
                                         ScalarValue::TimestampNanosecond(Some(value)) => {
-                                            let minus_one = Utc
-                                                .timestamp_nanos(*value - 1000)
-                                                .to_rfc3339_opts(SecondsFormat::Millis, true);
-                                            let value = Utc
-                                                .timestamp_nanos(*value)
-                                                .to_rfc3339_opts(SecondsFormat::Millis, true);
+                                            let minus_one = format_timestamp(*value - 1000);
+                                            let value = format_timestamp(*value);
 
                                             match expr_op {
                                                 Operator::Lt => minus_one,
@@ -577,18 +609,20 @@ impl FilterRules {
         let segment_member_var = segment_member_var.parse().unwrap();
         let meta_context = self.cube_context.meta.clone();
         move |egraph, subst| {
-            for cube in var_iter!(egraph[subst[cube_var]], FilterReplacerCube) {
+            for cubes in var_iter!(egraph[subst[cube_var]], FilterReplacerCubes) {
                 for expr_op in var_iter!(egraph[subst[op_var]], BinaryExprOp) {
                     for literal in var_iter!(egraph[subst[literal_var]], LiteralExprValue) {
-                        if let Some(cube) = cube
-                            .as_ref()
-                            .and_then(|cube| meta_context.find_cube_with_name(cube.to_string()))
-                        {
+                        for cube in cubes.iter().filter_map(|(_, cube)| {
+                            meta_context.find_cube_with_name(cube.to_string())
+                        }) {
                             if expr_op == &Operator::Eq {
                                 if literal == &ScalarValue::Boolean(Some(true)) {
                                     for column in
                                         var_iter!(egraph[subst[column_var]], ColumnExprColumn)
                                     {
+                                        if !is_cube_column(cubes, &cube.name, column) {
+                                            continue;
+                                        }
                                         let member_name = format!("{}.{}", cube.name, column.name);
                                         if let Some(_) = cube
                                             .segments
@@ -639,10 +673,10 @@ impl FilterRules {
         let filter_values_var = var!(filter_values_var);
         let meta_context = self.cube_context.meta.clone();
         move |egraph, subst| {
-            for cube in var_iter!(egraph[subst[cube_var]], FilterReplacerCube) {
-                if let Some(cube) = cube
-                    .as_ref()
-                    .and_then(|cube| meta_context.find_cube_with_name(cube.to_string()))
+            for cubes in var_iter!(egraph[subst[cube_var]], FilterReplacerCubes) {
+                for cube in cubes
+                    .iter()
+                    .filter_map(|(_, cube)| meta_context.find_cube_with_name(cube.to_string()))
                 {
                     if let Some(ConstantData::Intermediate(list)) =
                         &egraph[subst[list_var]].data.constant
@@ -653,6 +687,9 @@ impl FilterRules {
                             .collect::<Vec<_>>();
 
                         for column in var_iter!(egraph[subst[column_var]], ColumnExprColumn) {
+                            if !is_cube_column(cubes, &cube.name, column) {
+                                continue;
+                            }
                             let member_name = format!("{}.{}", cube.name, column.name);
                             if cube.contains_member(&member_name) {
                                 for negated in
@@ -722,12 +759,15 @@ impl FilterRules {
         let filter_values_var = var!(filter_values_var);
         let meta_context = self.cube_context.meta.clone();
         move |egraph, subst| {
-            for cube in var_iter!(egraph[subst[cube_var]], FilterReplacerCube) {
-                if let Some(cube) = cube
-                    .as_ref()
-                    .and_then(|cube| meta_context.find_cube_with_name(cube.to_string()))
+            for cubes in var_iter!(egraph[subst[cube_var]], FilterReplacerCubes) {
+                for cube in cubes
+                    .iter()
+                    .filter_map(|(_, cube)| meta_context.find_cube_with_name(cube.to_string()))
                 {
                     for column in var_iter!(egraph[subst[column_var]], ColumnExprColumn) {
+                        if !is_cube_column(cubes, &cube.name, column) {
+                            continue;
+                        }
                         let member_name = format!("{}.{}", cube.name, column.name);
                         if cube.contains_member(&member_name) {
                             subst.insert(
@@ -786,12 +826,15 @@ impl FilterRules {
         let filter_values_var = var!(filter_values_var);
         let meta_context = self.cube_context.meta.clone();
         move |egraph, subst| {
-            for cube in var_iter!(egraph[subst[cube_var]], FilterReplacerCube) {
-                if let Some(cube) = cube
-                    .as_ref()
-                    .and_then(|cube| meta_context.find_cube_with_name(cube.to_string()))
+            for cubes in var_iter!(egraph[subst[cube_var]], FilterReplacerCubes) {
+                for cube in cubes
+                    .iter()
+                    .filter_map(|(_, cube)| meta_context.find_cube_with_name(cube.to_string()))
                 {
                     for column in var_iter!(egraph[subst[column_var]], ColumnExprColumn) {
+                        if !is_cube_column(cubes, &cube.name, column) {
+                            continue;
+                        }
                         let member_name = format!("{}.{}", cube.name, column.name);
                         if let Some(_) = cube.lookup_dimension(&member_name) {
                             for negated in var_iter!(egraph[subst[negated_var]], BetweenExprNegated)
@@ -928,6 +971,7 @@ impl FilterRules {
         granularity_var: &'static str,
         date_range_var: &'static str,
         expr_var: &'static str,
+        data_type_var: &'static str,
     ) -> impl Fn(&mut EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>, &mut Subst) -> bool {
         let members_var = var!(members_var);
         let time_dimension_member_var = var!(time_dimension_member_var);
@@ -936,6 +980,7 @@ impl FilterRules {
         let granularity_var = var!(granularity_var);
         let date_range_var = var!(date_range_var);
         let expr_var = var!(expr_var);
+        let data_type_var = var!(data_type_var);
         move |egraph, subst| {
             for member in var_iter!(
                 egraph[subst[time_dimension_member_var]],
@@ -984,6 +1029,16 @@ impl FilterRules {
                             egraph.add(LogicalPlanLanguage::ColumnExpr([column])),
                         );
 
+                        subst.insert(
+                            data_type_var,
+                            egraph.add(LogicalPlanLanguage::TimeDimensionDataType(
+                                TimeDimensionDataType(DataType::Timestamp(
+                                    TimeUnit::Nanosecond,
+                                    None,
+                                )),
+                            )),
+                        );
+
                         return true;
                     }
                 }
@@ -1068,6 +1123,12 @@ impl FilterRules {
     }
 }
 
+fn is_cube_column(cubes: &[(String, String)], cube_name: &str, column: &Column) -> bool {
+    column_cube_names(cubes, column)
+        .iter()
+        .any(|c| c.eq_ignore_ascii_case(cube_name))
+}
+
 fn filter_flatten_rewrite_left(
     op: impl Display + Copy,
 ) -> Rewrite<LogicalPlanLanguage, LogicalPlanAnalysis> {
@@ -0,0 +1,281 @@
+use crate::compile::engine::provider::{CubeContext, CUBE_JOIN_FIELD};
+use crate::compile::rewrite::analysis::LogicalPlanAnalysis;
+use crate::compile::rewrite::rewriter::RewriteRules;
+use crate::compile::rewrite::rules::members::add_member_error;
+use crate::compile::rewrite::JoinJoinType;
+use crate::compile::rewrite::JoinLeftOn;
+use crate::compile::rewrite::JoinRightOn;
+use crate::compile::rewrite::JoinedCubesRelations;
+use crate::compile::rewrite::LogicalPlanLanguage;
+use crate::compile::rewrite::ProjectionAlias;
+use crate::compile::rewrite::TableScanSourceTableName;
+use crate::compile::rewrite::TableScanTableName;
+use crate::compile::rewrite::{
+    cube_scan, cube_scan_filters_empty_tail, cube_scan_members_empty_tail,
+    cube_scan_order_empty_tail, join, projection, table_scan, transforming_rewrite,
+};
+use crate::transport::MetaContext;
+use crate::var;
+use crate::var_iter;
+use datafusion::logical_plan::{Column, JoinType};
+use egg::{EGraph, Rewrite, Subst, Var};
+use std::sync::Arc;
+
+pub struct JoinRules {
+    cube_context: Arc<CubeContext>,
+}
+
+impl RewriteRules for JoinRules {
+    fn rewrite_rules(&self) -> Vec<Rewrite<LogicalPlanLanguage, LogicalPlanAnalysis>> {
+        let mut rules = Vec::new();
+        for (left_name, left) in join_sides("left") {
+            for (right_name, right) in join_sides("right") {
+                rules.push(transforming_rewrite(
+                    &format!("join-cubes-{}-{}", left_name, right_name),
+                    join(
+                        left.to_string(),
+                        right.to_string(),
+                        "?left_on",
+                        "?right_on",
+                        "?join_type",
+                        "?join_constraint",
+                    ),
+                    cube_scan(
+                        "?joined_cubes",
+                        "?members",
+                        cube_scan_filters_empty_tail(),
+                        cube_scan_order_empty_tail(),
+                        "CubeScanLimit:None",
+                        "CubeScanOffset:None",
+                        "CubeScanAliases:None",
+                    ),
+                    self.join_cubes(
+                        "?left_source",
+                        "?left_relation",
+                        "?right_source",
+                        "?right_relation",
+                        "?left_on",
+                        "?right_on",
+                        "?join_type",
+                        "?joined_cubes",
+                        "?members",
+                    ),
+                ));
+            }
+        }
+        rules
+    }
+}
+
+impl JoinRules {
+    pub fn new(cube_context: Arc<CubeContext>) -> Self {
+        Self { cube_context }
+    }
+
+    fn join_cubes(
+        &self,
+        left_source_var: &'static str,
+        left_relation_var: &'static str,
+        right_source_var: &'static str,
+        right_relation_var: &'static str,
+        left_on_var: &'static str,
+        right_on_var: &'static str,
+        join_type_var: &'static str,
+        joined_cubes_var: &'static str,
+        members_var: &'static str,
+    ) -> impl Fn(&mut EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>, &mut Subst) -> bool {
+        let left_source_var = var!(left_source_var);
+        let left_relation_var = var!(left_relation_var);
+        let right_source_var = var!(right_source_var);
+        let right_relation_var = var!(right_relation_var);
+        let left_on_var = var!(left_on_var);
+        let right_on_var = var!(right_on_var);
+        let join_type_var = var!(join_type_var);
+        let joined_cubes_var = var!(joined_cubes_var);
+        let members_var = var!(members_var);
+        let meta_context = self.cube_context.meta.clone();
+        move |egraph, subst| {
+            let left = join_side_cubes(
+                egraph,
+                subst,
+                &meta_context,
+                left_source_var,
+                left_relation_var,
+            );
+            let right = join_side_cubes(
+                egraph,
+                subst,
+                &meta_context,
+                right_source_var,
+                right_relation_var,
+            );
+            if let (Some(left), Some(right)) = (left, right) {
+                let left_on = var_iter!(egraph[subst[left_on_var]], JoinLeftOn)
+                    .next()
+                    .cloned()
+                    .unwrap_or_default();
+                let right_on = var_iter!(egraph[subst[right_on_var]], JoinRightOn)
+                    .next()
+                    .cloned()
+                    .unwrap_or_default();
+                for join_type in var_iter!(egraph[subst[join_type_var]], JoinJoinType).cloned() {
+                    let error = match join_type {
+                        JoinType::Left => join_condition_error(&left, &right, &left_on, &right_on)
+                            .or_else(|| join_relationship_error(&meta_context, &left, &right)),
+                        _ => Some(format!(
+                            "{} JOIN between cubes is not supported: Cube always joins cubes with LEFT JOIN following the data schema. Please use LEFT JOIN",
+                            format!("{:?}", join_type).to_uppercase()
+                        )),
+                    };
+
+                    let members = if let Some(error) = error {
+                        let error = add_member_error(egraph, error);
+                        let empty_tail = egraph.add(LogicalPlanLanguage::CubeScanMembers(vec![]));
+                        egraph.add(LogicalPlanLanguage::CubeScanMembers(vec![
+                            error, empty_tail,
+                        ]))
+                    } else {
+                        egraph.add(LogicalPlanLanguage::CubeScanMembers(vec![]))
+                    };
+                    subst.insert(members_var, members);
+
+                    subst.insert(
+                        joined_cubes_var,
+                        egraph.add(LogicalPlanLanguage::JoinedCubesRelations(
+                            JoinedCubesRelations(left.into_iter().chain(right).collect()),
+                        )),
+                    );
+                    return true;
+                }
+            }
+            false
+        }
+    }
+}
+
+/// Join inputs that can be merged into a single `CubeScan`: a cube table, an aliased cube table
+/// and cubes that were already joined together.
+fn join_sides(side: &str) -> Vec<(&'static str, String)> {
+    let cube_table_scan = |table_name: String| {
+        table_scan(
+            format!("?{}_source", side),
+            table_name,
+            format!("?{}_projection", side),
+            format!("?{}_filters", side),
+            format!("?{}_limit", side),
+        )
+    };
+    vec![
+        ("table", cube_table_scan(format!("?{}_relation", side))),
+        (
+            "alias",
+            projection(
+                format!("?{}_expr", side),
+                cube_table_scan(format!("?{}_table_name", side)),
+                format!("?{}_relation", side),
+            ),
+        ),
+        (
+            "joined",
+            cube_scan(
+                format!("?{}_source", side),
+                cube_scan_members_empty_tail(),
+                cube_scan_filters_empty_tail(),
+                cube_scan_order_empty_tail(),
+                "CubeScanLimit:None",
+                "CubeScanOffset:None",
+                "CubeScanAliases:None",
+            ),
+        ),
+    ]
+}
+
+/// `(relation, cube name)` pairs of a join input or `None` if it isn't backed by cubes.
+fn join_side_cubes(
+    egraph: &EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>,
+    subst: &Subst,
+    meta_context: &MetaContext,
+    source_var: Var,
+    relation_var: Var,
+) -> Option<Vec<(String, String)>> {
+    if let Some(relations) = var_iter!(egraph[subst[source_var]], JoinedCubesRelations).next() {
+        return Some(relations.clone());
+    }
+
+    let relation_id = *subst.get(relation_var)?;
+    let relation = var_iter!(egraph[relation_id], TableScanTableName)
+        .cloned()
+        .chain(var_iter!(egraph[relation_id], ProjectionAlias).filter_map(|alias| alias.clone()))
+        .next()?;
+    let cube = var_iter!(egraph[subst[source_var]], TableScanSourceTableName)
+        .find_map(|name| meta_context.find_cube_with_name(name.to_string()))?;
+    Some(vec![(relation, cube.name)])
+}
+
+/// Cube picks join keys from the relationships of the data schema, so the only condition it can
+/// honour is equality of the virtual join fields of a left and a right relation.
+fn join_condition_error(
+    left: &Vec<(String, String)>,
+    right: &Vec<(String, String)>,
+    left_on: &Vec<Column>,
+    right_on: &Vec<Column>,
+) -> Option<String> {
+    let is_join_field_of = |cubes: &Vec<(String, String)>, column: &Column| {
+        column.name.eq_ignore_ascii_case(CUBE_JOIN_FIELD)
+            && column
+                .relation
+                .as_ref()
+                .map(|relation| cubes.iter().any(|(r, _)| r.eq_ignore_ascii_case(relation)))
+                .unwrap_or(false)
+    };
+    if !left_on.is_empty()
+        && left_on.len() == right_on.len()
+        && left_on
+            .iter()
+            .zip(right_on.iter())
+            .all(|(l, r)| is_join_field_of(left, l) && is_join_field_of(right, r))
+    {
+        return None;
+    }
+
+    let side_cube = |cubes: &Vec<(String, String)>, on: &Vec<Column>| {
+        on.iter()
+            .filter_map(|column| column.relation.as_ref())
+            .find_map(|relation| cubes.iter().find(|(r, _)| r == relation))
+            .or_else(|| cubes.first())
+            .map(|(_, cube)| cube.to_string())
+            .unwrap_or_default()
+    };
+    let left_cube = side_cube(left, left_on);
+    let right_cube = side_cube(right, right_on);
+    Some(format!(
+        "Can't join '{}' and '{}' cubes: join condition should be {}.{} = {}.{}, Cube joins cubes by the relationships defined in the data schema",
+        left_cube, right_cube, left_cube, CUBE_JOIN_FIELD, right_cube, CUBE_JOIN_FIELD
+    ))
+}
+
+/// Cube resolves join paths itself so cubes can be joined only if they belong to the same
+/// connected component of the join graph.
+fn join_relationship_error(
+    meta_context: &MetaContext,
+    left: &Vec<(String, String)>,
+    right: &Vec<(String, String)>,
+) -> Option<String> {
+    for (_, left_cube) in left.iter() {
+        for (_, right_cube) in right.iter() {
+            let left_component = meta_context
+                .find_cube_with_name(left_cube.to_string())
+                .and_then(|cube| cube.connected_component);
+            let right_component = meta_context
+                .find_cube_with_name(right_cube.to_string())
+                .and_then(|cube| cube.connected_component);
+            if left_component.is_none() || left_component != right_component {
+                return Some(format!(
+                    "Can't join '{}' and '{}' cubes: there's no join relationship defined between them in the data schema",
+                    left_cube, right_cube
+                ));
+            }
+        }
+    }
+    None
+}
@@ -1,10 +1,11 @@
-use crate::compile::engine::provider::CubeContext;
+use crate::compile::engine::provider::{CubeContext, CUBE_JOIN_FIELD};
 use crate::compile::rewrite::analysis::LogicalPlanAnalysis;
 use crate::compile::rewrite::rewriter::RewriteRules;
 use crate::compile::rewrite::table_scan;
 use crate::compile::rewrite::AggregateFunctionExprDistinct;
 use crate::compile::rewrite::AggregateFunctionExprFun;
 use crate::compile::rewrite::AliasExprAlias;
+use crate::compile::rewrite::CastExprDataType;
 use crate::compile::rewrite::ColumnAliasReplacerAliases;
 use crate::compile::rewrite::ColumnAliasReplacerCube;
 use crate::compile::rewrite::ColumnExprColumn;
@@ -17,31 +18,36 @@ use crate::compile::rewrite::LogicalPlanLanguage;
 use crate::compile::rewrite::MeasureName;
 use crate::compile::rewrite::MemberErrorError;
 use crate::compile::rewrite::TableScanSourceTableName;
+use crate::compile::rewrite::TimeDimensionDataType;
 use crate::compile::rewrite::TimeDimensionDateRange;
 use crate::compile::rewrite::TimeDimensionGranularity;
 use crate::compile::rewrite::TimeDimensionName;
 use crate::compile::rewrite::{
     agg_fun_expr, aggr_aggr_expr, aggr_aggr_expr_empty_tail, aggr_group_expr,
-    aggr_group_expr_empty_tail, aggregate, alias_expr, column_alias_replacer,
-    column_name_to_member_name, cube_scan_members_empty_tail, expr_column_name,
-    expr_column_name_with_relation, fun_expr, limit, member_replacer, projection, projection_expr,
-    projection_expr_empty_tail, sort_expr, udaf_expr, WithColumnRelation,
+    aggr_group_expr_empty_tail, aggregate, alias_expr, column_alias_replacer, column_cube_names,
+    column_name_to_member_name, cube_scan_members_empty_tail, cube_scan_source_cubes,
+    cube_scan_source_relation, expr_column_name, expr_column_name_with_relation, filter, fun_expr,
+    limit, member_replacer, projection, projection_expr, projection_expr_empty_tail, sort_expr,
+    udaf_expr, WithColumnRelation,
 };
 use crate::compile::rewrite::{
-    binary_expr, column_expr, cube_scan, literal_expr, rewrite, transforming_rewrite,
+    binary_expr, cast_expr, column_expr, cube_scan, literal_expr, rewrite, transforming_rewrite,
 };
 use crate::compile::rewrite::{
     cube_scan_filters_empty_tail, cube_scan_members, dimension_expr, measure_expr,
     time_dimension_expr,
 };
 use crate::compile::rewrite::{cube_scan_order_empty_tail, transforming_chain_rewrite};
-use crate::transport::{V1CubeMetaDimensionExt, V1CubeMetaMeasureExt, V1CubeMetaSegmentExt};
+use crate::transport::{
+    MetaContext, V1CubeMetaDimensionExt, V1CubeMetaExt, V1CubeMetaMeasureExt, V1CubeMetaSegmentExt,
+};
 use crate::var_iter;
 use crate::{var, CubeError};
-use datafusion::logical_plan::{Column, DFSchema};
+use datafusion::arrow::datatypes::{DataType, TimeUnit};
+use datafusion::logical_plan::{Column, DFSchema, Expr};
 use datafusion::physical_plan::aggregates::AggregateFunction;
 use datafusion::scalar::ScalarValue;
-use egg::{EGraph, Id, Rewrite, Subst};
+use egg::{EGraph, Id, Rewrite, Subst, Var};
 use std::ops::Index;
 use std::sync::Arc;
 
@@ -186,6 +192,50 @@ impl RewriteRules for MemberRules {
                             "DateTrunc",
                             vec![literal_expr("?granularity"), column_expr("?column")],
                         ),
+                        "?time_dimension_data_type",
+                    ),
+                    member_replacer("?tail_group_expr", "?source_table_name"),
+                ),
+                self.transform_time_dimension(
+                    "?source_table_name",
+                    "?column",
+                    "?time_dimension_name",
+                    "?granularity",
+                    "?time_dimension_granularity",
+                    "?date_range",
+                    None,
+                    "?time_dimension_data_type",
+                ),
+            ),
+            // Postgres: CAST(col AS DATE), see `cast-to-date-to-date-trunc`
+            transforming_rewrite(
+                "date-trunc-cast",
+                member_replacer(
+                    aggr_group_expr(
+                        cast_expr(
+                            fun_expr(
+                                "DateTrunc",
+                                vec![literal_expr("?granularity"), column_expr("?column")],
+                            ),
+                            "?data_type",
+                        ),
+                        "?tail_group_expr",
+                    ),
+                    "?source_table_name",
+                ),
+                cube_scan_members(
+                    time_dimension_expr(
+                        "?time_dimension_name",
+                        "?time_dimension_granularity",
+                        "?date_range",
+                        cast_expr(
+                            fun_expr(
+                                "DateTrunc",
+                                vec![literal_expr("?granularity"), column_expr("?column")],
+                            ),
+                            "?data_type",
+                        ),
+                        "?time_dimension_data_type",
                     ),
                     member_replacer("?tail_group_expr", "?source_table_name"),
                 ),
@@ -196,6 +246,8 @@ impl RewriteRules for MemberRules {
                     "?granularity",
                     "?time_dimension_granularity",
                     "?date_range",
+                    Some("?data_type"),
+                    "?time_dimension_data_type",
                 ),
             ),
             transforming_rewrite(
@@ -219,6 +271,7 @@ impl RewriteRules for MemberRules {
                             "DateTrunc",
                             vec![literal_expr("?granularity"), column_expr("?column")],
                         ),
+                        "?time_dimension_data_type",
                     ),
                     member_replacer("?tail_group_expr", "?source_table_name"),
                 ),
@@ -229,6 +282,51 @@ impl RewriteRules for MemberRules {
                     "?granularity",
                     "?time_dimension_granularity",
                     "?date_range",
+                    None,
+                    "?time_dimension_data_type",
+                ),
+            ),
+            // Postgres: CAST(col AS DATE), see `cast-to-date-to-date-trunc`
+            transforming_rewrite(
+                "date-trunc-projection-cast",
+                member_replacer(
+                    projection_expr(
+                        cast_expr(
+                            fun_expr(
+                                "DateTrunc",
+                                vec![literal_expr("?granularity"), column_expr("?column")],
+                            ),
+                            "?data_type",
+                        ),
+                        "?tail_group_expr",
+                    ),
+                    "?source_table_name",
+                ),
+                cube_scan_members(
+                    time_dimension_expr(
+                        "?time_dimension_name",
+                        "?time_dimension_granularity",
+                        "?date_range",
+                        cast_expr(
+                            fun_expr(
+                                "DateTrunc",
+                                vec![literal_expr("?granularity"), column_expr("?column")],
+                            ),
+                            "?data_type",
+                        ),
+                        "?time_dimension_data_type",
+                    ),
+                    member_replacer("?tail_group_expr", "?source_table_name"),
+                ),
+                self.transform_time_dimension(
+                    "?source_table_name",
+                    "?column",
+                    "?time_dimension_name",
+                    "?granularity",
+                    "?time_dimension_granularity",
+                    "?date_range",
+                    Some("?data_type"),
+                    "?time_dimension_data_type",
                 ),
             ),
             transforming_rewrite(
@@ -238,12 +336,14 @@ impl RewriteRules for MemberRules {
                     "?time_dimension_granularity",
                     "?date_range",
                     "?original_expr",
+                    "?time_dimension_data_type",
                 ),
                 time_dimension_expr(
                     "?time_dimension_name",
                     "?time_dimension_granularity",
                     "?date_range",
                     "?alias",
+                    "?time_dimension_data_type",
                 ),
                 self.transform_original_expr_alias("?original_expr", "?alias"),
             ),
@@ -287,6 +387,41 @@ impl RewriteRules for MemberRules {
                     "?aliases",
                 ),
             ),
+            transforming_rewrite(
+                "push-down-aggregate-post-processing",
+                aggregate(
+                    cube_scan(
+                        "?source_table_name",
+                        cube_scan_members_empty_tail(),
+                        "?filters",
+                        "?orders",
+                        "?limit",
+                        "?offset",
+                        "?aliases",
+                    ),
+                    "?group_expr",
+                    "?aggr_expr",
+                ),
+                aggregate(
+                    cube_scan(
+                        "?source_table_name",
+                        member_replacer("?referenced_expr", "?source_table_name"),
+                        "?filters",
+                        "?orders",
+                        "?limit",
+                        "?offset",
+                        "?aliases",
+                    ),
+                    "?group_expr",
+                    "?aggr_expr",
+                ),
+                self.push_down_aggregate_post_processing(
+                    "?source_table_name",
+                    "?group_expr",
+                    "?aggr_expr",
+                    "?referenced_expr",
+                ),
+            ),
             rewrite(
                 "push-down-projection-to-empty-scan",
                 projection(
@@ -345,6 +480,81 @@ impl RewriteRules for MemberRules {
                     "?cube_aliases",
                 ),
             ),
+            transforming_rewrite(
+                "push-down-projection-post-processing",
+                projection(
+                    "?expr",
+                    cube_scan(
+                        "?source_table_name",
+                        cube_scan_members_empty_tail(),
+                        "?filters",
+                        "?orders",
+                        "?limit",
+                        "?offset",
+                        "?aliases",
+                    ),
+                    "?alias",
+                ),
+                projection(
+                    "?expr",
+                    cube_scan(
+                        "?source_table_name",
+                        member_replacer("?referenced_expr", "?source_table_name"),
+                        "?filters",
+                        "?orders",
+                        "?limit",
+                        "?offset",
+                        "?aliases",
+                    ),
+                    "?alias",
+                ),
+                self.push_down_post_processing(
+                    "?source_table_name",
+                    vec!["?expr"],
+                    "?referenced_expr",
+                ),
+            ),
+            transforming_rewrite(
+                "push-down-projection-filter-post-processing",
+                projection(
+                    "?expr",
+                    filter(
+                        "?predicate",
+                        cube_scan(
+                            "?source_table_name",
+                            cube_scan_members_empty_tail(),
+                            "?filters",
+                            "?orders",
+                            "?limit",
+                            "?offset",
+                            "?aliases",
+                        ),
+                    ),
+                    "?alias",
+                ),
+                projection(
+                    "?expr",
+                    filter(
+                        "?predicate",
+                        cube_scan(
+                            "?source_table_name",
+                            member_replacer("?referenced_expr", "?source_table_name"),
+                            "?filters",
+                            "?orders",
+                            "?limit",
+                            "?offset",
+                            "?aliases",
+                        ),
+                    ),
+                    "?alias",
+                ),
+                self.push_down_filter_post_processing(
+                    "?source_table_name",
+                    "?expr",
+                    "?predicate",
+                    "?referenced_expr",
+                ),
+            ),
             transforming_rewrite(
                 "limit-push-down",
                 limit(
@@ -466,6 +676,7 @@ impl RewriteRules for MemberRules {
                             "?time_dimension_granularity",
                             "?date_range",
                             "?expr",
+                            "?time_dimension_data_type",
                         ),
                         "?tail_group_expr",
                     ),
@@ -478,6 +689,7 @@ impl RewriteRules for MemberRules {
                         "?time_dimension_granularity",
                         "?date_range",
                         "?replaced_alias_expr",
+                        "?time_dimension_data_type",
                     ),
                     column_alias_replacer("?tail_group_expr", "?aliases", "?cube"),
                 ),
@@ -589,11 +801,12 @@ impl MemberRules {
         let cube_var = var!(cube_var);
         let cube_aliases_var = var!(cube_aliases_var);
         move |egraph, subst| {
-            for table_name in var_iter!(egraph[subst[table_name_var]], TableScanSourceTableName) {
+            for cubes in cube_scan_source_cubes(egraph, subst[table_name_var]) {
+                let table_name = cube_scan_source_relation(&cubes);
                 if let Some(expr_to_alias) =
                     &egraph.index(subst[projection_expr_var]).data.expr_to_alias
                 {
-                    let mut relation = WithColumnRelation(table_name.to_string());
+                    let mut relation = WithColumnRelation(table_name.clone());
                     let column_name_to_alias = expr_to_alias
                         .clone()
                         .into_iter()
@@ -606,8 +819,7 @@ impl MemberRules {
                         .clone()
                     {
                         let column_name_to_member_name =
-                            column_name_to_member_name(member_name_to_expr, table_name.to_string());
-                        let table_name = table_name.to_string();
+                            column_name_to_member_name(member_name_to_expr, table_name.clone());
                         if column_name_to_alias
                             .iter()
                             .all(|(c, _)| column_name_to_member_name.contains_key(c))
@@ -629,7 +841,7 @@ impl MemberRules {
                             subst.insert(cube_aliases_var, cube_aliases);
 
                             let cube = egraph.add(LogicalPlanLanguage::ColumnAliasReplacerCube(
-                                ColumnAliasReplacerCube(Some(table_name)),
+                                ColumnAliasReplacerCube(table_name),
                             ));
                             subst.insert(cube_var, cube);
                             return true;
@@ -641,6 +853,215 @@ impl MemberRules {
         }
     }
 
+    /// Collects columns referenced by expressions that can't be pushed down to Cube so these
+    /// columns are requested as members and the expressions are evaluated on top of `CubeScan`.
+    fn push_down_post_processing(
+        &self,
+        table_name_var: &'static str,
+        expr_vars: Vec<&'static str>,
+        referenced_expr_var: &'static str,
+    ) -> impl Fn(&mut EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>, &mut Subst) -> bool {
+        let table_name_var = var!(table_name_var);
+        let expr_vars = expr_vars
+            .into_iter()
+            .map(|expr_var| var!(expr_var))
+            .collect::<Vec<Var>>();
+        let referenced_expr_var = var!(referenced_expr_var);
+        move |egraph, subst| {
+            for _ in cube_scan_source_cubes(egraph, subst[table_name_var]) {
+                let mut columns = Vec::new();
+                for expr_var in expr_vars.iter() {
+                    if let Some(referenced_expr) =
+                        &egraph.index(subst[*expr_var]).data.referenced_expr
+                    {
+                        for expr in referenced_expr.iter() {
+                            if let Expr::Column(column) = expr {
+                                if !columns.contains(column) {
+                                    columns.push(column.clone());
+                                }
+                            }
+                        }
+                    } else {
+                        return false;
+                    }
+                }
+
+                if columns.is_empty() {
+                    return false;
+                }
+
+                let mut current = egraph.add(LogicalPlanLanguage::ProjectionExpr(Vec::new()));
+                for column in columns.into_iter().rev() {
+                    let column = egraph.add(LogicalPlanLanguage::ColumnExprColumn(
+                        ColumnExprColumn(column),
+                    ));
+                    let column_expr = egraph.add(LogicalPlanLanguage::ColumnExpr([column]));
+                    current = egraph.add(LogicalPlanLanguage::ProjectionExpr(vec![
+                        column_expr,
+                        current,
+                    ]));
+                }
+                subst.insert(referenced_expr_var, current);
+                return true;
+            }
+            false
+        }
+    }
+
+    /// Columns of the filter are requested as dimensions too, so measures of the projection would
+    /// be split by the ones which aren't projected. Such filters are pushed down only if the
+    /// projection has no measures or the filter references projected columns only.
+    fn push_down_filter_post_processing(
+        &self,
+        table_name_var: &'static str,
+        expr_var: &'static str,
+        predicate_var: &'static str,
+        referenced_expr_var: &'static str,
+    ) -> impl Fn(&mut EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>, &mut Subst) -> bool {
+        let push_down_post_processing = self.push_down_post_processing(
+            table_name_var,
+            vec![expr_var, predicate_var],
+            referenced_expr_var,
+        );
+        let table_name_var = var!(table_name_var);
+        let expr_var = var!(expr_var);
+        let predicate_var = var!(predicate_var);
+        let meta_context = self.cube_context.meta.clone();
+        move |egraph, subst| {
+            let projected = match &egraph[subst[expr_var]].data.referenced_expr {
+                Some(referenced_expr) => referenced_expr.clone(),
+                None => return false,
+            };
+            let filtered = match &egraph[subst[predicate_var]].data.referenced_expr {
+                Some(referenced_expr) => referenced_expr.clone(),
+                None => return false,
+            };
+
+            let projected_columns = projected
+                .iter()
+                .filter_map(|expr| match expr {
+                    Expr::Column(column) => Some(column),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let is_projected = |expr: &Expr| match expr {
+                Expr::Column(column) => projected_columns.contains(&column),
+                _ => false,
+            };
+
+            for cubes in cube_scan_source_cubes(egraph, subst[table_name_var]) {
+                let is_dimension_grain = projected.iter().all(|expr| match expr {
+                    Expr::Column(column) => {
+                        Self::is_dimension_column(&meta_context, &cubes, column)
+                    }
+                    _ => false,
+                });
+                if !is_dimension_grain && !filtered.iter().all(is_projected) {
+                    return false;
+                }
+            }
+
+            push_down_post_processing(egraph, subst)
+        }
+    }
+
+    fn is_dimension_column(
+        meta_context: &MetaContext,
+        cubes: &[(String, String)],
+        column: &Column,
+    ) -> bool {
+        column_cube_names(cubes, column).iter().any(|name| {
+            meta_context
+                .find_cube_with_name(name.to_string())
+                .map(|cube| {
+                    cube.lookup_dimension(&format!("{}.{}", cube.name, column.name))
+                        .is_some()
+                })
+                .unwrap_or(false)
+        })
+    }
+
+    /// Aggregate which can't be pushed down is evaluated over distinct values of the dimensions
+    /// it references. It's only correct for aggregates which don't depend on the number of rows.
+    fn push_down_aggregate_post_processing(
+        &self,
+        table_name_var: &'static str,
+        group_expr_var: &'static str,
+        aggr_expr_var: &'static str,
+        referenced_expr_var: &'static str,
+    ) -> impl Fn(&mut EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>, &mut Subst) -> bool {
+        let push_down_post_processing = self.push_down_post_processing(
+            table_name_var,
+            vec![group_expr_var, aggr_expr_var],
+            referenced_expr_var,
+        );
+        let table_name_var = var!(table_name_var);
+        let group_expr_var = var!(group_expr_var);
+        let aggr_expr_var = var!(aggr_expr_var);
+        let meta_context = self.cube_context.meta.clone();
+        move |egraph, subst| {
+            if !Self::is_distinct_rows_aggregate(egraph, subst[aggr_expr_var]) {
+                return false;
+            }
+
+            for cubes in cube_scan_source_cubes(egraph, subst[table_name_var]) {
+                for expr_var in [group_expr_var, aggr_expr_var].iter() {
+                    let referenced_expr = match &egraph[subst[*expr_var]].data.referenced_expr {
+                        Some(referenced_expr) => referenced_expr,
+                        None => return false,
+                    };
+                    for expr in referenced_expr.iter() {
+                        let column = match expr {
+                            Expr::Column(column) => column,
+                            _ => return false,
+                        };
+                        if !Self::is_dimension_column(&meta_context, &cubes, column) {
+                            return false;
+                        }
+                    }
+                }
+            }
+
+            push_down_post_processing(egraph, subst)
+        }
+    }
+
+    /// MIN, MAX and COUNT(DISTINCT) give the same result over distinct rows as over all rows.
+    fn is_distinct_rows_aggregate(
+        egraph: &EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>,
+        aggr_expr: Id,
+    ) -> bool {
+        if let Some(expr) = &egraph[aggr_expr].data.original_expr {
+            let expr = match expr {
+                Expr::Alias(expr, _) => expr.as_ref(),
+                expr => expr,
+            };
+            return match expr {
+                Expr::AggregateFunction {
+                    fun: AggregateFunction::Min,
+                    ..
+                }
+                | Expr::AggregateFunction {
+                    fun: AggregateFunction::Max,
+                    ..
+                } => true,
+                Expr::AggregateFunction {
+                    fun: AggregateFunction::Count,
+                    distinct,
+                    ..
+                } => *distinct,
+                _ => false,
+            };
+        }
+
+        egraph[aggr_expr].nodes.iter().any(|node| match node {
+            LogicalPlanLanguage::AggregateAggrExpr(params) => params
+                .iter()
+                .all(|id| Self::is_distinct_rows_aggregate(egraph, *id)),
+            _ => false,
+        })
+    }
+
     fn push_down_limit(
         &self,
         limit_var: &'static str,
@@ -722,14 +1143,13 @@ impl MemberRules {
         let member_var = member_var.parse().unwrap();
         let meta_context = self.cube_context.meta.clone();
         move |egraph, subst| {
-            for member_name in
-                var_iter!(egraph[subst[column_var]], ColumnExprColumn).map(|c| c.name.to_string())
-            {
-                for cube_name in var_iter!(egraph[subst[cube_var]], TableScanSourceTableName) {
+            for column in var_iter!(egraph[subst[column_var]], ColumnExprColumn).cloned() {
+                let member_name = column.name.to_string();
+                for cube_name in source_cube_names(egraph, subst[cube_var], &column) {
                     if let Some(cube) = meta_context
                         .cubes
                         .iter()
-                        .find(|c| c.name.eq_ignore_ascii_case(cube_name))
+                        .find(|c| c.name.eq_ignore_ascii_case(&cube_name))
                     {
                         let column_names = if let Some(alias_var) = &alias_var {
                             var_iter!(egraph[subst[*alias_var]], AliasExprAlias)
@@ -795,6 +1215,7 @@ impl MemberRules {
         }
     }
 
+    /// Segments and the join field are virtual columns, they aren't requested from Cube.
     fn transform_segment(
         &self,
         cube_var: &'static str,
@@ -804,16 +1225,17 @@ impl MemberRules {
         let column_var = column_var.parse().unwrap();
         let meta_context = self.cube_context.meta.clone();
         move |egraph, subst| {
-            for member_name in
-                var_iter!(egraph[subst[column_var]], ColumnExprColumn).map(|c| c.name.to_string())
-            {
-                for cube_name in var_iter!(egraph[subst[cube_var]], TableScanSourceTableName) {
+            for column in var_iter!(egraph[subst[column_var]], ColumnExprColumn) {
+                if column.name.eq_ignore_ascii_case(CUBE_JOIN_FIELD) {
+                    return true;
+                }
+                for cube_name in source_cube_names(egraph, subst[cube_var], column) {
                     if let Some(cube) = meta_context
                         .cubes
                         .iter()
-                        .find(|c| c.name.eq_ignore_ascii_case(cube_name))
+                        .find(|c| c.name.eq_ignore_ascii_case(&cube_name))
                     {
-                        let member_name = format!("{}.{}", cube_name, member_name);
+                        let member_name = format!("{}.{}", cube_name, column.name);
                         if let Some(_) = cube
                             .segments
                             .iter()
@@ -841,12 +1263,10 @@ impl MemberRules {
         let dimension_var = var!(dimension_var);
         let meta_context = self.cube_context.meta.clone();
         move |egraph, subst| {
-            for dimension_name in
-                var_iter!(egraph[subst[column_var]], ColumnExprColumn).map(|c| c.name.to_string())
-            {
-                for cube_name in var_iter!(egraph[subst[cube_var]], TableScanSourceTableName) {
+            for column in var_iter!(egraph[subst[column_var]], ColumnExprColumn).cloned() {
+                for cube_name in source_cube_names(egraph, subst[cube_var], &column) {
                     if let Some(cube) = meta_context.find_cube_with_name(cube_name.to_string()) {
-                        let dimension_name = format!("{}.{}", cube_name, dimension_name);
+                        let dimension_name = format!("{}.{}", cube_name, column.name);
                         if let Some(dimension) = cube
                             .dimensions
                             .iter()
@@ -900,6 +1320,8 @@ impl MemberRules {
         granularity_var: &'static str,
         time_dimension_granularity_var: &'static str,
         date_range_var: &'static str,
+        cast_data_type_var: Option<&'static str>,
+        time_dimension_data_type_var: &'static str,
     ) -> impl Fn(&mut EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>, &mut Subst) -> bool {
         let cube_var = cube_var.parse().unwrap();
         let dimension_var = dimension_var.parse().unwrap();
@@ -907,18 +1329,31 @@ impl MemberRules {
         let granularity_var = granularity_var.parse().unwrap();
         let time_dimension_granularity_var = time_dimension_granularity_var.parse().unwrap();
         let date_range_var = date_range_var.parse().unwrap();
+        let cast_data_type_var = cast_data_type_var.map(|var| var.parse::<Var>().unwrap());
+        let time_dimension_data_type_var = time_dimension_data_type_var.parse().unwrap();
         let meta_context = self.cube_context.meta.clone();
         move |egraph, subst| {
-            for time_dimension_name in var_iter!(egraph[subst[dimension_var]], ColumnExprColumn)
-                .map(|c| c.name.to_string())
-            {
-                for cube_name in var_iter!(egraph[subst[cube_var]], TableScanSourceTableName) {
+            // Only dates can be hydrated from the truncated time, other casts are done by DF
+            let data_type = match cast_data_type_var {
+                Some(cast_data_type_var) => {
+                    match var_iter!(egraph[subst[cast_data_type_var]], CastExprDataType)
+                        .find(|data_type| matches!(data_type, DataType::Date32))
+                    {
+                        Some(data_type) => data_type.clone(),
+                        None => return false,
+                    }
+                }
+                None => DataType::Timestamp(TimeUnit::Nanosecond, None),
+            };
+
+            for column in var_iter!(egraph[subst[dimension_var]], ColumnExprColumn).cloned() {
+                for cube_name in source_cube_names(egraph, subst[cube_var], &column) {
                     if let Some(cube) = meta_context
                         .cubes
                         .iter()
-                        .find(|c| c.name.eq_ignore_ascii_case(cube_name))
+                        .find(|c| c.name.eq_ignore_ascii_case(&cube_name))
                     {
-                        let time_dimension_name = format!("{}.{}", cube_name, time_dimension_name);
+                        let time_dimension_name = format!("{}.{}", cube_name, column.name);
                         if let Some(time_dimension) = cube.dimensions.iter().find(|d| {
                             d._type == "time" && d.name.eq_ignore_ascii_case(&time_dimension_name)
                         }) {
@@ -927,7 +1362,20 @@ impl MemberRules {
                             {
                                 match granularity {
                                     ScalarValue::Utf8(Some(granularity_value)) => {
-                                        let granularity_value = granularity_value.to_string();
+                                        let granularity_value = granularity_value.to_lowercase();
+                                        if !matches!(
+                                            granularity_value.as_str(),
+                                            "second"
+                                                | "minute"
+                                                | "hour"
+                                                | "day"
+                                                | "week"
+                                                | "month"
+                                                | "quarter"
+                                                | "year"
+                                        ) {
+                                            continue;
+                                        }
                                         subst.insert(
                                             time_dimension_name_var,
                                             egraph.add(LogicalPlanLanguage::TimeDimensionName(
@@ -952,6 +1400,12 @@ impl MemberRules {
                                                 ),
                                             ),
                                         );
+                                        subst.insert(
+                                            time_dimension_data_type_var,
+                                            egraph.add(LogicalPlanLanguage::TimeDimensionDataType(
+                                                TimeDimensionDataType(data_type.clone()),
+                                            )),
+                                        );
                                         return true;
                                     }
                                     _ => {}
@@ -1011,19 +1465,20 @@ impl MemberRules {
         let measure_out_var = measure_out_var.parse().unwrap();
         let meta_context = self.cube_context.meta.clone();
         move |egraph, subst| {
-            for measure_name in measure_var
+            for column in measure_var
                 .map(|measure_var| {
                     var_iter!(egraph[subst[measure_var]], ColumnExprColumn)
-                        .map(|c| c.name.to_string())
+                        .cloned()
                         .collect()
                 })
-                .unwrap_or(vec!["count".to_string()])
+                .unwrap_or(vec![Column::from_name("count")])
             {
-                for cube_name in var_iter!(egraph[subst[var]], TableScanSourceTableName) {
+                let measure_name = column.name.to_string();
+                for cube_name in source_cube_names(egraph, subst[var], &column) {
                     if let Some(cube) = meta_context
                         .cubes
                         .iter()
-                        .find(|c| c.name.eq_ignore_ascii_case(cube_name))
+                        .find(|c| c.name.eq_ignore_ascii_case(&cube_name))
                     {
                         for distinct in distinct_var
                             .map(|distinct_var| {
@@ -1069,15 +1524,15 @@ impl MemberRules {
                                 {
                                     if call_agg_type.is_some()
                                         && !measure
-                                            .is_same_agg_type(call_agg_type.as_ref().unwrap())
+                                            .is_compatible_agg_type(call_agg_type.as_ref().unwrap())
                                     {
                                         subst.insert(
                                             measure_out_var,
                                             add_member_error(egraph, format!(
-                                                "Measure aggregation type doesn't match. The aggregation type for '{}' is '{}()' but '{}()' was provided",
+                                                "Measure aggregation type doesn't match. The aggregation type for '{}' is '{}()' but '{}' was provided",
                                                 measure.get_real_name(),
                                                 measure.agg_type.as_ref().unwrap_or(&"unknown".to_string()).to_uppercase(),
-                                                call_agg_type.unwrap().to_uppercase(),
+                                                agg_type_to_sql_call(call_agg_type.unwrap()),
                                             )),
                                         );
                                     } else {
@@ -1106,9 +1561,9 @@ impl MemberRules {
                                     subst.insert(
                                         measure_out_var,
                                         add_member_error(egraph, format!(
-                                            "Dimension '{}' was used with the aggregate function '{}()'. Please use a measure instead",
+                                            "Dimension '{}' was used with the aggregate function '{}'. Please use a measure instead",
                                             dimension.get_real_name(),
-                                            call_agg_type.unwrap().to_uppercase(),
+                                            agg_type_to_sql_call(call_agg_type.unwrap()),
                                         )),
                                     );
 
@@ -1124,6 +1579,25 @@ impl MemberRules {
     }
 }
 
+fn agg_type_to_sql_call(agg_type: &str) -> String {
+    match agg_type {
+        "countDistinct" => "COUNT(DISTINCT)".to_string(),
+        "countDistinctApprox" => "APPROX_DISTINCT()".to_string(),
+        _ => format!("{}()", agg_type.to_uppercase()),
+    }
+}
+
+fn source_cube_names(
+    egraph: &EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>,
+    source: Id,
+    column: &Column,
+) -> Vec<String> {
+    cube_scan_source_cubes(egraph, source)
+        .iter()
+        .flat_map(|cubes| column_cube_names(cubes, column))
+        .collect()
+}
+
 pub fn add_member_error(
     egraph: &mut EGraph<LogicalPlanLanguage, LogicalPlanAnalysis>,
     member_error: String,
@@ -1,4 +1,5 @@
 pub mod dates;
 pub mod filters;
+pub mod joins;
 pub mod members;
 pub mod order;
@@ -7,10 +7,10 @@ use crate::compile::rewrite::OrderMember;
 use crate::compile::rewrite::OrderReplacerColumnNameToMember;
 use crate::compile::rewrite::OrderReplacerCube;
 use crate::compile::rewrite::SortExprAsc;
-use crate::compile::rewrite::TableScanSourceTableName;
 use crate::compile::rewrite::{
-    column_name_to_member_name, cube_scan_order, cube_scan_order_empty_tail, expr_column_name,
-    order, order_replacer, referenced_columns, sort, sort_exp, sort_exp_empty_tail, sort_expr,
+    column_name_to_member_name, cube_scan_order, cube_scan_order_empty_tail,
+    cube_scan_source_cubes, cube_scan_source_relation, expr_column_name, order, order_replacer,
+    referenced_columns, sort, sort_exp, sort_exp_empty_tail, sort_expr,
 };
 use crate::compile::rewrite::{cube_scan, rewrite, transforming_rewrite};
 use crate::var;
@@ -110,7 +110,8 @@ impl OrderRules {
         let aliases_var = var!(aliases_var);
         let cube_var = var!(cube_var);
         move |egraph, subst| {
-            for table_name in var_iter!(egraph[subst[table_name_var]], TableScanSourceTableName) {
+            for cubes in cube_scan_source_cubes(egraph, subst[table_name_var]) {
+                let table_name = cube_scan_source_relation(&cubes);
                 if let Some(referenced_expr) =
                     &egraph.index(subst[sort_exp_var]).data.referenced_expr
                 {
@@ -121,10 +122,9 @@ impl OrderRules {
                         .clone()
                     {
                         let column_name_to_member_name =
-                            column_name_to_member_name(member_name_to_expr, table_name.to_string());
+                            column_name_to_member_name(member_name_to_expr, table_name.clone());
                         let referenced_columns =
-                            referenced_columns(referenced_expr.clone(), table_name.to_string());
-                        let table_name = table_name.to_string();
+                            referenced_columns(referenced_expr.clone(), table_name.clone());
                         if referenced_columns
                             .iter()
                             .all(|c| column_name_to_member_name.contains_key(c))
@@ -141,7 +141,7 @@ impl OrderRules {
                             subst.insert(
                                 cube_var,
                                 egraph.add(LogicalPlanLanguage::OrderReplacerCube(
-                                    OrderReplacerCube(Some(table_name)),
+                                    OrderReplacerCube(table_name),
                                 )),
                             );
                             return true;
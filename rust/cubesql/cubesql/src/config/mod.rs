@@ -4,19 +4,22 @@ pub mod processing_loop;
 use crate::config::injection::{DIService, Injector};
 use crate::config::processing_loop::ProcessingLoop;
 use crate::sql::{
-    MySqlServer, PostgresServer, ServerManager, SessionManager, SqlAuthDefaultImpl, SqlAuthService,
+    tls::create_tls_acceptor, MySqlServer, PostgresServer, ServerManager, SessionManager,
+    SqlAuthDefaultImpl, SqlAuthService, SqlAuthStandaloneImpl,
 };
 use crate::telemetry::{start_track_event_loop, stop_track_event_loop};
-use crate::transport::{HttpTransport, TransportService};
+use crate::transport::{CubeStoreTransport, HttpTransport, TransportService};
 use crate::CubeError;
 use futures::future::join_all;
-use log::error;
+use log::{error, warn};
 
 use mockall::automock;
 
 use std::env;
 
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use tokio::task::JoinHandle;
 
@@ -119,6 +122,18 @@ pub trait ConfigObj: DIService {
     fn query_timeout(&self) -> u64;
 
     fn nonce(&self) -> &Option<Vec<u8>>;
+
+    fn tls_cert_path(&self) -> &Option<String>;
+
+    fn tls_key_path(&self) -> &Option<String>;
+
+    fn tls_required(&self) -> bool;
+
+    fn model_path(&self) -> &Option<String>;
+
+    fn cubestore_url(&self) -> &Option<String>;
+
+    fn record_path(&self) -> &Option<String>;
 }
 
 #[derive(Debug, Clone)]
@@ -127,6 +142,51 @@ pub struct ConfigObjImpl {
     pub postgres_bind_address: Option<String>,
     pub nonce: Option<Vec<u8>>,
     pub query_timeout: u64,
+    /// PEM encoded certificate chain which is used for TLS connections
+    pub tls_cert_path: Option<String>,
+    /// PEM encoded private key of the certificate
+    pub tls_key_path: Option<String>,
+    /// Reject connections which don't use TLS
+    pub tls_required: bool,
+    /// YAML or JSON data model of the standalone mode, which works without Cube API
+    pub model_path: Option<String>,
+    /// CubeStore which executes queries in the standalone mode, e.g. mysql://127.0.0.1:13306
+    pub cubestore_url: Option<String>,
+    /// Directory where client sessions are recorded as fixtures for `sql::replay`
+    pub record_path: Option<String>,
+}
+
+impl ConfigObjImpl {
+    /// Checks options which can't be verified per connection, so misconfiguration fails at startup
+    pub fn validate(&self) -> Result<(), CubeError> {
+        match (&self.tls_cert_path, &self.tls_key_path) {
+            (Some(cert_path), Some(key_path)) => {
+                create_tls_acceptor(cert_path, key_path)?;
+            }
+            (None, None) => {
+                if self.tls_required {
+                    return Err(CubeError::user(
+                        "CUBESQL_TLS_REQUIRED is set, but CUBESQL_TLS_CERT and CUBESQL_TLS_KEY are not configured".to_string(),
+                    ));
+                }
+            }
+            _ => {
+                return Err(CubeError::user(
+                    "Both CUBESQL_TLS_CERT and CUBESQL_TLS_KEY should be configured to enable TLS"
+                        .to_string(),
+                ));
+            }
+        }
+
+        // msql-srv doesn't negotiate CLIENT_SSL, plaintext connections are the only option
+        if self.tls_required && self.bind_address.is_some() {
+            return Err(CubeError::user(
+                "MySQL protocol doesn't support TLS connections yet, but TLS is required by CUBESQL_TLS_REQUIRED. Please unset CUBESQL_PORT and CUBESQL_BIND_ADDR to use PostgreSQL protocol only".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 crate::di_service!(ConfigObjImpl, [ConfigObj]);
@@ -148,6 +208,30 @@ impl ConfigObj for ConfigObjImpl {
     fn query_timeout(&self) -> u64 {
         self.query_timeout
     }
+
+    fn tls_cert_path(&self) -> &Option<String> {
+        &self.tls_cert_path
+    }
+
+    fn tls_key_path(&self) -> &Option<String> {
+        &self.tls_key_path
+    }
+
+    fn tls_required(&self) -> bool {
+        self.tls_required
+    }
+
+    fn model_path(&self) -> &Option<String> {
+        &self.model_path
+    }
+
+    fn cubestore_url(&self) -> &Option<String> {
+        &self.cubestore_url
+    }
+
+    fn record_path(&self) -> &Option<String> {
+        &self.record_path
+    }
 }
 
 lazy_static! {
@@ -161,20 +245,37 @@ impl Config {
             .ok()
             .map(|v| v.parse::<u64>().unwrap())
             .unwrap_or(120);
+        let tls_required = env::var("CUBESQL_TLS_REQUIRED")
+            .ok()
+            .map(|v| v.to_lowercase() == "true")
+            .unwrap_or(false);
+        let bind_address = env::var("CUBESQL_BIND_ADDR").ok().or_else(|| {
+            env::var("CUBESQL_PORT")
+                .ok()
+                .map(|v| format!("0.0.0.0:{}", v.parse::<u16>().unwrap()))
+        });
+        // MySQL protocol can't serve TLS connections, so its default listener is disabled
+        // when TLS is required. Explicitly configured one is rejected by validate()
+        let bind_address = match bind_address {
+            None if tls_required => None,
+            None => Some("0.0.0.0:3306".to_string()),
+            bind_address => bind_address,
+        };
         Config {
             injector: Injector::new(),
             config_obj: Arc::new(ConfigObjImpl {
-                bind_address: Some(env::var("CUBESQL_BIND_ADDR").ok().unwrap_or(
-                    format!("0.0.0.0:{}", env::var("CUBESQL_PORT")
-                            .ok()
-                            .map(|v| v.parse::<u16>().unwrap())
-                            .unwrap_or(3306u16)),
-                )),
+                bind_address,
                 postgres_bind_address: env::var("CUBESQL_PG_PORT")
                     .ok()
                     .map(|port| format!("0.0.0.0:{}", port.parse::<u16>().unwrap())),
                 nonce: None,
                 query_timeout,
+                tls_cert_path: env::var("CUBESQL_TLS_CERT").ok(),
+                tls_key_path: env::var("CUBESQL_TLS_KEY").ok(),
+                tls_required,
+                model_path: env::var("CUBESQL_MODEL_PATH").ok(),
+                cubestore_url: env::var("CUBESQL_CUBESTORE_URL").ok(),
+                record_path: env::var("CUBESQL_RECORD_PATH").ok(),
             }),
         }
     }
@@ -188,6 +289,12 @@ impl Config {
                 postgres_bind_address: None,
                 nonce: None,
                 query_timeout,
+                tls_cert_path: None,
+                tls_key_path: None,
+                tls_required: false,
+                model_path: None,
+                cubestore_url: None,
+                record_path: None,
             }),
         }
     }
@@ -212,25 +319,65 @@ impl Config {
     }
 
     pub async fn configure_injector(&self) {
+        self.config_obj
+            .validate()
+            .expect("Invalid Cube SQL configuration");
+
         let config_obj_to_register = self.config_obj.clone();
         self.injector
             .register_typed::<dyn ConfigObj, _, _, _>(async move |_| config_obj_to_register)
             .await;
 
-        self.injector
-            .register_typed::<dyn TransportService, _, _, _>(async move |_| {
-                Arc::new(HttpTransport::new())
-            })
-            .await;
+        // Standalone mode reads cubes from the data model file and queries CubeStore directly
+        match (
+            self.config_obj.model_path(),
+            self.config_obj.cubestore_url(),
+        ) {
+            (Some(model_path), Some(cubestore_url)) => {
+                let model_path = model_path.clone();
+                let cubestore_url = cubestore_url.clone();
+                self.injector
+                    .register_typed::<dyn TransportService, _, _, _>(async move |_| {
+                        Arc::new(
+                            CubeStoreTransport::new(model_path, cubestore_url).expect(
+                                "Unable to configure CubeStore transport for standalone mode",
+                            ),
+                        )
+                    })
+                    .await;
+
+                self.injector
+                    .register_typed::<dyn SqlAuthService, _, _, _>(async move |_| {
+                        Arc::new(SqlAuthStandaloneImpl)
+                    })
+                    .await;
+            }
+            _ => {
+                self.injector
+                    .register_typed::<dyn TransportService, _, _, _>(async move |_| {
+                        Arc::new(HttpTransport::new())
+                    })
+                    .await;
+
+                self.injector
+                    .register_typed::<dyn SqlAuthService, _, _, _>(async move |_| {
+                        Arc::new(SqlAuthDefaultImpl)
+                    })
+                    .await;
+            }
+        }
 
         self.injector
             .register_typed::<ServerManager, _, _, _>(async move |i| {
                 let config = i.get_service_typed::<dyn ConfigObj>().await;
-                Arc::new(ServerManager::new(
+                let mut server = ServerManager::new(
                     i.get_service_typed().await,
                     i.get_service_typed().await,
                     config.nonce().clone(),
-                ))
+                );
+                server.configuration.query_timeout = Duration::from_secs(config.query_timeout());
+                server.configuration.record_path = config.record_path().as_ref().map(PathBuf::from);
+                Arc::new(server)
             })
             .await;
 
@@ -240,16 +387,13 @@ impl Config {
             })
             .await;
 
-        self.injector
-            .register_typed::<dyn SqlAuthService, _, _, _>(async move |_| {
-                Arc::new(SqlAuthDefaultImpl)
-            })
-            .await;
-
         if self.config_obj.bind_address().is_some() {
             self.injector
                 .register_typed::<MySqlServer, _, _, _>(async move |i| {
                     let config = i.get_service_typed::<dyn ConfigObj>().await;
+                    if config.tls_cert_path().is_some() {
+                        warn!("TLS is configured, but MySQL protocol doesn't support it yet. MySQL connections are not encrypted");
+                    }
                     MySqlServer::new(
                         config.bind_address().as_ref().unwrap().to_string(),
                         i.get_service_typed().await,
@@ -262,8 +406,17 @@ impl Config {
             self.injector
                 .register_typed::<PostgresServer, _, _, _>(async move |i| {
                     let config = i.get_service_typed::<dyn ConfigObj>().await;
+                    let tls_acceptor = match (config.tls_cert_path(), config.tls_key_path()) {
+                        (Some(cert_path), Some(key_path)) => Some(
+                            create_tls_acceptor(cert_path, key_path)
+                                .expect("Unable to configure TLS for PostgreSQL server"),
+                        ),
+                        _ => None,
+                    };
                     PostgresServer::new(
                         config.postgres_bind_address().as_ref().unwrap().to_string(),
+                        tls_acceptor,
+                        config.tls_required(),
                         i.get_service_typed().await,
                     )
                 })
@@ -179,6 +179,12 @@ impl From<datafusion::error::DataFusionError> for CubeError {
     }
 }
 
+impl From<datafusion::arrow::error::ArrowError> for CubeError {
+    fn from(v: datafusion::arrow::error::ArrowError) -> Self {
+        CubeError::internal(format!("{:?}\n{}", v, Backtrace::capture()))
+    }
+}
+
 impl From<chrono::ParseError> for CubeError {
     fn from(v: chrono::ParseError) -> Self {
         CubeError::internal(v.to_string())
@@ -238,6 +244,18 @@ impl From<serde_json::Error> for CubeError {
     }
 }
 
+impl From<serde_yaml::Error> for CubeError {
+    fn from(v: serde_yaml::Error) -> Self {
+        CubeError::from_error(v)
+    }
+}
+
+impl From<mysql_async::Error> for CubeError {
+    fn from(v: mysql_async::Error) -> Self {
+        CubeError::from_error(v)
+    }
+}
+
 impl From<std::num::ParseFloatError> for CubeError {
     fn from(v: std::num::ParseFloatError) -> Self {
         CubeError::from_error(v)
@@ -1,4 +1,4 @@
-use std::{env, fmt::Debug, sync::Arc};
+use std::{collections::HashMap, env, fmt::Debug, sync::Arc};
 
 use async_trait::async_trait;
 
@@ -8,6 +8,10 @@ use crate::CubeError;
 pub struct AuthContext {
     pub access_token: String,
     pub base_path: String,
+    // Security context of the authenticated user which is passed with every load/meta request
+    pub security_context: Option<serde_json::Value>,
+    // Allows to switch session to another user by SET user = '...'
+    pub can_switch_user: bool,
 }
 
 #[derive(Debug)]
@@ -24,7 +28,12 @@ impl AuthenticateResponse {
 
 #[async_trait]
 pub trait SqlAuthService: Send + Sync + Debug {
-    async fn authenticate(&self, user: Option<String>) -> Result<AuthenticateResponse, CubeError>;
+    /// Authenticates `user` with connection `options` sent by the client (e.g. `application_name`).
+    async fn authenticate(
+        &self,
+        user: Option<String>,
+        options: HashMap<String, String>,
+    ) -> Result<AuthenticateResponse, CubeError>;
 }
 
 #[derive(Debug)]
@@ -34,7 +43,11 @@ crate::di_service!(SqlAuthDefaultImpl, [SqlAuthService]);
 
 #[async_trait]
 impl SqlAuthService for SqlAuthDefaultImpl {
-    async fn authenticate(&self, _user: Option<String>) -> Result<AuthenticateResponse, CubeError> {
+    async fn authenticate(
+        &self,
+        _user: Option<String>,
+        _options: HashMap<String, String>,
+    ) -> Result<AuthenticateResponse, CubeError> {
         Ok(AuthenticateResponse {
             context: AuthContext {
                 access_token: env::var("CUBESQL_CUBE_TOKEN")
@@ -43,6 +56,33 @@ impl SqlAuthService for SqlAuthDefaultImpl {
                 base_path: env::var("CUBESQL_CUBE_URL")
                     .ok()
                     .unwrap_or_else(|| panic!("CUBESQL_CUBE_URL is a required ENV variable")),
+                security_context: None,
+                can_switch_user: false,
+            },
+            password: None,
+        })
+    }
+}
+
+/// Auth of the standalone mode, there is no Cube API which requires a token
+#[derive(Debug)]
+pub struct SqlAuthStandaloneImpl;
+
+crate::di_service!(SqlAuthStandaloneImpl, [SqlAuthService]);
+
+#[async_trait]
+impl SqlAuthService for SqlAuthStandaloneImpl {
+    async fn authenticate(
+        &self,
+        _user: Option<String>,
+        _options: HashMap<String, String>,
+    ) -> Result<AuthenticateResponse, CubeError> {
+        Ok(AuthenticateResponse {
+            context: AuthContext {
+                access_token: "".to_string(),
+                base_path: "".to_string(),
+                security_context: None,
+                can_switch_user: false,
             },
             password: None,
         })
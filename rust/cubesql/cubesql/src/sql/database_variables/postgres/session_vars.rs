@@ -1,7 +1,20 @@
 use std::collections::HashMap;
 
-use crate::sql::database_variables::DatabaseVariables;
+use datafusion::scalar::ScalarValue;
+
+use crate::sql::database_variables::{DatabaseVariable, DatabaseVariables};
 
 pub fn defaults() -> DatabaseVariables {
-    HashMap::new()
+    let mut variables: DatabaseVariables = HashMap::new();
+
+    variables.insert(
+        "timezone".to_string(),
+        DatabaseVariable::system(
+            "timezone".to_string(),
+            ScalarValue::Utf8(Some("UTC".to_string())),
+            None,
+        ),
+    );
+
+    variables
 }
@@ -1,14 +1,14 @@
 use std::fmt::{self, Debug, Formatter};
 
-use chrono::{SecondsFormat, TimeZone, Utc};
+use chrono::{Duration, NaiveDate, SecondsFormat, TimeZone, Utc};
 use comfy_table::{Cell, Table};
 use datafusion::arrow::{
     array::{
-        Array, BooleanArray, Float64Array, Int16Array, Int32Array, Int64Array,
-        IntervalDayTimeArray, IntervalYearMonthArray, StringArray, TimestampMicrosecondArray,
-        TimestampNanosecondArray, UInt32Array, UInt64Array,
+        Array, BooleanArray, Date32Array, DecimalArray, Float64Array, Int16Array, Int32Array,
+        Int64Array, IntervalDayTimeArray, IntervalYearMonthArray, StringArray,
+        TimestampMicrosecondArray, TimestampNanosecondArray, UInt32Array, UInt64Array,
     },
-    datatypes::{DataType, IntervalUnit, TimeUnit},
+    datatypes::{DataType, IntervalUnit, Schema, TimeUnit},
     record_batch::RecordBatch,
 };
 
@@ -75,6 +75,8 @@ pub enum TableValue {
     Int64(i64),
     Boolean(bool),
     Float64(f64),
+    /// Exact decimal text, to keep precision of decimal measures
+    Decimal(String),
     Timestamp(TimestampValue),
 }
 
@@ -129,6 +131,7 @@ impl DataFrame {
                     TableValue::Int64(n) => table_row.push(n.to_string()),
                     TableValue::Boolean(b) => table_row.push(b.to_string()),
                     TableValue::Float64(n) => table_row.push(n.to_string()),
+                    TableValue::Decimal(n) => table_row.push(n.clone()),
                     TableValue::Timestamp(t) => table_row.push(t.to_string()),
                 }
             }
@@ -156,6 +159,14 @@ impl TimestampValue {
     pub fn get_time_stamp(&self) -> i64 {
         self.unix_nano
     }
+
+    /// Timestamps of a session with a time zone are local time in it, these are written
+    /// without the UTC designator.
+    pub fn to_local_string(&self) -> String {
+        Utc.timestamp_nanos(self.unix_nano)
+            .format("%Y-%m-%dT%H:%M:%S%.3f")
+            .to_string()
+    }
 }
 
 impl Debug for TimestampValue {
@@ -200,9 +211,10 @@ pub fn arrow_to_column_type(arrow_type: DataType) -> Result<ColumnType, CubeErro
     match arrow_type {
         DataType::Binary => Ok(ColumnType::Blob),
         DataType::Utf8 | DataType::LargeUtf8 => Ok(ColumnType::String),
-        DataType::Timestamp(_, _) => Ok(ColumnType::String),
+        DataType::Timestamp(_, _) => Ok(ColumnType::Timestamp),
         DataType::Interval(_) => Ok(ColumnType::String),
-        DataType::Float16 | DataType::Float64 => Ok(ColumnType::Double),
+        DataType::Date32 => Ok(ColumnType::String),
+        DataType::Float16 | DataType::Float64 | DataType::Decimal(_, _) => Ok(ColumnType::Double),
         DataType::Boolean => Ok(ColumnType::Int8),
         DataType::Int8
         | DataType::Int16
@@ -216,126 +228,175 @@ pub fn arrow_to_column_type(arrow_type: DataType) -> Result<ColumnType, CubeErro
     }
 }
 
+pub fn schema_to_columns(schema: &Schema) -> Result<Vec<Column>, CubeError> {
+    schema
+        .fields()
+        .iter()
+        .map(|field| {
+            Ok(Column::new(
+                field.name().clone(),
+                arrow_to_column_type(field.data_type().clone())?,
+                ColumnFlags::empty(),
+            ))
+        })
+        .collect()
+}
+
 pub fn batch_to_dataframe(batches: &Vec<RecordBatch>) -> Result<DataFrame, CubeError> {
     let mut cols = vec![];
     let mut all_rows = vec![];
 
     for batch in batches.iter() {
         if cols.is_empty() {
-            let schema = batch.schema().clone();
-            for (_i, field) in schema.fields().iter().enumerate() {
-                cols.push(Column::new(
-                    field.name().clone(),
-                    arrow_to_column_type(field.data_type().clone())?,
-                    ColumnFlags::empty(),
-                ));
-            }
-        }
-        if batch.num_rows() == 0 {
-            continue;
+            cols = schema_to_columns(&batch.schema())?;
         }
-        let mut rows = vec![];
+        all_rows.append(&mut batch_to_rows(batch)?);
+    }
 
-        for _ in 0..batch.num_rows() {
-            rows.push(Row::new(Vec::with_capacity(batch.num_columns())));
-        }
+    Ok(DataFrame::new(cols, all_rows))
+}
+
+/// Formats a decimal with `scale` fractional digits, without trailing zeros.
+fn decimal_to_string(value: i128, scale: usize) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    let fraction = fraction.trim_end_matches('0');
+    let sign = if value < 0 { "-" } else { "" };
+
+    if fraction.is_empty() {
+        format!("{}{}", sign, integer)
+    } else {
+        format!("{}{}.{}", sign, integer, fraction)
+    }
+}
 
-        for column_index in 0..batch.num_columns() {
-            let array = batch.column(column_index);
-            let num_rows = batch.num_rows();
-            match array.data_type() {
-                DataType::Int16 => convert_array!(array, num_rows, rows, Int16Array, Int64, i64),
-                DataType::Int32 => convert_array!(array, num_rows, rows, Int32Array, Int64, i64),
-                DataType::UInt32 => convert_array!(array, num_rows, rows, UInt32Array, Int64, i64),
-                DataType::UInt64 => convert_array!(array, num_rows, rows, UInt64Array, Int64, i64),
-                DataType::Int64 => convert_array!(array, num_rows, rows, Int64Array, Int64, i64),
-                DataType::Float64 => {
-                    let a = array.as_any().downcast_ref::<Float64Array>().unwrap();
-                    for i in 0..num_rows {
-                        rows[i].push(if a.is_null(i) {
-                            TableValue::Null
-                        } else {
-                            let decimal = a.value(i) as f64;
-                            TableValue::Float64(decimal)
-                        });
-                    }
+/// Converts a single batch, so that protocols can write rows as soon as a batch is produced.
+pub fn batch_to_rows(batch: &RecordBatch) -> Result<Vec<Row>, CubeError> {
+    let mut rows = vec![];
+
+    for _ in 0..batch.num_rows() {
+        rows.push(Row::new(Vec::with_capacity(batch.num_columns())));
+    }
+
+    for column_index in 0..batch.num_columns() {
+        let array = batch.column(column_index);
+        let num_rows = batch.num_rows();
+        match array.data_type() {
+            DataType::Int16 => convert_array!(array, num_rows, rows, Int16Array, Int64, i64),
+            DataType::Int32 => convert_array!(array, num_rows, rows, Int32Array, Int64, i64),
+            DataType::UInt32 => convert_array!(array, num_rows, rows, UInt32Array, Int64, i64),
+            DataType::UInt64 => convert_array!(array, num_rows, rows, UInt64Array, Int64, i64),
+            DataType::Int64 => convert_array!(array, num_rows, rows, Int64Array, Int64, i64),
+            DataType::Float64 => {
+                let a = array.as_any().downcast_ref::<Float64Array>().unwrap();
+                for i in 0..num_rows {
+                    rows[i].push(if a.is_null(i) {
+                        TableValue::Null
+                    } else {
+                        let decimal = a.value(i) as f64;
+                        TableValue::Float64(decimal)
+                    });
                 }
-                DataType::Utf8 => {
-                    let a = array.as_any().downcast_ref::<StringArray>().unwrap();
-                    for i in 0..num_rows {
-                        rows[i].push(if a.is_null(i) {
-                            TableValue::Null
-                        } else {
-                            TableValue::String(a.value(i).to_string())
-                        });
-                    }
+            }
+            DataType::Decimal(_, scale) => {
+                let a = array.as_any().downcast_ref::<DecimalArray>().unwrap();
+                for i in 0..num_rows {
+                    rows[i].push(if a.is_null(i) {
+                        TableValue::Null
+                    } else {
+                        TableValue::Decimal(decimal_to_string(a.value(i), *scale))
+                    });
                 }
-                DataType::Timestamp(TimeUnit::Microsecond, None) => {
-                    let a = array
-                        .as_any()
-                        .downcast_ref::<TimestampMicrosecondArray>()
-                        .unwrap();
-                    for i in 0..num_rows {
-                        rows[i].push(if a.is_null(i) {
-                            TableValue::Null
-                        } else {
-                            TableValue::Timestamp(TimestampValue::new(a.value(i) * 1000_i64))
-                        });
-                    }
+            }
+            DataType::Utf8 => {
+                let a = array.as_any().downcast_ref::<StringArray>().unwrap();
+                for i in 0..num_rows {
+                    rows[i].push(if a.is_null(i) {
+                        TableValue::Null
+                    } else {
+                        TableValue::String(a.value(i).to_string())
+                    });
                 }
-                DataType::Timestamp(TimeUnit::Nanosecond, None) => {
-                    let a = array
-                        .as_any()
-                        .downcast_ref::<TimestampNanosecondArray>()
-                        .unwrap();
-                    for i in 0..num_rows {
-                        rows[i].push(if a.is_null(i) {
-                            TableValue::Null
-                        } else {
-                            TableValue::Timestamp(TimestampValue::new(a.value(i)))
-                        });
-                    }
+            }
+            DataType::Timestamp(TimeUnit::Microsecond, None) => {
+                let a = array
+                    .as_any()
+                    .downcast_ref::<TimestampMicrosecondArray>()
+                    .unwrap();
+                for i in 0..num_rows {
+                    rows[i].push(if a.is_null(i) {
+                        TableValue::Null
+                    } else {
+                        TableValue::Timestamp(TimestampValue::new(a.value(i) * 1000_i64))
+                    });
                 }
-                DataType::Interval(IntervalUnit::DayTime) => {
-                    let a = array
-                        .as_any()
-                        .downcast_ref::<IntervalDayTimeArray>()
-                        .unwrap();
-                    for i in 0..num_rows {
-                        rows[i].push(TableValue::String(make_string_interval_day_time!(a, i)));
-                    }
+            }
+            DataType::Timestamp(TimeUnit::Nanosecond, None) => {
+                let a = array
+                    .as_any()
+                    .downcast_ref::<TimestampNanosecondArray>()
+                    .unwrap();
+                for i in 0..num_rows {
+                    rows[i].push(if a.is_null(i) {
+                        TableValue::Null
+                    } else {
+                        TableValue::Timestamp(TimestampValue::new(a.value(i)))
+                    });
                 }
-                DataType::Interval(IntervalUnit::YearMonth) => {
-                    let a = array
-                        .as_any()
-                        .downcast_ref::<IntervalYearMonthArray>()
-                        .unwrap();
-                    for i in 0..num_rows {
-                        rows[i].push(TableValue::String(make_string_interval_year_month!(a, i)));
-                    }
+            }
+            DataType::Interval(IntervalUnit::DayTime) => {
+                let a = array
+                    .as_any()
+                    .downcast_ref::<IntervalDayTimeArray>()
+                    .unwrap();
+                for i in 0..num_rows {
+                    rows[i].push(TableValue::String(make_string_interval_day_time!(a, i)));
                 }
-                DataType::Boolean => {
-                    let a = array.as_any().downcast_ref::<BooleanArray>().unwrap();
-                    for i in 0..num_rows {
-                        rows[i].push(if a.is_null(i) {
-                            TableValue::Null
-                        } else {
-                            TableValue::Boolean(a.value(i))
-                        });
-                    }
+            }
+            DataType::Interval(IntervalUnit::YearMonth) => {
+                let a = array
+                    .as_any()
+                    .downcast_ref::<IntervalYearMonthArray>()
+                    .unwrap();
+                for i in 0..num_rows {
+                    rows[i].push(TableValue::String(make_string_interval_year_month!(a, i)));
+                }
+            }
+            DataType::Date32 => {
+                let a = array.as_any().downcast_ref::<Date32Array>().unwrap();
+                for i in 0..num_rows {
+                    rows[i].push(if a.is_null(i) {
+                        TableValue::Null
+                    } else {
+                        let date =
+                            NaiveDate::from_ymd(1970, 1, 1) + Duration::days(a.value(i) as i64);
+                        TableValue::String(date.format("%Y-%m-%d").to_string())
+                    });
+                }
+            }
+            DataType::Boolean => {
+                let a = array.as_any().downcast_ref::<BooleanArray>().unwrap();
+                for i in 0..num_rows {
+                    rows[i].push(if a.is_null(i) {
+                        TableValue::Null
+                    } else {
+                        TableValue::Boolean(a.value(i))
+                    });
                 }
-                x => panic!("Unsupported data type: {:?}", x),
             }
+            x => panic!("Unsupported data type: {:?}", x),
         }
-        all_rows.append(&mut rows);
     }
 
-    Ok(DataFrame::new(cols, all_rows))
+    Ok(rows)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use datafusion::arrow::datatypes::Field;
+    use std::sync::Arc;
 
     #[test]
     fn test_dataframe_print() {
@@ -357,4 +418,34 @@ mod tests {
             +------------+"
         );
     }
+
+    #[test]
+    fn test_decimal_to_string() {
+        assert_eq!(decimal_to_string(50_500_000_000, 10), "5.05");
+        assert_eq!(decimal_to_string(-505, 4), "-0.0505");
+        assert_eq!(decimal_to_string(1200, 2), "12");
+        assert_eq!(decimal_to_string(0, 2), "0");
+        assert_eq!(decimal_to_string(42, 0), "42");
+    }
+
+    #[test]
+    fn test_batch_to_rows_date32() {
+        let schema = Arc::new(Schema::new(vec![Field::new("d", DataType::Date32, true)]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Date32Array::from(vec![Some(18969), None]))],
+        )
+        .unwrap();
+
+        let rows = batch_to_rows(&batch).unwrap();
+        assert_eq!(
+            rows.iter()
+                .map(|row| row.values().clone())
+                .collect::<Vec<_>>(),
+            vec![
+                vec![TableValue::String("2021-12-08".to_string())],
+                vec![TableValue::Null]
+            ]
+        );
+    }
 }
@@ -3,16 +3,25 @@ pub(crate) mod database_variables;
 pub(crate) mod dataframe;
 pub(crate) mod mysql;
 pub(crate) mod postgres;
+pub(crate) mod recorder;
+pub(crate) mod replay;
 pub(crate) mod server_manager;
 pub(crate) mod service;
 pub(crate) mod session;
 pub(crate) mod session_manager;
 pub(crate) mod statement;
+pub(crate) mod tls;
 pub(crate) mod types;
 
-pub use auth_service::{AuthContext, AuthenticateResponse, SqlAuthDefaultImpl, SqlAuthService};
+pub use auth_service::{
+    AuthContext, AuthenticateResponse, SqlAuthDefaultImpl, SqlAuthService, SqlAuthStandaloneImpl,
+};
 pub use mysql::MySqlServer;
 pub use postgres::PostgresServer;
+pub use recorder::{
+    RecordedLoad, RecordedQuery, RecordingTransport, SessionFixture, SessionRecorder,
+};
+pub use replay::{replay_fixtures, replay_session, ReplayTransport};
 pub use server_manager::ServerManager;
 pub use service::*;
 pub use session::{Session, SessionProcessList, SessionProperties, SessionState};
@@ -6,8 +6,11 @@ use std::time::SystemTime;
 
 use async_trait::async_trait;
 
+use chrono::{TimeZone, Utc};
 use datafusion::execution::dataframe_impl::DataFrameImpl;
+use datafusion::physical_plan::SendableRecordBatchStream;
 use datafusion::prelude::DataFrame as DFDataFrame;
+use futures::StreamExt;
 
 use log::debug;
 use log::error;
@@ -15,17 +18,18 @@ use log::trace;
 
 //use msql_srv::*;
 use msql_srv::{
-    AsyncMysqlIntermediary, AsyncMysqlShim, Column, ErrorKind, InitWriter, ParamParser,
-    QueryResultWriter, StatementMetaWriter,
+    AsyncMysqlIntermediary, AsyncMysqlShim, Column, ErrorKind, InitWriter, ParamParser, ParamValue,
+    QueryResultWriter, RowWriter, StatementMetaWriter, ValueInner,
 };
 
 use tokio::net::TcpListener;
 use tokio::sync::{watch, RwLock};
 
-use crate::compile::convert_sql_to_cube_query;
-use crate::compile::parser::parse_sql_to_statement;
+use crate::compile::parser::{parse_sql_to_statement, parse_system_command, SystemCommand};
+use crate::compile::{convert_statement_to_cube_query, QueryPlan};
 use crate::config::processing_loop::ProcessingLoop;
 
+use crate::sql::recorder::SessionRecorder;
 use crate::sql::session::DatabaseProtocol;
 use crate::sql::statement::BindValue;
 use crate::sql::statement::StatementBinder;
@@ -33,17 +37,25 @@ use crate::sql::statement::StatementPrepare;
 use crate::sql::Session;
 use crate::sql::SessionManager;
 use crate::sql::{
-    dataframe::{self, batch_to_dataframe},
+    dataframe::{self, arrow_to_column_type, batch_to_rows},
     AuthContext, ColumnFlags, ColumnType, QueryResponse, StatusFlags,
 };
 use crate::CubeError;
 use msql_srv::ColumnType as MySQLColumnType;
 use sqlparser::ast;
 
+#[derive(Debug)]
+struct PreparedStatement {
+    statement: ast::Statement,
+    /// Result columns described at prepare time. Empty for statements without a result set
+    /// and for ones which can't be planned until parameter values are known.
+    columns: Vec<Column>,
+}
+
 #[derive(Debug)]
 struct PreparedStatements {
     id: u32,
-    statements: HashMap<u32, ast::Statement>,
+    statements: HashMap<u32, PreparedStatement>,
 }
 
 impl PreparedStatements {
@@ -56,7 +68,7 @@ impl PreparedStatements {
 }
 
 #[derive(Debug)]
-struct MySqlConnection {
+pub(crate) struct MySqlConnection {
     // Prepared statements
     statements: Arc<RwLock<PreparedStatements>>,
     // Shared
@@ -77,61 +89,34 @@ impl Drop for MySqlConnection {
 }
 
 impl MySqlConnection {
+    pub(crate) fn new(session: Arc<Session>) -> Self {
+        Self {
+            session,
+            statements: Arc::new(RwLock::new(PreparedStatements::new())),
+        }
+    }
+
     // This method write response back to client after execution
     async fn handle_query<'a, W: io::Write + Send>(
         &'a mut self,
         query: &'a str,
         results: QueryResultWriter<'a, W>,
     ) -> Result<(), io::Error> {
-        match self.execute_query(query).await {
-            Err(e) => {
-                error!("Error during processing {}: {}", query, e.to_string());
-                results.error(ErrorKind::ER_INTERNAL_ERROR, e.message.as_bytes())?;
-
-                Ok(())
-            }
-            Ok(QueryResponse::Ok(status)) => {
-                results.completed(0, 0, status.to_mysql_flags())?;
-                Ok(())
-            }
-            Ok(QueryResponse::ResultSet(_, data_frame)) => {
-                let columns = data_frame
-                    .get_columns()
-                    .iter()
-                    .map(|c| Column {
-                        table: "result".to_string(), // TODO
-                        column: c.get_name(),
-                        coltype: c.get_type().to_mysql(),
-                        colflags: c.get_flags().to_mysql(),
-                    })
-                    .collect::<Vec<_>>();
-
-                let mut rw = results.start(&columns)?;
-
-                for row in data_frame.get_rows().iter() {
-                    for (_i, value) in row.values().iter().enumerate() {
-                        match value {
-                            dataframe::TableValue::String(s) => rw.write_col(s)?,
-                            dataframe::TableValue::Timestamp(s) => rw.write_col(s.to_string())?,
-                            dataframe::TableValue::Boolean(s) => rw.write_col(s.to_string())?,
-                            dataframe::TableValue::Float64(s) => rw.write_col(s)?,
-                            dataframe::TableValue::Int64(s) => rw.write_col(s)?,
-                            dataframe::TableValue::Null => rw.write_col(Option::<String>::None)?,
-                        }
-                    }
-
-                    rw.end_row()?;
-                }
-
-                rw.finish()?;
-
-                Ok(())
-            }
+        let recorder = self.session.state.recorder();
+        if let Some(recorder) = &recorder {
+            recorder.begin_query(query);
         }
+
+        let response = self.execute_query(query).await;
+        let local_timestamps = self.session.state.time_zone().is_some();
+        write_response(query, response, results, false, local_timestamps, recorder).await
     }
 
     // This method executes query and return it as DataFrame
-    async fn execute_query<'a>(&'a mut self, query: &'a str) -> Result<QueryResponse, CubeError> {
+    pub(crate) async fn execute_query<'a>(
+        &'a mut self,
+        query: &'a str,
+    ) -> Result<QueryResponse, CubeError> {
         let _start = SystemTime::now();
 
         let query = query.replace("SELECT FROM", "SELECT * FROM");
@@ -191,32 +176,21 @@ impl MySqlConnection {
                     )
                 ),)
             )
+        } else if let Some(command) = parse_system_command(&query) {
+            match command {
+                SystemCommand::RefreshMeta => {
+                    self.session.server.transport
+                        .invalidate_meta(self.auth_context().await?)
+                        .await?;
+                }
+            }
+
+            return Ok(QueryResponse::Ok(StatusFlags::empty()));
         } else if !ignore {
             trace!("query was not detected");
 
-            let meta = self.session.server.transport
-                .meta(self.auth_context()?)
-                .await?;
-
-            let plan = convert_sql_to_cube_query(&query, meta, self.session.clone())?;
-            match plan {
-                crate::compile::QueryPlan::MetaOk(status) => {
-                    return Ok(QueryResponse::Ok(status));
-                },
-                crate::compile::QueryPlan::MetaTabular(status, data_frame) => {
-                    return Ok(QueryResponse::ResultSet(status, data_frame));
-                },
-                crate::compile::QueryPlan::DataFusionSelect(status, plan, ctx) => {
-                    let df = DataFrameImpl::new(
-                        ctx.state,
-                        &plan,
-                    );
-                    let batches = df.collect().await?;
-                    let response =  batch_to_dataframe(&batches)?;
-
-                    return Ok(QueryResponse::ResultSet(status, Arc::new(response)))
-                }
-            }
+            let statement = parse_sql_to_statement(&query, DatabaseProtocol::MySQL)?;
+            return self.execute_statement(statement).await;
         }
 
         if ignore {
@@ -229,13 +203,84 @@ impl MySqlConnection {
         }
     }
 
-    pub(crate) fn auth_context(&self) -> Result<Arc<AuthContext>, CubeError> {
-        if let Some(ctx) = self.session.state.auth_context() {
-            Ok(Arc::new(ctx))
-        } else {
-            Err(CubeError::internal("must be auth".to_string()))
+    async fn execute_statement(
+        &mut self,
+        statement: ast::Statement,
+    ) -> Result<QueryResponse, CubeError> {
+        self.session.state.begin_query();
+
+        let meta = self
+            .session
+            .server
+            .transport
+            .meta(self.auth_context().await?)
+            .await?;
+        if let Some(recorder) = self.session.state.recorder() {
+            recorder.record_meta(&meta);
+        }
+
+        let plan = convert_statement_to_cube_query(&statement, meta, self.session.clone())?;
+        match plan {
+            QueryPlan::MetaOk(status) => Ok(QueryResponse::Ok(status)),
+            QueryPlan::MetaTabular(status, data_frame) => {
+                Ok(QueryResponse::ResultSet(status, data_frame))
+            }
+            QueryPlan::DataFusionSelect(status, plan, ctx) => {
+                let df = DataFrameImpl::new(ctx.state, &plan);
+                let stream = df.execute_stream().await?;
+
+                Ok(QueryResponse::Stream(status, stream))
+            }
         }
     }
+
+    // Plans the statement with NULL in place of parameters to find out its result columns
+    async fn describe_statement(
+        &self,
+        statement: &ast::Statement,
+        parameters: usize,
+    ) -> Result<Vec<Column>, CubeError> {
+        // Planning of other statements may change the session, e.g. SET
+        if !matches!(statement, ast::Statement::Query(_)) {
+            return Ok(vec![]);
+        }
+
+        let mut statement = statement.clone();
+        let mut binder = StatementBinder::new((0..parameters).map(|_| BindValue::Null).collect());
+        binder.bind(&mut statement);
+
+        let meta = self
+            .session
+            .server
+            .transport
+            .meta(self.auth_context().await?)
+            .await?;
+
+        let columns = match convert_statement_to_cube_query(&statement, meta, self.session.clone())?
+        {
+            QueryPlan::MetaOk(_) => vec![],
+            QueryPlan::MetaTabular(_, data_frame) => data_frame.get_columns().clone(),
+            QueryPlan::DataFusionSelect(_, plan, _) => plan
+                .schema()
+                .fields()
+                .iter()
+                .map(|f| {
+                    Ok(dataframe::Column::new(
+                        f.name().clone(),
+                        arrow_to_column_type(f.data_type().clone())?,
+                        ColumnFlags::empty(),
+                    ))
+                })
+                .collect::<Result<Vec<_>, CubeError>>()?,
+        };
+
+        Ok(columns.iter().map(|c| to_mysql_column(c, true)).collect())
+    }
+
+    pub(crate) async fn auth_context(&self) -> Result<Arc<AuthContext>, CubeError> {
+        // MySQL handshake doesn't provide connection options
+        self.session.auth_context(HashMap::new()).await
+    }
 }
 
 #[async_trait]
@@ -255,7 +300,7 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for MySqlConnection {
         input: &'a str,
         info: StatementMetaWriter<'a, W>,
     ) -> Result<(), Self::Error> {
-        debug!("[mysql] on_execute: {}", input);
+        debug!("[mysql] on_prepare: {}", input);
 
         let mut statement =
             match parse_sql_to_statement(&input.to_string(), DatabaseProtocol::MySQL) {
@@ -269,6 +314,14 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for MySqlConnection {
         let mut stmt_prepare = StatementPrepare::new();
         let paramaters = stmt_prepare.prepare(&mut statement);
 
+        let columns = match self.describe_statement(&statement, paramaters.len()).await {
+            Ok(columns) => columns,
+            Err(e) => {
+                debug!("[mysql] Unable to describe prepared statement: {}", e);
+                vec![]
+            }
+        };
+
         let mut state = self.statements.write().await;
         if state.statements.len()
             > self
@@ -287,9 +340,12 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for MySqlConnection {
             state.id = state.id + 1;
 
             let next_id = state.id;
-            state.statements.insert(next_id, statement);
+            info.reply(next_id, paramaters, &columns)?;
+            state
+                .statements
+                .insert(next_id, PreparedStatement { statement, columns });
 
-            info.reply(state.id, paramaters, &[])
+            Ok(())
         }
     }
 
@@ -308,49 +364,34 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for MySqlConnection {
             if possible_statement.is_none() {
                 return results.error(ErrorKind::ER_INTERNAL_ERROR, b"Unknown statement");
             } else {
-                possible_statement.unwrap().clone()
+                possible_statement.unwrap().statement.clone()
             }
         };
 
         let mut values_to_bind: Vec<BindValue> = vec![];
 
         for p in params_parser.into_iter() {
-            let bind_value = match p.coltype {
-                MySQLColumnType::MYSQL_TYPE_TINY => {
-                    BindValue::Bool(Into::<u8>::into(p.value) == 0_u8)
-                }
-                MySQLColumnType::MYSQL_TYPE_SHORT => {
-                    BindValue::Int64(Into::<i16>::into(p.value) as i64)
-                }
-                MySQLColumnType::MYSQL_TYPE_LONG => {
-                    BindValue::Int64(Into::<i32>::into(p.value) as i64)
-                }
-                MySQLColumnType::MYSQL_TYPE_LONGLONG => {
-                    BindValue::Int64(Into::<i64>::into(p.value))
-                }
-                MySQLColumnType::MYSQL_TYPE_FLOAT => {
-                    BindValue::Float64(Into::<f32>::into(p.value) as f64)
-                }
-                MySQLColumnType::MYSQL_TYPE_DOUBLE => {
-                    BindValue::Float64(Into::<f64>::into(p.value))
-                }
-                MySQLColumnType::MYSQL_TYPE_VAR_STRING | MySQLColumnType::MYSQL_TYPE_STRING => {
-                    BindValue::String(Into::<&str>::into(p.value).to_string())
+            match bind_value(p) {
+                Ok(bind_value) => values_to_bind.push(bind_value),
+                Err(e) => {
+                    return results.error(ErrorKind::ER_WRONG_ARGUMENTS, e.message.as_bytes());
                 }
-                ct => unimplemented!(
-                    "Unsupported column type for biding value into prepared statement: {:?}",
-                    ct
-                ),
-            };
-
-            values_to_bind.push(bind_value);
+            }
         }
 
+        let query = statement.to_string();
         let mut binder = StatementBinder::new(values_to_bind);
         binder.bind(&mut statement);
 
-        self.handle_query(statement.to_string().as_str(), results)
-            .await
+        // Bound statement is recorded, so it can be replayed as a plain query
+        let recorder = self.session.state.recorder();
+        if let Some(recorder) = &recorder {
+            recorder.begin_query(&statement.to_string());
+        }
+
+        let response = self.execute_statement(statement).await;
+        let local_timestamps = self.session.state.time_zone().is_some();
+        write_response(&query, response, results, true, local_timestamps, recorder).await
     }
 
     /// On close will be called when client sends COM_STMT_CLOSE
@@ -392,7 +433,7 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for MySqlConnection {
             .session
             .server
             .auth
-            .authenticate(user.clone())
+            .authenticate(user.clone(), HashMap::new())
             .await
             .map_err(|e| {
                 if e.message != *"Incorrect user name or password" {
@@ -408,6 +449,9 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for MySqlConnection {
         self.session
             .state
             .set_auth_context(Some(auth_response.context));
+        if let (Some(recorder), Some(user)) = (self.session.state.recorder(), user) {
+            recorder.record_startup(vec![("user".to_string(), user)]);
+        }
 
         Ok(passwd)
     }
@@ -442,12 +486,287 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for MySqlConnection {
             return Ok(());
         };
 
+        if let Some(recorder) = self.session.state.recorder() {
+            recorder.record_startup(vec![("database".to_string(), database.to_string())]);
+        }
+
         writter.ok()?;
 
         Ok(())
     }
 }
 
+fn to_mysql_column(column: &dataframe::Column, binary: bool) -> Column {
+    Column {
+        table: "result".to_string(), // TODO
+        column: column.get_name(),
+        coltype: if binary {
+            column.get_type().to_mysql_binary()
+        } else {
+            column.get_type().to_mysql()
+        },
+        colflags: column.get_flags().to_mysql(),
+    }
+}
+
+// Text protocol is used for plain queries and binary one for prepared statements
+async fn write_response<'a, W: io::Write + Send>(
+    query: &str,
+    response: Result<QueryResponse, CubeError>,
+    results: QueryResultWriter<'a, W>,
+    binary: bool,
+    local_timestamps: bool,
+    recorder: Option<Arc<SessionRecorder>>,
+) -> Result<(), io::Error> {
+    match response {
+        Err(e) => write_error(query, e, results, &recorder),
+        Ok(QueryResponse::Ok(status)) => {
+            results.completed(0, 0, status.to_mysql_flags())?;
+            Ok(())
+        }
+        Ok(QueryResponse::ResultSet(_, data_frame)) => {
+            let columns = data_frame
+                .get_columns()
+                .iter()
+                .map(|c| to_mysql_column(c, binary))
+                .collect::<Vec<_>>();
+
+            if let Some(recorder) = &recorder {
+                recorder.record_columns(data_frame.get_columns());
+                recorder.record_rows(data_frame.get_rows());
+            }
+
+            let mut rw = results.start(&columns)?;
+
+            for row in data_frame.get_rows().iter() {
+                write_row(&mut rw, row, &columns, binary, local_timestamps)?;
+            }
+
+            rw.finish()?;
+
+            Ok(())
+        }
+        Ok(QueryResponse::Stream(_, mut stream)) => {
+            let columns = match dataframe::schema_to_columns(&stream.schema()) {
+                Ok(columns) => {
+                    if let Some(recorder) = &recorder {
+                        recorder.record_columns(&columns);
+                    }
+
+                    columns
+                        .iter()
+                        .map(|c| to_mysql_column(c, binary))
+                        .collect::<Vec<_>>()
+                }
+                Err(e) => return write_error(query, e, results, &recorder),
+            };
+
+            // Errors usually come with the first batch, when the client still can get them
+            let first_batch = match next_rows(&mut stream).await {
+                Ok(rows) => rows,
+                Err(e) => return write_error(query, e, results, &recorder),
+            };
+
+            // TODO: msql-srv writes the response into a buffer which is sent once the handler
+            // returns and `RowWriter` has no way to flush it, so unlike PostgreSQL the client
+            // gets the rows when the query completes, without backpressure. Sending rows per
+            // batch needs support for it in msql-srv. Until then rows are only converted batch
+            // by batch, so the record batches of the result are never collected.
+            let mut rw = results.start(&columns)?;
+            write_batches(query, &mut stream, first_batch, &recorder, |row| {
+                write_row(&mut rw, row, &columns, binary, local_timestamps)
+            })
+            .await?;
+
+            rw.finish()?;
+
+            Ok(())
+        }
+    }
+}
+
+async fn next_rows(
+    stream: &mut SendableRecordBatchStream,
+) -> Result<Option<Vec<dataframe::Row>>, CubeError> {
+    match stream.next().await {
+        None => Ok(None),
+        Some(batch) => Ok(Some(batch_to_rows(&batch?)?)),
+    }
+}
+
+async fn write_batches(
+    query: &str,
+    stream: &mut SendableRecordBatchStream,
+    first_batch: Option<Vec<dataframe::Row>>,
+    recorder: &Option<Arc<SessionRecorder>>,
+    mut write_row: impl FnMut(&dataframe::Row) -> Result<(), io::Error>,
+) -> Result<(), io::Error> {
+    let mut rows = first_batch;
+    while let Some(batch) = rows {
+        if let Some(recorder) = recorder {
+            recorder.record_rows(&batch);
+        }
+
+        for row in batch.iter() {
+            write_row(row)?;
+        }
+
+        rows = next_rows(stream).await.map_err(|e| {
+            error!("Error during processing {}: {}", query, e.to_string());
+            if let Some(recorder) = recorder {
+                recorder.record_error(&e);
+            }
+
+            io::Error::new(io::ErrorKind::Other, e.to_string())
+        })?;
+    }
+
+    Ok(())
+}
+
+fn write_error<'a, W: io::Write + Send>(
+    query: &str,
+    e: CubeError,
+    results: QueryResultWriter<'a, W>,
+    recorder: &Option<Arc<SessionRecorder>>,
+) -> Result<(), io::Error> {
+    error!("Error during processing {}: {}", query, e.to_string());
+    if let Some(recorder) = recorder {
+        recorder.record_error(&e);
+    }
+
+    results.error(ErrorKind::ER_INTERNAL_ERROR, e.message.as_bytes())?;
+
+    Ok(())
+}
+
+fn write_row<W: io::Write>(
+    rw: &mut RowWriter<W>,
+    row: &dataframe::Row,
+    columns: &Vec<Column>,
+    binary: bool,
+    local_timestamps: bool,
+) -> Result<(), io::Error> {
+    for (value, column) in row.values().iter().zip(columns.iter()) {
+        if binary {
+            write_binary_value(rw, value, column.coltype)?;
+            continue;
+        }
+
+        match value {
+            dataframe::TableValue::String(s) => rw.write_col(s)?,
+            dataframe::TableValue::Timestamp(s) if local_timestamps => {
+                rw.write_col(s.to_local_string())?
+            }
+            dataframe::TableValue::Timestamp(s) => rw.write_col(s.to_string())?,
+            dataframe::TableValue::Boolean(s) => rw.write_col(s.to_string())?,
+            dataframe::TableValue::Float64(s) => rw.write_col(s)?,
+            dataframe::TableValue::Decimal(s) => rw.write_col(s)?,
+            dataframe::TableValue::Int64(s) => rw.write_col(s)?,
+            dataframe::TableValue::Null => rw.write_col(Option::<String>::None)?,
+        }
+    }
+
+    rw.end_row()
+}
+
+// Binary protocol fails on values which don't match the column type, these are sent as strings
+fn write_binary_value<W: io::Write>(
+    rw: &mut RowWriter<W>,
+    value: &dataframe::TableValue,
+    coltype: MySQLColumnType,
+) -> Result<(), io::Error> {
+    match (value, coltype) {
+        (dataframe::TableValue::Null, _) => rw.write_col(Option::<String>::None),
+        (dataframe::TableValue::Int64(v), MySQLColumnType::MYSQL_TYPE_LONGLONG) => rw.write_col(*v),
+        (dataframe::TableValue::Int64(v), MySQLColumnType::MYSQL_TYPE_LONG) => {
+            rw.write_col(*v as i32)
+        }
+        (dataframe::TableValue::Int64(v), MySQLColumnType::MYSQL_TYPE_TINY) => {
+            rw.write_col(*v as i8)
+        }
+        (dataframe::TableValue::Boolean(v), MySQLColumnType::MYSQL_TYPE_TINY) => {
+            rw.write_col(*v as i8)
+        }
+        (dataframe::TableValue::Float64(v), MySQLColumnType::MYSQL_TYPE_DOUBLE) => rw.write_col(*v),
+        (dataframe::TableValue::Decimal(v), MySQLColumnType::MYSQL_TYPE_DOUBLE) => {
+            rw.write_col(v.parse::<f64>().unwrap_or_default())
+        }
+        (dataframe::TableValue::Timestamp(v), MySQLColumnType::MYSQL_TYPE_DATETIME) => {
+            rw.write_col(Utc.timestamp_nanos(v.get_time_stamp()).naive_utc())
+        }
+        (dataframe::TableValue::String(v), _) => rw.write_col(v),
+        (dataframe::TableValue::Timestamp(v), _) => rw.write_col(v.to_string()),
+        (dataframe::TableValue::Boolean(v), _) => rw.write_col(v.to_string()),
+        (dataframe::TableValue::Float64(v), _) => rw.write_col(v.to_string()),
+        (dataframe::TableValue::Decimal(v), _) => rw.write_col(v),
+        (dataframe::TableValue::Int64(v), _) => rw.write_col(v.to_string()),
+    }
+}
+
+fn bind_value(p: ParamValue) -> Result<BindValue, CubeError> {
+    if let ValueInner::NULL = p.value.into_inner() {
+        return Ok(BindValue::Null);
+    }
+
+    let bind_value = match p.coltype {
+        MySQLColumnType::MYSQL_TYPE_TINY => BindValue::Bool(Into::<u8>::into(p.value) != 0_u8),
+        MySQLColumnType::MYSQL_TYPE_SHORT | MySQLColumnType::MYSQL_TYPE_YEAR => {
+            BindValue::Int64(Into::<i16>::into(p.value) as i64)
+        }
+        MySQLColumnType::MYSQL_TYPE_LONG | MySQLColumnType::MYSQL_TYPE_INT24 => {
+            BindValue::Int64(Into::<i32>::into(p.value) as i64)
+        }
+        MySQLColumnType::MYSQL_TYPE_LONGLONG => BindValue::Int64(Into::<i64>::into(p.value)),
+        MySQLColumnType::MYSQL_TYPE_FLOAT => BindValue::Float64(Into::<f32>::into(p.value) as f64),
+        MySQLColumnType::MYSQL_TYPE_DOUBLE => BindValue::Float64(Into::<f64>::into(p.value)),
+        MySQLColumnType::MYSQL_TYPE_DECIMAL | MySQLColumnType::MYSQL_TYPE_NEWDECIMAL => {
+            BindValue::Decimal(Into::<&str>::into(p.value).to_string())
+        }
+        MySQLColumnType::MYSQL_TYPE_DATE => BindValue::String(
+            Into::<chrono::NaiveDate>::into(p.value)
+                .format("%Y-%m-%d")
+                .to_string(),
+        ),
+        MySQLColumnType::MYSQL_TYPE_DATETIME | MySQLColumnType::MYSQL_TYPE_TIMESTAMP => {
+            BindValue::String(
+                Into::<chrono::NaiveDateTime>::into(p.value)
+                    .format("%Y-%m-%d %H:%M:%S%.f")
+                    .to_string(),
+            )
+        }
+        MySQLColumnType::MYSQL_TYPE_TIME => {
+            let seconds = Into::<std::time::Duration>::into(p.value).as_secs();
+            BindValue::String(format!(
+                "{:02}:{:02}:{:02}",
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            ))
+        }
+        MySQLColumnType::MYSQL_TYPE_VAR_STRING
+        | MySQLColumnType::MYSQL_TYPE_STRING
+        | MySQLColumnType::MYSQL_TYPE_VARCHAR
+        | MySQLColumnType::MYSQL_TYPE_ENUM
+        | MySQLColumnType::MYSQL_TYPE_SET
+        | MySQLColumnType::MYSQL_TYPE_JSON
+        | MySQLColumnType::MYSQL_TYPE_TINY_BLOB
+        | MySQLColumnType::MYSQL_TYPE_MEDIUM_BLOB
+        | MySQLColumnType::MYSQL_TYPE_LONG_BLOB
+        | MySQLColumnType::MYSQL_TYPE_BLOB => {
+            BindValue::String(String::from_utf8_lossy(Into::<&[u8]>::into(p.value)).to_string())
+        }
+        ct => {
+            return Err(CubeError::user(format!(
+                "Unsupported column type for binding value into prepared statement: {:?}",
+                ct
+            )))
+        }
+    };
+
+    Ok(bind_value)
+}
+
 pub struct MySqlServer {
     address: String,
     session_manager: Arc<SessionManager>,
@@ -492,16 +811,22 @@ impl ProcessingLoop for MySqlServer {
                 socket.peer_addr().unwrap().to_string(),
             );
 
+            let connection_token = session.state.connection_token();
             tokio::spawn(async move {
-                if let Err(e) = AsyncMysqlIntermediary::run_on(
-                    MySqlConnection {
-                        session,
-                        statements: Arc::new(RwLock::new(PreparedStatements::new())),
-                    },
-                    socket,
-                )
-                .await
-                {
+                let connection =
+                    AsyncMysqlIntermediary::run_on(MySqlConnection::new(session), socket);
+
+                // Dropping the connection closes the socket, see KILL CONNECTION
+                let result = tokio::select! {
+                    result = connection => result,
+                    _ = connection_token.cancelled() => {
+                        trace!("[mysql] Connection was killed");
+
+                        return;
+                    }
+                };
+
+                if let Err(e) = result {
                     error!("Error during processing MySQL connection: {}", e);
                     trace!("Details: {:?}", e);
                 }
@@ -526,3 +851,100 @@ impl MySqlServer {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use datafusion::{
+        arrow::{
+            array::Int64Array,
+            datatypes::{DataType, Field, Schema, SchemaRef},
+            error::Result as ArrowResult,
+            record_batch::RecordBatch,
+        },
+        physical_plan::RecordBatchStream,
+    };
+    use futures::Stream;
+
+    use super::*;
+
+    /// Logs when each batch is pulled from the stream.
+    struct LoggedStream {
+        schema: SchemaRef,
+        batches: std::vec::IntoIter<RecordBatch>,
+        log: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl Stream for LoggedStream {
+        type Item = ArrowResult<RecordBatch>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let batch = self.batches.next();
+            let event = match &batch {
+                Some(_) => "next batch",
+                None => "end",
+            };
+            self.log.lock().unwrap().push(event.to_string());
+            Poll::Ready(batch.map(Ok))
+        }
+    }
+
+    impl RecordBatchStream for LoggedStream {
+        fn schema(&self) -> SchemaRef {
+            self.schema.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_batches() -> Result<(), CubeError> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, false)]));
+        let batches = (0..3)
+            .map(|i| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int64Array::from(vec![i * 2, i * 2 + 1]))],
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut stream: SendableRecordBatchStream = Box::pin(LoggedStream {
+            schema,
+            batches: batches.into_iter(),
+            log: log.clone(),
+        });
+
+        let first_batch = next_rows(&mut stream).await?;
+        assert_eq!(first_batch.as_ref().map(|rows| rows.len()), Some(2));
+
+        write_batches("SELECT a", &mut stream, first_batch, &None, |row| {
+            log.lock()
+                .unwrap()
+                .push(format!("row {:?}", row.values()[0]));
+            Ok(())
+        })
+        .await?;
+
+        // Rows of a batch are written before the next batch is pulled
+        assert_eq!(
+            log.lock().unwrap().clone(),
+            vec![
+                "next batch",
+                "row Int64(0)",
+                "row Int64(1)",
+                "next batch",
+                "row Int64(2)",
+                "row Int64(3)",
+                "next batch",
+                "row Int64(4)",
+                "row Int64(5)",
+                "end",
+            ]
+        );
+
+        Ok(())
+    }
+}
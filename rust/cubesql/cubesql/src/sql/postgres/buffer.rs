@@ -98,21 +98,31 @@ pub async fn write_message<Writer: AsyncWriteExt + Unpin, Message: Serialize>(
     writer: &mut Writer,
     message: Message,
 ) -> Result<(), Error> {
-    let mut packet_buffer = Vec::with_capacity(64);
-    packet_buffer.push(message.code());
-    match message.serialize() {
-        Some(buffer) => {
-            let size = u32::try_from(buffer.len() + 4).map_err(|_| {
-                Error::new(
-                    ErrorKind::OutOfMemory,
-                    "Unable to convert buffer length to a suitable memory size",
-                )
-            })?;
-            packet_buffer.extend_from_slice(&size.to_be_bytes());
-            packet_buffer.extend_from_slice(&buffer);
-        }
-        _ => (),
-    };
+    write_messages(writer, vec![message]).await
+}
+
+/// Writes messages with a single flush, e.g. all data rows of a record batch.
+pub async fn write_messages<Writer: AsyncWriteExt + Unpin, Message: Serialize>(
+    writer: &mut Writer,
+    messages: Vec<Message>,
+) -> Result<(), Error> {
+    let mut packet_buffer = Vec::with_capacity(64 * messages.len());
+    for message in messages {
+        packet_buffer.push(message.code());
+        match message.serialize() {
+            Some(buffer) => {
+                let size = u32::try_from(buffer.len() + 4).map_err(|_| {
+                    Error::new(
+                        ErrorKind::OutOfMemory,
+                        "Unable to convert buffer length to a suitable memory size",
+                    )
+                })?;
+                packet_buffer.extend_from_slice(&size.to_be_bytes());
+                packet_buffer.extend_from_slice(&buffer);
+            }
+            _ => (),
+        };
+    }
     writer.write_all(&packet_buffer).await?;
     writer.flush().await?;
     Ok(())
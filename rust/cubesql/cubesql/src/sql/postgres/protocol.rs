@@ -13,6 +13,8 @@ use super::buffer;
 const DEFAULT_CAPACITY: usize = 64;
 
 pub const SSL_REQUEST_PROTOCOL: u16 = 1234;
+// CancelRequest is sent instead of the startup message as version 1234.5678
+pub const CANCEL_REQUEST_PROTOCOL: u16 = 5678;
 
 pub struct StartupMessage {
     pub protocol_version: ProtocolVersion,
@@ -45,6 +47,21 @@ impl StartupMessage {
     }
 }
 
+pub struct CancelRequest {
+    pub process_id: u32,
+    pub secret: u32,
+}
+
+impl CancelRequest {
+    // Reads the rest of the startup message, see `StartupMessage::from`
+    pub async fn from(buffer: &mut Cursor<Vec<u8>>) -> Result<Self, Error> {
+        let process_id = buffer.read_u32().await?;
+        let secret = buffer.read_u32().await?;
+
+        Ok(Self { process_id, secret })
+    }
+}
+
 pub struct ErrorResponse {
     // https://www.postgresql.org/docs/14/protocol-error-fields.html
     pub severity: ErrorSeverity,
@@ -83,11 +100,13 @@ impl Serialize for ErrorResponse {
     }
 }
 
-pub struct SSLResponse {}
+pub struct SSLResponse {
+    accepted: bool,
+}
 
 impl SSLResponse {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(accepted: bool) -> Self {
+        Self { accepted }
     }
 }
 
@@ -97,6 +116,14 @@ impl Serialize for SSLResponse {
     fn serialize(&self) -> Option<Vec<u8>> {
         None
     }
+
+    fn code(&self) -> u8 {
+        if self.accepted {
+            b'S'
+        } else {
+            Self::CODE
+        }
+    }
 }
 
 pub struct Authentication {
@@ -117,6 +144,28 @@ impl Serialize for Authentication {
     }
 }
 
+pub struct BackendKeyData {
+    process_id: u32,
+    secret: u32,
+}
+
+impl BackendKeyData {
+    pub fn new(process_id: u32, secret: u32) -> Self {
+        Self { process_id, secret }
+    }
+}
+
+impl Serialize for BackendKeyData {
+    const CODE: u8 = b'K';
+
+    fn serialize(&self) -> Option<Vec<u8>> {
+        let mut buffer = Vec::with_capacity(8);
+        buffer.extend_from_slice(&self.process_id.to_be_bytes());
+        buffer.extend_from_slice(&self.secret.to_be_bytes());
+        Some(buffer)
+    }
+}
+
 pub struct ReadyForQuery {
     transaction_status: TransactionStatus,
 }
@@ -739,4 +788,28 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_startup_message_parse_cancel_request() -> Result<(), CubeError> {
+        let buffer = parse_hex_dump(
+            r#"
+            00 00 00 10 04 d2 16 2e 00 00 00 07 12 34 56 78   .............4Vx
+            "#
+            .to_string(),
+        );
+        let mut cursor = Cursor::new(buffer);
+
+        let mut buffer = buffer::read_contents(&mut cursor).await?;
+        let startup_message = StartupMessage::from(&mut buffer).await?;
+        assert_eq!(
+            startup_message.protocol_version,
+            ProtocolVersion::new(SSL_REQUEST_PROTOCOL, CANCEL_REQUEST_PROTOCOL)
+        );
+
+        let cancel_request = CancelRequest::from(&mut buffer).await?;
+        assert_eq!(cancel_request.process_id, 7);
+        assert_eq!(cancel_request.secret, 0x12345678);
+
+        Ok(())
+    }
 }
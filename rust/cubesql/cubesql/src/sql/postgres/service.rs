@@ -6,18 +6,17 @@ use tokio::{
     net::TcpListener,
     sync::{watch, RwLock},
 };
+use tokio_rustls::TlsAcceptor;
 
-use crate::{
-    config::processing_loop::ProcessingLoop,
-    sql::{session::DatabaseProtocol, SessionManager},
-    CubeError,
-};
+use crate::{config::processing_loop::ProcessingLoop, sql::SessionManager, CubeError};
 
 use super::shim::AsyncPostgresShim;
 
 pub struct PostgresServer {
     // options
     address: String,
+    tls_acceptor: Option<TlsAcceptor>,
+    tls_required: bool,
     close_socket_rx: RwLock<watch::Receiver<bool>>,
     close_socket_tx: watch::Sender<bool>,
     // reference
@@ -56,13 +55,21 @@ impl ProcessingLoop for PostgresServer {
                 }
             };
 
-            let session = self.session_manager.create_session(
-                DatabaseProtocol::PostgreSQL,
-                socket.peer_addr().unwrap().to_string(),
-            );
-
+            // Session is created by the shim, CancelRequest connections don't need one
+            let session_manager = self.session_manager.clone();
+            let host = socket.peer_addr().unwrap().to_string();
+            let tls_acceptor = self.tls_acceptor.clone();
+            let tls_required = self.tls_required;
             tokio::spawn(async move {
-                if let Err(e) = AsyncPostgresShim::run_on(socket, session).await {
+                if let Err(e) = AsyncPostgresShim::run_on(
+                    socket,
+                    session_manager,
+                    host,
+                    tls_acceptor,
+                    tls_required,
+                )
+                .await
+                {
                     error!("Error during processing PostgreSQL connection: {}", e);
                 }
             });
@@ -76,10 +83,17 @@ impl ProcessingLoop for PostgresServer {
 }
 
 impl PostgresServer {
-    pub fn new(address: String, session_manager: Arc<SessionManager>) -> Arc<Self> {
+    pub fn new(
+        address: String,
+        tls_acceptor: Option<TlsAcceptor>,
+        tls_required: bool,
+        session_manager: Arc<SessionManager>,
+    ) -> Arc<Self> {
         let (close_socket_tx, close_socket_rx) = watch::channel(false);
         Arc::new(Self {
             address,
+            tls_acceptor,
+            tls_required,
             session_manager,
             close_socket_rx: RwLock::new(close_socket_rx),
             close_socket_tx,
@@ -5,28 +5,43 @@ use std::{
 };
 
 use datafusion::{dataframe::DataFrame, execution::dataframe_impl::DataFrameImpl};
+use futures::StreamExt;
 use log::{debug, error, trace};
-use tokio::{io::AsyncWriteExt, net::TcpStream};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio_rustls::TlsAcceptor;
 
 use crate::{
-    compile::convert_sql_to_cube_query,
+    compile::{
+        convert_sql_to_cube_query,
+        parser::{parse_system_command, SystemCommand},
+    },
     sql::{
-        dataframe::{batch_to_dataframe, TableValue},
-        AuthContext, QueryResponse, Session,
+        dataframe::{batch_to_rows, schema_to_columns, Row, TableValue},
+        session::DatabaseProtocol,
+        AuthContext, QueryResponse, Session, SessionManager, StatusFlags,
     },
     CubeError,
 };
 
 use super::{
     buffer,
-    protocol::{self, FrontendMessage, SSL_REQUEST_PROTOCOL},
+    protocol::{self, FrontendMessage, CANCEL_REQUEST_PROTOCOL, SSL_REQUEST_PROTOCOL},
 };
 
+pub trait PostgresSocket: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> PostgresSocket for T {}
+
 pub struct AsyncPostgresShim {
-    socket: TcpStream,
-    #[allow(unused)]
+    socket: Box<dyn PostgresSocket>,
     parameters: HashMap<String, String>,
-    session: Arc<Session>,
+    // Created once the startup message is accepted, so CancelRequest connections don't get one
+    session: Option<Arc<Session>>,
+    session_manager: Arc<SessionManager>,
+    host: String,
+    tls_acceptor: Option<TlsAcceptor>,
+    tls_required: bool,
+    is_tls: bool,
 }
 
 #[derive(PartialEq, Eq)]
@@ -37,16 +52,31 @@ pub enum StartupState {
 }
 
 impl AsyncPostgresShim {
-    pub async fn run_on(socket: TcpStream, session: Arc<Session>) -> Result<(), Error> {
+    pub async fn run_on(
+        socket: impl PostgresSocket + 'static,
+        session_manager: Arc<SessionManager>,
+        host: String,
+        tls_acceptor: Option<TlsAcceptor>,
+        tls_required: bool,
+    ) -> Result<(), Error> {
         let mut shim = Self {
-            socket,
+            socket: Box::new(socket),
             parameters: HashMap::new(),
-            session,
+            session: None,
+            session_manager,
+            host,
+            tls_acceptor,
+            tls_required,
+            is_tls: false,
         };
         match shim.run().await {
             Err(e) => {
                 if e.kind() == ErrorKind::UnexpectedEof
-                    && shim.session.state.auth_context().is_none()
+                    && shim
+                        .session
+                        .as_ref()
+                        .map(|session| session.state.auth_context().is_none())
+                        .unwrap_or(true)
                 {
                     return Ok(());
                 }
@@ -70,6 +100,11 @@ impl AsyncPostgresShim {
             StartupState::Denied => return Ok(()),
         }
 
+        self.session = Some(
+            self.session_manager
+                .create_session(DatabaseProtocol::PostgreSQL, self.host.clone()),
+        );
+
         match buffer::read_message(&mut self.socket).await? {
             FrontendMessage::PasswordMessage(password_message) => {
                 if !self.authenticate(password_message).await? {
@@ -94,6 +129,12 @@ impl AsyncPostgresShim {
         }
     }
 
+    fn session(&self) -> &Arc<Session> {
+        self.session
+            .as_ref()
+            .expect("session is created after the startup message")
+    }
+
     pub async fn write<Message: protocol::Serialize>(
         &mut self,
         message: Message,
@@ -106,8 +147,25 @@ impl AsyncPostgresShim {
 
         let startup_message = protocol::StartupMessage::from(&mut buffer).await?;
 
+        if startup_message.protocol_version.major == SSL_REQUEST_PROTOCOL
+            && startup_message.protocol_version.minor == CANCEL_REQUEST_PROTOCOL
+        {
+            let cancel_request = protocol::CancelRequest::from(&mut buffer).await?;
+            self.cancel_request(cancel_request);
+
+            // CancelRequest is never answered, the connection is just closed
+            return Ok(StartupState::Denied);
+        }
+
         if startup_message.protocol_version.major == SSL_REQUEST_PROTOCOL {
-            self.write(protocol::SSLResponse::new()).await?;
+            match self.tls_acceptor.clone() {
+                Some(tls_acceptor) if !self.is_tls => {
+                    self.write(protocol::SSLResponse::new(true)).await?;
+                    self.upgrade_to_tls(tls_acceptor).await?;
+                }
+                _ => self.write(protocol::SSLResponse::new(false)).await?,
+            }
+
             return Ok(StartupState::SslRequested);
         }
 
@@ -126,6 +184,16 @@ impl AsyncPostgresShim {
             return Ok(StartupState::Denied);
         }
 
+        if self.tls_required && !self.is_tls {
+            let error_response = protocol::ErrorResponse::new(
+                protocol::ErrorSeverity::Fatal,
+                protocol::ErrorCode::InvalidAuthorizationSpecification,
+                "SSL connection is required".to_string(),
+            );
+            buffer::write_message(&mut self.socket, error_response).await?;
+            return Ok(StartupState::Denied);
+        }
+
         self.parameters = startup_message.parameters;
         if !self.parameters.contains_key("user") {
             let error_response = protocol::ErrorResponse::new(
@@ -151,16 +219,45 @@ impl AsyncPostgresShim {
         return Ok(StartupState::Success);
     }
 
+    // Cancels the query of another connection, it's identified by the key from BackendKeyData
+    fn cancel_request(&self, cancel_request: protocol::CancelRequest) {
+        match self.session_manager.get_session(cancel_request.process_id) {
+            Some(session)
+                if session.state.protocol == DatabaseProtocol::PostgreSQL
+                    && session.state.secret == cancel_request.secret =>
+            {
+                debug!(
+                    "[pg] Cancelling query of connection {}",
+                    cancel_request.process_id
+                );
+                session.state.cancel_query();
+            }
+            _ => debug!(
+                "[pg] Ignoring CancelRequest for unknown connection {}",
+                cancel_request.process_id
+            ),
+        }
+    }
+
+    async fn upgrade_to_tls(&mut self, tls_acceptor: TlsAcceptor) -> Result<(), Error> {
+        // Socket is moved into TLS stream, so it's replaced by a stub for the time of handshake
+        let socket = std::mem::replace(&mut self.socket, Box::new(tokio::io::duplex(1).0));
+        self.socket = Box::new(tls_acceptor.accept(socket).await?);
+        self.is_tls = true;
+
+        Ok(())
+    }
+
     pub async fn authenticate(
         &mut self,
         password_message: protocol::PasswordMessage,
     ) -> Result<bool, Error> {
         let user = self.parameters.get("user").unwrap().clone();
         let authenticate_response = self
-            .session
+            .session()
             .server
             .auth
-            .authenticate(Some(user.clone()))
+            .authenticate(Some(user.clone()), self.parameters.clone())
             .await;
         let mut auth_context: Option<AuthContext> = None;
         let auth_success = match authenticate_response {
@@ -184,8 +281,11 @@ impl AsyncPostgresShim {
             return Ok(false);
         }
 
-        self.session.state.set_user(Some(user));
-        self.session.state.set_auth_context(auth_context);
+        self.session().state.set_user(Some(user));
+        self.session().state.set_auth_context(auth_context);
+        if let Some(recorder) = self.session().state.recorder() {
+            recorder.record_startup(self.parameters.clone());
+        }
 
         self.write(protocol::Authentication::new(
             protocol::AuthenticationRequest::Ok,
@@ -208,6 +308,12 @@ impl AsyncPostgresShim {
                 .await?;
         }
 
+        self.write(protocol::BackendKeyData::new(
+            self.session().state.connection_id,
+            self.session().state.secret,
+        ))
+        .await?;
+
         self.write(protocol::ReadyForQuery::new(
             protocol::TransactionStatus::Idle,
         ))
@@ -219,17 +325,13 @@ impl AsyncPostgresShim {
     pub async fn process_query(&mut self, query: protocol::Query) -> Result<(), Error> {
         let query = query.query;
         debug!("Query: {}", query);
+        let recorder = self.session().state.recorder();
+        if let Some(recorder) = &recorder {
+            recorder.begin_query(&query);
+        }
+
         match self.execute_query(&query).await {
-            Err(e) => {
-                let error_message = e.to_string();
-                error!("Error during processing {}: {}", query, error_message);
-                self.write(protocol::ErrorResponse::new(
-                    protocol::ErrorSeverity::Error,
-                    protocol::ErrorCode::InternalError,
-                    error_message,
-                ))
-                .await?;
-            }
+            Err(e) => return self.query_error(&query, e).await,
             Ok(QueryResponse::Ok(_)) => {
                 self.write(protocol::CommandComplete::new(
                     protocol::CommandCompleteTag::Select,
@@ -244,29 +346,66 @@ impl AsyncPostgresShim {
                 }
 
                 self.write(protocol::RowDescription::new(fields)).await?;
+                if let Some(recorder) = &recorder {
+                    recorder.record_columns(frame.get_columns());
+                    recorder.record_rows(frame.get_rows());
+                }
 
-                for row in frame.get_rows().iter() {
-                    let mut values = Vec::new();
-                    for value in row.values().iter() {
-                        let value = match value {
-                            TableValue::Null => None,
-                            TableValue::String(v) => Some(v.clone()),
-                            TableValue::Int64(v) => Some(format!("{}", v)),
-                            TableValue::Boolean(v) => {
-                                Some((if *v { "t" } else { "v" }).to_string())
-                            }
-                            TableValue::Float64(v) => Some(format!("{}", v)),
-                            TableValue::Timestamp(v) => Some(v.to_string()),
-                        };
-                        values.push(value);
-                    }
+                let local_timestamps = self.session().state.time_zone().is_some();
+                let rows = frame
+                    .get_rows()
+                    .iter()
+                    .map(|row| data_row(row, local_timestamps))
+                    .collect::<Vec<_>>();
+                buffer::write_messages(&mut self.socket, rows).await?;
+
+                self.write(protocol::CommandComplete::new(
+                    protocol::CommandCompleteTag::Select,
+                    frame.len() as u32,
+                ))
+                .await?;
+            }
+            Ok(QueryResponse::Stream(_, mut stream)) => {
+                let columns = match schema_to_columns(&stream.schema()) {
+                    Ok(columns) => columns,
+                    Err(e) => return self.query_error(&query, e).await,
+                };
+                let mut fields = Vec::new();
+                for column in columns.iter() {
+                    fields.push(protocol::RowDescriptionField::new(column.get_name()))
+                }
 
-                    self.write(protocol::DataRow::new(values)).await?;
+                self.write(protocol::RowDescription::new(fields)).await?;
+                if let Some(recorder) = &recorder {
+                    recorder.record_columns(&columns);
+                }
+
+                // Every batch is written before the next one is polled, so a slow client
+                // holds back the query instead of piling rows up in memory
+                let local_timestamps = self.session().state.time_zone().is_some();
+                let mut total = 0;
+                while let Some(batch) = stream.next().await {
+                    let rows = match batch
+                        .map_err(CubeError::from)
+                        .and_then(|batch| batch_to_rows(&batch))
+                    {
+                        Ok(rows) => rows,
+                        Err(e) => return self.query_error(&query, e).await,
+                    };
+                    total += rows.len();
+                    if let Some(recorder) = &recorder {
+                        recorder.record_rows(&rows);
+                    }
+                    let rows = rows
+                        .iter()
+                        .map(|row| data_row(row, local_timestamps))
+                        .collect::<Vec<_>>();
+                    buffer::write_messages(&mut self.socket, rows).await?;
                 }
 
                 self.write(protocol::CommandComplete::new(
                     protocol::CommandCompleteTag::Select,
-                    0,
+                    total as u32,
                 ))
                 .await?;
             }
@@ -278,50 +417,266 @@ impl AsyncPostgresShim {
         Ok(())
     }
 
+    // Error may come in the middle of data rows, the client drops the rows it received then
+    async fn query_error(&mut self, query: &str, e: CubeError) -> Result<(), Error> {
+        let error_message = e.to_string();
+        error!("Error during processing {}: {}", query, error_message);
+        if let Some(recorder) = self.session().state.recorder() {
+            recorder.record_error(&e);
+        }
+
+        self.write(protocol::ErrorResponse::new(
+            protocol::ErrorSeverity::Error,
+            protocol::ErrorCode::InternalError,
+            error_message,
+        ))
+        .await?;
+        self.write(protocol::ReadyForQuery::new(
+            protocol::TransactionStatus::Idle,
+        ))
+        .await
+    }
+
     pub async fn execute_query(&mut self, query: &str) -> Result<QueryResponse, CubeError> {
-        let meta = self
-            .session
-            .server
-            .transport
-            .meta(self.auth_context()?)
-            .await?;
+        execute_query(self.session().clone(), self.auth_context().await?, query).await
+    }
 
-        let plan = convert_sql_to_cube_query(&query.to_string(), meta, self.session.clone())?;
-        match plan {
-            crate::compile::QueryPlan::MetaOk(status) => {
-                return Ok(QueryResponse::Ok(status));
-            }
-            crate::compile::QueryPlan::MetaTabular(status, data_frame) => {
-                return Ok(QueryResponse::ResultSet(status, data_frame));
-            }
-            crate::compile::QueryPlan::DataFusionSelect(status, plan, ctx) => {
-                let df = DataFrameImpl::new(ctx.state, &plan);
-                let batches = df.collect().await?;
-                let response = batch_to_dataframe(&batches)?;
+    pub(crate) async fn auth_context(&self) -> Result<Arc<AuthContext>, CubeError> {
+        self.session().auth_context(self.parameters.clone()).await
+    }
+}
 
-                return Ok(QueryResponse::ResultSet(status, Arc::new(response)));
+/// Executes a simple query of the Postgres session. It doesn't touch the socket, so the session
+/// replay goes through it too.
+pub(crate) async fn execute_query(
+    session: Arc<Session>,
+    auth_context: Arc<AuthContext>,
+    query: &str,
+) -> Result<QueryResponse, CubeError> {
+    if let Some(command) = parse_system_command(query) {
+        match command {
+            SystemCommand::RefreshMeta => {
+                session
+                    .server
+                    .transport
+                    .invalidate_meta(auth_context)
+                    .await?;
             }
         }
+
+        return Ok(QueryResponse::Ok(StatusFlags::empty()));
     }
 
-    pub(crate) fn auth_context(&self) -> Result<Arc<AuthContext>, CubeError> {
-        if let Some(ctx) = self.session.state.auth_context() {
-            Ok(Arc::new(ctx))
-        } else {
-            Err(CubeError::internal("must be auth".to_string()))
+    session.state.begin_query();
+
+    let meta = session.server.transport.meta(auth_context).await?;
+    if let Some(recorder) = session.state.recorder() {
+        recorder.record_meta(&meta);
+    }
+
+    let plan = convert_sql_to_cube_query(&query.to_string(), meta, session)?;
+    match plan {
+        crate::compile::QueryPlan::MetaOk(status) => Ok(QueryResponse::Ok(status)),
+        crate::compile::QueryPlan::MetaTabular(status, data_frame) => {
+            Ok(QueryResponse::ResultSet(status, data_frame))
+        }
+        crate::compile::QueryPlan::DataFusionSelect(status, plan, ctx) => {
+            let df = DataFrameImpl::new(ctx.state, &plan);
+            let stream = df.execute_stream().await?;
+
+            Ok(QueryResponse::Stream(status, stream))
         }
     }
 }
 
+fn data_row(row: &Row, local_timestamps: bool) -> protocol::DataRow {
+    let mut values = Vec::new();
+    for value in row.values().iter() {
+        let value = match value {
+            TableValue::Null => None,
+            TableValue::String(v) => Some(v.clone()),
+            TableValue::Int64(v) => Some(format!("{}", v)),
+            TableValue::Boolean(v) => Some((if *v { "t" } else { "f" }).to_string()),
+            TableValue::Float64(v) => Some(format!("{}", v)),
+            TableValue::Decimal(v) => Some(v.clone()),
+            TableValue::Timestamp(v) if local_timestamps => Some(v.to_local_string()),
+            TableValue::Timestamp(v) => Some(v.to_string()),
+        };
+        values.push(value);
+    }
+
+    protocol::DataRow::new(values)
+}
+
 impl Drop for AsyncPostgresShim {
     fn drop(&mut self) {
-        trace!(
-            "[pg] Droping connection {}",
-            self.session.state.connection_id
-        );
+        if let Some(session) = &self.session {
+            trace!("[pg] Droping connection {}", session.state.connection_id);
 
-        self.session
-            .session_manager
-            .drop_session(self.session.state.connection_id)
+            self.session_manager
+                .drop_session(session.state.connection_id)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::TryFrom, fs::File, io::BufReader, sync::Arc};
+
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream};
+    use tokio_rustls::{
+        rustls::{Certificate, ClientConfig, RootCertStore, ServerName},
+        TlsConnector,
+    };
+
+    use super::*;
+    use crate::{
+        sql::{tls::create_tls_acceptor, ServerManager, SessionManager, SqlAuthStandaloneImpl},
+        transport::HttpTransport,
+    };
+
+    const FIXTURES_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/tls");
+
+    fn start_shim(tls_required: bool) -> DuplexStream {
+        let session_manager = Arc::new(SessionManager::new(Arc::new(ServerManager::new(
+            Arc::new(SqlAuthStandaloneImpl),
+            Arc::new(HttpTransport::new()),
+            None,
+        ))));
+
+        start_shim_with_session_manager(tls_required, session_manager)
+    }
+
+    fn start_shim_with_session_manager(
+        tls_required: bool,
+        session_manager: Arc<SessionManager>,
+    ) -> DuplexStream {
+        let tls_acceptor = create_tls_acceptor(
+            &format!("{}/cert.pem", FIXTURES_PATH),
+            &format!("{}/key.pem", FIXTURES_PATH),
+        )
+        .unwrap();
+
+        let (client, server) = duplex(64 * 1024);
+        tokio::spawn(AsyncPostgresShim::run_on(
+            server,
+            session_manager,
+            "127.0.0.1".to_string(),
+            Some(tls_acceptor),
+            tls_required,
+        ));
+
+        client
+    }
+
+    fn ssl_request() -> Vec<u8> {
+        let mut message = 8u32.to_be_bytes().to_vec();
+        message.extend_from_slice(&80877103u32.to_be_bytes());
+        message
+    }
+
+    fn startup_message() -> Vec<u8> {
+        let mut body = 196608u32.to_be_bytes().to_vec();
+        for value in ["user", "test", "database", "db", ""] {
+            body.extend_from_slice(value.as_bytes());
+            body.push(0);
+        }
+
+        let mut message = ((body.len() + 4) as u32).to_be_bytes().to_vec();
+        message.extend(body);
+        message
+    }
+
+    fn tls_connector() -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        let ca = rustls_pemfile::certs(&mut BufReader::new(
+            File::open(format!("{}/ca.pem", FIXTURES_PATH)).unwrap(),
+        ))
+        .unwrap();
+        for cert in ca {
+            roots.add(&Certificate(cert)).unwrap();
+        }
+
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        TlsConnector::from(Arc::new(config))
+    }
+
+    #[tokio::test]
+    async fn test_ssl_request_upgrades_to_tls() -> Result<(), CubeError> {
+        let mut client = start_shim(true);
+
+        client.write_all(&ssl_request()).await?;
+        assert_eq!(client.read_u8().await?, b'S');
+
+        let mut tls = tls_connector()
+            .connect(ServerName::try_from("localhost").unwrap(), client)
+            .await?;
+        tls.write_all(&startup_message()).await?;
+        tls.flush().await?;
+
+        // AuthenticationCleartextPassword
+        assert_eq!(tls.read_u8().await?, b'R');
+        assert_eq!(tls.read_u32().await?, 8);
+        assert_eq!(tls.read_u32().await?, 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_rejected_when_tls_required() -> Result<(), CubeError> {
+        let mut client = start_shim(true);
+
+        client.write_all(&startup_message()).await?;
+
+        assert_eq!(client.read_u8().await?, b'E');
+        let mut response = vec![0; client.read_u32().await? as usize - 4];
+        client.read_exact(&mut response).await?;
+        assert!(String::from_utf8_lossy(&response).contains("SSL connection is required"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancel_request_without_session() -> Result<(), CubeError> {
+        let session_manager = Arc::new(SessionManager::new(Arc::new(ServerManager::new(
+            Arc::new(SqlAuthStandaloneImpl),
+            Arc::new(HttpTransport::new()),
+            None,
+        ))));
+        let session =
+            session_manager.create_session(DatabaseProtocol::PostgreSQL, "127.0.0.1".to_string());
+        let token = session.state.begin_query();
+
+        let mut client = start_shim_with_session_manager(false, session_manager.clone());
+        let mut message = 16u32.to_be_bytes().to_vec();
+        message.extend_from_slice(&80877102u32.to_be_bytes());
+        message.extend_from_slice(&session.state.connection_id.to_be_bytes());
+        message.extend_from_slice(&session.state.secret.to_be_bytes());
+        client.write_all(&message).await?;
+
+        // CancelRequest is never answered, the connection is closed
+        let mut response = vec![];
+        client.read_to_end(&mut response).await?;
+        assert!(response.is_empty());
+
+        assert!(token.is_cancelled());
+        assert_eq!(session_manager.process_list().len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_plaintext_allowed_when_tls_optional() -> Result<(), CubeError> {
+        let mut client = start_shim(false);
+
+        client.write_all(&startup_message()).await?;
+
+        assert_eq!(client.read_u8().await?, b'R');
+
+        Ok(())
     }
 }
@@ -0,0 +1,322 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex as MutexSync},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use cubeclient::models::{V1CubeMeta, V1LoadRequestQuery, V1LoadResponse};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    compile::MetaContext,
+    sql::{
+        dataframe::{Column, Row, TableValue},
+        session::DatabaseProtocol,
+        AuthContext,
+    },
+    transport::TransportService,
+    CubeError,
+};
+
+/// Full client session which is recorded by the server (CUBESQL_RECORD_PATH) and replayed
+/// offline by `sql::replay` against the recorded Cube responses.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionFixture {
+    /// mysql or postgres
+    pub protocol: String,
+    /// Startup parameters of the client: user, database, application_name, etc
+    #[serde(default)]
+    pub startup: BTreeMap<String, String>,
+    /// Cubes of the data model, the last meta which was used by the session
+    #[serde(default)]
+    pub meta: Vec<V1CubeMeta>,
+    /// Unique load requests which were sent to Cube and their responses
+    #[serde(default)]
+    pub loads: Vec<RecordedLoad>,
+    #[serde(default)]
+    pub queries: Vec<RecordedQuery>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedLoad {
+    pub request: V1LoadRequestQuery,
+    pub response: V1LoadResponse,
+}
+
+/// Query and the result which the client got. Values are written as text, NULL is `null`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedQuery {
+    pub query: String,
+    #[serde(default)]
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub rows: Vec<Vec<Option<String>>>,
+    /// First line of the error, backtraces of internal errors are dropped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SessionFixture {
+    pub fn new(protocol: &DatabaseProtocol) -> Self {
+        Self {
+            protocol: match protocol {
+                DatabaseProtocol::MySQL => "mysql".to_string(),
+                DatabaseProtocol::PostgreSQL => "postgres".to_string(),
+            },
+            ..Self::default()
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, CubeError> {
+        let content = fs::read_to_string(path)?;
+
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn database_protocol(&self) -> Result<DatabaseProtocol, CubeError> {
+        match self.protocol.as_str() {
+            "mysql" => Ok(DatabaseProtocol::MySQL),
+            "postgres" => Ok(DatabaseProtocol::PostgreSQL),
+            other => Err(CubeError::user(format!(
+                "Unknown protocol '{}' of session fixture, expected mysql or postgres",
+                other
+            ))),
+        }
+    }
+}
+
+impl RecordedQuery {
+    pub fn new(query: String) -> Self {
+        Self {
+            query,
+            columns: vec![],
+            rows: vec![],
+            error: None,
+        }
+    }
+
+    pub fn push_columns(&mut self, columns: &[Column]) {
+        self.columns = columns.iter().map(|c| c.get_name()).collect();
+    }
+
+    pub fn push_rows(&mut self, rows: &[Row]) {
+        for row in rows.iter() {
+            self.rows
+                .push(row.values().iter().map(recorded_value).collect());
+        }
+    }
+
+    pub fn set_error(&mut self, error: &CubeError) {
+        self.error = error.to_string().lines().next().map(|l| l.to_string());
+    }
+}
+
+// Protocol independent text form, it's the same for MySQL and Postgres sessions
+fn recorded_value(value: &TableValue) -> Option<String> {
+    match value {
+        TableValue::Null => None,
+        TableValue::String(v) => Some(v.clone()),
+        TableValue::Int64(v) => Some(v.to_string()),
+        TableValue::Boolean(v) => Some(v.to_string()),
+        TableValue::Float64(v) => Some(v.to_string()),
+        TableValue::Decimal(v) => Some(v.clone()),
+        TableValue::Timestamp(v) => Some(v.to_string()),
+    }
+}
+
+/// Records the session into `{protocol}-{unix time}-{connection id}.json`, the file is written
+/// when the session is dropped.
+#[derive(Debug)]
+pub struct SessionRecorder {
+    path: PathBuf,
+    fixture: MutexSync<SessionFixture>,
+}
+
+impl SessionRecorder {
+    pub fn new(dir: &Path, connection_id: u32, protocol: &DatabaseProtocol) -> Self {
+        let fixture = SessionFixture::new(protocol);
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Self {
+            path: dir.join(format!(
+                "{}-{}-{}.json",
+                fixture.protocol, started, connection_id
+            )),
+            fixture: MutexSync::new(fixture),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn update(&self, f: impl FnOnce(&mut SessionFixture)) {
+        let mut guard = self
+            .fixture
+            .lock()
+            .expect("failed to unlock session fixture for writing");
+        f(&mut guard)
+    }
+
+    pub fn record_startup(&self, parameters: impl IntoIterator<Item = (String, String)>) {
+        self.update(|fixture| fixture.startup.extend(parameters))
+    }
+
+    pub fn record_meta(&self, meta: &MetaContext) {
+        self.update(|fixture| fixture.meta = meta.cubes.clone())
+    }
+
+    pub fn record_load(&self, request: V1LoadRequestQuery, response: V1LoadResponse) {
+        self.update(|fixture| {
+            if !fixture.loads.iter().any(|load| load.request == request) {
+                fixture.loads.push(RecordedLoad { request, response });
+            }
+        })
+    }
+
+    pub fn begin_query(&self, query: &str) {
+        self.update(|fixture| fixture.queries.push(RecordedQuery::new(query.to_string())))
+    }
+
+    fn update_query(&self, f: impl FnOnce(&mut RecordedQuery)) {
+        self.update(|fixture| {
+            if let Some(query) = fixture.queries.last_mut() {
+                f(query)
+            }
+        })
+    }
+
+    pub fn record_columns(&self, columns: &[Column]) {
+        self.update_query(|query| query.push_columns(columns))
+    }
+
+    pub fn record_rows(&self, rows: &[Row]) {
+        self.update_query(|query| query.push_rows(rows))
+    }
+
+    pub fn record_error(&self, error: &CubeError) {
+        self.update_query(|query| query.set_error(error))
+    }
+
+    /// Writes the fixture, sessions without queries (cancel requests, failed auth) are skipped.
+    pub fn save(&self) -> Result<(), CubeError> {
+        let content = {
+            let guard = self
+                .fixture
+                .lock()
+                .expect("failed to unlock session fixture for reading");
+            if guard.queries.is_empty() {
+                return Ok(());
+            }
+
+            serde_json::to_string_pretty(&*guard)?
+        };
+
+        fs::write(&self.path, content)?;
+
+        Ok(())
+    }
+}
+
+/// Transport of a recorded session, it stores meta and load responses of the wrapped transport.
+#[derive(Debug)]
+pub struct RecordingTransport {
+    transport: Arc<dyn TransportService>,
+    recorder: Arc<SessionRecorder>,
+}
+
+impl RecordingTransport {
+    pub fn new(transport: Arc<dyn TransportService>, recorder: Arc<SessionRecorder>) -> Self {
+        Self {
+            transport,
+            recorder,
+        }
+    }
+}
+
+#[async_trait]
+impl TransportService for RecordingTransport {
+    async fn meta(&self, ctx: Arc<AuthContext>) -> Result<Arc<MetaContext>, CubeError> {
+        let meta = self.transport.meta(ctx).await?;
+        self.recorder.record_meta(&meta);
+
+        Ok(meta)
+    }
+
+    async fn load(
+        &self,
+        query: V1LoadRequestQuery,
+        ctx: Arc<AuthContext>,
+    ) -> Result<V1LoadResponse, CubeError> {
+        let response = self.transport.load(query.clone(), ctx).await?;
+        self.recorder.record_load(query, response.clone());
+
+        Ok(response)
+    }
+
+    async fn invalidate_meta(&self, ctx: Arc<AuthContext>) -> Result<(), CubeError> {
+        self.transport.invalidate_meta(ctx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::{ColumnFlags, ColumnType};
+
+    #[test]
+    fn test_session_recorder() -> Result<(), CubeError> {
+        let dir = std::env::temp_dir();
+        let recorder = SessionRecorder::new(&dir, 7, &DatabaseProtocol::PostgreSQL);
+        assert!(recorder
+            .path()
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("postgres-"));
+
+        recorder.record_startup(vec![("user".to_string(), "ovr".to_string())]);
+        recorder.begin_query("SELECT 1 AS one, NULL AS two");
+        recorder.record_columns(&[
+            Column::new("one".to_string(), ColumnType::Int64, ColumnFlags::empty()),
+            Column::new("two".to_string(), ColumnType::String, ColumnFlags::empty()),
+        ]);
+        recorder.record_rows(&[Row::new(vec![TableValue::Int64(1), TableValue::Null])]);
+        recorder.begin_query("SELECT unknown");
+        recorder.record_error(&CubeError::internal(
+            "Unknown column\nbacktrace".to_string(),
+        ));
+        recorder.save()?;
+
+        let fixture = SessionFixture::from_file(recorder.path())?;
+        fs::remove_file(recorder.path())?;
+
+        assert_eq!(fixture.database_protocol()?, DatabaseProtocol::PostgreSQL);
+        assert_eq!(fixture.startup.get("user"), Some(&"ovr".to_string()));
+        assert_eq!(
+            fixture.queries,
+            vec![
+                RecordedQuery {
+                    query: "SELECT 1 AS one, NULL AS two".to_string(),
+                    columns: vec!["one".to_string(), "two".to_string()],
+                    rows: vec![vec![Some("1".to_string()), None]],
+                    error: None,
+                },
+                RecordedQuery {
+                    query: "SELECT unknown".to_string(),
+                    columns: vec![],
+                    rows: vec![],
+                    error: Some("Internal: Unknown column".to_string()),
+                }
+            ]
+        );
+
+        Ok(())
+    }
+}
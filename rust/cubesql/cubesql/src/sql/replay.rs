@@ -0,0 +1,245 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use async_trait::async_trait;
+use cubeclient::models::{V1LoadRequestQuery, V1LoadResponse};
+use futures::StreamExt;
+
+use crate::{
+    compile::MetaContext,
+    sql::{
+        dataframe::{batch_to_rows, schema_to_columns},
+        mysql::service::MySqlConnection,
+        postgres::shim,
+        recorder::{RecordedLoad, RecordedQuery, SessionFixture},
+        session::DatabaseProtocol,
+        AuthContext, QueryResponse, ServerManager, Session, SessionManager, SqlAuthStandaloneImpl,
+    },
+    transport::TransportService,
+    CubeError,
+};
+
+/// Answers with the meta and load responses of a recorded session. Load requests which differ
+/// from the recorded ones are errors, it's how changes of generated Cube queries are caught.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    meta: Arc<MetaContext>,
+    loads: Vec<RecordedLoad>,
+}
+
+impl ReplayTransport {
+    pub fn new(fixture: &SessionFixture) -> Self {
+        Self {
+            meta: Arc::new(MetaContext::new(fixture.meta.clone())),
+            loads: fixture.loads.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl TransportService for ReplayTransport {
+    async fn meta(&self, _ctx: Arc<AuthContext>) -> Result<Arc<MetaContext>, CubeError> {
+        Ok(self.meta.clone())
+    }
+
+    async fn load(
+        &self,
+        query: V1LoadRequestQuery,
+        _ctx: Arc<AuthContext>,
+    ) -> Result<V1LoadResponse, CubeError> {
+        match self.loads.iter().find(|load| load.request == query) {
+            Some(load) => Ok(load.response.clone()),
+            None => Err(CubeError::user(format!(
+                "Load request is not recorded in the session fixture: {}",
+                serde_json::to_string(&query)?
+            ))),
+        }
+    }
+}
+
+/// Query entry point of the recorded protocol, the same one the shim uses for simple queries
+enum ReplayConnection {
+    MySql(MySqlConnection),
+    Postgres(Arc<Session>),
+}
+
+impl ReplayConnection {
+    async fn execute_query(&mut self, query: &str) -> Result<QueryResponse, CubeError> {
+        match self {
+            ReplayConnection::MySql(connection) => connection.execute_query(query).await,
+            ReplayConnection::Postgres(session) => {
+                let auth_context = session.auth_context(HashMap::new()).await?;
+                shim::execute_query(session.clone(), auth_context, query).await
+            }
+        }
+    }
+}
+
+/// Replays queries of the recorded session one by one through the query entry point of its
+/// protocol and returns results in the fixture format, so they can be compared with
+/// `fixture.queries`. Protocol messages are not replayed, only what the session has sent and
+/// received.
+pub async fn replay_session(fixture: &SessionFixture) -> Result<Vec<RecordedQuery>, CubeError> {
+    let transport = Arc::new(ReplayTransport::new(fixture));
+    let server = Arc::new(ServerManager::new(
+        Arc::new(SqlAuthStandaloneImpl),
+        transport.clone(),
+        None,
+    ));
+    let session_manager = Arc::new(SessionManager::new(server));
+    let protocol = fixture.database_protocol()?;
+    let session = session_manager.create_session(protocol.clone(), "127.0.0.1".to_string());
+
+    // Populate like shims
+    session.state.set_user(fixture.startup.get("user").cloned());
+    session
+        .state
+        .set_database(fixture.startup.get("database").cloned());
+    session.state.set_auth_context(Some(AuthContext {
+        access_token: "".to_string(),
+        base_path: "".to_string(),
+        security_context: None,
+        can_switch_user: false,
+    }));
+
+    let mut connection = match protocol {
+        DatabaseProtocol::MySQL => ReplayConnection::MySql(MySqlConnection::new(session.clone())),
+        DatabaseProtocol::PostgreSQL => ReplayConnection::Postgres(session.clone()),
+    };
+
+    let mut results = Vec::with_capacity(fixture.queries.len());
+    for recorded in fixture.queries.iter() {
+        let mut result = RecordedQuery::new(recorded.query.clone());
+        if let Err(e) = replay_query(&mut result, &mut connection).await {
+            result.set_error(&e);
+        }
+
+        results.push(result);
+    }
+
+    // MySQL connection drops the session itself
+    drop(connection);
+    session_manager.drop_session(session.state.connection_id);
+
+    Ok(results)
+}
+
+async fn replay_query(
+    result: &mut RecordedQuery,
+    connection: &mut ReplayConnection,
+) -> Result<(), CubeError> {
+    match connection.execute_query(&result.query).await? {
+        QueryResponse::Ok(_) => {}
+        QueryResponse::ResultSet(_, frame) => {
+            result.push_columns(frame.get_columns());
+            result.push_rows(frame.get_rows());
+        }
+        QueryResponse::Stream(_, mut stream) => {
+            result.push_columns(&schema_to_columns(&stream.schema())?);
+
+            while let Some(batch) = stream.next().await {
+                result.push_rows(&batch_to_rows(&batch?)?);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Replays all `*.json` fixtures of the directory, returns differences with recorded results.
+pub async fn replay_fixtures(dir: &Path) -> Result<Vec<String>, CubeError> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| path.extension().map(|ext| ext == "json").unwrap_or(false));
+    paths.sort();
+
+    let mut mismatches = vec![];
+    for path in paths {
+        let fixture = SessionFixture::from_file(&path)?;
+        let results = replay_session(&fixture).await?;
+
+        for (expected, actual) in fixture.queries.iter().zip(results.iter()) {
+            if expected != actual {
+                mismatches.push(format!(
+                    "{}: {}\nexpected: {:?}\nactual: {:?}",
+                    path.display(),
+                    expected.query,
+                    expected,
+                    actual
+                ));
+            }
+        }
+    }
+
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sessions recorded with CUBESQL_RECORD_PATH are copied to fixtures/sessions, see DEVELOPMENT.md
+    #[tokio::test]
+    async fn test_replay_session_fixtures() -> Result<(), CubeError> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/sessions");
+        let mismatches = replay_fixtures(&dir).await?;
+
+        assert!(mismatches.is_empty(), "{}", mismatches.join("\n\n"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replay_unknown_load() -> Result<(), CubeError> {
+        let fixture: SessionFixture = serde_json::from_str(
+            r#"{
+                "protocol": "postgres",
+                "startup": {"user": "ovr", "database": "db"},
+                "meta": [{
+                    "name": "Orders",
+                    "measures": [{"name": "Orders.count", "type": "number", "aggType": "count"}],
+                    "dimensions": [],
+                    "segments": []
+                }],
+                "queries": [{"query": "SELECT COUNT(*) AS cnt FROM Orders"}]
+            }"#,
+        )?;
+
+        let results = replay_session(&fixture).await?;
+        assert_eq!(results.len(), 1);
+        assert!(results[0]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("Load request is not recorded in the session fixture"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replay_mysql_session() -> Result<(), CubeError> {
+        let fixture: SessionFixture = serde_json::from_str(
+            r#"{
+                "protocol": "mysql",
+                "startup": {"user": "ovr", "database": "db"},
+                "queries": [
+                    {"query": "SELECT CAST('test plain returns' AS CHAR(60)) AS anon_1"},
+                    {"query": "COMMIT"}
+                ]
+            }"#,
+        )?;
+
+        // Queries which are answered by the MySQL shim itself, without the compiler
+        let results = replay_session(&fixture).await?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].error, None);
+        assert_eq!(results[0].columns, vec!["anon_1".to_string()]);
+        assert_eq!(
+            results[0].rows,
+            vec![vec![Some("test plain returns".to_string())]]
+        );
+        assert_eq!(results[1], RecordedQuery::new("COMMIT".to_string()));
+
+        Ok(())
+    }
+}
@@ -1,4 +1,8 @@
-use std::sync::{Arc, RwLock as RwLockSync};
+use std::{
+    path::PathBuf,
+    sync::{Arc, RwLock as RwLockSync},
+    time::Duration,
+};
 
 use crate::{
     sql::{
@@ -15,12 +19,18 @@ use super::{database_variables::DatabaseVariables, session::DatabaseProtocol};
 pub struct ServerConfiguration {
     /// Max number of prepared statements which can be allocated per connection
     pub connection_max_prepared_statements: usize,
+    /// Max time to wait for Cube to load the data of a query, CUBESQL_QUERY_TIMEOUT
+    pub query_timeout: Duration,
+    /// Directory for fixtures of recorded client sessions, CUBESQL_RECORD_PATH
+    pub record_path: Option<PathBuf>,
 }
 
 impl Default for ServerConfiguration {
     fn default() -> Self {
         Self {
             connection_max_prepared_statements: 50,
+            query_timeout: Duration::from_secs(120),
+            record_path: None,
         }
     }
 }
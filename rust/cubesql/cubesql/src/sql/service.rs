@@ -1,8 +1,12 @@
 use std::sync::Arc;
 
+use datafusion::physical_plan::SendableRecordBatchStream;
+
 use super::{dataframe, StatusFlags};
 
 pub enum QueryResponse {
     Ok(StatusFlags),
     ResultSet(StatusFlags, Arc<dataframe::DataFrame>),
+    /// Rows of queries executed by DataFusion, which are written to the client batch by batch.
+    Stream(StatusFlags, SendableRecordBatchStream),
 }
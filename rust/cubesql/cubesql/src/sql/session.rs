@@ -1,13 +1,22 @@
-use std::sync::{Arc, RwLock as RwLockSync};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock as RwLockSync},
+};
+
+use chrono::{FixedOffset, Utc};
+use datafusion::scalar::ScalarValue;
+use tokio_util::sync::CancellationToken;
 
+use crate::compile::engine::udf::{normalize_time_zone, time_zone_offset_at};
 use crate::sql::database_variables::{
-    mysql_default_session_variables, postgres_default_session_variables,
+    mysql_default_session_variables, postgres_default_session_variables, DatabaseVariable,
 };
 
 use super::{
-    database_variables::DatabaseVariables, server_manager::ServerManager,
-    session_manager::SessionManager, AuthContext,
+    database_variables::DatabaseVariables, recorder::SessionRecorder,
+    server_manager::ServerManager, session_manager::SessionManager, AuthContext,
 };
+use crate::CubeError;
 
 extern crate lazy_static;
 
@@ -42,6 +51,10 @@ pub struct SessionState {
     pub host: String,
     // client protocol, mysql/postgresql, immutable
     pub protocol: DatabaseProtocol,
+    // secret key which is required by postgres CancelRequest, immutable
+    pub secret: u32,
+    // recorder of the session fixture when CUBESQL_RECORD_PATH is set, immutable
+    recorder: Option<Arc<SessionRecorder>>,
 
     // session db variables
     variables: RwLockSync<Option<DatabaseVariables>>,
@@ -51,6 +64,11 @@ pub struct SessionState {
     // @todo Remove RWLock after split of Connection & SQLWorker
     // Context for Transport
     auth_context: RwLockSync<Option<AuthContext>>,
+
+    // Cancellation of the query which is executed by the session, replaced on every query
+    query_token: RwLockSync<CancellationToken>,
+    // Closes the connection of the session, see `close_connection`
+    connection_token: CancellationToken,
 }
 
 impl SessionState {
@@ -59,17 +77,62 @@ impl SessionState {
         host: String,
         protocol: DatabaseProtocol,
         auth_context: Option<AuthContext>,
+        recorder: Option<Arc<SessionRecorder>>,
     ) -> Self {
         Self {
             connection_id,
             host,
             protocol,
+            secret: rand::random(),
+            recorder,
             variables: RwLockSync::new(None),
             properties: RwLockSync::new(SessionProperties::new(None, None)),
             auth_context: RwLockSync::new(auth_context),
+            query_token: RwLockSync::new(CancellationToken::new()),
+            connection_token: CancellationToken::new(),
         }
     }
 
+    /// Starts a new query, cancellation of the previous one doesn't affect it.
+    pub fn begin_query(&self) -> CancellationToken {
+        let mut guard = self
+            .query_token
+            .write()
+            .expect("failed to unlock query token for writing");
+        *guard = CancellationToken::new();
+        guard.clone()
+    }
+
+    /// Token of the current query, it's checked by `CubeScanExecutionPlan` while waiting for Cube.
+    pub fn query_token(&self) -> CancellationToken {
+        let guard = self
+            .query_token
+            .read()
+            .expect("failed to unlock query token for reading");
+        guard.clone()
+    }
+
+    /// Cancels the current query: `KILL QUERY` in MySQL and CancelRequest in Postgres.
+    pub fn cancel_query(&self) {
+        self.query_token().cancel();
+    }
+
+    /// Token which is cancelled once the connection has to be closed, servers stop processing
+    /// the connection on it.
+    pub fn connection_token(&self) -> CancellationToken {
+        self.connection_token.clone()
+    }
+
+    /// Cancels the current query and closes the connection: `KILL [CONNECTION]` in MySQL.
+    pub fn close_connection(&self) {
+        self.cancel_query();
+        self.connection_token.cancel();
+    }
+
+    pub fn recorder(&self) -> Option<Arc<SessionRecorder>> {
+        self.recorder.clone()
+    }
+
     pub fn user(&self) -> Option<String> {
         let guard = self
             .properties
@@ -134,6 +197,35 @@ impl SessionState {
         }
     }
 
+    /// Name of the session variable which holds the time zone: `SET time_zone` in MySQL and
+    /// `SET TIME ZONE` / `SET timezone` in Postgres.
+    pub fn time_zone_variable(&self) -> &'static str {
+        match self.protocol {
+            DatabaseProtocol::MySQL => "time_zone",
+            DatabaseProtocol::PostgreSQL => "timezone",
+        }
+    }
+
+    /// Session time zone which is passed to Cube, `None` for UTC. The variable is validated by
+    /// `SET`, see `normalize_time_zone`.
+    pub fn time_zone(&self) -> Option<String> {
+        match self.all_variables().get(self.time_zone_variable()) {
+            Some(DatabaseVariable {
+                value: ScalarValue::Utf8(Some(zone)),
+                ..
+            }) => normalize_time_zone(zone).ok().flatten(),
+            _ => None,
+        }
+    }
+
+    /// Current offset of the session time zone used to evaluate `now()` locally. Named zones are
+    /// resolved at the current time, as their offset changes with DST.
+    pub fn time_zone_offset(&self) -> FixedOffset {
+        self.time_zone()
+            .and_then(|zone| time_zone_offset_at(&zone, &Utc::now().naive_utc()))
+            .unwrap_or_else(|| FixedOffset::east(0))
+    }
+
     pub fn set_variables(&self, variables: DatabaseVariables) {
         let mut to_override = false;
 
@@ -178,6 +270,26 @@ pub struct Session {
 }
 
 impl Session {
+    /// Auth context for Transport. It's dropped when the session switches to another user by
+    /// `SET user = '...'`, so the new user is authenticated with the same connection `options`.
+    pub async fn auth_context(
+        &self,
+        options: HashMap<String, String>,
+    ) -> Result<Arc<AuthContext>, CubeError> {
+        if let Some(ctx) = self.state.auth_context() {
+            return Ok(Arc::new(ctx));
+        }
+
+        let user = self
+            .state
+            .user()
+            .ok_or_else(|| CubeError::internal("must be auth".to_string()))?;
+        let response = self.server.auth.authenticate(Some(user), options).await?;
+        self.state.set_auth_context(Some(response.context.clone()));
+
+        Ok(Arc::new(response.context))
+    }
+
     pub fn to_process_list(self: &Arc<Self>) -> SessionProcessList {
         SessionProcessList {
             id: self.state.connection_id,
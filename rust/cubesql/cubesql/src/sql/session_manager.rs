@@ -6,9 +6,12 @@ use std::{
     },
 };
 
+use log::error;
+
 use crate::CubeError;
 
 use super::{
+    recorder::SessionRecorder,
     server_manager::ServerManager,
     session::{DatabaseProtocol, Session, SessionProcessList, SessionState},
 };
@@ -39,11 +42,23 @@ impl SessionManager {
         host: String,
     ) -> Arc<Session> {
         let connection_id = self.last_id.fetch_add(1, Ordering::SeqCst);
+        let recorder = self
+            .server
+            .configuration
+            .record_path
+            .as_ref()
+            .map(|path| Arc::new(SessionRecorder::new(path, connection_id, &protocol)));
 
         let sess = Session {
             session_manager: self.clone(),
             server: self.server.clone(),
-            state: Arc::new(SessionState::new(connection_id, host, protocol, None)),
+            state: Arc::new(SessionState::new(
+                connection_id,
+                host,
+                protocol,
+                None,
+                recorder,
+            )),
         };
 
         let session_ref = Arc::new(sess);
@@ -69,11 +84,30 @@ impl SessionManager {
             .collect::<Vec<SessionProcessList>>()
     }
 
+    pub fn get_session(&self, connection_id: u32) -> Option<Arc<Session>> {
+        let guard = self
+            .sessions
+            .read()
+            .expect("failed to unlock sessions for reading session");
+        guard.get(&connection_id).cloned()
+    }
+
     pub fn drop_session(&self, connection_id: u32) {
         let mut guard = self
             .sessions
             .write()
             .expect("failed to unlock sessions for droping session");
-        guard.remove(&connection_id);
+        let session = guard.remove(&connection_id);
+        drop(guard);
+
+        if let Some(recorder) = session.and_then(|session| session.state.recorder()) {
+            if let Err(e) = recorder.save() {
+                error!(
+                    "Unable to save session fixture {}: {}",
+                    recorder.path().display(),
+                    e
+                );
+            }
+        }
     }
 }
@@ -1,9 +1,13 @@
+use chrono::FixedOffset;
 use msql_srv::{Column, ColumnFlags, ColumnType};
 use sqlparser::ast;
 
 #[derive(Debug)]
 pub enum BindValue {
+    Null,
     String(String),
+    /// Decimal number as it was sent by the client, to keep its precision.
+    Decimal(String),
     Int64(i64),
     #[allow(unused)]
     UInt64(u64),
@@ -16,11 +20,34 @@ trait Visitor<'ast> {
 
     fn visit_identifier(&mut self, _identifier: &mut ast::Ident) {}
 
+    /// Called after the arguments of the function were visited.
+    fn visit_function(&mut self, _fun: &mut ast::Function) {}
+
+    /// Called after the casted expression was visited, `expr` is the cast itself.
+    fn visit_cast(&mut self, _expr: &mut ast::Expr) {}
+
     fn visit_expr(&mut self, expr: &mut ast::Expr) {
         match expr {
             ast::Expr::Value(value) => self.visit_value(value),
             ast::Expr::Identifier(identifier) => self.visit_identifier(identifier),
             ast::Expr::Nested(v) => self.visit_expr(&mut *v),
+            ast::Expr::UnaryOp { expr, .. } => self.visit_expr(&mut *expr),
+            ast::Expr::Cast { expr: inner, .. } => {
+                self.visit_expr(&mut *inner);
+                self.visit_cast(expr);
+            }
+            ast::Expr::Extract { expr, .. } => self.visit_expr(&mut *expr),
+            ast::Expr::IsNull(expr) | ast::Expr::IsNotNull(expr) => self.visit_expr(&mut *expr),
+            ast::Expr::Function(f) => {
+                for arg in f.args.iter_mut() {
+                    match arg {
+                        ast::FunctionArg::Named { arg, .. } => self.visit_expr(arg),
+                        ast::FunctionArg::Unnamed(arg) => self.visit_expr(arg),
+                    }
+                }
+
+                self.visit_function(f);
+            }
             ast::Expr::Between {
                 expr,
                 negated: _,
@@ -70,6 +97,7 @@ trait Visitor<'ast> {
     fn visit_select_item(&mut self, select: &mut ast::SelectItem) {
         match select {
             ast::SelectItem::UnnamedExpr(expr) => self.visit_expr(expr),
+            ast::SelectItem::ExprWithAlias { expr, .. } => self.visit_expr(expr),
             _ => {}
         }
     }
@@ -86,6 +114,14 @@ trait Visitor<'ast> {
         for from in &mut select.from {
             self.visit_table_with_joins(from);
         }
+
+        for group_by in &mut select.group_by {
+            self.visit_expr(group_by);
+        }
+
+        if let Some(having) = &mut select.having {
+            self.visit_expr(having);
+        };
     }
 
     fn visit_set_expr(&mut self, body: &mut ast::SetExpr) {
@@ -102,6 +138,14 @@ trait Visitor<'ast> {
 
     fn visit_query(&mut self, query: &mut Box<ast::Query>) {
         self.visit_set_expr(&mut query.body);
+
+        for order_by in &mut query.order_by {
+            self.visit_expr(&mut order_by.expr);
+        }
+
+        if let Some(limit) = &mut query.limit {
+            self.visit_expr(limit);
+        };
     }
 
     fn visit_statement(&mut self, statement: &mut ast::Statement) {
@@ -130,13 +174,16 @@ impl StatementPrepare {
 }
 
 impl<'ast> Visitor<'ast> for StatementPrepare {
-    fn visit_value(&mut self, _: &mut ast::Value) {
-        self.parameters.push(Column {
-            table: String::new(),
-            column: "not implemented".to_owned(),
-            coltype: ColumnType::MYSQL_TYPE_VAR_STRING,
-            colflags: ColumnFlags::empty(),
-        })
+    fn visit_value(&mut self, value: &mut ast::Value) {
+        if let ast::Value::Placeholder(_) = value {
+            // Types of parameters are not inferred, clients send values with their own types
+            self.parameters.push(Column {
+                table: String::new(),
+                column: "?".to_owned(),
+                coltype: ColumnType::MYSQL_TYPE_VAR_STRING,
+                colflags: ColumnFlags::empty(),
+            })
+        }
     }
 }
 
@@ -173,9 +220,15 @@ impl<'ast> Visitor<'ast> for StatementBinder {
                 self.position += 1;
 
                 match to_replace {
+                    BindValue::Null => {
+                        *value = ast::Value::Null;
+                    }
                     BindValue::String(v) => {
                         *value = ast::Value::SingleQuotedString(v.clone());
                     }
+                    BindValue::Decimal(v) => {
+                        *value = ast::Value::Number(v.clone(), false);
+                    }
                     BindValue::Bool(v) => {
                         *value = ast::Value::Boolean(*v);
                     }
@@ -195,6 +248,136 @@ impl<'ast> Visitor<'ast> for StatementBinder {
     }
 }
 
+/// Evaluates `now()` in the session time zone by rewriting it to
+/// `convert_tz(now(), '+00:00', '<offset>')`.
+#[derive(Debug)]
+pub struct StatementTimeZone {
+    offset: FixedOffset,
+}
+
+impl StatementTimeZone {
+    pub fn new(offset: FixedOffset) -> Self {
+        Self { offset }
+    }
+
+    pub fn apply(&mut self, stmt: &mut ast::Statement) {
+        if self.offset.local_minus_utc() != 0 {
+            self.visit_statement(stmt);
+        }
+    }
+
+    fn is_now(expr: &ast::Expr) -> bool {
+        match expr {
+            ast::Expr::Function(fun) => {
+                fun.name.to_string().eq_ignore_ascii_case("now") && fun.args.is_empty()
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `now()` back from `convert_tz(now(), ...)` created by this visitor.
+    fn unwrap_now(expr: &ast::Expr) -> Option<ast::Expr> {
+        match expr {
+            ast::Expr::Function(fun) if fun.name.to_string() == "convert_tz" => {
+                match fun.args.first() {
+                    Some(ast::FunctionArg::Unnamed(now)) if Self::is_now(now) => Some(now.clone()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for StatementTimeZone {
+    fn visit_function(&mut self, fun: &mut ast::Function) {
+        // `now() AT TIME ZONE '<zone>'` converts the current instant, not the session time
+        if fun.name.to_string().eq_ignore_ascii_case("timezone") {
+            if let [_, ast::FunctionArg::Unnamed(timestamp)] = fun.args.as_mut_slice() {
+                if let Some(now) = Self::unwrap_now(timestamp) {
+                    *timestamp = now;
+                }
+            }
+
+            return;
+        }
+
+        if !fun.name.to_string().eq_ignore_ascii_case("now") || !fun.args.is_empty() {
+            return;
+        }
+
+        let now = ast::Expr::Function(fun.clone());
+        let zone = |zone: String| {
+            ast::FunctionArg::Unnamed(ast::Expr::Value(ast::Value::SingleQuotedString(zone)))
+        };
+
+        fun.name = ast::ObjectName(vec![ast::Ident::new("convert_tz")]);
+        fun.args = vec![
+            ast::FunctionArg::Unnamed(now),
+            zone("+00:00".to_string()),
+            zone(self.offset.to_string()),
+        ];
+    }
+}
+
+/// Prefix of the marker type, `<expr> AT TIME ZONE '<zone>'` is parsed as
+/// `<expr>::"AT TIME ZONE '<zone>'"` (see `parser::rewrite_at_time_zone`).
+pub const AT_TIME_ZONE_MARKER: &str = "AT TIME ZONE ";
+
+/// Replaces casts to the `AT TIME ZONE` marker type by `timezone('<zone>', <expr>)` calls.
+#[derive(Debug)]
+pub struct StatementAtTimeZone {}
+
+impl StatementAtTimeZone {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn apply(&mut self, stmt: &mut ast::Statement) {
+        self.visit_statement(stmt);
+    }
+
+    fn marker_zone(data_type: &ast::DataType) -> Option<String> {
+        match data_type {
+            ast::DataType::Custom(name) => match name.0.as_slice() {
+                [ident] if ident.quote_style == Some('"') => ident
+                    .value
+                    .strip_prefix(AT_TIME_ZONE_MARKER)
+                    .and_then(|zone| zone.strip_prefix('\''))
+                    .and_then(|zone| zone.strip_suffix('\''))
+                    .map(|zone| zone.replace("''", "'")),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for StatementAtTimeZone {
+    fn visit_cast(&mut self, expr: &mut ast::Expr) {
+        if let ast::Expr::Cast {
+            expr: timestamp,
+            data_type,
+        } = expr
+        {
+            if let Some(zone) = Self::marker_zone(data_type) {
+                let timestamp = timestamp.as_ref().clone();
+                *expr = ast::Expr::Function(ast::Function {
+                    name: ast::ObjectName(vec![ast::Ident::new("timezone")]),
+                    args: vec![
+                        ast::FunctionArg::Unnamed(ast::Expr::Value(
+                            ast::Value::SingleQuotedString(zone),
+                        )),
+                        ast::FunctionArg::Unnamed(timestamp),
+                    ],
+                    over: None,
+                    distinct: false,
+                });
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -299,6 +482,63 @@ mod tests {
             vec![BindValue::String("test1".to_string())],
         )?;
 
+        test_binder(
+            r#"
+                SELECT *
+                FROM testdata
+                WHERE fieldA = $1 AND fieldB IS NOT NULL AND lower(fieldC) = $2
+                LIMIT $3
+            "#,
+            "SELECT * FROM testdata WHERE fieldA = NULL AND fieldB IS NOT NULL AND lower(fieldC) = 12.345 LIMIT 10",
+            vec![
+                BindValue::Null,
+                BindValue::Decimal("12.345".to_string()),
+                BindValue::Int64(10),
+            ],
+        )?;
+
         Ok(())
     }
+
+    #[test]
+    fn test_time_zone_now() {
+        let stmts = Parser::parse_sql(
+            &PostgreSqlDialect {},
+            "SELECT NOW() AS n, 1 FROM testdata WHERE fieldA > now()",
+        )
+        .unwrap();
+
+        let mut stmt = stmts[0].clone();
+        StatementTimeZone::new(FixedOffset::east(0)).apply(&mut stmt);
+        assert_eq!(
+            stmt.to_string(),
+            "SELECT NOW() AS n, 1 FROM testdata WHERE fieldA > now()"
+        );
+
+        StatementTimeZone::new(FixedOffset::east(3 * 3600)).apply(&mut stmt);
+        assert_eq!(
+            stmt.to_string(),
+            "SELECT convert_tz(NOW(), '+00:00', '+03:00') AS n, 1 FROM testdata WHERE fieldA > convert_tz(now(), '+00:00', '+03:00')"
+        );
+
+        // now() AT TIME ZONE 'UTC'
+        let stmts =
+            Parser::parse_sql(&PostgreSqlDialect {}, "SELECT timezone('UTC', now())").unwrap();
+        let mut stmt = stmts[0].clone();
+        StatementTimeZone::new(FixedOffset::east(3 * 3600)).apply(&mut stmt);
+        assert_eq!(stmt.to_string(), "SELECT timezone('UTC', now())");
+    }
+
+    #[test]
+    fn test_prepare_placeholders_only() {
+        let stmts = Parser::parse_sql(
+            &PostgreSqlDialect {},
+            "SELECT 1, $1 FROM testdata WHERE fieldA = 'a' AND fieldB = $2",
+        )
+        .unwrap();
+
+        let mut stmt = stmts[0].clone();
+        let mut prepare = StatementPrepare::new();
+        assert_eq!(prepare.prepare(&mut stmt).len(), 2);
+    }
 }
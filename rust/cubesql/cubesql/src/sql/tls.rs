@@ -0,0 +1,47 @@
+use std::{fs::File, io::BufReader, sync::Arc};
+
+use rustls_pemfile::Item;
+use tokio_rustls::{
+    rustls::{Certificate, PrivateKey, ServerConfig},
+    TlsAcceptor,
+};
+
+use crate::CubeError;
+
+/// Creates TLS acceptor from PEM encoded certificate chain and private key (PKCS#1, PKCS#8 or SEC1)
+pub fn create_tls_acceptor(cert_path: &str, key_path: &str) -> Result<TlsAcceptor, CubeError> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))?
+        .into_iter()
+        .map(Certificate)
+        .collect::<Vec<_>>();
+    if certs.is_empty() {
+        return Err(CubeError::user(format!(
+            "Unable to find TLS certificate in {}",
+            cert_path
+        )));
+    }
+
+    let mut reader = BufReader::new(File::open(key_path)?);
+    let key = loop {
+        match rustls_pemfile::read_one(&mut reader)? {
+            Some(Item::RSAKey(key)) | Some(Item::PKCS8Key(key)) | Some(Item::ECKey(key)) => {
+                break PrivateKey(key)
+            }
+            Some(_) => continue,
+            None => {
+                return Err(CubeError::user(format!(
+                    "Unable to find TLS private key in {}",
+                    key_path
+                )))
+            }
+        }
+    };
+
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| CubeError::user(format!("Invalid TLS certificate or key: {}", e)))?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
@@ -19,6 +19,19 @@ impl ColumnType {
     pub fn to_mysql(&self) -> MysqlColumnType {
         MysqlColumnType::MYSQL_TYPE_BLOB
     }
+
+    /// Binary protocol encodes values according to the column type, so it has to be exact.
+    pub fn to_mysql_binary(&self) -> MysqlColumnType {
+        match self {
+            ColumnType::String | ColumnType::VarStr => MysqlColumnType::MYSQL_TYPE_VAR_STRING,
+            ColumnType::Double => MysqlColumnType::MYSQL_TYPE_DOUBLE,
+            ColumnType::Int8 => MysqlColumnType::MYSQL_TYPE_TINY,
+            ColumnType::Int32 => MysqlColumnType::MYSQL_TYPE_LONG,
+            ColumnType::Int64 => MysqlColumnType::MYSQL_TYPE_LONGLONG,
+            ColumnType::Blob => MysqlColumnType::MYSQL_TYPE_BLOB,
+            ColumnType::Timestamp => MysqlColumnType::MYSQL_TYPE_DATETIME,
+        }
+    }
 }
 
 bitflags! {
@@ -1,6 +1,6 @@
 use std::ops::RangeFrom;
 
-use cubeclient::models::V1CubeMeta;
+use cubeclient::models::{V1CubeMeta, V1CubeMetaMeasure};
 
 use crate::sql::ColumnType;
 
@@ -66,6 +66,13 @@ impl MetaContext {
         None
     }
 
+    pub fn find_measure_with_name(&self, name: String) -> Option<V1CubeMetaMeasure> {
+        let cube_name = name.split('.').next()?;
+        let cube = self.cubes.iter().find(|c| c.name.eq(cube_name))?;
+
+        cube.measures.iter().find(|m| m.name.eq(&name)).cloned()
+    }
+
     pub fn find_cube_table_with_oid(&self, oid: u32) -> Option<CubeMetaTable> {
         self.tables.iter().find(|table| table.oid == oid).cloned()
     }
@@ -85,6 +92,7 @@ mod tests {
             V1CubeMeta {
                 name: "test1".to_string(),
                 title: None,
+                connected_component: None,
                 dimensions: vec![],
                 measures: vec![],
                 segments: vec![],
@@ -92,6 +100,7 @@ mod tests {
             V1CubeMeta {
                 name: "test2".to_string(),
                 title: None,
+                connected_component: None,
                 dimensions: vec![],
                 measures: vec![],
                 segments: vec![],
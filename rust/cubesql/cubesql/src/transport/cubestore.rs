@@ -0,0 +1,950 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use cubeclient::models::{
+    V1LoadRequestQuery, V1LoadRequestQueryFilterItem, V1LoadResponse, V1LoadResult,
+    V1LoadResultAnnotation,
+};
+use log::trace;
+use mysql_async::{prelude::Queryable, Opts, Pool, Row, Value};
+use sqlparser::ast::escape_single_quote_string;
+use tokio::sync::RwLock as RwLockAsync;
+
+use crate::{
+    compile::{engine::udf::fixed_time_zone_offset, MetaContext},
+    sql::AuthContext,
+    CubeError,
+};
+
+use super::{
+    model::{DataModel, ModelCube},
+    TransportService,
+};
+
+/// Cube API applies the same limit when it's not specified by the query
+const DEFAULT_LIMIT: i32 = 10000;
+
+const GRANULARITIES: [&str; 8] = [
+    "second", "minute", "hour", "day", "week", "month", "quarter", "year",
+];
+
+#[derive(Debug)]
+struct QueryMember {
+    // Name of the member in the response, e.g. `Orders.createdAt.day` for time dimensions
+    name: String,
+    sql: String,
+    member_type: String,
+}
+
+impl QueryMember {
+    fn alias(&self) -> String {
+        format!("`{}`", self.name.replace('.', "__"))
+    }
+}
+
+#[derive(Debug)]
+struct FilterSql {
+    sql: String,
+    // Filters by measures go to HAVING
+    measure: bool,
+}
+
+/// SQL query for CubeStore which is generated from the load request. Queries to a single cube
+/// are supported, there are no joins in the standalone mode.
+#[derive(Debug)]
+pub struct CubeStoreQuery<'a> {
+    cube: &'a ModelCube,
+    // UTC offset of the query time zone, e.g. `+03:00`
+    time_zone: Option<String>,
+    dimensions: Vec<QueryMember>,
+    measures: Vec<QueryMember>,
+    filters: Vec<String>,
+    having: Vec<String>,
+    order: Vec<String>,
+    limit: i32,
+    offset: Option<i32>,
+}
+
+impl<'a> CubeStoreQuery<'a> {
+    pub fn new(model: &'a DataModel, query: &V1LoadRequestQuery) -> Result<Self, CubeError> {
+        let cube = Self::find_query_cube(model, query)?;
+        let time_zone = match &query.timezone {
+            None => None,
+            Some(zone) => match fixed_time_zone_offset(zone) {
+                Some(offset) if offset.local_minus_utc() == 0 => None,
+                Some(offset) => Some(offset.to_string()),
+                None => {
+                    return Err(CubeError::user(format!(
+                        "Time zone '{}' is not supported by CubeStore transport, use UTC offset",
+                        zone
+                    )))
+                }
+            },
+        };
+
+        let mut cubestore_query = Self {
+            cube,
+            time_zone,
+            dimensions: vec![],
+            measures: vec![],
+            filters: vec![],
+            having: vec![],
+            order: vec![],
+            limit: query.limit.unwrap_or(DEFAULT_LIMIT),
+            offset: query.offset,
+        };
+
+        for dimension in query.dimensions.iter().flatten() {
+            let dimension = cubestore_query.member(dimension)?;
+            cubestore_query.dimensions.push(dimension);
+        }
+
+        for time_dimension in query.time_dimensions.iter().flatten() {
+            let member = cubestore_query.member(&time_dimension.dimension)?;
+
+            if let Some(date_range) = &time_dimension.date_range {
+                let filter = match date_range.as_array().map(|range| range.as_slice()) {
+                    Some([serde_json::Value::String(from), serde_json::Value::String(to)]) => {
+                        Self::date_range_sql(&member, from, to, false)
+                    }
+                    _ => {
+                        return Err(CubeError::user(format!(
+                            "Unsupported date range {} of '{}', only [from, to] ranges are supported in standalone mode",
+                            date_range, time_dimension.dimension
+                        )))
+                    }
+                };
+                cubestore_query.filters.push(filter);
+            }
+
+            if let Some(granularity) = &time_dimension.granularity {
+                if !GRANULARITIES.contains(&granularity.as_str()) {
+                    return Err(CubeError::user(format!(
+                        "Unknown granularity '{}' of '{}'",
+                        granularity, time_dimension.dimension
+                    )));
+                }
+
+                cubestore_query.dimensions.push(QueryMember {
+                    name: format!("{}.{}", member.name, granularity),
+                    sql: format!("date_trunc('{}', {})", granularity, member.sql),
+                    member_type: member.member_type,
+                });
+            }
+        }
+
+        for measure in query.measures.iter().flatten() {
+            let measure = cubestore_query.member(measure)?;
+            cubestore_query.measures.push(measure);
+        }
+
+        for segment in query.segments.iter().flatten() {
+            let name = Self::member_name(segment);
+            let segment = cube.find_segment(name).ok_or_else(|| {
+                CubeError::user(format!(
+                    "Segment '{}' is not defined in data model",
+                    segment
+                ))
+            })?;
+            cubestore_query.filters.push(format!(
+                "({})",
+                segment.sql.replace("{CUBE}", &Self::alias(cube))
+            ));
+        }
+
+        for filter in query.filters.iter().flatten() {
+            let filter = cubestore_query.filter_sql(filter)?;
+            if filter.measure {
+                cubestore_query.having.push(filter.sql);
+            } else {
+                cubestore_query.filters.push(filter.sql);
+            }
+        }
+
+        for order in query.order.iter().flatten() {
+            let (member, direction) = match order.as_slice() {
+                [member] => (member, "ASC".to_string()),
+                [member, direction] => (member, direction.to_uppercase()),
+                _ => {
+                    return Err(CubeError::user(format!(
+                        "Unexpected order {:?}, expected [member, direction]",
+                        order
+                    )))
+                }
+            };
+            if direction != "ASC" && direction != "DESC" {
+                return Err(CubeError::user(format!(
+                    "Unexpected order direction '{}' of '{}'",
+                    direction, member
+                )));
+            }
+
+            let prefix = format!("{}.", member);
+            let selected = cubestore_query
+                .dimensions
+                .iter()
+                .chain(cubestore_query.measures.iter())
+                .find(|selected| selected.name == *member || selected.name.starts_with(&prefix));
+            let sql = match selected {
+                Some(selected) => selected.alias(),
+                None => cubestore_query.member(member)?.sql,
+            };
+            cubestore_query.order.push(format!("{} {}", sql, direction));
+        }
+
+        Ok(cubestore_query)
+    }
+
+    fn find_query_cube(
+        model: &'a DataModel,
+        query: &V1LoadRequestQuery,
+    ) -> Result<&'a ModelCube, CubeError> {
+        let mut members = vec![];
+        members.extend(query.measures.iter().flatten().cloned());
+        members.extend(query.dimensions.iter().flatten().cloned());
+        members.extend(query.segments.iter().flatten().cloned());
+        members.extend(
+            query
+                .time_dimensions
+                .iter()
+                .flatten()
+                .map(|time_dimension| time_dimension.dimension.clone()),
+        );
+        for filter in query.filters.iter().flatten() {
+            Self::filter_members(filter, &mut members)?;
+        }
+
+        let cubes = members
+            .iter()
+            .map(|member| member.split('.').next().unwrap_or_default().to_string())
+            .collect::<BTreeSet<_>>();
+
+        match cubes.len() {
+            0 => Err(CubeError::user(
+                "Query must reference at least one member".to_string(),
+            )),
+            1 => {
+                let name = cubes.iter().next().unwrap();
+                model.find_cube(name).ok_or_else(|| {
+                    CubeError::user(format!("Cube '{}' is not defined in data model", name))
+                })
+            }
+            _ => Err(CubeError::user(format!(
+                "Joins are not supported in standalone mode, query references cubes: {}",
+                cubes.into_iter().collect::<Vec<_>>().join(", ")
+            ))),
+        }
+    }
+
+    fn filter_members(
+        filter: &V1LoadRequestQueryFilterItem,
+        members: &mut Vec<String>,
+    ) -> Result<(), CubeError> {
+        if let Some(member) = &filter.member {
+            members.push(member.clone());
+        }
+
+        for group in filter.or.iter().chain(filter.and.iter()) {
+            for filter in Self::sub_filters(group)? {
+                Self::filter_members(&filter, members)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn sub_filters(
+        group: &Vec<serde_json::Value>,
+    ) -> Result<Vec<V1LoadRequestQueryFilterItem>, CubeError> {
+        Ok(group
+            .iter()
+            .map(|filter| serde_json::from_value(filter.clone()))
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn alias(cube: &ModelCube) -> String {
+        format!("`{}`", cube.name)
+    }
+
+    fn member_name(member: &str) -> &str {
+        member
+            .split_once('.')
+            .map(|(_, name)| name)
+            .unwrap_or(member)
+    }
+
+    /// SQL of dimension or aggregation of measure
+    fn member(&self, member: &str) -> Result<QueryMember, CubeError> {
+        let name = Self::member_name(member);
+        let alias = Self::alias(self.cube);
+
+        if let Some(dimension) = self.cube.find_dimension(name) {
+            let mut sql = self
+                .cube
+                .member_sql(&alias, &dimension.name, &dimension.sql);
+            if let (Some(offset), "time") = (&self.time_zone, dimension.dimension_type.as_str()) {
+                sql = format!("CONVERT_TZ({}, '{}')", sql, offset);
+            }
+
+            return Ok(QueryMember {
+                name: member.to_string(),
+                sql,
+                member_type: dimension.dimension_type.clone(),
+            });
+        }
+
+        if let Some(measure) = self.cube.find_measure(name) {
+            let sql = self.cube.member_sql(&alias, &measure.name, &measure.sql);
+            let sql = match (measure.measure_type.as_str(), &measure.sql) {
+                ("count", None) => "count(*)".to_string(),
+                ("countDistinct", _) | ("countDistinctApprox", _) => {
+                    format!("count(distinct {})", sql)
+                }
+                // Aggregation is a part of sql
+                ("number", _) => sql,
+                (agg_type, _) => format!("{}({})", agg_type, sql),
+            };
+
+            return Ok(QueryMember {
+                name: member.to_string(),
+                sql,
+                member_type: "number".to_string(),
+            });
+        }
+
+        Err(CubeError::user(format!(
+            "Member '{}' is not defined in data model",
+            member
+        )))
+    }
+
+    fn literal(value: &str, member_type: &str) -> String {
+        match member_type {
+            "number" if value.parse::<f64>().is_ok() => value.to_string(),
+            "boolean" if value == "true" || value == "false" => value.to_string(),
+            "time" => format!("to_timestamp('{}')", escape_single_quote_string(value)),
+            _ => format!("'{}'", escape_single_quote_string(value)),
+        }
+    }
+
+    /// Values of `contains` and similar filters are matched literally, so LIKE wildcards are
+    /// escaped with the default escape character.
+    fn escape_like_pattern(value: &str) -> String {
+        let mut result = String::with_capacity(value.len());
+        for c in value.chars() {
+            if matches!(c, '\\' | '%' | '_') {
+                result.push('\\');
+            }
+            result.push(c);
+        }
+
+        result
+    }
+
+    fn date_range_sql(member: &QueryMember, from: &str, to: &str, not: bool) -> String {
+        // Dates without time cover the whole day
+        let from = if from.len() == 10 {
+            format!("{}T00:00:00.000", from)
+        } else {
+            from.to_string()
+        };
+        let to = if to.len() == 10 {
+            format!("{}T23:59:59.999", to)
+        } else {
+            to.to_string()
+        };
+
+        if not {
+            format!(
+                "({} < {} OR {} > {})",
+                member.sql,
+                Self::literal(&from, "time"),
+                member.sql,
+                Self::literal(&to, "time")
+            )
+        } else {
+            format!(
+                "({} >= {} AND {} <= {})",
+                member.sql,
+                Self::literal(&from, "time"),
+                member.sql,
+                Self::literal(&to, "time")
+            )
+        }
+    }
+
+    fn filter_sql(&self, filter: &V1LoadRequestQueryFilterItem) -> Result<FilterSql, CubeError> {
+        for (group, operator) in [(&filter.or, "OR"), (&filter.and, "AND")] {
+            if let Some(group) = group {
+                let filters = Self::sub_filters(group)?
+                    .iter()
+                    .map(|filter| self.filter_sql(filter))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let measure = filters.iter().any(|filter| filter.measure);
+                if measure && filters.iter().any(|filter| !filter.measure) {
+                    return Err(CubeError::user(
+                        "Filters by measures and dimensions can't be mixed in a logical group"
+                            .to_string(),
+                    ));
+                }
+
+                return Ok(FilterSql {
+                    sql: format!(
+                        "({})",
+                        filters
+                            .into_iter()
+                            .map(|filter| filter.sql)
+                            .collect::<Vec<_>>()
+                            .join(&format!(" {} ", operator))
+                    ),
+                    measure,
+                });
+            }
+        }
+
+        let (member, operator) = match (&filter.member, &filter.operator) {
+            (Some(member), Some(operator)) => (member, operator),
+            _ => {
+                return Err(CubeError::user(format!(
+                    "Filter must define member and operator: {:?}",
+                    filter
+                )))
+            }
+        };
+        let values = filter.values.clone().unwrap_or_default();
+        let require_values = |count: usize| -> Result<(), CubeError> {
+            if values.len() < count {
+                return Err(CubeError::user(format!(
+                    "Filter '{}' of '{}' requires {} value(s)",
+                    operator, member, count
+                )));
+            }
+
+            Ok(())
+        };
+
+        let member = self.member(member)?;
+        let literals = values
+            .iter()
+            .map(|value| Self::literal(value, &member.member_type))
+            .collect::<Vec<_>>();
+        let like = |prefix: &str, suffix: &str, not: &str| -> Vec<String> {
+            values
+                .iter()
+                .map(|value| {
+                    format!(
+                        "{} {}ILIKE '{}{}{}'",
+                        member.sql,
+                        not,
+                        prefix,
+                        escape_single_quote_string(&Self::escape_like_pattern(value)),
+                        suffix
+                    )
+                })
+                .collect()
+        };
+
+        let sql = match operator.as_str() {
+            "equals" => {
+                require_values(1)?;
+                if literals.len() == 1 {
+                    format!("{} = {}", member.sql, literals[0])
+                } else {
+                    format!("{} IN ({})", member.sql, literals.join(", "))
+                }
+            }
+            "notEquals" => {
+                require_values(1)?;
+                if literals.len() == 1 {
+                    format!(
+                        "({} <> {} OR {} IS NULL)",
+                        member.sql, literals[0], member.sql
+                    )
+                } else {
+                    format!(
+                        "({} NOT IN ({}) OR {} IS NULL)",
+                        member.sql,
+                        literals.join(", "),
+                        member.sql
+                    )
+                }
+            }
+            "contains" => {
+                require_values(1)?;
+                format!("({})", like("%", "%", "").join(" OR "))
+            }
+            "notContains" => {
+                require_values(1)?;
+                format!(
+                    "(({}) OR {} IS NULL)",
+                    like("%", "%", "NOT ").join(" AND "),
+                    member.sql
+                )
+            }
+            "startsWith" => {
+                require_values(1)?;
+                format!("({})", like("", "%", "").join(" OR "))
+            }
+            "endsWith" => {
+                require_values(1)?;
+                format!("({})", like("%", "", "").join(" OR "))
+            }
+            "gt" | "gte" | "lt" | "lte" => {
+                require_values(1)?;
+                let op = match operator.as_str() {
+                    "gt" => ">",
+                    "gte" => ">=",
+                    "lt" => "<",
+                    _ => "<=",
+                };
+                format!("{} {} {}", member.sql, op, literals[0])
+            }
+            "set" => format!("{} IS NOT NULL", member.sql),
+            "notSet" => format!("{} IS NULL", member.sql),
+            "inDateRange" | "notInDateRange" => {
+                require_values(2)?;
+                Self::date_range_sql(
+                    &member,
+                    &values[0],
+                    &values[1],
+                    operator == "notInDateRange",
+                )
+            }
+            "beforeDate" => {
+                require_values(1)?;
+                format!("{} < {}", member.sql, Self::literal(&values[0], "time"))
+            }
+            "afterDate" => {
+                require_values(1)?;
+                format!("{} > {}", member.sql, Self::literal(&values[0], "time"))
+            }
+            operator => {
+                return Err(CubeError::user(format!(
+                    "Unsupported filter operator '{}' in standalone mode",
+                    operator
+                )))
+            }
+        };
+
+        Ok(FilterSql {
+            sql,
+            measure: self
+                .cube
+                .find_measure(Self::member_name(&member.name))
+                .is_some(),
+        })
+    }
+
+    pub fn to_sql(&self) -> String {
+        let select = self
+            .dimensions
+            .iter()
+            .chain(self.measures.iter())
+            .map(|member| format!("{} AS {}", member.sql, member.alias()))
+            .collect::<Vec<_>>();
+
+        let mut sql = format!(
+            "SELECT {} FROM {} AS {}",
+            select.join(", "),
+            self.cube.sql_table,
+            Self::alias(self.cube)
+        );
+
+        if !self.filters.is_empty() {
+            sql.push_str(&format!(" WHERE {}", self.filters.join(" AND ")));
+        }
+
+        // Rows are grouped by dimensions even without measures, like Cube API does
+        if !self.dimensions.is_empty() {
+            let group_by = self
+                .dimensions
+                .iter()
+                .map(|dimension| dimension.sql.clone())
+                .collect::<Vec<_>>();
+            sql.push_str(&format!(" GROUP BY {}", group_by.join(", ")));
+        }
+
+        if !self.having.is_empty() {
+            sql.push_str(&format!(" HAVING {}", self.having.join(" AND ")));
+        }
+
+        if !self.order.is_empty() {
+            sql.push_str(&format!(" ORDER BY {}", self.order.join(", ")));
+        }
+
+        sql.push_str(&format!(" LIMIT {}", self.limit));
+        if let Some(offset) = self.offset {
+            sql.push_str(&format!(" OFFSET {}", offset));
+        }
+
+        sql
+    }
+
+    /// Transforms the row of CubeStore to the row of Cube API, it's an object by member names
+    pub fn transform_row(&self, values: Vec<Value>) -> serde_json::Value {
+        let row = self
+            .dimensions
+            .iter()
+            .chain(self.measures.iter())
+            .zip(values.into_iter())
+            .map(|(member, value)| {
+                (
+                    member.name.clone(),
+                    Self::transform_value(value, &member.member_type),
+                )
+            })
+            .collect::<serde_json::Map<_, _>>();
+
+        serde_json::Value::Object(row)
+    }
+
+    fn transform_value(value: Value, member_type: &str) -> serde_json::Value {
+        let value = match value {
+            Value::NULL => return serde_json::Value::Null,
+            Value::Bytes(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+            Value::Int(n) => n.to_string(),
+            Value::UInt(n) => n.to_string(),
+            Value::Float(n) => n.to_string(),
+            Value::Double(n) => n.to_string(),
+            Value::Date(year, month, day, hour, minute, second, micros) => format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}",
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+                micros / 1000
+            ),
+            Value::Time(negative, days, hours, minutes, seconds, _) => format!(
+                "{}{}:{:02}:{:02}",
+                if negative { "-" } else { "" },
+                days * 24 + hours as u32,
+                minutes,
+                seconds
+            ),
+        };
+
+        match member_type {
+            "boolean" => match value.as_str() {
+                "true" | "1" => serde_json::Value::Bool(true),
+                "false" | "0" => serde_json::Value::Bool(false),
+                _ => serde_json::Value::String(value),
+            },
+            // Cube API returns timestamps as local time with milliseconds
+            "time" => {
+                let timestamp = value.trim_end_matches('Z');
+                for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+                    if let Ok(timestamp) = NaiveDateTime::parse_from_str(timestamp, format) {
+                        return serde_json::Value::String(
+                            timestamp.format("%Y-%m-%dT%H:%M:%S%.3f").to_string(),
+                        );
+                    }
+                }
+
+                serde_json::Value::String(value)
+            }
+            _ => serde_json::Value::String(value),
+        }
+    }
+}
+
+/// This transport is used in standalone mode without Cube API: cubes are defined by the data
+/// model file and queries are executed by CubeStore over its MySQL protocol.
+#[derive(Debug)]
+pub struct CubeStoreTransport {
+    model_path: PathBuf,
+    model: RwLockAsync<(Arc<DataModel>, Arc<MetaContext>)>,
+    pool: Pool,
+}
+
+impl CubeStoreTransport {
+    pub fn new(model_path: String, cubestore_url: String) -> Result<Self, CubeError> {
+        let model_path = PathBuf::from(model_path);
+        let model = Self::load_model(&model_path)?;
+        let opts = Opts::from_url(&cubestore_url).map_err(|e| {
+            CubeError::user(format!("Invalid CubeStore url '{}': {}", cubestore_url, e))
+        })?;
+
+        Ok(Self {
+            model_path,
+            model: RwLockAsync::new(model),
+            pool: Pool::new(opts),
+        })
+    }
+
+    fn load_model(path: &Path) -> Result<(Arc<DataModel>, Arc<MetaContext>), CubeError> {
+        let model = DataModel::from_file(path)?;
+        let meta = MetaContext::new(model.to_meta());
+
+        Ok((Arc::new(model), Arc::new(meta)))
+    }
+}
+
+crate::di_service!(CubeStoreTransport, [TransportService]);
+
+#[async_trait]
+impl TransportService for CubeStoreTransport {
+    async fn meta(&self, _ctx: Arc<AuthContext>) -> Result<Arc<MetaContext>, CubeError> {
+        Ok(self.model.read().await.1.clone())
+    }
+
+    async fn load(
+        &self,
+        query: V1LoadRequestQuery,
+        _ctx: Arc<AuthContext>,
+    ) -> Result<V1LoadResponse, CubeError> {
+        let model = self.model.read().await.0.clone();
+        let cubestore_query = CubeStoreQuery::new(&model, &query)?;
+        let sql = cubestore_query.to_sql();
+        trace!("[cubestore] Query: {}", sql);
+
+        let mut conn = self.pool.get_conn().await?;
+        let rows: Vec<Row> = conn.query(sql).await?;
+        let data = rows
+            .into_iter()
+            .map(|row| cubestore_query.transform_row(row.unwrap()))
+            .collect();
+
+        let annotation = V1LoadResultAnnotation::new(
+            serde_json::json!({}),
+            serde_json::json!({}),
+            serde_json::json!({}),
+            serde_json::json!({}),
+        );
+
+        Ok(V1LoadResponse::new(vec![V1LoadResult::new(
+            annotation, data,
+        )]))
+    }
+
+    // SYS REFRESH META reloads the data model file
+    async fn invalidate_meta(&self, _ctx: Arc<AuthContext>) -> Result<(), CubeError> {
+        let model = Self::load_model(&self.model_path)?;
+        *self.model.write().await = model;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cubeclient::models::V1LoadRequestQueryTimeDimension;
+
+    use super::*;
+
+    fn get_test_model() -> DataModel {
+        DataModel::from_yaml(
+            r#"
+cubes:
+  - name: Orders
+    sql_table: orders.orders
+    measures:
+      - name: count
+        type: count
+      - name: totalAmount
+        sql: amount
+        type: sum
+    dimensions:
+      - name: status
+        type: string
+      - name: amount
+        type: number
+      - name: isPaid
+        sql: is_paid
+        type: boolean
+      - name: createdAt
+        sql: "{CUBE}.created_at"
+        type: time
+    segments:
+      - name: completed
+        sql: "{CUBE}.status = 'completed'"
+  - name: Logs
+    sql_table: logs.logs
+    measures:
+      - name: count
+        type: count
+"#,
+        )
+        .unwrap()
+    }
+
+    fn filter(member: &str, operator: &str, values: Vec<&str>) -> V1LoadRequestQueryFilterItem {
+        V1LoadRequestQueryFilterItem {
+            member: Some(member.to_string()),
+            operator: Some(operator.to_string()),
+            values: Some(values.into_iter().map(|v| v.to_string()).collect()),
+            or: None,
+            and: None,
+        }
+    }
+
+    fn to_sql(model: &DataModel, query: V1LoadRequestQuery) -> Result<String, CubeError> {
+        Ok(CubeStoreQuery::new(model, &query)?.to_sql())
+    }
+
+    #[test]
+    fn test_cubestore_query_sql() -> Result<(), CubeError> {
+        let model = get_test_model();
+
+        let mut query = V1LoadRequestQuery::new();
+        query.measures = Some(vec!["Orders.count".to_string()]);
+        query.dimensions = Some(vec!["Orders.status".to_string()]);
+        query.time_dimensions = Some(vec![V1LoadRequestQueryTimeDimension {
+            dimension: "Orders.createdAt".to_string(),
+            granularity: Some("day".to_string()),
+            date_range: Some(serde_json::json!(["2021-01-01", "2021-01-31"])),
+        }]);
+        query.filters = Some(vec![filter("Orders.status", "equals", vec!["it's new"])]);
+        query.order = Some(vec![vec![
+            "Orders.createdAt".to_string(),
+            "desc".to_string(),
+        ]]);
+        query.limit = Some(100);
+
+        assert_eq!(
+            to_sql(&model, query)?,
+            "SELECT `Orders`.`status` AS `Orders__status`, date_trunc('day', `Orders`.created_at) AS `Orders__createdAt__day`, count(*) AS `Orders__count` \
+            FROM orders.orders AS `Orders` \
+            WHERE (`Orders`.created_at >= to_timestamp('2021-01-01T00:00:00.000') AND `Orders`.created_at <= to_timestamp('2021-01-31T23:59:59.999')) AND `Orders`.`status` = 'it''s new' \
+            GROUP BY `Orders`.`status`, date_trunc('day', `Orders`.created_at) \
+            ORDER BY `Orders__createdAt__day` DESC \
+            LIMIT 100"
+        );
+
+        let mut query = V1LoadRequestQuery::new();
+        query.measures = Some(vec!["Orders.totalAmount".to_string()]);
+        query.segments = Some(vec!["Orders.completed".to_string()]);
+        query.filters = Some(vec![
+            filter("Orders.totalAmount", "gt", vec!["100"]),
+            V1LoadRequestQueryFilterItem {
+                or: Some(vec![
+                    serde_json::to_value(filter("Orders.amount", "lte", vec!["5"]))?,
+                    serde_json::to_value(filter("Orders.isPaid", "notSet", vec![]))?,
+                ]),
+                ..V1LoadRequestQueryFilterItem::new()
+            },
+        ]);
+        query.timezone = Some("Etc/GMT-3".to_string());
+
+        assert_eq!(
+            to_sql(&model, query)?,
+            "SELECT sum(`Orders`.amount) AS `Orders__totalAmount` \
+            FROM orders.orders AS `Orders` \
+            WHERE (`Orders`.status = 'completed') AND (`Orders`.`amount` <= 5 OR `Orders`.is_paid IS NULL) \
+            HAVING sum(`Orders`.amount) > 100 \
+            LIMIT 10000"
+        );
+
+        let mut query = V1LoadRequestQuery::new();
+        query.dimensions = Some(vec!["Orders.createdAt".to_string()]);
+        query.filters = Some(vec![filter(
+            "Orders.createdAt",
+            "afterDate",
+            vec!["2021-01-01T00:00:00.000"],
+        )]);
+        query.timezone = Some("+03:00".to_string());
+
+        assert_eq!(
+            to_sql(&model, query)?,
+            "SELECT CONVERT_TZ(`Orders`.created_at, '+03:00') AS `Orders__createdAt` \
+            FROM orders.orders AS `Orders` \
+            WHERE CONVERT_TZ(`Orders`.created_at, '+03:00') > to_timestamp('2021-01-01T00:00:00.000') \
+            GROUP BY CONVERT_TZ(`Orders`.created_at, '+03:00') \
+            LIMIT 10000"
+        );
+
+        let mut query = V1LoadRequestQuery::new();
+        query.dimensions = Some(vec!["Orders.status".to_string()]);
+        query.filters = Some(vec![filter(
+            "Orders.status",
+            "contains",
+            vec!["100%_it's\\"],
+        )]);
+
+        assert_eq!(
+            to_sql(&model, query)?,
+            "SELECT `Orders`.`status` AS `Orders__status` \
+            FROM orders.orders AS `Orders` \
+            WHERE (`Orders`.`status` ILIKE '%100\\%\\_it''s\\\\%') \
+            GROUP BY `Orders`.`status` \
+            LIMIT 10000"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cubestore_query_errors() {
+        let model = get_test_model();
+
+        let mut query = V1LoadRequestQuery::new();
+        query.measures = Some(vec!["Orders.count".to_string(), "Logs.count".to_string()]);
+        assert_eq!(
+            to_sql(&model, query).unwrap_err().message,
+            "Joins are not supported in standalone mode, query references cubes: Logs, Orders"
+        );
+
+        let mut query = V1LoadRequestQuery::new();
+        query.measures = Some(vec!["Orders.count".to_string()]);
+        query.timezone = Some("America/Los_Angeles".to_string());
+        assert_eq!(
+            to_sql(&model, query).unwrap_err().message,
+            "Time zone 'America/Los_Angeles' is not supported by CubeStore transport, use UTC offset"
+        );
+
+        let mut query = V1LoadRequestQuery::new();
+        query.measures = Some(vec!["Orders.unknown".to_string()]);
+        assert_eq!(
+            to_sql(&model, query).unwrap_err().message,
+            "Member 'Orders.unknown' is not defined in data model"
+        );
+    }
+
+    #[test]
+    fn test_cubestore_query_transform_row() -> Result<(), CubeError> {
+        let model = get_test_model();
+
+        let mut query = V1LoadRequestQuery::new();
+        query.measures = Some(vec!["Orders.count".to_string()]);
+        query.dimensions = Some(vec!["Orders.isPaid".to_string()]);
+        query.time_dimensions = Some(vec![V1LoadRequestQueryTimeDimension {
+            dimension: "Orders.createdAt".to_string(),
+            granularity: Some("month".to_string()),
+            date_range: None,
+        }]);
+
+        let cubestore_query = CubeStoreQuery::new(&model, &query)?;
+        assert_eq!(
+            cubestore_query.transform_row(vec![
+                Value::Bytes(b"true".to_vec()),
+                Value::Bytes(b"2021-01-01 00:00:00.000000".to_vec()),
+                Value::Int(5),
+            ]),
+            serde_json::json!({
+                "Orders.isPaid": true,
+                "Orders.createdAt.month": "2021-01-01T00:00:00.000",
+                "Orders.count": "5",
+            })
+        );
+        assert_eq!(
+            cubestore_query.transform_row(vec![
+                Value::NULL,
+                Value::Date(2021, 2, 1, 0, 0, 0, 0),
+                Value::Bytes(b"0".to_vec()),
+            ]),
+            serde_json::json!({
+                "Orders.isPaid": null,
+                "Orders.createdAt.month": "2021-02-01T00:00:00.000",
+                "Orders.count": "0",
+            })
+        );
+
+        Ok(())
+    }
+}
@@ -5,9 +5,13 @@ use crate::sql::ColumnType;
 pub trait V1CubeMetaMeasureExt {
     fn get_real_name(&self) -> String;
 
-    fn is_same_agg_type(&self, expect_agg_type: &str) -> bool;
+    /// Checks that an SQL aggregate function can be used on top of the measure aggregation
+    fn is_compatible_agg_type(&self, call_agg_type: &str) -> bool;
 
     fn get_sql_type(&self) -> ColumnType;
+
+    /// Sums are exact in the database, Cube responds with them as decimal strings
+    fn is_decimal(&self) -> bool;
 }
 
 impl V1CubeMetaMeasureExt for V1CubeMetaMeasure {
@@ -17,18 +21,22 @@ impl V1CubeMetaMeasureExt for V1CubeMetaMeasure {
         dimension_name.to_string()
     }
 
-    fn is_same_agg_type(&self, expect_agg_type: &str) -> bool {
-        if self.agg_type.is_some() {
-            if expect_agg_type.eq(&"countDistinct".to_string()) {
-                let agg_type = self.agg_type.as_ref().unwrap();
-
-                agg_type.eq(&"countDistinct".to_string())
-                    || agg_type.eq(&"countDistinctApprox".to_string())
-            } else {
-                self.agg_type.as_ref().unwrap().eq(expect_agg_type)
-            }
-        } else {
-            false
+    fn is_compatible_agg_type(&self, call_agg_type: &str) -> bool {
+        match &self.agg_type {
+            Some(agg_type) => match agg_type.as_str() {
+                // Cube already aggregates by the requested dimensions, so numeric measures can
+                // be summed, averaged or compared on top of its result. Distinct counts can't.
+                "count" => matches!(call_agg_type, "count" | "sum" | "avg" | "min" | "max"),
+                "sum" | "runningTotal" | "number" => {
+                    matches!(call_agg_type, "sum" | "avg" | "min" | "max")
+                }
+                "countDistinct" => call_agg_type == "countDistinct",
+                "countDistinctApprox" => {
+                    matches!(call_agg_type, "countDistinct" | "countDistinctApprox")
+                }
+                agg_type => agg_type == call_agg_type,
+            },
+            None => false,
         }
     }
 
@@ -53,6 +61,10 @@ impl V1CubeMetaMeasureExt for V1CubeMetaMeasure {
             _ => from_type,
         }
     }
+
+    fn is_decimal(&self) -> bool {
+        matches!(self.agg_type.as_deref(), Some("sum") | Some("runningTotal"))
+    }
 }
 
 pub trait V1CubeMetaSegmentExt {
@@ -1,7 +1,11 @@
 pub(crate) mod ctx;
+pub(crate) mod cubestore;
 pub(crate) mod ext;
+pub(crate) mod model;
 pub(crate) mod service;
 
 pub use ctx::*;
+pub use cubestore::*;
 pub use ext::*;
+pub use model::*;
 pub use service::*;
@@ -0,0 +1,336 @@
+use std::{fs, path::Path};
+
+use cubeclient::models::{V1CubeMeta, V1CubeMetaDimension, V1CubeMetaMeasure, V1CubeMetaSegment};
+use serde_derive::Deserialize;
+
+use crate::CubeError;
+
+/// Data model of the standalone mode, it's read from YAML or JSON file:
+///
+/// ```yaml
+/// cubes:
+///   - name: Orders
+///     sql_table: public.orders
+///     measures:
+///       - name: count
+///         type: count
+///       - name: totalAmount
+///         sql: amount
+///         type: sum
+///     dimensions:
+///       - name: status
+///         type: string
+///       - name: createdAt
+///         sql: created_at
+///         type: time
+///     segments:
+///       - name: completed
+///         sql: "{CUBE}.status = 'completed'"
+/// ```
+///
+/// `sql` of members is an expression over columns of `sql_table`, `{CUBE}` is replaced by the
+/// table alias. It's the column with the name of the member when it's not specified.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DataModel {
+    pub cubes: Vec<ModelCube>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelCube {
+    pub name: String,
+    pub title: Option<String>,
+    #[serde(alias = "sqlTable")]
+    pub sql_table: String,
+    #[serde(default)]
+    pub measures: Vec<ModelMeasure>,
+    #[serde(default)]
+    pub dimensions: Vec<ModelDimension>,
+    #[serde(default)]
+    pub segments: Vec<ModelSegment>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelMeasure {
+    pub name: String,
+    pub title: Option<String>,
+    pub sql: Option<String>,
+    #[serde(rename = "type")]
+    pub measure_type: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelDimension {
+    pub name: String,
+    pub sql: Option<String>,
+    #[serde(rename = "type")]
+    pub dimension_type: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelSegment {
+    pub name: String,
+    pub title: Option<String>,
+    pub sql: String,
+}
+
+const MEASURE_TYPES: [&str; 8] = [
+    "count",
+    "countDistinct",
+    "countDistinctApprox",
+    "sum",
+    "avg",
+    "min",
+    "max",
+    "number",
+];
+
+const DIMENSION_TYPES: [&str; 4] = ["string", "number", "time", "boolean"];
+
+impl DataModel {
+    pub fn from_file(path: &Path) -> Result<Self, CubeError> {
+        let content = fs::read_to_string(path)?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yml") | Some("yaml") => Self::from_yaml(&content),
+            Some("json") => Self::from_json(&content),
+            _ => Err(CubeError::user(format!(
+                "Unsupported data model file '{}', expected .yml, .yaml or .json",
+                path.display()
+            ))),
+        }
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self, CubeError> {
+        let model: DataModel = serde_yaml::from_str(content)?;
+        model.validate()?;
+
+        Ok(model)
+    }
+
+    pub fn from_json(content: &str) -> Result<Self, CubeError> {
+        let model: DataModel = serde_json::from_str(content)?;
+        model.validate()?;
+
+        Ok(model)
+    }
+
+    fn validate(&self) -> Result<(), CubeError> {
+        for cube in self.cubes.iter() {
+            for measure in cube.measures.iter() {
+                if !MEASURE_TYPES.contains(&measure.measure_type.as_str()) {
+                    return Err(CubeError::user(format!(
+                        "Unknown type '{}' of measure '{}.{}', expected one of: {}",
+                        measure.measure_type,
+                        cube.name,
+                        measure.name,
+                        MEASURE_TYPES.join(", ")
+                    )));
+                }
+
+                if measure.sql.is_none() && measure.measure_type != "count" {
+                    return Err(CubeError::user(format!(
+                        "Measure '{}.{}' of type '{}' must define sql",
+                        cube.name, measure.name, measure.measure_type
+                    )));
+                }
+            }
+
+            for dimension in cube.dimensions.iter() {
+                if !DIMENSION_TYPES.contains(&dimension.dimension_type.as_str()) {
+                    return Err(CubeError::user(format!(
+                        "Unknown type '{}' of dimension '{}.{}', expected one of: {}",
+                        dimension.dimension_type,
+                        cube.name,
+                        dimension.name,
+                        DIMENSION_TYPES.join(", ")
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn find_cube(&self, name: &str) -> Option<&ModelCube> {
+        self.cubes.iter().find(|cube| cube.name == name)
+    }
+
+    pub fn to_meta(&self) -> Vec<V1CubeMeta> {
+        self.cubes.iter().map(ModelCube::to_meta).collect()
+    }
+}
+
+impl ModelCube {
+    pub fn find_measure(&self, name: &str) -> Option<&ModelMeasure> {
+        self.measures.iter().find(|measure| measure.name == name)
+    }
+
+    pub fn find_dimension(&self, name: &str) -> Option<&ModelDimension> {
+        self.dimensions
+            .iter()
+            .find(|dimension| dimension.name == name)
+    }
+
+    pub fn find_segment(&self, name: &str) -> Option<&ModelSegment> {
+        self.segments.iter().find(|segment| segment.name == name)
+    }
+
+    /// SQL expression of the member, `alias` is the alias of `sql_table` in the query.
+    pub fn member_sql(&self, alias: &str, name: &str, sql: &Option<String>) -> String {
+        match sql {
+            Some(sql) => sql.replace("{CUBE}", alias),
+            None => format!("{}.`{}`", alias, name),
+        }
+    }
+
+    pub fn to_meta(&self) -> V1CubeMeta {
+        V1CubeMeta {
+            name: self.name.clone(),
+            title: self.title.clone(),
+            connected_component: None,
+            measures: self
+                .measures
+                .iter()
+                .map(|measure| V1CubeMetaMeasure {
+                    name: format!("{}.{}", self.name, measure.name),
+                    title: measure.title.clone(),
+                    _type: "number".to_string(),
+                    agg_type: Some(measure.measure_type.clone()),
+                })
+                .collect(),
+            dimensions: self
+                .dimensions
+                .iter()
+                .map(|dimension| V1CubeMetaDimension {
+                    name: format!("{}.{}", self.name, dimension.name),
+                    _type: dimension.dimension_type.clone(),
+                })
+                .collect(),
+            segments: self
+                .segments
+                .iter()
+                .map(|segment| {
+                    let title = segment
+                        .title
+                        .clone()
+                        .unwrap_or_else(|| segment.name.clone());
+
+                    V1CubeMetaSegment {
+                        name: format!("{}.{}", self.name, segment.name),
+                        title: title.clone(),
+                        short_title: title,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: &str = r#"
+cubes:
+  - name: Orders
+    sql_table: orders.orders
+    measures:
+      - name: count
+        type: count
+      - name: totalAmount
+        sql: amount
+        type: sum
+    dimensions:
+      - name: status
+        type: string
+      - name: createdAt
+        sql: "{CUBE}.created_at"
+        type: time
+    segments:
+      - name: completed
+        sql: "{CUBE}.status = 'completed'"
+"#;
+
+    #[test]
+    fn test_data_model_to_meta() -> Result<(), CubeError> {
+        let model = DataModel::from_yaml(MODEL)?;
+        let meta = model.to_meta();
+
+        assert_eq!(meta.len(), 1);
+        assert_eq!(meta[0].name, "Orders");
+        assert_eq!(
+            meta[0].measures[1],
+            V1CubeMetaMeasure {
+                name: "Orders.totalAmount".to_string(),
+                title: None,
+                _type: "number".to_string(),
+                agg_type: Some("sum".to_string()),
+            }
+        );
+        assert_eq!(
+            meta[0].dimensions[1],
+            V1CubeMetaDimension::new("Orders.createdAt".to_string(), "time".to_string())
+        );
+        assert_eq!(meta[0].segments[0].name, "Orders.completed");
+
+        let cube = model.find_cube("Orders").unwrap();
+        let created_at = cube.find_dimension("createdAt").unwrap();
+        assert_eq!(
+            cube.member_sql("`o`", &created_at.name, &created_at.sql),
+            "`o`.created_at"
+        );
+        let status = cube.find_dimension("status").unwrap();
+        assert_eq!(
+            cube.member_sql("`o`", &status.name, &status.sql),
+            "`o`.`status`"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_model_json() -> Result<(), CubeError> {
+        let model = DataModel::from_json(
+            r#"{"cubes": [{"name": "Logs", "sqlTable": "logs.logs", "measures": [{"name": "count", "type": "count"}]}]}"#,
+        )?;
+        assert_eq!(model.cubes[0].sql_table, "logs.logs");
+        assert!(model.cubes[0].dimensions.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_model_validation() {
+        let err = DataModel::from_yaml(
+            r#"
+cubes:
+  - name: Orders
+    sql_table: orders.orders
+    measures:
+      - name: amount
+        type: sum
+"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Measure 'Orders.amount' of type 'sum' must define sql"
+        );
+
+        let err = DataModel::from_yaml(
+            r#"
+cubes:
+  - name: Orders
+    sql_table: orders.orders
+    dimensions:
+      - name: status
+        type: text
+"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.message,
+            "Unknown type 'text' of dimension 'Orders.status', expected one of: string, number, time, boolean"
+        );
+    }
+}
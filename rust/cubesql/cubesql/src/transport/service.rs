@@ -2,16 +2,20 @@ use async_trait::async_trait;
 use cubeclient::apis::{
     configuration::Configuration as ClientConfiguration, default_api as cube_api,
 };
-use cubeclient::models::{V1LoadRequest, V1LoadRequestQuery, V1LoadResponse};
+use cubeclient::models::{V1LoadRequest, V1LoadRequestQuery, V1LoadResponse, V1MetaResponse};
 
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock as RwLockAsync;
-use tokio::time::Instant;
 
 use crate::{compile::MetaContext, sql::AuthContext, CubeError};
 
+const META_CACHE_CAPACITY: usize = 1000;
+const META_CACHE_IDLE_TTL: Duration = Duration::from_secs(30 * 60);
+const META_CACHE_REVALIDATE_AFTER: Duration = Duration::from_secs(60);
+
 #[async_trait]
 pub trait TransportService: Send + Sync + Debug {
     // Load meta information about cubes
@@ -23,29 +27,171 @@ pub trait TransportService: Send + Sync + Debug {
         query: V1LoadRequestQuery,
         ctx: Arc<AuthContext>,
     ) -> Result<V1LoadResponse, CubeError>;
+
+    // Drop cached meta information for the context, used by SYS REFRESH META
+    async fn invalidate_meta(&self, _ctx: Arc<AuthContext>) -> Result<(), CubeError> {
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
 struct MetaCacheBucket {
-    lifetime: Instant,
+    compiler_id: String,
     value: Arc<MetaContext>,
+    // When meta was received from the API
+    checked_at: Instant,
+    last_used_at: Instant,
+}
+
+/// Meta cache which is shared between sessions of the same security context.
+/// We don't persist DF in the SessionState, so every query asks for meta and
+/// without this cache busy BI connections produce a lot of meta requests.
+/// Meta is requested again after `revalidate_after`, the built MetaContext is kept if the
+/// compiler id (schema version) didn't change. Load responses with another compiler id and
+/// `SYS REFRESH META` drop it right away. Responses without compiler id can't be checked and
+/// aren't cached. Access tokens expire and rotate, so buckets which aren't used for `idle_ttl` are
+/// dropped and the least recently used ones are evicted above `capacity`.
+#[derive(Debug)]
+pub struct MetaCache {
+    buckets: RwLockAsync<HashMap<String, MetaCacheBucket>>,
+    capacity: usize,
+    idle_ttl: Duration,
+    revalidate_after: Duration,
+}
+
+impl MetaCache {
+    pub fn new() -> Self {
+        Self::with_limits(
+            META_CACHE_CAPACITY,
+            META_CACHE_IDLE_TTL,
+            META_CACHE_REVALIDATE_AFTER,
+        )
+    }
+
+    pub fn with_limits(capacity: usize, idle_ttl: Duration, revalidate_after: Duration) -> Self {
+        Self {
+            buckets: RwLockAsync::new(HashMap::new()),
+            capacity,
+            idle_ttl,
+            revalidate_after,
+        }
+    }
+
+    fn cache_key(ctx: &AuthContext) -> String {
+        format!(
+            "{}:{}:{}",
+            ctx.base_path,
+            ctx.access_token,
+            ctx.security_context
+                .as_ref()
+                .map(|security_context| security_context.to_string())
+                .unwrap_or_default()
+        )
+    }
+
+    /// Returns meta which doesn't need to be revalidated yet.
+    pub async fn get(&self, ctx: &AuthContext) -> Option<Arc<MetaContext>> {
+        let mut buckets = self.buckets.write().await;
+        let bucket = buckets.get_mut(&Self::cache_key(ctx))?;
+        if bucket.checked_at.elapsed() >= self.revalidate_after {
+            return None;
+        }
+
+        bucket.last_used_at = Instant::now();
+        Some(bucket.value.clone())
+    }
+
+    /// Stores meta received from the API. If the schema wasn't recompiled since the previous
+    /// request (same compiler id), the already built MetaContext is kept.
+    pub async fn insert(&self, ctx: &AuthContext, response: V1MetaResponse) -> Arc<MetaContext> {
+        let key = Self::cache_key(ctx);
+        let mut buckets = self.buckets.write().await;
+
+        let compiler_id = match response.compiler_id {
+            Some(compiler_id) => compiler_id,
+            None => {
+                buckets.remove(&key);
+                return Arc::new(MetaContext::new(response.cubes.unwrap_or_else(Vec::new)));
+            }
+        };
+
+        let value = match buckets.get(&key) {
+            Some(bucket) if bucket.compiler_id == compiler_id => bucket.value.clone(),
+            _ => Arc::new(MetaContext::new(response.cubes.unwrap_or_else(Vec::new))),
+        };
+
+        let now = Instant::now();
+        buckets.insert(
+            key,
+            MetaCacheBucket {
+                compiler_id,
+                value: value.clone(),
+                checked_at: now,
+                last_used_at: now,
+            },
+        );
+        self.evict(&mut buckets);
+
+        value
+    }
+
+    fn evict(&self, buckets: &mut HashMap<String, MetaCacheBucket>) {
+        let idle_ttl = self.idle_ttl;
+        buckets.retain(|_, bucket| bucket.last_used_at.elapsed() < idle_ttl);
+
+        while buckets.len() > self.capacity {
+            let least_recently_used = buckets
+                .iter()
+                .min_by_key(|(_, bucket)| bucket.last_used_at)
+                .map(|(key, _)| key.clone());
+            match least_recently_used {
+                Some(key) => buckets.remove(&key),
+                None => break,
+            };
+        }
+    }
+
+    /// Drops meta of the context if the load was served by another compiler (schema version).
+    pub async fn check_compiler_id(&self, ctx: &AuthContext, compiler_id: Option<&str>) {
+        let compiler_id = match compiler_id {
+            Some(compiler_id) => compiler_id,
+            None => return,
+        };
+
+        let key = Self::cache_key(ctx);
+        let is_outdated = {
+            let buckets = self.buckets.read().await;
+            buckets
+                .get(&key)
+                .map(|bucket| bucket.compiler_id != compiler_id)
+                .unwrap_or(false)
+        };
+        if is_outdated {
+            let mut buckets = self.buckets.write().await;
+            if let Some(bucket) = buckets.get(&key) {
+                if bucket.compiler_id != compiler_id {
+                    buckets.remove(&key);
+                }
+            }
+        }
+    }
+
+    pub async fn invalidate(&self, ctx: &AuthContext) {
+        let mut buckets = self.buckets.write().await;
+        buckets.remove(&Self::cache_key(ctx));
+    }
 }
 
 /// This transports is used in standalone mode
 #[derive(Debug)]
 pub struct HttpTransport {
-    /// We use simple cache to improve DX with standalone mode
-    /// because currently we dont persist DF in the SessionState
-    /// and it causes a lot of HTTP requests which slow down BI connections
-    cache: RwLockAsync<Option<MetaCacheBucket>>,
+    cache: MetaCache,
 }
 
-const CACHE_LIFETIME_DURATION: Duration = Duration::from_secs(5);
-
 impl HttpTransport {
     pub fn new() -> Self {
         Self {
-            cache: RwLockAsync::new(None),
+            cache: MetaCache::new(),
         }
     }
 
@@ -63,32 +209,13 @@ crate::di_service!(HttpTransport, [TransportService]);
 #[async_trait]
 impl TransportService for HttpTransport {
     async fn meta(&self, ctx: Arc<AuthContext>) -> Result<Arc<MetaContext>, CubeError> {
-        {
-            let store = self.cache.read().await;
-            if let Some(cache_bucket) = &*store {
-                if cache_bucket.lifetime.elapsed() < CACHE_LIFETIME_DURATION {
-                    return Ok(cache_bucket.value.clone());
-                };
-            };
+        if let Some(value) = self.cache.get(&ctx).await {
+            return Ok(value);
         }
 
-        let response = cube_api::meta_v1(&self.get_client_config_for_ctx(ctx)).await?;
-
-        let mut store = self.cache.write().await;
-        if let Some(cache_bucket) = &*store {
-            if cache_bucket.lifetime.elapsed() < CACHE_LIFETIME_DURATION {
-                return Ok(cache_bucket.value.clone());
-            }
-        };
-
-        let value = Arc::new(MetaContext::new(response.cubes.unwrap_or_else(Vec::new)));
-
-        *store = Some(MetaCacheBucket {
-            lifetime: Instant::now(),
-            value: value.clone(),
-        });
+        let response = cube_api::meta_v1(&self.get_client_config_for_ctx(ctx.clone())).await?;
 
-        Ok(value)
+        Ok(self.cache.insert(&ctx, response).await)
     }
 
     async fn load(
@@ -101,8 +228,132 @@ impl TransportService for HttpTransport {
             query_type: Some("multi".to_string()),
         };
         let response =
-            cube_api::load_v1(&self.get_client_config_for_ctx(ctx), Some(request)).await?;
+            cube_api::load_v1(&self.get_client_config_for_ctx(ctx.clone()), Some(request)).await?;
+        self.cache
+            .check_compiler_id(&ctx, response.compiler_id.as_deref())
+            .await;
 
         Ok(response)
     }
+
+    async fn invalidate_meta(&self, ctx: Arc<AuthContext>) -> Result<(), CubeError> {
+        self.cache.invalidate(&ctx).await;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth_context(security_context: serde_json::Value) -> AuthContext {
+        AuthContext {
+            access_token: "access_token".to_string(),
+            base_path: "base_path".to_string(),
+            security_context: Some(security_context),
+            can_switch_user: false,
+        }
+    }
+
+    fn meta_response(compiler_id: &str) -> V1MetaResponse {
+        V1MetaResponse {
+            cubes: Some(vec![]),
+            compiler_id: Some(compiler_id.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_meta_cache() {
+        let cache = MetaCache::new();
+        let tenant_a = auth_context(serde_json::json!({ "tenant": "a" }));
+        let tenant_b = auth_context(serde_json::json!({ "tenant": "b" }));
+
+        let value = cache.insert(&tenant_a, meta_response("1")).await;
+        assert!(Arc::ptr_eq(&cache.get(&tenant_a).await.unwrap(), &value));
+        assert!(cache.get(&tenant_b).await.is_none());
+
+        // Same schema version keeps already built meta
+        let same = cache.insert(&tenant_a, meta_response("1")).await;
+        assert!(Arc::ptr_eq(&same, &value));
+
+        let recompiled = cache.insert(&tenant_a, meta_response("2")).await;
+        assert!(!Arc::ptr_eq(&recompiled, &value));
+
+        // Loads served by the same compiler keep meta, another compiler drops it
+        cache.check_compiler_id(&tenant_a, Some("2")).await;
+        assert!(Arc::ptr_eq(
+            &cache.get(&tenant_a).await.unwrap(),
+            &recompiled
+        ));
+        cache.check_compiler_id(&tenant_a, None).await;
+        assert!(cache.get(&tenant_a).await.is_some());
+        cache.check_compiler_id(&tenant_a, Some("3")).await;
+        assert!(cache.get(&tenant_a).await.is_none());
+
+        cache.insert(&tenant_a, meta_response("3")).await;
+        cache.invalidate(&tenant_a).await;
+        assert!(cache.get(&tenant_a).await.is_none());
+
+        // Meta without compiler id can't be validated
+        cache
+            .insert(
+                &tenant_b,
+                V1MetaResponse {
+                    cubes: Some(vec![]),
+                    compiler_id: None,
+                },
+            )
+            .await;
+        assert!(cache.get(&tenant_b).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_meta_cache_revalidation() {
+        let cache =
+            MetaCache::with_limits(10, Duration::from_secs(3600), Duration::from_millis(10));
+        let tenant = auth_context(serde_json::json!({ "tenant": "a" }));
+
+        let value = cache.insert(&tenant, meta_response("1")).await;
+        assert!(cache.get(&tenant).await.is_some());
+
+        // Meta is requested again, the same compiler id keeps already built meta
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(cache.get(&tenant).await.is_none());
+        let same = cache.insert(&tenant, meta_response("1")).await;
+        assert!(Arc::ptr_eq(&same, &value));
+        assert!(cache.get(&tenant).await.is_some());
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let recompiled = cache.insert(&tenant, meta_response("2")).await;
+        assert!(!Arc::ptr_eq(&recompiled, &value));
+    }
+
+    #[tokio::test]
+    async fn test_meta_cache_eviction() {
+        let cache = MetaCache::with_limits(2, Duration::from_secs(3600), Duration::from_secs(3600));
+        let tenant_a = auth_context(serde_json::json!({ "tenant": "a" }));
+        let tenant_b = auth_context(serde_json::json!({ "tenant": "b" }));
+        let tenant_c = auth_context(serde_json::json!({ "tenant": "c" }));
+
+        cache.insert(&tenant_a, meta_response("1")).await;
+        cache.insert(&tenant_b, meta_response("1")).await;
+        assert!(cache.get(&tenant_a).await.is_some());
+
+        // tenant_b is the least recently used one
+        cache.insert(&tenant_c, meta_response("1")).await;
+        assert!(cache.get(&tenant_a).await.is_some());
+        assert!(cache.get(&tenant_b).await.is_none());
+        assert!(cache.get(&tenant_c).await.is_some());
+
+        let cache =
+            MetaCache::with_limits(10, Duration::from_millis(10), Duration::from_secs(3600));
+        cache.insert(&tenant_a, meta_response("1")).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // Idle buckets are dropped
+        cache.insert(&tenant_b, meta_response("1")).await;
+        assert!(cache.get(&tenant_a).await.is_none());
+        assert!(cache.get(&tenant_b).await.is_some());
+    }
 }
@@ -95,6 +95,12 @@ impl HllInstance {
         }
     }
 
+    /// Adds an element with the specified 64-bit hash. Switches to the dense representation, as
+    /// the sparse one keeps more bits of the hash than we have inputs for.
+    pub fn insert_hash(&mut self, hash: u64) {
+        self.ensure_dense().insert_hash(hash)
+    }
+
     /// Reads v1 of https://github.com/aggregateknowledge/hll-storage-spec and converts it to the
     /// Airlift representation of HLL. This means extra limitations on input and can produce
     /// different estimates due to implementation differences.
@@ -827,7 +833,6 @@ impl DenseHll {
         }
     }
 
-    #[allow(dead_code)]
     fn insert_hash(&mut self, hash: u64) {
         let index = compute_index(hash, self.index_bit_len);
         let value = compute_value(hash, self.index_bit_len);
@@ -1127,7 +1132,6 @@ fn index_bit_length(n: u32) -> Result<u8> {
     }
 }
 
-#[allow(dead_code)]
 fn compute_index(hash: u64, index_bit_len: u8) -> u32 {
     return (hash >> (64 - index_bit_len)) as u32;
 }
@@ -1136,7 +1140,6 @@ fn compute_value(hash: u64, index_bit_len: u8) -> u8 {
     return number_of_leading_zeros(hash, index_bit_len) + 1;
 }
 
-#[allow(dead_code)]
 fn number_of_leading_zeros(hash: u64, index_bit_len: u8) -> u8 {
     // place a 1 in the LSB to preserve the original number of leading zeros if the hash happens to be 0.
     let value = (hash << index_bit_len) | (1 << (index_bit_len - 1));
@@ -1559,6 +1562,27 @@ mod tests {
             assert_eq!(hll.cardinality(), 655);
         }
     }
+
+    #[test]
+    fn test_instance_insert() {
+        use crate::instance::HllInstance;
+        use std::hash::Hasher;
+        use twox_hash::XxHash64;
+
+        let mut hll = HllInstance::new(4096).unwrap();
+        for i in 0..100_000 {
+            let mut hasher = XxHash64::default();
+            hasher.write_i32(i % 10_000);
+            hll.insert_hash(hasher.finish());
+        }
+        assert_eq!(hll.num_buckets(), 4096);
+        let error = (hll.cardinality() as f64 - 10_000.) / 10_000.;
+        assert!(error.abs() < 0.05, "estimate: {}", hll.cardinality());
+
+        let restored = HllInstance::read(&hll.write()).unwrap();
+        assert_eq!(restored.cardinality(), hll.cardinality());
+    }
+
     // TODO: port tests for Sparse HLLs and HLLInstance.
 
     struct TestingHll {
@@ -20,8 +20,7 @@ use crate::instance::HllInstance;
 ///
 /// Port of the HyperLogLog from Airlift.
 /// You can deserialize sketches produced by Airlift by using `read()`.
-/// In fact, the code to add new elements has not been ported yet, so reading existing sketches is
-/// the only way to produce non-empty sets at this point.
+/// New elements are added by their hashes with `insert_hash()`.
 #[derive(Debug, Clone)]
 pub struct HllSketch {
     instance: HllInstance,
@@ -73,6 +72,11 @@ impl HllSketch {
         return self.instance.cardinality();
     }
 
+    /// Adds an element to the set. `hash` must be a well-distributed 64-bit hash of the element.
+    pub fn insert_hash(&mut self, hash: u64) {
+        self.instance.insert_hash(hash)
+    }
+
     /// Merges elements from `o` into the current sketch.
     /// Afterwards the current sketch estimates the size of the union.
     ///
@@ -56,6 +56,8 @@ pub fn sql_tests() -> Vec<(&'static str, TestFn)> {
         t("timestamp_seconds_frac", timestamp_seconds_frac),
         t("column_escaping", column_escaping),
         t("information_schema", information_schema),
+        t("system_query_history", system_query_history),
+        t("system_query_pools", system_query_pools),
         t("case_column_escaping", case_column_escaping),
         t("inner_column_escaping", inner_column_escaping),
         t("convert_tz", convert_tz),
@@ -164,6 +166,12 @@ pub fn sql_tests() -> Vec<(&'static str, TestFn)> {
             "unique_key_and_multi_measures_for_stream_table",
             unique_key_and_multi_measures_for_stream_table,
         ),
+        t("upsert", upsert),
+        t("insert_select", insert_select),
+        t("load_data_infile", load_data_infile),
+        t("export", export),
+        t("schema_lifecycle", schema_lifecycle),
+        t("analyze_table", analyze_table),
         t("divide_by_zero", divide_by_zero),
         t(
             "filter_multiple_in_for_decimal",
@@ -1092,6 +1100,88 @@ async fn information_schema(service: Box<dyn SqlClient>) {
     );
 }
 
+async fn system_query_history(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA foo").await.unwrap();
+    service
+        .exec_query("CREATE TABLE foo.numbers (n int)")
+        .await
+        .unwrap();
+    service
+        .exec_query("INSERT INTO foo.numbers (n) VALUES (1), (2)")
+        .await
+        .unwrap();
+
+    for _ in 0..2 {
+        service
+            .exec_query("SELECT n FROM foo.numbers ORDER BY n")
+            .await
+            .unwrap();
+    }
+    service
+        .exec_query("SELECT missing FROM foo.numbers")
+        .await
+        .unwrap_err();
+
+    let result = service
+        .exec_query(
+            "SELECT query, rows, cache_hit, error IS NULL FROM system.query_history ORDER BY id",
+        )
+        .await
+        .unwrap();
+    let select = TableValue::String("SELECT n FROM foo.numbers ORDER BY n".to_string());
+    assert_eq!(
+        to_rows(&result),
+        vec![
+            vec![
+                select.clone(),
+                TableValue::Int(2),
+                TableValue::Boolean(false),
+                TableValue::Boolean(true),
+            ],
+            vec![
+                select,
+                TableValue::Int(2),
+                TableValue::Boolean(true),
+                TableValue::Boolean(true),
+            ],
+            vec![
+                TableValue::String("SELECT missing FROM foo.numbers".to_string()),
+                TableValue::Null,
+                TableValue::Boolean(false),
+                TableValue::Boolean(false),
+            ],
+        ]
+    );
+}
+
+async fn system_query_pools(service: Box<dyn SqlClient>) {
+    let result = service
+        .exec_query(
+            "SELECT name, concurrency, running, queued FROM system.query_pools ORDER BY name",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&result),
+        vec![vec![
+            TableValue::String("default".to_string()),
+            TableValue::Int(0),
+            TableValue::Int(0),
+            TableValue::Int(0),
+        ]]
+    );
+
+    service
+        .exec_query("SET query_pool = 'default'")
+        .await
+        .unwrap();
+    let err = service
+        .exec_query("SET query_pool = 'missing'")
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("Unknown query pool"), "{}", err);
+}
+
 async fn case_column_escaping(service: Box<dyn SqlClient>) {
     service.exec_query("CREATE SCHEMA foo").await.unwrap();
 
@@ -4591,6 +4681,366 @@ async fn unique_key_and_multi_measures_for_stream_table(service: Box<dyn SqlClie
     );
 }
 
+async fn upsert(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query(
+            "CREATE TABLE s.counters (id int, name text, hits int, last_seen timestamp) \
+             UNIQUE KEY (id) AGGREGATIONS (sum(hits), max(last_seen))",
+        )
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.counters (id, name, hits, last_seen) VALUES \
+             (1, 'a', 1, '2021-01-01T00:00:00.000'), \
+             (2, 'b', 5, '2021-01-02T00:00:00.000')",
+        )
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.counters (id, name, hits, last_seen) VALUES \
+             (1, 'a2', 2, '2020-12-31T00:00:00.000'), \
+             (1, 'a3', 3, '2021-01-03T00:00:00.000') \
+             ON DUPLICATE KEY UPDATE name = VALUES(name), hits = hits + VALUES(hits), \
+             last_seen = GREATEST(last_seen, VALUES(last_seen))",
+        )
+        .await
+        .unwrap();
+
+    let r = service
+        .exec_query("SELECT id, name, hits, last_seen FROM s.counters ORDER BY id")
+        .await
+        .unwrap();
+    let ts = |s: &str| timestamp_from_string(s).unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[
+            (1, "a3", 6, ts("2021-01-03T00:00:00.000")),
+            (2, "b", 5, ts("2021-01-02T00:00:00.000")),
+        ])
+    );
+    let r = service
+        .exec_query("SELECT sum(hits) FROM s.counters")
+        .await
+        .unwrap();
+    assert_eq!(to_rows(&r), rows(&[(11)]));
+
+    let err = service
+        .exec_query(
+            "INSERT INTO s.counters (id, name, hits, last_seen) VALUES (1, 'a', 1, '2021-01-01T00:00:00.000') \
+             ON DUPLICATE KEY UPDATE name = VALUES(name), hits = VALUES(hits), last_seen = VALUES(last_seen)",
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        err.message.contains("should be hits = hits + VALUES(hits)"),
+        "{}",
+        err
+    );
+    let err = service
+        .exec_query(
+            "INSERT INTO s.counters (id, name, hits, last_seen) VALUES (1, 'a', 1, '2021-01-01T00:00:00.000') \
+             ON DUPLICATE KEY UPDATE hits = hits + VALUES(hits)",
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        err.message
+            .contains("Column name is missing in ON DUPLICATE KEY UPDATE"),
+        "{}",
+        err
+    );
+
+    service
+        .exec_query("CREATE TABLE s.plain (id int, hits int)")
+        .await
+        .unwrap();
+    let err = service
+        .exec_query(
+            "INSERT INTO s.plain (id, hits) VALUES (1, 1) \
+             ON DUPLICATE KEY UPDATE hits = hits + VALUES(hits)",
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("requires UNIQUE KEY"), "{}", err);
+
+    let err = service
+        .exec_query(
+            "CREATE TABLE s.bad (id int, name text) UNIQUE KEY (id) AGGREGATIONS (sum(name))",
+        )
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("can't be applied"), "{}", err);
+}
+
+async fn insert_select(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.a (id int, name text, amount int)")
+        .await
+        .unwrap();
+    service
+        .exec_query("CREATE TABLE s.b (id int, name text, amount int)")
+        .await
+        .unwrap();
+    service
+        .exec_query(
+            "INSERT INTO s.a (id, name, amount) VALUES (1, 'a', 10), (2, 'b', 20), (3, 'c', 30)",
+        )
+        .await
+        .unwrap();
+
+    service
+        .exec_query("INSERT INTO s.b SELECT id, name, amount FROM s.a WHERE id > 1")
+        .await
+        .unwrap();
+    service
+        .exec_query("INSERT INTO s.b (id, amount) SELECT 4, sum(amount) FROM s.a")
+        .await
+        .unwrap();
+    let r = service
+        .exec_query("SELECT id, name, amount FROM s.b ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        vec![
+            vec![
+                TableValue::Int(2),
+                TableValue::String("b".to_string()),
+                TableValue::Int(20)
+            ],
+            vec![
+                TableValue::Int(3),
+                TableValue::String("c".to_string()),
+                TableValue::Int(30)
+            ],
+            vec![TableValue::Int(4), TableValue::Null, TableValue::Int(60)],
+        ]
+    );
+
+    let err = service
+        .exec_query("INSERT INTO s.b SELECT id, name FROM s.a")
+        .await
+        .unwrap_err();
+    assert!(
+        err.message
+            .contains("expects 3 columns, but SELECT returns 2"),
+        "{}",
+        err
+    );
+}
+
+async fn load_data_infile(service: Box<dyn SqlClient>) {
+    let with_header = write_tmp_file(indoc! {"
+        amount,id
+        2,1
+        3,2
+    "})
+    .unwrap();
+    let no_header = write_tmp_file(indoc! {"
+        3,c
+    "})
+    .unwrap();
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.t (id int, name text, amount int)")
+        .await
+        .unwrap();
+    service
+        .exec_query(&format!(
+            "LOAD DATA INFILE '{}' INTO TABLE s.t IGNORE 1 LINES (id, amount)",
+            with_header.path().to_string_lossy()
+        ))
+        .await
+        .unwrap();
+    service
+        .exec_query(&format!(
+            "LOAD DATA INFILE '{}' INTO TABLE s.t (id, name)",
+            no_header.path().to_string_lossy()
+        ))
+        .await
+        .unwrap();
+    let r = service
+        .exec_query("SELECT id, name, amount FROM s.t ORDER BY id")
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        vec![
+            vec![TableValue::Int(1), TableValue::Null, TableValue::Int(2)],
+            vec![TableValue::Int(2), TableValue::Null, TableValue::Int(3)],
+            vec![
+                TableValue::Int(3),
+                TableValue::String("c".to_string()),
+                TableValue::Null
+            ],
+        ]
+    );
+
+    let err = service
+        .exec_query("LOAD DATA LOCAL INFILE 'data.csv' INTO TABLE s.t")
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("upload-temp-file"), "{}", err);
+}
+
+async fn export(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.t (id int, name text)")
+        .await
+        .unwrap();
+    service
+        .exec_query("INSERT INTO s.t (id, name) VALUES (1, 'a'), (2, 'b'), (3, NULL)")
+        .await
+        .unwrap();
+
+    for format in &["parquet", "csv"] {
+        let r = service
+            .exec_query(&format!(
+                "EXPORT (SELECT id, name FROM s.t WHERE id > 1) TO 'exports/t' \
+                 WITH (format = '{}', max_file_size = 1000000)",
+                format
+            ))
+            .await
+            .unwrap();
+        assert_eq!(r.get_columns()[0].get_name(), "file");
+        assert!(!r.get_rows().is_empty());
+        for row in r.get_rows() {
+            match &row.values()[0] {
+                TableValue::String(f) => {
+                    assert!(f.starts_with("exports/t/"), "{}", f);
+                    assert!(f.ends_with(&format!(".{}", format)), "{}", f);
+                }
+                v => panic!("not a file name: {:?}", v),
+            }
+        }
+    }
+
+    let err = service
+        .exec_query("EXPORT (SELECT id FROM s.t) TO 'exports/t' WITH (format = 'json')")
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("Bad export format"), "{}", err);
+    let err = service
+        .exec_query("EXPORT (SELECT id FROM s.t) TO 's3://other-bucket/exports'")
+        .await
+        .unwrap_err();
+    assert!(
+        err.message
+            .contains("should point to the Cube Store bucket"),
+        "{}",
+        err
+    );
+}
+
+async fn schema_lifecycle(service: Box<dyn SqlClient>) {
+    service
+        .exec_query("CREATE SCHEMA pre WITH (keep_versions = 2, ttl = '7 days')")
+        .await
+        .unwrap();
+    service.exec_query("CREATE SCHEMA other").await.unwrap();
+    for t in &[
+        "pre.orders_main_aaa_bbb_1",
+        "pre.orders_main_ccc_bbb_2",
+        "pre.orders_main_ddd_bbb_3",
+        "pre.users_main_aaa_bbb_1",
+        "other.orders_main_aaa_bbb_1",
+        "other.orders_main_ccc_bbb_2",
+        "other.orders_main_ddd_bbb_3",
+    ] {
+        service
+            .exec_query(&format!("CREATE TABLE {} (id int)", t))
+            .await
+            .unwrap();
+    }
+
+    let r = service
+        .exec_query(
+            "SELECT table_schema, table_name, reason FROM system.gc_candidates \
+             ORDER BY table_schema, table_name",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[("pre", "orders_main_aaa_bbb_1", "keep_versions")])
+    );
+
+    // Options apply to existing schemas, statements without them keep the policy.
+    service
+        .exec_query("CREATE SCHEMA IF NOT EXISTS other WITH (keep_versions = 1)")
+        .await
+        .unwrap();
+    service
+        .exec_query("CREATE SCHEMA IF NOT EXISTS other")
+        .await
+        .unwrap();
+    let r = service
+        .exec_query(
+            "SELECT table_schema, table_name, reason FROM system.gc_candidates \
+             ORDER BY table_schema, table_name",
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        to_rows(&r),
+        rows(&[
+            ("other", "orders_main_aaa_bbb_1", "keep_versions"),
+            ("other", "orders_main_ccc_bbb_2", "keep_versions"),
+            ("pre", "orders_main_aaa_bbb_1", "keep_versions"),
+        ])
+    );
+
+    let err = service
+        .exec_query("CREATE SCHEMA bad WITH (ttl = 'forever')")
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("Can't parse ttl"), "{}", err);
+    let err = service
+        .exec_query("CREATE SCHEMA bad WITH (keep_versions = 0)")
+        .await
+        .unwrap_err();
+    assert!(err.message.contains("keep_versions"), "{}", err);
+}
+
+async fn analyze_table(service: Box<dyn SqlClient>) {
+    service.exec_query("CREATE SCHEMA s").await.unwrap();
+    service
+        .exec_query("CREATE TABLE s.Data(a int, b int)")
+        .await
+        .unwrap();
+    service
+        .exec_query("CREATE INDEX by_b ON s.Data(b, a)")
+        .await
+        .unwrap();
+    let values = (0..100).map(|i| format!("({}, {})", i, i % 10)).join(", ");
+    service
+        .exec_query(&format!("INSERT INTO s.Data(a, b) VALUES {}", values))
+        .await
+        .unwrap();
+
+    // Both indexes have the same score, so the default one is used until the table is analyzed.
+    let query = "SELECT a, b FROM s.Data WHERE a >= 0 AND b = 5";
+    let p = service.plan_query(query).await.unwrap();
+    let plan = pp_phys_plan(p.worker.as_ref());
+    assert!(plan.contains("Scan, index: default:"), "{}", plan);
+
+    service.exec_query("ANALYZE TABLE s.Data").await.unwrap();
+    let p = service.plan_query(query).await.unwrap();
+    let plan = pp_phys_plan(p.worker.as_ref());
+    assert!(plan.contains("Scan, index: by_b:"), "{}", plan);
+
+    let r = service.exec_query(query).await.unwrap();
+    assert_eq!(to_rows(&r).len(), 10);
+
+    let err = service.exec_query("ANALYZE TABLE Data").await.unwrap_err();
+    assert!(err.message.contains("Schema's name"), "{}", err);
+}
+
 async fn divide_by_zero(service: Box<dyn SqlClient>) {
     service.exec_query("CREATE SCHEMA s").await.unwrap();
     service
@@ -2,7 +2,7 @@
 //! The convention is to prefix all metrics with `cs.` (short for CubeStore).
 
 use crate::util::metrics;
-use crate::util::metrics::{Counter, Histogram};
+use crate::util::metrics::{Counter, Gauge, Histogram};
 
 /// The number of process startups.
 pub static STARTUPS: Counter = metrics::counter("cs.startup");
@@ -13,3 +13,16 @@ pub static DATA_QUERY_TIME_MS: Histogram = metrics::histogram("cs.sql.query.data
 /// Incoming SQL queries that only read metadata or do trivial computations.
 pub static META_QUERIES: Counter = metrics::counter("cs.sql.query.meta");
 pub static META_QUERY_TIME_MS: Histogram = metrics::histogram("cs.sql.query.meta.ms");
+
+/// Jobs waiting for execution, reported with the `job_type` tag.
+pub static JOBS_QUEUE_DEPTH: Gauge = metrics::gauge("cs.jobs.queue");
+/// Bytes of partition and chunk files written by compactions.
+pub static COMPACTION_BYTES: Counter = metrics::counter("cs.compaction.bytes");
+/// Latency of remote file system calls, reported with the `operation` tag.
+pub static REMOTE_FS_OPERATION_TIME_MS: Histogram = metrics::histogram("cs.remote_fs.operation.ms");
+/// Select worker processes which are busy with queries and the total number of them.
+pub static WORKER_POOL_BUSY: Gauge = metrics::gauge("cs.worker_pool.busy");
+pub static WORKER_POOL_SIZE: Gauge = metrics::gauge("cs.worker_pool.size");
+/// Lookups in the SQL result cache.
+pub static SQL_CACHE_HITS: Counter = metrics::counter("cs.sql.cache.hit");
+pub static SQL_CACHE_MISSES: Counter = metrics::counter("cs.sql.cache.miss");
@@ -4,6 +4,7 @@ use cubestore::http::status::serve_status_probes;
 use cubestore::telemetry::{init_agent_sender, track_event};
 use cubestore::util::logger::init_cube_logger;
 use cubestore::util::metrics::init_metrics;
+use cubestore::util::otel::{init_otel_tracing, shutdown_otel_tracing};
 use cubestore::util::{metrics, spawn_malloc_trim_loop};
 use datafusion::cube_ext;
 use log::debug;
@@ -57,6 +58,9 @@ fn main() {
     }
     let runtime = tokio_builder.build().unwrap();
     runtime.block_on(async move {
+        if let Err(e) = init_otel_tracing("cubestore") {
+            log::error!("Can't init tracing: {}", e);
+        }
         init_agent_sender().await;
 
         validate_config(config.config_obj().as_ref()).report_and_abort_on_errors();
@@ -72,6 +76,7 @@ fn main() {
         stop_on_ctrl_c(&services).await;
         services.wait_processing_loops().await.unwrap();
     });
+    shutdown_otel_tracing();
 }
 
 async fn stop_on_ctrl_c(s: &CubeServices) {
@@ -1,6 +1,8 @@
+use crate::export::ExportOptions;
 use crate::metastore::{MetaStoreRpcMethodCall, MetaStoreRpcMethodResult};
 use crate::queryplanner::query_executor::SerializedRecordBatchStream;
 use crate::queryplanner::serialized_plan::SerializedPlan;
+use crate::util::otel::TraceContext;
 use crate::CubeError;
 use arrow::datatypes::SchemaRef;
 use serde::{Deserialize, Serialize};
@@ -21,6 +23,24 @@ pub enum NetworkMessage {
     ExplainAnalyze(SerializedPlan),
     ExplainAnalyzeResult(Result<String, CubeError>),
 
+    /// Partial select on the worker which results are written as inactive chunks of the target
+    /// table. Returns the number of inserted rows and the chunks to activate.
+    InsertSelect {
+        plan: SerializedPlan,
+        table_id: u64,
+        columns: Vec<String>,
+    },
+    InsertSelectResult(Result<(u64, Vec<(u64, Option<u64>)>), CubeError>),
+
+    /// Partial select on the worker which results are written into the remote storage.
+    /// Returns remote paths of the written files.
+    ExportSelect {
+        plan: SerializedPlan,
+        file_prefix: String,
+        options: ExportOptions,
+    },
+    ExportSelectResult(Result<Vec<String>, CubeError>),
+
     /// Select that sends results in batches. The immediate response is [SelectResultSchema],
     /// followed by a stream of [SelectResultBatch].
     SelectStart(SerializedPlan),
@@ -52,7 +72,7 @@ pub enum NetworkMessage {
 
 const MAGIC: u32 = 94107;
 
-const NETWORK_MESSAGE_VERSION: u32 = 1;
+const NETWORK_MESSAGE_VERSION: u32 = 2;
 
 impl NetworkMessage {
     pub fn is_streaming_request(&self) -> bool {
@@ -76,6 +96,9 @@ impl NetworkMessage {
     }
 
     async fn send_impl(&self, socket: &mut TcpStream) -> Result<(), std::io::Error> {
+        let mut ser = flexbuffers::FlexbufferSerializer::new();
+        TraceContext::current().serialize(&mut ser).unwrap();
+        let trace_context_buffer = ser.take_buffer();
         let mut ser = flexbuffers::FlexbufferSerializer::new();
         self.serialize(&mut ser).unwrap();
         let message_buffer = ser.take_buffer();
@@ -89,6 +112,8 @@ impl NetworkMessage {
         // magic number
         socket.write_u32(MAGIC).await?;
         socket.write_u32(NETWORK_MESSAGE_VERSION).await?;
+        socket.write_u32(trace_context_buffer.len() as u32).await?;
+        socket.write_all(trace_context_buffer.as_slice()).await?;
         socket.write_u64(len).await?;
         socket.write_all(message_buffer.as_slice()).await?;
         Ok(())
@@ -101,8 +126,26 @@ impl NetworkMessage {
         }
     }
 
+    /// Receives a message along with the trace context of the sender.
+    pub async fn receive_with_trace_context(
+        socket: &mut TcpStream,
+    ) -> Result<(Self, TraceContext), CubeError> {
+        match Self::maybe_receive_with_trace_context(socket).await? {
+            Some(m) => Ok(m),
+            None => Err(CubeError::user("Connection closed unexpectedly. Please check your worker and meta connection environment variables.".to_string())),
+        }
+    }
+
     /// Either receives a message or waits for the connection to close.
     pub async fn maybe_receive(socket: &mut TcpStream) -> Result<Option<Self>, CubeError> {
+        Ok(Self::maybe_receive_with_trace_context(socket)
+            .await?
+            .map(|(m, _)| m))
+    }
+
+    pub async fn maybe_receive_with_trace_context(
+        socket: &mut TcpStream,
+    ) -> Result<Option<(Self, TraceContext)>, CubeError> {
         let magic = socket.read_u32().await;
         if let Err(e) = &magic {
             // TODO: corner case with `0 < n < 8` read bytes.
@@ -118,6 +161,20 @@ impl NetworkMessage {
         if ver != NETWORK_MESSAGE_VERSION {
             return Err(CubeError::user(format!("Network protocol version mismatch. Expected {} but received {}. It seems multiple versions of Cube Store images running within the same cluster.", NETWORK_MESSAGE_VERSION, ver)));
         }
+        let trace_context_len = socket.read_u32().await? as u64;
+        if MAX_TRACE_CONTEXT_LEN < trace_context_len {
+            return Err(CubeError::internal(format!(
+                "invalid trace context: declared length is too large, {} bytes",
+                trace_context_len
+            )));
+        }
+        let mut buffer = Vec::with_capacity(trace_context_len as usize);
+        (&mut *socket)
+            .take(trace_context_len)
+            .read_to_end(&mut buffer)
+            .await?;
+        let trace_context = TraceContext::deserialize(flexbuffers::Reader::get_root(&buffer)?)?;
+
         let len = socket.read_u64().await?;
 
         if MAX_NETWORK_MSG_LEN < len {
@@ -137,9 +194,10 @@ impl NetworkMessage {
         let mut buffer = Vec::with_capacity(len as usize);
         socket.take(len).read_to_end(&mut buffer).await?;
         let r = flexbuffers::Reader::get_root(&buffer)?;
-        Ok(Some(Self::deserialize(r)?))
+        Ok(Some((Self::deserialize(r)?, trace_context)))
     }
 }
 
 // Anything larger is considered to be an invalid message.
 const MAX_NETWORK_MSG_LEN: u64 = 20 * 1024 * 1024 * 1024; // 20GiB
+const MAX_TRACE_CONTEXT_LEN: u64 = 64 * 1024;
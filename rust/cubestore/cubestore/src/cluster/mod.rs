@@ -14,7 +14,9 @@ use crate::config::injection::{DIService, Injector};
 use crate::config::{is_router, WorkerServices};
 #[allow(unused_imports)]
 use crate::config::{Config, ConfigObj};
-use crate::import::ImportService;
+use crate::export::{export_batches, ExportOptions};
+use crate::import::limits::ConcurrencyLimits;
+use crate::import::{ImportService, Ingestion};
 use crate::metastore::chunks::chunk_file_name;
 use crate::metastore::job::{Job, JobStatus, JobType};
 use crate::metastore::table::Table;
@@ -26,6 +28,7 @@ use crate::metastore::{
     MetaStoreRpcClientTransport, MetaStoreRpcMethodCall, MetaStoreRpcMethodResult,
     MetaStoreRpcServer,
 };
+use crate::queryplanner::planning::get_worker_plan;
 use crate::queryplanner::query_executor::{QueryExecutor, SerializedRecordBatchStream};
 use crate::queryplanner::serialized_plan::SerializedPlan;
 use crate::remotefs::RemoteFs;
@@ -39,11 +42,12 @@ use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
 use core::mem;
 use datafusion::cube_ext;
-use datafusion::physical_plan::{RecordBatchStream, SendableRecordBatchStream};
+use datafusion::physical_plan::merge::MergeExec;
+use datafusion::physical_plan::{ExecutionPlan, RecordBatchStream, SendableRecordBatchStream};
 use flatbuffers::bitflags::_core::pin::Pin;
 use futures::future::join_all;
 use futures::task::{Context, Poll};
-use futures::{Future, Stream};
+use futures::{Future, Stream, StreamExt};
 use futures_timer::Delay;
 use itertools::Itertools;
 use log::{debug, error, info, warn};
@@ -94,6 +98,27 @@ pub trait Cluster: DIService + Send + Sync {
         plan: SerializedPlan,
     ) -> Result<String, CubeError>;
 
+    /// Runs select on a single worker node and writes its results as chunks of `table_id`
+    /// on that worker. Returns the number of inserted rows and ids with file sizes of the written
+    /// chunks. Chunks are left inactive, the caller activates them once all workers succeed.
+    async fn run_insert_select(
+        &self,
+        node_name: &str,
+        plan: SerializedPlan,
+        table_id: u64,
+        columns: Vec<String>,
+    ) -> Result<(u64, Vec<(u64, Option<u64>)>), CubeError>;
+
+    /// Runs select on a single worker node and writes its results into the remote storage as
+    /// files starting with `file_prefix`. Returns remote paths of the written files.
+    async fn run_export_select(
+        &self,
+        node_name: &str,
+        plan: SerializedPlan,
+        file_prefix: String,
+        options: ExportOptions,
+    ) -> Result<Vec<String>, CubeError>;
+
     /// Like [run_select], but streams results as they are requested.
     /// This allows to send only a limited number of results, if the caller does not need all.
     async fn run_select_stream(
@@ -334,6 +359,54 @@ impl Cluster for ClusterImpl {
         }
     }
 
+    #[instrument(level = "trace", skip(self, plan, columns))]
+    async fn run_insert_select(
+        &self,
+        node_name: &str,
+        plan: SerializedPlan,
+        table_id: u64,
+        columns: Vec<String>,
+    ) -> Result<(u64, Vec<(u64, Option<u64>)>), CubeError> {
+        let response = self
+            .send_or_process_locally(
+                node_name,
+                NetworkMessage::InsertSelect {
+                    plan,
+                    table_id,
+                    columns,
+                },
+            )
+            .await?;
+        match response {
+            NetworkMessage::InsertSelectResult(r) => r,
+            _ => panic!("unexpected result for insert select"),
+        }
+    }
+
+    #[instrument(level = "trace", skip(self, plan, options))]
+    async fn run_export_select(
+        &self,
+        node_name: &str,
+        plan: SerializedPlan,
+        file_prefix: String,
+        options: ExportOptions,
+    ) -> Result<Vec<String>, CubeError> {
+        let response = self
+            .send_or_process_locally(
+                node_name,
+                NetworkMessage::ExportSelect {
+                    plan,
+                    file_prefix,
+                    options,
+                },
+            )
+            .await?;
+        match response {
+            NetworkMessage::ExportSelectResult(r) => r,
+            _ => panic!("unexpected result for export select"),
+        }
+    }
+
     async fn run_select_stream(
         &self,
         node_name: &str,
@@ -497,6 +570,26 @@ impl Cluster for ClusterImpl {
                 let res = self.run_local_explain_analyze_worker(plan).await;
                 NetworkMessage::ExplainAnalyzeResult(res)
             }
+            NetworkMessage::InsertSelect {
+                plan,
+                table_id,
+                columns,
+            } => {
+                let res = self
+                    .run_local_insert_select_worker(plan, table_id, columns)
+                    .await;
+                NetworkMessage::InsertSelectResult(res)
+            }
+            NetworkMessage::ExportSelect {
+                plan,
+                file_prefix,
+                options,
+            } => {
+                let res = self
+                    .run_local_export_select_worker(plan, file_prefix, options)
+                    .await;
+                NetworkMessage::ExportSelectResult(res)
+            }
             NetworkMessage::WarmupDownload(remote_path, expected_file_size) => {
                 let res = self
                     .remote_fs
@@ -506,7 +599,9 @@ impl Cluster for ClusterImpl {
             }
             NetworkMessage::SelectResult(_)
             | NetworkMessage::WarmupDownloadResult(_)
-            | NetworkMessage::ExplainAnalyzeResult(_) => {
+            | NetworkMessage::ExplainAnalyzeResult(_)
+            | NetworkMessage::InsertSelectResult(_)
+            | NetworkMessage::ExportSelectResult(_) => {
                 panic!("result sent to worker");
             }
             NetworkMessage::AddMemoryChunk { chunk_id, data } => {
@@ -898,6 +993,17 @@ impl JobRunner {
                     Self::fail_job_row_key(job)
                 }
             }
+            JobType::AnalyzePartition => {
+                if let RowKey::Table(TableId::Partitions, partition_id) = job.row_reference() {
+                    let compaction_service = self.compaction_service.clone();
+                    let partition_id = *partition_id;
+                    Ok(cube_ext::spawn(async move {
+                        compaction_service.analyze(partition_id).await
+                    }))
+                } else {
+                    Self::fail_job_row_key(job)
+                }
+            }
         }
     }
 
@@ -1041,24 +1147,27 @@ impl ClusterImpl {
             cluster.clone(),
             on_socket_bound,
             async move |c, mut socket| {
-                let m = match NetworkMessage::receive(&mut socket).await {
-                    Ok(m) => m,
-                    Err(e) => {
-                        error!("Network error: {}", e);
-                        return;
-                    }
-                };
+                let (m, trace_context) =
+                    match NetworkMessage::receive_with_trace_context(&mut socket).await {
+                        Ok(m) => m,
+                        Err(e) => {
+                            error!("Network error: {}", e);
+                            return;
+                        }
+                    };
+                let span = tracing::span!(tracing::Level::TRACE, "process_message_on_worker");
+                trace_context.attach(&span);
 
                 if !m.is_streaming_request() {
-                    let response = c.process_message_on_worker(m).await;
+                    let response = c.process_message_on_worker(m).instrument(span).await;
                     if let Err(e) = response.send(&mut socket).await {
                         error!("Network error: {}", e);
                         return;
                     }
                 } else {
-                    let mut p = c.start_stream_on_worker(m).await;
+                    let mut p = c.start_stream_on_worker(m).instrument(span.clone()).await;
                     loop {
-                        let (response, finished) = p.next().await;
+                        let (response, finished) = p.next().instrument(span.clone()).await;
                         match response.maybe_send(&mut socket).await {
                             // All ok, continue streaming.
                             Ok(true) => {}
@@ -1157,10 +1266,14 @@ impl ClusterImpl {
                 let cluster = c.clone();
                 let process_fn = process_fn.clone();
                 async move {
-                    let request = NetworkMessage::receive(&mut socket).await;
+                    let request = NetworkMessage::receive_with_trace_context(&mut socket).await;
                     let response;
                     match request {
-                        Ok(m) => response = process_fn(cluster.clone(), m).await,
+                        Ok((m, trace_context)) => {
+                            let span = tracing::span!(tracing::Level::TRACE, "process_on_port");
+                            trace_context.attach(&span);
+                            response = process_fn(cluster.clone(), m).instrument(span).await
+                        }
                         Err(e) => {
                             error!("Network error: {}", e);
                             return;
@@ -1176,6 +1289,78 @@ impl ClusterImpl {
         .await
     }
 
+    #[instrument(level = "trace", skip(self, plan_node, columns))]
+    async fn run_local_insert_select_worker(
+        &self,
+        plan_node: SerializedPlan,
+        table_id: u64,
+        columns: Vec<String>,
+    ) -> Result<(u64, Vec<(u64, Option<u64>)>), CubeError> {
+        let mut batches = self.run_local_select_worker_stream(plan_node).await?;
+        let table = self.meta_store.get_table_by_id(table_id).await?;
+        let target_columns = columns
+            .iter()
+            .map(|name| {
+                table
+                    .get_row()
+                    .get_columns()
+                    .iter()
+                    .find(|c| c.get_name() == name)
+                    .ok_or_else(|| {
+                        CubeError::internal(format!(
+                            "Column '{}' not found in table #{}",
+                            name, table_id
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let injector = self.injector.upgrade().unwrap();
+        let mut ingestion = Ingestion::new(
+            self.meta_store.clone(),
+            injector.get_service_typed::<dyn ChunkDataStore>().await,
+            injector.get_service_typed::<ConcurrencyLimits>().await,
+            table.clone(),
+        );
+        let mut rows = 0;
+        while let Some(batch) = batches.next().await {
+            let batch = batch?;
+            if batch.num_rows() == 0 {
+                continue;
+            }
+            if batch.num_columns() != target_columns.len() {
+                return Err(CubeError::internal(format!(
+                    "Insert select returned {} columns but {} expected",
+                    batch.num_columns(),
+                    target_columns.len()
+                )));
+            }
+            rows += batch.num_rows() as u64;
+            ingestion
+                .queue_columns(&target_columns, batch.columns().to_vec())
+                .await?;
+        }
+        Ok((rows, ingestion.wait_uploads().await?))
+    }
+
+    #[instrument(level = "trace", skip(self, plan_node, options))]
+    async fn run_local_export_select_worker(
+        &self,
+        plan_node: SerializedPlan,
+        file_prefix: String,
+        options: ExportOptions,
+    ) -> Result<Vec<String>, CubeError> {
+        let batches = self.run_local_select_worker_stream(plan_node).await?;
+        export_batches(
+            self.remote_fs.clone(),
+            batches.schema(),
+            batches,
+            &file_prefix,
+            &options,
+        )
+        .await
+    }
+
     #[instrument(level = "trace", skip(self, plan_node))]
     async fn run_local_select_worker(
         &self,
@@ -1189,31 +1374,7 @@ impl ClusterImpl {
             warn!("Warmup download for select ({:?})", warmup);
         }
 
-        let chunk_store = self
-            .injector
-            .upgrade()
-            .unwrap()
-            .get_service_typed::<dyn ChunkDataStore>()
-            .await;
-
-        let in_memory_chunks_to_load = plan_node.in_memory_chunks_to_load();
-        let in_memory_chunks_futures = in_memory_chunks_to_load
-            .iter()
-            .map(|c| chunk_store.get_chunk_columns(c.clone()))
-            .collect::<Vec<_>>();
-
-        let chunk_id_to_record_batches = in_memory_chunks_to_load
-            .clone()
-            .into_iter()
-            .map(|c| c.get_id())
-            .zip(
-                join_all(in_memory_chunks_futures)
-                    .await
-                    .into_iter()
-                    .collect::<Result<Vec<_>, _>>()?
-                    .into_iter(),
-            )
-            .collect::<HashMap<_, _>>();
+        let chunk_id_to_record_batches = self.load_in_memory_chunks(&plan_node).await?;
 
         let mut res = None;
         #[cfg(not(target_os = "windows"))]
@@ -1266,6 +1427,68 @@ impl ClusterImpl {
         res.unwrap()
     }
 
+    /// Runs the worker part of the plan in this process and streams its results. Unlike
+    /// [ClusterImpl::run_local_select_worker], results are not collected first, so this is used
+    /// when the worker consumes them itself, e.g. to insert or export them.
+    async fn run_local_select_worker_stream(
+        &self,
+        plan_node: SerializedPlan,
+    ) -> Result<SendableRecordBatchStream, CubeError> {
+        let remote_to_local_names = self.warmup_select_worker_files(&plan_node).await?;
+        let chunk_id_to_record_batches = self.load_in_memory_chunks(&plan_node).await?;
+        let (physical_plan, _) = self
+            .query_executor
+            .worker_plan(plan_node, remote_to_local_names, chunk_id_to_record_batches)
+            .await?;
+        let worker_plan = match get_worker_plan(&physical_plan) {
+            Some((p, _)) => p,
+            None => {
+                error!("No worker marker in physical plan: {:?}", physical_plan);
+                return Err(CubeError::internal(
+                    "Invalid physical plan on worker".to_string(),
+                ));
+            }
+        };
+        let worker_plan: Arc<dyn ExecutionPlan> =
+            if worker_plan.output_partitioning().partition_count() == 1 {
+                worker_plan
+            } else {
+                Arc::new(MergeExec::new(worker_plan))
+            };
+        Ok(worker_plan.execute(0).await?)
+    }
+
+    async fn load_in_memory_chunks(
+        &self,
+        plan_node: &SerializedPlan,
+    ) -> Result<HashMap<u64, Vec<RecordBatch>>, CubeError> {
+        let chunk_store = self
+            .injector
+            .upgrade()
+            .unwrap()
+            .get_service_typed::<dyn ChunkDataStore>()
+            .await;
+
+        let in_memory_chunks_to_load = plan_node.in_memory_chunks_to_load();
+        let in_memory_chunks_futures = in_memory_chunks_to_load
+            .iter()
+            .map(|c| chunk_store.get_chunk_columns(c.clone()))
+            .collect::<Vec<_>>();
+
+        Ok(in_memory_chunks_to_load
+            .clone()
+            .into_iter()
+            .map(|c| c.get_id())
+            .zip(
+                join_all(in_memory_chunks_futures)
+                    .await
+                    .into_iter()
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter(),
+            )
+            .collect::<HashMap<_, _>>())
+    }
+
     async fn run_local_explain_analyze_worker(
         &self,
         plan_node: SerializedPlan,
@@ -2,6 +2,7 @@ use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::panic;
 use std::process::Child;
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -19,7 +20,9 @@ use tokio::sync::{oneshot, watch, Notify, RwLock};
 use tracing::{instrument, Instrument};
 use tracing_futures::WithSubscriber;
 
+use crate::app_metrics;
 use crate::config::{Config, WorkerServices};
+use crate::util::otel::{init_otel_tracing, shutdown_otel_tracing, TraceContext};
 use crate::util::respawn::respawn;
 use crate::CubeError;
 use datafusion::cube_ext;
@@ -66,6 +69,8 @@ impl<
         let (stopped_tx, stopped_rx) = watch::channel(false);
 
         let mut workers = Vec::new();
+        let busy = Arc::new(AtomicI64::new(0));
+        app_metrics::WORKER_POOL_SIZE.report(num as i64);
 
         for i in 1..=num {
             let process = Arc::new(WorkerProcess::<T, R, P>::new(
@@ -73,6 +78,7 @@ impl<
                 queue.clone(),
                 timeout.clone(),
                 stopped_rx.clone(),
+                busy.clone(),
             ));
             workers.push(process.clone());
         }
@@ -122,6 +128,8 @@ pub struct WorkerProcess<
     processor: PhantomData<P>,
     stopped_rx: RwLock<watch::Receiver<bool>>,
     finished_notify: Arc<Notify>,
+    /// Number of processes of the pool which are busy with messages
+    busy: Arc<AtomicI64>,
 }
 
 impl<
@@ -135,6 +143,7 @@ impl<
         queue: Arc<unlimited::Queue<Message<T, R>>>,
         timeout: Duration,
         stopped_rx: watch::Receiver<bool>,
+        busy: Arc<AtomicI64>,
     ) -> Self {
         WorkerProcess {
             name,
@@ -143,6 +152,7 @@ impl<
             stopped_rx: RwLock::new(stopped_rx),
             finished_notify: Arc::new(Notify::new()),
             processor: PhantomData,
+            busy,
         }
     }
 
@@ -172,6 +182,8 @@ impl<
                                 message
                             }
                         };
+                        app_metrics::WORKER_POOL_BUSY
+                            .report(self.busy.fetch_add(1, Ordering::SeqCst) + 1);
                         let process_message_res_timeout = tokio::time::timeout(
                             self.timeout,
                             self.process_message(message, args_tx, res_rx),
@@ -179,6 +191,8 @@ impl<
                         .instrument(span)
                         .with_subscriber(dispatcher)
                         .await;
+                        app_metrics::WORKER_POOL_BUSY
+                            .report(self.busy.fetch_sub(1, Ordering::SeqCst) - 1);
                         let process_message_res = match process_message_res_timeout {
                             Ok(r) => r,
                             Err(e) => Err(CubeError::internal(format!(
@@ -224,17 +238,31 @@ impl<
     async fn process_message(
         &self,
         message: T,
-        args_tx: IpcSender<T>,
+        args_tx: IpcSender<(T, TraceContext)>,
         res_rx: IpcReceiver<Result<R, CubeError>>,
-    ) -> Result<(R, IpcSender<T>, IpcReceiver<Result<R, CubeError>>), CubeError> {
-        args_tx.send(message)?;
+    ) -> Result<
+        (
+            R,
+            IpcSender<(T, TraceContext)>,
+            IpcReceiver<Result<R, CubeError>>,
+        ),
+        CubeError,
+    > {
+        args_tx.send((message, TraceContext::current()))?;
         let (res, res_rx) = cube_ext::spawn_blocking(move || (res_rx.recv(), res_rx)).await?;
         Ok((res??, args_tx, res_rx))
     }
 
     fn spawn_process(
         &self,
-    ) -> Result<(IpcSender<T>, IpcReceiver<Result<R, CubeError>>, Child), CubeError> {
+    ) -> Result<
+        (
+            IpcSender<(T, TraceContext)>,
+            IpcReceiver<Result<R, CubeError>>,
+            Child,
+        ),
+        CubeError,
+    > {
         let (args_tx, args_rx) = ipc::channel()?;
         let (res_tx, res_rx) = ipc::channel()?;
 
@@ -261,7 +289,7 @@ impl<
 
 #[derive(Serialize, Deserialize)]
 pub struct WorkerProcessArgs<T, R, P: ?Sized> {
-    args: IpcReceiver<T>,
+    args: IpcReceiver<(T, TraceContext)>,
     results: IpcSender<Result<R, CubeError>>,
     processor: PhantomData<P>,
 }
@@ -280,7 +308,10 @@ where
         tokio_builder.worker_threads(var.parse().unwrap());
     }
     let runtime = tokio_builder.build().unwrap();
-    runtime.block_on(async move {
+    let exit_code = runtime.block_on(async move {
+        if let Err(e) = init_otel_tracing("cubestore-worker") {
+            error!("Can't init tracing: {}", e);
+        }
         let config = Config::default();
         config.configure_injector().await;
         let services = config.worker_services().await;
@@ -288,12 +319,17 @@ where
         loop {
             let res = rx.recv();
             match res {
-                Ok(args) => {
-                    let result =
-                        match async_try_with_catch_unwind(P::process(&services, args)).await {
-                            Ok(result) => result,
-                            Err(panic) => Err(CubeError::from(panic)),
-                        };
+                Ok((args, trace_context)) => {
+                    let span = tracing::span!(tracing::Level::TRACE, "worker_process");
+                    trace_context.attach(&span);
+                    let result = match async_try_with_catch_unwind(
+                        P::process(&services, args).instrument(span),
+                    )
+                    .await
+                    {
+                        Ok(result) => result,
+                        Err(panic) => Err(CubeError::from(panic)),
+                    };
                     let send_res = tx.send(result);
                     if let Err(e) = send_res {
                         error!("Worker message send error: {:?}", e);
@@ -306,7 +342,9 @@ where
                 }
             }
         }
-    })
+    });
+    shutdown_otel_tracing();
+    exit_code
 }
 
 #[cfg(test)]
@@ -20,7 +20,10 @@ use crate::remotefs::minio::MINIORemoteFs;
 use crate::remotefs::queue::QueueRemoteFs;
 use crate::remotefs::s3::S3RemoteFs;
 use crate::remotefs::{LocalDirRemoteFs, RemoteFs};
+use crate::scheduler::lifecycle::TableAccessTracker;
 use crate::scheduler::SchedulerImpl;
+use crate::sql::query_history::QueryHistory;
+use crate::sql::query_pools::{QueryPoolConfig, QueryPools};
 use crate::sql::{SqlService, SqlServiceImpl};
 use crate::store::compaction::{CompactionService, CompactionServiceImpl};
 use crate::store::{ChunkDataStore, ChunkStore, WALDataStore, WALStore};
@@ -36,6 +39,7 @@ use log::{debug, error};
 use mockall::automock;
 use rocksdb::{Options, DB};
 use simple_logger::SimpleLogger;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::future::Future;
 use std::path::PathBuf;
@@ -327,6 +331,8 @@ pub trait ConfigObj: DIService {
 
     fn data_dir(&self) -> &PathBuf;
 
+    fn store_provider(&self) -> &FileStoreProvider;
+
     fn connection_timeout(&self) -> u64;
 
     fn server_name(&self) -> &String;
@@ -344,6 +350,20 @@ pub trait ConfigObj: DIService {
     fn max_cached_queries(&self) -> usize;
 
     fn dump_dir(&self) -> &Option<PathBuf>;
+
+    fn query_history_size(&self) -> usize;
+
+    fn slow_query_log_path(&self) -> &Option<PathBuf>;
+
+    fn slow_query_threshold_ms(&self) -> u64;
+
+    fn query_pools(&self) -> &Vec<QueryPoolConfig>;
+
+    fn query_pool_users(&self) -> &HashMap<String, String>;
+
+    fn max_concurrent_queries(&self) -> usize;
+
+    fn query_memory_limit(&self) -> u64;
 }
 
 #[derive(Debug, Clone)]
@@ -383,6 +403,13 @@ pub struct ConfigObjImpl {
     pub enable_startup_warmup: bool,
     pub malloc_trim_every_secs: u64,
     pub max_cached_queries: usize,
+    pub query_history_size: usize,
+    pub slow_query_log_path: Option<PathBuf>,
+    pub slow_query_threshold_ms: u64,
+    pub query_pools: Vec<QueryPoolConfig>,
+    pub query_pool_users: HashMap<String, String>,
+    pub max_concurrent_queries: usize,
+    pub query_memory_limit: u64,
 }
 
 crate::di_service!(ConfigObjImpl, [ConfigObj]);
@@ -518,6 +545,38 @@ impl ConfigObj for ConfigObjImpl {
     fn dump_dir(&self) -> &Option<PathBuf> {
         &self.dump_dir
     }
+
+    fn store_provider(&self) -> &FileStoreProvider {
+        &self.store_provider
+    }
+
+    fn query_history_size(&self) -> usize {
+        self.query_history_size
+    }
+
+    fn slow_query_log_path(&self) -> &Option<PathBuf> {
+        &self.slow_query_log_path
+    }
+
+    fn slow_query_threshold_ms(&self) -> u64 {
+        self.slow_query_threshold_ms
+    }
+
+    fn query_pools(&self) -> &Vec<QueryPoolConfig> {
+        &self.query_pools
+    }
+
+    fn query_pool_users(&self) -> &HashMap<String, String> {
+        &self.query_pool_users
+    }
+
+    fn max_concurrent_queries(&self) -> usize {
+        self.max_concurrent_queries
+    }
+
+    fn query_memory_limit(&self) -> u64 {
+        self.query_memory_limit
+    }
 }
 
 lazy_static! {
@@ -655,6 +714,25 @@ impl Config {
                 enable_startup_warmup: env_bool("CUBESTORE_STARTUP_WARMUP", true),
                 malloc_trim_every_secs: env_parse("CUBESTORE_MALLOC_TRIM_EVERY_SECS", 30),
                 max_cached_queries: env_parse("CUBESTORE_MAX_CACHED_QUERIES", 10_000),
+                query_history_size: env_parse("CUBESTORE_QUERY_HISTORY_SIZE", 1000),
+                slow_query_log_path: env::var("CUBESTORE_SLOW_QUERY_LOG").ok().map(PathBuf::from),
+                slow_query_threshold_ms: env_parse("CUBESTORE_SLOW_QUERY_THRESHOLD_MS", 5000),
+                query_pools: env::var("CUBESTORE_QUERY_POOLS")
+                    .ok()
+                    .map(|v| {
+                        QueryPoolConfig::parse_list(&v)
+                            .expect("Invalid CUBESTORE_QUERY_POOLS value")
+                    })
+                    .unwrap_or(Vec::new()),
+                query_pool_users: env::var("CUBESTORE_QUERY_POOL_USERS")
+                    .ok()
+                    .map(|v| {
+                        QueryPoolConfig::parse_users(&v)
+                            .expect("Invalid CUBESTORE_QUERY_POOL_USERS value")
+                    })
+                    .unwrap_or(HashMap::new()),
+                max_concurrent_queries: env_parse("CUBESTORE_MAX_CONCURRENT_QUERIES", 0),
+                query_memory_limit: env_parse("CUBESTORE_QUERY_MEMORY_LIMIT", 0),
             }),
         }
     }
@@ -703,6 +781,13 @@ impl Config {
                 enable_startup_warmup: true,
                 malloc_trim_every_secs: 0,
                 max_cached_queries: 10_000,
+                query_history_size: 1000,
+                slow_query_log_path: None,
+                slow_query_threshold_ms: 5000,
+                query_pools: Vec::new(),
+                query_pool_users: HashMap::new(),
+                max_concurrent_queries: 0,
+                query_memory_limit: 0,
                 meta_store_log_upload_interval: 30,
                 meta_store_snapshot_interval: 300,
                 gc_loop_interval: 60,
@@ -1066,15 +1151,59 @@ impl Config {
             })
             .await;
 
+        self.injector
+            .register_typed::<QueryHistory, _, _, _>(async move |i| {
+                let c = i.get_service_typed::<dyn ConfigObj>().await;
+                let slow_query_log = c.slow_query_log_path().as_ref().and_then(|path| {
+                    QueryHistory::open_slow_query_log(path)
+                        .map_err(|e| {
+                            error!(
+                                "Unable to open slow query log {}, slow queries won't be logged: {}",
+                                path.display(),
+                                e
+                            )
+                        })
+                        .ok()
+                });
+                QueryHistory::new(
+                    c.query_history_size(),
+                    slow_query_log,
+                    Duration::from_millis(c.slow_query_threshold_ms()),
+                )
+            })
+            .await;
+
+        self.injector
+            .register_typed::<QueryPools, _, _, _>(async move |i| {
+                let c = i.get_service_typed::<dyn ConfigObj>().await;
+                QueryPools::new(
+                    c.query_pools().clone(),
+                    c.query_pool_users().clone(),
+                    c.max_concurrent_queries(),
+                )
+            })
+            .await;
+
+        self.injector
+            .register_typed::<TableAccessTracker, _, _, _>(async move |_| TableAccessTracker::new())
+            .await;
+
         self.injector
             .register_typed::<dyn QueryPlanner, _, _, _>(async move |i| {
-                QueryPlannerImpl::new(i.get_service_typed().await, i.get_service_typed().await)
+                QueryPlannerImpl::new(
+                    i.get_service_typed().await,
+                    i.get_service_typed().await,
+                    i.get_service_typed().await,
+                    i.get_service_typed().await,
+                    i.get_service_typed().await,
+                )
             })
             .await;
 
         self.injector
-            .register_typed::<dyn QueryExecutor, _, _, _>(async move |_| {
-                Arc::new(QueryExecutorImpl)
+            .register_typed::<dyn QueryExecutor, _, _, _>(async move |i| {
+                let c = i.get_service_typed::<dyn ConfigObj>().await;
+                QueryExecutorImpl::new(c.query_memory_limit(), c.data_dir().join("spill"))
             })
             .await;
 
@@ -1117,6 +1246,8 @@ impl Config {
                     Duration::from_secs(c.query_timeout()),
                     Duration::from_secs(c.import_job_timeout() * 2),
                     c.max_cached_queries(),
+                    i.get_service_typed().await,
+                    i.get_service_typed().await,
                 )
             })
             .await;
@@ -1129,6 +1260,7 @@ impl Config {
                     i.get_service_typed().await,
                     event_sender_to_move.subscribe(),
                     i.get_service_typed().await,
+                    i.get_service_typed().await,
                 ))
             })
             .await;
@@ -0,0 +1,406 @@
+use crate::config::FileStoreProvider;
+use crate::metastore::Column;
+use crate::queryplanner::query_executor::batch_to_dataframe;
+use crate::remotefs::{ensure_temp_file_is_dropped, RemoteFs};
+use crate::table::TableValue;
+use crate::CubeError;
+use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use datafusion::cube_ext;
+use futures::{Stream, StreamExt};
+use hex::ToHex;
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::{WriterProperties, WriterVersion};
+use serde::{Deserialize, Serialize};
+use sqlparser::ast::{SqlOption, Value};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub enum ExportFormat {
+    Parquet,
+    CSV,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Parquet => "parquet",
+            ExportFormat::CSV => "csv",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Each node starts a new file once this many bytes of in-memory data went into the current
+    /// one. Sizes of written files differ as they are encoded and compressed.
+    pub max_file_size: Option<u64>,
+}
+
+impl ExportOptions {
+    pub fn from_sql_options(options: &[SqlOption]) -> Result<ExportOptions, CubeError> {
+        let mut res = ExportOptions {
+            format: ExportFormat::Parquet,
+            max_file_size: None,
+        };
+        for option in options {
+            match option.name.value.to_lowercase().as_str() {
+                "format" => {
+                    res.format = match &option.value {
+                        Value::SingleQuotedString(f) if f.eq_ignore_ascii_case("parquet") => {
+                            ExportFormat::Parquet
+                        }
+                        Value::SingleQuotedString(f) if f.eq_ignore_ascii_case("csv") => {
+                            ExportFormat::CSV
+                        }
+                        v => {
+                            return Err(CubeError::user(format!(
+                                "Bad export format {}, 'parquet' or 'csv' expected",
+                                v
+                            )))
+                        }
+                    }
+                }
+                "max_file_size" => {
+                    let size = match &option.value {
+                        Value::Number(n, _) => n.parse::<u64>().ok(),
+                        _ => None,
+                    };
+                    match size {
+                        Some(size) if size > 0 => res.max_file_size = Some(size),
+                        _ => {
+                            return Err(CubeError::user(format!(
+                                "max_file_size should be a positive number of bytes but {} found",
+                                option.value
+                            )))
+                        }
+                    }
+                }
+                _ => {
+                    return Err(CubeError::user(format!(
+                        "Unknown export option: {}",
+                        option.name
+                    )))
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Maps export `location` to a path prefix inside the remote storage of Cube Store.
+/// Locations with a scheme, e.g. `s3://bucket/prefix`, should point to the configured bucket and
+/// sub path, which isn't part of the returned prefix.
+pub fn export_remote_prefix(
+    provider: &FileStoreProvider,
+    location: &str,
+) -> Result<String, CubeError> {
+    let path = match location.find("://") {
+        Some(i) => {
+            let scheme = &location[..i];
+            let rest = &location[i + 3..];
+            let (bucket, path) = match rest.find('/') {
+                Some(j) => (&rest[..j], &rest[j + 1..]),
+                None => (rest, ""),
+            };
+            let configured = match provider {
+                FileStoreProvider::S3 {
+                    bucket_name,
+                    sub_path,
+                    ..
+                }
+                | FileStoreProvider::MINIO {
+                    bucket_name,
+                    sub_path,
+                } => Some(("s3", bucket_name, sub_path)),
+                FileStoreProvider::GCS {
+                    bucket_name,
+                    sub_path,
+                } => Some(("gs", bucket_name, sub_path)),
+                FileStoreProvider::Local | FileStoreProvider::Filesystem { .. } => None,
+            };
+            match configured {
+                Some((s, b, sub_path)) if s == scheme && b == bucket => {
+                    let path = path.trim_start_matches('/');
+                    match sub_path.as_deref().map(|p| p.trim_matches('/')) {
+                        None | Some("") => path,
+                        Some(sub_path) => {
+                            let in_sub_path = path.starts_with(sub_path)
+                                && path[sub_path.len()..].starts_with('/');
+                            if !in_sub_path {
+                                return Err(CubeError::user(format!(
+                                    "Export location '{}' should point inside the Cube Store \
+                                     sub path, e.g. '{}://{}/{}/exports'",
+                                    location, s, b, sub_path
+                                )));
+                            }
+                            &path[sub_path.len()..]
+                        }
+                    }
+                }
+                _ => {
+                    return Err(CubeError::user(format!(
+                        "Export location '{}' should point to the Cube Store bucket",
+                        location
+                    )))
+                }
+            }
+        }
+        None => location,
+    };
+    let path = path.trim_matches('/');
+    if path.is_empty()
+        || path
+            .split('/')
+            .any(|p| p.is_empty() || p == "." || p == "..")
+    {
+        return Err(CubeError::user(format!(
+            "Export location '{}' should contain a path prefix, e.g. 's3://bucket/exports'",
+            location
+        )));
+    }
+    Ok(path.to_string())
+}
+
+/// Writes batches of `stream` into `<file_prefix>-<n>.<extension>` files of the remote storage
+/// as they arrive and returns remote paths of the files. Nothing is written when there are no rows.
+pub async fn export_batches(
+    remote_fs: Arc<dyn RemoteFs>,
+    schema: SchemaRef,
+    mut batches: impl Stream<Item = Result<RecordBatch, ArrowError>> + Send + Unpin,
+    file_prefix: &str,
+    options: &ExportOptions,
+) -> Result<Vec<String>, CubeError> {
+    let mut files = Vec::new();
+    let mut current: Option<ExportFile> = None;
+    while let Some(batch) = batches.next().await {
+        let batch = batch?;
+        if batch.num_rows() == 0 {
+            continue;
+        }
+        if current.is_none() {
+            let remote_path = format!(
+                "{}-{}.{}",
+                file_prefix,
+                files.len(),
+                options.format.extension()
+            );
+            let local_path = remote_fs.temp_upload_path(&remote_path).await?;
+            current = Some(ExportFile::create(
+                local_path,
+                remote_path,
+                options.format,
+                schema.clone(),
+            ));
+        }
+        let file = current.as_mut().unwrap();
+        file.write(batch).await?;
+        // Each file gets at least one batch, so the files can be a bit larger than the limit.
+        if options
+            .max_file_size
+            .map(|m| file.size >= m)
+            .unwrap_or(false)
+        {
+            files.push(current.take().unwrap().upload(remote_fs.as_ref()).await?);
+        }
+    }
+    if let Some(file) = current {
+        files.push(file.upload(remote_fs.as_ref()).await?);
+    }
+    Ok(files)
+}
+
+/// Export file that is being written by a blocking task. The local file is removed if it's not
+/// uploaded.
+struct ExportFile {
+    local_path: String,
+    remote_path: String,
+    write_tx: Option<tokio::sync::mpsc::Sender<RecordBatch>>,
+    io_job: Option<JoinHandle<Result<(), CubeError>>>,
+    /// In-memory size of the written batches.
+    size: u64,
+    uploaded: bool,
+}
+
+impl ExportFile {
+    fn create(
+        local_path: String,
+        remote_path: String,
+        format: ExportFormat,
+        schema: SchemaRef,
+    ) -> ExportFile {
+        let (write_tx, mut write_rx) = tokio::sync::mpsc::channel(1);
+        let path = local_path.clone();
+        let io_job = cube_ext::spawn_blocking(move || -> Result<(), CubeError> {
+            let mut writer = FileWriter::create(&path, format, schema)?;
+            while let Some(batch) = write_rx.blocking_recv() {
+                writer.write(&batch)?;
+            }
+            writer.finish()
+        });
+        ExportFile {
+            local_path,
+            remote_path,
+            write_tx: Some(write_tx),
+            io_job: Some(io_job),
+            size: 0,
+            uploaded: false,
+        }
+    }
+
+    async fn write(&mut self, batch: RecordBatch) -> Result<(), CubeError> {
+        self.size += batch
+            .columns()
+            .iter()
+            .map(|c| c.get_array_memory_size() as u64)
+            .sum::<u64>();
+        if self.write_tx.as_ref().unwrap().send(batch).await.is_err() {
+            // Writer stopped on error.
+            self.write_tx = None;
+            self.io_job.take().unwrap().await??;
+            return Err(CubeError::internal(
+                "Export file writer stopped unexpectedly".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn upload(mut self, remote_fs: &dyn RemoteFs) -> Result<String, CubeError> {
+        self.write_tx = None;
+        self.io_job.take().unwrap().await??;
+        remote_fs
+            .upload_file(&self.local_path, &self.remote_path)
+            .await?;
+        self.uploaded = true;
+        Ok(self.remote_path.clone())
+    }
+}
+
+impl Drop for ExportFile {
+    fn drop(&mut self) {
+        if !self.uploaded {
+            ensure_temp_file_is_dropped(self.local_path.clone());
+        }
+    }
+}
+
+enum FileWriter {
+    Parquet(ArrowWriter<File>),
+    CSV(csv::Writer<File>),
+}
+
+impl FileWriter {
+    fn create(path: &str, format: ExportFormat, schema: SchemaRef) -> Result<Self, CubeError> {
+        let file = File::create(path)?;
+        Ok(match format {
+            ExportFormat::Parquet => {
+                let props = WriterProperties::builder()
+                    .set_writer_version(WriterVersion::PARQUET_2_0)
+                    .build();
+                FileWriter::Parquet(ArrowWriter::try_new(file, schema, Some(props))?)
+            }
+            ExportFormat::CSV => {
+                let mut w = csv::Writer::from_writer(file);
+                w.write_record(schema.fields().iter().map(|f| f.name()))
+                    .map_err(csv_error)?;
+                FileWriter::CSV(w)
+            }
+        })
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<(), CubeError> {
+        match self {
+            FileWriter::Parquet(w) => w.write(batch)?,
+            FileWriter::CSV(w) => {
+                let frame = batch_to_dataframe(&vec![batch.clone()])?;
+                let columns = frame.get_columns();
+                for row in frame.get_rows() {
+                    w.write_record(
+                        row.values()
+                            .iter()
+                            .zip(columns.iter())
+                            .map(|(v, c)| csv_value(v, c)),
+                    )
+                    .map_err(csv_error)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), CubeError> {
+        match self {
+            FileWriter::Parquet(mut w) => {
+                w.close()?;
+            }
+            FileWriter::CSV(mut w) => w.flush()?,
+        }
+        Ok(())
+    }
+}
+
+fn csv_error(e: csv::Error) -> CubeError {
+    CubeError::internal(format!("CSV export: {}", e))
+}
+
+/// Values are written the same way they are returned over MySQL protocol. Nulls are written as
+/// empty strings, which is how CSV imports read them back.
+fn csv_value(value: &TableValue, column: &Column) -> String {
+    match value {
+        TableValue::Null => String::new(),
+        TableValue::String(s) => s.clone(),
+        TableValue::Int(i) => i.to_string(),
+        TableValue::Decimal(v) => {
+            let scale = u8::try_from(column.get_column_type().target_scale()).unwrap();
+            v.to_string(scale)
+        }
+        TableValue::Float(v) => v.to_string(),
+        TableValue::Bytes(b) => format!("0x{}", b.encode_hex_upper::<String>()),
+        TableValue::Timestamp(t) => t.to_string(),
+        TableValue::Boolean(b) => b.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remote_prefix() {
+        let s3 = FileStoreProvider::S3 {
+            region: "us-west-2".to_string(),
+            bucket_name: "cube".to_string(),
+            sub_path: None,
+        };
+        assert_eq!(
+            export_remote_prefix(&s3, "s3://cube/exports/daily/").unwrap(),
+            "exports/daily"
+        );
+        assert!(export_remote_prefix(&s3, "s3://other/exports").is_err());
+        assert!(export_remote_prefix(&s3, "gs://cube/exports").is_err());
+        assert!(export_remote_prefix(&s3, "s3://cube").is_err());
+        assert!(export_remote_prefix(&s3, "s3://cube/a/../b").is_err());
+
+        let gcs = FileStoreProvider::GCS {
+            bucket_name: "cube".to_string(),
+            sub_path: Some("/store/".to_string()),
+        };
+        assert_eq!(
+            export_remote_prefix(&gcs, "gs://cube/store/exports/daily").unwrap(),
+            "exports/daily"
+        );
+        assert!(export_remote_prefix(&gcs, "gs://cube/exports").is_err());
+        assert!(export_remote_prefix(&gcs, "gs://cube/store").is_err());
+        assert!(export_remote_prefix(&gcs, "gs://cube/store/").is_err());
+        assert!(export_remote_prefix(&gcs, "gs://cube/storefront/exports").is_err());
+
+        let local = FileStoreProvider::Local;
+        assert_eq!(export_remote_prefix(&local, "exports").unwrap(), "exports");
+        assert!(export_remote_prefix(&local, "s3://cube/exports").is_err());
+    }
+}
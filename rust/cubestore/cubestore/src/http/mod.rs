@@ -94,6 +94,8 @@ impl HttpServer {
                         Ok(user) => Ok(SqlQueryContext {
                             user,
                             trace_obj: None,
+                            query_pool: None,
+                            local_infile: None,
                         }),
                         Err(_) => Err(warp::reject::custom(CubeRejection::NotAuthorized)),
                     }
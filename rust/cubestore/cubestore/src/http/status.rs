@@ -2,6 +2,7 @@ use crate::config::injection::Injector;
 use crate::config::{is_router, uses_remote_metastore, Config};
 use crate::metastore::MetaStore;
 use crate::sql::SqlService;
+use crate::util::metrics;
 use crate::CubeError;
 use std::convert::Infallible;
 use std::net::SocketAddr;
@@ -15,23 +16,38 @@ pub fn serve_status_probes(c: &Config) {
         None => return,
     };
 
-    let p = match RouterProbes::try_new(c) {
-        Some(p) => p,
-        None => return,
-    };
+    // Probes are served only by the router, metrics are served by every node.
+    let p = RouterProbes::try_new(c);
 
     let pc = p.clone();
     let l = warp::path!("livez").and_then(move || {
         let pc = pc.clone();
-        async move { status_probe_reply("liveness", pc.is_live().await) }
+        async move {
+            match pc {
+                Some(pc) => status_probe_reply("liveness", pc.is_live().await),
+                None => Ok(StatusCode::NOT_FOUND),
+            }
+        }
     });
     let r = warp::path!("readyz").and_then(move || {
         let p = p.clone();
-        async move { status_probe_reply("readiness", p.is_ready().await) }
+        async move {
+            match p {
+                Some(p) => status_probe_reply("readiness", p.is_ready().await),
+                None => Ok(StatusCode::NOT_FOUND),
+            }
+        }
+    });
+    let m = warp::path!("metrics").map(|| {
+        warp::reply::with_header(
+            metrics::render_prometheus(),
+            "content-type",
+            "text/plain; version=0.0.4",
+        )
     });
 
     let addr: SocketAddr = addr.parse().expect("cannot parse status probe address");
-    match warp::serve(l.or(r)).try_bind_ephemeral(addr) {
+    match warp::serve(l.or(r).or(m)).try_bind_ephemeral(addr) {
         Ok((addr, f)) => {
             log::info!("Serving status probes at {}", addr);
             tokio::spawn(f);
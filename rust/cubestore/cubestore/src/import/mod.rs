@@ -5,7 +5,9 @@ use std::path::Path;
 use std::pin::Pin;
 use std::sync::Arc;
 
-use arrow::array::{ArrayBuilder, ArrayRef};
+use arrow::array::{new_null_array, Array, ArrayBuilder, ArrayRef, Int64Array};
+use arrow::compute::cast;
+use arrow::datatypes::Field;
 use async_compression::tokio::bufread::GzipDecoder;
 use async_std::io::SeekFrom;
 use async_std::task::{Context, Poll};
@@ -367,6 +369,15 @@ pub trait ImportService: DIService + Send + Sync {
     async fn import_table(&self, table_id: u64) -> Result<(), CubeError>;
     async fn import_table_part(&self, table_id: u64, location: &str) -> Result<(), CubeError>;
     async fn estimate_location_row_count(&self, location: &str) -> Result<u64, CubeError>;
+    /// Imports `columns` of the table from a location that is not a part of the table
+    /// definition, e.g. on `LOAD DATA INFILE`. Returns the number of imported rows.
+    async fn load_data(
+        &self,
+        table: IdRow<Table>,
+        location: &str,
+        format: ImportFormat,
+        columns: Vec<Column>,
+    ) -> Result<u64, CubeError>;
 }
 
 crate::di_service!(MockImportService, [ImportService]);
@@ -406,6 +417,7 @@ impl ImportServiceImpl {
         location: &str,
         table_id: u64,
         temp_dir: &Path,
+        is_table_location: bool,
     ) -> Result<(File, Option<TempPath>), CubeError> {
         if location.starts_with("http") {
             let (file, path) = tempfile::Builder::new()
@@ -422,18 +434,22 @@ impl ImportServiceImpl {
                 file.write_all(slice).await?;
             }
             log::info!("Import downloaded {} ({} bytes)", location, size);
-            self.meta_store
-                .update_location_download_size(table_id, location.to_string(), size as u64)
-                .await?;
+            if is_table_location {
+                self.meta_store
+                    .update_location_download_size(table_id, location.to_string(), size as u64)
+                    .await?;
+            }
             file.seek(SeekFrom::Start(0)).await?;
             Ok((file, Some(path)))
         } else if location.starts_with("temp://") {
             let temp_file = self.download_temp_file(location).await?;
             let size = temp_file.metadata().await?.len();
             log::info!("Import downloaded {} ({} bytes)", location, size);
-            self.meta_store
-                .update_location_download_size(table_id, location.to_string(), size as u64)
-                .await?;
+            if is_table_location {
+                self.meta_store
+                    .update_location_download_size(table_id, location.to_string(), size as u64)
+                    .await?;
+            }
             Ok((temp_file, None))
         } else {
             Ok((File::open(location.clone()).await?, None))
@@ -466,19 +482,22 @@ impl ImportServiceImpl {
         table: &IdRow<Table>,
         format: ImportFormat,
         location: &str,
-    ) -> Result<(), CubeError> {
+        columns: Vec<Column>,
+        is_table_location: bool,
+    ) -> Result<u64, CubeError> {
         let temp_dir = self.config_obj.data_dir().join("tmp");
         tokio::fs::create_dir_all(temp_dir.clone()).await?;
 
         let (file, tmp_path) = self
-            .resolve_location(location.clone(), table.get_id(), &temp_dir)
+            .resolve_location(
+                location.clone(),
+                table.get_id(),
+                &temp_dir,
+                is_table_location,
+            )
             .await?;
         let mut row_stream = format
-            .row_stream(
-                file,
-                location.to_string(),
-                table.get_row().get_columns().clone(),
-            )
+            .row_stream(file, location.to_string(), columns.clone())
             .await?;
 
         let mut ingestion = Ingestion::new(
@@ -492,28 +511,36 @@ impl ImportServiceImpl {
             builders.into_iter().map(|mut b| b.finish()).collect_vec()
         };
 
-        let table_cols = table.get_row().get_columns().as_slice();
+        let table_cols = columns.as_slice();
+        let column_refs = table_cols.iter().collect_vec();
         let mut builders = create_array_builders(table_cols);
         let mut num_rows = 0;
+        let mut total_rows = 0;
         while let Some(row) = row_stream.next().await {
             if let Some(row) = row? {
                 append_row(&mut builders, table_cols, &row);
                 num_rows += 1;
+                total_rows += 1;
 
                 if num_rows >= self.config_obj.wal_split_threshold() as usize {
                     let mut to_add = create_array_builders(table_cols);
                     mem::swap(&mut builders, &mut to_add);
                     num_rows = 0;
 
-                    ingestion.queue_data_frame(finish(to_add)).await?;
+                    ingestion
+                        .queue_columns(&column_refs, finish(to_add))
+                        .await?;
                 }
             }
         }
 
         mem::drop(tmp_path);
 
-        ingestion.queue_data_frame(finish(builders)).await?;
-        ingestion.wait_completion().await
+        ingestion
+            .queue_columns(&column_refs, finish(builders))
+            .await?;
+        ingestion.wait_completion().await?;
+        Ok(total_rows)
     }
 
     fn estimate_rows(location: &str, size: Option<u64>) -> u64 {
@@ -551,7 +578,14 @@ impl ImportService for ImportServiceImpl {
                 table
             )))?;
         for location in locations.iter() {
-            self.do_import(&table, *format, location).await?;
+            self.do_import(
+                &table,
+                *format,
+                location,
+                table.get_row().get_columns().clone(),
+                true,
+            )
+            .await?;
         }
 
         for location in locations.iter() {
@@ -588,7 +622,14 @@ impl ImportService for ImportServiceImpl {
         if Table::is_stream_location(location) {
             self.streaming_service.stream_table(table, location).await?;
         } else {
-            self.do_import(&table, *format, location).await?;
+            self.do_import(
+                &table,
+                *format,
+                location,
+                table.get_row().get_columns().clone(),
+                true,
+            )
+            .await?;
             self.drop_temp_uploads(&location).await?;
         }
 
@@ -617,6 +658,20 @@ impl ImportService for ImportServiceImpl {
             ))
         }
     }
+
+    async fn load_data(
+        &self,
+        table: IdRow<Table>,
+        location: &str,
+        format: ImportFormat,
+        columns: Vec<Column>,
+    ) -> Result<u64, CubeError> {
+        let rows = self
+            .do_import(&table, format, location, columns, false)
+            .await?;
+        self.drop_temp_uploads(location).await?;
+        Ok(rows)
+    }
 }
 
 /// Handles row-based data ingestion, e.g. on CSV import and SQL insert.
@@ -626,7 +681,7 @@ pub struct Ingestion {
     limits: Arc<ConcurrencyLimits>,
     table: IdRow<Table>,
 
-    partition_jobs: Vec<JoinHandle<Result<(), CubeError>>>,
+    partition_jobs: Vec<JoinHandle<Result<Vec<(u64, Option<u64>)>, CubeError>>>,
 }
 
 impl Ingestion {
@@ -648,7 +703,6 @@ impl Ingestion {
     pub async fn queue_data_frame(&mut self, rows: Vec<ArrayRef>) -> Result<(), CubeError> {
         let active_data_frame = self.limits.acquire_data_frame().await?;
 
-        let meta_store = self.meta_store.clone();
         let chunk_store = self.chunk_store.clone();
         let columns = self.table.get_row().get_columns().clone().clone();
         let table_id = self.table.get_id();
@@ -661,28 +715,90 @@ impl Ingestion {
             std::mem::drop(active_data_frame);
 
             // More data frame processing can proceed now as we dropped `active_data_frame`.
-            // Time to wait to chunks to upload and activate them.
-            let new_chunk_ids: Result<Vec<(u64, Option<u64>)>, CubeError> = join_all(new_chunks)
+            // Time to wait to chunks to upload.
+            join_all(new_chunks)
                 .await
                 .into_iter()
                 .map(|c| {
                     let (c, file_size) = c??;
                     Ok((c.get_id(), file_size))
                 })
-                .collect();
-            meta_store.activate_chunks(table_id, new_chunk_ids?).await
+                .collect()
         }));
 
         Ok(())
     }
 
+    /// Queues values of `columns`, which may come in any order and only cover a part of the table.
+    /// Values are cast to the types of table columns. Omitted columns are filled with nulls,
+    /// except for `__seq`, which is assigned by the metastore.
+    pub async fn queue_columns(
+        &mut self,
+        columns: &[&Column],
+        arrays: Vec<ArrayRef>,
+    ) -> Result<(), CubeError> {
+        if columns.len() != arrays.len() {
+            return Err(CubeError::internal(format!(
+                "Expected values of {} columns, but got {}",
+                columns.len(),
+                arrays.len()
+            )));
+        }
+        let num_rows = arrays.first().map(|a| a.len()).unwrap_or(0);
+        let table = self.table.get_row();
+        let seq_column = table.seq_column().map(|c| c.get_index());
+        let mut rows = Vec::with_capacity(table.get_columns().len());
+        for c in table.get_columns() {
+            let field: Field = c.into();
+            let array = match columns.iter().position(|i| i.get_name() == c.get_name()) {
+                Some(i) if arrays[i].data_type() == field.data_type() => arrays[i].clone(),
+                Some(i) => cast(&arrays[i], field.data_type()).map_err(|e| {
+                    CubeError::user(format!(
+                        "Can't convert values of column {} to {}: {}",
+                        c.get_name(),
+                        c.get_column_type(),
+                        e
+                    ))
+                })?,
+                None if seq_column == Some(c.get_index()) => {
+                    let first = self
+                        .meta_store
+                        .allocate_table_seq(self.table.get_id(), num_rows as u64)
+                        .await? as i64;
+                    Arc::new(Int64Array::from(
+                        (first..first + num_rows as i64).collect::<Vec<_>>(),
+                    ))
+                }
+                None => new_null_array(field.data_type(), num_rows),
+            };
+            rows.push(array);
+        }
+        self.queue_data_frame(rows).await
+    }
+
+    /// Waits for the queued data to be uploaded and activates it.
     pub async fn wait_completion(self) -> Result<(), CubeError> {
+        let meta_store = self.meta_store.clone();
+        let table_id = self.table.get_id();
         for j in self.partition_jobs {
-            j.await??;
+            meta_store.activate_chunks(table_id, j.await??).await?;
         }
 
         Ok(())
     }
+
+    /// Waits for the queued data to be uploaded, but leaves the chunks inactive. Returns ids and
+    /// file sizes of the chunks to pass to [MetaStore::activate_chunks] later, e.g. when all
+    /// nodes that take part in the insert are done. Inactive chunks are removed by the scheduler
+    /// if they are never activated.
+    pub async fn wait_uploads(self) -> Result<Vec<(u64, Option<u64>)>, CubeError> {
+        let mut chunk_ids = Vec::new();
+        for j in self.partition_jobs {
+            chunk_ids.extend(j.await??);
+        }
+
+        Ok(chunk_ids)
+    }
 }
 
 #[cfg(test)]
@@ -45,6 +45,7 @@ pub mod app_metrics;
 pub mod cluster;
 pub mod codegen;
 pub mod config;
+pub mod export;
 pub mod http;
 pub mod import;
 pub mod metastore;
@@ -1,6 +1,7 @@
 use super::{
     BaseRocksSecondaryIndex, Column, Index, IndexId, RocksSecondaryIndex, RocksTable, TableId,
 };
+use crate::metastore::statistics::IndexStatistics;
 use crate::metastore::{IdRow, MetaStoreEvent};
 use crate::{rocks_table_impl, CubeError};
 use byteorder::{BigEndian, WriteBytesExt};
@@ -30,6 +31,7 @@ impl Index {
             sort_key_size,
             partition_split_key_size,
             multi_index_id,
+            statistics: None,
         })
     }
 
@@ -61,6 +63,17 @@ impl Index {
     pub fn multi_index_id(&self) -> Option<u64> {
         self.multi_index_id
     }
+
+    /// Set by `ANALYZE TABLE`, used to estimate costs of queries.
+    pub fn statistics(&self) -> &Option<IndexStatistics> {
+        &self.statistics
+    }
+
+    pub fn update_statistics(&self, statistics: Option<IndexStatistics>) -> Index {
+        let mut i = self.clone();
+        i.statistics = statistics;
+        i
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
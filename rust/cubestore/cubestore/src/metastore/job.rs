@@ -18,6 +18,36 @@ pub enum JobType {
     MultiPartitionSplit,
     FinishMultiSplit,
     RepartitionChunk,
+    AnalyzePartition,
+}
+
+impl JobType {
+    pub const ALL_NAMES: [&'static str; 9] = [
+        "WalPartitioning",
+        "PartitionCompaction",
+        "TableImport",
+        "Repartition",
+        "TableImportCSV",
+        "MultiPartitionSplit",
+        "FinishMultiSplit",
+        "RepartitionChunk",
+        "AnalyzePartition",
+    ];
+
+    /// Job type name without arguments, used as a metric tag.
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobType::WalPartitioning => "WalPartitioning",
+            JobType::PartitionCompaction => "PartitionCompaction",
+            JobType::TableImport => "TableImport",
+            JobType::Repartition => "Repartition",
+            JobType::TableImportCSV(_) => "TableImportCSV",
+            JobType::MultiPartitionSplit => "MultiPartitionSplit",
+            JobType::FinishMultiSplit => "FinishMultiSplit",
+            JobType::RepartitionChunk => "RepartitionChunk",
+            JobType::AnalyzePartition => "AnalyzePartition",
+        }
+    }
 }
 
 fn get_job_type_index(j: &JobType) -> u32 {
@@ -30,6 +60,7 @@ fn get_job_type_index(j: &JobType) -> u32 {
         JobType::MultiPartitionSplit => 6,
         JobType::FinishMultiSplit => 7,
         JobType::RepartitionChunk => 8,
+        JobType::AnalyzePartition => 9,
     }
 }
 
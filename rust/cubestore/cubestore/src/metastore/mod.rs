@@ -6,6 +6,7 @@ pub mod multi_index;
 pub mod partition;
 pub mod schema;
 pub mod source;
+pub mod statistics;
 pub mod table;
 pub mod wal;
 
@@ -63,8 +64,9 @@ use partition::{PartitionRocksIndex, PartitionRocksTable};
 use regex::Regex;
 use rocksdb::backup::BackupEngineOptions;
 use rocksdb::checkpoint::Checkpoint;
-use schema::{SchemaRocksIndex, SchemaRocksTable};
+use schema::{SchemaLifecycle, SchemaRocksIndex, SchemaRocksTable};
 use smallvec::alloc::fmt::Formatter;
+use statistics::{ColumnStatistics, IndexStatistics};
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display};
@@ -73,7 +75,7 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Mutex;
 use std::time::{Duration, SystemTime};
-use table::Table;
+use table::{AggregateColumnIndex, AggregateFunction, Table};
 use table::{TableRocksIndex, TableRocksTable};
 use tokio::fs::File;
 use tokio::sync::broadcast::Sender;
@@ -294,6 +296,33 @@ impl DataFrameValue<String> for Option<Vec<u64>> {
     }
 }
 
+impl DataFrameValue<String> for Option<Vec<ColumnStatistics>> {
+    fn value(v: &Self) -> String {
+        v.as_ref()
+            .map(|v| format!("{:?}", v.iter().map(|c| c.name()).collect_vec()))
+            .unwrap_or("NULL".to_string())
+    }
+}
+
+impl DataFrameValue<String> for Option<IndexStatistics> {
+    fn value(v: &Self) -> String {
+        v.as_ref()
+            .map(|v| format!("{} rows at {}", v.row_count(), v.analyzed_at()))
+            .unwrap_or("NULL".to_string())
+    }
+}
+
+impl DataFrameValue<String> for Vec<AggregateColumnIndex> {
+    fn value(v: &Self) -> String {
+        format!(
+            "[{}]",
+            v.iter()
+                .map(|a| format!("{}({})", a.function(), a.index()))
+                .join(", ")
+        )
+    }
+}
+
 impl DataFrameValue<String> for Option<Row> {
     fn value(v: &Self) -> String {
         v.as_ref()
@@ -510,7 +539,11 @@ pub enum ImportFormat {
 data_frame_from! {
 #[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash)]
 pub struct Schema {
-    name: String
+    name: String,
+    #[serde(default)]
+    keep_versions: Option<u64>,
+    #[serde(default)]
+    ttl_secs: Option<u64>
 }
 }
 
@@ -524,7 +557,9 @@ pub struct Index {
     #[serde(default)]
     partition_split_key_size: Option<u64>,
     #[serde(default)]
-    multi_index_id: Option<u64>
+    multi_index_id: Option<u64>,
+    #[serde(default)]
+    statistics: Option<IndexStatistics>
 }
 }
 
@@ -554,7 +589,9 @@ pub struct Partition {
     #[serde(default)]
     suffix: Option<String>,
     #[serde(default)]
-    file_size: Option<u64>
+    file_size: Option<u64>,
+    #[serde(default)]
+    column_statistics: Option<Vec<ColumnStatistics>>
 }
 }
 
@@ -725,10 +762,12 @@ pub struct PartitionData {
 pub trait MetaStore: DIService + Send + Sync {
     async fn wait_for_current_seq_to_sync(&self) -> Result<(), CubeError>;
     fn schemas_table(&self) -> SchemaMetaStoreTable;
+    /// With `if_not_exists`, a non-empty `lifecycle` replaces the one of the existing schema.
     async fn create_schema(
         &self,
         schema_name: String,
         if_not_exists: bool,
+        lifecycle: SchemaLifecycle,
     ) -> Result<IdRow<Schema>, CubeError>;
     async fn get_schemas(&self) -> Result<Vec<IdRow<Schema>>, CubeError>;
     async fn get_schema_by_id(&self, schema_id: u64) -> Result<IdRow<Schema>, CubeError>;
@@ -760,6 +799,7 @@ pub trait MetaStore: DIService + Send + Sync {
         indexes: Vec<IndexDef>,
         is_ready: bool,
         unique_key_column_names: Option<Vec<String>>,
+        aggregates: Vec<(String, AggregateFunction)>,
         partition_split_threshold: Option<u64>,
     ) -> Result<IdRow<Table>, CubeError>;
     async fn table_ready(&self, id: u64, is_ready: bool) -> Result<IdRow<Table>, CubeError>;
@@ -769,6 +809,10 @@ pub trait MetaStore: DIService + Send + Sync {
         location: String,
         download_size: u64,
     ) -> Result<IdRow<Table>, CubeError>;
+    /// Reserves `count` consecutive values of the generated `__seq` column for the table and
+    /// returns the first one. Values keep increasing across restarts and router nodes and start
+    /// at the current time in microseconds, which is where streaming tables start their `__seq`.
+    async fn allocate_table_seq(&self, table_id: u64, count: u64) -> Result<u64, CubeError>;
     async fn get_table(
         &self,
         schema_name: String,
@@ -820,6 +864,11 @@ pub trait MetaStore: DIService + Send + Sync {
     ) -> Result<(), CubeError>;
     async fn delete_partition(&self, partition_id: u64) -> Result<IdRow<Partition>, CubeError>;
     async fn mark_partition_warmed_up(&self, partition_id: u64) -> Result<(), CubeError>;
+    async fn update_partition_statistics(
+        &self,
+        partition_id: u64,
+        statistics: Vec<ColumnStatistics>,
+    ) -> Result<IdRow<Partition>, CubeError>;
     async fn delete_middle_man_partition(
         &self,
         partition_id: u64,
@@ -850,6 +899,11 @@ pub trait MetaStore: DIService + Send + Sync {
         index_id: u64,
     ) -> Result<Vec<IdRow<Partition>>, CubeError>;
     async fn get_index(&self, index_id: u64) -> Result<IdRow<Index>, CubeError>;
+    async fn update_index_statistics(
+        &self,
+        index_id: u64,
+        statistics: Option<IndexStatistics>,
+    ) -> Result<IdRow<Index>, CubeError>;
 
     async fn create_partitioned_index(
         &self,
@@ -2823,6 +2877,7 @@ impl MetaStore for RocksMetaStore {
         &self,
         schema_name: String,
         if_not_exists: bool,
+        lifecycle: SchemaLifecycle,
     ) -> Result<IdRow<Schema>, CubeError> {
         self.write_operation(move |db_ref, batch_pipe| {
             batch_pipe.invalidate_tables_cache();
@@ -2830,12 +2885,17 @@ impl MetaStore for RocksMetaStore {
             if if_not_exists {
                 let rows = table.get_rows_by_index(&schema_name, &SchemaRocksIndex::Name)?;
                 if let Some(row) = rows.into_iter().nth(0) {
+                    if !lifecycle.is_empty() && row.get_row().lifecycle() != lifecycle {
+                        return Ok(table.update_with_fn(
+                            row.get_id(),
+                            |s| s.clone().with_lifecycle(lifecycle),
+                            batch_pipe,
+                        )?);
+                    }
                     return Ok(row);
                 }
             }
-            let schema = Schema {
-                name: schema_name.clone(),
-            };
+            let schema = Schema::new(schema_name.clone()).with_lifecycle(lifecycle);
             Ok(table.insert(schema, batch_pipe)?)
         })
         .await
@@ -2958,6 +3018,7 @@ impl MetaStore for RocksMetaStore {
         indexes: Vec<IndexDef>,
         is_ready: bool,
         unique_key_column_names: Option<Vec<String>>,
+        aggregates: Vec<(String, AggregateFunction)>,
         partition_split_threshold: Option<u64>,
     ) -> Result<IdRow<Table>, CubeError> {
         self.write_operation(move |db_ref, batch_pipe| {
@@ -2998,6 +3059,49 @@ impl MetaStore for RocksMetaStore {
             } else {
                 None
             };
+            let mut aggregate_column_indices = Vec::with_capacity(aggregates.len());
+            for (column_name, function) in aggregates {
+                let key_indices = unique_key_column_indices.as_ref().ok_or_else(|| {
+                    CubeError::user(format!(
+                        "Aggregate columns require UNIQUE KEY to be defined for table {}",
+                        table_name
+                    ))
+                })?;
+                let column = columns
+                    .iter()
+                    .find(|c| c.name == column_name)
+                    .ok_or_else(|| {
+                        CubeError::user(format!(
+                            "Aggregate column {} not found among column definitions {:?}",
+                            column_name, columns
+                        ))
+                    })?;
+                if key_indices.contains(&(column.column_index as u64)) {
+                    return Err(CubeError::user(format!(
+                        "Unique key column {} can't be aggregated",
+                        column_name
+                    )));
+                }
+                if !function.is_applicable_to(&column.column_type) {
+                    return Err(CubeError::user(format!(
+                        "Aggregate function {} can't be applied to column {} of type {}",
+                        function, column_name, column.column_type
+                    )));
+                }
+                if aggregate_column_indices
+                    .iter()
+                    .any(|a: &AggregateColumnIndex| a.index() == column.column_index as u64)
+                {
+                    return Err(CubeError::user(format!(
+                        "Column {} is aggregated more than once",
+                        column_name
+                    )));
+                }
+                aggregate_column_indices.push(AggregateColumnIndex::new(
+                    column.column_index as u64,
+                    function,
+                ));
+            }
             let table = Table::new(
                 table_name,
                 schema_id.get_id(),
@@ -3008,6 +3112,7 @@ impl MetaStore for RocksMetaStore {
                 unique_key_column_indices,
                 seq_column_index,
                 partition_split_threshold,
+                aggregate_column_indices,
             );
             let table_id = rocks_table.insert(table, batch_pipe)?;
             for index_def in indexes.into_iter() {
@@ -3109,6 +3214,24 @@ impl MetaStore for RocksMetaStore {
         .await
     }
 
+    async fn allocate_table_seq(&self, table_id: u64, count: u64) -> Result<u64, CubeError> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_micros() as u64;
+        self.write_operation(move |db_ref, batch_pipe| {
+            batch_pipe.invalidate_tables_cache();
+            let rocks_table = TableRocksTable::new(db_ref.clone());
+            let table = rocks_table.update_with_fn(
+                table_id,
+                |r| r.update_last_generated_seq(count, now),
+                batch_pipe,
+            )?;
+            Ok(table.get_row().last_generated_seq() - count + 1)
+        })
+        .await
+    }
+
     async fn get_table(
         &self,
         schema_name: String,
@@ -3426,6 +3549,24 @@ impl MetaStore for RocksMetaStore {
         .await
     }
 
+    async fn update_partition_statistics(
+        &self,
+        partition_id: u64,
+        statistics: Vec<ColumnStatistics>,
+    ) -> Result<IdRow<Partition>, CubeError> {
+        self.write_operation(move |db_ref, batch_pipe| {
+            let table = PartitionRocksTable::new(db_ref);
+            let partition = table.get_row_or_not_found(partition_id)?;
+            table.update(
+                partition_id,
+                partition.row.update_column_statistics(statistics),
+                &partition.row,
+                batch_pipe,
+            )
+        })
+        .await
+    }
+
     async fn delete_middle_man_partition(
         &self,
         partition_id: u64,
@@ -3788,6 +3929,24 @@ impl MetaStore for RocksMetaStore {
         .await
     }
 
+    async fn update_index_statistics(
+        &self,
+        index_id: u64,
+        statistics: Option<IndexStatistics>,
+    ) -> Result<IdRow<Index>, CubeError> {
+        self.write_operation(move |db_ref, batch_pipe| {
+            let table = IndexRocksTable::new(db_ref);
+            let index = table.get_row_or_not_found(index_id)?;
+            table.update(
+                index_id,
+                index.row.update_statistics(statistics),
+                &index.row,
+                batch_pipe,
+            )
+        })
+        .await
+    }
+
     async fn create_partitioned_index(
         &self,
         schema: String,
@@ -4947,9 +5106,7 @@ mod tests {
 
     #[test]
     fn macro_test() {
-        let s = Schema {
-            name: "foo".to_string(),
-        };
+        let s = Schema::new("foo".to_string());
         assert_eq!(format_table_value!(s, name, String), "foo");
     }
 
@@ -4970,17 +5127,17 @@ mod tests {
             );
 
             let schema_1 = meta_store
-                .create_schema("foo".to_string(), false)
+                .create_schema("foo".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
             println!("New id: {}", schema_1.id);
             let schema_2 = meta_store
-                .create_schema("bar".to_string(), false)
+                .create_schema("bar".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
             println!("New id: {}", schema_2.id);
             let schema_3 = meta_store
-                .create_schema("boo".to_string(), false)
+                .create_schema("boo".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
             println!("New id: {}", schema_3.id);
@@ -4990,7 +5147,7 @@ mod tests {
             let schema_3_id = schema_3.id;
 
             assert!(meta_store
-                .create_schema("foo".to_string(), false)
+                .create_schema("foo".to_string(), false, SchemaLifecycle::default())
                 .await
                 .is_err());
 
@@ -5023,24 +5180,9 @@ mod tests {
             assert_eq!(
                 meta_store.get_schemas().await.unwrap(),
                 vec![
-                    IdRow::new(
-                        1,
-                        Schema {
-                            name: "foo".to_string()
-                        }
-                    ),
-                    IdRow::new(
-                        2,
-                        Schema {
-                            name: "bar".to_string()
-                        }
-                    ),
-                    IdRow::new(
-                        3,
-                        Schema {
-                            name: "boo".to_string()
-                        }
-                    ),
+                    IdRow::new(1, Schema::new("foo".to_string())),
+                    IdRow::new(2, Schema::new("bar".to_string())),
+                    IdRow::new(3, Schema::new("boo".to_string())),
                 ]
             );
 
@@ -5049,31 +5191,16 @@ mod tests {
                     .rename_schema("foo".to_string(), "foo1".to_string())
                     .await
                     .unwrap(),
-                IdRow::new(
-                    schema_1_id,
-                    Schema {
-                        name: "foo1".to_string()
-                    }
-                )
+                IdRow::new(schema_1_id, Schema::new("foo1".to_string()))
             );
             assert!(meta_store.get_schema("foo".to_string()).await.is_err());
             assert_eq!(
                 meta_store.get_schema("foo1".to_string()).await.unwrap(),
-                IdRow::new(
-                    schema_1_id,
-                    Schema {
-                        name: "foo1".to_string()
-                    }
-                )
+                IdRow::new(schema_1_id, Schema::new("foo1".to_string()))
             );
             assert_eq!(
                 meta_store.get_schema_by_id(schema_1_id).await.unwrap(),
-                IdRow::new(
-                    schema_1_id,
-                    Schema {
-                        name: "foo1".to_string()
-                    }
-                )
+                IdRow::new(schema_1_id, Schema::new("foo1".to_string()))
             );
 
             assert!(meta_store
@@ -5086,31 +5213,16 @@ mod tests {
                     .rename_schema_by_id(schema_2_id, "bar1".to_string())
                     .await
                     .unwrap(),
-                IdRow::new(
-                    schema_2_id,
-                    Schema {
-                        name: "bar1".to_string()
-                    }
-                )
+                IdRow::new(schema_2_id, Schema::new("bar1".to_string()))
             );
             assert!(meta_store.get_schema("bar".to_string()).await.is_err());
             assert_eq!(
                 meta_store.get_schema("bar1".to_string()).await.unwrap(),
-                IdRow::new(
-                    schema_2_id,
-                    Schema {
-                        name: "bar1".to_string()
-                    }
-                )
+                IdRow::new(schema_2_id, Schema::new("bar1".to_string()))
             );
             assert_eq!(
                 meta_store.get_schema_by_id(schema_2_id).await.unwrap(),
-                IdRow::new(
-                    schema_2_id,
-                    Schema {
-                        name: "bar1".to_string()
-                    }
-                )
+                IdRow::new(schema_2_id, Schema::new("bar1".to_string()))
             );
 
             assert_eq!(
@@ -5158,7 +5270,7 @@ mod tests {
             );
 
             meta_store
-                .create_schema("foo".to_string(), false)
+                .create_schema("foo".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
 
@@ -5181,7 +5293,7 @@ mod tests {
             sleep(Duration::from_millis(300));
 
             meta_store
-                .create_schema("foo".to_string(), false)
+                .create_schema("foo".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
         }
@@ -5205,7 +5317,7 @@ mod tests {
             );
 
             let schema_1 = meta_store
-                .create_schema("foo".to_string(), false)
+                .create_schema("foo".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
             let mut columns = Vec::new();
@@ -5231,6 +5343,7 @@ mod tests {
                     vec![],
                     true,
                     None,
+                    vec![],
                     None,
                 )
                 .await
@@ -5248,6 +5361,7 @@ mod tests {
                     vec![],
                     true,
                     None,
+                    vec![],
                     None,
                 )
                 .await
@@ -5261,6 +5375,23 @@ mod tests {
                 table1
             );
 
+            let started_at = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_micros() as u64;
+            let first = meta_store.allocate_table_seq(table1_id, 3).await.unwrap();
+            assert!(first >= started_at);
+            // Values keep increasing even if the clock doesn't.
+            assert!(meta_store.allocate_table_seq(table1_id, 2).await.unwrap() >= first + 3);
+            let table = meta_store.get_table_by_id(table1_id).await.unwrap();
+            assert_eq!(
+                table
+                    .get_row()
+                    .update_last_generated_seq(2, 0)
+                    .last_generated_seq(),
+                table.get_row().last_generated_seq() + 2
+            );
+
             let expected_index = Index::try_new(
                 "default".to_string(),
                 table1_id,
@@ -5289,7 +5420,7 @@ mod tests {
             services.start_processing_loops().await.unwrap();
             services
                 .meta_store
-                .create_schema("foo1".to_string(), false)
+                .create_schema("foo1".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
             services
@@ -5301,7 +5432,7 @@ mod tests {
                 .unwrap();
             services
                 .meta_store
-                .create_schema("foo".to_string(), false)
+                .create_schema("foo".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
             services
@@ -5313,7 +5444,7 @@ mod tests {
                 .unwrap();
             services
                 .meta_store
-                .create_schema("bar".to_string(), false)
+                .create_schema("bar".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
             services
@@ -5365,7 +5496,7 @@ mod tests {
             services.start_processing_loops().await.unwrap();
             services
                 .meta_store
-                .create_schema("foo1".to_string(), false)
+                .create_schema("foo1".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
             while !services
@@ -5387,7 +5518,7 @@ mod tests {
                 .unwrap();
             services
                 .meta_store
-                .create_schema("foo".to_string(), false)
+                .create_schema("foo".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
             while !services
@@ -5409,7 +5540,7 @@ mod tests {
                 .unwrap();
             services
                 .meta_store
-                .create_schema("bar".to_string(), false)
+                .create_schema("bar".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
             while !services
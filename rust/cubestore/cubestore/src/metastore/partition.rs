@@ -1,6 +1,7 @@
 use super::{
     BaseRocksSecondaryIndex, IndexId, Partition, RocksSecondaryIndex, RocksTable, TableId,
 };
+use crate::metastore::statistics::ColumnStatistics;
 use crate::metastore::{IdRow, MetaStoreEvent};
 use crate::rocks_table_impl;
 use crate::table::Row;
@@ -34,6 +35,7 @@ impl Partition {
                     .to_lowercase(),
             ),
             file_size: None,
+            column_statistics: None,
         }
     }
 
@@ -54,6 +56,7 @@ impl Partition {
                     .to_lowercase(),
             ),
             file_size: None,
+            column_statistics: None,
         }
     }
     pub fn get_min_val(&self) -> &Option<Row> {
@@ -154,6 +157,24 @@ impl Partition {
     pub fn suffix(&self) -> &Option<String> {
         &self.suffix
     }
+
+    /// Set by `ANALYZE TABLE`. Partitions created afterwards have no statistics.
+    pub fn column_statistics(&self) -> &Option<Vec<ColumnStatistics>> {
+        &self.column_statistics
+    }
+
+    pub fn update_column_statistics(&self, statistics: Vec<ColumnStatistics>) -> Partition {
+        let mut p = self.clone();
+        p.column_statistics = Some(statistics);
+        p
+    }
+
+    /// Statistics are only needed to plan queries, this keeps them out of plans sent to workers.
+    pub fn without_column_statistics(&self) -> Partition {
+        let mut p = self.clone();
+        p.column_statistics = None;
+        p
+    }
 }
 
 pub fn partition_file_name(partition_id: u64, suffix: &Option<String>) -> String {
@@ -2,17 +2,49 @@ use super::{BaseRocksSecondaryIndex, IndexId, RocksSecondaryIndex, RocksTable, S
 use crate::metastore::{IdRow, MetaStoreEvent};
 use crate::rocks_table_impl;
 use rocksdb::DB;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Retention policy of the tables in a schema, enforced by the scheduler's GC loop.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, Eq, PartialEq, Hash)]
+pub struct SchemaLifecycle {
+    /// How many most recent versions of each pre-aggregation table to keep.
+    pub keep_versions: Option<u64>,
+    /// Tables which were not queried for this long are dropped.
+    pub ttl_secs: Option<u64>,
+}
+
+impl SchemaLifecycle {
+    pub fn is_empty(&self) -> bool {
+        self.keep_versions.is_none() && self.ttl_secs.is_none()
+    }
+}
 
 impl Schema {
     pub fn new(name: String) -> Schema {
-        Schema { name }
+        Schema {
+            name,
+            keep_versions: None,
+            ttl_secs: None,
+        }
+    }
+
+    pub fn with_lifecycle(mut self, lifecycle: SchemaLifecycle) -> Schema {
+        self.keep_versions = lifecycle.keep_versions;
+        self.ttl_secs = lifecycle.ttl_secs;
+        self
     }
 
     pub fn get_name(&self) -> &String {
         &self.name
     }
 
+    pub fn lifecycle(&self) -> SchemaLifecycle {
+        SchemaLifecycle {
+            keep_versions: self.keep_versions,
+            ttl_secs: self.ttl_secs,
+        }
+    }
+
     pub fn set_name(&mut self, name: &String) {
         self.name = name.clone();
     }
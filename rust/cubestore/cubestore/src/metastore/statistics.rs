@@ -0,0 +1,312 @@
+use crate::metastore::{Column, ColumnType, Index};
+use crate::table::{cmp_same_types, TableValue};
+use crate::CubeError;
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Utc};
+use cubehll::HllSketch;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::cmp::{max, Ordering};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Number of buckets in sketches of distinct values. Gives about 3% error.
+const HLL_BUCKETS: u32 = 1024;
+/// Number of buckets in equi-depth histograms.
+const HISTOGRAM_BUCKETS: usize = 16;
+/// Fraction of non-null values assumed to match a range condition when there is no histogram.
+const DEFAULT_RANGE_SELECTIVITY: f64 = 1. / 3.;
+
+/// Statistics of a single column collected by `ANALYZE TABLE`.
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash)]
+pub struct ColumnStatistics {
+    name: String,
+    row_count: u64,
+    null_count: u64,
+    /// HyperLogLog sketch of non-null values, used to merge distinct counts of partitions.
+    /// Index statistics do not keep it, as indexes are sent to workers with every query plan.
+    #[serde(with = "serde_bytes")]
+    distinct_values: Vec<u8>,
+    distinct_count: u64,
+    /// Bounds of an equi-depth histogram of non-null values, i.e. each bucket between two adjacent
+    /// bounds holds about the same number of rows. Empty for unordered types and empty columns.
+    histogram: Vec<TableValue>,
+}
+
+impl ColumnStatistics {
+    /// Collects statistics of `column`, which is at `position` in every batch of `data`.
+    pub fn collect(
+        column: &Column,
+        position: usize,
+        data: &[RecordBatch],
+    ) -> Result<ColumnStatistics, CubeError> {
+        let ordered = match column.get_column_type() {
+            ColumnType::Bytes | ColumnType::HyperLogLog(_) => false,
+            _ => true,
+        };
+        let mut sketch = HllSketch::new(HLL_BUCKETS)?;
+        let mut values = Vec::new();
+        let mut row_count = 0;
+        let mut null_count = 0;
+        for b in data {
+            let a = b.column(position);
+            row_count += a.len() as u64;
+            for i in 0..a.len() {
+                let v = TableValue::from_array(a.as_ref(), i);
+                if v == TableValue::Null {
+                    null_count += 1;
+                    continue;
+                }
+                let mut hasher = DefaultHasher::new();
+                v.hash(&mut hasher);
+                sketch.insert_hash(hasher.finish());
+                if ordered {
+                    values.push(v);
+                }
+            }
+        }
+        values.sort_unstable_by(cmp_same_types);
+
+        let histogram = if values.is_empty() {
+            Vec::new()
+        } else {
+            (0..=HISTOGRAM_BUCKETS)
+                .map(|k| values[k * (values.len() - 1) / HISTOGRAM_BUCKETS].clone())
+                .collect()
+        };
+        Ok(ColumnStatistics {
+            name: column.get_name().clone(),
+            row_count,
+            null_count,
+            distinct_values: sketch.write(),
+            distinct_count: sketch.cardinality().min(row_count - null_count),
+            histogram,
+        })
+    }
+
+    /// Combines statistics of the same column from different partitions.
+    pub fn merge(stats: &[&ColumnStatistics]) -> Result<ColumnStatistics, CubeError> {
+        assert!(!stats.is_empty());
+        let mut sketch = HllSketch::new(HLL_BUCKETS)?;
+        for s in stats {
+            sketch.merge_with(&HllSketch::read(&s.distinct_values)?);
+        }
+        let row_count = stats.iter().map(|s| s.row_count).sum::<u64>();
+        let null_count = stats.iter().map(|s| s.null_count).sum::<u64>();
+
+        // Every bound except the first one closes a bucket with the same share of partition rows.
+        let mut bounds = Vec::new();
+        for s in stats.iter().filter(|s| !s.histogram.is_empty()) {
+            let weight = s.non_null_count() as f64 / (s.histogram.len() - 1).max(1) as f64;
+            bounds.push((&s.histogram[0], 0.));
+            bounds.extend(s.histogram[1..].iter().map(|v| (v, weight)));
+        }
+        bounds.sort_by(|(l, _), (r, _)| cmp_same_types(l, r));
+        let total = bounds.iter().map(|(_, w)| w).sum::<f64>();
+        let mut histogram = Vec::new();
+        if !bounds.is_empty() {
+            let mut cumulative = 0.;
+            let mut j = 0;
+            for k in 0..HISTOGRAM_BUCKETS {
+                let target = total * k as f64 / HISTOGRAM_BUCKETS as f64;
+                while j + 1 < bounds.len() && cumulative < target {
+                    j += 1;
+                    cumulative += bounds[j].1;
+                }
+                histogram.push(bounds[j].0.clone());
+            }
+            histogram.push(bounds[bounds.len() - 1].0.clone());
+        }
+
+        Ok(ColumnStatistics {
+            name: stats[0].name.clone(),
+            row_count,
+            null_count,
+            distinct_values: sketch.write(),
+            distinct_count: sketch.cardinality().min(row_count - null_count),
+            histogram,
+        })
+    }
+
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn row_count(&self) -> u64 {
+        self.row_count
+    }
+
+    pub fn null_count(&self) -> u64 {
+        self.null_count
+    }
+
+    pub fn non_null_count(&self) -> u64 {
+        self.row_count - self.null_count
+    }
+
+    pub fn distinct_count(&self) -> u64 {
+        self.distinct_count
+    }
+
+    pub fn histogram(&self) -> &Vec<TableValue> {
+        &self.histogram
+    }
+
+    /// Estimated fraction of rows with values between `min` and `max`, both inclusive. `None`
+    /// means there is no bound on that side.
+    pub fn selectivity(&self, min: Option<&TableValue>, max: Option<&TableValue>) -> f64 {
+        if self.row_count == 0 {
+            return 0.;
+        }
+        let non_null = self.non_null_count() as f64 / self.row_count as f64;
+        let equal = non_null / max(self.distinct_count, 1) as f64;
+        match (min, max) {
+            (None, None) => return 1.,
+            (Some(l), Some(r)) if l == r => return equal,
+            _ => {}
+        }
+        if self.histogram.is_empty() {
+            return (non_null * DEFAULT_RANGE_SELECTIVITY).max(equal);
+        }
+        let below_max = max.map(|v| self.fraction_below(v)).unwrap_or(1.);
+        let below_min = min.map(|v| self.fraction_below(v)).unwrap_or(0.);
+        (non_null * (below_max - below_min).max(0.)).max(equal)
+    }
+
+    /// Estimated fraction of non-null values that are less than or equal to `v`.
+    fn fraction_below(&self, v: &TableValue) -> f64 {
+        let h = &self.histogram;
+        if cmp_same_types(v, &h[0]) == Ordering::Less {
+            return 0.;
+        }
+        if cmp_same_types(v, &h[h.len() - 1]) != Ordering::Less {
+            return 1.;
+        }
+        let bucket = h
+            .iter()
+            .rposition(|b| cmp_same_types(b, v) != Ordering::Greater)
+            .unwrap();
+        // Assume values are in the middle of their bucket on average.
+        (bucket as f64 + 0.5) / (h.len() - 1) as f64
+    }
+}
+
+/// Statistics of an index, merged from statistics of its partitions by `ANALYZE TABLE`.
+/// These are not updated on writes, so the numbers get outdated until the next analyze.
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash)]
+pub struct IndexStatistics {
+    row_count: u64,
+    analyzed_at: DateTime<Utc>,
+    columns: Vec<ColumnStatistics>,
+}
+
+impl IndexStatistics {
+    /// Partitions without statistics are not accounted for.
+    pub fn merge(
+        index: &Index,
+        partitions: &[&Vec<ColumnStatistics>],
+        analyzed_at: DateTime<Utc>,
+    ) -> Result<IndexStatistics, CubeError> {
+        let mut columns = Vec::with_capacity(index.columns().len());
+        for c in index.columns() {
+            let stats = partitions
+                .iter()
+                .filter_map(|p| p.iter().find(|s| s.name == *c.get_name()))
+                .collect_vec();
+            if !stats.is_empty() {
+                let mut merged = ColumnStatistics::merge(&stats)?;
+                merged.distinct_values = Vec::new();
+                columns.push(merged);
+            }
+        }
+        Ok(IndexStatistics {
+            row_count: columns.first().map(|c| c.row_count).unwrap_or(0),
+            analyzed_at,
+            columns,
+        })
+    }
+
+    pub fn row_count(&self) -> u64 {
+        self.row_count
+    }
+
+    pub fn analyzed_at(&self) -> &DateTime<Utc> {
+        &self.analyzed_at
+    }
+
+    pub fn columns(&self) -> &Vec<ColumnStatistics> {
+        &self.columns
+    }
+
+    pub fn column(&self, name: &str) -> Option<&ColumnStatistics> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, Int64Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    fn batch(ids: Vec<Option<i64>>, names: Vec<&str>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(ids)) as ArrayRef,
+                Arc::new(StringArray::from(names)) as ArrayRef,
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn collect_and_merge() {
+        let id = Column::new("id".to_string(), ColumnType::Int, 0);
+        let name = Column::new("name".to_string(), ColumnType::String, 1);
+
+        let first = vec![batch(
+            (0..1000)
+                .map(|i| if i % 10 == 0 { None } else { Some(i) })
+                .collect(),
+            (0..1000)
+                .map(|i| if i % 2 == 0 { "even" } else { "odd" })
+                .collect(),
+        )];
+        let ids = ColumnStatistics::collect(&id, 0, &first).unwrap();
+        assert_eq!(ids.row_count(), 1000);
+        assert_eq!(ids.null_count(), 100);
+        assert!((ids.distinct_count() as i64 - 900).abs() < 30);
+        assert_eq!(ids.histogram().len(), HISTOGRAM_BUCKETS + 1);
+        assert_eq!(ids.histogram()[0], TableValue::Int(1));
+        assert_eq!(ids.histogram()[HISTOGRAM_BUCKETS], TableValue::Int(999));
+
+        let names = ColumnStatistics::collect(&name, 1, &first).unwrap();
+        assert_eq!(names.distinct_count(), 2);
+        let odd = TableValue::String("odd".to_string());
+        assert_eq!(names.selectivity(Some(&odd), Some(&odd)), 0.5);
+
+        let second = vec![batch(
+            (1000..2000).map(Some).collect(),
+            (0..1000).map(|_| "odd").collect(),
+        )];
+        let ids2 = ColumnStatistics::collect(&id, 0, &second).unwrap();
+        let merged = ColumnStatistics::merge(&[&ids, &ids2]).unwrap();
+        assert_eq!(merged.row_count(), 2000);
+        assert_eq!(merged.null_count(), 100);
+        assert!((merged.distinct_count() as i64 - 1900).abs() < 60);
+        assert_eq!(merged.histogram().len(), HISTOGRAM_BUCKETS + 1);
+        assert_eq!(merged.histogram()[0], TableValue::Int(1));
+        assert_eq!(merged.histogram()[HISTOGRAM_BUCKETS], TableValue::Int(1999));
+
+        let below_1000 = merged.selectivity(None, Some(&TableValue::Int(1000)));
+        assert!(0.35 < below_1000 && below_1000 < 0.6, "{}", below_1000);
+        assert!(merged.selectivity(Some(&TableValue::Int(5000)), None) < 0.001);
+        assert_eq!(merged.selectivity(None, None), 1.);
+    }
+}
@@ -11,6 +11,7 @@ use chrono::Utc;
 use itertools::Itertools;
 use rocksdb::DB;
 use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
 use std::io::Write;
 use std::sync::Arc;
 
@@ -36,8 +37,72 @@ pub struct Table {
     #[serde(default)]
     location_download_sizes: Option<Vec<u64>>,
     #[serde(default)]
-    partition_split_threshold: Option<u64>
+    partition_split_threshold: Option<u64>,
+    #[serde(default)]
+    aggregate_column_indices: Vec<AggregateColumnIndex>,
+    #[serde(default)]
+    last_generated_seq: u64
+}
+}
+
+/// How values of a non-key column are combined when rows with the same unique key meet.
+/// Columns without an aggregate function keep the value of the row with the largest sequence.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Eq, PartialEq, Hash)]
+pub enum AggregateFunction {
+    Sum,
+    Max,
+    Min,
+}
+
+impl AggregateFunction {
+    pub fn from_name(name: &str) -> Option<AggregateFunction> {
+        match name.to_lowercase().as_str() {
+            "sum" => Some(AggregateFunction::Sum),
+            "max" => Some(AggregateFunction::Max),
+            "min" => Some(AggregateFunction::Min),
+            _ => None,
+        }
+    }
+
+    pub fn is_applicable_to(&self, column_type: &ColumnType) -> bool {
+        match (self, column_type) {
+            (_, ColumnType::Int) | (_, ColumnType::Decimal { .. }) | (_, ColumnType::Float) => true,
+            (AggregateFunction::Max, ColumnType::Timestamp)
+            | (AggregateFunction::Min, ColumnType::Timestamp) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for AggregateFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Max => "max",
+            AggregateFunction::Min => "min",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Eq, PartialEq, Hash)]
+pub struct AggregateColumnIndex {
+    index: u64,
+    function: AggregateFunction,
 }
+
+impl AggregateColumnIndex {
+    pub fn new(index: u64, function: AggregateFunction) -> AggregateColumnIndex {
+        AggregateColumnIndex { index, function }
+    }
+
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    pub fn function(&self) -> AggregateFunction {
+        self.function
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
@@ -65,6 +130,7 @@ impl Table {
         unique_key_column_indices: Option<Vec<u64>>,
         seq_column_index: Option<u64>,
         partition_split_threshold: Option<u64>,
+        aggregate_column_indices: Vec<AggregateColumnIndex>,
     ) -> Table {
         let location_download_sizes = locations.as_ref().map(|locations| vec![0; locations.len()]);
         Table {
@@ -80,6 +146,8 @@ impl Table {
             seq_column_index,
             location_download_sizes,
             partition_split_threshold,
+            aggregate_column_indices,
+            last_generated_seq: 0,
         }
     }
     pub fn get_columns(&self) -> &Vec<Column> {
@@ -174,6 +242,33 @@ impl Table {
             .map(|c| &self.columns[*c as usize])
     }
 
+    /// Columns merged with an aggregate function instead of the last value on unique key conflicts.
+    pub fn aggregate_columns(&self) -> Vec<(&Column, AggregateFunction)> {
+        self.aggregate_column_indices
+            .iter()
+            .map(|a| (&self.columns[a.index as usize], a.function))
+            .collect()
+    }
+
+    pub fn aggregate_function(&self, column: &Column) -> Option<AggregateFunction> {
+        self.aggregate_column_indices
+            .iter()
+            .find(|a| a.index as usize == column.get_index())
+            .map(|a| a.function)
+    }
+
+    /// The last value generated for the `__seq` column on inserts that don't provide it.
+    pub fn last_generated_seq(&self) -> u64 {
+        self.last_generated_seq
+    }
+
+    /// Generated values follow `now` and never go back, even if the clock does.
+    pub fn update_last_generated_seq(&self, count: u64, now: u64) -> Self {
+        let mut table = self.clone();
+        table.last_generated_seq = now.max(self.last_generated_seq + 1) + count - 1;
+        table
+    }
+
     pub fn in_memory_ingest(&self) -> bool {
         self.seq_column_index.is_some()
     }
@@ -0,0 +1,514 @@
+use crate::sql::parser::{CubeStoreParser, Statement};
+use crate::sql::{SqlQueryContext, SqlService};
+use crate::CubeError;
+use std::cmp::min;
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tempfile::NamedTempFile;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use uuid::Uuid;
+
+const COM_QUERY: u8 = 0x03;
+const LOCAL_INFILE_REQUEST: u8 = 0xfb;
+const ERR_PACKET: u8 = 0xff;
+const ER_INTERNAL_ERROR: u16 = 1815;
+const MAX_PAYLOAD_LEN: usize = 0xffffff;
+
+/// Location of the file received for the current `LOAD DATA LOCAL INFILE` query. It's set right
+/// before the query is passed to the protocol server and taken by the query handler.
+pub type LocalInfileSlot = Arc<Mutex<Option<String>>>;
+
+type Exchange<S> = Pin<Box<dyn Future<Output = (S, Result<Option<u8>, CubeError>)> + Send>>;
+
+/// Client connection as seen by the protocol server, which has no support for
+/// `LOAD DATA LOCAL INFILE`. Packets go to the server as is until the client sends a COM_QUERY
+/// that parses as such load. Before the server gets this query, the client is asked for the
+/// file, which is uploaded as a temp file and its location is put into `local_infile`. Sequence
+/// ids of the server response are then shifted to follow the packets of the file exchange.
+///
+/// Clients have to enable local files on their side, the server greeting doesn't announce them.
+pub struct LocalInfileStream<S> {
+    /// Taken by the file exchange while it runs.
+    client: Option<S>,
+    sql_service: Arc<dyn SqlService>,
+    local_infile: LocalInfileSlot,
+    /// Bytes read from the client that don't make a whole packet yet.
+    incoming: Vec<u8>,
+    /// Packet to be read by the server, starting at `pending_pos`.
+    pending: Vec<u8>,
+    pending_pos: usize,
+    exchange: Option<Exchange<S>>,
+    /// Added to sequence ids of the response to the current command.
+    seq_offset: u8,
+    written: PacketCursor,
+}
+
+impl<S> LocalInfileStream<S> {
+    pub fn new(
+        client: S,
+        sql_service: Arc<dyn SqlService>,
+        local_infile: LocalInfileSlot,
+    ) -> LocalInfileStream<S> {
+        LocalInfileStream {
+            client: Some(client),
+            sql_service,
+            local_infile,
+            incoming: Vec::new(),
+            pending: Vec::new(),
+            pending_pos: 0,
+            exchange: None,
+            seq_offset: 0,
+            written: PacketCursor::default(),
+        }
+    }
+}
+
+fn exchange_in_progress() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "LOAD DATA LOCAL INFILE exchange is in progress",
+    )
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send + 'static> AsyncRead for LocalInfileStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        loop {
+            if let Some(exchange) = &mut this.exchange {
+                let (client, res) = match exchange.as_mut().poll(cx) {
+                    Poll::Ready(r) => r,
+                    Poll::Pending => return Poll::Pending,
+                };
+                this.exchange = None;
+                this.client = Some(client);
+                match res {
+                    Ok(Some(last_seq)) => {
+                        this.seq_offset = last_seq;
+                        this.written = PacketCursor::default();
+                    }
+                    // The client got an error instead of the query response.
+                    Ok(None) => this.pending.clear(),
+                    Err(e) => return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, e))),
+                }
+            }
+
+            if this.pending_pos < this.pending.len() {
+                let n = min(buf.remaining(), this.pending.len() - this.pending_pos);
+                buf.put_slice(&this.pending[this.pending_pos..this.pending_pos + n]);
+                this.pending_pos += n;
+                return Poll::Ready(Ok(()));
+            }
+
+            if let Some(len) = packet_len(&this.incoming) {
+                this.pending = this.incoming.drain(..len).collect();
+                this.pending_pos = 0;
+                // Each command starts a new sequence.
+                if this.pending[3] == 0 {
+                    this.seq_offset = 0;
+                    if let Some(file_name) = local_infile_file_name(&this.pending[4..]) {
+                        let mut client = this.client.take().unwrap();
+                        let sql_service = this.sql_service.clone();
+                        let local_infile = this.local_infile.clone();
+                        this.exchange = Some(Box::pin(async move {
+                            let res = receive_local_file(
+                                &mut client,
+                                sql_service.as_ref(),
+                                &local_infile,
+                                &file_name,
+                            )
+                            .await;
+                            (client, res)
+                        }));
+                    }
+                }
+                continue;
+            }
+
+            let client = this.client.as_mut().ok_or_else(exchange_in_progress)?;
+            let mut chunk = [0u8; 8192];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            match Pin::new(client).poll_read(cx, &mut chunk) {
+                Poll::Ready(Ok(())) if chunk.filled().is_empty() => return Poll::Ready(Ok(())),
+                Poll::Ready(Ok(())) => this.incoming.extend_from_slice(chunk.filled()),
+                r => return r,
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send + 'static> AsyncWrite for LocalInfileStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let client = this.client.as_mut().ok_or_else(exchange_in_progress)?;
+        if this.seq_offset == 0 {
+            return Pin::new(client).poll_write(cx, buf);
+        }
+        let mut shifted = buf.to_vec();
+        let offset = this.seq_offset;
+        this.written
+            .clone()
+            .advance(buf, |i| shifted[i] = shifted[i].wrapping_add(offset));
+        let n = match Pin::new(client).poll_write(cx, &shifted) {
+            Poll::Ready(Ok(n)) => n,
+            r => return r,
+        };
+        this.written.advance(&buf[..n], |_| {});
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let client = self.client.as_mut().ok_or_else(exchange_in_progress)?;
+        Pin::new(client).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let client = self.client.as_mut().ok_or_else(exchange_in_progress)?;
+        Pin::new(client).poll_shutdown(cx)
+    }
+}
+
+/// Tracks packet boundaries in a stream of bytes.
+#[derive(Clone, Default)]
+struct PacketCursor {
+    /// Bytes of the current packet header seen so far.
+    header: Vec<u8>,
+    payload_left: usize,
+}
+
+impl PacketCursor {
+    /// Moves over `bytes`, calling `on_seq` with the index of every sequence id in them.
+    fn advance(&mut self, bytes: &[u8], mut on_seq: impl FnMut(usize)) {
+        let mut i = 0;
+        while i < bytes.len() {
+            if self.payload_left > 0 {
+                let n = min(self.payload_left, bytes.len() - i);
+                self.payload_left -= n;
+                i += n;
+                continue;
+            }
+            self.header.push(bytes[i]);
+            if self.header.len() == 4 {
+                on_seq(i);
+                self.payload_left = self.header[0] as usize
+                    | (self.header[1] as usize) << 8
+                    | (self.header[2] as usize) << 16;
+                self.header.clear();
+            }
+            i += 1;
+        }
+    }
+}
+
+/// Length of the first packet in `bytes` with its header, if it's complete.
+fn packet_len(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() < 4 {
+        return None;
+    }
+    let len = 4 + (bytes[0] as usize | (bytes[1] as usize) << 8 | (bytes[2] as usize) << 16);
+    if bytes.len() < len {
+        return None;
+    }
+    Some(len)
+}
+
+/// Requests `file_name` from the client and uploads its contents as a temp file, which location
+/// is put into `local_infile`. Returns the sequence id of the last packet of the exchange, or
+/// `None` if the upload failed and the error was reported to the client instead. Network errors
+/// end the connection.
+async fn receive_local_file<S>(
+    client: &mut S,
+    sql_service: &dyn SqlService,
+    local_infile: &LocalInfileSlot,
+    file_name: &str,
+) -> Result<Option<u8>, CubeError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut request = vec![LOCAL_INFILE_REQUEST];
+    request.extend_from_slice(file_name.as_bytes());
+    write_packet(client, 1, &request).await?;
+    client.flush().await?;
+
+    let mut file = create_temp_file(sql_service).await;
+    let mut last_seq;
+    let mut continued = false;
+    loop {
+        let (seq, payload) = read_packet(client).await?.ok_or_else(|| {
+            CubeError::user(format!(
+                "Connection closed while receiving '{}' for LOAD DATA LOCAL INFILE",
+                file_name
+            ))
+        })?;
+        last_seq = seq;
+        // An empty packet ends the file unless it continues a packet of the maximum size.
+        if payload.is_empty() && !continued {
+            break;
+        }
+        continued = payload.len() == MAX_PAYLOAD_LEN;
+        // Keep reading the file after errors to leave the connection in a consistent state.
+        let error = match &mut file {
+            Ok((_, f)) => f.write_all(&payload).await.err(),
+            Err(_) => None,
+        };
+        if let Some(e) = error {
+            file = Err(e.into());
+        }
+    }
+
+    let location = match file {
+        Ok((temp_file, mut f)) => upload_temp_file(sql_service, temp_file, &mut f, file_name).await,
+        Err(e) => Err(e),
+    };
+    match location {
+        Ok(location) => {
+            *local_infile.lock().unwrap() = Some(location);
+            Ok(Some(last_seq))
+        }
+        Err(e) => {
+            write_error(client, last_seq.wrapping_add(1), &e).await?;
+            client.flush().await?;
+            Ok(None)
+        }
+    }
+}
+
+async fn create_temp_file(
+    sql_service: &dyn SqlService,
+) -> Result<(NamedTempFile, tokio::fs::File), CubeError> {
+    let temp_file = NamedTempFile::new_in(
+        sql_service
+            .temp_uploads_dir(SqlQueryContext::default())
+            .await?,
+    )?;
+    let file = tokio::fs::File::create(temp_file.path()).await?;
+    Ok((temp_file, file))
+}
+
+async fn upload_temp_file(
+    sql_service: &dyn SqlService,
+    temp_file: NamedTempFile,
+    file: &mut tokio::fs::File,
+    file_name: &str,
+) -> Result<String, CubeError> {
+    file.flush().await?;
+    // Keep the original name, its extension tells whether the file is compressed.
+    let base_name = Path::new(file_name)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = format!("local-infile-{}-{}", Uuid::new_v4(), base_name);
+    sql_service
+        .upload_temp_file(SqlQueryContext::default(), name.clone(), temp_file.path())
+        .await?;
+    Ok(format!("temp://{}", name))
+}
+
+fn local_infile_file_name(payload: &[u8]) -> Option<String> {
+    if payload.first() != Some(&COM_QUERY) {
+        return None;
+    }
+    let query = std::str::from_utf8(&payload[1..]).ok()?;
+    // Avoid parsing queries that can't be loads.
+    if !query.trim_start().get(..4)?.eq_ignore_ascii_case("load") {
+        return None;
+    }
+    match CubeStoreParser::new(query).ok()?.parse_statement().ok()? {
+        Statement::LoadData {
+            local: true,
+            location,
+            ..
+        } => Some(location),
+        _ => None,
+    }
+}
+
+async fn read_packet<R: AsyncRead + Unpin>(r: &mut R) -> Result<Option<(u8, Vec<u8>)>, CubeError> {
+    let mut header = [0u8; 4];
+    match r.read_exact(&mut header).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = header[0] as usize | (header[1] as usize) << 8 | (header[2] as usize) << 16;
+    let mut payload = vec![0; len];
+    r.read_exact(&mut payload).await?;
+    Ok(Some((header[3], payload)))
+}
+
+async fn write_packet<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    seq: u8,
+    payload: &[u8],
+) -> Result<(), CubeError> {
+    let len = payload.len();
+    w.write_all(&[len as u8, (len >> 8) as u8, (len >> 16) as u8, seq])
+        .await?;
+    w.write_all(payload).await?;
+    Ok(())
+}
+
+async fn write_error<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    seq: u8,
+    e: &CubeError,
+) -> Result<(), CubeError> {
+    let mut payload = vec![ERR_PACKET];
+    payload.extend_from_slice(&ER_INTERNAL_ERROR.to_le_bytes());
+    payload.extend_from_slice(b"#HY000");
+    payload.extend_from_slice(e.message.as_bytes());
+    write_packet(w, seq, &payload).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::QueryPlans;
+    use crate::store::DataFrame;
+    use async_trait::async_trait;
+    use tempfile::TempDir;
+
+    struct UploadService {
+        dir: TempDir,
+        uploads: Mutex<Vec<(String, Vec<u8>)>>,
+    }
+
+    crate::di_service!(UploadService, [SqlService]);
+
+    #[async_trait]
+    impl SqlService for UploadService {
+        async fn exec_query(&self, _query: &str) -> Result<Arc<DataFrame>, CubeError> {
+            unimplemented!()
+        }
+
+        async fn exec_query_with_context(
+            &self,
+            _context: SqlQueryContext,
+            _query: &str,
+        ) -> Result<Arc<DataFrame>, CubeError> {
+            unimplemented!()
+        }
+
+        async fn plan_query(&self, _query: &str) -> Result<QueryPlans, CubeError> {
+            unimplemented!()
+        }
+
+        async fn upload_temp_file(
+            &self,
+            _context: SqlQueryContext,
+            name: String,
+            file_path: &Path,
+        ) -> Result<(), CubeError> {
+            let contents = std::fs::read(file_path)?;
+            self.uploads.lock().unwrap().push((name, contents));
+            Ok(())
+        }
+
+        async fn temp_uploads_dir(&self, _context: SqlQueryContext) -> Result<String, CubeError> {
+            Ok(self.dir.path().to_string_lossy().to_string())
+        }
+    }
+
+    fn query(q: &str) -> Vec<u8> {
+        let mut payload = vec![COM_QUERY];
+        payload.extend_from_slice(q.as_bytes());
+        payload
+    }
+
+    #[tokio::test]
+    async fn local_infile_exchange() {
+        let service = Arc::new(UploadService {
+            dir: tempfile::tempdir().unwrap(),
+            uploads: Mutex::new(Vec::new()),
+        });
+        let local_infile = LocalInfileSlot::default();
+        let (mut client, socket) = tokio::io::duplex(1024);
+        let mut server = LocalInfileStream::new(socket, service.clone(), local_infile.clone());
+
+        let load = query("LOAD DATA LOCAL INFILE '/tmp/data.csv' INTO TABLE foo.bar");
+        write_packet(&mut client, 0, &load).await.unwrap();
+        client.flush().await.unwrap();
+        let received = tokio::spawn(async move {
+            let packet = read_packet(&mut server).await.unwrap().unwrap();
+            (server, packet)
+        });
+        let (seq, request) = read_packet(&mut client).await.unwrap().unwrap();
+        assert_eq!(seq, 1);
+        assert_eq!(request, b"\xfb/tmp/data.csv".to_vec());
+
+        write_packet(&mut client, 2, b"id\n1\n").await.unwrap();
+        write_packet(&mut client, 3, b"2\n").await.unwrap();
+        write_packet(&mut client, 4, b"").await.unwrap();
+        client.flush().await.unwrap();
+
+        // The server gets the original query with the upload location.
+        let (mut server, (seq, received)) = received.await.unwrap();
+        assert_eq!(seq, 0);
+        assert_eq!(received, load);
+        let location = local_infile.lock().unwrap().take().unwrap();
+        {
+            let uploads = service.uploads.lock().unwrap();
+            assert_eq!(uploads.len(), 1);
+            assert_eq!(format!("temp://{}", uploads[0].0), location);
+            assert!(uploads[0].0.ends_with("-data.csv"));
+            assert_eq!(uploads[0].1, b"id\n1\n2\n".to_vec());
+        }
+
+        // OK packet follows the packets of the exchange, even if it's written in parts.
+        write_packet(&mut server, 1, b"\x00\x02\x00\x00\x00")
+            .await
+            .unwrap();
+        server.write_all(&[1, 0]).await.unwrap();
+        server.write_all(&[0, 2, 0]).await.unwrap();
+        server.flush().await.unwrap();
+        let (seq, _) = read_packet(&mut client).await.unwrap().unwrap();
+        assert_eq!(seq, 5);
+        assert_eq!(
+            read_packet(&mut client).await.unwrap().unwrap(),
+            (6, b"\x00".to_vec())
+        );
+
+        // Regular queries are passed as is.
+        let select = query("SELECT 1");
+        write_packet(&mut client, 0, &select).await.unwrap();
+        client.flush().await.unwrap();
+        assert_eq!(
+            read_packet(&mut server).await.unwrap().unwrap(),
+            (0, select)
+        );
+        write_packet(&mut server, 1, b"\x01").await.unwrap();
+        server.flush().await.unwrap();
+        assert_eq!(
+            read_packet(&mut client).await.unwrap().unwrap(),
+            (1, b"\x01".to_vec())
+        );
+        assert!(local_infile.lock().unwrap().is_none());
+
+        drop(client);
+        assert_eq!(read_packet(&mut server).await.unwrap(), None);
+    }
+
+    #[test]
+    fn local_infile_queries() {
+        assert_eq!(
+            local_infile_file_name(&query("load data local infile 'a.csv' into table foo.bar")),
+            Some("a.csv".to_string())
+        );
+        assert_eq!(local_infile_file_name(&query("SELECT 1")), None);
+        assert_eq!(
+            local_infile_file_name(&query("LOAD DATA INFILE 'a.csv' INTO TABLE foo.bar")),
+            None
+        );
+    }
+}
@@ -1,4 +1,6 @@
 use crate::config::processing_loop::ProcessingLoop;
+use crate::mysql::local_infile::{LocalInfileSlot, LocalInfileStream};
+use crate::sql::query_pools::parse_set_query_pool;
 use crate::sql::{SqlQueryContext, SqlService};
 use crate::table::TableValue;
 use crate::util::time_span::warn_long;
@@ -16,10 +18,14 @@ use std::time::SystemTime;
 use tokio::net::TcpListener;
 use tokio::sync::{watch, RwLock};
 
+mod local_infile;
+
 struct Backend {
     sql_service: Arc<dyn SqlService>,
     auth: Arc<dyn SqlAuthService>,
     user: Option<String>,
+    query_pool: Option<String>,
+    local_infile: LocalInfileSlot,
 }
 
 #[async_trait]
@@ -61,6 +67,8 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for Backend {
                 SqlQueryContext {
                     user: self.user.clone(),
                     trace_obj: None,
+                    query_pool: self.query_pool.clone(),
+                    local_infile: self.local_infile.lock().unwrap().take(),
                 },
                 query,
             )
@@ -74,6 +82,9 @@ impl<W: io::Write + Send> AsyncMysqlShim<W> for Backend {
             results.error(ErrorKind::ER_INTERNAL_ERROR, e.message.as_bytes())?;
             return Ok(());
         }
+        if let Some(query_pool) = parse_set_query_pool(query) {
+            self.query_pool = Some(query_pool);
+        }
         let _s = warn_long("sending query results", Duration::from_millis(100));
         let data_frame = res.unwrap();
         let columns = data_frame
@@ -187,16 +198,19 @@ impl ProcessingLoop for MySqlServer {
                 }
             };
 
+            let local_infile = LocalInfileSlot::default();
             let sql_service = self.sql_service.clone();
             let auth = self.auth.clone();
             cube_ext::spawn(async move {
                 if let Err(e) = AsyncMysqlIntermediary::run_on(
                     Backend {
-                        sql_service,
+                        sql_service: sql_service.clone(),
                         auth,
                         user: None,
+                        query_pool: None,
+                        local_infile: local_infile.clone(),
                     },
-                    socket,
+                    LocalInfileStream::new(socket, sql_service, local_infile),
                 )
                 .await
                 {
@@ -0,0 +1,371 @@
+use crate::queryplanner::query_memory::{batch_memory_size, MemoryReservation, QueryMemory};
+use arrow::datatypes::SchemaRef;
+use arrow::error::Result as ArrowResult;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::memory::MemoryExec;
+use datafusion::physical_plan::{
+    ExecutionPlan, OptimizerHints, Partitioning, RecordBatchStream, SendableRecordBatchStream,
+};
+use futures::{Stream, StreamExt};
+use log::debug;
+use std::any::Any;
+use std::fmt::{Debug, Formatter};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// Picks between two plans over the same single partition `input` at execution time. The input
+/// is buffered while it fits into the memory limit of the query. If it does, `in_memory` plan
+/// (e.g. Sort or HashAggregate) runs over the buffered batches. Otherwise, the query switches to
+/// `spilling` plan which sorts the input with [super::spill_sort::SpillSortExec].
+///
+/// `in_memory` plan may keep its own state on top of the buffered input, e.g. the groups of
+/// HashAggregate. It can't be measured while the plan runs, so every input row reserves
+/// `state_bytes_per_row` upfront and the query spills before the state grows over the limit.
+#[derive(Debug)]
+pub struct AdaptiveSpillExec {
+    input: Arc<dyn ExecutionPlan>,
+    in_memory: Arc<dyn ExecutionPlan>,
+    spilling: Arc<dyn ExecutionPlan>,
+    memory: Arc<QueryMemory>,
+    state_bytes_per_row: u64,
+}
+
+impl AdaptiveSpillExec {
+    /// Both `in_memory` and `spilling` must read `input`.
+    pub fn new(
+        input: Arc<dyn ExecutionPlan>,
+        in_memory: Arc<dyn ExecutionPlan>,
+        spilling: Arc<dyn ExecutionPlan>,
+        memory: Arc<QueryMemory>,
+        state_bytes_per_row: u64,
+    ) -> AdaptiveSpillExec {
+        AdaptiveSpillExec {
+            input,
+            in_memory,
+            spilling,
+            memory,
+            state_bytes_per_row,
+        }
+    }
+
+    pub fn in_memory(&self) -> &Arc<dyn ExecutionPlan> {
+        &self.in_memory
+    }
+}
+
+#[async_trait]
+impl ExecutionPlan for AdaptiveSpillExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.in_memory.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        Partitioning::UnknownPartitioning(1)
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        &self,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+        assert_eq!(children.len(), 1);
+        let input = children.into_iter().next().unwrap();
+        Ok(Arc::new(AdaptiveSpillExec {
+            in_memory: replace_input(&self.in_memory, &self.input, input.clone())?,
+            spilling: replace_input(&self.spilling, &self.input, input.clone())?,
+            input,
+            memory: self.memory.clone(),
+            state_bytes_per_row: self.state_bytes_per_row,
+        }))
+    }
+
+    fn output_hints(&self) -> OptimizerHints {
+        // Spilling plan produces the same rows, sorted at least in the same way.
+        self.in_memory.output_hints()
+    }
+
+    async fn execute(
+        &self,
+        partition: usize,
+    ) -> Result<SendableRecordBatchStream, DataFusionError> {
+        if partition != 0 {
+            return Err(DataFusionError::Internal(format!(
+                "AdaptiveSpillExec invalid partition {}",
+                partition
+            )));
+        }
+
+        let schema = self.input.schema();
+        let mut input = self.input.execute(0).await?;
+        let mut buffered = Vec::new();
+        let mut reservation: Option<MemoryReservation> = None;
+        while let Some(batch) = input.next().await {
+            let batch = batch?;
+            let size =
+                batch_memory_size(&batch) + batch.num_rows() as u64 * self.state_bytes_per_row;
+            match self.memory.try_reserve(size) {
+                Some(r) => match &mut reservation {
+                    Some(reservation) => reservation.merge(r),
+                    None => reservation = Some(r),
+                },
+                None => {
+                    debug!(
+                        "Input does not fit into {} bytes of query memory, spilling to disk",
+                        self.memory.limit()
+                    );
+                    // Spilling plan reserves the buffered batches again as it reads them.
+                    drop(reservation);
+                    buffered.push(batch);
+                    let replay = Arc::new(ReplayExec {
+                        schema,
+                        input: Mutex::new(Some((buffered, input))),
+                    });
+                    return replace_input(&self.spilling, &self.input, replay)?
+                        .execute(0)
+                        .await;
+                }
+            }
+            buffered.push(batch);
+        }
+
+        let buffered = Arc::new(MemoryExec::try_new(&[buffered], schema, None)?);
+        let output = replace_input(&self.in_memory, &self.input, buffered)?
+            .execute(0)
+            .await?;
+        Ok(Box::pin(AdaptiveStream {
+            schema: output.schema(),
+            inner: Box::pin(output),
+            _reservation: reservation,
+        }))
+    }
+}
+
+/// Replaces `input` that is read by `plan` or one of its descendants.
+fn replace_input(
+    plan: &Arc<dyn ExecutionPlan>,
+    input: &Arc<dyn ExecutionPlan>,
+    new_input: Arc<dyn ExecutionPlan>,
+) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+    let mut children = plan.children();
+    if children.len() != 1 {
+        return Err(DataFusionError::Internal(
+            "AdaptiveSpillExec expects plans with a single input".to_string(),
+        ));
+    }
+    let child = children.pop().unwrap();
+    let child = if Arc::ptr_eq(&child, input) {
+        new_input
+    } else {
+        replace_input(&child, input, new_input)?
+    };
+    plan.with_new_children(vec![child])
+}
+
+/// Replays batches that were already read from the input followed by the rest of it.
+struct ReplayExec {
+    schema: SchemaRef,
+    input: Mutex<Option<(Vec<RecordBatch>, SendableRecordBatchStream)>>,
+}
+
+impl Debug for ReplayExec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ReplayExec")
+    }
+}
+
+#[async_trait]
+impl ExecutionPlan for ReplayExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        Partitioning::UnknownPartitioning(1)
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![]
+    }
+
+    fn with_new_children(
+        &self,
+        _children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+        Err(DataFusionError::Internal(
+            "ReplayExec does not have children".to_string(),
+        ))
+    }
+
+    async fn execute(
+        &self,
+        partition: usize,
+    ) -> Result<SendableRecordBatchStream, DataFusionError> {
+        if partition != 0 {
+            return Err(DataFusionError::Internal(format!(
+                "ReplayExec invalid partition {}",
+                partition
+            )));
+        }
+        let (buffered, rest) = match self.input.lock().unwrap().take() {
+            Some(input) => input,
+            None => {
+                return Err(DataFusionError::Internal(
+                    "ReplayExec can only be executed once".to_string(),
+                ))
+            }
+        };
+        Ok(Box::pin(AdaptiveStream {
+            schema: self.schema.clone(),
+            inner: Box::pin(futures::stream::iter(buffered.into_iter().map(Ok)).chain(rest)),
+            _reservation: None,
+        }))
+    }
+}
+
+struct AdaptiveStream {
+    schema: SchemaRef,
+    inner: Pin<Box<dyn Stream<Item = ArrowResult<RecordBatch>> + Send>>,
+    /// Memory of the buffered input, held until the output is consumed.
+    _reservation: Option<MemoryReservation>,
+}
+
+impl Stream for AdaptiveStream {
+    type Item = ArrowResult<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+impl RecordBatchStream for AdaptiveStream {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queryplanner::spill_sort::SpillSortExec;
+    use arrow::array::Int64Array;
+    use arrow::compute::SortOptions;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::physical_plan::collect;
+    use datafusion::physical_plan::expressions::{Column, PhysicalSortExpr};
+    use datafusion::physical_plan::sort::SortExec;
+    use std::path::PathBuf;
+
+    fn input_batches() -> Vec<RecordBatch> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, false)]));
+        (0..5)
+            .map(|i| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int64Array::from(vec![(i * 7) % 5, 10 - i]))],
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    async fn sort(memory_limit: u64) -> (Vec<i64>, u64) {
+        let batches = input_batches();
+        let schema = batches[0].schema();
+        let input: Arc<dyn ExecutionPlan> =
+            Arc::new(MemoryExec::try_new(&[batches], schema.clone(), None).unwrap());
+        let expr = vec![PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions::default(),
+        }];
+        let dir = tempfile::tempdir().unwrap();
+        let memory = QueryMemory::new(memory_limit, dir.path().to_path_buf());
+        let plan = AdaptiveSpillExec::new(
+            input.clone(),
+            Arc::new(SortExec::try_new(expr.clone(), input.clone()).unwrap()),
+            Arc::new(SpillSortExec::new(input, expr, memory.clone(), 2)),
+            memory.clone(),
+            0,
+        );
+
+        let result = collect(Arc::new(plan)).await.unwrap();
+        assert_eq!(memory.used(), 0);
+        let values = result
+            .iter()
+            .flat_map(|b| {
+                let a = b.column(0).as_any().downcast_ref::<Int64Array>().unwrap();
+                (0..a.len()).map(|i| a.value(i)).collect::<Vec<_>>()
+            })
+            .collect();
+        (values, memory.peak())
+    }
+
+    /// Runs ascending sort in memory and descending one when spilling.
+    async fn chosen_plan(memory_limit: u64, state_bytes_per_row: u64) -> Vec<i64> {
+        let batches = input_batches();
+        let schema = batches[0].schema();
+        let input: Arc<dyn ExecutionPlan> =
+            Arc::new(MemoryExec::try_new(&[batches], schema, None).unwrap());
+        let sort = |descending| {
+            let expr = vec![PhysicalSortExpr {
+                expr: Arc::new(Column::new("a", 0)),
+                options: SortOptions {
+                    descending,
+                    nulls_first: false,
+                },
+            }];
+            Arc::new(SortExec::try_new(expr, input.clone()).unwrap())
+        };
+        let memory = QueryMemory::new(memory_limit, PathBuf::new());
+        let plan = AdaptiveSpillExec::new(
+            input.clone(),
+            sort(false),
+            sort(true),
+            memory.clone(),
+            state_bytes_per_row,
+        );
+        let result = collect(Arc::new(plan)).await.unwrap();
+        assert_eq!(memory.used(), 0);
+        let a = result[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        (0..a.len()).map(|i| a.value(i)).collect()
+    }
+
+    #[tokio::test]
+    async fn reserves_state_of_in_memory_plan() {
+        let batch_size = batch_memory_size(&input_batches()[0]);
+        let in_memory = chosen_plan(5 * batch_size, 0).await;
+        assert_eq!(in_memory, vec![0, 1, 2, 3, 4, 6, 7, 8, 9, 10]);
+        // Input fits into the limit, but not together with the state of the in-memory plan.
+        let spilled = chosen_plan(5 * batch_size, batch_size).await;
+        assert_eq!(spilled, vec![10, 9, 8, 7, 6, 4, 3, 2, 1, 0]);
+    }
+
+    #[tokio::test]
+    async fn switches_to_spilling_plan() {
+        let batch_size = batch_memory_size(&input_batches()[0]);
+        let (in_memory, in_memory_peak) = sort(0).await;
+        let (spilled, spilled_peak) = sort(batch_size).await;
+        assert_eq!(in_memory, vec![0, 1, 2, 3, 4, 6, 7, 8, 9, 10]);
+        assert_eq!(spilled, in_memory);
+        // Unlimited query keeps all of the input in memory, the spilling one a batch at a time.
+        assert_eq!(in_memory_peak, 5 * batch_size);
+        assert!(spilled_peak < in_memory_peak);
+    }
+}
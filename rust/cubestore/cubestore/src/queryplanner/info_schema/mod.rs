@@ -1,7 +1,10 @@
 pub mod info_schema_schemata;
 pub mod info_schema_tables;
 pub mod system_chunks;
+pub mod system_gc_candidates;
 pub mod system_indexes;
 pub mod system_jobs;
 pub mod system_partitions;
+pub mod system_query_history;
+pub mod system_query_pools;
 pub mod system_tables;
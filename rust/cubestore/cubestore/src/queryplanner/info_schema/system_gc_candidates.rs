@@ -0,0 +1,114 @@
+use crate::queryplanner::{InfoSchemaTableDef, InfoSchemaTableDefContext};
+use crate::scheduler::lifecycle::{gc_candidates, GCCandidate};
+use crate::CubeError;
+use arrow::array::{ArrayRef, StringArray, TimestampNanosecondArray, UInt64Array};
+use arrow::datatypes::{DataType, Field, TimeUnit};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::sync::Arc;
+
+pub struct SystemGCCandidatesTableDef;
+
+#[async_trait]
+impl InfoSchemaTableDef for SystemGCCandidatesTableDef {
+    type T = GCCandidate;
+
+    async fn rows(&self, ctx: InfoSchemaTableDefContext) -> Result<Arc<Vec<Self::T>>, CubeError> {
+        Ok(Arc::new(
+            gc_candidates(
+                ctx.meta_store.as_ref(),
+                ctx.table_access.as_ref(),
+                Utc::now(),
+            )
+            .await?,
+        ))
+    }
+
+    fn columns(&self) -> Vec<(Field, Box<dyn Fn(Arc<Vec<Self::T>>) -> ArrayRef>)> {
+        vec![
+            (
+                Field::new("table_id", DataType::UInt64, false),
+                Box::new(|candidates| {
+                    Arc::new(UInt64Array::from(
+                        candidates
+                            .iter()
+                            .map(|c| c.table.table.get_id())
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("table_schema", DataType::Utf8, false),
+                Box::new(|candidates| {
+                    Arc::new(StringArray::from(
+                        candidates
+                            .iter()
+                            .map(|c| c.table.schema.get_row().get_name().as_str())
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("table_name", DataType::Utf8, false),
+                Box::new(|candidates| {
+                    Arc::new(StringArray::from(
+                        candidates
+                            .iter()
+                            .map(|c| c.table.table.get_row().get_table_name().as_str())
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("reason", DataType::Utf8, false),
+                Box::new(|candidates| {
+                    Arc::new(StringArray::from(
+                        candidates
+                            .iter()
+                            .map(|c| c.reason.to_string())
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new(
+                    "created_at",
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    true,
+                ),
+                Box::new(|candidates| {
+                    Arc::new(TimestampNanosecondArray::from(
+                        candidates
+                            .iter()
+                            .map(|c| {
+                                c.table
+                                    .table
+                                    .get_row()
+                                    .created_at()
+                                    .as_ref()
+                                    .map(|t| t.timestamp_nanos())
+                            })
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new(
+                    "last_accessed_at",
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    true,
+                ),
+                Box::new(|candidates| {
+                    Arc::new(TimestampNanosecondArray::from(
+                        candidates
+                            .iter()
+                            .map(|c| c.last_accessed_at.map(|t| t.timestamp_nanos()))
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+        ]
+    }
+}
+
+crate::base_info_schema_table_def!(SystemGCCandidatesTableDef);
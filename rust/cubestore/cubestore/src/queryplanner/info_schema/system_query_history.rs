@@ -0,0 +1,126 @@
+use crate::queryplanner::{InfoSchemaTableDef, InfoSchemaTableDefContext};
+use crate::sql::query_history::QueryHistoryEntry;
+use crate::CubeError;
+use arrow::array::{ArrayRef, BooleanArray, StringArray, TimestampNanosecondArray, UInt64Array};
+use arrow::datatypes::{DataType, Field, TimeUnit};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+pub struct SystemQueryHistoryTableDef;
+
+#[async_trait]
+impl InfoSchemaTableDef for SystemQueryHistoryTableDef {
+    type T = QueryHistoryEntry;
+
+    async fn rows(&self, ctx: InfoSchemaTableDefContext) -> Result<Arc<Vec<Self::T>>, CubeError> {
+        Ok(Arc::new(ctx.query_history.entries()))
+    }
+
+    fn columns(&self) -> Vec<(Field, Box<dyn Fn(Arc<Vec<Self::T>>) -> ArrayRef>)> {
+        vec![
+            (
+                Field::new("id", DataType::UInt64, false),
+                Box::new(|queries| {
+                    Arc::new(UInt64Array::from(
+                        queries.iter().map(|q| q.id).collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new(
+                    "started_at",
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    false,
+                ),
+                Box::new(|queries| {
+                    Arc::new(TimestampNanosecondArray::from(
+                        queries
+                            .iter()
+                            .map(|q| q.started_at.timestamp_nanos())
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("query", DataType::Utf8, false),
+                Box::new(|queries| {
+                    Arc::new(StringArray::from(
+                        queries.iter().map(|q| q.query.as_str()).collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("user", DataType::Utf8, true),
+                Box::new(|queries| {
+                    Arc::new(StringArray::from(
+                        queries
+                            .iter()
+                            .map(|q| q.user.as_deref())
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("duration_ms", DataType::UInt64, false),
+                Box::new(|queries| {
+                    Arc::new(UInt64Array::from(
+                        queries.iter().map(|q| q.duration_ms).collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("rows", DataType::UInt64, true),
+                Box::new(|queries| {
+                    Arc::new(UInt64Array::from(
+                        queries.iter().map(|q| q.rows).collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("bytes_scanned", DataType::UInt64, false),
+                Box::new(|queries| {
+                    Arc::new(UInt64Array::from(
+                        queries.iter().map(|q| q.bytes_scanned).collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("partitions", DataType::UInt64, false),
+                Box::new(|queries| {
+                    Arc::new(UInt64Array::from(
+                        queries.iter().map(|q| q.partitions).collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("chunks", DataType::UInt64, false),
+                Box::new(|queries| {
+                    Arc::new(UInt64Array::from(
+                        queries.iter().map(|q| q.chunks).collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("cache_hit", DataType::Boolean, false),
+                Box::new(|queries| {
+                    Arc::new(BooleanArray::from(
+                        queries.iter().map(|q| q.cache_hit).collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("error", DataType::Utf8, true),
+                Box::new(|queries| {
+                    Arc::new(StringArray::from(
+                        queries
+                            .iter()
+                            .map(|q| q.error.as_deref())
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+        ]
+    }
+}
+
+crate::base_info_schema_table_def!(SystemQueryHistoryTableDef);
@@ -0,0 +1,90 @@
+use crate::queryplanner::{InfoSchemaTableDef, InfoSchemaTableDefContext};
+use crate::sql::query_pools::QueryPoolState;
+use crate::CubeError;
+use arrow::array::{ArrayRef, Int64Array, StringArray, UInt64Array};
+use arrow::datatypes::{DataType, Field};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+pub struct SystemQueryPoolsTableDef;
+
+#[async_trait]
+impl InfoSchemaTableDef for SystemQueryPoolsTableDef {
+    type T = QueryPoolState;
+
+    async fn rows(&self, ctx: InfoSchemaTableDefContext) -> Result<Arc<Vec<Self::T>>, CubeError> {
+        Ok(Arc::new(ctx.query_pools.states()))
+    }
+
+    fn columns(&self) -> Vec<(Field, Box<dyn Fn(Arc<Vec<Self::T>>) -> ArrayRef>)> {
+        vec![
+            (
+                Field::new("name", DataType::Utf8, false),
+                Box::new(|pools| {
+                    Arc::new(StringArray::from(
+                        pools
+                            .iter()
+                            .map(|p| p.config.name.as_str())
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("concurrency", DataType::UInt64, false),
+                Box::new(|pools| {
+                    Arc::new(UInt64Array::from(
+                        pools
+                            .iter()
+                            .map(|p| p.config.concurrency as u64)
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("memory_limit", DataType::UInt64, false),
+                Box::new(|pools| {
+                    Arc::new(UInt64Array::from(
+                        pools
+                            .iter()
+                            .map(|p| p.config.memory_limit)
+                            .collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("priority", DataType::Int64, false),
+                Box::new(|pools| {
+                    Arc::new(Int64Array::from(
+                        pools.iter().map(|p| p.config.priority).collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("running", DataType::UInt64, false),
+                Box::new(|pools| {
+                    Arc::new(UInt64Array::from(
+                        pools.iter().map(|p| p.running as u64).collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("queued", DataType::UInt64, false),
+                Box::new(|pools| {
+                    Arc::new(UInt64Array::from(
+                        pools.iter().map(|p| p.queued as u64).collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+            (
+                Field::new("memory_used", DataType::UInt64, false),
+                Box::new(|pools| {
+                    Arc::new(UInt64Array::from(
+                        pools.iter().map(|p| p.memory_used).collect::<Vec<_>>(),
+                    ))
+                }),
+            ),
+        ]
+    }
+}
+
+crate::base_info_schema_table_def!(SystemQueryPoolsTableDef);
@@ -0,0 +1,412 @@
+use crate::metastore::table::AggregateFunction;
+use crate::metastore::ColumnType;
+use crate::queryplanner::query_executor::arrow_to_column_type;
+use crate::table::data::{append_value, cmp_partition_key, cmp_same_types, create_array_builder};
+use crate::table::TableValue;
+use crate::util::decimal::Decimal;
+use arrow::array::{ArrayBuilder, UInt32Array};
+use arrow::compute::{concat, take};
+use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use datafusion::cube_ext::ordfloat::OrdF64;
+use datafusion::cube_ext::stream::StreamWithSchema;
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::expressions::Column;
+use datafusion::physical_plan::{
+    Distribution, ExecutionPlan, OptimizerHints, Partitioning, SendableRecordBatchStream,
+};
+use futures::StreamExt;
+use itertools::Itertools;
+use std::any::Any;
+use std::cmp::Ordering;
+use std::mem::replace;
+use std::sync::Arc;
+
+/// Collapses rows with the same unique key into one. Aggregate columns are combined with their
+/// function, other columns are taken from the last row of the key.
+/// Input must be sorted by the key columns, with rows of the same key ordered by sequence.
+#[derive(Debug)]
+pub struct MergeByUniqueKeyExec {
+    input: Arc<dyn ExecutionPlan>,
+    key_columns: Vec<Column>,
+    aggregates: Vec<(Column, AggregateFunction)>,
+}
+
+impl MergeByUniqueKeyExec {
+    pub fn try_new(
+        input: Arc<dyn ExecutionPlan>,
+        key_columns: Vec<Column>,
+        aggregates: Vec<(Column, AggregateFunction)>,
+    ) -> Result<Self, DataFusionError> {
+        if key_columns.is_empty() {
+            return Err(DataFusionError::Internal(
+                "Empty key columns for MergeByUniqueKeyExec".to_string(),
+            ));
+        }
+        Ok(MergeByUniqueKeyExec {
+            input,
+            key_columns,
+            aggregates,
+        })
+    }
+
+    pub fn aggregates(&self) -> &[(Column, AggregateFunction)] {
+        &self.aggregates
+    }
+}
+
+#[async_trait]
+impl ExecutionPlan for MergeByUniqueKeyExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        Partitioning::UnknownPartitioning(1)
+    }
+
+    fn required_child_distribution(&self) -> Distribution {
+        Distribution::SinglePartition
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        &self,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+        assert_eq!(children.len(), 1);
+        Ok(Arc::new(MergeByUniqueKeyExec {
+            input: children[0].clone(),
+            key_columns: self.key_columns.clone(),
+            aggregates: self.aggregates.clone(),
+        }))
+    }
+
+    fn output_hints(&self) -> OptimizerHints {
+        // Aggregated values may differ even if the inputs are the same.
+        let mut hints = self.input.output_hints();
+        hints
+            .single_value_columns
+            .retain(|c| !self.aggregates.iter().any(|(a, _)| a.index() == *c));
+        hints
+    }
+
+    async fn execute(
+        &self,
+        partition: usize,
+    ) -> Result<SendableRecordBatchStream, DataFusionError> {
+        if partition != 0 {
+            return Err(DataFusionError::Internal(format!(
+                "MergeByUniqueKeyExec invalid partition {}",
+                partition
+            )));
+        }
+        let schema = self.schema();
+        let mut aggregates = Vec::with_capacity(self.aggregates.len());
+        for (c, f) in &self.aggregates {
+            let column_type = arrow_to_column_type(schema.field(c.index()).data_type().clone())?;
+            aggregates.push((c.index(), *f, column_type));
+        }
+        aggregates.sort_by_key(|(c, _, _)| *c);
+        let merger = UniqueKeyMerger {
+            key: self.key_columns.iter().map(|c| c.index()).collect(),
+            aggregates,
+            pending: None,
+        };
+
+        let input = self.input.execute(0).await?;
+        let output = futures::stream::unfold(Some((input, merger)), |state| async move {
+            let (mut input, mut merger) = state?;
+            loop {
+                match input.next().await {
+                    Some(Ok(b)) => match merger.push(b) {
+                        Ok(None) => continue,
+                        Ok(Some(b)) => return Some((Ok(b), Some((input, merger)))),
+                        Err(e) => return Some((Err(e), None)),
+                    },
+                    Some(Err(e)) => return Some((Err(e), None)),
+                    None => return merger.finish().transpose().map(|r| (r, None)),
+                }
+            }
+        });
+        Ok(Box::pin(StreamWithSchema::wrap(schema, output)))
+    }
+}
+
+struct UniqueKeyMerger {
+    key: Vec<usize>,
+    /// Ordered by column index.
+    aggregates: Vec<(usize, AggregateFunction, ColumnType)>,
+    /// The last key of the previous batch, it may continue in the next one.
+    pending: Option<Group>,
+}
+
+struct Group {
+    key: Vec<TableValue>,
+    values: Vec<TableValue>,
+    last_row: LastRow,
+}
+
+enum LastRow {
+    /// Single row batch, kept from one of the previous batches.
+    Carried(RecordBatch),
+    Current(usize),
+}
+
+impl UniqueKeyMerger {
+    fn push(&mut self, b: RecordBatch) -> Result<Option<RecordBatch>, ArrowError> {
+        let num_rows = b.num_rows();
+        if num_rows == 0 {
+            return Ok(None);
+        }
+        let key_cols = self.key.iter().map(|k| b.column(*k).clone()).collect_vec();
+        let mut builders = self.aggregate_builders();
+        let mut carried = None;
+        let mut last_rows = Vec::new();
+
+        let mut group = self.pending.take();
+        for row in 0..num_rows {
+            let same_key = match &group {
+                Some(g) => {
+                    cmp_partition_key(g.key.len(), &g.key, &key_cols, row) == Ordering::Equal
+                }
+                None => false,
+            };
+            if same_key {
+                let g = group.as_mut().unwrap();
+                for ((c, f, _), v) in self.aggregates.iter().zip(g.values.iter_mut()) {
+                    let new = TableValue::from_array(b.column(*c).as_ref(), row);
+                    *v = merge_values(*f, replace(v, TableValue::Null), new);
+                }
+                g.last_row = LastRow::Current(row);
+                continue;
+            }
+            if let Some(g) = group.take() {
+                self.append_group(g, &mut builders, &mut carried, &mut last_rows);
+            }
+            group = Some(Group {
+                key: TableValue::from_columns(&key_cols, row),
+                values: self
+                    .aggregates
+                    .iter()
+                    .map(|(c, _, _)| TableValue::from_array(b.column(*c).as_ref(), row))
+                    .collect(),
+                last_row: LastRow::Current(row),
+            });
+        }
+
+        let mut group = group.unwrap();
+        if let LastRow::Current(row) = group.last_row {
+            group.last_row = LastRow::Carried(b.slice(row, 1));
+        }
+        self.pending = Some(group);
+
+        if carried.is_none() && last_rows.is_empty() {
+            return Ok(None);
+        }
+        let last_rows = UInt32Array::from(last_rows);
+        let mut builders = builders.into_iter();
+        let mut columns = Vec::with_capacity(b.num_columns());
+        for (i, c) in b.columns().iter().enumerate() {
+            if self.aggregates.iter().any(|(a, _, _)| *a == i) {
+                columns.push(builders.next().unwrap().finish());
+                continue;
+            }
+            let taken = take(c.as_ref(), &last_rows, None)?;
+            match &carried {
+                Some(carried) => {
+                    columns.push(concat(&[carried.column(i).as_ref(), taken.as_ref()])?)
+                }
+                None => columns.push(taken),
+            }
+        }
+        Ok(Some(RecordBatch::try_new(b.schema(), columns)?))
+    }
+
+    fn finish(&mut self) -> Result<Option<RecordBatch>, ArrowError> {
+        let group = match self.pending.take() {
+            Some(g) => g,
+            None => return Ok(None),
+        };
+        let mut builders = self.aggregate_builders();
+        let mut carried = None;
+        self.append_group(group, &mut builders, &mut carried, &mut Vec::new());
+        let carried = carried.unwrap();
+        let mut builders = builders.into_iter();
+        let columns = carried
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                if self.aggregates.iter().any(|(a, _, _)| *a == i) {
+                    builders.next().unwrap().finish()
+                } else {
+                    c.clone()
+                }
+            })
+            .collect();
+        Ok(Some(RecordBatch::try_new(carried.schema(), columns)?))
+    }
+
+    fn aggregate_builders(&self) -> Vec<Box<dyn ArrayBuilder>> {
+        self.aggregates
+            .iter()
+            .map(|(_, _, t)| create_array_builder(t))
+            .collect()
+    }
+
+    fn append_group(
+        &self,
+        g: Group,
+        builders: &mut [Box<dyn ArrayBuilder>],
+        carried: &mut Option<RecordBatch>,
+        last_rows: &mut Vec<u32>,
+    ) {
+        for ((b, (_, _, t)), v) in builders.iter_mut().zip(&self.aggregates).zip(&g.values) {
+            append_value(b.as_mut(), t, v);
+        }
+        match g.last_row {
+            LastRow::Carried(b) => {
+                debug_assert!(carried.is_none() && last_rows.is_empty());
+                *carried = Some(b)
+            }
+            LastRow::Current(row) => last_rows.push(row as u32),
+        }
+    }
+}
+
+fn merge_values(f: AggregateFunction, acc: TableValue, v: TableValue) -> TableValue {
+    match (acc, v) {
+        (TableValue::Null, v) => v,
+        (acc, TableValue::Null) => acc,
+        (acc, v) => match f {
+            AggregateFunction::Sum => match (acc, v) {
+                (TableValue::Int(a), TableValue::Int(b)) => TableValue::Int(a.wrapping_add(b)),
+                (TableValue::Decimal(a), TableValue::Decimal(b)) => {
+                    TableValue::Decimal(Decimal::new(a.raw_value() + b.raw_value()))
+                }
+                (TableValue::Float(a), TableValue::Float(b)) => {
+                    TableValue::Float(OrdF64(a.0 + b.0))
+                }
+                (a, b) => panic!("Can't sum {:?} and {:?}", a, b),
+            },
+            AggregateFunction::Max => {
+                if cmp_same_types(&acc, &v) == Ordering::Less {
+                    v
+                } else {
+                    acc
+                }
+            }
+            AggregateFunction::Min => {
+                if cmp_same_types(&acc, &v) == Ordering::Greater {
+                    v
+                } else {
+                    acc
+                }
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, Int64Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::physical_plan::collect;
+    use datafusion::physical_plan::memory::MemoryExec;
+
+    #[tokio::test]
+    async fn merges_across_batches() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("k", DataType::Int64, false),
+            Field::new("__seq", DataType::Int64, false),
+            Field::new("last", DataType::Int64, true),
+            Field::new("sum", DataType::Int64, true),
+            Field::new("max", DataType::Int64, true),
+        ]));
+        // (k, __seq, last, sum, max), sorted by key and sequence.
+        let rows: Vec<(i64, i64, Option<i64>, Option<i64>, Option<i64>)> = vec![
+            (1, 1, Some(10), Some(1), Some(5)),
+            (1, 2, Some(11), None, Some(3)),
+            (2, 3, Some(20), Some(2), None),
+            (2, 4, None, Some(3), Some(7)),
+            (2, 5, Some(22), Some(4), Some(1)),
+            (3, 6, Some(30), Some(5), Some(2)),
+            (4, 7, Some(40), Some(6), Some(4)),
+            (4, 8, Some(41), Some(7), Some(9)),
+        ];
+        let batches = rows
+            .chunks(3)
+            .map(|c| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int64Array::from(c.iter().map(|r| r.0).collect_vec())),
+                        Arc::new(Int64Array::from(c.iter().map(|r| r.1).collect_vec())),
+                        Arc::new(Int64Array::from(c.iter().map(|r| r.2).collect_vec())),
+                        Arc::new(Int64Array::from(c.iter().map(|r| r.3).collect_vec())),
+                        Arc::new(Int64Array::from(c.iter().map(|r| r.4).collect_vec())),
+                    ],
+                )
+                .unwrap()
+            })
+            .collect_vec();
+        let input = MemoryExec::try_new(&[batches], schema.clone(), None).unwrap();
+        let merge = MergeByUniqueKeyExec::try_new(
+            Arc::new(input),
+            vec![Column::new("k", 0)],
+            vec![
+                (Column::new("max", 4), AggregateFunction::Max),
+                (Column::new("sum", 3), AggregateFunction::Sum),
+            ],
+        )
+        .unwrap();
+
+        let mut result = Vec::new();
+        for b in collect(Arc::new(merge)).await.unwrap() {
+            let c = |i: usize| {
+                b.column(i)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap()
+                    .clone()
+            };
+            let value = |a: &Int64Array, r: usize| {
+                if a.is_valid(r) {
+                    Some(a.value(r))
+                } else {
+                    None
+                }
+            };
+            let (k, seq, last, sum, max) = (c(0), c(1), c(2), c(3), c(4));
+            for r in 0..b.num_rows() {
+                result.push((
+                    k.value(r),
+                    seq.value(r),
+                    value(&last, r),
+                    value(&sum, r),
+                    value(&max, r),
+                ));
+            }
+        }
+        assert_eq!(
+            result,
+            vec![
+                (1, 2, Some(11), Some(1), Some(5)),
+                (2, 5, Some(22), Some(9), Some(7)),
+                (3, 6, Some(30), Some(5), Some(2)),
+                (4, 8, Some(41), Some(13), Some(9)),
+            ]
+        );
+    }
+}
@@ -6,13 +6,17 @@ mod planning;
 pub use planning::PlanningMeta;
 pub mod pretty_printers;
 pub mod query_executor;
+pub mod query_memory;
 pub mod serialized_plan;
 mod topk;
 pub use topk::MIN_TOPK_STREAM_ROWS;
+mod adaptive_spill;
 mod coalesce;
 mod filter_by_key_range;
 pub mod info_schema;
+pub mod merge_by_unique_key;
 mod now;
+mod spill_sort;
 pub mod udfs;
 
 use crate::config::injection::DIService;
@@ -23,9 +27,12 @@ use crate::metastore::{IdRow, MetaStore};
 use crate::queryplanner::info_schema::info_schema_schemata::SchemataInfoSchemaTableDef;
 use crate::queryplanner::info_schema::info_schema_tables::TablesInfoSchemaTableDef;
 use crate::queryplanner::info_schema::system_chunks::SystemChunksTableDef;
+use crate::queryplanner::info_schema::system_gc_candidates::SystemGCCandidatesTableDef;
 use crate::queryplanner::info_schema::system_indexes::SystemIndexesTableDef;
 use crate::queryplanner::info_schema::system_jobs::SystemJobsTableDef;
 use crate::queryplanner::info_schema::system_partitions::SystemPartitionsTableDef;
+use crate::queryplanner::info_schema::system_query_history::SystemQueryHistoryTableDef;
+use crate::queryplanner::info_schema::system_query_pools::SystemQueryPoolsTableDef;
 use crate::queryplanner::info_schema::system_tables::SystemTablesTableDef;
 use crate::queryplanner::now::MaterializeNow;
 use crate::queryplanner::planning::{choose_index_ext, ClusterSendNode};
@@ -34,6 +41,9 @@ use crate::queryplanner::serialized_plan::SerializedPlan;
 use crate::queryplanner::topk::ClusterAggregateTopK;
 use crate::queryplanner::udfs::aggregate_udf_by_kind;
 use crate::queryplanner::udfs::{scalar_udf_by_kind, CubeAggregateUDFKind, CubeScalarUDFKind};
+use crate::scheduler::lifecycle::TableAccessTracker;
+use crate::sql::query_history::QueryHistory;
+use crate::sql::query_pools::QueryPools;
 use crate::store::DataFrame;
 use crate::{app_metrics, metastore, CubeError};
 use arrow::array::ArrayRef;
@@ -41,6 +51,7 @@ use arrow::datatypes::Field;
 use arrow::record_batch::RecordBatch;
 use arrow::{datatypes::Schema, datatypes::SchemaRef};
 use async_trait::async_trait;
+use chrono::Utc;
 use core::fmt;
 use datafusion::catalog::TableReference;
 use datafusion::datasource::datasource::{Statistics, TableProviderFilterPushDown};
@@ -76,6 +87,9 @@ crate::di_service!(MockQueryPlanner, [QueryPlanner]);
 pub struct QueryPlannerImpl {
     meta_store: Arc<dyn MetaStore>,
     config: Arc<dyn ConfigObj>,
+    query_history: Arc<QueryHistory>,
+    query_pools: Arc<QueryPools>,
+    table_access: Arc<TableAccessTracker>,
 }
 
 crate::di_service!(QueryPlannerImpl, [QueryPlanner]);
@@ -93,6 +107,9 @@ impl QueryPlanner for QueryPlannerImpl {
         let schema_provider = MetaStoreSchemaProvider::new(
             self.meta_store.get_tables_with_path(false).await?,
             self.meta_store.clone(),
+            self.query_history.clone(),
+            self.query_pools.clone(),
+            self.table_access.clone(),
         );
 
         let query_planner = SqlToRel::new(&schema_provider);
@@ -108,6 +125,10 @@ impl QueryPlanner for QueryPlannerImpl {
                 self.config.enable_topk(),
             )
             .await?;
+            self.table_access.record(
+                meta.indices.iter().map(|i| i.table_path.table.get_id()),
+                Utc::now(),
+            );
             let workers = compute_workers(
                 self.config.as_ref(),
                 &logical_plan,
@@ -144,8 +165,17 @@ impl QueryPlannerImpl {
     pub fn new(
         meta_store: Arc<dyn MetaStore>,
         config: Arc<dyn ConfigObj>,
+        query_history: Arc<QueryHistory>,
+        query_pools: Arc<QueryPools>,
+        table_access: Arc<TableAccessTracker>,
     ) -> Arc<QueryPlannerImpl> {
-        Arc::new(QueryPlannerImpl { meta_store, config })
+        Arc::new(QueryPlannerImpl {
+            meta_store,
+            config,
+            query_history,
+            query_pools,
+            table_access,
+        })
     }
 }
 
@@ -162,6 +192,9 @@ struct MetaStoreSchemaProvider {
     _data: Arc<Vec<TablePath>>,
     by_name: HashSet<TableKey>,
     meta_store: Arc<dyn MetaStore>,
+    query_history: Arc<QueryHistory>,
+    query_pools: Arc<QueryPools>,
+    table_access: Arc<TableAccessTracker>,
 }
 
 /// Points into [MetaStoreSchemaProvider::data], never null.
@@ -192,12 +225,30 @@ impl Hash for TableKey {
 }
 
 impl MetaStoreSchemaProvider {
-    pub fn new(tables: Arc<Vec<TablePath>>, meta_store: Arc<dyn MetaStore>) -> Self {
+    pub fn new(
+        tables: Arc<Vec<TablePath>>,
+        meta_store: Arc<dyn MetaStore>,
+        query_history: Arc<QueryHistory>,
+        query_pools: Arc<QueryPools>,
+        table_access: Arc<TableAccessTracker>,
+    ) -> Self {
         let by_name = tables.iter().map(|t| TableKey(t)).collect();
         Self {
             _data: tables,
             by_name,
             meta_store,
+            query_history,
+            query_pools,
+            table_access,
+        }
+    }
+
+    fn info_schema_ctx(&self) -> InfoSchemaTableDefContext {
+        InfoSchemaTableDefContext {
+            meta_store: self.meta_store.clone(),
+            query_history: self.query_history.clone(),
+            query_pools: self.query_pools.clone(),
+            table_access: self.table_access.clone(),
         }
     }
 }
@@ -222,6 +273,7 @@ impl ContextProvider for MetaStoreSchemaProvider {
                     None,
                     None,
                     None,
+                    Vec::new(),
                 ),
             ),
             schema: Arc::new(IdRow::new(0, metastore::Schema::new(schema.to_string()))),
@@ -248,33 +300,45 @@ impl ContextProvider for MetaStoreSchemaProvider {
             });
         res.or_else(|| match (schema, table) {
             ("information_schema", "tables") => Some(Arc::new(InfoSchemaTableProvider::new(
-                self.meta_store.clone(),
+                self.info_schema_ctx(),
                 InfoSchemaTable::Tables,
             ))),
             ("information_schema", "schemata") => Some(Arc::new(InfoSchemaTableProvider::new(
-                self.meta_store.clone(),
+                self.info_schema_ctx(),
                 InfoSchemaTable::Schemata,
             ))),
             ("system", "tables") => Some(Arc::new(InfoSchemaTableProvider::new(
-                self.meta_store.clone(),
+                self.info_schema_ctx(),
                 InfoSchemaTable::SystemTables,
             ))),
             ("system", "indexes") => Some(Arc::new(InfoSchemaTableProvider::new(
-                self.meta_store.clone(),
+                self.info_schema_ctx(),
                 InfoSchemaTable::SystemIndexes,
             ))),
             ("system", "partitions") => Some(Arc::new(InfoSchemaTableProvider::new(
-                self.meta_store.clone(),
+                self.info_schema_ctx(),
                 InfoSchemaTable::SystemPartitions,
             ))),
             ("system", "chunks") => Some(Arc::new(InfoSchemaTableProvider::new(
-                self.meta_store.clone(),
+                self.info_schema_ctx(),
                 InfoSchemaTable::SystemChunks,
             ))),
             ("system", "jobs") => Some(Arc::new(InfoSchemaTableProvider::new(
-                self.meta_store.clone(),
+                self.info_schema_ctx(),
                 InfoSchemaTable::SystemJobs,
             ))),
+            ("system", "query_history") => Some(Arc::new(InfoSchemaTableProvider::new(
+                self.info_schema_ctx(),
+                InfoSchemaTable::SystemQueryHistory,
+            ))),
+            ("system", "query_pools") => Some(Arc::new(InfoSchemaTableProvider::new(
+                self.info_schema_ctx(),
+                InfoSchemaTable::SystemQueryPools,
+            ))),
+            ("system", "gc_candidates") => Some(Arc::new(InfoSchemaTableProvider::new(
+                self.info_schema_ctx(),
+                InfoSchemaTable::SystemGCCandidates,
+            ))),
             _ => None,
         })
     }
@@ -312,13 +376,25 @@ pub enum InfoSchemaTable {
     SystemIndexes,
     SystemPartitions,
     SystemChunks,
+    SystemQueryHistory,
+    SystemQueryPools,
+    SystemGCCandidates,
+}
+
+/// Sources of rows for [InfoSchemaTableDef]s.
+#[derive(Clone)]
+pub struct InfoSchemaTableDefContext {
+    pub meta_store: Arc<dyn MetaStore>,
+    pub query_history: Arc<QueryHistory>,
+    pub query_pools: Arc<QueryPools>,
+    pub table_access: Arc<TableAccessTracker>,
 }
 
 #[async_trait]
 pub trait InfoSchemaTableDef {
     type T: Send + Sync;
 
-    async fn rows(&self, meta_store: Arc<dyn MetaStore>) -> Result<Arc<Vec<Self::T>>, CubeError>;
+    async fn rows(&self, ctx: InfoSchemaTableDefContext) -> Result<Arc<Vec<Self::T>>, CubeError>;
 
     fn columns(&self) -> Vec<(Field, Box<dyn Fn(Arc<Vec<Self::T>>) -> ArrayRef>)>;
 }
@@ -327,7 +403,7 @@ pub trait InfoSchemaTableDef {
 pub trait BaseInfoSchemaTableDef {
     fn schema(&self) -> SchemaRef;
 
-    async fn scan(&self, meta_store: Arc<dyn MetaStore>) -> Result<RecordBatch, CubeError>;
+    async fn scan(&self, ctx: InfoSchemaTableDefContext) -> Result<RecordBatch, CubeError>;
 }
 
 #[macro_export]
@@ -346,9 +422,9 @@ macro_rules! base_info_schema_table_def {
 
             async fn scan(
                 &self,
-                meta_store: Arc<dyn crate::metastore::MetaStore>,
+                ctx: crate::queryplanner::InfoSchemaTableDefContext,
             ) -> Result<arrow::record_batch::RecordBatch, crate::CubeError> {
-                let rows = self.rows(meta_store).await?;
+                let rows = self.rows(ctx).await?;
                 let schema = self.schema();
                 let columns = self.columns();
                 let columns = columns
@@ -371,6 +447,9 @@ impl InfoSchemaTable {
             InfoSchemaTable::SystemChunks => Box::new(SystemChunksTableDef),
             InfoSchemaTable::SystemPartitions => Box::new(SystemPartitionsTableDef),
             InfoSchemaTable::SystemJobs => Box::new(SystemJobsTableDef),
+            InfoSchemaTable::SystemQueryHistory => Box::new(SystemQueryHistoryTableDef),
+            InfoSchemaTable::SystemQueryPools => Box::new(SystemQueryPoolsTableDef),
+            InfoSchemaTable::SystemGCCandidates => Box::new(SystemGCCandidatesTableDef),
         }
     }
 
@@ -378,19 +457,19 @@ impl InfoSchemaTable {
         self.table_def().schema()
     }
 
-    async fn scan(&self, meta_store: Arc<dyn MetaStore>) -> Result<RecordBatch, CubeError> {
-        self.table_def().scan(meta_store).await
+    async fn scan(&self, ctx: InfoSchemaTableDefContext) -> Result<RecordBatch, CubeError> {
+        self.table_def().scan(ctx).await
     }
 }
 
 pub struct InfoSchemaTableProvider {
-    meta_store: Arc<dyn MetaStore>,
+    ctx: InfoSchemaTableDefContext,
     table: InfoSchemaTable,
 }
 
 impl InfoSchemaTableProvider {
-    fn new(meta_store: Arc<dyn MetaStore>, table: InfoSchemaTable) -> InfoSchemaTableProvider {
-        InfoSchemaTableProvider { meta_store, table }
+    fn new(ctx: InfoSchemaTableDefContext, table: InfoSchemaTable) -> InfoSchemaTableProvider {
+        InfoSchemaTableProvider { ctx, table }
     }
 }
 
@@ -411,7 +490,7 @@ impl TableProvider for InfoSchemaTableProvider {
         _limit: Option<usize>,
     ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
         let exec = InfoSchemaTableExec {
-            meta_store: self.meta_store.clone(),
+            ctx: self.ctx.clone(),
             table: self.table.clone(),
             projection: projection.clone(),
             projected_schema: project_schema(&self.schema(), projection.as_deref()),
@@ -442,7 +521,7 @@ fn project_schema(s: &Schema, projection: Option<&[usize]>) -> SchemaRef {
 
 #[derive(Clone)]
 pub struct InfoSchemaTableExec {
-    meta_store: Arc<dyn MetaStore>,
+    ctx: InfoSchemaTableDefContext,
     table: InfoSchemaTable,
     projected_schema: SchemaRef,
     projection: Option<Vec<usize>>,
@@ -483,7 +562,7 @@ impl ExecutionPlan for InfoSchemaTableExec {
         &self,
         partition: usize,
     ) -> Result<SendableRecordBatchStream, DataFusionError> {
-        let batch = self.table.scan(self.meta_store.clone()).await?;
+        let batch = self.table.scan(self.ctx.clone()).await?;
         let mem_exec =
             MemoryExec::try_new(&vec![vec![batch]], self.schema(), self.projection.clone())?;
         mem_exec.execute(partition).await
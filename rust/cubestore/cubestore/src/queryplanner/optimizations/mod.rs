@@ -2,6 +2,7 @@ use crate::cluster::Cluster;
 use crate::queryplanner::optimizations::distributed_partial_aggregate::push_aggregate_to_workers;
 use crate::queryplanner::optimizations::prefer_inplace_aggregates::try_switch_to_inplace_aggregates;
 use crate::queryplanner::planning::CubeExtensionPlanner;
+use crate::queryplanner::query_memory::QueryMemory;
 use crate::queryplanner::serialized_plan::SerializedPlan;
 use datafusion::error::DataFusionError;
 use datafusion::execution::context::{ExecutionContextState, QueryPlanner};
@@ -9,15 +10,19 @@ use datafusion::logical_plan::LogicalPlan;
 use datafusion::physical_plan::planner::DefaultPhysicalPlanner;
 use datafusion::physical_plan::{ExecutionPlan, PhysicalPlanner};
 use rewrite_plan::rewrite_physical_plan;
+use spill_to_disk::replace_with_spilling_operators;
 use std::sync::Arc;
 
 mod distributed_partial_aggregate;
 mod prefer_inplace_aggregates;
 pub mod rewrite_plan;
+mod spill_to_disk;
 
 pub struct CubeQueryPlanner {
     cluster: Option<Arc<dyn Cluster>>,
     serialized_plan: Arc<SerializedPlan>,
+    /// Set when the query has a memory limit.
+    memory: Option<(Arc<QueryMemory>, /*batch_size*/ usize)>,
 }
 
 impl CubeQueryPlanner {
@@ -28,6 +33,7 @@ impl CubeQueryPlanner {
         CubeQueryPlanner {
             cluster: Some(cluster),
             serialized_plan,
+            memory: None,
         }
     }
 
@@ -35,6 +41,15 @@ impl CubeQueryPlanner {
         CubeQueryPlanner {
             serialized_plan,
             cluster: None,
+            memory: None,
+        }
+    }
+
+    /// Spill sorts and aggregations to disk when the query runs out of `memory`.
+    pub fn with_memory_limit(self, memory: Arc<QueryMemory>, batch_size: usize) -> Self {
+        CubeQueryPlanner {
+            memory: Some((memory, batch_size)),
+            ..self
         }
     }
 }
@@ -52,13 +67,20 @@ impl QueryPlanner for CubeQueryPlanner {
             })])
             .create_physical_plan(logical_plan, ctx_state)?;
         // TODO: assert there is only a single ClusterSendExec in the plan.
-        finalize_physical_plan(p)
+        finalize_physical_plan(p, &self.memory)
     }
 }
 
 fn finalize_physical_plan(
     p: Arc<dyn ExecutionPlan>,
+    memory: &Option<(Arc<QueryMemory>, usize)>,
 ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
     let p = rewrite_physical_plan(p.as_ref(), &mut |p| try_switch_to_inplace_aggregates(p))?;
-    rewrite_physical_plan(p.as_ref(), &mut |p| push_aggregate_to_workers(p))
+    let p = rewrite_physical_plan(p.as_ref(), &mut |p| push_aggregate_to_workers(p))?;
+    match memory {
+        Some((memory, batch_size)) => rewrite_physical_plan(p.as_ref(), &mut |p| {
+            replace_with_spilling_operators(p, memory, *batch_size)
+        }),
+        None => Ok(p),
+    }
 }
@@ -0,0 +1,124 @@
+use crate::queryplanner::adaptive_spill::AdaptiveSpillExec;
+use crate::queryplanner::query_memory::QueryMemory;
+use crate::queryplanner::spill_sort::SpillSortExec;
+use crate::queryplanner::topk::AggregateTopKExec;
+use arrow::compute::SortOptions;
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::expressions::{Column, PhysicalSortExpr};
+use datafusion::physical_plan::hash_aggregate::{AggregateStrategy, HashAggregateExec};
+use datafusion::physical_plan::merge::MergeExec;
+use datafusion::physical_plan::planner::compute_aggregation_strategy;
+use datafusion::physical_plan::sort::SortExec;
+use datafusion::physical_plan::ExecutionPlan;
+use std::sync::Arc;
+
+/// Rough size of a group key or an accumulator in the state of HashAggregate. Every input row
+/// may start a new group, so in-memory aggregation reserves it per row and column.
+const GROUP_STATE_COLUMN_SIZE: u64 = 64;
+
+/// Makes operators that keep all of their input in memory account it in the query memory.
+/// Sorts and aggregations get a fallback that spills to disk once the query reaches its limit:
+///     Sort -> AdaptiveSpill
+///             |- in memory: Sort
+///             `- spilling: SpillSort
+///     HashAggregate -> AdaptiveSpill
+///                      |- in memory: HashAggregate
+///                      `- spilling: InplaceAggregate
+///                                   `- SpillSort (by group columns)
+///
+/// Sorted aggregation is slower, so it's only used when the input and the estimated group state
+/// don't fit into memory. Top-k aggregation can't spill and fails the query when it runs out of
+/// memory, so do sorts that can't fit a single input batch.
+pub fn replace_with_spilling_operators(
+    p: Arc<dyn ExecutionPlan>,
+    memory: &Arc<QueryMemory>,
+    batch_size: usize,
+) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+    if let Some(topk) = p.as_any().downcast_ref::<AggregateTopKExec>() {
+        return Ok(Arc::new(topk.with_memory(memory.clone())));
+    }
+
+    if let Some(sort) = p.as_any().downcast_ref::<SortExec>() {
+        let schema = sort.input().schema();
+        for e in sort.expr() {
+            if !SpillSortExec::is_supported_type(&e.expr.data_type(&schema)?) {
+                return Ok(p);
+            }
+        }
+        let input = single_partition(sort.input().clone());
+        let spilling = Arc::new(SpillSortExec::new(
+            input.clone(),
+            sort.expr().to_vec(),
+            memory.clone(),
+            batch_size,
+        ));
+        return Ok(Arc::new(AdaptiveSpillExec::new(
+            input.clone(),
+            p.with_new_children(vec![input])?,
+            spilling,
+            memory.clone(),
+            0,
+        )));
+    }
+
+    let agg;
+    if let Some(a) = p.as_any().downcast_ref::<HashAggregateExec>() {
+        agg = a;
+    } else {
+        return Ok(p);
+    }
+    if agg.strategy() != AggregateStrategy::Hash || agg.group_expr().len() == 0 {
+        return Ok(p);
+    }
+    let schema = agg.input().schema();
+    let mut sort_expr = Vec::with_capacity(agg.group_expr().len());
+    for (e, _) in agg.group_expr() {
+        if !e.as_any().is::<Column>() || !SpillSortExec::is_supported_type(&e.data_type(&schema)?) {
+            return Ok(p);
+        }
+        sort_expr.push(PhysicalSortExpr {
+            expr: e.clone(),
+            options: SortOptions {
+                descending: false,
+                nulls_first: false,
+            },
+        });
+    }
+
+    let input = single_partition(agg.input().clone());
+    let sorted_input = Arc::new(SpillSortExec::new(
+        input.clone(),
+        sort_expr,
+        memory.clone(),
+        batch_size,
+    ));
+    let (strategy, order) = compute_aggregation_strategy(sorted_input.as_ref(), agg.group_expr());
+    if strategy != AggregateStrategy::InplaceSorted {
+        return Ok(p);
+    }
+    let spilling = Arc::new(HashAggregateExec::try_new(
+        AggregateStrategy::InplaceSorted,
+        order,
+        *agg.mode(),
+        agg.group_expr().into(),
+        agg.aggr_expr().into(),
+        sorted_input,
+        agg.input_schema().clone(),
+    )?);
+    let state_columns = (agg.group_expr().len() + agg.aggr_expr().len()) as u64;
+    Ok(Arc::new(AdaptiveSpillExec::new(
+        input.clone(),
+        p.with_new_children(vec![input])?,
+        spilling,
+        memory.clone(),
+        state_columns * GROUP_STATE_COLUMN_SIZE,
+    )))
+}
+
+fn single_partition(p: Arc<dyn ExecutionPlan>) -> Arc<dyn ExecutionPlan> {
+    if p.output_partitioning().partition_count() == 1 {
+        p
+    } else {
+        Arc::new(MergeExec::new(p))
+    }
+}
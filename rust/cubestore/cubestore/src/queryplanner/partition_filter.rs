@@ -44,6 +44,23 @@ impl PartitionFilter {
             (None, None) => true,
         }
     }
+
+    /// Estimates the fraction of rows matched by the filter. `column_selectivity(i, min, max)`
+    /// should estimate the fraction of rows with `min <= row[i] <= max`, `None` means no bound.
+    pub fn selectivity(
+        &self,
+        column_selectivity: impl Fn(usize, Option<&TableValue>, Option<&TableValue>) -> f64,
+    ) -> f64 {
+        if self.min_max.is_empty() {
+            return 1.;
+        }
+        let s = self
+            .min_max
+            .iter()
+            .map(|mm| mm.selectivity(&column_selectivity))
+            .sum::<f64>();
+        s.min(1.)
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -53,6 +70,26 @@ struct MinMaxCondition {
 }
 
 impl MinMaxCondition {
+    /// Only the columns compared for equality and the one right after them narrow down the range
+    /// of the sort key, conditions on the rest of the columns are ignored.
+    fn selectivity(
+        &self,
+        column_selectivity: &impl Fn(usize, Option<&TableValue>, Option<&TableValue>) -> f64,
+    ) -> f64 {
+        let mut r = 1.;
+        for i in 0..self.min.len() {
+            let (min, max) = (self.min[i].as_ref(), self.max[i].as_ref());
+            if min.is_none() && max.is_none() {
+                break;
+            }
+            r *= column_selectivity(i, min, max);
+            if min != max {
+                break;
+            }
+        }
+        r
+    }
+
     /// Assuming max is unbounded.
     pub fn can_match_min(&self, min_row: &[TableValue]) -> bool {
         let n = self.max.len();
@@ -1104,6 +1141,31 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_selectivity() {
+        let s = schema(&[("a", DataType::Int64), ("b", DataType::Int64)]);
+        let selectivity = |sql| {
+            PartitionFilter::extract(&s, &[parse(sql, &s)]).selectivity(|_, min, max| {
+                if min == max {
+                    0.1
+                } else {
+                    0.5
+                }
+            })
+        };
+        let close = |l: f64, r: f64| (l - r).abs() < 1e-9;
+
+        assert!(close(selectivity("a = 1 AND b = 2"), 0.01));
+        assert!(close(selectivity("a = 1 AND b > 2"), 0.05));
+        assert!(close(selectivity("a > 1 AND b = 2"), 0.5));
+        assert!(close(selectivity("b = 2"), 1.));
+        assert!(close(selectivity("a = 1 OR a = 2"), 0.2));
+        assert!(close(
+            selectivity("a IN (1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11)"),
+            1.
+        ));
+    }
+
     fn schema(s: &[(&str, DataType)]) -> Schema {
         Schema::new(
             s.iter()
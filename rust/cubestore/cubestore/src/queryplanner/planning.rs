@@ -534,9 +534,14 @@ async fn pick_index(
                 .filter(|i| i.get_row().multi_index_id().is_some()),
             &projection_columns,
             &filter_columns,
+            &c.filters,
+        );
+        let optimal = optimal_index_by_score(
+            filtered_by_sort_on,
+            &projection_columns,
+            &filter_columns,
+            &c.filters,
         );
-        let optimal =
-            optimal_index_by_score(filtered_by_sort_on, &projection_columns, &filter_columns);
         if let Some(index) = optimal_with_partitioned_index.or(optimal) {
             (
                 Ok(index),
@@ -562,6 +567,7 @@ async fn pick_index(
                     indices.iter().skip(1),
                     &projection_columns,
                     &filter_columns,
+                    &c.filters,
                 );
 
                 let index = optimal.unwrap_or(default_index);
@@ -614,8 +620,9 @@ fn optimal_index_by_score<'a, T: Iterator<Item = &'a IdRow<Index>>>(
     indexes: T,
     projection_columns: &Vec<Column>,
     filter_columns: &HashSet<logical_plan::Column>,
+    filters: &[Expr],
 ) -> Option<&'a IdRow<Index>> {
-    indexes
+    let scored = indexes
         .filter_map(|i| {
             let filter_index_positions = CubeTable::project_to_index_positions(
                 &filter_columns.iter().map(|c| c.name.to_string()).collect(),
@@ -641,8 +648,39 @@ fn optimal_index_by_score<'a, T: Iterator<Item = &'a IdRow<Index>>>(
             println!("optimal_index_by_score: {:?}", res);
             res
         })
-        .min_by_key(|(_, score)| score.clone())
-        .map(|(index, _)| index)
+        .collect_vec();
+    // Costs are only comparable when all candidates were analyzed.
+    let costs = scored
+        .iter()
+        .map(|(i, _)| estimated_rows_to_read(i, filters))
+        .collect::<Option<Vec<_>>>();
+    match costs {
+        Some(costs) => scored
+            .into_iter()
+            .zip(costs)
+            .min_by_key(|((_, score), cost)| (*cost, score.clone()))
+            .map(|((index, _), _)| index),
+        None => scored
+            .into_iter()
+            .min_by_key(|(_, score)| score.clone())
+            .map(|(index, _)| index),
+    }
+}
+
+/// Estimates the number of rows read from the index with `filters` applied using statistics
+/// collected by `ANALYZE TABLE`. Returns `None` for indexes that were not analyzed.
+fn estimated_rows_to_read(index: &IdRow<Index>, filters: &[Expr]) -> Option<u64> {
+    let stats = index.get_row().statistics().as_ref()?;
+    let key_stats = index
+        .get_row()
+        .columns()
+        .iter()
+        .take(index.get_row().sort_key_size() as usize)
+        .map(|c| stats.column(c.get_name()))
+        .collect::<Option<Vec<_>>>()?;
+    let filter = PartitionFilter::extract(&partition_filter_schema(index), filters);
+    let selectivity = filter.selectivity(|i, min, max| key_stats[i].selectivity(min, max));
+    Some((stats.row_count() as f64 * selectivity).ceil() as u64)
 }
 
 fn pick_partitions(
@@ -673,6 +711,13 @@ fn pick_partitions(
             continue;
         }
 
+        let partition = match partition.get_row().column_statistics() {
+            None => partition,
+            Some(_) => IdRow::new(
+                partition.get_id(),
+                partition.get_row().without_column_statistics(),
+            ),
+        };
         partition_snapshots.push(PartitionSnapshot { chunks, partition });
     }
     log::trace!(
@@ -995,6 +1040,7 @@ pub mod tests {
 
     use crate::config::Config;
     use crate::metastore::multi_index::MultiPartition;
+    use crate::metastore::statistics::{ColumnStatistics, IndexStatistics};
     use crate::metastore::table::{Table, TablePath};
     use crate::metastore::{Chunk, Column, ColumnType, IdRow, Index, Partition, Schema};
     use crate::queryplanner::planning::{choose_index, try_extract_cluster_send, PlanIndexStore};
@@ -1003,8 +1049,12 @@ pub mod tests {
     use crate::queryplanner::serialized_plan::RowRange;
     use crate::queryplanner::{pretty_printers, CubeTableLogical};
     use crate::sql::parser::{CubeStoreParser, Statement};
+    use crate::table::parquet::arrow_schema;
     use crate::table::{Row, TableValue};
     use crate::CubeError;
+    use arrow::array::{ArrayRef, Int64Array};
+    use arrow::record_batch::RecordBatch;
+    use chrono::Utc;
     use datafusion::catalog::TableReference;
     use std::collections::HashMap;
     use std::iter::FromIterator;
@@ -1216,6 +1266,93 @@ pub mod tests {
         );
         let pp = pretty_printers::pp_plan(&choose_index(&plan, &indices).await.unwrap().0);
         assert!(!pp.contains("TopK"), "plan contained topk:\n{}", pp);
+
+        // Statistics show there are fewer groups than the limit.
+        let mut indices = default_indices();
+        indices.indices = indices
+            .indices
+            .iter()
+            .map(|i| analyzed(i, 1000, 5))
+            .collect();
+        let plan = initial_plan(
+            "SELECT order_customer, SUM(order_amount) FROM s.Orders \
+             GROUP BY 1 ORDER BY 2 DESC LIMIT 10",
+            &indices,
+        );
+        let pp = pretty_printers::pp_plan(&choose_index(&plan, &indices).await.unwrap().0);
+        assert!(!pp.contains("TopK"), "plan contained topk:\n{}", pp);
+
+        // And more groups.
+        indices.indices = indices
+            .indices
+            .iter()
+            .map(|i| analyzed(i, 1000, 100))
+            .collect();
+        let pp = pretty_printers::pp_plan(&choose_index(&plan, &indices).await.unwrap().0);
+        assert!(pp.contains("TopK"), "plan did not contain topk:\n{}", pp);
+    }
+
+    #[tokio::test]
+    pub async fn test_choose_index_by_statistics() {
+        let mut indices = default_indices();
+        let orders = indices
+            .tables
+            .iter()
+            .position(|t| t.get_table_name() == "Orders")
+            .unwrap() as u64;
+        let orders_cols = indices.tables[orders as usize].get_columns().clone();
+        indices.indices.push(
+            Index::try_new(
+                "by_id".to_string(),
+                orders,
+                put_first("order_id", &orders_cols),
+                2,
+                None,
+                None,
+            )
+            .unwrap(),
+        );
+        let plan = initial_plan(
+            "SELECT order_customer, order_id FROM s.Orders \
+             WHERE order_customer > 0 AND order_id = 5",
+            &indices,
+        );
+
+        // Both indexes have the same score, the first one wins.
+        let pp = pretty_printers::pp_plan(&choose_index(&plan, &indices).await.unwrap().0);
+        assert!(pp.contains("index: by_customer"), "{}", pp);
+
+        // With statistics, the index that reads fewer rows wins.
+        indices.indices = indices
+            .indices
+            .iter()
+            .map(|i| analyzed(i, 1000, 1000))
+            .collect();
+        let pp = pretty_printers::pp_plan(&choose_index(&plan, &indices).await.unwrap().0);
+        assert!(pp.contains("index: by_id"), "{}", pp);
+    }
+
+    /// Adds statistics of `rows` rows to the index. Every column has values `0..distinct`.
+    fn analyzed(index: &Index, rows: i64, distinct: i64) -> Index {
+        let columns = index
+            .columns()
+            .iter()
+            .map(|_| {
+                Arc::new(Int64Array::from(
+                    (0..rows).map(|r| r % distinct).collect_vec(),
+                )) as ArrayRef
+            })
+            .collect();
+        let data = vec![RecordBatch::try_new(Arc::new(arrow_schema(index)), columns).unwrap()];
+        let stats = index
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(i, c)| ColumnStatistics::collect(c, i, &data).unwrap())
+            .collect_vec();
+        index.update_statistics(Some(
+            IndexStatistics::merge(index, &[&stats], Utc::now()).unwrap(),
+        ))
     }
 
     #[tokio::test]
@@ -1401,6 +1538,7 @@ pub mod tests {
             None,
             None,
             None,
+            Vec::new(),
         ));
         i.indices.push(
             Index::try_new(
@@ -1444,6 +1582,7 @@ pub mod tests {
             None,
             None,
             None,
+            Vec::new(),
         ));
         i.indices.push(
             Index::try_new(
@@ -1491,6 +1630,7 @@ pub mod tests {
             None,
             None,
             None,
+            Vec::new(),
         ));
 
         i
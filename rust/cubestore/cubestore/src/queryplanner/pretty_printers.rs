@@ -16,11 +16,14 @@ use datafusion::physical_plan::sort::SortExec;
 use datafusion::physical_plan::ExecutionPlan;
 use itertools::{repeat_n, Itertools};
 
+use crate::queryplanner::adaptive_spill::AdaptiveSpillExec;
 use crate::queryplanner::filter_by_key_range::FilterByKeyRangeExec;
+use crate::queryplanner::merge_by_unique_key::MergeByUniqueKeyExec;
 use crate::queryplanner::panic::{PanicWorkerExec, PanicWorkerNode};
 use crate::queryplanner::planning::{ClusterSendNode, WorkerExec};
 use crate::queryplanner::query_executor::{ClusterSendExec, CubeTable, CubeTableExec};
 use crate::queryplanner::serialized_plan::{IndexSnapshot, RowRange};
+use crate::queryplanner::spill_sort::SpillSortExec;
 use crate::queryplanner::topk::ClusterAggregateTopK;
 use crate::queryplanner::topk::{AggregateTopKExec, SortColumn};
 use crate::queryplanner::CubeTableLogical;
@@ -28,7 +31,7 @@ use datafusion::cube_ext::join::CrossJoinExec;
 use datafusion::cube_ext::joinagg::CrossJoinAggExec;
 use datafusion::cube_ext::rolling::RollingWindowAggExec;
 use datafusion::physical_plan::empty::EmptyExec;
-use datafusion::physical_plan::expressions::Column;
+use datafusion::physical_plan::expressions::{Column, PhysicalSortExpr};
 use datafusion::physical_plan::memory::MemoryExec;
 use datafusion::physical_plan::merge::MergeExec;
 use datafusion::physical_plan::parquet::ParquetExec;
@@ -326,22 +329,15 @@ fn pp_phys_plan_indented(p: &dyn ExecutionPlan, indent: usize, o: &PPOptions, ou
         } else if let Some(s) = a.downcast_ref::<SortExec>() {
             *out += "Sort";
             if o.show_sort_by {
-                *out += &format!(
-                    ", by: [{}]",
-                    s.expr()
-                        .iter()
-                        .map(|e| {
-                            let mut r = format!("{}", e.expr);
-                            if e.options.descending {
-                                r += " desc";
-                            }
-                            if !e.options.nulls_first {
-                                r += " nulls last";
-                            }
-                            r
-                        })
-                        .join(", ")
-                );
+                *out += &format!(", by: {}", pp_sort_exprs(s.expr()));
+            }
+        } else if let Some(s) = a.downcast_ref::<AdaptiveSpillExec>() {
+            *out += "AdaptiveSpill, in memory: ";
+            pp_instance(s.in_memory().as_ref(), 0, o, out);
+        } else if let Some(s) = a.downcast_ref::<SpillSortExec>() {
+            *out += "SpillSort";
+            if o.show_sort_by {
+                *out += &format!(", by: {}", pp_sort_exprs(s.expr()));
             }
         } else if let Some(_) = a.downcast_ref::<HashJoinExec>() {
             *out += "HashJoin";
@@ -414,6 +410,14 @@ fn pp_phys_plan_indented(p: &dyn ExecutionPlan, indent: usize, o: &PPOptions, ou
             *out += "RollingWindowAgg";
         } else if let Some(_) = a.downcast_ref::<LastRowByUniqueKeyExec>() {
             *out += "LastRowByUniqueKey";
+        } else if let Some(m) = a.downcast_ref::<MergeByUniqueKeyExec>() {
+            *out += &format!(
+                "MergeByUniqueKey, aggregates: [{}]",
+                m.aggregates()
+                    .iter()
+                    .map(|(c, f)| format!("{}({})", f, c.name()))
+                    .join(", ")
+            );
         } else if let Some(_) = a.downcast_ref::<MemoryExec>() {
             *out += "MemoryScan";
         } else {
@@ -433,6 +437,25 @@ fn pp_phys_plan_indented(p: &dyn ExecutionPlan, indent: usize, o: &PPOptions, ou
     }
 }
 
+fn pp_sort_exprs(exprs: &[PhysicalSortExpr]) -> String {
+    format!(
+        "[{}]",
+        exprs
+            .iter()
+            .map(|e| {
+                let mut r = format!("{}", e.expr);
+                if e.options.descending {
+                    r += " desc";
+                }
+                if !e.options.nulls_first {
+                    r += " nulls last";
+                }
+                r
+            })
+            .join(", ")
+    )
+}
+
 fn pp_row_range(r: &RowRange) -> String {
     if r.matches_all_rows() {
         return String::new();
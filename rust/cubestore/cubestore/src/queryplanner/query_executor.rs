@@ -5,9 +5,11 @@ use crate::metastore::multi_index::MultiPartition;
 use crate::metastore::table::Table;
 use crate::metastore::{Column, ColumnType, IdRow, Index, Partition};
 use crate::queryplanner::filter_by_key_range::FilterByKeyRangeExec;
+use crate::queryplanner::merge_by_unique_key::MergeByUniqueKeyExec;
 use crate::queryplanner::optimizations::CubeQueryPlanner;
 use crate::queryplanner::planning::get_worker_plan;
 use crate::queryplanner::pretty_printers::{pp_phys_plan, pp_plan};
+use crate::queryplanner::query_memory::QueryMemory;
 use crate::queryplanner::serialized_plan::{IndexSnapshot, RowFilter, RowRange, SerializedPlan};
 use crate::store::DataFrame;
 use crate::table::{Row, TableValue, TimestampValue};
@@ -50,6 +52,7 @@ use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
 use std::io::Cursor;
 use std::mem::take;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::SystemTime;
 use tracing::{instrument, Instrument};
@@ -92,10 +95,16 @@ pub trait QueryExecutor: DIService + Send + Sync {
 
 crate::di_service!(MockQueryExecutor, [QueryExecutor]);
 
-pub struct QueryExecutorImpl;
+pub struct QueryExecutorImpl {
+    /// Per query, zero means no limit.
+    memory_limit: u64,
+    spill_dir: PathBuf,
+}
 
 crate::di_service!(QueryExecutorImpl, [QueryExecutor]);
 
+const BATCH_SIZE: usize = 4096;
+
 #[async_trait]
 impl QueryExecutor for QueryExecutorImpl {
     #[instrument(level = "trace", skip(self, plan, cluster))]
@@ -105,7 +114,10 @@ impl QueryExecutor for QueryExecutorImpl {
         cluster: Arc<dyn Cluster>,
     ) -> Result<(SchemaRef, Vec<RecordBatch>), CubeError> {
         let collect_span = tracing::span!(tracing::Level::TRACE, "collect_physical_plan");
-        let (physical_plan, logical_plan) = self.router_plan(plan, cluster).await?;
+        let memory = self.query_memory();
+        let (physical_plan, logical_plan) = self
+            .router_plan_with_memory(plan, cluster, memory.clone())
+            .await?;
         let split_plan = physical_plan;
 
         trace!(
@@ -118,6 +130,9 @@ impl QueryExecutor for QueryExecutorImpl {
         let results = collect(split_plan.clone()).instrument(collect_span).await;
         let execution_time = execution_time.elapsed()?;
         debug!("Query data processing time: {:?}", execution_time,);
+        if let Some(memory) = &memory {
+            debug!("Query memory peak: {} bytes", memory.peak());
+        }
         app_metrics::DATA_QUERY_TIME_MS.report(execution_time.as_millis() as i64);
         if execution_time.as_millis() > 200 {
             warn!(
@@ -153,8 +168,14 @@ impl QueryExecutor for QueryExecutorImpl {
         remote_to_local_names: HashMap<String, String>,
         chunk_id_to_record_batches: HashMap<u64, Vec<RecordBatch>>,
     ) -> Result<(SchemaRef, Vec<RecordBatch>), CubeError> {
+        let memory = self.query_memory();
         let (physical_plan, logical_plan) = self
-            .worker_plan(plan, remote_to_local_names, chunk_id_to_record_batches)
+            .worker_plan_with_memory(
+                plan,
+                remote_to_local_names,
+                chunk_id_to_record_batches,
+                memory.clone(),
+            )
             .await?;
 
         let worker_plan;
@@ -185,6 +206,9 @@ impl QueryExecutor for QueryExecutorImpl {
             "Partition Query data processing time: {:?}",
             execution_time.elapsed()?
         );
+        if let Some(memory) = &memory {
+            debug!("Partition Query memory peak: {} bytes", memory.peak());
+        }
         if execution_time.elapsed()?.as_millis() > 200 || results.is_err() {
             warn!(
                 "Slow Partition Query ({:?}):\n{}",
@@ -219,13 +243,8 @@ impl QueryExecutor for QueryExecutorImpl {
         plan: SerializedPlan,
         cluster: Arc<dyn Cluster>,
     ) -> Result<(Arc<dyn ExecutionPlan>, LogicalPlan), CubeError> {
-        let plan_to_move = plan.logical_plan(HashMap::new(), HashMap::new())?;
-        let serialized_plan = Arc::new(plan);
-        let ctx = self.router_context(cluster.clone(), serialized_plan.clone())?;
-        Ok((
-            ctx.clone().create_physical_plan(&plan_to_move.clone())?,
-            plan_to_move,
-        ))
+        self.router_plan_with_memory(plan, cluster, self.query_memory())
+            .await
     }
 
     async fn worker_plan(
@@ -234,14 +253,13 @@ impl QueryExecutor for QueryExecutorImpl {
         remote_to_local_names: HashMap<String, String>,
         chunk_id_to_record_batches: HashMap<u64, Vec<RecordBatch>>,
     ) -> Result<(Arc<dyn ExecutionPlan>, LogicalPlan), CubeError> {
-        let plan_to_move = plan.logical_plan(remote_to_local_names, chunk_id_to_record_batches)?;
-        let plan = Arc::new(plan);
-        let ctx = self.worker_context(plan.clone())?;
-        let plan_ctx = ctx.clone();
-        Ok((
-            plan_ctx.create_physical_plan(&plan_to_move.clone())?,
-            plan_to_move,
-        ))
+        self.worker_plan_with_memory(
+            plan,
+            remote_to_local_names,
+            chunk_id_to_record_batches,
+            self.query_memory(),
+        )
+        .await
     }
     async fn pp_worker_plan(
         &self,
@@ -268,18 +286,76 @@ impl QueryExecutor for QueryExecutorImpl {
 }
 
 impl QueryExecutorImpl {
+    pub fn new(memory_limit: u64, spill_dir: PathBuf) -> Arc<QueryExecutorImpl> {
+        Arc::new(QueryExecutorImpl {
+            memory_limit,
+            spill_dir,
+        })
+    }
+
+    /// Memory of a single query, shared by all of its operators on this node.
+    fn query_memory(&self) -> Option<Arc<QueryMemory>> {
+        if self.memory_limit == 0 {
+            return None;
+        }
+        Some(QueryMemory::new(self.memory_limit, self.spill_dir.clone()))
+    }
+
+    fn with_memory_limit(
+        planner: CubeQueryPlanner,
+        memory: Option<Arc<QueryMemory>>,
+    ) -> CubeQueryPlanner {
+        match memory {
+            Some(memory) => planner.with_memory_limit(memory, BATCH_SIZE),
+            None => planner,
+        }
+    }
+
+    async fn router_plan_with_memory(
+        &self,
+        plan: SerializedPlan,
+        cluster: Arc<dyn Cluster>,
+        memory: Option<Arc<QueryMemory>>,
+    ) -> Result<(Arc<dyn ExecutionPlan>, LogicalPlan), CubeError> {
+        let plan_to_move = plan.logical_plan(HashMap::new(), HashMap::new())?;
+        let serialized_plan = Arc::new(plan);
+        let ctx = self.router_context(cluster.clone(), serialized_plan.clone(), memory)?;
+        Ok((
+            ctx.clone().create_physical_plan(&plan_to_move.clone())?,
+            plan_to_move,
+        ))
+    }
+
+    async fn worker_plan_with_memory(
+        &self,
+        plan: SerializedPlan,
+        remote_to_local_names: HashMap<String, String>,
+        chunk_id_to_record_batches: HashMap<u64, Vec<RecordBatch>>,
+        memory: Option<Arc<QueryMemory>>,
+    ) -> Result<(Arc<dyn ExecutionPlan>, LogicalPlan), CubeError> {
+        let plan_to_move = plan.logical_plan(remote_to_local_names, chunk_id_to_record_batches)?;
+        let plan = Arc::new(plan);
+        let ctx = self.worker_context(plan.clone(), memory)?;
+        let plan_ctx = ctx.clone();
+        Ok((
+            plan_ctx.create_physical_plan(&plan_to_move.clone())?,
+            plan_to_move,
+        ))
+    }
+
     fn router_context(
         &self,
         cluster: Arc<dyn Cluster>,
         serialized_plan: Arc<SerializedPlan>,
+        memory: Option<Arc<QueryMemory>>,
     ) -> Result<Arc<ExecutionContext>, CubeError> {
         Ok(Arc::new(ExecutionContext::with_config(
             ExecutionConfig::new()
-                .with_batch_size(4096)
+                .with_batch_size(BATCH_SIZE)
                 .with_concurrency(1)
-                .with_query_planner(Arc::new(CubeQueryPlanner::new_on_router(
-                    cluster,
-                    serialized_plan,
+                .with_query_planner(Arc::new(Self::with_memory_limit(
+                    CubeQueryPlanner::new_on_router(cluster, serialized_plan),
+                    memory,
                 ))),
         )))
     }
@@ -287,12 +363,16 @@ impl QueryExecutorImpl {
     fn worker_context(
         &self,
         serialized_plan: Arc<SerializedPlan>,
+        memory: Option<Arc<QueryMemory>>,
     ) -> Result<Arc<ExecutionContext>, CubeError> {
         Ok(Arc::new(ExecutionContext::with_config(
             ExecutionConfig::new()
-                .with_batch_size(4096)
+                .with_batch_size(BATCH_SIZE)
                 .with_concurrency(1)
-                .with_query_planner(Arc::new(CubeQueryPlanner::new_on_worker(serialized_plan))),
+                .with_query_planner(Arc::new(Self::with_memory_limit(
+                    CubeQueryPlanner::new_on_worker(serialized_plan),
+                    memory,
+                ))),
         )))
     }
 }
@@ -625,18 +705,40 @@ impl CubeTable {
                 .collect::<Result<Vec<_>, _>>()?;
             let mut exec: Arc<dyn ExecutionPlan> =
                 Arc::new(MergeSortExec::try_new(read_data, sort_columns)?);
-            exec = Arc::new(LastRowByUniqueKeyExec::try_new(
-                exec,
-                key_columns
-                    .iter()
-                    .map(|c| {
-                        datafusion::physical_plan::expressions::Column::new_with_schema(
-                            c.get_name().as_str(),
-                            &schema,
-                        )
-                    })
-                    .collect::<Result<Vec<_>, _>>()?,
-            )?);
+            let key_columns = key_columns
+                .iter()
+                .map(|c| {
+                    datafusion::physical_plan::expressions::Column::new_with_schema(
+                        c.get_name().as_str(),
+                        &schema,
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            // Chunks that are not compacted yet must be merged the same way compaction does.
+            let aggregates = self
+                .index_snapshot
+                .table_path
+                .table
+                .get_row()
+                .aggregate_columns()
+                .into_iter()
+                .filter_map(|(c, f)| {
+                    let i = schema.index_of(c.get_name()).ok()?;
+                    Some((
+                        datafusion::physical_plan::expressions::Column::new(c.get_name(), i),
+                        f,
+                    ))
+                })
+                .collect_vec();
+            if aggregates.is_empty() {
+                exec = Arc::new(LastRowByUniqueKeyExec::try_new(exec, key_columns)?);
+            } else {
+                exec = Arc::new(MergeByUniqueKeyExec::try_new(
+                    exec,
+                    key_columns,
+                    aggregates,
+                )?);
+            }
 
             // At this point data is projected for last row query and we need to re-project it to what actually queried
             let s = exec.schema();
@@ -1380,7 +1482,7 @@ fn regroup_batches(
     Ok(r)
 }
 
-fn slice_copy(a: &dyn Array, start: usize, len: usize) -> ArrayRef {
+pub(crate) fn slice_copy(a: &dyn Array, start: usize, len: usize) -> ArrayRef {
     // If we use [Array::slice], serialization will still copy the whole contents.
     let mut a = MutableArrayData::new(vec![a.data()], false, len);
     a.extend(0, start, start + len);
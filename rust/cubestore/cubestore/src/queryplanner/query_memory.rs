@@ -0,0 +1,151 @@
+use arrow::record_batch::RecordBatch;
+use datafusion::error::DataFusionError;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Memory accounted to a single query. Operators that buffer data reserve memory here and spill
+/// to disk once a reservation is refused. Zero limit means no limit.
+#[derive(Debug)]
+pub struct QueryMemory {
+    limit: u64,
+    used: AtomicU64,
+    peak: AtomicU64,
+    spill_dir: PathBuf,
+}
+
+impl QueryMemory {
+    pub fn new(limit: u64, spill_dir: PathBuf) -> Arc<QueryMemory> {
+        Arc::new(QueryMemory {
+            limit,
+            used: AtomicU64::new(0),
+            peak: AtomicU64::new(0),
+            spill_dir,
+        })
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    pub fn peak(&self) -> u64 {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    pub fn spill_dir(&self) -> &PathBuf {
+        &self.spill_dir
+    }
+
+    /// Returns `None` if the reservation would exceed the limit.
+    pub fn try_reserve(self: &Arc<Self>, bytes: u64) -> Option<MemoryReservation> {
+        let mut used = self.used.load(Ordering::Relaxed);
+        loop {
+            if self.limit != 0 && used + bytes > self.limit {
+                return None;
+            }
+            match self.used.compare_exchange_weak(
+                used,
+                used + bytes,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(actual) => used = actual,
+            }
+        }
+        self.peak.fetch_max(used + bytes, Ordering::Relaxed);
+        Some(MemoryReservation {
+            memory: self.clone(),
+            bytes,
+        })
+    }
+
+    /// Same as [Self::try_reserve], but fails the query if the reservation would exceed the
+    /// limit. Used by `operator` when there is nothing left to spill.
+    pub fn reserve(
+        self: &Arc<Self>,
+        bytes: u64,
+        operator: &str,
+    ) -> Result<MemoryReservation, DataFusionError> {
+        self.try_reserve(bytes).ok_or_else(|| {
+            DataFusionError::Execution(format!(
+                "{} exceeds query memory limit of {} bytes: {} more bytes are needed and there is nothing left to spill",
+                operator, self.limit, bytes
+            ))
+        })
+    }
+}
+
+/// Memory held by an operator, returned to the query on drop.
+#[derive(Debug)]
+pub struct MemoryReservation {
+    memory: Arc<QueryMemory>,
+    bytes: u64,
+}
+
+impl MemoryReservation {
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Moves memory of `other` into this reservation.
+    pub fn merge(&mut self, mut other: MemoryReservation) {
+        debug_assert!(Arc::ptr_eq(&self.memory, &other.memory));
+        self.bytes += other.bytes;
+        other.bytes = 0;
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.memory.used.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+pub fn batch_memory_size(batch: &RecordBatch) -> u64 {
+    batch
+        .columns()
+        .iter()
+        .map(|c| c.get_array_memory_size() as u64)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reservations() {
+        let memory = QueryMemory::new(100, PathBuf::new());
+        let mut a = memory.try_reserve(60).unwrap();
+        assert!(memory.try_reserve(50).is_none());
+        let b = memory.try_reserve(40).unwrap();
+        assert_eq!(memory.used(), 100);
+
+        drop(b);
+        assert_eq!(memory.used(), 60);
+        let err = memory.reserve(70, "Sort").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Sort exceeds query memory limit of 100 bytes"));
+        a.merge(memory.reserve(30, "Sort").unwrap());
+        assert_eq!(a.bytes(), 90);
+        assert_eq!(memory.used(), 90);
+        assert_eq!(memory.peak(), 100);
+
+        drop(a);
+        assert_eq!(memory.used(), 0);
+        assert_eq!(memory.peak(), 100);
+    }
+
+    #[test]
+    fn unlimited() {
+        let memory = QueryMemory::new(0, PathBuf::new());
+        let _r = memory.try_reserve(u32::MAX as u64).unwrap();
+        assert_eq!(memory.used(), u32::MAX as u64);
+    }
+}
@@ -0,0 +1,682 @@
+use crate::queryplanner::query_executor::slice_copy;
+use crate::queryplanner::query_memory::{batch_memory_size, MemoryReservation, QueryMemory};
+use crate::table::data::cmp_same_types;
+use crate::table::data::TableValueR;
+use crate::table::TableValue;
+use arrow::array::{make_array, Array, ArrayRef, MutableArrayData};
+use arrow::compute::{lexsort_to_indices, take, SortOptions};
+use arrow::datatypes::{DataType, SchemaRef, TimeUnit};
+use arrow::error::{ArrowError, Result as ArrowResult};
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use datafusion::cube_ext;
+use datafusion::error::DataFusionError;
+use datafusion::physical_plan::expressions::{Column, PhysicalSortExpr};
+use datafusion::physical_plan::{
+    ExecutionPlan, OptimizerHints, Partitioning, RecordBatchStream, SendableRecordBatchStream,
+};
+use futures::{Stream, StreamExt};
+use log::debug;
+use std::any::Any;
+use std::cmp::{min, Ordering};
+use std::fs::File;
+use std::mem::take as take_mem;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tempfile::NamedTempFile;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Sorts its input like [datafusion::physical_plan::sort::SortExec], but stays within the memory
+/// limit of the query. Once the buffered input does not fit, it is sorted and written to the
+/// spill directory as a sorted run. Runs are merged back when the input is exhausted.
+#[derive(Debug)]
+pub struct SpillSortExec {
+    input: Arc<dyn ExecutionPlan>,
+    expr: Vec<PhysicalSortExpr>,
+    memory: Arc<QueryMemory>,
+    batch_size: usize,
+}
+
+impl SpillSortExec {
+    pub fn new(
+        input: Arc<dyn ExecutionPlan>,
+        expr: Vec<PhysicalSortExpr>,
+        memory: Arc<QueryMemory>,
+        batch_size: usize,
+    ) -> SpillSortExec {
+        SpillSortExec {
+            input,
+            expr,
+            memory,
+            batch_size,
+        }
+    }
+
+    pub fn expr(&self) -> &[PhysicalSortExpr] {
+        &self.expr
+    }
+
+    /// Sort keys of spilled runs are compared as [TableValue]s, so only the types that can be
+    /// converted are supported.
+    pub fn is_supported_type(t: &DataType) -> bool {
+        match t {
+            DataType::Int64
+            | DataType::Utf8
+            | DataType::Binary
+            | DataType::Float64
+            | DataType::Boolean
+            | DataType::Timestamp(TimeUnit::Microsecond, None) => true,
+            DataType::Int64Decimal(scale) => match scale {
+                0 | 1 | 2 | 3 | 4 | 5 | 10 => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+#[async_trait]
+impl ExecutionPlan for SpillSortExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        Partitioning::UnknownPartitioning(1)
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        &self,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>, DataFusionError> {
+        assert_eq!(children.len(), 1);
+        Ok(Arc::new(SpillSortExec {
+            input: children.into_iter().next().unwrap(),
+            expr: self.expr.clone(),
+            memory: self.memory.clone(),
+            batch_size: self.batch_size,
+        }))
+    }
+
+    fn output_hints(&self) -> OptimizerHints {
+        // Only report the sort order for ascending sorts on plain columns, same as the
+        // other operators do.
+        let sort_order = self
+            .expr
+            .iter()
+            .map(|e| {
+                if e.options.descending {
+                    return None;
+                }
+                let name = e.expr.as_any().downcast_ref::<Column>()?.name();
+                self.input.schema().index_of(name).ok()
+            })
+            .collect();
+        OptimizerHints {
+            sort_order,
+            single_value_columns: self.input.output_hints().single_value_columns,
+        }
+    }
+
+    async fn execute(
+        &self,
+        partition: usize,
+    ) -> Result<SendableRecordBatchStream, DataFusionError> {
+        if partition != 0 {
+            return Err(DataFusionError::Internal(format!(
+                "SpillSortExec invalid partition {}",
+                partition
+            )));
+        }
+        if self.input.output_partitioning().partition_count() != 1 {
+            return Err(DataFusionError::Internal(
+                "SpillSortExec requires a single input partition".to_string(),
+            ));
+        }
+
+        let mut input = self.input.execute(0).await?;
+        let mut sorter = ExternalSorter {
+            schema: self.schema(),
+            expr: self.expr.clone(),
+            memory: self.memory.clone(),
+            batch_size: self.batch_size,
+            buffered: Vec::new(),
+            reservation: None,
+            runs: Vec::new(),
+        };
+        while let Some(batch) = input.next().await {
+            sorter.push(batch?).await?;
+        }
+        sorter.finish().await
+    }
+}
+
+struct ExternalSorter {
+    schema: SchemaRef,
+    expr: Vec<PhysicalSortExpr>,
+    memory: Arc<QueryMemory>,
+    batch_size: usize,
+    buffered: Vec<RecordBatch>,
+    /// Memory held by `buffered`.
+    reservation: Option<MemoryReservation>,
+    runs: Vec<NamedTempFile>,
+}
+
+impl ExternalSorter {
+    async fn push(&mut self, batch: RecordBatch) -> Result<(), DataFusionError> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        let size = batch_memory_size(&batch);
+        let reservation = match self.memory.try_reserve(size) {
+            Some(r) => r,
+            None => {
+                if !self.buffered.is_empty() {
+                    self.spill().await?;
+                }
+                self.memory.reserve(size, "Sort")?
+            }
+        };
+        match &mut self.reservation {
+            Some(r) => r.merge(reservation),
+            None => self.reservation = Some(reservation),
+        }
+        self.buffered.push(batch);
+        Ok(())
+    }
+
+    async fn spill(&mut self) -> Result<(), DataFusionError> {
+        let batches = take_mem(&mut self.buffered);
+        let schema = self.schema.clone();
+        let expr = self.expr.clone();
+        let spill_dir = self.memory.spill_dir().clone();
+        let batch_size = self.batch_size;
+        let run = cube_ext::spawn_blocking(move || -> Result<NamedTempFile, DataFusionError> {
+            let sorted = sort_batches(&schema, &batches, &expr)?;
+            write_run(&spill_dir, &sorted, batch_size)
+        })
+        .await
+        .map_err(|e| DataFusionError::Execution(e.to_string()))??;
+        debug!(
+            "Spilled {} bytes of sort input to {}",
+            self.reservation.as_ref().map(|r| r.bytes()).unwrap_or(0),
+            run.path().display()
+        );
+        self.runs.push(run);
+        self.reservation = None;
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<SendableRecordBatchStream, DataFusionError> {
+        let schema = self.schema.clone();
+        if self.runs.is_empty() {
+            let batches = take_mem(&mut self.buffered);
+            let expr = self.expr.clone();
+            let sorted: Vec<ArrowResult<RecordBatch>> = if batches.is_empty() {
+                Vec::new()
+            } else {
+                let schema = schema.clone();
+                vec![Ok(cube_ext::spawn_blocking(move || {
+                    sort_batches(&schema, &batches, &expr)
+                })
+                .await
+                .map_err(|e| DataFusionError::Execution(e.to_string()))??)]
+            };
+            return Ok(Box::pin(SortedStream {
+                schema,
+                inner: Box::pin(futures::stream::iter(sorted)),
+                _reservation: self.reservation,
+            }));
+        }
+
+        if !self.buffered.is_empty() {
+            self.spill().await?;
+        }
+        let (tx, rx) = mpsc::channel(1);
+        let runs = take_mem(&mut self.runs);
+        let expr = self.expr.clone();
+        let batch_size = self.batch_size;
+        let merge_schema = schema.clone();
+        cube_ext::spawn_blocking(move || {
+            if let Err(e) = merge_runs(&merge_schema, &expr, runs, batch_size, &tx) {
+                let _ = tx.blocking_send(Err(ArrowError::ExternalError(Box::new(e))));
+            }
+        });
+        Ok(Box::pin(SortedStream {
+            schema,
+            inner: Box::pin(ReceiverStream::new(rx)),
+            _reservation: None,
+        }))
+    }
+}
+
+struct SortedStream {
+    schema: SchemaRef,
+    inner: Pin<Box<dyn Stream<Item = ArrowResult<RecordBatch>> + Send>>,
+    _reservation: Option<MemoryReservation>,
+}
+
+impl Stream for SortedStream {
+    type Item = ArrowResult<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.as_mut().poll_next(cx)
+    }
+}
+
+impl RecordBatchStream for SortedStream {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+fn sort_batches(
+    schema: &SchemaRef,
+    batches: &[RecordBatch],
+    expr: &[PhysicalSortExpr],
+) -> Result<RecordBatch, DataFusionError> {
+    let batch = RecordBatch::concat(schema, batches)?;
+    let sort_columns = expr
+        .iter()
+        .map(|e| e.evaluate_to_sort_column(&batch))
+        .collect::<Result<Vec<_>, DataFusionError>>()?;
+    let indices = lexsort_to_indices(&sort_columns, None)?;
+    let columns = batch
+        .columns()
+        .iter()
+        .map(|c| take(c.as_ref(), &indices, None))
+        .collect::<ArrowResult<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+fn write_run(
+    spill_dir: &PathBuf,
+    batch: &RecordBatch,
+    batch_size: usize,
+) -> Result<NamedTempFile, DataFusionError> {
+    std::fs::create_dir_all(spill_dir)?;
+    let mut file = tempfile::Builder::new()
+        .prefix("sort-")
+        .suffix(".arrow")
+        .tempfile_in(spill_dir)?;
+    let schema = batch.schema();
+    let mut writer = FileWriter::try_new(file.as_file_mut(), schema.as_ref())?;
+    let mut row = 0;
+    while row != batch.num_rows() {
+        let len = min(batch.num_rows() - row, batch_size);
+        writer.write(&RecordBatch::try_new(
+            schema.clone(),
+            batch
+                .columns()
+                .iter()
+                .map(|c| slice_copy(c.as_ref(), row, len))
+                .collect(),
+        )?)?;
+        row += len;
+    }
+    writer.finish()?;
+    Ok(file)
+}
+
+/// Reads a sorted run batch by batch, keeping the sort key of the current row.
+struct RunCursor {
+    reader: FileReader<File>,
+    batch: RecordBatch,
+    /// Incremented each time `batch` changes.
+    generation: u64,
+    sort_columns: Vec<ArrayRef>,
+    row: usize,
+    key: Vec<TableValue>,
+}
+
+impl RunCursor {
+    fn open(
+        run: &NamedTempFile,
+        expr: &[PhysicalSortExpr],
+    ) -> Result<Option<Self>, DataFusionError> {
+        let mut reader = FileReader::try_new(run.reopen()?)?;
+        let (batch, sort_columns) = match Self::read_batch(&mut reader, expr)? {
+            Some(b) => b,
+            None => return Ok(None),
+        };
+        let mut cursor = RunCursor {
+            reader,
+            batch,
+            generation: 0,
+            sort_columns,
+            row: 0,
+            key: Vec::new(),
+        };
+        cursor.update_key();
+        Ok(Some(cursor))
+    }
+
+    /// Returns the next non-empty batch with its evaluated sort columns.
+    fn read_batch(
+        reader: &mut FileReader<File>,
+        expr: &[PhysicalSortExpr],
+    ) -> Result<Option<(RecordBatch, Vec<ArrayRef>)>, DataFusionError> {
+        for batch in reader {
+            let batch = batch?;
+            if batch.num_rows() == 0 {
+                continue;
+            }
+            let sort_columns = expr
+                .iter()
+                .map(|e| Ok(e.evaluate_to_sort_column(&batch)?.values))
+                .collect::<Result<Vec<_>, DataFusionError>>()?;
+            return Ok(Some((batch, sort_columns)));
+        }
+        Ok(None)
+    }
+
+    /// Returns false when the run is exhausted.
+    fn next_batch(&mut self, expr: &[PhysicalSortExpr]) -> Result<bool, DataFusionError> {
+        let (batch, sort_columns) = match Self::read_batch(&mut self.reader, expr)? {
+            Some(b) => b,
+            None => return Ok(false),
+        };
+        self.batch = batch;
+        self.sort_columns = sort_columns;
+        self.generation += 1;
+        self.row = 0;
+        self.update_key();
+        Ok(true)
+    }
+
+    /// Returns false when the run is exhausted.
+    fn advance(&mut self, expr: &[PhysicalSortExpr]) -> Result<bool, DataFusionError> {
+        self.row += 1;
+        if self.row == self.batch.num_rows() {
+            return self.next_batch(expr);
+        }
+        self.update_key();
+        Ok(true)
+    }
+
+    fn update_key(&mut self) {
+        let row = self.row;
+        self.key = self
+            .sort_columns
+            .iter()
+            .map(|c| TableValue::from_array(c.as_ref(), row))
+            .collect();
+    }
+}
+
+fn cmp_sort_keys(l: &[TableValue], r: &[TableValue], options: &[SortOptions]) -> Ordering {
+    for i in 0..options.len() {
+        let o = &options[i];
+        let c = match (&l[i], &r[i]) {
+            (TableValue::Null, TableValue::Null) => Ordering::Equal,
+            (TableValue::Null, _) if o.nulls_first => Ordering::Less,
+            (TableValue::Null, _) => Ordering::Greater,
+            (_, TableValue::Null) if o.nulls_first => Ordering::Greater,
+            (_, TableValue::Null) => Ordering::Less,
+            (l, r) => {
+                let c = cmp_same_types(
+                    &TableValueR::from_heap_allocated(l),
+                    &TableValueR::from_heap_allocated(r),
+                );
+                if o.descending {
+                    c.reverse()
+                } else {
+                    c
+                }
+            }
+        };
+        if c != Ordering::Equal {
+            return c;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Part of an output batch taken from a single run.
+struct OutputSlice {
+    cursor: usize,
+    generation: u64,
+    batch: RecordBatch,
+    start: usize,
+    len: usize,
+}
+
+fn merge_runs(
+    schema: &SchemaRef,
+    expr: &[PhysicalSortExpr],
+    runs: Vec<NamedTempFile>,
+    batch_size: usize,
+    tx: &mpsc::Sender<ArrowResult<RecordBatch>>,
+) -> Result<(), DataFusionError> {
+    let options = expr.iter().map(|e| e.options).collect::<Vec<_>>();
+    let mut cursors = Vec::with_capacity(runs.len());
+    for r in &runs {
+        cursors.push(RunCursor::open(r, expr)?);
+    }
+
+    let mut slices: Vec<OutputSlice> = Vec::new();
+    let mut output_rows = 0;
+    loop {
+        // The number of runs is small, a linear scan is good enough.
+        let mut min_cursor: Option<usize> = None;
+        for i in 0..cursors.len() {
+            let c = match &cursors[i] {
+                Some(c) => c,
+                None => continue,
+            };
+            match min_cursor {
+                Some(m)
+                    if cmp_sort_keys(&c.key, &cursors[m].as_ref().unwrap().key, &options)
+                        != Ordering::Less => {}
+                _ => min_cursor = Some(i),
+            }
+        }
+        let i = match min_cursor {
+            Some(i) => i,
+            None => break,
+        };
+
+        let c = cursors[i].as_mut().unwrap();
+        match slices.last_mut() {
+            Some(s)
+                if s.cursor == i && s.generation == c.generation && s.start + s.len == c.row =>
+            {
+                s.len += 1
+            }
+            _ => slices.push(OutputSlice {
+                cursor: i,
+                generation: c.generation,
+                batch: c.batch.clone(),
+                start: c.row,
+                len: 1,
+            }),
+        }
+        output_rows += 1;
+        if !c.advance(expr)? {
+            cursors[i] = None;
+        }
+
+        if output_rows == batch_size {
+            if !send_output(schema, &take_mem(&mut slices), output_rows, tx)? {
+                return Ok(());
+            }
+            output_rows = 0;
+        }
+    }
+    if output_rows != 0 {
+        send_output(schema, &slices, output_rows, tx)?;
+    }
+    Ok(())
+}
+
+/// Returns false if the receiver is gone.
+fn send_output(
+    schema: &SchemaRef,
+    slices: &[OutputSlice],
+    num_rows: usize,
+    tx: &mpsc::Sender<ArrowResult<RecordBatch>>,
+) -> Result<bool, DataFusionError> {
+    let mut columns = Vec::with_capacity(schema.fields().len());
+    for i in 0..schema.fields().len() {
+        let mut data = MutableArrayData::new(
+            slices.iter().map(|s| s.batch.column(i).data()).collect(),
+            false,
+            num_rows,
+        );
+        for (j, s) in slices.iter().enumerate() {
+            data.extend(j, s.start, s.start + s.len);
+        }
+        columns.push(make_array(data.freeze()));
+    }
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+    Ok(tx.blocking_send(Ok(batch)).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray};
+    use arrow::datatypes::{Field, Schema};
+    use datafusion::physical_plan::collect;
+    use datafusion::physical_plan::memory::MemoryExec;
+    use itertools::Itertools;
+
+    fn batches(schema: &SchemaRef, rows: &[(Option<i64>, &str)]) -> Vec<RecordBatch> {
+        rows.chunks(3)
+            .map(|c| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int64Array::from(c.iter().map(|r| r.0).collect_vec())),
+                        Arc::new(StringArray::from(c.iter().map(|r| r.1).collect_vec())),
+                    ],
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    fn output(batches: &[RecordBatch]) -> Vec<(Option<i64>, String)> {
+        let mut r = Vec::new();
+        for b in batches {
+            let a = b.column(0).as_any().downcast_ref::<Int64Array>().unwrap();
+            let s = b.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+            for i in 0..b.num_rows() {
+                r.push((
+                    if a.is_valid(i) {
+                        Some(a.value(i))
+                    } else {
+                        None
+                    },
+                    s.value(i).to_string(),
+                ));
+            }
+        }
+        r
+    }
+
+    /// Spilling sort runs with the memory limit of a single input batch.
+    async fn sort(spill: bool, descending: bool) -> Vec<(Option<i64>, String)> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int64, true),
+            Field::new("s", DataType::Utf8, false),
+        ]));
+        let rows = (0..20)
+            .map(|i| {
+                let a = if i % 7 == 0 {
+                    None
+                } else {
+                    Some((i * 13) % 10)
+                };
+                (a, ["x", "y", "z"][i as usize % 3])
+            })
+            .collect_vec();
+        let batches = batches(&schema, &rows);
+        let memory_limit = if spill {
+            batches.iter().map(batch_memory_size).max().unwrap()
+        } else {
+            0
+        };
+        let input = MemoryExec::try_new(&[batches], schema.clone(), None).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let memory = QueryMemory::new(memory_limit, dir.path().to_path_buf());
+        let expr = vec![
+            PhysicalSortExpr {
+                expr: Arc::new(Column::new("a", 0)),
+                options: SortOptions {
+                    descending,
+                    nulls_first: false,
+                },
+            },
+            PhysicalSortExpr {
+                expr: Arc::new(Column::new("s", 1)),
+                options: SortOptions::default(),
+            },
+        ];
+        let sort = SpillSortExec::new(Arc::new(input), expr, memory.clone(), 4);
+        let r = output(&collect(Arc::new(sort)).await.unwrap());
+        assert_eq!(memory.used(), 0);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+        r
+    }
+
+    #[tokio::test]
+    async fn spills_and_merges() {
+        for descending in [false, true].iter() {
+            let in_memory = sort(false, *descending).await;
+            // Every batch goes to a separate run.
+            let spilled = sort(true, *descending).await;
+            assert_eq!(in_memory, spilled);
+
+            let mut expected = in_memory.clone();
+            expected.sort_by(|l, r| {
+                let c = match (l.0, r.0) {
+                    (None, None) => Ordering::Equal,
+                    (None, _) => Ordering::Greater,
+                    (_, None) => Ordering::Less,
+                    (Some(l), Some(r)) if *descending => r.cmp(&l),
+                    (Some(l), Some(r)) => l.cmp(&r),
+                };
+                c.then_with(|| l.1.cmp(&r.1))
+            });
+            assert_eq!(in_memory, expected);
+            assert_eq!(in_memory.len(), 20);
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_when_batch_does_not_fit() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int64, true),
+            Field::new("s", DataType::Utf8, false),
+        ]));
+        let rows = vec![(Some(1), "x"), (Some(2), "y")];
+        let input = MemoryExec::try_new(&[batches(&schema, &rows)], schema, None).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let memory = QueryMemory::new(1, dir.path().to_path_buf());
+        let expr = vec![PhysicalSortExpr {
+            expr: Arc::new(Column::new("a", 0)),
+            options: SortOptions::default(),
+        }];
+        let sort = SpillSortExec::new(Arc::new(input), expr, memory.clone(), 4);
+        let err = collect(Arc::new(sort)).await.unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Sort exceeds query memory limit of 1 bytes"));
+        assert_eq!(memory.used(), 0);
+    }
+}
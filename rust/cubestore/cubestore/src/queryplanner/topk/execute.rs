@@ -1,3 +1,4 @@
+use crate::queryplanner::query_memory::{batch_memory_size, MemoryReservation, QueryMemory};
 use crate::queryplanner::topk::SortColumn;
 use arrow::array::ArrayRef;
 use arrow::compute::SortOptions;
@@ -28,6 +29,7 @@ use std::any::Any;
 use std::collections::BTreeSet;
 use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
+use std::mem::{size_of, take};
 use std::sync::Arc;
 
 #[derive(Debug)]
@@ -40,6 +42,8 @@ pub struct AggregateTopKExec {
     /// Always an instance of ClusterSendExec or WorkerExec.
     pub cluster: Arc<dyn ExecutionPlan>,
     pub schema: SchemaRef,
+    /// Groups seen so far are accounted here when the query has a memory limit.
+    pub memory: Option<Arc<QueryMemory>>,
 }
 
 /// Third item is the neutral value for the corresponding aggregate function.
@@ -67,6 +71,20 @@ impl AggregateTopKExec {
             order_by,
             cluster,
             schema,
+            memory: None,
+        }
+    }
+
+    pub fn with_memory(&self, memory: Arc<QueryMemory>) -> AggregateTopKExec {
+        AggregateTopKExec {
+            limit: self.limit,
+            key_len: self.key_len,
+            agg_expr: self.agg_expr.clone(),
+            agg_descr: self.agg_descr.clone(),
+            order_by: self.order_by.clone(),
+            cluster: self.cluster.clone(),
+            schema: self.schema.clone(),
+            memory: Some(memory),
         }
     }
 
@@ -136,6 +154,7 @@ impl ExecutionPlan for AggregateTopKExec {
             order_by: self.order_by.clone(),
             cluster,
             schema: self.schema.clone(),
+            memory: self.memory.clone(),
         }))
     }
 
@@ -176,6 +195,7 @@ impl ExecutionPlan for AggregateTopKExec {
             &self.agg_expr,
             &self.agg_descr,
             &mut buffer,
+            self.memory.as_ref(),
         )?;
         let mut wanted_nodes = vec![true; nodes];
         let mut batches = Vec::with_capacity(nodes);
@@ -227,6 +247,9 @@ struct TopKState<'a> {
     groups: HashSet<GroupKey<'a>>,
     /// Final output.
     top: Vec<usize>,
+    memory: Option<&'a Arc<QueryMemory>>,
+    /// Memory held by groups in `buffer`.
+    reservation: Option<MemoryReservation>,
 }
 
 struct Group {
@@ -318,6 +341,7 @@ impl TopKState<'_> {
         agg_expr: &'a Vec<Arc<dyn AggregateExpr>>,
         agg_descr: &'a [AggDescr],
         buffer: &'a mut TopKBuffer,
+        memory: Option<&'a Arc<QueryMemory>>,
     ) -> Result<TopKState<'a>, DataFusionError> {
         Ok(TopKState {
             limit,
@@ -332,6 +356,8 @@ impl TopKState<'_> {
             sorted: BTreeSet::new(),
             groups: HashSet::new(),
             top: Vec::new(),
+            memory,
+            reservation: None,
         })
     }
 
@@ -388,6 +414,18 @@ impl TopKState<'_> {
             .collect_vec();
         num_rows.sort_unstable();
 
+        // Groups keep a key and two accumulator sets, roughly twice the size of an input row.
+        let group_sizes = batches
+            .iter()
+            .map(|b| match b {
+                Some(b) if b.num_rows() != 0 => {
+                    size_of::<Group>() as u64 + 2 * batch_memory_size(b) / b.num_rows() as u64
+                }
+                _ => 0,
+            })
+            .collect_vec();
+        let mut new_groups_memory = 0;
+
         let mut row_i = 0;
         let mut pop_top_counter = self.limit;
         for row_limit in num_rows {
@@ -438,6 +476,7 @@ impl TopKState<'_> {
                         g.accumulators = create_accumulators(self.agg_expr).unwrap();
                         g.estimates = create_accumulators(self.agg_expr).unwrap();
                         g.nodes = self.finished_nodes.clone();
+                        new_groups_memory += group_sizes[node];
                     }
 
                     // Update the group.
@@ -472,6 +511,9 @@ impl TopKState<'_> {
                 }
 
                 row_i += 1;
+                if new_groups_memory != 0 {
+                    self.reserve(take(&mut new_groups_memory))?;
+                }
 
                 pop_top_counter -= 1;
                 if pop_top_counter == 0 {
@@ -494,6 +536,20 @@ impl TopKState<'_> {
         self.pop_top_elements()
     }
 
+    /// Top-k can't spill its groups, so the query fails once it exceeds the memory limit.
+    fn reserve(&mut self, bytes: u64) -> Result<(), DataFusionError> {
+        let memory = match self.memory {
+            Some(m) => m,
+            None => return Ok(()),
+        };
+        let reservation = memory.reserve(bytes, "Top-k aggregation")?;
+        match &mut self.reservation {
+            Some(r) => r.merge(reservation),
+            None => self.reservation = Some(reservation),
+        }
+        Ok(())
+    }
+
     /// Moves groups with known top scores into the [top].
     /// Returns true iff [top] contains the correct answer to the top-k query.
     fn pop_top_elements(&mut self) -> Result<bool, DataFusionError> {
@@ -764,6 +820,7 @@ fn to_min_value(s: &mut ScalarValue) {
 
 #[cfg(test)]
 mod tests {
+    use crate::queryplanner::query_memory::QueryMemory;
     use crate::queryplanner::topk::{AggregateTopKExec, SortColumn};
     use arrow::array::{Array, ArrayRef, Int64Array};
     use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
@@ -782,6 +839,7 @@ mod tests {
     use itertools::Itertools;
 
     use std::iter::FromIterator;
+    use std::path::PathBuf;
     use std::sync::Arc;
 
     #[tokio::test]
@@ -887,6 +945,43 @@ mod tests {
         assert_eq!(r, vec![vec![1, 1101], vec![2, 1100]]);
     }
 
+    #[tokio::test]
+    async fn topk_memory_limit() {
+        let proto = mock_topk(
+            2,
+            &[DataType::Int64],
+            &[AggregateFunction::Sum],
+            vec![SortColumn {
+                agg_index: 0,
+                asc: false,
+                nulls_first: true,
+            }],
+        )
+        .unwrap();
+        let bs = proto.cluster.schema();
+        let inputs = || {
+            vec![
+                vec![make_batch(&bs, &[&[1, 100], &[0, 50], &[8, 11], &[6, 10]])],
+                vec![make_batch(&bs, &[&[6, 40], &[1, 20], &[0, 15], &[8, 9]])],
+            ]
+        };
+
+        let memory = QueryMemory::new(1 << 20, PathBuf::new());
+        let r = run_topk(&proto.with_memory(memory.clone()), inputs())
+            .await
+            .unwrap();
+        assert_eq!(r, vec![vec![1, 120], vec![0, 65]]);
+        assert!(memory.peak() > 0);
+        assert_eq!(memory.used(), 0);
+
+        let memory = QueryMemory::new(1, PathBuf::new());
+        let err = run_topk(&proto.with_memory(memory.clone()), inputs())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("exceeds query memory limit"));
+        assert_eq!(memory.used(), 0);
+    }
+
     #[tokio::test]
     async fn topk_missing_elements() {
         // Start with sum, descending order.
@@ -65,6 +65,9 @@ pub fn materialize_topk(p: LogicalPlan) -> Result<LogicalPlan, DataFusionError>
                                 } else {
                                     return Ok(p);
                                 }
+                                if !may_have_more_groups(*limit, group_expr, cs) {
+                                    return Ok(p);
+                                }
                                 let topk = LogicalPlan::Extension {
                                     node: Arc::new(ClusterAggregateTopK {
                                         limit: *limit,
@@ -114,6 +117,35 @@ pub fn materialize_topk(p: LogicalPlan) -> Result<LogicalPlan, DataFusionError>
     Ok(p)
 }
 
+/// Top-k pays off only when there are more groups than `limit`. Otherwise the usual aggregation
+/// reads the same rows in a single pass. Uses statistics collected by `ANALYZE TABLE` to estimate
+/// the number of groups and assumes there are many when they are not available.
+fn may_have_more_groups(limit: usize, group_expr: &[Expr], cs: &ClusterSendNode) -> bool {
+    let mut groups: u64 = 0;
+    for snapshot in cs.snapshots.iter().flatten() {
+        let index = snapshot.index.get_row();
+        let stats = match index.statistics() {
+            Some(stats) => stats,
+            None => return true,
+        };
+        let mut index_groups: u64 = 1;
+        for e in group_expr {
+            let column = match e {
+                Expr::Column(c) => stats.column(&c.name),
+                _ => None,
+            };
+            let column = match column {
+                Some(c) => c,
+                None => return true,
+            };
+            let nulls = if column.null_count() == 0 { 0 } else { 1 };
+            index_groups = index_groups.saturating_mul(column.distinct_count() + nulls);
+        }
+        groups = groups.saturating_add(index_groups.min(stats.row_count()));
+    }
+    groups == 0 || limit < groups as usize
+}
+
 fn aggr_exprs_allow_topk(agg_exprs: &[Expr]) -> bool {
     for a in agg_exprs {
         match a {
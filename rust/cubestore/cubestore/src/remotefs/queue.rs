@@ -1,3 +1,4 @@
+use crate::app_metrics;
 use crate::config::ConfigObj;
 use crate::di_service;
 use crate::remotefs::{RemoteFile, RemoteFs};
@@ -8,6 +9,7 @@ use core::fmt;
 use datafusion::cube_ext;
 use deadqueue::unlimited;
 use futures::future::join_all;
+use futures::Future;
 use log::error;
 use smallvec::alloc::fmt::Formatter;
 use std::collections::HashSet;
@@ -15,8 +17,10 @@ use std::fmt::Debug;
 use std::fs::Metadata;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::{broadcast, watch, RwLock};
 use tokio::time::Duration;
+use tracing::instrument;
 
 pub struct QueueRemoteFs {
     config: Arc<dyn ConfigObj>,
@@ -154,12 +158,18 @@ impl QueueRemoteFs {
                     .await?
                     .contains(remote_path.as_str())
                 {
-                    let mut res = self
-                        .remote_fs
-                        .upload_file(&temp_upload_path, &remote_path)
-                        .await;
+                    let mut res = time_remote_fs_operation(
+                        "upload",
+                        self.remote_fs.upload_file(&temp_upload_path, &remote_path),
+                    )
+                    .await;
                     if let Ok(size) = res {
-                        match self.remote_fs.list_with_metadata(&remote_path).await {
+                        match time_remote_fs_operation(
+                            "list",
+                            self.remote_fs.list_with_metadata(&remote_path),
+                        )
+                        .await
+                        {
                             Ok(list) => {
                                 let list_res = list.iter().next().ok_or(CubeError::internal(
                                     format!("File {} can't be listed after upload", remote_path),
@@ -192,7 +202,8 @@ impl QueueRemoteFs {
             RemoteFsOp::Delete(file) => {
                 self.result_sender.send(RemoteFsOpResult::Delete(
                     file.to_string(),
-                    self.remote_fs.delete_file(file.as_str()).await,
+                    time_remote_fs_operation("delete", self.remote_fs.delete_file(file.as_str()))
+                        .await,
                 ))?;
             }
             x => panic!("Unexpected operation: {:?}", x),
@@ -203,10 +214,12 @@ impl QueueRemoteFs {
     async fn download_loop(&self, to_process: RemoteFsOp) -> Result<(), CubeError> {
         match to_process {
             RemoteFsOp::Download(file, expected_file_size) => {
-                let result = self
-                    .remote_fs
-                    .download_file(file.as_str(), expected_file_size)
-                    .await;
+                let result = time_remote_fs_operation(
+                    "download",
+                    self.remote_fs
+                        .download_file(file.as_str(), expected_file_size),
+                )
+                .await;
                 let mut downloading =
                     acquire_lock("download loop downloading", self.downloading.write()).await?;
                 self.result_sender
@@ -343,6 +356,7 @@ impl RemoteFs for QueueRemoteFs {
         }
     }
 
+    #[instrument(level = "trace", skip(self))]
     async fn download_file(
         &self,
         remote_path: &str,
@@ -418,11 +432,11 @@ impl RemoteFs for QueueRemoteFs {
     }
 
     async fn list(&self, remote_prefix: &str) -> Result<Vec<String>, CubeError> {
-        self.remote_fs.list(remote_prefix).await
+        time_remote_fs_operation("list", self.remote_fs.list(remote_prefix)).await
     }
 
     async fn list_with_metadata(&self, remote_prefix: &str) -> Result<Vec<RemoteFile>, CubeError> {
-        self.remote_fs.list_with_metadata(remote_prefix).await
+        time_remote_fs_operation("list", self.remote_fs.list_with_metadata(remote_prefix)).await
     }
 
     async fn local_path(&self) -> String {
@@ -434,6 +448,16 @@ impl RemoteFs for QueueRemoteFs {
     }
 }
 
+async fn time_remote_fs_operation<T>(operation: &str, f: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    let res = f.await;
+    app_metrics::REMOTE_FS_OPERATION_TIME_MS.report_with_tags(
+        start.elapsed().as_millis() as i64,
+        &[("operation", operation)],
+    );
+    res
+}
+
 impl QueueRemoteFs {
     async fn check_file_size(
         remote_path: &str,
@@ -0,0 +1,309 @@
+use crate::metastore::table::TablePath;
+use crate::metastore::MetaStore;
+use crate::CubeError;
+use chrono::{DateTime, Duration, Utc};
+use itertools::Itertools;
+use std::cmp::max;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Keeps the time of the last query to each table. Access times are not persisted, so every
+/// table counts as accessed when the process started.
+pub struct TableAccessTracker {
+    started_at: DateTime<Utc>,
+    last_access: Mutex<HashMap<u64, DateTime<Utc>>>,
+}
+
+crate::di_service!(TableAccessTracker, []);
+
+impl TableAccessTracker {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            started_at: Utc::now(),
+            last_access: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn record(&self, table_ids: impl IntoIterator<Item = u64>, at: DateTime<Utc>) {
+        let mut last_access = self.last_access.lock().unwrap();
+        for id in table_ids {
+            let time = last_access.entry(id).or_insert(at);
+            *time = max(*time, at);
+        }
+    }
+
+    /// Time of the last recorded query to the table, if any.
+    pub fn last_recorded(&self, table_id: u64) -> Option<DateTime<Utc>> {
+        self.last_access.lock().unwrap().get(&table_id).cloned()
+    }
+
+    fn last_access(&self, table: &TablePath) -> DateTime<Utc> {
+        let mut res = self.started_at;
+        if let Some(created_at) = table.table.get_row().created_at() {
+            res = max(res, *created_at);
+        }
+        if let Some(recorded) = self.last_recorded(table.table.get_id()) {
+            res = max(res, recorded);
+        }
+        res
+    }
+
+    fn forget(&self, table_id: u64) {
+        self.last_access.lock().unwrap().remove(&table_id);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GCReason {
+    /// Superseded by `keep_versions` more recent versions of the same pre-aggregation table.
+    KeepVersions,
+    /// Not queried for longer than `ttl`.
+    Ttl,
+}
+
+impl fmt::Display for GCReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GCReason::KeepVersions => f.write_str("keep_versions"),
+            GCReason::Ttl => f.write_str("ttl"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GCCandidate {
+    pub table: TablePath,
+    pub reason: GCReason,
+    pub last_accessed_at: Option<DateTime<Utc>>,
+}
+
+/// Tables to be dropped according to the lifecycle policies of their schemas.
+pub async fn gc_candidates(
+    meta_store: &dyn MetaStore,
+    access: &TableAccessTracker,
+    now: DateTime<Utc>,
+) -> Result<Vec<GCCandidate>, CubeError> {
+    let tables = meta_store.get_tables_with_path(false).await?;
+    Ok(compute_gc_candidates(tables.as_ref(), access, now))
+}
+
+/// Drops all current GC candidates and returns them.
+pub async fn drop_gc_candidates(
+    meta_store: &dyn MetaStore,
+    access: &TableAccessTracker,
+) -> Result<Vec<GCCandidate>, CubeError> {
+    let candidates = gc_candidates(meta_store, access, Utc::now()).await?;
+    for c in candidates.iter() {
+        log::info!(
+            "Dropping table {} (#{}) by schema lifecycle policy: {}",
+            c.table.table_name(),
+            c.table.table.get_id(),
+            c.reason
+        );
+        meta_store.drop_table(c.table.table.get_id()).await?;
+        access.forget(c.table.table.get_id());
+    }
+    Ok(candidates)
+}
+
+fn compute_gc_candidates(
+    tables: &[TablePath],
+    access: &TableAccessTracker,
+    now: DateTime<Utc>,
+) -> Vec<GCCandidate> {
+    let mut res = Vec::new();
+    let with_policy = tables
+        .iter()
+        .filter(|t| !t.schema.get_row().lifecycle().is_empty())
+        .collect_vec();
+
+    let mut superseded = Vec::new();
+    let groups = with_policy
+        .iter()
+        .filter_map(|t| {
+            let keep = t.schema.get_row().lifecycle().keep_versions?;
+            let base = version_base_name(t.table.get_row().get_table_name())?;
+            Some(((t.schema.get_id(), base), (keep, *t)))
+        })
+        .into_group_map();
+    for (_, mut versions) in groups {
+        let keep = versions[0].0 as usize;
+        // Newest first.
+        versions.sort_by(|(_, l), (_, r)| {
+            r.table
+                .get_row()
+                .created_at()
+                .cmp(l.table.get_row().created_at())
+                .then(r.table.get_id().cmp(&l.table.get_id()))
+        });
+        superseded.extend(
+            versions
+                .into_iter()
+                .skip(keep)
+                .map(|(_, t)| t.table.get_id()),
+        );
+    }
+
+    for t in with_policy {
+        let reason = if superseded.contains(&t.table.get_id()) {
+            GCReason::KeepVersions
+        } else {
+            match t.schema.get_row().lifecycle().ttl_secs {
+                Some(ttl) if access.last_access(t) + Duration::seconds(ttl as i64) < now => {
+                    GCReason::Ttl
+                }
+                _ => continue,
+            }
+        };
+        res.push(GCCandidate {
+            table: t.clone(),
+            reason,
+            last_accessed_at: access.last_recorded(t.table.get_id()),
+        });
+    }
+    res
+}
+
+/// Versions of a pre-aggregation table are named `<name>_<content>_<structure>_<updated>`,
+/// where `<content>` and `<structure>` are 8 character hashes and `<updated>` is a unix timestamp.
+/// Returns `<name>` for such tables.
+fn version_base_name(table_name: &str) -> Option<&str> {
+    let parts = table_name.rsplitn(4, '_').collect_vec();
+    if parts.len() != 4 || parts[3].is_empty() {
+        return None;
+    }
+    let is_hash = |p: &str| {
+        p.len() == 8
+            && p.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    };
+    let is_timestamp = |p: &str| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit());
+    if !is_timestamp(parts[0]) || !is_hash(parts[1]) || !is_hash(parts[2]) {
+        return None;
+    }
+    Some(parts[3])
+}
+
+/// Parses durations like `'7 days'` or `'12h'`. Plain numbers are seconds.
+pub fn parse_ttl(s: &str) -> Result<u64, CubeError> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| CubeError::user(format!("Can't parse ttl '{}'", s)))?;
+    let multiplier = match unit.trim().to_lowercase().as_str() {
+        "" | "s" | "sec" | "second" | "seconds" => 1,
+        "m" | "min" | "minute" | "minutes" => 60,
+        "h" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => 24 * 60 * 60,
+        "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(CubeError::user(format!(
+                "Can't parse ttl '{}': unknown unit '{}'",
+                s,
+                unit.trim()
+            )))
+        }
+    };
+    Ok(number * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metastore::schema::SchemaLifecycle;
+    use crate::metastore::table::Table;
+    use crate::metastore::{IdRow, Schema};
+
+    #[test]
+    fn ttl() {
+        assert_eq!(parse_ttl("7 days").unwrap(), 7 * 24 * 3600);
+        assert_eq!(parse_ttl("12h").unwrap(), 12 * 3600);
+        assert_eq!(parse_ttl("30").unwrap(), 30);
+        assert!(parse_ttl("a week").is_err());
+        assert!(parse_ttl("3 fortnights").is_err());
+    }
+
+    #[test]
+    fn version_names() {
+        assert_eq!(
+            version_base_name("orders_main_abcd1234_defg5678_1609459200"),
+            Some("orders_main")
+        );
+        assert_eq!(version_base_name("events_2021_01_01"), None);
+        assert_eq!(version_base_name("events_2021_02_01"), None);
+        assert_eq!(version_base_name("a_b_c_d"), None);
+        assert_eq!(version_base_name("_abcd1234_defg5678_1609459200"), None);
+        assert_eq!(version_base_name("orders_abcd1234_defg5678_latest"), None);
+    }
+
+    #[test]
+    fn candidates() {
+        let versioned = Arc::new(IdRow::new(
+            1,
+            Schema::new("pre_aggregations".to_string()).with_lifecycle(SchemaLifecycle {
+                keep_versions: Some(2),
+                ttl_secs: Some(3600),
+            }),
+        ));
+        let unmanaged = Arc::new(IdRow::new(2, Schema::new("other".to_string())));
+        let table = |id: u64, schema: &Arc<IdRow<Schema>>, name: &str| TablePath {
+            table: IdRow::new(
+                id,
+                Table::new(
+                    name.to_string(),
+                    schema.get_id(),
+                    Vec::new(),
+                    None,
+                    None,
+                    true,
+                    None,
+                    None,
+                    None,
+                    Vec::new(),
+                ),
+            ),
+            schema: schema.clone(),
+        };
+        let tables = vec![
+            table(1, &versioned, "orders_main_abcd1234_defg5678_1609459200"),
+            table(2, &versioned, "orders_main_abcd1234_ghij0123_1609459300"),
+            table(3, &versioned, "orders_main_xyzw4321_ghij0123_1609459400"),
+            table(4, &versioned, "users_rollup_abcd1234_defg5678_1609459200"),
+            table(5, &versioned, "plain"),
+            table(6, &unmanaged, "orders_main_abcd1234_defg5678_1609459200"),
+            table(7, &versioned, "events_2021_01_01"),
+            table(8, &versioned, "events_2021_02_01"),
+            table(9, &versioned, "events_2021_03_01"),
+        ];
+
+        let access = TableAccessTracker::new();
+        let now = Utc::now();
+        let ids = |c: Vec<GCCandidate>| {
+            c.into_iter()
+                .map(|c| (c.table.table.get_id(), c.reason))
+                .collect_vec()
+        };
+        assert_eq!(
+            ids(compute_gc_candidates(&tables, &access, now)),
+            vec![(1, GCReason::KeepVersions)]
+        );
+
+        access.record(vec![4, 7, 8, 9], now + Duration::minutes(30));
+        assert_eq!(
+            ids(compute_gc_candidates(
+                &tables,
+                &access,
+                now + Duration::minutes(80)
+            )),
+            vec![
+                (1, GCReason::KeepVersions),
+                (2, GCReason::Ttl),
+                (3, GCReason::Ttl),
+                (5, GCReason::Ttl)
+            ]
+        );
+    }
+}
@@ -1,12 +1,16 @@
+pub mod lifecycle;
+
+use crate::app_metrics;
 use crate::cluster::{pick_worker_by_ids, Cluster};
 use crate::config::ConfigObj;
-use crate::metastore::job::{Job, JobType};
+use crate::metastore::job::{Job, JobStatus, JobType};
 use crate::metastore::partition::partition_file_name;
 use crate::metastore::table::Table;
 use crate::metastore::{
     deactivate_table_on_corrupt_data, IdRow, MetaStore, MetaStoreEvent, Partition, RowKey, TableId,
 };
 use crate::remotefs::RemoteFs;
+use crate::scheduler::lifecycle::{drop_gc_candidates, TableAccessTracker};
 use crate::store::{ChunkStore, WALStore};
 use crate::util::time_span::warn_long_fut;
 use crate::util::WorkerLoop;
@@ -17,7 +21,7 @@ use flatbuffers::bitflags::_core::cmp::Ordering;
 use flatbuffers::bitflags::_core::time::Duration;
 use futures_timer::Delay;
 use log::error;
-use std::collections::{BinaryHeap, HashSet};
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::broadcast::Receiver;
 use tokio::sync::{broadcast, Mutex, Notify, RwLock};
@@ -45,12 +49,14 @@ impl SchedulerImpl {
         remote_fs: Arc<dyn RemoteFs>,
         event_receiver: Receiver<MetaStoreEvent>,
         config: Arc<dyn ConfigObj>,
+        table_access: Arc<TableAccessTracker>,
     ) -> SchedulerImpl {
         let cancel_token = CancellationToken::new();
         let gc_loop = DataGCLoop::new(
             meta_store.clone(),
             remote_fs.clone(),
             config.clone(),
+            table_access,
             cancel_token.clone(),
         );
         SchedulerImpl {
@@ -215,6 +221,29 @@ impl SchedulerImpl {
             error!("Error deleting middle man partitions: {}", e);
         }
 
+        if let Err(e) = self.report_jobs_queue_depth().await {
+            error!("Error reporting jobs queue depth: {}", e);
+        }
+
+        Ok(())
+    }
+
+    async fn report_jobs_queue_depth(&self) -> Result<(), CubeError> {
+        let mut scheduled = HashMap::new();
+        for job in self.meta_store.all_jobs().await? {
+            if let JobStatus::Scheduled(_) = job.get_row().status() {
+                *scheduled
+                    .entry(job.get_row().job_type().name())
+                    .or_insert(0) += 1;
+            }
+        }
+        // Job types without scheduled jobs are reported too, so gauges don't stick to old values.
+        for job_type in JobType::ALL_NAMES.iter() {
+            app_metrics::JOBS_QUEUE_DEPTH.report_with_tags(
+                scheduled.get(job_type).cloned().unwrap_or(0),
+                &[("job_type", *job_type)],
+            );
+        }
         Ok(())
     }
 
@@ -750,10 +779,12 @@ enum GCTask {
 
 /// Cleans up deactivated partitions and chunks on remote fs.
 /// Ensures enough time has passed that queries over those files finish.
+/// Also drops tables according to the lifecycle policies of their schemas.
 struct DataGCLoop {
     metastore: Arc<dyn MetaStore>,
     remote_fs: Arc<dyn RemoteFs>,
     config: Arc<dyn ConfigObj>,
+    table_access: Arc<TableAccessTracker>,
     stop: CancellationToken,
     task_notify: Notify,
     pending: RwLock<(BinaryHeap<GCTimedTask>, HashSet<GCTask>)>,
@@ -764,12 +795,14 @@ impl DataGCLoop {
         metastore: Arc<dyn MetaStore>,
         remote_fs: Arc<dyn RemoteFs>,
         config: Arc<dyn ConfigObj>,
+        table_access: Arc<TableAccessTracker>,
         stop: CancellationToken,
     ) -> Arc<Self> {
         Arc::new(DataGCLoop {
             metastore,
             remote_fs,
             config,
+            table_access,
             stop,
             task_notify: Notify::new(),
             pending: RwLock::new((BinaryHeap::new(), HashSet::new())),
@@ -799,6 +832,7 @@ impl DataGCLoop {
     }
 
     async fn run(&self) {
+        let mut last_lifecycle_check = Instant::now();
         loop {
             tokio::select! {
                 _ = self.stop.cancelled() => {
@@ -808,6 +842,16 @@ impl DataGCLoop {
                 _ = self.task_notify.notified() => {}
             };
 
+            if last_lifecycle_check.elapsed() >= Duration::from_secs(self.config.gc_loop_interval())
+            {
+                last_lifecycle_check = Instant::now();
+                if let Err(e) =
+                    drop_gc_candidates(self.metastore.as_ref(), self.table_access.as_ref()).await
+                {
+                    log::error!("Error enforcing schema lifecycle policies: {}", e);
+                }
+            }
+
             while self
                 .pending
                 .read()
@@ -1,3 +1,4 @@
+use crate::app_metrics;
 use crate::queryplanner::serialized_plan::SerializedPlan;
 use crate::store::DataFrame;
 use crate::CubeError;
@@ -60,6 +61,19 @@ impl SqlResultCache {
         plan: SerializedPlan,
         exec: impl FnOnce(SerializedPlan) -> F,
     ) -> Result<Arc<DataFrame>, CubeError>
+    where
+        F: Future<Output = Result<DataFrame, CubeError>> + Send + 'static,
+    {
+        Ok(self.get_with_cache_hit(query, plan, exec).await?.0)
+    }
+
+    /// Same as [get], but also tells whether the result was taken from the cache.
+    pub async fn get_with_cache_hit<F>(
+        &self,
+        query: &str,
+        plan: SerializedPlan,
+        exec: impl FnOnce(SerializedPlan) -> F,
+    ) -> Result<(Arc<DataFrame>, bool), CubeError>
     where
         F: Future<Output = Result<DataFrame, CubeError>> + Send + 'static,
     {
@@ -78,6 +92,7 @@ impl SqlResultCache {
 
         if let Some(sender) = sender {
             trace!("Missing cache for '{}'", query);
+            app_metrics::SQL_CACHE_MISSES.increment();
             let result = exec(plan).await.map(|d| Arc::new(d));
             if let Err(e) = sender.send(Some(result.clone())) {
                 trace!(
@@ -89,17 +104,18 @@ impl SqlResultCache {
                 trace!("Removing error result from cache");
                 self.cache.write().await.pop(&key);
             }
-            return result;
+            return Ok((result?, false));
         }
 
         if let Some(receiver) = &mut receiver {
+            app_metrics::SQL_CACHE_HITS.increment();
             loop {
                 receiver.changed().await?;
                 let x = receiver.borrow();
                 let value = x.as_ref();
                 if let Some(value) = value {
                     trace!("Using cache for '{}'", query);
-                    return value.clone();
+                    return Ok((value.clone()?, true));
                 }
             }
         }
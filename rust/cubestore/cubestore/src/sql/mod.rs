@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use arrow::array::*;
 use arrow::compute::kernels::cast_utils::string_to_timestamp_nanos;
@@ -14,6 +14,7 @@ use chrono::format::Pad::Zero;
 use chrono::format::Parsed;
 use chrono::{ParseResult, Utc};
 use datafusion::cube_ext;
+use datafusion::physical_plan::merge::MergeExec;
 use datafusion::physical_plan::ExecutionPlan;
 use datafusion::sql::parser::Statement as DFStatement;
 use futures::future::join_all;
@@ -31,6 +32,7 @@ use tokio::io::AsyncWriteExt;
 use tokio::time::timeout;
 use tracing::instrument;
 use tracing_futures::WithSubscriber;
+use uuid::Uuid;
 
 use cubehll::HllSketch;
 use parser::Statement as CubeStoreStatement;
@@ -38,14 +40,18 @@ use parser::Statement as CubeStoreStatement;
 use crate::cluster::{Cluster, JobEvent, JobResultListener};
 use crate::config::injection::DIService;
 use crate::config::ConfigObj;
+use crate::export::{export_batches, export_remote_prefix, ExportOptions};
 use crate::import::limits::ConcurrencyLimits;
 use crate::import::{ImportService, Ingestion};
-use crate::metastore::job::JobType;
+use crate::metastore::job::{Job, JobType};
 use crate::metastore::multi_index::MultiIndex;
+use crate::metastore::schema::SchemaLifecycle;
 use crate::metastore::source::SourceCredentials;
+use crate::metastore::statistics::IndexStatistics;
+use crate::metastore::table::{AggregateFunction, Table};
 use crate::metastore::{
-    is_valid_plain_binary_hll, table::Table, HllFlavour, IdRow, ImportFormat, Index, IndexDef,
-    MetaStoreTable, RowKey, Schema, TableId,
+    is_valid_plain_binary_hll, HllFlavour, IdRow, ImportFormat, Index, IndexDef, MetaStoreTable,
+    RowKey, Schema, TableId,
 };
 use crate::queryplanner::panic::PanicWorkerNode;
 use crate::queryplanner::pretty_printers::{pp_phys_plan, pp_plan};
@@ -53,8 +59,11 @@ use crate::queryplanner::query_executor::{batch_to_dataframe, ClusterSendExec, Q
 use crate::queryplanner::serialized_plan::{RowFilter, SerializedPlan};
 use crate::queryplanner::{PlanningMeta, QueryPlan, QueryPlanner};
 use crate::remotefs::RemoteFs;
+use crate::scheduler::lifecycle::parse_ttl;
 use crate::sql::cache::SqlResultCache;
 use crate::sql::parser::{CubeStoreParser, PartitionedIndexRef, SystemCommand};
+use crate::sql::query_history::{QueryHistory, QueryStats};
+use crate::sql::query_pools::{parse_set_query_pool, QueryPools};
 use crate::store::ChunkDataStore;
 use crate::table::{data, Row, TableValue, TimestampValue};
 use crate::telemetry::incoming_traffic_agent_event;
@@ -72,6 +81,8 @@ use std::mem::take;
 
 pub mod cache;
 pub(crate) mod parser;
+pub mod query_history;
+pub mod query_pools;
 
 #[async_trait]
 pub trait SqlService: DIService + Send + Sync {
@@ -105,6 +116,10 @@ pub struct QueryPlans {
 pub struct SqlQueryContext {
     pub user: Option<String>,
     pub trace_obj: Option<String>,
+    /// Set by `SET query_pool = '<name>'` for the rest of the session.
+    pub query_pool: Option<String>,
+    /// Location of the file sent by the client for `LOAD DATA LOCAL INFILE`.
+    pub local_infile: Option<String>,
 }
 
 impl SqlQueryContext {
@@ -129,6 +144,8 @@ pub struct SqlServiceImpl {
     query_timeout: Duration,
     create_table_timeout: Duration,
     cache: SqlResultCache,
+    query_history: Arc<QueryHistory>,
+    query_pools: Arc<QueryPools>,
 }
 
 crate::di_service!(SqlServiceImpl, [SqlService]);
@@ -148,6 +165,8 @@ impl SqlServiceImpl {
         query_timeout: Duration,
         create_table_timeout: Duration,
         max_cached_queries: usize,
+        query_history: Arc<QueryHistory>,
+        query_pools: Arc<QueryPools>,
     ) -> Arc<SqlServiceImpl> {
         Arc::new(SqlServiceImpl {
             db,
@@ -163,6 +182,8 @@ impl SqlServiceImpl {
             create_table_timeout,
             remote_fs,
             cache: SqlResultCache::new(max_cached_queries),
+            query_history,
+            query_pools,
         })
     }
 
@@ -170,8 +191,9 @@ impl SqlServiceImpl {
         &self,
         name: String,
         if_not_exists: bool,
+        lifecycle: SchemaLifecycle,
     ) -> Result<IdRow<Schema>, CubeError> {
-        self.db.create_schema(name, if_not_exists).await
+        self.db.create_schema(name, if_not_exists, lifecycle).await
     }
 
     async fn create_table(
@@ -184,10 +206,24 @@ impl SqlServiceImpl {
         import_format: Option<ImportFormat>,
         indexes: Vec<Statement>,
         unique_key: Option<Vec<Ident>>,
+        aggregates: Option<Vec<(Ident, Ident)>>,
         partitioned_index: Option<PartitionedIndexRef>,
         trace_obj: &Option<String>,
     ) -> Result<IdRow<Table>, CubeError> {
         let columns_to_set = convert_columns_type(columns)?;
+        let aggregates = aggregates
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(function, column)| {
+                let f = AggregateFunction::from_name(&function.value).ok_or_else(|| {
+                    CubeError::user(format!(
+                        "Unsupported aggregate function '{}' for column {}, expected one of: sum, max, min",
+                        function.value, column.value
+                    ))
+                })?;
+                Ok((column.value, f))
+            })
+            .collect::<Result<Vec<_>, CubeError>>()?;
         let mut indexes_to_create = Vec::new();
         if let Some(mut p) = partitioned_index {
             let part_index_name = match p.name.0.as_mut_slice() {
@@ -251,6 +287,7 @@ impl SqlServiceImpl {
                     indexes_to_create,
                     true,
                     unique_key.map(|keys| keys.iter().map(|c| c.value.to_string()).collect()),
+                    aggregates,
                     None,
                 )
                 .await;
@@ -301,6 +338,7 @@ impl SqlServiceImpl {
                 indexes_to_create,
                 false,
                 unique_key.map(|keys| keys.iter().map(|c| c.value.to_string()).collect()),
+                aggregates,
                 partition_split_threshold,
             )
             .await?;
@@ -417,6 +455,307 @@ impl SqlServiceImpl {
             .await?)
     }
 
+    async fn insert(
+        &self,
+        query: &str,
+        table_name: &ObjectName,
+        columns: &Vec<Ident>,
+        source: &Query,
+        on_duplicate_key_update: Option<&Vec<(Ident, Expr)>>,
+    ) -> Result<u64, CubeError> {
+        let nv = &table_name.0;
+        if nv.len() != 2 {
+            return Err(CubeError::user(format!(
+                "Schema's name should be present in query (boo.table1). Your query was '{}'",
+                query
+            )));
+        }
+        let schema_name = &nv[0].value;
+        let table_name = &nv[1].value;
+
+        if let Some(assignments) = on_duplicate_key_update {
+            let table = self
+                .db
+                .get_table(schema_name.clone(), table_name.clone())
+                .await?;
+            check_on_duplicate_key_update(table.get_row(), assignments)?;
+        }
+
+        match &source.body {
+            SetExpr::Values(Values(data)) => {
+                self.insert_data(schema_name.clone(), table_name.clone(), columns, data)
+                    .await
+            }
+            SetExpr::Select(_) | SetExpr::SetOperation { .. } | SetExpr::Query(_) => {
+                self.insert_select(schema_name.clone(), table_name.clone(), columns, source)
+                    .await
+            }
+            _ => Err(CubeError::user(format!(
+                "Data should be present in query. Your query was '{}'",
+                query
+            ))),
+        }
+    }
+
+    async fn load_data(
+        &self,
+        query: &str,
+        table_name: &ObjectName,
+        columns: &Vec<Ident>,
+        location: &str,
+        header: bool,
+    ) -> Result<u64, CubeError> {
+        let nv = &table_name.0;
+        if nv.len() != 2 {
+            return Err(CubeError::user(format!(
+                "Schema's name should be present in query (boo.table1). Your query was '{}'",
+                query
+            )));
+        }
+        let table = self
+            .db
+            .get_table(nv[0].value.clone(), nv[1].value.clone())
+            .await?;
+        let seq_column = table.get_row().seq_column().map(|c| c.get_index());
+        let load_columns = if columns.is_empty() {
+            table
+                .get_row()
+                .get_columns()
+                .iter()
+                .filter(|c| Some(c.get_index()) != seq_column)
+                .cloned()
+                .collect_vec()
+        } else {
+            columns
+                .iter()
+                .map(|column| {
+                    table
+                        .get_row()
+                        .get_columns()
+                        .iter()
+                        .find(|c| *c.get_name() == column.value)
+                        .cloned()
+                        .ok_or_else(|| {
+                            CubeError::user(format!(
+                                "Column {} is not present in table {}.{}.",
+                                column.value, nv[0].value, nv[1].value
+                            ))
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let format = if header {
+            ImportFormat::CSV
+        } else {
+            ImportFormat::CSVNoHeader
+        };
+        self.import_service
+            .load_data(table, location, format, load_columns)
+            .await
+    }
+
+    /// Collects statistics of every partition on its worker, then merges them per index.
+    async fn analyze_table(&self, query: &str, table_name: &ObjectName) -> Result<(), CubeError> {
+        let nv = &table_name.0;
+        if nv.len() != 2 {
+            return Err(CubeError::user(format!(
+                "Schema's name should be present in query (boo.table1). Your query was '{}'",
+                query
+            )));
+        }
+        let table = self
+            .db
+            .get_table(nv[0].value.clone(), nv[1].value.clone())
+            .await?;
+        let indexes = self.db.get_table_indexes(table.get_id()).await?;
+
+        let listener = self.cluster.job_result_listener();
+        let mut wait_for = Vec::new();
+        for index in indexes.iter() {
+            for p in self
+                .db
+                .get_active_partitions_by_index_id(index.get_id())
+                .await?
+            {
+                let node = self.cluster.node_name_by_partition(&p);
+                let key = RowKey::Table(TableId::Partitions, p.get_id());
+                let job = self
+                    .db
+                    .add_job(Job::new(
+                        key.clone(),
+                        JobType::AnalyzePartition,
+                        node.clone(),
+                    ))
+                    .await?;
+                if job.is_some() {
+                    self.cluster.notify_job_runner(node).await?;
+                }
+                wait_for.push((key, JobType::AnalyzePartition));
+            }
+        }
+        for r in listener.wait_for_job_results(wait_for).await? {
+            if let JobEvent::Error(_, _, e) = r {
+                return Err(CubeError::user(format!("ANALYZE TABLE failed: {}", e)));
+            }
+        }
+
+        let analyzed_at = Utc::now();
+        for index in indexes {
+            let partitions = self
+                .db
+                .get_active_partitions_by_index_id(index.get_id())
+                .await?;
+            let stats = partitions
+                .iter()
+                .filter_map(|p| p.get_row().column_statistics().as_ref())
+                .collect_vec();
+            let statistics = IndexStatistics::merge(index.get_row(), &stats, analyzed_at)?;
+            self.db
+                .update_index_statistics(index.get_id(), Some(statistics))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Selected rows are written straight from the workers when the whole query can be run
+    /// there. Queries that need a final step on the router (e.g. aggregations over several
+    /// partitions) are collected on the router first. Either way, written chunks are activated
+    /// together once the whole select succeeds.
+    async fn insert_select(
+        &self,
+        schema_name: String,
+        table_name: String,
+        columns: &Vec<Ident>,
+        source: &Query,
+    ) -> Result<u64, CubeError> {
+        let table = self
+            .db
+            .get_table(schema_name.clone(), table_name.clone())
+            .await?;
+        let seq_column = table.get_row().seq_column().map(|c| c.get_index());
+        let target_columns = if columns.is_empty() {
+            table
+                .get_row()
+                .get_columns()
+                .iter()
+                .filter(|c| Some(c.get_index()) != seq_column)
+                .collect_vec()
+        } else {
+            columns
+                .iter()
+                .map(|column| {
+                    table
+                        .get_row()
+                        .get_columns()
+                        .iter()
+                        .find(|c| *c.get_name() == column.value)
+                        .ok_or_else(|| {
+                            CubeError::user(format!(
+                                "Column {} is not present in table {}.{}.",
+                                column.value, schema_name, table_name
+                            ))
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let logical_plan = self
+            .query_planner
+            .logical_plan(DFStatement::Statement(Statement::Query(Box::new(
+                source.clone(),
+            ))))
+            .await?;
+        let serialized = match logical_plan {
+            QueryPlan::Select(serialized, _) => serialized,
+            QueryPlan::Meta(_) => {
+                return Err(CubeError::user(format!(
+                    "INSERT ... SELECT should read from a table. Your query was '{}'",
+                    source
+                )))
+            }
+        };
+        app_metrics::DATA_QUERIES.increment();
+
+        let (physical_plan, _) = self
+            .query_executor
+            .router_plan(serialized.clone(), self.cluster.clone())
+            .await?;
+        let selected_columns = physical_plan.schema().fields().len();
+        if selected_columns != target_columns.len() {
+            return Err(CubeError::user(format!(
+                "INSERT into {}.{} expects {} columns, but SELECT returns {}",
+                schema_name,
+                table_name,
+                target_columns.len(),
+                selected_columns
+            )));
+        }
+        if let Some(worker_plans) = worker_only_plans(&physical_plan) {
+            let column_names = target_columns
+                .iter()
+                .map(|c| c.get_name().clone())
+                .collect_vec();
+            let table_id = table.get_id();
+            let results = timeout(
+                self.query_timeout,
+                join_all(worker_plans.into_iter().map(|(node, plan)| {
+                    let cluster = self.cluster.clone();
+                    let column_names = column_names.clone();
+                    async move {
+                        cluster
+                            .run_insert_select(&node, plan, table_id, column_names)
+                            .await
+                    }
+                })),
+            )
+            .await?
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+            // Rows become visible at once, only when all workers succeed.
+            let mut inserted = 0;
+            let mut chunks = Vec::new();
+            for (rows, c) in results {
+                inserted += rows;
+                chunks.extend(c);
+            }
+            if !chunks.is_empty() {
+                self.db.activate_chunks(table_id, chunks).await?;
+            }
+            return Ok(inserted);
+        }
+
+        let (_, batches) = timeout(
+            self.query_timeout,
+            self.query_executor
+                .execute_router_plan(serialized, self.cluster.clone()),
+        )
+        .await??;
+        let mut ingestion = Ingestion::new(
+            self.db.clone(),
+            self.chunk_store.clone(),
+            self.limits.clone(),
+            table.clone(),
+        );
+        let mut inserted = 0;
+        for batch in batches {
+            if batch.num_rows() == 0 {
+                continue;
+            }
+            inserted += batch.num_rows() as u64;
+            ingestion
+                .queue_columns(&target_columns, batch.columns().to_vec())
+                .await?;
+        }
+        let chunks = ingestion.wait_uploads().await?;
+        if !chunks.is_empty() {
+            self.db.activate_chunks(table.get_id(), chunks).await?;
+        }
+        Ok(inserted)
+    }
+
+    /// Rows with the same unique key are merged according to the table definition: aggregate
+    /// columns are combined with their function and the rest are taken from the row with the
+    /// largest `__seq`, which is generated from the insertion order unless provided explicitly.
     async fn insert_data<'a>(
         &'a self,
         schema_name: String,
@@ -445,6 +784,12 @@ impl SqlServiceImpl {
             };
             real_col.push(c);
         }
+        // Parsed arrays are ordered by column index.
+        let sorted_col = real_col
+            .iter()
+            .cloned()
+            .sorted_by_key(|c| c.get_index())
+            .collect_vec();
 
         let mut ingestion = Ingestion::new(
             self.db.clone(),
@@ -454,12 +799,169 @@ impl SqlServiceImpl {
         );
         for rows_chunk in data.chunks(self.rows_per_chunk) {
             let rows = parse_chunk(rows_chunk, &real_col)?;
-            ingestion.queue_data_frame(rows).await?;
+            ingestion.queue_columns(&sorted_col, rows).await?;
         }
         ingestion.wait_completion().await?;
         Ok(data.len() as u64)
     }
 
+    async fn select(
+        &self,
+        context: &SqlQueryContext,
+        query: &str,
+        q: Box<Query>,
+        stats: &mut QueryStats,
+    ) -> Result<Arc<DataFrame>, CubeError> {
+        let logical_plan = self
+            .query_planner
+            .logical_plan(DFStatement::Statement(Statement::Query(q)))
+            .await?;
+        // TODO distribute and combine
+        let res = match logical_plan {
+            QueryPlan::Meta(logical_plan) => {
+                app_metrics::META_QUERIES.increment();
+                Arc::new(self.query_planner.execute_meta_plan(logical_plan).await?)
+            }
+            QueryPlan::Select(serialized, workers) => {
+                app_metrics::DATA_QUERIES.increment();
+                *stats = QueryStats::from_plan(&serialized);
+                let cluster = self.cluster.clone();
+                let executor = self.query_executor.clone();
+                let query_pools = self.query_pools.clone();
+                let query_pool = query_pools.pool_for(context);
+                let memory = stats.bytes_scanned;
+                let exec = async move |plan: SerializedPlan| {
+                    // Cached results don't need a slot in the pool.
+                    let _permit = query_pools.acquire(&query_pool, memory).await?;
+                    let records;
+                    if workers.len() == 0 {
+                        records = executor.execute_router_plan(plan, cluster).await?.1;
+                    } else {
+                        // Pick one of the workers to run as main for the request.
+                        let i = thread_rng().sample(Uniform::new(0, workers.len()));
+                        let rs = cluster.route_select(&workers[i], plan).await?.1;
+                        records = rs
+                            .into_iter()
+                            .map(|r| r.read())
+                            .collect::<Result<Vec<_>, _>>()?;
+                    }
+                    Ok(
+                        cube_ext::spawn_blocking(move || -> Result<DataFrame, CubeError> {
+                            let df = batch_to_dataframe(&records)?;
+                            Ok(df)
+                        })
+                        .await??,
+                    )
+                };
+                let (res, cache_hit) = timeout(
+                    self.query_timeout,
+                    self.cache
+                        .get_with_cache_hit(query, serialized, exec)
+                        .with_current_subscriber(),
+                )
+                .await??;
+                if cache_hit {
+                    // Nothing was read, the result came from the cache
+                    *stats = QueryStats {
+                        cache_hit: true,
+                        ..QueryStats::default()
+                    };
+                }
+                res
+            }
+        };
+        Ok(res)
+    }
+
+    /// Like `INSERT ... SELECT`, files are written by the workers when the whole query can be
+    /// run there, and on the router otherwise.
+    async fn export(
+        &self,
+        q: Box<Query>,
+        location: String,
+        options: Vec<SqlOption>,
+    ) -> Result<Arc<DataFrame>, CubeError> {
+        let options = ExportOptions::from_sql_options(&options)?;
+        let remote_prefix = export_remote_prefix(self.config_obj.store_provider(), &location)?;
+        let logical_plan = self
+            .query_planner
+            .logical_plan(DFStatement::Statement(Statement::Query(q)))
+            .await?;
+        let serialized = match logical_plan {
+            QueryPlan::Select(serialized, _) => serialized,
+            QueryPlan::Meta(_) => {
+                return Err(CubeError::user(
+                    "EXPORT query should read from a table".to_string(),
+                ))
+            }
+        };
+        app_metrics::DATA_QUERIES.increment();
+
+        let file_prefix = format!("{}/{}", remote_prefix, Uuid::new_v4());
+        let (physical_plan, _) = self
+            .query_executor
+            .router_plan(serialized.clone(), self.cluster.clone())
+            .await?;
+        let files = if let Some(worker_plans) = worker_only_plans(&physical_plan) {
+            timeout(
+                self.query_timeout,
+                join_all(
+                    worker_plans
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, (node, plan))| {
+                            let cluster = self.cluster.clone();
+                            let file_prefix = format!("{}-{}", file_prefix, i);
+                            let options = options.clone();
+                            async move {
+                                cluster
+                                    .run_export_select(&node, plan, file_prefix, options)
+                                    .await
+                            }
+                        }),
+                ),
+            )
+            .await?
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect_vec()
+        } else {
+            let (schema, batches) = timeout(
+                self.query_timeout,
+                self.query_executor
+                    .execute_router_plan(serialized, self.cluster.clone()),
+            )
+            .await??;
+            export_batches(
+                self.remote_fs.clone(),
+                schema,
+                futures::stream::iter(batches.into_iter().map(Ok)),
+                &file_prefix,
+                &options,
+            )
+            .await?
+        };
+
+        // Report files in the same form as the requested location.
+        let location = location.trim_end_matches('/');
+        let columns = vec![Column::new("file".to_string(), ColumnType::String, 0)];
+        Ok(Arc::new(DataFrame::new(
+            columns,
+            files
+                .into_iter()
+                .map(|f| {
+                    Row::new(vec![TableValue::String(format!(
+                        "{}/{}",
+                        location,
+                        &f[remote_prefix.len() + 1..]
+                    ))])
+                })
+                .collect(),
+        )))
+    }
+
     async fn dump_select_inputs(
         &self,
         query: &str,
@@ -719,14 +1221,24 @@ impl SqlService for SqlServiceImpl {
                 }
             },
             CubeStoreStatement::Statement(Statement::SetVariable { .. }) => {
+                if let Some(query_pool) = parse_set_query_pool(query) {
+                    if !self.query_pools.has_pool(&query_pool) {
+                        return Err(CubeError::user(format!(
+                            "Unknown query pool '{}'",
+                            query_pool
+                        )));
+                    }
+                }
                 Ok(Arc::new(DataFrame::new(vec![], vec![])))
             }
             CubeStoreStatement::CreateSchema {
                 schema_name,
                 if_not_exists,
+                options,
             } => {
                 let name = schema_name.to_string();
-                let res = self.create_schema(name, if_not_exists).await?;
+                let lifecycle = schema_lifecycle(&options)?;
+                let res = self.create_schema(name, if_not_exists, lifecycle).await?;
                 Ok(Arc::new(DataFrame::from(vec![res])))
             }
             CubeStoreStatement::CreateTable {
@@ -741,6 +1253,7 @@ impl SqlService for SqlServiceImpl {
                 indexes,
                 locations,
                 unique_key,
+                aggregates,
                 partitioned_index,
             } => {
                 let nv = &name.0;
@@ -784,6 +1297,7 @@ impl SqlService for SqlServiceImpl {
                         Some(import_format),
                         indexes,
                         unique_key,
+                        aggregates,
                         partitioned_index,
                         &context.trace_obj,
                     )
@@ -939,72 +1453,61 @@ impl SqlService for SqlServiceImpl {
                 source,
                 ..
             }) => {
-                let data = if let SetExpr::Values(Values(data_series)) = &source.body {
-                    data_series
-                } else {
-                    return Err(CubeError::user(format!(
-                        "Data should be present in query. Your query was '{}'",
-                        query
-                    )));
-                };
-
-                let nv = &table_name.0;
-                if nv.len() != 2 {
-                    return Err(CubeError::user(format!("Schema's name should be present in query (boo.table1). Your query was '{}'", query)));
-                }
-                let schema_name = &nv[0].value;
-                let table_name = &nv[1].value;
-
-                self.insert_data(schema_name.clone(), table_name.clone(), &columns, data)
+                self.insert(query, &table_name, &columns, &source, None)
                     .await?;
                 Ok(Arc::new(DataFrame::new(vec![], vec![])))
             }
-            CubeStoreStatement::Statement(Statement::Query(q)) => {
-                let logical_plan = self
-                    .query_planner
-                    .logical_plan(DFStatement::Statement(Statement::Query(q)))
-                    .await?;
-                // TODO distribute and combine
-                let res = match logical_plan {
-                    QueryPlan::Meta(logical_plan) => {
-                        app_metrics::META_QUERIES.increment();
-                        Arc::new(self.query_planner.execute_meta_plan(logical_plan).await?)
-                    }
-                    QueryPlan::Select(serialized, workers) => {
-                        app_metrics::DATA_QUERIES.increment();
-                        let cluster = self.cluster.clone();
-                        let executor = self.query_executor.clone();
-                        timeout(
-                            self.query_timeout,
-                            self.cache
-                                .get(query, serialized, async move |plan| {
-                                    let records;
-                                    if workers.len() == 0 {
-                                        records =
-                                            executor.execute_router_plan(plan, cluster).await?.1;
-                                    } else {
-                                        // Pick one of the workers to run as main for the request.
-                                        let i = thread_rng().sample(Uniform::new(0, workers.len()));
-                                        let rs = cluster.route_select(&workers[i], plan).await?.1;
-                                        records = rs
-                                            .into_iter()
-                                            .map(|r| r.read())
-                                            .collect::<Result<Vec<_>, _>>()?;
-                                    }
-                                    Ok(cube_ext::spawn_blocking(
-                                        move || -> Result<DataFrame, CubeError> {
-                                            let df = batch_to_dataframe(&records)?;
-                                            Ok(df)
-                                        },
-                                    )
-                                    .await??)
-                                })
-                                .with_current_subscriber(),
-                        )
-                        .await??
+            CubeStoreStatement::LoadData {
+                local,
+                location,
+                table_name,
+                columns,
+                header,
+            } => {
+                let location = match (local, context.local_infile) {
+                    (false, _) => location,
+                    (true, Some(uploaded)) => uploaded,
+                    (true, None) => {
+                        return Err(CubeError::user(format!(
+                            "LOAD DATA LOCAL INFILE is only supported over the MySQL protocol. \
+                             Upload the file with POST /upload-temp-file?name=<name> and use \
+                             LOAD DATA INFILE 'temp://<name>' instead. Your query was '{}'",
+                            query
+                        )))
                     }
                 };
-                Ok(res)
+                self.load_data(query, &table_name, &columns, &location, header)
+                    .await?;
+                Ok(Arc::new(DataFrame::new(vec![], vec![])))
+            }
+            CubeStoreStatement::Upsert {
+                insert:
+                    Statement::Insert {
+                        table_name,
+                        columns,
+                        source,
+                        ..
+                    },
+                assignments,
+            } => {
+                self.insert(query, &table_name, &columns, &source, Some(&assignments))
+                    .await?;
+                Ok(Arc::new(DataFrame::new(vec![], vec![])))
+            }
+            CubeStoreStatement::Statement(Statement::Query(q)) => {
+                let started_at = Utc::now();
+                let start = Instant::now();
+                let mut stats = QueryStats::default();
+                let res = self.select(&context, query, q, &mut stats).await;
+                self.query_history.record(
+                    &context,
+                    query,
+                    started_at,
+                    start.elapsed(),
+                    stats,
+                    &res,
+                );
+                res
             }
             CubeStoreStatement::Statement(Statement::Explain {
                 analyze,
@@ -1019,6 +1522,15 @@ impl SqlService for SqlServiceImpl {
             },
 
             CubeStoreStatement::Dump(q) => self.dump_select_inputs(query, q).await,
+            CubeStoreStatement::Export {
+                query: q,
+                location,
+                options,
+            } => self.export(q, location, options).await,
+            CubeStoreStatement::AnalyzeTable { table_name } => {
+                self.analyze_table(query, &table_name).await?;
+                Ok(Arc::new(DataFrame::new(vec![], vec![])))
+            }
             _ => Err(CubeError::user(format!("Unsupported SQL: '{}'", query))),
         }
     }
@@ -1182,6 +1694,171 @@ fn convert_columns_type(columns: &Vec<ColumnDef>) -> Result<Vec<Column>, CubeErr
     Ok(rolupdb_columns)
 }
 
+/// Parses `WITH (keep_versions = <n>, ttl = '<duration>')` of `CREATE SCHEMA`.
+fn schema_lifecycle(options: &Vec<SqlOption>) -> Result<SchemaLifecycle, CubeError> {
+    let mut lifecycle = SchemaLifecycle::default();
+    for option in options {
+        match (option.name.value.to_lowercase().as_str(), &option.value) {
+            ("keep_versions", Value::Number(n, _)) => match n.parse::<u64>() {
+                Ok(n) if n > 0 => lifecycle.keep_versions = Some(n),
+                _ => {
+                    return Err(CubeError::user(format!(
+                        "keep_versions should be a positive integer but {} found",
+                        n
+                    )))
+                }
+            },
+            ("ttl", Value::SingleQuotedString(ttl)) => {
+                lifecycle.ttl_secs = Some(parse_ttl(ttl)?);
+            }
+            ("ttl", Value::Number(n, _)) => {
+                lifecycle.ttl_secs = Some(parse_ttl(n)?);
+            }
+            _ => {
+                return Err(CubeError::user(format!(
+                    "Unsupported schema option: {}",
+                    option
+                )))
+            }
+        }
+    }
+    Ok(lifecycle)
+}
+
+/// Returns per-worker plans if the router only collects results from the workers, i.e. each
+/// worker can produce its part of the final result on its own.
+fn worker_only_plans(plan: &Arc<dyn ExecutionPlan>) -> Option<Vec<(String, SerializedPlan)>> {
+    let mut root = plan.clone();
+    if root.as_any().is::<MergeExec>() {
+        root = root.children()[0].clone();
+    }
+    let send = root.as_any().downcast_ref::<ClusterSendExec>()?;
+    Some(send.worker_plans())
+}
+
+/// Only updates that match how the table merges rows on unique key conflicts are accepted:
+/// `col = VALUES(col)` for regular columns, `col = col + VALUES(col)` for `sum`,
+/// `col = GREATEST(col, VALUES(col))` for `max` and `col = LEAST(col, VALUES(col))` for `min`.
+///
+/// The clause is only checked against the table definition and doesn't change how rows are
+/// merged: conflicts are resolved by the aggregate functions of the table on reads and
+/// compaction, whether the clause is present or not.
+fn check_on_duplicate_key_update(
+    table: &Table,
+    assignments: &Vec<(Ident, Expr)>,
+) -> Result<(), CubeError> {
+    let key_columns = table.unique_key_columns().ok_or_else(|| {
+        CubeError::user(format!(
+            "ON DUPLICATE KEY UPDATE requires UNIQUE KEY to be defined for table {}",
+            table.get_table_name()
+        ))
+    })?;
+    let seq_column = table.seq_column();
+    let mut updated = Vec::with_capacity(assignments.len());
+    for (column, value) in assignments {
+        let c = table
+            .get_columns()
+            .iter()
+            .find(|c| c.get_name() == &column.value)
+            .ok_or_else(|| {
+                CubeError::user(format!(
+                    "Column {} is not present in table {}",
+                    column.value,
+                    table.get_table_name()
+                ))
+            })?;
+        if key_columns.iter().any(|k| k.get_index() == c.get_index())
+            || seq_column.map(|s| s.get_index()) == Some(c.get_index())
+        {
+            return Err(CubeError::user(format!(
+                "Column {} can't be updated in ON DUPLICATE KEY UPDATE",
+                column.value
+            )));
+        }
+        let expected = table.aggregate_function(c);
+        let actual = on_duplicate_key_function(&column.value, value).ok_or_else(|| {
+            CubeError::user(format!(
+                "Unsupported ON DUPLICATE KEY UPDATE expression for column {}: {}",
+                column.value, value
+            ))
+        })?;
+        if actual != expected {
+            return Err(CubeError::user(format!(
+                "ON DUPLICATE KEY UPDATE for column {} should be {}, as defined by table {}",
+                column.value,
+                on_duplicate_key_update_sql(&column.value, expected),
+                table.get_table_name()
+            )));
+        }
+        updated.push(c.get_index());
+    }
+    for c in table.get_columns() {
+        if updated.contains(&c.get_index())
+            || key_columns.iter().any(|k| k.get_index() == c.get_index())
+            || seq_column.map(|s| s.get_index()) == Some(c.get_index())
+        {
+            continue;
+        }
+        return Err(CubeError::user(format!(
+            "Column {} is missing in ON DUPLICATE KEY UPDATE, expected {}",
+            c.get_name(),
+            on_duplicate_key_update_sql(c.get_name(), table.aggregate_function(c))
+        )));
+    }
+    Ok(())
+}
+
+/// `Some(None)` stands for replacing the value, `None` for an unsupported expression.
+fn on_duplicate_key_function(column: &str, value: &Expr) -> Option<Option<AggregateFunction>> {
+    let is_column = |e: &Expr| matches!(e, Expr::Identifier(i) if i.value == column);
+    let is_new_value = |e: &Expr| match e {
+        Expr::Function(f) => {
+            f.name.to_string().eq_ignore_ascii_case("values")
+                && f.args.len() == 1
+                && matches!(&f.args[0], FunctionArg::Unnamed(e) if is_column(e))
+        }
+        _ => false,
+    };
+    let is_column_and_new_value =
+        |l: &Expr, r: &Expr| is_column(l) && is_new_value(r) || is_new_value(l) && is_column(r);
+    match value {
+        e if is_new_value(e) => Some(None),
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::Plus,
+            right,
+        } if is_column_and_new_value(&**left, &**right) => Some(Some(AggregateFunction::Sum)),
+        Expr::Function(f) if f.args.len() == 2 => {
+            let args = f
+                .args
+                .iter()
+                .map(|a| match a {
+                    FunctionArg::Unnamed(e) => Some(e),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            if !is_column_and_new_value(args[0], args[1]) {
+                return None;
+            }
+            match f.name.to_string().to_lowercase().as_str() {
+                "greatest" => Some(Some(AggregateFunction::Max)),
+                "least" => Some(Some(AggregateFunction::Min)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn on_duplicate_key_update_sql(column: &str, function: Option<AggregateFunction>) -> String {
+    match function {
+        None => format!("{0} = VALUES({0})", column),
+        Some(AggregateFunction::Sum) => format!("{0} = {0} + VALUES({0})", column),
+        Some(AggregateFunction::Max) => format!("{0} = GREATEST({0}, VALUES({0}))", column),
+        Some(AggregateFunction::Min) => format!("{0} = LEAST({0}, VALUES({0}))", column),
+    }
+}
+
 fn parse_chunk(chunk: &[Vec<Expr>], column: &Vec<&Column>) -> Result<Vec<ArrayRef>, CubeError> {
     let mut buffer = Vec::new();
     let mut builders = column
@@ -1624,13 +2301,17 @@ mod tests {
                 query_timeout,
                 query_timeout,
                 10_000, // max_cached_queries
+                QueryHistory::new(0, None, query_timeout),
+                QueryPools::new(Vec::new(), HashMap::new(), 0),
             );
             let i = service.exec_query("CREATE SCHEMA foo").await.unwrap();
             assert_eq!(
                 i.get_rows()[0],
                 Row::new(vec![
                     TableValue::Int(1),
-                    TableValue::String("foo".to_string())
+                    TableValue::String("foo".to_string()),
+                    TableValue::String("NULL".to_string()),
+                    TableValue::String("NULL".to_string()),
                 ])
             );
         }
@@ -1678,13 +2359,17 @@ mod tests {
                 query_timeout,
                 query_timeout,
                 10_000, // max_cached_queries
+                QueryHistory::new(0, None, query_timeout),
+                QueryPools::new(Vec::new(), HashMap::new(), 0),
             );
             let i = service.exec_query("CREATE SCHEMA Foo").await.unwrap();
             assert_eq!(
                 i.get_rows()[0],
                 Row::new(vec![
                     TableValue::Int(1),
-                    TableValue::String("Foo".to_string())
+                    TableValue::String("Foo".to_string()),
+                    TableValue::String("NULL".to_string()),
+                    TableValue::String("NULL".to_string()),
                 ])
             );
             let query = "CREATE TABLE Foo.Persons (
@@ -1709,6 +2394,7 @@ mod tests {
                 TableValue::String("NULL".to_string()),
                 TableValue::String("NULL".to_string()),
                 TableValue::String("NULL".to_string()),
+                TableValue::String("[]".to_string()),
             ]));
         }
         let _ = DB::destroy(&Options::default(), path);
@@ -1855,6 +2541,68 @@ mod tests {
             .await;
     }
 
+    #[tokio::test]
+    async fn spill_to_disk() {
+        Config::test("spill_to_disk")
+            .update_config(|mut c| {
+                // Fits any single input batch, but not the whole input.
+                c.query_memory_limit = 128 << 10;
+                c
+            })
+            .start_test(async move |services| {
+                let service = services.sql_service;
+
+                service.exec_query("CREATE SCHEMA foo").await.unwrap();
+                service
+                    .exec_query("CREATE TABLE foo.numbers (n int, s text)")
+                    .await
+                    .unwrap();
+                for i in 0..10 {
+                    let values = (0..2000)
+                        .map(|j| format!("({}, 's{}')", (i * 2000 + j) % 97, j % 13))
+                        .join(", ");
+                    service
+                        .exec_query(&format!("INSERT INTO foo.numbers (n, s) VALUES {}", values))
+                        .await
+                        .unwrap();
+                }
+
+                let result = service
+                    .exec_query(
+                        "SELECT n, count(*) FROM foo.numbers GROUP BY 1 ORDER BY 2 DESC, 1 LIMIT 3",
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    result.get_rows(),
+                    &vec![
+                        Row::new(vec![TableValue::Int(0), TableValue::Int(207)]),
+                        Row::new(vec![TableValue::Int(1), TableValue::Int(207)]),
+                        Row::new(vec![TableValue::Int(2), TableValue::Int(207)]),
+                    ]
+                );
+
+                let result = service
+                    .exec_query("SELECT s, n FROM foo.numbers ORDER BY s DESC, n")
+                    .await
+                    .unwrap();
+                let rows = result
+                    .get_rows()
+                    .iter()
+                    .map(|r| match r.values().as_slice() {
+                        [TableValue::String(s), TableValue::Int(n)] => (s.clone(), *n),
+                        v => panic!("unexpected row {:?}", v),
+                    })
+                    .collect_vec();
+                let mut expected = rows.clone();
+                expected.sort_by(|l, r| r.0.cmp(&l.0).then(l.1.cmp(&r.1)));
+                assert_eq!(rows.len(), 20000);
+                assert_eq!(rows[0].0, "s9");
+                assert_eq!(rows, expected);
+            })
+            .await;
+    }
+
     #[tokio::test]
     async fn over_2k_booleans() {
         Config::test("over_2k_booleans").update_config(|mut c| {
@@ -1,5 +1,6 @@
 use sqlparser::ast::{
-    HiveDistributionStyle, Ident, ObjectName, Query, SqlOption, Statement as SQLStatement, Value,
+    Expr, HiveDistributionStyle, Ident, ObjectName, Query, SqlOption, Statement as SQLStatement,
+    Value,
 };
 use sqlparser::dialect::keywords::Keyword;
 use sqlparser::dialect::Dialect;
@@ -42,10 +43,14 @@ pub enum Statement {
         indexes: Vec<SQLStatement>,
         locations: Option<Vec<String>>,
         unique_key: Option<Vec<Ident>>,
+        /// (function, column) pairs from `AGGREGATIONS (sum(col), ...)`.
+        aggregates: Option<Vec<(Ident, Ident)>>,
     },
     CreateSchema {
         schema_name: ObjectName,
         if_not_exists: bool,
+        /// `WITH (keep_versions = <n>, ttl = '<duration>')`.
+        options: Vec<SqlOption>,
     },
     CreateSource {
         name: Ident,
@@ -55,6 +60,30 @@ pub enum Statement {
     },
     System(SystemCommand),
     Dump(Box<Query>),
+    /// `EXPORT (<query>) TO '<location>' [WITH (format = 'parquet', max_file_size = <bytes>)]`.
+    Export {
+        query: Box<Query>,
+        location: String,
+        options: Vec<SqlOption>,
+    },
+    /// `LOAD DATA [LOCAL] INFILE '<location>' INTO TABLE <table> [IGNORE 1 LINES] [(col, ...)]`.
+    LoadData {
+        local: bool,
+        location: String,
+        table_name: ObjectName,
+        columns: Vec<Ident>,
+        /// Set by `IGNORE 1 LINES`. Columns are matched by the header names in this case.
+        header: bool,
+    },
+    /// `INSERT ... ON DUPLICATE KEY UPDATE col = <expr>, ...`.
+    Upsert {
+        insert: SQLStatement,
+        assignments: Vec<(Ident, Expr)>,
+    },
+    /// `ANALYZE TABLE <schema>.<table>`.
+    AnalyzeTable {
+        table_name: ObjectName,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -102,6 +131,26 @@ impl<'a> CubeStoreParser<'a> {
                     };
                     Ok(Statement::Dump(q))
                 }
+                _ if w.value.eq_ignore_ascii_case("export") => {
+                    self.parser.next_token();
+                    self.parse_export()
+                }
+                Keyword::INSERT => self.parse_insert(),
+                _ if w.value.eq_ignore_ascii_case("load") => {
+                    self.parser.next_token();
+                    self.parse_load_data()
+                }
+                _ if w.value.eq_ignore_ascii_case("analyze") => {
+                    self.parser.next_token();
+                    if !self.parse_custom_token("table") {
+                        return Err(ParserError::ParserError(format!(
+                            "Expected TABLE after ANALYZE, found: {}",
+                            self.parser.peek_token()
+                        )));
+                    }
+                    let table_name = self.parser.parse_object_name()?;
+                    Ok(Statement::AnalyzeTable { table_name })
+                }
                 _ => Ok(Statement::Statement(self.parser.parse_statement()?)),
             },
             _ => Ok(Statement::Statement(self.parser.parse_statement()?)),
@@ -149,6 +198,110 @@ impl<'a> CubeStoreParser<'a> {
         }
     }
 
+    fn parse_insert(&mut self) -> Result<Statement, ParserError> {
+        let insert = self.parser.parse_statement()?;
+        if !self.parser.parse_keyword(Keyword::ON) {
+            return Ok(Statement::Statement(insert));
+        }
+        if !(self.parse_custom_token("duplicate")
+            && self.parser.parse_keywords(&[Keyword::KEY, Keyword::UPDATE]))
+        {
+            return Err(ParserError::ParserError(format!(
+                "Expected DUPLICATE KEY UPDATE after ON, found: {}",
+                self.parser.peek_token()
+            )));
+        }
+        let assignments = self.parser.parse_comma_separated(|p| {
+            let column = p.parse_identifier()?;
+            p.expect_token(&Token::Eq)?;
+            let value = p.parse_expr()?;
+            Ok((column, value))
+        })?;
+        Ok(Statement::Upsert {
+            insert,
+            assignments,
+        })
+    }
+
+    fn parse_export(&mut self) -> Result<Statement, ParserError> {
+        self.parser.expect_token(&Token::LParen)?;
+        let query = Box::new(self.parser.parse_query()?);
+        self.parser.expect_token(&Token::RParen)?;
+        if !self.parse_custom_token("to") {
+            return Err(ParserError::ParserError(format!(
+                "Expected TO after export query, found: {}",
+                self.parser.peek_token()
+            )));
+        }
+        let location = self.parser.parse_literal_string()?;
+        let options = self.parser.parse_options(Keyword::WITH)?;
+        Ok(Statement::Export {
+            query,
+            location,
+            options,
+        })
+    }
+
+    fn parse_load_data(&mut self) -> Result<Statement, ParserError> {
+        if !self.parse_custom_token("data") {
+            return Err(ParserError::ParserError(format!(
+                "Expected DATA after LOAD, found: {}",
+                self.parser.peek_token()
+            )));
+        }
+        let local = self.parse_custom_token("local");
+        if !self.parse_custom_token("infile") {
+            return Err(ParserError::ParserError(format!(
+                "Expected INFILE, found: {}",
+                self.parser.peek_token()
+            )));
+        }
+        let location = self.parser.parse_literal_string()?;
+        if !(self.parse_custom_token("into") && self.parse_custom_token("table")) {
+            return Err(ParserError::ParserError(format!(
+                "Expected INTO TABLE, found: {}",
+                self.parser.peek_token()
+            )));
+        }
+        let table_name = self.parser.parse_object_name()?;
+        let header = if self.parse_custom_token("ignore") {
+            match self.parser.parse_number_value()? {
+                Value::Number(n, _) if n == "1" => {}
+                x => {
+                    return Err(ParserError::ParserError(format!(
+                        "Only IGNORE 1 LINES is supported but {} found",
+                        x
+                    )))
+                }
+            }
+            if !self.parse_custom_token("lines") && !self.parse_custom_token("rows") {
+                return Err(ParserError::ParserError(format!(
+                    "Expected LINES, found: {}",
+                    self.parser.peek_token()
+                )));
+            }
+            true
+        } else {
+            false
+        };
+        let columns = if self.parser.consume_token(&Token::LParen) {
+            let columns = self
+                .parser
+                .parse_comma_separated(|p| p.parse_identifier())?;
+            self.parser.expect_token(&Token::RParen)?;
+            columns
+        } else {
+            Vec::new()
+        };
+        Ok(Statement::LoadData {
+            local,
+            location,
+            table_name,
+            columns,
+            header,
+        })
+    }
+
     fn parse_custom_token(&mut self, token: &str) -> bool {
         if let Token::Word(w) = self.parser.peek_token() {
             if w.value.eq_ignore_ascii_case(token) {
@@ -192,6 +345,21 @@ impl<'a> CubeStoreParser<'a> {
                 None
             };
 
+            let aggregates = if self.parse_custom_token("aggregations") {
+                self.parser.expect_token(&Token::LParen)?;
+                let res = self.parser.parse_comma_separated(|p| {
+                    let function = p.parse_identifier()?;
+                    p.expect_token(&Token::LParen)?;
+                    let column = p.parse_identifier()?;
+                    p.expect_token(&Token::RParen)?;
+                    Ok((function, column))
+                })?;
+                self.parser.expect_token(&Token::RParen)?;
+                Some(res)
+            } else {
+                None
+            };
+
             let mut indexes = Vec::new();
 
             while self.parser.parse_keyword(Keyword::INDEX) {
@@ -247,6 +415,7 @@ impl<'a> CubeStoreParser<'a> {
                 partitioned_index,
                 locations,
                 unique_key,
+                aggregates,
             })
         } else {
             Ok(Statement::Statement(statement))
@@ -277,9 +446,11 @@ impl<'a> CubeStoreParser<'a> {
             self.parser
                 .parse_keywords(&[Keyword::IF, Keyword::NOT, Keyword::EXISTS]);
         let schema_name = self.parser.parse_object_name()?;
+        let options = self.parser.parse_options(Keyword::WITH)?;
         Ok(Statement::CreateSchema {
             schema_name,
             if_not_exists,
+            options,
         })
     }
 
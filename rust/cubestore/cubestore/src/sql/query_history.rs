@@ -0,0 +1,259 @@
+use crate::queryplanner::serialized_plan::SerializedPlan;
+use crate::sql::SqlQueryContext;
+use crate::store::DataFrame;
+use crate::CubeError;
+use chrono::{DateTime, Utc};
+use datafusion::cube_ext;
+use log::error;
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Slow queries waiting to be written, the ones above it are dropped.
+const SLOW_QUERY_LOG_QUEUE_SIZE: usize = 1024;
+
+/// Finished query as seen by the router. Exposed as `system.query_history`.
+#[derive(Clone, Debug, Serialize)]
+pub struct QueryHistoryEntry {
+    pub id: u64,
+    pub started_at: DateTime<Utc>,
+    pub query: String,
+    pub user: Option<String>,
+    pub duration_ms: u64,
+    pub rows: Option<u64>,
+    pub bytes_scanned: u64,
+    pub partitions: u64,
+    pub chunks: u64,
+    pub cache_hit: bool,
+    pub error: Option<String>,
+}
+
+/// Data touched by a query. Stays empty for queries that don't read tables.
+#[derive(Clone, Debug, Default)]
+pub struct QueryStats {
+    pub bytes_scanned: u64,
+    pub partitions: u64,
+    pub chunks: u64,
+    pub cache_hit: bool,
+}
+
+impl QueryStats {
+    pub fn from_plan(plan: &SerializedPlan) -> Self {
+        let mut partitions = HashSet::new();
+        let mut chunks = HashSet::new();
+        let mut bytes_scanned = 0;
+        for index in plan.index_snapshots().iter() {
+            for p in index.partitions.iter() {
+                if partitions.insert(p.partition.get_id()) {
+                    bytes_scanned += p.partition.get_row().file_size().unwrap_or(0);
+                }
+                for c in p.chunks.iter() {
+                    if chunks.insert(c.get_id()) {
+                        bytes_scanned += c.get_row().file_size().unwrap_or(0);
+                    }
+                }
+            }
+        }
+        Self {
+            bytes_scanned,
+            partitions: partitions.len() as u64,
+            chunks: chunks.len() as u64,
+            cache_hit: false,
+        }
+    }
+}
+
+/// Bounded ring buffer of recently finished queries. Queries slower than the threshold are
+/// additionally appended to the slow query log file as JSON lines if it's configured. The file
+/// is written by a blocking task, so queries only queue their entries.
+pub struct QueryHistory {
+    capacity: usize,
+    next_id: AtomicU64,
+    entries: Mutex<VecDeque<QueryHistoryEntry>>,
+    slow_query_threshold: Duration,
+    slow_query_log: Option<mpsc::Sender<QueryHistoryEntry>>,
+}
+
+crate::di_service!(QueryHistory, []);
+
+impl QueryHistory {
+    /// Must be called within the tokio runtime if `slow_query_log` is set.
+    pub fn new(
+        capacity: usize,
+        slow_query_log: Option<File>,
+        slow_query_threshold: Duration,
+    ) -> Arc<Self> {
+        let slow_query_log = slow_query_log.map(|mut file| {
+            let (tx, mut rx) = mpsc::channel(SLOW_QUERY_LOG_QUEUE_SIZE);
+            cube_ext::spawn_blocking(move || {
+                while let Some(entry) = rx.blocking_recv() {
+                    if let Err(e) = Self::write_slow_query(&mut file, &entry) {
+                        error!("Can't write to slow query log: {}", e);
+                    }
+                }
+            });
+            tx
+        });
+        Arc::new(Self {
+            capacity,
+            next_id: AtomicU64::new(1),
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            slow_query_threshold,
+            slow_query_log,
+        })
+    }
+
+    pub fn open_slow_query_log(path: &Path) -> Result<File, CubeError> {
+        Ok(OpenOptions::new().create(true).append(true).open(path)?)
+    }
+
+    pub fn record(
+        &self,
+        context: &SqlQueryContext,
+        query: &str,
+        started_at: DateTime<Utc>,
+        duration: Duration,
+        stats: QueryStats,
+        result: &Result<Arc<DataFrame>, CubeError>,
+    ) {
+        let entry = QueryHistoryEntry {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            started_at,
+            query: query.to_string(),
+            user: context.user.clone(),
+            duration_ms: duration.as_millis() as u64,
+            rows: result.as_ref().ok().map(|df| df.len() as u64),
+            bytes_scanned: stats.bytes_scanned,
+            partitions: stats.partitions,
+            chunks: stats.chunks,
+            cache_hit: stats.cache_hit,
+            error: result.as_ref().err().map(|e| e.message.clone()),
+        };
+
+        if let Some(log) = &self.slow_query_log {
+            if duration >= self.slow_query_threshold {
+                if let Err(e) = log.try_send(entry.clone()) {
+                    error!("Can't queue query {} for slow query log: {}", entry.id, e);
+                }
+            }
+        }
+
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Recorded queries, oldest first.
+    pub fn entries(&self) -> Vec<QueryHistoryEntry> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn write_slow_query(file: &mut File, entry: &QueryHistoryEntry) -> Result<(), CubeError> {
+        let line = serde_json::to_string(entry)?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::{Row, TableValue};
+
+    fn ok_result(rows: usize) -> Result<Arc<DataFrame>, CubeError> {
+        Ok(Arc::new(DataFrame::new(
+            Vec::new(),
+            (0..rows)
+                .map(|i| Row::new(vec![TableValue::Int(i as i64)]))
+                .collect(),
+        )))
+    }
+
+    #[test]
+    fn ring_buffer() {
+        let history = QueryHistory::new(2, None, Duration::from_secs(1));
+        let context = SqlQueryContext::default();
+        for (i, q) in ["SELECT 1", "SELECT 2", "SELECT 3"].iter().enumerate() {
+            history.record(
+                &context,
+                q,
+                Utc::now(),
+                Duration::from_millis(10),
+                QueryStats::default(),
+                &ok_result(i),
+            );
+        }
+        history.record(
+            &context,
+            "SELECT foo",
+            Utc::now(),
+            Duration::from_millis(10),
+            QueryStats::default(),
+            &Err(CubeError::user("no such column".to_string())),
+        );
+
+        let entries = history.entries();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|e| (e.id, e.query.as_str(), e.rows, e.error.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (3, "SELECT 3", Some(2), None),
+                (4, "SELECT foo", None, Some("no such column".to_string())),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn slow_query_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("slow.log");
+        let history = QueryHistory::new(
+            10,
+            Some(QueryHistory::open_slow_query_log(&path).unwrap()),
+            Duration::from_millis(100),
+        );
+        let context = SqlQueryContext::default();
+        for (q, ms) in [("SELECT fast", 10), ("SELECT slow", 200)].iter() {
+            history.record(
+                &context,
+                q,
+                Utc::now(),
+                Duration::from_millis(*ms),
+                QueryStats::default(),
+                &ok_result(1),
+            );
+        }
+
+        // Dropping the history stops the writer once the queue is written
+        drop(history);
+        let mut log = String::new();
+        for _ in 0..100 {
+            log = std::fs::read_to_string(&path).unwrap();
+            if !log.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 1);
+        assert!(
+            lines[0].contains("\"query\":\"SELECT slow\""),
+            "{}",
+            lines[0]
+        );
+        assert!(lines[0].contains("\"duration_ms\":200"), "{}", lines[0]);
+    }
+}
@@ -0,0 +1,508 @@
+use crate::sql::SqlQueryContext;
+use crate::CubeError;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+pub const DEFAULT_QUERY_POOL: &'static str = "default";
+
+/// Limits of a named resource pool. Zero means no limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryPoolConfig {
+    pub name: String,
+    pub concurrency: usize,
+    pub memory_limit: u64,
+    /// Queued queries of pools with higher priority are admitted first.
+    pub priority: i64,
+}
+
+impl QueryPoolConfig {
+    pub fn unlimited(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            concurrency: 0,
+            memory_limit: 0,
+            priority: 0,
+        }
+    }
+
+    /// Parses `CUBESTORE_QUERY_POOLS` format:
+    /// `interactive:concurrency=8,memory=2GB,priority=10;adhoc:concurrency=2,memory=512MB`.
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, CubeError> {
+        let mut pools = Vec::new();
+        for spec in s.split(';').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let (name, options) = match spec.find(':') {
+                Some(i) => (spec[..i].trim(), &spec[i + 1..]),
+                None => (spec, ""),
+            };
+            if name.is_empty() {
+                return Err(CubeError::user(format!(
+                    "Query pool name is missing in '{}'",
+                    spec
+                )));
+            }
+            let mut pool = Self::unlimited(name);
+            for option in options
+                .split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+            {
+                let (key, value) = match option.find('=') {
+                    Some(i) => (option[..i].trim(), option[i + 1..].trim()),
+                    None => {
+                        return Err(CubeError::user(format!(
+                            "Expected key=value in query pool '{}' but got '{}'",
+                            name, option
+                        )))
+                    }
+                };
+                let invalid = || {
+                    CubeError::user(format!(
+                        "Invalid {} value in query pool '{}': '{}'",
+                        key, name, value
+                    ))
+                };
+                match key.to_lowercase().as_str() {
+                    "concurrency" => pool.concurrency = value.parse().map_err(|_| invalid())?,
+                    "memory" => pool.memory_limit = parse_size(value).ok_or_else(invalid)?,
+                    "priority" => pool.priority = value.parse().map_err(|_| invalid())?,
+                    _ => {
+                        return Err(CubeError::user(format!(
+                            "Unknown option '{}' in query pool '{}'",
+                            key, name
+                        )))
+                    }
+                }
+            }
+            if pools.iter().any(|p: &QueryPoolConfig| p.name == pool.name) {
+                return Err(CubeError::user(format!(
+                    "Query pool '{}' is defined twice",
+                    name
+                )));
+            }
+            pools.push(pool);
+        }
+        Ok(pools)
+    }
+
+    /// Parses `CUBESTORE_QUERY_POOL_USERS` format: `dashboard:interactive,analyst:adhoc`.
+    pub fn parse_users(s: &str) -> Result<HashMap<String, String>, CubeError> {
+        let mut users = HashMap::new();
+        for mapping in s.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            match mapping.find(':') {
+                Some(i) => {
+                    users.insert(
+                        mapping[..i].trim().to_string(),
+                        mapping[i + 1..].trim().to_string(),
+                    );
+                }
+                None => {
+                    return Err(CubeError::user(format!(
+                        "Expected user:pool but got '{}'",
+                        mapping
+                    )))
+                }
+            }
+        }
+        Ok(users)
+    }
+}
+
+fn parse_size(s: &str) -> Option<u64> {
+    let s = s.to_uppercase();
+    let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier = match &s[digits.len()..] {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        "T" | "TB" => 1 << 40,
+        _ => return None,
+    };
+    digits.trim().parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Returns the pool name of `SET query_pool = '<name>'` statement.
+pub fn parse_set_query_pool(query: &str) -> Option<String> {
+    lazy_static! {
+        static ref SET_QUERY_POOL: Regex =
+            Regex::new(r"(?i)^\s*SET\s+(?:SESSION\s+)?query_pool\s*=\s*'?([\w\-]+)'?\s*;?\s*$")
+                .unwrap();
+    }
+    SET_QUERY_POOL
+        .captures(query)
+        .map(|c| c.get(1).unwrap().as_str().to_string())
+}
+
+/// Point in time state of a pool. Exposed as `system.query_pools`.
+#[derive(Clone, Debug)]
+pub struct QueryPoolState {
+    pub config: QueryPoolConfig,
+    pub running: usize,
+    pub queued: usize,
+    pub memory_used: u64,
+}
+
+struct Waiter {
+    seq: u64,
+    pool: String,
+    memory: u64,
+    priority: i64,
+    sender: oneshot::Sender<QueryPoolPermit>,
+}
+
+struct State {
+    pools: HashMap<String, QueryPoolState>,
+    running: usize,
+    waiters: Vec<Waiter>,
+    next_seq: u64,
+}
+
+impl State {
+    fn fits(&self, max_concurrent_queries: usize, pool: &str, memory: u64) -> bool {
+        let pool = &self.pools[pool];
+        (max_concurrent_queries == 0 || self.running < max_concurrent_queries)
+            && (pool.config.concurrency == 0 || pool.running < pool.config.concurrency)
+            && (pool.config.memory_limit == 0
+                || pool.memory_used + memory <= pool.config.memory_limit)
+    }
+
+    fn start(&mut self, pool: &str, memory: u64) {
+        self.running += 1;
+        let pool = self.pools.get_mut(pool).unwrap();
+        pool.running += 1;
+        pool.memory_used += memory;
+    }
+
+    fn finish(&mut self, pool: &str, memory: u64) {
+        self.running -= 1;
+        let pool = self.pools.get_mut(pool).unwrap();
+        pool.running -= 1;
+        pool.memory_used -= memory;
+    }
+}
+
+/// Admission control for data queries on the router. Each query takes a slot of its pool for
+/// the time it's executed on workers. Queries that don't fit into the limits are queued and
+/// admitted in the priority order as soon as running queries finish.
+pub struct QueryPools {
+    max_concurrent_queries: usize,
+    users: HashMap<String, String>,
+    state: Mutex<State>,
+}
+
+crate::di_service!(QueryPools, []);
+
+impl QueryPools {
+    pub fn new(
+        pools: Vec<QueryPoolConfig>,
+        users: HashMap<String, String>,
+        max_concurrent_queries: usize,
+    ) -> Arc<Self> {
+        let mut pools = pools
+            .into_iter()
+            .map(|config| {
+                (
+                    config.name.clone(),
+                    QueryPoolState {
+                        config,
+                        running: 0,
+                        queued: 0,
+                        memory_used: 0,
+                    },
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        pools
+            .entry(DEFAULT_QUERY_POOL.to_string())
+            .or_insert_with(|| QueryPoolState {
+                config: QueryPoolConfig::unlimited(DEFAULT_QUERY_POOL),
+                running: 0,
+                queued: 0,
+                memory_used: 0,
+            });
+        Arc::new(Self {
+            max_concurrent_queries,
+            users,
+            state: Mutex::new(State {
+                pools,
+                running: 0,
+                waiters: Vec::new(),
+                next_seq: 0,
+            }),
+        })
+    }
+
+    pub fn has_pool(&self, name: &str) -> bool {
+        self.state.lock().unwrap().pools.contains_key(name)
+    }
+
+    /// Pool set for the session, otherwise the one assigned to the user or the default pool.
+    pub fn pool_for(&self, context: &SqlQueryContext) -> String {
+        context
+            .query_pool
+            .clone()
+            .or_else(|| {
+                context
+                    .user
+                    .as_ref()
+                    .and_then(|u| self.users.get(u))
+                    .cloned()
+            })
+            .unwrap_or_else(|| DEFAULT_QUERY_POOL.to_string())
+    }
+
+    /// Waits until query estimated to use `memory` bytes can run in `pool`.
+    pub async fn acquire(
+        self: &Arc<Self>,
+        pool: &str,
+        memory: u64,
+    ) -> Result<QueryPoolPermit, CubeError> {
+        let permit = {
+            let mut state = self.state.lock().unwrap();
+            let (memory_limit, priority) = match state.pools.get(pool) {
+                Some(p) => (p.config.memory_limit, p.config.priority),
+                None => {
+                    return Err(CubeError::user(format!("Unknown query pool '{}'", pool)));
+                }
+            };
+            if memory_limit != 0 && memory_limit < memory {
+                return Err(CubeError::user(format!(
+                    "Query needs to scan {} bytes which exceeds memory budget of '{}' query pool ({} bytes)",
+                    memory, pool, memory_limit
+                )));
+            }
+            // Queries of the same pool are admitted in order. Waiters of other pools are only
+            // queued because they don't fit, so they can't be overtaken unfairly.
+            let pool_has_waiters = state.waiters.iter().any(|w| w.pool == pool);
+            if !pool_has_waiters && state.fits(self.max_concurrent_queries, pool, memory) {
+                state.start(pool, memory);
+                Ok(self.permit(pool, memory))
+            } else {
+                let (sender, receiver) = oneshot::channel();
+                let seq = state.next_seq;
+                state.next_seq += 1;
+                state.pools.get_mut(pool).unwrap().queued += 1;
+                state.waiters.push(Waiter {
+                    seq,
+                    pool: pool.to_string(),
+                    memory,
+                    priority,
+                    sender,
+                });
+                Err(receiver)
+            }
+        };
+        match permit {
+            Ok(permit) => Ok(permit),
+            // Permit is created on admission, so the slot is released even if this future is
+            // dropped before it receives the permit.
+            Err(receiver) => receiver
+                .await
+                .map_err(|_| CubeError::internal(format!("Query pool '{}' has been closed", pool))),
+        }
+    }
+
+    fn permit(self: &Arc<Self>, pool: &str, memory: u64) -> QueryPoolPermit {
+        QueryPoolPermit {
+            pools: self.clone(),
+            pool: pool.to_string(),
+            memory,
+        }
+    }
+
+    pub fn states(&self) -> Vec<QueryPoolState> {
+        let mut states = self
+            .state
+            .lock()
+            .unwrap()
+            .pools
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        states.sort_by(|a, b| a.config.name.cmp(&b.config.name));
+        states
+    }
+
+    fn release(self: &Arc<Self>, pool: &str, memory: u64) {
+        let rejected = {
+            let mut state = self.state.lock().unwrap();
+            state.finish(pool, memory);
+            self.admit_waiters(&mut state)
+        };
+        // Releasing takes the lock again, so permits of gone waiters are dropped after unlocking.
+        drop(rejected);
+    }
+
+    /// Returns permits of waiters that have gone during admission.
+    fn admit_waiters(self: &Arc<Self>, state: &mut State) -> Vec<QueryPoolPermit> {
+        let mut rejected = Vec::new();
+        let mut waiters = std::mem::take(&mut state.waiters);
+        waiters.sort_by_key(|w| (-w.priority, w.seq));
+        for w in waiters {
+            // Waiter is gone if its query was cancelled, e.g. timed out.
+            if w.sender.is_closed() {
+                state.pools.get_mut(&w.pool).unwrap().queued -= 1;
+                continue;
+            }
+            if !state.fits(self.max_concurrent_queries, &w.pool, w.memory) {
+                state.waiters.push(w);
+                continue;
+            }
+            state.pools.get_mut(&w.pool).unwrap().queued -= 1;
+            state.start(&w.pool, w.memory);
+            if let Err(permit) = w.sender.send(self.permit(&w.pool, w.memory)) {
+                rejected.push(permit);
+            }
+        }
+        rejected
+    }
+}
+
+/// Slot of a running query, released on drop.
+pub struct QueryPoolPermit {
+    pools: Arc<QueryPools>,
+    pool: String,
+    memory: u64,
+}
+
+impl Drop for QueryPoolPermit {
+    fn drop(&mut self) {
+        self.pools.release(&self.pool, self.memory);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+    use std::time::Duration;
+
+    fn pools(spec: &str, max_concurrent_queries: usize) -> Arc<QueryPools> {
+        QueryPools::new(
+            QueryPoolConfig::parse_list(spec).unwrap(),
+            QueryPoolConfig::parse_users("dashboard:interactive").unwrap(),
+            max_concurrent_queries,
+        )
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            QueryPoolConfig::parse_list(
+                "interactive:concurrency=8,memory=2GB,priority=10; adhoc:concurrency=2"
+            )
+            .unwrap(),
+            vec![
+                QueryPoolConfig {
+                    name: "interactive".to_string(),
+                    concurrency: 8,
+                    memory_limit: 2 << 30,
+                    priority: 10,
+                },
+                QueryPoolConfig {
+                    name: "adhoc".to_string(),
+                    concurrency: 2,
+                    memory_limit: 0,
+                    priority: 0,
+                },
+            ]
+        );
+        assert!(QueryPoolConfig::parse_list("adhoc:memory=lots").is_err());
+        assert!(QueryPoolConfig::parse_list("adhoc:threads=2").is_err());
+        assert!(QueryPoolConfig::parse_list("a;a").is_err());
+
+        assert_eq!(
+            parse_set_query_pool("SET query_pool = 'interactive'"),
+            Some("interactive".to_string())
+        );
+        assert_eq!(
+            parse_set_query_pool("set SESSION query_pool=adhoc;"),
+            Some("adhoc".to_string())
+        );
+        assert_eq!(parse_set_query_pool("SET time_zone = 'UTC'"), None);
+    }
+
+    #[test]
+    fn pool_for_context() {
+        let pools = pools("interactive", 0);
+        let context = |user: Option<&str>, query_pool: Option<&str>| SqlQueryContext {
+            user: user.map(|u| u.to_string()),
+            query_pool: query_pool.map(|p| p.to_string()),
+            ..SqlQueryContext::default()
+        };
+        assert_eq!(pools.pool_for(&context(None, None)), "default");
+        assert_eq!(
+            pools.pool_for(&context(Some("dashboard"), None)),
+            "interactive"
+        );
+        assert_eq!(
+            pools.pool_for(&context(Some("dashboard"), Some("default"))),
+            "default"
+        );
+    }
+
+    #[tokio::test]
+    async fn concurrency_and_memory() {
+        let pools = pools("adhoc:concurrency=1,memory=100", 0);
+        assert!(pools.acquire("missing", 0).await.is_err());
+        assert!(pools.acquire("adhoc", 101).await.is_err());
+
+        let first = pools.acquire("adhoc", 60).await.unwrap();
+        let mut second = pools.acquire("adhoc", 10).boxed();
+        assert!((&mut second).now_or_never().is_none());
+        assert_eq!(pools.states()[0].queued, 1);
+
+        drop(first);
+        let second = tokio::time::timeout(Duration::from_secs(1), second)
+            .await
+            .unwrap()
+            .unwrap();
+        let state = &pools.states()[0];
+        assert_eq!((state.running, state.queued, state.memory_used), (1, 0, 10));
+        drop(second);
+        assert_eq!(pools.states()[0].running, 0);
+    }
+
+    #[tokio::test]
+    async fn priorities() {
+        let pools = pools("interactive:priority=10;adhoc:priority=1", 1);
+        let running = pools.acquire("adhoc", 0).await.unwrap();
+        let mut adhoc = pools.acquire("adhoc", 0).boxed();
+        assert!((&mut adhoc).now_or_never().is_none());
+        let mut interactive = pools.acquire("interactive", 0).boxed();
+        assert!((&mut interactive).now_or_never().is_none());
+
+        drop(running);
+        let _interactive = (&mut interactive).now_or_never().unwrap().unwrap();
+        assert!((&mut adhoc).now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn other_pool_waiters_are_not_blocking() {
+        let pools = pools("interactive:concurrency=1,priority=10;adhoc", 0);
+        let _running = pools.acquire("interactive", 0).await.unwrap();
+        let mut interactive = pools.acquire("interactive", 0).boxed();
+        assert!((&mut interactive).now_or_never().is_none());
+
+        let _adhoc = pools.acquire("adhoc", 0).now_or_never().unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn admitted_waiter_dropped() {
+        let pools = pools("adhoc:concurrency=1,memory=100", 0);
+        let first = pools.acquire("adhoc", 60).await.unwrap();
+        let mut second = pools.acquire("adhoc", 10).boxed();
+        assert!((&mut second).now_or_never().is_none());
+
+        // Slot is booked for the second query, but it's cancelled before receiving the permit.
+        drop(first);
+        assert_eq!(pools.states()[0].running, 1);
+        drop(second);
+
+        let state = &pools.states()[0];
+        assert_eq!((state.running, state.queued, state.memory_used), (0, 0, 0));
+        let _third = pools.acquire("adhoc", 100).now_or_never().unwrap().unwrap();
+    }
+}
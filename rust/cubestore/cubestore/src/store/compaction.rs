@@ -1,10 +1,14 @@
+use crate::app_metrics;
 use crate::config::injection::DIService;
 use crate::config::ConfigObj;
 use crate::metastore::multi_index::MultiPartition;
 use crate::metastore::partition::partition_file_name;
+use crate::metastore::statistics::ColumnStatistics;
+use crate::metastore::table::AggregateFunction;
 use crate::metastore::{
     deactivate_table_on_corrupt_data, Chunk, IdRow, MetaStore, Partition, PartitionData,
 };
+use crate::queryplanner::merge_by_unique_key::MergeByUniqueKeyExec;
 use crate::remotefs::{ensure_temp_file_is_dropped, RemoteFs};
 use crate::store::{ChunkDataStore, ChunkStore, ROW_GROUP_SIZE};
 use crate::table::data::{cmp_min_rows, cmp_partition_key};
@@ -18,6 +22,7 @@ use arrow::datatypes::DataType;
 use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
 use datafusion::cube_ext;
+use datafusion::error::DataFusionError;
 use datafusion::physical_plan::empty::EmptyExec;
 use datafusion::physical_plan::expressions::{Column, Count, Literal};
 use datafusion::physical_plan::hash_aggregate::{
@@ -54,6 +59,8 @@ pub trait CompactionService: DIService + Send + Sync {
         multi_partition_id: u64,
         partition_id: u64,
     ) -> Result<(), CubeError>;
+    /// Collects column statistics of the partition data, including its chunks.
+    async fn analyze(&self, partition_id: u64) -> Result<(), CubeError>;
 }
 
 pub struct CompactionServiceImpl {
@@ -264,7 +271,8 @@ impl CompactionService for CompactionServiceImpl {
             .get_table_by_id(index.get_row().table_id())
             .await?;
         let unique_key = table.get_row().unique_key_columns();
-        let records = merge_chunks(key_size, main_table, new, unique_key).await?;
+        let aggregates = table.get_row().aggregate_columns();
+        let records = merge_chunks(key_size, main_table, new, unique_key, aggregates).await?;
         let count_and_min =
             write_to_files(records, total_rows as usize, store, new_local_files2).await?;
 
@@ -275,6 +283,7 @@ impl CompactionService for CompactionServiceImpl {
                 .remote_fs
                 .upload_file(&new_local_files[0], &remote)
                 .await?;
+            app_metrics::COMPACTION_BYTES.add(file_size as i64);
             let chunk_ids = chunks.iter().map(|c| c.get_id()).collect_vec();
             let swapped = self
                 .meta_store
@@ -303,6 +312,7 @@ impl CompactionService for CompactionServiceImpl {
                         .remote_fs
                         .upload_file(&new_local_files[i], new_remote_path.as_str())
                         .await?;
+                    app_metrics::COMPACTION_BYTES.add(file_size as i64);
                     filtered_partitions.push((p, file_size));
                 }
                 EitherOrBoth::Left(p) => {
@@ -489,6 +499,45 @@ impl CompactionService for CompactionServiceImpl {
         s.split_single_partition(data).await?;
         s.finish(false).await
     }
+
+    async fn analyze(&self, partition_id: u64) -> Result<(), CubeError> {
+        let (partition, index, _, _) = self
+            .meta_store
+            .get_partition_for_compaction(partition_id)
+            .await?;
+        let mut data = Vec::new();
+        for chunk in self
+            .meta_store
+            .get_chunks_by_partition(partition_id, false)
+            .await?
+        {
+            data.extend(self.chunk_store.get_chunk_columns(chunk).await?);
+        }
+        if let Some(f) = partition.get_row().get_full_name(partition.get_id()) {
+            let result = self
+                .remote_fs
+                .download_file(&f, partition.get_row().file_size())
+                .await;
+            deactivate_table_on_corrupt_data(self.meta_store.clone(), &result, &partition).await;
+            let local = result?;
+            let store = ParquetTableStore::new(index.get_row().clone(), ROW_GROUP_SIZE);
+            data.extend(cube_ext::spawn_blocking(move || store.read_columns(&local)).await??);
+        }
+
+        let columns = index.get_row().columns().clone();
+        let statistics = cube_ext::spawn_blocking(move || {
+            columns
+                .iter()
+                .enumerate()
+                .map(|(i, c)| ColumnStatistics::collect(c, i, &data))
+                .collect::<Result<Vec<_>, CubeError>>()
+        })
+        .await??;
+        self.meta_store
+            .update_partition_statistics(partition_id, statistics)
+            .await?;
+        Ok(())
+    }
 }
 
 /// Compute keys that partitions must be split by.
@@ -832,6 +881,7 @@ async fn merge_chunks(
     l: Arc<dyn ExecutionPlan>,
     r: Vec<ArrayRef>,
     unique_key_columns: Option<Vec<&crate::metastore::Column>>,
+    aggregate_columns: Vec<(&crate::metastore::Column, AggregateFunction)>,
 ) -> Result<SendableRecordBatchStream, CubeError> {
     let schema = l.schema();
     let r = RecordBatch::try_new(schema.clone(), r)?;
@@ -849,18 +899,36 @@ async fn merge_chunks(
     let mut res: Arc<dyn ExecutionPlan> = Arc::new(MergeSortExec::try_new(Arc::new(inputs), key)?);
 
     if let Some(key_columns) = unique_key_columns {
-        res = Arc::new(LastRowByUniqueKeyExec::try_new(
-            res.clone(),
-            key_columns
+        let key_columns = key_columns
+            .iter()
+            .map(|c| {
+                datafusion::physical_plan::expressions::Column::new_with_schema(
+                    c.get_name().as_str(),
+                    &res.schema(),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if aggregate_columns.is_empty() {
+            res = Arc::new(LastRowByUniqueKeyExec::try_new(res.clone(), key_columns)?)
+        } else {
+            let aggregates = aggregate_columns
                 .iter()
-                .map(|c| {
-                    datafusion::physical_plan::expressions::Column::new_with_schema(
-                        c.get_name().as_str(),
-                        &res.schema(),
-                    )
+                .map(|(c, f)| {
+                    Ok((
+                        datafusion::physical_plan::expressions::Column::new_with_schema(
+                            c.get_name().as_str(),
+                            &res.schema(),
+                        )?,
+                        *f,
+                    ))
                 })
-                .collect::<Result<Vec<_>, _>>()?,
-        )?)
+                .collect::<Result<Vec<_>, DataFusionError>>()?;
+            res = Arc::new(MergeByUniqueKeyExec::try_new(
+                res.clone(),
+                key_columns,
+                aggregates,
+            )?)
+        }
     }
 
     Ok(res.execute(0).await?)
@@ -870,6 +938,7 @@ async fn merge_chunks(
 mod tests {
     use super::*;
     use crate::config::MockConfigObj;
+    use crate::metastore::schema::SchemaLifecycle;
     use crate::metastore::{Column, ColumnType, RocksMetaStore};
     use crate::store::MockChunkDataStore;
     use crate::table::{cmp_same_types, Row, TableValue};
@@ -883,7 +952,7 @@ mod tests {
         let mut chunk_store = MockChunkDataStore::new();
         let mut config = MockConfigObj::new();
         metastore
-            .create_schema("foo".to_string(), false)
+            .create_schema("foo".to_string(), false, SchemaLifecycle::default())
             .await
             .unwrap();
         let cols = vec![Column::new("name".to_string(), ColumnType::String, 0)];
@@ -897,6 +966,7 @@ mod tests {
                 vec![],
                 true,
                 None,
+                vec![],
                 None,
             )
             .await
@@ -524,6 +524,7 @@ mod tests {
     use crate::assert_eq_columns;
     use crate::cluster::MockCluster;
     use crate::config::Config;
+    use crate::metastore::schema::SchemaLifecycle;
     use crate::metastore::RocksMetaStore;
     use crate::remotefs::LocalDirRemoteFs;
     use crate::table::data::{concat_record_batches, rows_to_columns};
@@ -572,7 +573,7 @@ mod tests {
 
             store
                 .meta_store
-                .create_schema("s".to_string(), false)
+                .create_schema("s".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
             let table = store
@@ -586,6 +587,7 @@ mod tests {
                     Vec::new(),
                     true,
                     None,
+                    vec![],
                     None,
                 )
                 .await
@@ -656,7 +658,7 @@ mod tests {
 
             let data_frame = DataFrame::new(col.clone(), first_rows);
             meta_store
-                .create_schema("foo".to_string(), false)
+                .create_schema("foo".to_string(), false, SchemaLifecycle::default())
                 .await
                 .unwrap();
             let table = meta_store
@@ -669,6 +671,7 @@ mod tests {
                     vec![],
                     true,
                     None,
+                    vec![],
                     None,
                 )
                 .await
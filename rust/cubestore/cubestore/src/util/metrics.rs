@@ -7,6 +7,10 @@
 //! methods on the created objects. See DataDog documentation for more information on different
 //! metric types.
 //!
+//! All updates are also aggregated in the in-process registry, which can be scraped in the
+//! Prometheus text format with [render_prometheus]. Histograms and distributions are exposed as
+//! summaries without quantiles, i.e. only `_sum` and `_count` are available.
+//!
 //! Code does not do any sampling or buffering at the time. Too frequent metric updates can cause
 //! load on consuming servers or loose UDP packets entirely. We prefer to report only not very
 //! frequently updated metrics for now to avoid more complex implementation. We can consider
@@ -15,9 +19,11 @@
 //! Note that misconfiguration (invalid port, address, etc) can cause metric updates to be silently
 //! ignored. This is by design to avoid interrupting normal operation.
 use crate::CubeError;
+use std::collections::BTreeMap;
+use std::fmt::Write;
 use std::net::ToSocketAddrs;
 use std::net::UdpSocket;
-use std::sync::Once;
+use std::sync::{Mutex, Once};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Compatibility {
@@ -60,20 +66,30 @@ pub const fn distribution(name: &'static str) -> IntMetric {
     }
 }
 
+/// Tags are sent as DogStatsD tags and exposed as Prometheus labels.
+/// They are dropped in `statsd`-compatible mode.
+pub type Tags<'a> = &'a [(&'a str, &'a str)];
+
 pub struct Counter {
     metric: Metric,
 }
 
 impl Counter {
     pub fn add(&self, v: i64) {
-        if let Some(s) = sink() {
-            s.send(&self.metric, v)
-        }
+        self.add_with_tags(v, &[])
+    }
+
+    pub fn add_with_tags(&self, v: i64, tags: Tags) {
+        self.metric.record(v, tags)
     }
 
     pub fn increment(&self) {
         self.add(1)
     }
+
+    pub fn increment_with_tags(&self, tags: Tags) {
+        self.add_with_tags(1, tags)
+    }
 }
 
 pub struct IntMetric {
@@ -82,9 +98,11 @@ pub struct IntMetric {
 
 impl IntMetric {
     pub fn report(&self, v: i64) {
-        if let Some(s) = sink() {
-            s.send(&self.metric, v)
-        }
+        self.report_with_tags(v, &[])
+    }
+
+    pub fn report_with_tags(&self, v: i64, tags: Tags) {
+        self.metric.record(v, tags)
     }
 }
 
@@ -92,6 +110,7 @@ pub type Gauge = IntMetric;
 pub type Histogram = IntMetric;
 pub type Distribution = IntMetric;
 
+#[derive(Clone, Copy)]
 enum MetricType {
     Counter,
     Gauge,
@@ -108,6 +127,13 @@ impl Metric {
     const fn new(name: &'static str, kind: MetricType) -> Metric {
         Metric { name, kind }
     }
+
+    fn record(&self, value: i64, tags: Tags) {
+        if let Some(s) = sink() {
+            s.send(self, value, tags)
+        }
+        registry::record(self, value, tags)
+    }
 }
 
 struct Sink {
@@ -127,7 +153,7 @@ impl Sink {
         Ok(Sink { socket, mode })
     }
 
-    fn send(&self, m: &Metric, value: i64) {
+    fn send(&self, m: &Metric, value: i64, tags: Tags) {
         let kind = match m.kind {
             MetricType::Counter => "c",
             MetricType::Gauge => "g",
@@ -138,9 +164,115 @@ impl Sink {
         };
         // We deliberately choose to loose metric submissions on failures.
         // TODO: handle EWOULDBLOCK with background sends or at least internal failure counters.
-        let _ = self
-            .socket
-            .send(format!("{}:{}|{}", m.name, value, kind).as_bytes());
+        let mut message = format!("{}:{}|{}", m.name, value, kind);
+        if !tags.is_empty() && self.mode == Compatibility::DogStatsD {
+            message += "|#";
+            message += &tags
+                .iter()
+                .map(|(k, v)| format!("{}:{}", k, v))
+                .collect::<Vec<_>>()
+                .join(",");
+        }
+        let _ = self.socket.send(message.as_bytes());
+    }
+}
+
+/// Renders all metrics reported by the process in the Prometheus text exposition format.
+pub fn render_prometheus() -> String {
+    registry::render()
+}
+
+mod registry {
+    use super::*;
+
+    type MetricKey = (&'static str, Vec<(String, String)>);
+
+    struct MetricValue {
+        kind: MetricType,
+        /// Sum for counters, histograms and distributions. Last value for gauges.
+        value: i64,
+        count: u64,
+    }
+
+    lazy_static! {
+        static ref REGISTRY: Mutex<BTreeMap<MetricKey, MetricValue>> = Mutex::new(BTreeMap::new());
+    }
+
+    pub(super) fn record(m: &Metric, value: i64, tags: Tags) {
+        let key = (
+            m.name,
+            tags.iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        );
+        let mut registry = REGISTRY.lock().unwrap();
+        let entry = registry.entry(key).or_insert(MetricValue {
+            kind: m.kind,
+            value: 0,
+            count: 0,
+        });
+        match m.kind {
+            MetricType::Gauge => entry.value = value,
+            MetricType::Counter | MetricType::Histogram | MetricType::Distribution => {
+                entry.value += value
+            }
+        }
+        entry.count += 1;
+    }
+
+    pub(super) fn render() -> String {
+        let registry = REGISTRY.lock().unwrap();
+        let mut out = String::new();
+        let mut last_name = None;
+        for ((name, tags), value) in registry.iter() {
+            let name = prometheus_name(name);
+            let labels = prometheus_labels(tags);
+            let type_name = match value.kind {
+                MetricType::Counter => "counter",
+                MetricType::Gauge => "gauge",
+                MetricType::Histogram | MetricType::Distribution => "summary",
+            };
+            if last_name.as_ref() != Some(&name) {
+                let _ = writeln!(out, "# TYPE {} {}", name, type_name);
+            }
+            match value.kind {
+                MetricType::Counter | MetricType::Gauge => {
+                    let _ = writeln!(out, "{}{} {}", name, labels, value.value);
+                }
+                MetricType::Histogram | MetricType::Distribution => {
+                    let _ = writeln!(out, "{}_sum{} {}", name, labels, value.value);
+                    let _ = writeln!(out, "{}_count{} {}", name, labels, value.count);
+                }
+            }
+            last_name = Some(name);
+        }
+        out
+    }
+
+    fn prometheus_name(name: &str) -> String {
+        name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect()
+    }
+
+    fn prometheus_labels(tags: &Vec<(String, String)>) -> String {
+        if tags.is_empty() {
+            return String::new();
+        }
+        let labels = tags
+            .iter()
+            .map(|(k, v)| {
+                format!(
+                    "{}=\"{}\"",
+                    prometheus_name(k),
+                    v.replace('\\', "\\\\")
+                        .replace('"', "\\\"")
+                        .replace('\n', "\\n")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("{{{}}}", labels)
     }
 }
 
@@ -177,3 +309,26 @@ mod global_sink {
 }
 
 use global_sink::sink;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus() {
+        static TEST_COUNTER: Counter = counter("cs.test.render.counter");
+        static TEST_HISTOGRAM: Histogram = histogram("cs.test.render.ms");
+
+        TEST_COUNTER.increment_with_tags(&[("job_type", "PartitionCompaction")]);
+        TEST_COUNTER.add_with_tags(2, &[("job_type", "PartitionCompaction")]);
+        TEST_HISTOGRAM.report(10);
+        TEST_HISTOGRAM.report(30);
+
+        let rendered = render_prometheus();
+        assert!(rendered.contains("# TYPE cs_test_render_counter counter\n"));
+        assert!(rendered.contains("cs_test_render_counter{job_type=\"PartitionCompaction\"} 3\n"));
+        assert!(rendered.contains("# TYPE cs_test_render_ms summary\n"));
+        assert!(rendered.contains("cs_test_render_ms_sum 40\n"));
+        assert!(rendered.contains("cs_test_render_ms_count 2\n"));
+    }
+}
@@ -6,6 +6,7 @@ pub mod logger;
 mod malloc_trim_loop;
 pub mod maybe_owned;
 pub mod metrics;
+pub mod otel;
 #[cfg(not(target_os = "windows"))]
 pub mod respawn;
 pub mod strings;
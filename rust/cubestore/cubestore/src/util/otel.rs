@@ -0,0 +1,335 @@
+use crate::CubeError;
+use async_trait::async_trait;
+use log::info;
+use opentelemetry::sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::sdk::{trace, Resource};
+use opentelemetry::trace::{SpanId, SpanKind, StatusCode, TracerProvider};
+use opentelemetry::{global, Array, Key, KeyValue, Value};
+use opentelemetry_otlp::WithExportConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// W3C trace context (`traceparent` and `tracestate` headers) of a span. Passed along with
+/// messages sent to other nodes and worker processes so their spans end up in the same trace.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TraceContext {
+    headers: HashMap<String, String>,
+}
+
+impl TraceContext {
+    /// Context of the current span. Empty if tracing export isn't enabled.
+    pub fn current() -> Self {
+        let context = tracing::Span::current().context();
+        let mut headers = HashMap::new();
+        global::get_text_map_propagator(|p| p.inject_context(&context, &mut headers));
+        Self { headers }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    /// Makes `span` a child of the remote span this context was taken from.
+    pub fn attach(&self, span: &tracing::Span) {
+        if self.is_empty() {
+            return;
+        }
+        let context = global::get_text_map_propagator(|p| p.extract(&self.headers));
+        span.set_parent(context);
+    }
+}
+
+/// Installs the span exporter if either `CUBESTORE_OTLP_ENDPOINT` (OTLP gRPC collector, e.g.
+/// `http://localhost:4317`) or `CUBESTORE_OTLP_FILE` (OTLP JSON lines are appended to the file)
+/// is set.
+/// Must be called within the tokio runtime as exporters are batching spans in background.
+pub fn init_otel_tracing(service_name: &str) -> Result<bool, CubeError> {
+    let trace_config = trace::config().with_resource(Resource::new(vec![KeyValue::new(
+        "service.name",
+        service_name.to_string(),
+    )]));
+    let tracer = if let Ok(endpoint) = std::env::var("CUBESTORE_OTLP_ENDPOINT") {
+        info!("Exporting traces to {}", endpoint);
+        opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(trace_config)
+            .install_batch(opentelemetry::runtime::Tokio)
+            .map_err(|e| CubeError::internal(format!("Can't init OTLP exporter: {}", e)))?
+    } else if let Ok(path) = std::env::var("CUBESTORE_OTLP_FILE") {
+        info!("Writing traces to {}", path);
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        let provider = trace::TracerProvider::builder()
+            .with_batch_exporter(
+                OtlpJsonFileExporter::new(tokio::fs::File::from_std(file)),
+                opentelemetry::runtime::Tokio,
+            )
+            .with_config(trace_config)
+            .build();
+        let tracer = provider.tracer("cubestore", Some(env!("CARGO_PKG_VERSION")));
+        global::set_tracer_provider(provider);
+        tracer
+    } else {
+        return Ok(false);
+    };
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let subscriber =
+        tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+    tracing::subscriber::set_global_default(subscriber)
+        .map_err(|e| CubeError::internal(format!("Can't set tracing subscriber: {}", e)))?;
+    Ok(true)
+}
+
+/// Flushes spans that are still buffered by the exporter.
+pub fn shutdown_otel_tracing() {
+    global::shutdown_tracer_provider();
+}
+
+/// Writes every exported batch of spans as a line of OTLP JSON (`ExportTraceServiceRequest`), the
+/// format of the OpenTelemetry Collector file exporter, so the file can be replayed to any
+/// OTLP backend.
+#[derive(Debug)]
+pub struct OtlpJsonFileExporter {
+    file: tokio::fs::File,
+}
+
+impl OtlpJsonFileExporter {
+    pub fn new(file: tokio::fs::File) -> Self {
+        Self { file }
+    }
+
+    pub fn encode(batch: &[SpanData]) -> serde_json::Value {
+        let mut resources: Vec<(serde_json::Value, Vec<serde_json::Value>)> = Vec::new();
+        for span in batch.iter() {
+            let resource = json!({
+                "attributes": span
+                    .resource
+                    .as_ref()
+                    .map(|resource| {
+                        resource
+                            .iter()
+                            .map(|(key, value)| key_value_to_json(key, value))
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default()
+            });
+            let span = span_to_json(span);
+            match resources.iter_mut().find(|(r, _)| r == &resource) {
+                Some((_, spans)) => spans.push(span),
+                None => resources.push((resource, vec![span])),
+            }
+        }
+
+        json!({
+            "resourceSpans": resources
+                .into_iter()
+                .map(|(resource, spans)| {
+                    json!({
+                        "resource": resource,
+                        "instrumentationLibrarySpans": [{
+                            "instrumentationLibrary": { "name": "cubestore" },
+                            "spans": spans,
+                        }],
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+    }
+}
+
+#[async_trait]
+impl SpanExporter for OtlpJsonFileExporter {
+    async fn export(&mut self, batch: Vec<SpanData>) -> ExportResult {
+        let mut line = serde_json::to_vec(&Self::encode(&batch))
+            .map_err(|e| format!("Can't encode spans: {}", e))?;
+        line.push(b'\n');
+        self.file
+            .write_all(&line)
+            .await
+            .map_err(|e| format!("Can't write spans: {}", e))?;
+        Ok(())
+    }
+}
+
+fn span_to_json(span: &SpanData) -> serde_json::Value {
+    let parent_span_id = if span.parent_span_id == SpanId::invalid() {
+        String::new()
+    } else {
+        span.parent_span_id.to_hex()
+    };
+    json!({
+        "traceId": span.span_context.trace_id().to_hex(),
+        "spanId": span.span_context.span_id().to_hex(),
+        "parentSpanId": parent_span_id,
+        "name": span.name,
+        "kind": match span.span_kind {
+            SpanKind::Internal => 1,
+            SpanKind::Server => 2,
+            SpanKind::Client => 3,
+            SpanKind::Producer => 4,
+            SpanKind::Consumer => 5,
+        },
+        "startTimeUnixNano": unix_nanos(span.start_time),
+        "endTimeUnixNano": unix_nanos(span.end_time),
+        "attributes": span
+            .attributes
+            .iter()
+            .map(|(key, value)| key_value_to_json(key, value))
+            .collect::<Vec<_>>(),
+        "events": span
+            .events
+            .iter()
+            .map(|event| {
+                json!({
+                    "timeUnixNano": unix_nanos(event.timestamp),
+                    "name": event.name,
+                    "attributes": event
+                        .attributes
+                        .iter()
+                        .map(|kv| key_value_to_json(&kv.key, &kv.value))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>(),
+        "links": span
+            .links
+            .iter()
+            .map(|link| {
+                json!({
+                    "traceId": link.span_context().trace_id().to_hex(),
+                    "spanId": link.span_context().span_id().to_hex(),
+                    "attributes": link
+                        .attributes()
+                        .iter()
+                        .map(|kv| key_value_to_json(&kv.key, &kv.value))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>(),
+        "status": {
+            "code": match span.status_code {
+                StatusCode::Unset => 0,
+                StatusCode::Ok => 1,
+                StatusCode::Error => 2,
+            },
+            "message": span.status_message,
+        },
+    })
+}
+
+// 64-bit integers are strings in the JSON mapping of protobuf
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+        .to_string()
+}
+
+fn key_value_to_json(key: &Key, value: &Value) -> serde_json::Value {
+    json!({ "key": key.as_str(), "value": value_to_json(value) })
+}
+
+fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Bool(v) => json!({ "boolValue": v }),
+        Value::I64(v) => json!({ "intValue": v.to_string() }),
+        Value::F64(v) => json!({ "doubleValue": v }),
+        Value::String(v) => json!({ "stringValue": v }),
+        Value::Array(array) => {
+            let values: Vec<serde_json::Value> = match array {
+                Array::Bool(values) => values.iter().map(|v| json!({ "boolValue": v })).collect(),
+                Array::I64(values) => values
+                    .iter()
+                    .map(|v| json!({ "intValue": v.to_string() }))
+                    .collect(),
+                Array::F64(values) => values.iter().map(|v| json!({ "doubleValue": v })).collect(),
+                Array::String(values) => {
+                    values.iter().map(|v| json!({ "stringValue": v })).collect()
+                }
+            };
+            json!({ "arrayValue": { "values": values } })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::sdk::trace::{EvictedHashMap, EvictedQueue};
+    use opentelemetry::sdk::InstrumentationLibrary;
+    use opentelemetry::trace::{SpanContext, TraceFlags, TraceId, TraceState};
+    use std::borrow::Cow;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_otlp_json_file_exporter() {
+        let mut attributes = EvictedHashMap::new(8, 8);
+        attributes.insert(KeyValue::new("sql", "SELECT 1"));
+        let span = SpanData {
+            span_context: SpanContext::new(
+                TraceId::from_u128(7),
+                SpanId::from_u64(99),
+                TraceFlags::SAMPLED,
+                false,
+                TraceState::default(),
+            ),
+            parent_span_id: SpanId::from_u64(1),
+            span_kind: SpanKind::Server,
+            name: Cow::Borrowed("query"),
+            start_time: UNIX_EPOCH + Duration::from_secs(1),
+            end_time: UNIX_EPOCH + Duration::from_secs(2),
+            attributes,
+            events: EvictedQueue::new(8),
+            links: EvictedQueue::new(8),
+            status_code: StatusCode::Ok,
+            status_message: Cow::Borrowed(""),
+            resource: Some(Arc::new(Resource::new(vec![KeyValue::new(
+                "service.name",
+                "cubestore",
+            )]))),
+            instrumentation_lib: InstrumentationLibrary::new("cubestore", None),
+        };
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut exporter =
+            OtlpJsonFileExporter::new(tokio::fs::File::from_std(file.reopen().unwrap()));
+        exporter.export(vec![span.clone()]).await.unwrap();
+        exporter.export(vec![span]).await.unwrap();
+
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        let lines = content.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+
+        let request: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        let resource_spans = &request["resourceSpans"][0];
+        assert_eq!(
+            resource_spans["resource"]["attributes"][0],
+            json!({ "key": "service.name", "value": { "stringValue": "cubestore" } })
+        );
+        let span = &resource_spans["instrumentationLibrarySpans"][0]["spans"][0];
+        assert_eq!(span["traceId"], json!("00000000000000000000000000000007"));
+        assert_eq!(span["spanId"], json!("0000000000000063"));
+        assert_eq!(span["parentSpanId"], json!("0000000000000001"));
+        assert_eq!(span["kind"], json!(2));
+        assert_eq!(span["startTimeUnixNano"], json!("1000000000"));
+        assert_eq!(
+            span["attributes"][0],
+            json!({ "key": "sql", "value": { "stringValue": "SELECT 1" } })
+        );
+    }
+}